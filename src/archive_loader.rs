@@ -0,0 +1,176 @@
+use std::fs::File;
+use std::io::{self, Read, Seek};
+use std::path::Path;
+
+use zip::result::ZipError;
+
+use crate::compressed_loader::{self, CompressedFile};
+use crate::encoding::TextEncoding;
+
+/// Local file header and (for an empty archive) end of central directory.
+const ZIP_MAGICS: &[&[u8]] = &[b"PK\x03\x04", b"PK\x05\x06"];
+
+/// Split `archive::member` into the archive path and the member name.
+///
+/// A local file whose name contains `::` is opened as is.
+pub fn parse_member_path(input: &str) -> Option<(&str, &str)> {
+    if Path::new(input).exists() {
+        return None;
+    }
+    let (archive, member) = input.split_once("::")?;
+    if archive.is_empty() || member.is_empty() {
+        return None;
+    }
+    Some((archive, member))
+}
+
+/// Open one member of a zip or tar archive (plain or compressed with any
+/// format `compressed_loader` knows) without extracting the rest.
+///
+/// Tar archives are streamed until the member is found; zip members are
+/// looked up in the central directory. Only the member itself is held in
+/// memory, indexed like a `CompressedFile`.
+pub fn open_member(
+    archive: &Path,
+    member: &str,
+    encoding: Option<TextEncoding>,
+) -> io::Result<CompressedFile> {
+    let mut file = File::open(archive)?;
+    let mut magic = Vec::with_capacity(4);
+    (&mut file).take(4).read_to_end(&mut magic)?;
+    file.rewind()?;
+
+    let (data, format) = if ZIP_MAGICS.contains(&magic.as_slice()) {
+        (read_zip_member(file, member)?, "zip".to_string())
+    } else {
+        let (reader, decoder) = compressed_loader::open_maybe_compressed(file)?;
+        let format = match decoder {
+            Some(decoder) => format!("tar, {}", decoder.name),
+            None => "tar".to_string(),
+        };
+        (read_tar_member(reader, member)?, format)
+    };
+
+    let data = data.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} has no member {}", archive.display(), member),
+        )
+    })?;
+    let name = format!("{}::{}", archive.display(), member);
+    Ok(CompressedFile::from_data(data, encoding, &name, &format))
+}
+
+/// Whether a tar entry path names `member`, ignoring a leading `./`.
+fn is_member(path: &Path, member: &str) -> bool {
+    let path = path.strip_prefix("./").unwrap_or(path);
+    path == Path::new(member.trim_start_matches("./"))
+}
+
+fn read_tar_member(reader: impl Read, member: &str) -> io::Result<Option<Vec<u8>>> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.header().entry_type().is_file() && is_member(&entry.path()?, member) {
+            let mut data = Vec::with_capacity(entry.size() as usize);
+            entry.read_to_end(&mut data)?;
+            return Ok(Some(data));
+        }
+    }
+    Ok(None)
+}
+
+fn read_zip_member(file: File, member: &str) -> io::Result<Option<Vec<u8>>> {
+    let mut archive = zip::ZipArchive::new(file).map_err(zip_error)?;
+    let mut entry = match archive.by_name(member.trim_start_matches("./")) {
+        Ok(entry) => entry,
+        Err(ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(zip_error(e)),
+    };
+    let mut data = Vec::with_capacity(entry.size() as usize);
+    entry.read_to_end(&mut data)?;
+    Ok(Some(data))
+}
+
+fn zip_error(e: ZipError) -> io::Error {
+    match e {
+        ZipError::Io(e) => e,
+        e => io::Error::new(io::ErrorKind::InvalidData, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_source::FileSource;
+    use std::io::Write;
+
+    const LOG: &[u8] = b"first\nsecond\nthird\n";
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("pog-archive-{}-{}", std::process::id(), name))
+    }
+
+    fn tar_bytes() -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, data) in [("./README", &b"readme\n"[..]), ("./service/app.log", LOG)] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, data).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_parse_member_path() {
+        assert_eq!(
+            parse_member_path("logs.tar.gz::service/app.log"),
+            Some(("logs.tar.gz", "service/app.log"))
+        );
+        assert_eq!(parse_member_path("logs.tar.gz::"), None);
+        assert_eq!(parse_member_path("logs.tar.gz"), None);
+
+        let path = temp_path("backup::2024.log");
+        std::fs::write(&path, LOG).unwrap();
+        assert_eq!(parse_member_path(path.to_str().unwrap()), None);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_tar_gz_member() {
+        let mut enc = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        enc.write_all(&tar_bytes()).unwrap();
+        let path = temp_path("bundle.tar.gz");
+        std::fs::write(&path, enc.finish().unwrap()).unwrap();
+
+        let file = open_member(&path, "service/app.log", None).unwrap();
+        assert_eq!(file.line_count(), 3);
+        assert_eq!(file.get_line(1).unwrap(), Some("second".to_string()));
+        assert!(file.display_name().ends_with("::service/app.log (tar, gzip)"));
+
+        let missing = open_member(&path, "service/other.log", None).err().unwrap();
+        assert_eq!(missing.kind(), io::ErrorKind::NotFound);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_zip_member() {
+        let path = temp_path("bundle.zip");
+        let mut writer = zip::ZipWriter::new(File::create(&path).unwrap());
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated);
+        writer.start_file("service/app.log", options).unwrap();
+        writer.write_all(LOG).unwrap();
+        writer.finish().unwrap();
+
+        let file = open_member(&path, "service/app.log", None).unwrap();
+        assert_eq!(file.get_line(2).unwrap(), Some("third".to_string()));
+        assert!(file.display_name().ends_with("(zip)"));
+        assert!(open_member(&path, "nope.log", None).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
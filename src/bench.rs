@@ -0,0 +1,205 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+
+use crate::error::Result;
+use crate::file_source::FileSource;
+use crate::search::SearchConfig;
+use crate::{open_file_source, FilePath, LINES_PER_PAGE};
+
+const DEFAULT_SEED: u64 = 0x2545_F491_4F6C_DD1D;
+
+pub struct BenchOptions {
+    pub iterations: usize,
+    pub pattern: String,
+    pub search_lines: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LatencyStats {
+    pub min: Duration,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub max: Duration,
+}
+
+impl LatencyStats {
+    pub fn from_samples(mut samples: Vec<Duration>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort();
+        let total: Duration = samples.iter().sum();
+        let percentile = |p: usize| samples[(samples.len() - 1) * p / 100];
+        Some(Self {
+            min: samples[0],
+            mean: total / samples.len() as u32,
+            p50: percentile(50),
+            p95: percentile(95),
+            max: samples[samples.len() - 1],
+        })
+    }
+}
+
+/// Small xorshift generator so viewport offsets are reproducible across runs.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+pub fn run(path: &FilePath, options: &BenchOptions) -> Result<()> {
+    let pattern = Regex::new(&options.pattern)?;
+
+    let open_start = Instant::now();
+    let source = open_file_source(path, None)?;
+    source.wait_indexed();
+    let open_time = open_start.elapsed();
+
+    let total_lines = source.line_count();
+    let file_size = source.file_size()?;
+
+    println!("pog bench: {}", source.display_name());
+    println!("  lines:     {}", total_lines);
+    println!("  size:      {} bytes", file_size);
+    println!();
+
+    println!("indexing");
+    println!("  time:       {}", format_duration(open_time));
+    println!("  throughput: {}", format_throughput(file_size, total_lines, open_time));
+    println!();
+
+    let samples = bench_viewport(&source, options.iterations)?;
+    println!("viewport fetch ({} x {} lines)", samples.len(), LINES_PER_PAGE);
+    let stats = LatencyStats::from_samples(samples);
+    match &stats {
+        Some(stats) => {
+            println!("  min:  {}", format_duration(stats.min));
+            println!("  mean: {}", format_duration(stats.mean));
+            println!("  p50:  {}", format_duration(stats.p50));
+            println!("  p95:  {}", format_duration(stats.p95));
+            println!("  max:  {}", format_duration(stats.max));
+        }
+        None => println!("  skipped (empty file)"),
+    }
+    println!();
+
+    // Scan in the same chunk size the viewer would pick for this source
+    let search_config = stats
+        .map(|s| SearchConfig::for_latency(s.p50))
+        .unwrap_or_default();
+    let search_lines = options.search_lines.unwrap_or(total_lines).min(total_lines);
+    let (match_count, search_time) =
+        bench_search(&source, &pattern, search_lines, search_config.chunk_size)?;
+    let scanned_bytes = if total_lines == 0 {
+        0
+    } else {
+        (file_size as u128 * search_lines as u128 / total_lines as u128) as u64
+    };
+    println!(
+        "search /{}/ ({} lines, chunk {})",
+        options.pattern, search_lines, search_config.chunk_size
+    );
+    println!("  matches:    {}", match_count);
+    println!("  time:       {}", format_duration(search_time));
+    println!("  throughput: {}", format_throughput(scanned_bytes, search_lines, search_time));
+
+    Ok(())
+}
+
+fn bench_viewport(source: &Arc<dyn FileSource>, iterations: usize) -> Result<Vec<Duration>> {
+    let total_lines = source.line_count();
+    if total_lines == 0 {
+        return Ok(Vec::new());
+    }
+
+    let max_start = total_lines.saturating_sub(LINES_PER_PAGE);
+    let mut rng = XorShift(DEFAULT_SEED);
+    let mut samples = Vec::with_capacity(iterations);
+
+    for _ in 0..iterations {
+        let start = (rng.next() % (max_start as u64 + 1)) as usize;
+        let fetch_start = Instant::now();
+        source.get_lines(start, LINES_PER_PAGE)?;
+        samples.push(fetch_start.elapsed());
+    }
+
+    Ok(samples)
+}
+
+/// Count matches in the first `line_limit` lines the way the viewer's match
+/// count does, a chunk at a time through `FileSource::search_range`.
+fn bench_search(
+    source: &Arc<dyn FileSource>,
+    pattern: &Regex,
+    line_limit: usize,
+    chunk_size: usize,
+) -> Result<(usize, Duration)> {
+    let mut match_count = 0;
+    let search_start = Instant::now();
+
+    let mut current = 0;
+    while current < line_limit {
+        let end = (current + chunk_size).min(line_limit);
+        match_count += source.search_range(pattern, current, end)?.len();
+        current = end;
+    }
+
+    Ok((match_count, search_start.elapsed()))
+}
+
+fn format_duration(d: Duration) -> String {
+    let micros = d.as_micros();
+    if micros < 1_000 {
+        format!("{} us", micros)
+    } else if micros < 1_000_000 {
+        format!("{:.2} ms", micros as f64 / 1_000.0)
+    } else {
+        format!("{:.2} s", d.as_secs_f64())
+    }
+}
+
+fn format_throughput(bytes: u64, lines: usize, elapsed: Duration) -> String {
+    let secs = elapsed.as_secs_f64();
+    if secs <= 0.0 {
+        return "n/a".to_string();
+    }
+    let mib_per_sec = bytes as f64 / (1024.0 * 1024.0) / secs;
+    let lines_per_sec = lines as f64 / secs;
+    format!("{:.1} MiB/s, {:.0} lines/s", mib_per_sec, lines_per_sec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_stats_empty() {
+        assert_eq!(LatencyStats::from_samples(Vec::new()), None);
+    }
+
+    #[test]
+    fn test_latency_stats() {
+        let samples: Vec<Duration> = (1..=100).rev().map(Duration::from_millis).collect();
+        let stats = LatencyStats::from_samples(samples).unwrap();
+        assert_eq!(stats.min, Duration::from_millis(1));
+        assert_eq!(stats.max, Duration::from_millis(100));
+        assert_eq!(stats.p50, Duration::from_millis(50));
+        assert_eq!(stats.p95, Duration::from_millis(95));
+        assert_eq!(stats.mean, Duration::from_micros(50_500));
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_micros(250)), "250 us");
+        assert_eq!(format_duration(Duration::from_micros(1_500)), "1.50 ms");
+        assert_eq!(format_duration(Duration::from_millis(2_500)), "2.50 s");
+    }
+}
@@ -0,0 +1,103 @@
+// Marks and search highlights use logical character columns; Pango maps those
+// ranges onto visual runs, so spans only need to avoid splitting clusters.
+
+/// Returns true for combining marks that must stay attached to the preceding
+/// base character (Hebrew points, Arabic harakat, generic diacritics, joiners).
+pub fn is_combining_mark(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}'   // combining diacritical marks
+        | '\u{0483}'..='\u{0489}' // cyrillic
+        | '\u{0591}'..='\u{05BD}' // hebrew points and accents
+        | '\u{05BF}'
+        | '\u{05C1}'..='\u{05C2}'
+        | '\u{05C4}'..='\u{05C5}'
+        | '\u{05C7}'
+        | '\u{0610}'..='\u{061A}' // arabic
+        | '\u{064B}'..='\u{065F}'
+        | '\u{0670}'
+        | '\u{06D6}'..='\u{06DC}'
+        | '\u{06DF}'..='\u{06E4}'
+        | '\u{06E7}'..='\u{06E8}'
+        | '\u{06EA}'..='\u{06ED}'
+        | '\u{0711}'              // syriac
+        | '\u{0730}'..='\u{074A}'
+        | '\u{08D3}'..='\u{08FF}' // arabic extended-a
+        | '\u{1AB0}'..='\u{1AFF}' // combining diacritical marks extended
+        | '\u{1DC0}'..='\u{1DFF}' // combining diacritical marks supplement
+        | '\u{200C}'..='\u{200D}' // zero width non-joiner / joiner
+        | '\u{20D0}'..='\u{20FF}' // combining marks for symbols
+        | '\u{FB1E}'
+        | '\u{FE00}'..='\u{FE0F}' // variation selectors
+        | '\u{FE20}'..='\u{FE2F}' // combining half marks
+    )
+}
+
+/// Give every combining mark the color of the base character it belongs to,
+/// so highlight boundaries always fall on cluster boundaries.
+pub fn snap_to_clusters<T: Clone>(chars: &[char], colors: &mut [Option<T>]) {
+    for i in 1..chars.len().min(colors.len()) {
+        if is_combining_mark(chars[i]) {
+            colors[i] = colors[i - 1].clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_combining_mark() {
+        assert!(is_combining_mark('\u{05B8}')); // hebrew qamats
+        assert!(is_combining_mark('\u{064E}')); // arabic fatha
+        assert!(is_combining_mark('\u{0301}')); // combining acute
+        assert!(!is_combining_mark('a'));
+        assert!(!is_combining_mark('ש'));
+        assert!(!is_combining_mark('م'));
+    }
+
+    #[test]
+    fn test_combining_diacritical_marks_extended() {
+        assert!(is_combining_mark('\u{1AB0}')); // combining doubled circumflex accent
+        assert!(is_combining_mark('\u{1AC0}'));
+        assert!(is_combining_mark('\u{1AFF}'));
+        assert!(!is_combining_mark('\u{1AAF}'));
+    }
+
+    #[test]
+    fn test_combining_diacritical_marks_supplement() {
+        assert!(is_combining_mark('\u{1DC0}')); // combining dotted grave accent
+        assert!(is_combining_mark('\u{1DE7}'));
+        assert!(is_combining_mark('\u{1DFF}'));
+        assert!(!is_combining_mark('\u{1E00}'));
+    }
+
+    #[test]
+    fn test_combining_marks_for_symbols() {
+        assert!(is_combining_mark('\u{20D0}')); // combining left harpoon above
+        assert!(is_combining_mark('\u{20E3}')); // combining enclosing keycap
+        assert!(is_combining_mark('\u{20FF}'));
+        assert!(!is_combining_mark('\u{20CF}'));
+    }
+
+    #[test]
+    fn test_combining_half_marks() {
+        assert!(is_combining_mark('\u{FE20}')); // combining ligature left half
+        assert!(is_combining_mark('\u{FE2F}'));
+        assert!(!is_combining_mark('\u{FE30}'));
+    }
+
+    #[test]
+    fn test_snap_to_clusters() {
+        // "مَر": meem + fatha + reh, highlight ends between meem and its fatha
+        let chars: Vec<char> = "m\u{064E}r".chars().collect();
+        let mut colors = vec![Some("red"), None, None];
+        snap_to_clusters(&chars, &mut colors);
+        assert_eq!(colors, vec![Some("red"), Some("red"), None]);
+
+        // Highlight starting on the mark is pulled back to the base character's color
+        let mut colors = vec![None, Some("red"), Some("red")];
+        snap_to_clusters(&chars, &mut colors);
+        assert_eq!(colors, vec![None, None, Some("red")]);
+    }
+}
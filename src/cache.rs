@@ -1,5 +1,7 @@
 use std::collections::HashMap;
 
+use crate::encoding::TextEncoding;
+
 pub const CHUNK_SIZE: usize = 500;
 
 pub struct CachedChunk {
@@ -61,6 +63,18 @@ impl LineCache {
         self.update_access_order(start_line);
     }
 
+    /// Drop the chunk holding `line_num`, e.g. after lines were appended to it
+    pub fn remove_chunk_for_line(&mut self, line_num: usize) {
+        let chunk_start = Self::chunk_start_for_line(line_num);
+        self.chunks.remove(&chunk_start);
+        self.access_order.retain(|&x| x != chunk_start);
+    }
+
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+        self.access_order.clear();
+    }
+
     fn update_access_order(&mut self, chunk_start: usize) {
         self.access_order.retain(|&x| x != chunk_start);
         self.access_order.push(chunk_start);
@@ -74,9 +88,90 @@ impl LineCache {
     }
 }
 
+/// Counts lines of a byte stream fed in pieces, with the same line rules as
+/// `build_line_index`, keeping only the offset of every `CHUNK_SIZE`th line.
+pub struct LineCounter {
+    encoding: TextEncoding,
+    chunk_offsets: Vec<u64>,
+    line_count: usize,
+    pos: u64,
+    /// Bytes of the current, incomplete code unit
+    unit: Vec<u8>,
+    /// A newline was seen; a line starts if any byte follows
+    line_pending: bool,
+}
+
+impl LineCounter {
+    pub fn new(encoding: TextEncoding) -> Self {
+        let mut counter = Self {
+            encoding,
+            chunk_offsets: Vec::new(),
+            line_count: 0,
+            pos: 0,
+            unit: Vec::with_capacity(2),
+            line_pending: false,
+        };
+        counter.start_line();
+        counter
+    }
+
+    pub fn encoding(&self) -> TextEncoding {
+        self.encoding
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_count
+    }
+
+    /// Bytes fed so far
+    pub fn byte_len(&self) -> u64 {
+        self.pos
+    }
+
+    /// Byte range `(start, end)` of the chunk starting at line `chunk_start`.
+    pub fn chunk_bytes(&self, chunk_start: usize) -> (u64, u64) {
+        let chunk = chunk_start / CHUNK_SIZE;
+        let start = self.chunk_offsets[chunk];
+        let end = self.chunk_offsets.get(chunk + 1).copied().unwrap_or(self.pos);
+        (start, end)
+    }
+
+    fn start_line(&mut self) {
+        if self.line_count.is_multiple_of(CHUNK_SIZE) {
+            self.chunk_offsets.push(self.pos);
+        }
+        self.line_count += 1;
+    }
+
+    pub fn feed(&mut self, data: &[u8]) {
+        let newline = self.encoding.newline();
+        for &byte in data {
+            if self.line_pending {
+                self.line_pending = false;
+                self.start_line();
+            }
+            self.unit.push(byte);
+            self.pos += 1;
+            if self.unit.len() == newline.len() {
+                self.line_pending = self.unit == newline;
+                self.unit.clear();
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::file_loader::build_line_index;
+
+    fn count(data: &[u8], encoding: TextEncoding, piece: usize) -> LineCounter {
+        let mut counter = LineCounter::new(encoding);
+        for part in data.chunks(piece.max(1)) {
+            counter.feed(part);
+        }
+        counter
+    }
 
     #[test]
     fn test_chunk_start_calculation() {
@@ -113,4 +208,30 @@ mod tests {
         assert!(cache.contains_line(500));
         assert!(cache.contains_line(1000));
     }
+
+    #[test]
+    fn test_line_counter_matches_line_index() {
+        let utf8 = TextEncoding::default();
+        for data in [&b""[..], b"a", b"a\n", b"a\nb", b"a\n\nb\n"] {
+            for piece in 1..4 {
+                let counter = count(data, utf8, piece);
+                assert_eq!(counter.line_count, build_line_index(data, utf8).len());
+                assert_eq!(counter.pos, data.len() as u64);
+            }
+        }
+
+        let utf16 = TextEncoding::parse("utf-16le").unwrap();
+        let data = b"a\0\n\0\n\x0a\n\0b\0";
+        assert_eq!(count(data, utf16, 3).line_count, build_line_index(data, utf16).len());
+    }
+
+    #[test]
+    fn test_line_counter_chunk_offsets() {
+        let line = b"0123456789\n";
+        let data: Vec<u8> = line.repeat(CHUNK_SIZE * 2 + 1);
+        let counter = count(&data, TextEncoding::default(), 4096);
+        assert_eq!(counter.line_count, CHUNK_SIZE * 2 + 1);
+        let chunk_bytes = (CHUNK_SIZE * line.len()) as u64;
+        assert_eq!(counter.chunk_offsets, vec![0, chunk_bytes, 2 * chunk_bytes]);
+    }
 }
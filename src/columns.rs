@@ -0,0 +1,225 @@
+use crate::error;
+use crate::file_source::FileSource;
+
+/// A fixed-width field colored on every line, for mainframe-style logs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldStyle {
+    pub start_col: usize, // 0-based
+    pub end_col: usize,   // exclusive
+    pub color: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ColumnLayout {
+    /// Columns after which a vertical guide is drawn (1-based)
+    pub guides: Vec<usize>,
+    pub fields: Vec<FieldStyle>,
+}
+
+/// Parse a comma-separated list of guide columns, e.g. `20,40,80`.
+pub fn parse_guides(input: &str) -> Result<Vec<usize>, String> {
+    let mut guides = Vec::new();
+    for part in input.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let col: usize = part
+            .parse()
+            .map_err(|_| format!("invalid guide column: {}", part))?;
+        if col == 0 {
+            return Err("guide columns must be >= 1".to_string());
+        }
+        guides.push(col);
+    }
+    guides.sort_unstable();
+    guides.dedup();
+    Ok(guides)
+}
+
+/// Parse a comma-separated list of `<start>-<end>:<color>` field specs.
+/// Columns are 1-based with an exclusive end, like `mark` regions.
+pub fn parse_fields(input: &str) -> Result<Vec<FieldStyle>, String> {
+    let mut fields = Vec::new();
+    for part in input.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (range, color) = part
+            .split_once(':')
+            .ok_or_else(|| format!("invalid field (expected <start>-<end>:<color>): {}", part))?;
+        let (start_str, end_str) = range
+            .split_once('-')
+            .ok_or_else(|| format!("invalid field range: {}", range))?;
+        let start: usize = start_str
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid field range: {}", range))?;
+        let end: usize = end_str
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid field range: {}", range))?;
+        if start == 0 || end == 0 {
+            return Err("column numbers must be >= 1".to_string());
+        }
+        if start >= end {
+            return Err("start column must be less than end column".to_string());
+        }
+        let color = color.trim();
+        if color.is_empty() {
+            return Err(format!("missing color for field: {}", range));
+        }
+        fields.push(FieldStyle {
+            start_col: start - 1,
+            end_col: end - 1,
+            color: color.to_string(),
+        });
+    }
+    fields.sort_by_key(|f| f.start_col);
+    Ok(fields)
+}
+
+/// Split `text` after `max_columns` characters, returning the part to show
+/// and how many characters were cut, or None if it fits.
+pub fn truncate_line(text: &str, max_columns: usize) -> Option<(&str, usize)> {
+    let (cut, _) = text.char_indices().nth(max_columns)?;
+    Some((&text[..cut], text[cut..].chars().count()))
+}
+
+/// Rows a line of `chars` characters takes when wrapped every `columns`
+/// characters; an empty line still takes one.
+pub fn wrapped_rows(chars: usize, columns: usize) -> usize {
+    chars.div_ceil(columns.max(1)).max(1)
+}
+
+/// How many of the lines taking `rows` rows each fit in `page_rows` rows,
+/// counted from the first; at least one, so a line taller than the page
+/// is still shown.
+pub fn lines_fitting(rows: impl IntoIterator<Item = usize>, page_rows: usize) -> usize {
+    let mut used = 0;
+    let mut fitting = 0;
+    for line_rows in rows {
+        used += line_rows;
+        if used > page_rows && fitting > 0 {
+            break;
+        }
+        fitting += 1;
+    }
+    fitting
+}
+
+/// A source seen through one fixed-width field: each line cut down to the
+/// columns `start_col..end_col`, so a search matches only within the field.
+/// Match columns count from the start of the field.
+pub struct FieldView<'a> {
+    source: &'a dyn FileSource,
+    start_col: usize,
+    end_col: usize,
+}
+
+impl<'a> FieldView<'a> {
+    pub fn new(source: &'a dyn FileSource, start_col: usize, end_col: usize) -> Self {
+        Self {
+            source,
+            start_col,
+            end_col,
+        }
+    }
+
+    fn cut(&self, text: &str) -> String {
+        text.chars()
+            .skip(self.start_col)
+            .take(self.end_col.saturating_sub(self.start_col))
+            .collect()
+    }
+}
+
+impl FileSource for FieldView<'_> {
+    fn line_count(&self) -> usize {
+        self.source.line_count()
+    }
+
+    fn file_size(&self) -> error::Result<u64> {
+        self.source.file_size()
+    }
+
+    fn get_line(&self, line_num: usize) -> error::Result<Option<String>> {
+        Ok(self.source.get_line(line_num)?.map(|text| self.cut(&text)))
+    }
+
+    fn get_lines(&self, start_line: usize, count: usize) -> error::Result<Vec<(usize, String)>> {
+        let lines = self.source.get_lines(start_line, count)?;
+        Ok(lines
+            .into_iter()
+            .map(|(line_num, text)| (line_num, self.cut(&text)))
+            .collect())
+    }
+
+    fn display_name(&self) -> &str {
+        self.source.display_name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_source::VecSource;
+
+    #[test]
+    fn test_parse_guides() {
+        assert_eq!(parse_guides("80, 20,40"), Ok(vec![20, 40, 80]));
+        assert_eq!(parse_guides("20,20"), Ok(vec![20]));
+        assert_eq!(parse_guides(""), Ok(vec![]));
+        assert!(parse_guides("0").is_err());
+        assert!(parse_guides("abc").is_err());
+    }
+
+    #[test]
+    fn test_parse_fields() {
+        assert_eq!(
+            parse_fields("9-17:#ccddee, 1-9:light blue"),
+            Ok(vec![
+                FieldStyle { start_col: 0, end_col: 8, color: "light blue".to_string() },
+                FieldStyle { start_col: 8, end_col: 16, color: "#ccddee".to_string() },
+            ])
+        );
+        assert!(parse_fields("1-8").is_err());
+        assert!(parse_fields("8-1:red").is_err());
+        assert!(parse_fields("0-5:red").is_err());
+        assert!(parse_fields("1-5:").is_err());
+        assert!(parse_fields("a-5:red").is_err());
+    }
+
+    #[test]
+    fn test_field_view_cuts_lines() {
+        let source = VecSource::new(["JOB00042 ABEND   S0C7", "JÖB7 OK"]);
+        let field = FieldView::new(&source, 9, 17);
+        assert_eq!(field.get_line(0).unwrap(), Some("ABEND   ".to_string()));
+        assert_eq!(
+            field.get_lines(0, 5).unwrap(),
+            vec![(0, "ABEND   ".to_string()), (1, "".to_string())]
+        );
+        // Columns count characters, not bytes
+        assert_eq!(
+            FieldView::new(&source, 1, 4).get_line(1).unwrap(),
+            Some("ÖB7".to_string())
+        );
+        let regex = regex::Regex::new("^A").unwrap();
+        assert_eq!(field.search_range(&regex, 0, 2).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_truncate_line() {
+        assert_eq!(truncate_line("short", 5), None);
+        assert_eq!(truncate_line("abcdefgh", 5), Some(("abcde", 3)));
+        // Counts characters, not bytes
+        assert_eq!(truncate_line("héllo wörld", 4), Some(("héll", 7)));
+        assert_eq!(truncate_line("", 1), None);
+    }
+
+    #[test]
+    fn test_wrapped_rows() {
+        assert_eq!(wrapped_rows(0, 80), 1);
+        assert_eq!(wrapped_rows(80, 80), 1);
+        assert_eq!(wrapped_rows(81, 80), 2);
+        assert_eq!(wrapped_rows(10, 0), 10);
+        assert_eq!(lines_fitting([1, 3, 2, 1], 6), 3);
+        assert_eq!(lines_fitting([1, 1], 6), 2);
+        // A line taller than the page is shown anyway
+        assert_eq!(lines_fitting([9, 1], 6), 1);
+        assert_eq!(lines_fitting([], 6), 0);
+    }
+}
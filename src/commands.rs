@@ -1,11 +1,39 @@
 use std::fmt;
 
+use crate::file_source::SourceChange;
+use crate::filter::{FilterKind, FoldMode};
+use crate::hexview::ViewMode;
+use crate::levels::LevelStyle;
+use crate::table::Delimiter;
+use crate::timestamp::{parse_time_bound, TimeRange, Timestamp};
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum PogCommand {
     Goto { line: usize },
+    GotoPercent { percent: f64, of: PercentOf },
+    GotoTime { time: Timestamp },
+    Scroll { delta: isize },  // Lines, negative = up
+    PageDown,
+    PageUp,
+    Bottom,
     Lines,
     Top,
     Size,
+    Viewport { lines: Option<usize> },  // None = get page size, Some = set it
+    Status,
+    Reload,
+    Open { path: String },  // Any file pog can open from the command line
+    Quit,
+    Subscribe { topics: Vec<EventTopic> },  // Empty = every topic
+    Begin,
+    Commit,
+    Rollback,
+    Batch { commands: Vec<PogCommand> },  // Committed by the server, never parsed
+    Line { line: usize },
+    Get { start: usize, count: usize },
+    Context { line: usize, radius: usize },  // `radius` lines before and after
+    Tail { count: usize },
+    Copy { start: usize, end: usize },  // 1-based, inclusive
     Cursor { line: Option<usize> },  // None = get cursor, Some = set cursor
     Mark {
         line: usize,
@@ -16,10 +44,72 @@ pub enum PogCommand {
         line: usize,
         region: Option<(usize, usize)>,  // Optional: specific region to unmark
     },
-    Search { pattern: String },
+    MarkMatches {
+        color: String,
+        pattern: Option<String>,  // None = the current search
+    },
+    MarkPattern { color: String, pattern: String },
+    UnmarkAll,
+    UnmarkPattern { pattern: String },
+    UnmarkColor { color: String },
+    Annotate { line: usize, text: String },
+    Annotations,
+    AnnotationRemove { line: usize },
+    AnnotationsClear,
+    BookmarkSet { name: String, line: Option<usize> },  // None = the cursor line
+    BookmarkGoto { name: String },
+    BookmarkList,
+    BookmarkRemove { name: String },
+    SaveMarks { path: String },
+    LoadMarks { path: String },
+    Export { start: usize, end: usize, path: String },  // 1-based, inclusive
+    ExportCancel,
+    Grep {
+        pattern: String,
+        ignore_case: bool,
+        from: usize,  // 1-based line the scan starts at
+        max: Option<usize>,  // None = the default page of matching lines
+    },
+    Search {
+        pattern: String,
+        ignore_case: bool,
+        literal: bool,  // Plain text rather than a regex
+        multiline: bool,  // Matches may span lines
+        time_range: TimeRange,  // Only lines stamped within it are searched
+        field: Option<usize>,  // Only this configured field (1-based) is searched
+        key: Option<String>,  // Only the value of this logfmt key is searched
+    },
     SearchNext,
     SearchPrev,
     SearchClear,
+    SearchWrap { enabled: Option<bool> },  // None = get, Some = turn on or off
+    SearchSave { name: String },
+    SearchLoad { name: Option<String> },  // None = list the saved names
+    Duplicate,
+    View { mode: Option<ViewMode> },  // None = get mode, Some = set mode
+    Wrap { enabled: Option<bool> },  // None = get, Some = turn on or off
+    RelativeNumbers { enabled: Option<bool> },  // None = get, Some = turn on or off
+    Levels { style: Option<LevelStyle> },  // None = get, Some = set
+    TimeColumn { enabled: Option<bool> },  // None = get, Some = turn on or off
+    MarksPanel { visible: Option<bool> },  // None = get, Some = show or hide
+    Follow { enabled: Option<bool> },  // None = get, Some = turn on or off
+    Table { action: TableAction },
+    Logfmt { action: LogfmtAction },
+    Filter { kind: FilterKind, pattern: String },
+    FilterClear,
+    Fold { action: FoldAction },
+    Hide { start: usize, end: usize },  // 1-based, inclusive
+    UnhideAll,
+    Pin { line: usize },
+    Unpin { line: usize },
+    Highlight { color: String, pattern: String },
+    Unhighlight { pattern: String },
+    HighlightClear,
+    Highlights,
+    Help,
+    Version,
+    Title { text: Option<String> },  // None = back to the file name
+    StatusText { text: Option<String> },  // None = hide the status text
 }
 
 #[derive(Debug, Clone)]
@@ -38,6 +128,324 @@ impl fmt::Display for CommandResponse {
     }
 }
 
+impl PogCommand {
+    /// Whether the command adds or removes marks when it succeeds.
+    pub fn changes_marks(&self) -> bool {
+        matches!(
+            self,
+            PogCommand::Mark { .. }
+                | PogCommand::Unmark { .. }
+                | PogCommand::UnmarkAll
+                | PogCommand::UnmarkPattern { .. }
+                | PogCommand::UnmarkColor { .. }
+                | PogCommand::LoadMarks { .. }
+                | PogCommand::MarkMatches { .. }
+                | PogCommand::MarkPattern { .. }
+        )
+    }
+}
+
+/// What a `table` command does to the window's column view. Columns are
+/// 0-based.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableAction {
+    Get,
+    Off,
+    /// Split lines on a delimiter, or the one most lines on screen have
+    On { delimiter: Option<Delimiter> },
+    Width { column: usize, width: usize },
+    Hide { column: usize },
+    /// Show a hidden column again, or every hidden column
+    Show { column: Option<usize> },
+}
+
+/// What a `logfmt` command does to the window's display of `key=value` lines.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogfmtAction {
+    Get,
+    Enable { enabled: bool },
+    Hide { key: String },
+    /// Show a hidden key again, or every hidden key
+    Show { key: Option<String> },
+}
+
+/// What the percentage of `goto <percent>%` is taken of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PercentOf {
+    /// The view's lines, so 50% is its middle line
+    Lines,
+    /// The file's bytes, for files whose lines vary wildly in length
+    Bytes,
+}
+
+/// `<percent>% [lines|bytes]`, as taken by `goto` and the go-to dialog.
+pub fn parse_goto_percent(text: &str) -> Result<(f64, PercentOf), String> {
+    let parts: Vec<&str> = text.split_whitespace().collect();
+    let (percent, of) = match parts[..] {
+        [percent] => (percent, PercentOf::Lines),
+        [percent, of] => match of.to_lowercase().as_str() {
+            "lines" => (percent, PercentOf::Lines),
+            "bytes" => (percent, PercentOf::Bytes),
+            _ => return Err(format!("invalid percentage of: {} (expected lines or bytes)", of)),
+        },
+        _ => return Err(usage("goto")),
+    };
+    let value = percent
+        .strip_suffix('%')
+        .and_then(|value| value.parse::<f64>().ok())
+        .filter(|value| (0.0..=100.0).contains(value))
+        .ok_or_else(|| format!("invalid percentage: {} (expected 0% to 100%)", percent))?;
+    Ok((value, of))
+}
+
+/// What a `fold` command does to runs of repeated lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FoldAction {
+    Get,
+    Off,
+    /// Fold runs, expanding none of them
+    On { mode: FoldMode },
+}
+
+/// What a `subscribe` connection can ask to be told about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventTopic {
+    Viewport,
+    Search,
+    Marks,
+    File,
+    Export,
+}
+
+impl EventTopic {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "viewport" => Ok(EventTopic::Viewport),
+            "search" => Ok(EventTopic::Search),
+            "marks" => Ok(EventTopic::Marks),
+            "file" => Ok(EventTopic::File),
+            "export" => Ok(EventTopic::Export),
+            _ => Err(format!(
+                "invalid event topic: {} (expected viewport, search, marks, file or export)",
+                s
+            )),
+        }
+    }
+}
+
+/// A change in the viewer, pushed to subscribed connections as an
+/// `EVENT <topic> ...` line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ViewerEvent {
+    /// The first and last visible lines, 1-based
+    Viewport { top: usize, bottom: usize },
+    SearchStarted { pattern: String },
+    /// The whole-file match count finished
+    SearchDone { matches: usize },
+    Marks { count: usize },
+    File { change: SourceChange, lines: usize },
+    ExportProgress { written: usize, total: usize },
+    ExportDone { lines: usize, path: String },
+    ExportFailed { message: String },
+}
+
+impl ViewerEvent {
+    pub fn topic(&self) -> EventTopic {
+        match self {
+            ViewerEvent::Viewport { .. } => EventTopic::Viewport,
+            ViewerEvent::SearchStarted { .. } | ViewerEvent::SearchDone { .. } => {
+                EventTopic::Search
+            }
+            ViewerEvent::Marks { .. } => EventTopic::Marks,
+            ViewerEvent::File { .. } => EventTopic::File,
+            ViewerEvent::ExportProgress { .. }
+            | ViewerEvent::ExportDone { .. }
+            | ViewerEvent::ExportFailed { .. } => EventTopic::Export,
+        }
+    }
+}
+
+impl fmt::Display for ViewerEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ViewerEvent::Viewport { top, bottom } => write!(f, "EVENT viewport {} {}", top, bottom),
+            ViewerEvent::SearchStarted { pattern } => write!(f, "EVENT search started {}", pattern),
+            ViewerEvent::SearchDone { matches } => write!(f, "EVENT search done {}", matches),
+            ViewerEvent::Marks { count } => write!(f, "EVENT marks {}", count),
+            ViewerEvent::File { change, lines } => {
+                let change = match change {
+                    SourceChange::Unchanged | SourceChange::Grew => "grew",
+                    SourceChange::Truncated => "truncated",
+                    SourceChange::Replaced => "replaced",
+                    SourceChange::Indexed => "indexed",
+                    SourceChange::Reloaded => "reloaded",
+                };
+                write!(f, "EVENT file {} {}", change, lines)
+            }
+            ViewerEvent::ExportProgress { written, total } => {
+                write!(f, "EVENT export progress {} {}", written, total)
+            }
+            ViewerEvent::ExportDone { lines, path } => {
+                write!(f, "EVENT export done {} {}", lines, path)
+            }
+            ViewerEvent::ExportFailed { message } => write!(f, "EVENT export failed {}", message),
+        }
+    }
+}
+
+/// The response text of `get`: the number of lines, then each line as
+/// `<line_number> <byte_length> <text>` on its own line. The length lets a
+/// client read the text exactly, whatever characters it holds.
+pub fn frame_lines(lines: &[(usize, String)]) -> String {
+    let mut framed = lines.len().to_string();
+    for (line_num, text) in lines {
+        framed.push_str(&format!("\n{} {} {}", line_num + 1, text.len(), text));
+    }
+    framed
+}
+
+/// The response text of `commit`: the number of commands run, then each
+/// one's response as `<byte_length> <response>`, framed like `get` so that
+/// multi-line responses stay apart.
+pub fn frame_responses(responses: &[CommandResponse]) -> String {
+    let mut framed = responses.len().to_string();
+    for response in responses {
+        let text = response.to_string();
+        framed.push_str(&format!("\n{} {}", text.len(), text));
+    }
+    framed
+}
+
+/// The response text of `highlights`: the number of rules, then each as
+/// `<color> <pattern>` on its own line in the order they were added. Colors
+/// have no spaces, so the pattern is the rest of the line.
+pub fn frame_highlights(rules: &[(&str, &str)]) -> String {
+    let mut framed = rules.len().to_string();
+    for (color, pattern) in rules {
+        framed.push_str(&format!("\n{} {}", color, pattern));
+    }
+    framed
+}
+
+/// The response text of `status`: the number of fields, then each as
+/// `<key> <value>` on its own line. Backslashes and line breaks in values
+/// are escaped, so every field stays on one line.
+pub fn frame_fields(fields: &[(&str, String)]) -> String {
+    let mut framed = fields.len().to_string();
+    for (key, value) in fields {
+        let value = value
+            .replace('\\', "\\\\")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r");
+        framed.push_str(&format!("\n{} {}", key, value));
+    }
+    framed
+}
+
+/// Revision of the socket protocol reported by `version`. Bumped when a
+/// command's syntax or response changes in a way older clients would
+/// misread; new commands alone leave it as is, `help` lists those.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// The syntax of every command, as given in its usage error and listed by `help`.
+const COMMAND_USAGES: &[&str] = &[
+    "goto <line_number> | <percent>% [lines|bytes]",
+    "goto-time <time>",
+    "scroll <+N|-N>",
+    "page-down",
+    "page-up",
+    "bottom",
+    "lines",
+    "top",
+    "size",
+    "viewport [lines]",
+    "status",
+    "reload",
+    "open <path>",
+    "quit",
+    "begin",
+    "commit",
+    "rollback",
+    "subscribe [topic...]",
+    "line <line_number>",
+    "get <start_line> <count>",
+    "context <line_number> <n>",
+    "tail <count>",
+    "follow [on|off]",
+    "copy <start_line> <end_line>",
+    "cursor [line_number]",
+    "mark <line_number> [<start>-<end>] <color>",
+    "unmark <line_number> [<start>-<end>]",
+    "unmark-all",
+    "unmark-pattern <regex_pattern>",
+    "unmark-color <color>",
+    "save-marks <path>",
+    "load-marks <path>",
+    "export <start_line> <end_line> <path> | cancel",
+    "mark-pattern <color> <regex_pattern>",
+    "mark-matches <color> [regex_pattern]",
+    "grep [-i] [--from <line_number>] [--max <count>] <regex_pattern>",
+    "search [-i] [--after <time>] [--before <time>] [--field <n> | --key <name>] <regex_pattern>",
+    "search-multiline [-i] [--after <time>] [--before <time>] <regex_pattern>",
+    "search-literal [-i] [--after <time>] [--before <time>] [--field <n> | --key <name>] <text>",
+    "search-next",
+    "search-prev",
+    "search-clear",
+    "search-wrap [on|off]",
+    "search-save <name>",
+    "search-load [name]",
+    "annotate <line_number> <text>",
+    "annotations [remove <line_number> | clear]",
+    "bookmark set <name> [line_number] | goto <name> | list | remove <name>",
+    "duplicate",
+    "view [hex|text]",
+    "wrap [on|off]",
+    "relative-numbers [on|off]",
+    "levels [line|word|off]",
+    "time-column [on|off]",
+    "marks-panel [on|off]",
+    "table [on|off|space|tab|comma] | split <regex> | width <column> <chars> | hide <column> | show [column]",
+    "logfmt [on|off] | hide <key> | show [key]",
+    "filter <regex_pattern>",
+    "filter-out <regex_pattern>",
+    "filter-clear",
+    "fold [on|off|exact|similar]",
+    "hide <start_line> <end_line>",
+    "unhide-all",
+    "pin <line_number>",
+    "unpin <line_number>",
+    "highlight <color> <regex_pattern>",
+    "unhighlight <regex_pattern>",
+    "highlight-clear",
+    "clear-highlights",
+    "highlights",
+    "help",
+    "version",
+    "title [text]",
+    "status-text [text]",
+];
+
+/// The usage error of `command`, one of `COMMAND_USAGES`.
+fn usage(command: &str) -> String {
+    let syntax = COMMAND_USAGES
+        .iter()
+        .find(|syntax| syntax.split(' ').next() == Some(command))
+        .copied()
+        .unwrap_or(command);
+    format!("usage: {}", syntax)
+}
+
+/// The response text of `help`: the number of commands, then the syntax of
+/// each on its own line, starting with the command's name.
+pub fn help_text() -> String {
+    let mut framed = COMMAND_USAGES.len().to_string();
+    for syntax in COMMAND_USAGES {
+        framed.push('\n');
+        framed.push_str(syntax);
+    }
+    framed
+}
+
 pub fn parse_command(input: &str) -> Result<PogCommand, String> {
     let input = input.trim();
     let parts: Vec<&str> = input.split_whitespace().collect();
@@ -47,9 +455,13 @@ pub fn parse_command(input: &str) -> Result<PogCommand, String> {
     }
 
     match parts[0].to_lowercase().as_str() {
+        "goto" if parts.get(1).is_some_and(|arg| arg.ends_with('%')) => {
+            let (percent, of) = parse_goto_percent(&input[parts[0].len()..])?;
+            Ok(PogCommand::GotoPercent { percent, of })
+        }
         "goto" => {
             if parts.len() != 2 {
-                return Err("usage: goto <line_number>".to_string());
+                return Err(usage("goto"));
             }
             let line: usize = parts[1]
                 .parse()
@@ -59,24 +471,201 @@ pub fn parse_command(input: &str) -> Result<PogCommand, String> {
             }
             Ok(PogCommand::Goto { line })
         }
+        "goto-time" => {
+            // The time may hold a space, quoted or not
+            let text = input[parts[0].len()..].trim();
+            let text = text
+                .strip_prefix('"')
+                .and_then(|t| t.strip_suffix('"'))
+                .unwrap_or(text);
+            if text.is_empty() {
+                return Err(usage("goto-time"));
+            }
+            Ok(PogCommand::GotoTime {
+                time: parse_time_bound(text)?,
+            })
+        }
+        "scroll" => {
+            let delta = match parts[1..] {
+                [delta] => delta
+                    .parse::<isize>()
+                    .map_err(|_| format!("invalid line count: {}", delta))?,
+                _ => return Err(usage("scroll")),
+            };
+            Ok(PogCommand::Scroll { delta })
+        }
+        "page-down" | "page-up" | "bottom" => {
+            if parts.len() != 1 {
+                return Err(usage(&parts[0].to_lowercase()));
+            }
+            Ok(match parts[0].to_lowercase().as_str() {
+                "page-down" => PogCommand::PageDown,
+                "page-up" => PogCommand::PageUp,
+                _ => PogCommand::Bottom,
+            })
+        }
         "lines" => {
             if parts.len() != 1 {
-                return Err("usage: lines".to_string());
+                return Err(usage("lines"));
             }
             Ok(PogCommand::Lines)
         }
         "top" => {
             if parts.len() != 1 {
-                return Err("usage: top".to_string());
+                return Err(usage("top"));
             }
             Ok(PogCommand::Top)
         }
         "size" => {
             if parts.len() != 1 {
-                return Err("usage: size".to_string());
+                return Err(usage("size"));
             }
             Ok(PogCommand::Size)
         }
+        "viewport" => match parts[1..] {
+            [] => Ok(PogCommand::Viewport { lines: None }),
+            [lines] => {
+                let lines: usize = lines
+                    .parse()
+                    .map_err(|_| format!("invalid line count: {}", lines))?;
+                if lines == 0 {
+                    return Err("line count must be >= 1".to_string());
+                }
+                Ok(PogCommand::Viewport { lines: Some(lines) })
+            }
+            _ => Err(usage("viewport")),
+        },
+        "status" => {
+            if parts.len() != 1 {
+                return Err(usage("status"));
+            }
+            Ok(PogCommand::Status)
+        }
+        "reload" => {
+            if parts.len() != 1 {
+                return Err(usage("reload"));
+            }
+            Ok(PogCommand::Reload)
+        }
+        "open" => {
+            // The path is taken verbatim, spaces included
+            let path = input[parts[0].len()..].trim();
+            if path.is_empty() {
+                return Err(usage("open"));
+            }
+            Ok(PogCommand::Open {
+                path: path.to_string(),
+            })
+        }
+        "quit" => {
+            if parts.len() != 1 {
+                return Err(usage("quit"));
+            }
+            Ok(PogCommand::Quit)
+        }
+        "begin" | "commit" | "rollback" => {
+            if parts.len() != 1 {
+                return Err(usage(&parts[0].to_lowercase()));
+            }
+            Ok(match parts[0].to_lowercase().as_str() {
+                "begin" => PogCommand::Begin,
+                "commit" => PogCommand::Commit,
+                _ => PogCommand::Rollback,
+            })
+        }
+        "subscribe" => Ok(PogCommand::Subscribe {
+            topics: parts[1..]
+                .iter()
+                .map(|topic| EventTopic::parse(topic))
+                .collect::<Result<_, _>>()?,
+        }),
+        "line" => {
+            if parts.len() != 2 {
+                return Err(usage("line"));
+            }
+            let line: usize = parts[1]
+                .parse()
+                .map_err(|_| format!("invalid line number: {}", parts[1]))?;
+            if line == 0 {
+                return Err("line number must be >= 1".to_string());
+            }
+            Ok(PogCommand::Line { line })
+        }
+        "get" => {
+            if parts.len() != 3 {
+                return Err(usage("get"));
+            }
+            let start: usize = parts[1]
+                .parse()
+                .map_err(|_| format!("invalid line number: {}", parts[1]))?;
+            if start == 0 {
+                return Err("line number must be >= 1".to_string());
+            }
+            let count: usize = parts[2]
+                .parse()
+                .map_err(|_| format!("invalid count: {}", parts[2]))?;
+            if count == 0 {
+                return Err("count must be >= 1".to_string());
+            }
+            Ok(PogCommand::Get { start, count })
+        }
+        "context" => {
+            if parts.len() != 3 {
+                return Err(usage("context"));
+            }
+            let line: usize = parts[1]
+                .parse()
+                .map_err(|_| format!("invalid line number: {}", parts[1]))?;
+            if line == 0 {
+                return Err("line number must be >= 1".to_string());
+            }
+            let radius: usize = parts[2]
+                .parse()
+                .map_err(|_| format!("invalid count: {}", parts[2]))?;
+            Ok(PogCommand::Context { line, radius })
+        }
+        "tail" => {
+            if parts.len() != 2 {
+                return Err(usage("tail"));
+            }
+            let count: usize = parts[1]
+                .parse()
+                .map_err(|_| format!("invalid count: {}", parts[1]))?;
+            if count == 0 {
+                return Err("count must be >= 1".to_string());
+            }
+            Ok(PogCommand::Tail { count })
+        }
+        "follow" => {
+            let enabled = match parts.get(1).map(|s| s.to_lowercase()).as_deref() {
+                None => None,
+                Some("on") => Some(true),
+                Some("off") => Some(false),
+                Some(_) => return Err(usage("follow")),
+            };
+            if parts.len() > 2 {
+                return Err(usage("follow"));
+            }
+            Ok(PogCommand::Follow { enabled })
+        }
+        "copy" => {
+            if parts.len() != 3 {
+                return Err(usage("copy"));
+            }
+            let start: usize = parts[1]
+                .parse()
+                .map_err(|_| format!("invalid line number: {}", parts[1]))?;
+            let end: usize = parts[2]
+                .parse()
+                .map_err(|_| format!("invalid line number: {}", parts[2]))?;
+            if start == 0 || end == 0 {
+                return Err("line number must be >= 1".to_string());
+            }
+            if start > end {
+                return Err("start line must not be after end line".to_string());
+            }
+            Ok(PogCommand::Copy { start, end })
+        }
         "cursor" => {
             if parts.len() == 1 {
                 Ok(PogCommand::Cursor { line: None })
@@ -89,12 +678,12 @@ pub fn parse_command(input: &str) -> Result<PogCommand, String> {
                 }
                 Ok(PogCommand::Cursor { line: Some(line) })
             } else {
-                Err("usage: cursor [line_number]".to_string())
+                Err(usage("cursor"))
             }
         }
         "mark" => {
             if parts.len() < 3 {
-                return Err("usage: mark <line_number> [<start>-<end>] <color>".to_string());
+                return Err(usage("mark"));
             }
             let line: usize = parts[1]
                 .parse()
@@ -130,7 +719,7 @@ pub fn parse_command(input: &str) -> Result<PogCommand, String> {
         }
         "unmark" => {
             if parts.len() < 2 {
-                return Err("usage: unmark <line_number> [<start>-<end>]".to_string());
+                return Err(usage("unmark"));
             }
             let line: usize = parts[1]
                 .parse()
@@ -158,120 +747,893 @@ pub fn parse_command(input: &str) -> Result<PogCommand, String> {
 
             Ok(PogCommand::Unmark { line, region })
         }
-        "search" => {
+        "unmark-all" => {
+            if parts.len() != 1 {
+                return Err(usage("unmark-all"));
+            }
+            Ok(PogCommand::UnmarkAll)
+        }
+        "unmark-pattern" => {
+            if parts.len() < 2 {
+                return Err(usage("unmark-pattern"));
+            }
+            Ok(PogCommand::UnmarkPattern {
+                pattern: parts[1..].join(" "),
+            })
+        }
+        "unmark-color" => {
+            if parts.len() != 2 {
+                return Err(usage("unmark-color"));
+            }
+            Ok(PogCommand::UnmarkColor {
+                color: parts[1].to_string(),
+            })
+        }
+        "save-marks" | "load-marks" => {
+            // The path is taken verbatim, spaces included
+            let path = input[parts[0].len()..].trim();
+            if path.is_empty() {
+                return Err(usage(&parts[0].to_lowercase()));
+            }
+            let path = path.to_string();
+            if parts[0].eq_ignore_ascii_case("save-marks") {
+                Ok(PogCommand::SaveMarks { path })
+            } else {
+                Ok(PogCommand::LoadMarks { path })
+            }
+        }
+        "export" => {
+            if parts.len() == 2 && parts[1].eq_ignore_ascii_case("cancel") {
+                return Ok(PogCommand::ExportCancel);
+            }
+            if parts.len() < 4 {
+                return Err(usage("export"));
+            }
+            let start: usize = parts[1]
+                .parse()
+                .map_err(|_| format!("invalid line number: {}", parts[1]))?;
+            let end: usize = parts[2]
+                .parse()
+                .map_err(|_| format!("invalid line number: {}", parts[2]))?;
+            if start == 0 || end == 0 {
+                return Err("line number must be >= 1".to_string());
+            }
+            if start > end {
+                return Err("start line must not be after end line".to_string());
+            }
+            // The path is taken verbatim, spaces included
+            let path = parts[..3]
+                .iter()
+                .fold(input, |rest, part| rest[part.len()..].trim_start());
+            Ok(PogCommand::Export {
+                start,
+                end,
+                path: path.to_string(),
+            })
+        }
+        "mark-pattern" => {
+            if parts.len() < 3 {
+                return Err(usage("mark-pattern"));
+            }
+            Ok(PogCommand::MarkPattern {
+                color: parts[1].to_string(),
+                pattern: parts[2..].join(" "),
+            })
+        }
+        "mark-matches" => {
             if parts.len() < 2 {
-                return Err("usage: search <regex_pattern>".to_string());
+                return Err(usage("mark-matches"));
+            }
+            let pattern = (parts.len() > 2).then(|| parts[2..].join(" "));
+            Ok(PogCommand::MarkMatches {
+                color: parts[1].to_string(),
+                pattern,
+            })
+        }
+        "grep" => {
+            let mut ignore_case = false;
+            let mut from = 1;
+            let mut max = None;
+            let mut rest = &parts[1..];
+            loop {
+                match rest {
+                    ["-i", tail @ ..] => {
+                        ignore_case = true;
+                        rest = tail;
+                    }
+                    ["--from", value, tail @ ..] => {
+                        from = value
+                            .parse()
+                            .map_err(|_| format!("invalid line number: {}", value))?;
+                        if from == 0 {
+                            return Err("line number must be >= 1".to_string());
+                        }
+                        rest = tail;
+                    }
+                    ["--max", value, tail @ ..] => {
+                        let count: usize = value
+                            .parse()
+                            .map_err(|_| format!("invalid count: {}", value))?;
+                        if count == 0 {
+                            return Err("count must be >= 1".to_string());
+                        }
+                        max = Some(count);
+                        rest = tail;
+                    }
+                    _ => break,
+                }
             }
-            let pattern = parts[1..].join(" ");
+            if rest.is_empty() {
+                return Err(usage("grep"));
+            }
+            Ok(PogCommand::Grep {
+                pattern: rest.join(" "),
+                ignore_case,
+                from,
+                max,
+            })
+        }
+        "search" | "search-multiline" => {
+            let multiline = parts[0].eq_ignore_ascii_case("search-multiline");
+            let (options, rest) = parse_search_options(&input[parts[0].len()..])?;
+            let pattern = rest.split_whitespace().collect::<Vec<_>>().join(" ");
             if pattern.is_empty() {
-                return Err("search pattern cannot be empty".to_string());
+                return Err(usage(&parts[0].to_lowercase()));
+            }
+            if multiline && options.field.is_some() {
+                return Err("--field does not apply to search-multiline".to_string());
+            }
+            if multiline && options.key.is_some() {
+                return Err("--key does not apply to search-multiline".to_string());
             }
-            Ok(PogCommand::Search { pattern })
+            Ok(PogCommand::Search {
+                pattern,
+                ignore_case: options.ignore_case,
+                literal: false,
+                multiline,
+                time_range: options.time_range,
+                field: options.field,
+                key: options.key,
+            })
+        }
+        "search-literal" => {
+            // The text is taken verbatim, spaces included
+            let (options, text) = parse_search_options(&input[parts[0].len()..])?;
+            if text.is_empty() {
+                return Err(usage("search-literal"));
+            }
+            Ok(PogCommand::Search {
+                pattern: text.to_string(),
+                ignore_case: options.ignore_case,
+                literal: true,
+                multiline: false,
+                time_range: options.time_range,
+                field: options.field,
+                key: options.key,
+            })
         }
         "search-next" => {
             if parts.len() != 1 {
-                return Err("usage: search-next".to_string());
+                return Err(usage("search-next"));
             }
             Ok(PogCommand::SearchNext)
         }
         "search-prev" => {
             if parts.len() != 1 {
-                return Err("usage: search-prev".to_string());
+                return Err(usage("search-prev"));
             }
             Ok(PogCommand::SearchPrev)
         }
         "search-clear" => {
             if parts.len() != 1 {
-                return Err("usage: search-clear".to_string());
+                return Err(usage("search-clear"));
             }
             Ok(PogCommand::SearchClear)
         }
-        cmd => Err(format!("unknown command: {}", cmd)),
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_parse_goto() {
-        assert_eq!(
-            parse_command("goto 100"),
-            Ok(PogCommand::Goto { line: 100 })
-        );
-        assert_eq!(
-            parse_command("GOTO 1"),
-            Ok(PogCommand::Goto { line: 1 })
-        );
-        assert_eq!(
-            parse_command("  goto   42  "),
-            Ok(PogCommand::Goto { line: 42 })
-        );
-    }
-
-    #[test]
-    fn test_parse_lines() {
-        assert_eq!(parse_command("lines"), Ok(PogCommand::Lines));
-        assert_eq!(parse_command("LINES"), Ok(PogCommand::Lines));
-        assert_eq!(parse_command("  lines  "), Ok(PogCommand::Lines));
-        assert!(parse_command("lines extra").is_err());
-    }
-
-    #[test]
-    fn test_parse_top() {
-        assert_eq!(parse_command("top"), Ok(PogCommand::Top));
-        assert_eq!(parse_command("TOP"), Ok(PogCommand::Top));
-        assert!(parse_command("top extra").is_err());
-    }
-
-    #[test]
-    fn test_parse_size() {
-        assert_eq!(parse_command("size"), Ok(PogCommand::Size));
-        assert_eq!(parse_command("SIZE"), Ok(PogCommand::Size));
-        assert!(parse_command("size extra").is_err());
-    }
-
-    #[test]
-    fn test_parse_mark() {
-        // Full-line marks
-        assert_eq!(
-            parse_command("mark 10 red"),
-            Ok(PogCommand::Mark { line: 10, region: None, color: "red".to_string() })
-        );
-        assert_eq!(
-            parse_command("MARK 5 #FF0000"),
-            Ok(PogCommand::Mark { line: 5, region: None, color: "#FF0000".to_string() })
-        );
-        assert_eq!(
-            parse_command("mark 1 light blue"),
-            Ok(PogCommand::Mark { line: 1, region: None, color: "light blue".to_string() })
-        );
-        assert!(parse_command("mark").is_err());
-        assert!(parse_command("mark 10").is_err());
-        assert!(parse_command("mark abc red").is_err());
-        assert!(parse_command("mark 0 red").is_err());
-    }
-
-    #[test]
-    fn test_parse_mark_region() {
-        // Region marks
-        assert_eq!(
-            parse_command("mark 10 5-20 red"),
-            Ok(PogCommand::Mark { line: 10, region: Some((5, 20)), color: "red".to_string() })
-        );
-        assert_eq!(
-            parse_command("mark 100 1-50 #FF0000"),
-            Ok(PogCommand::Mark { line: 100, region: Some((1, 50)), color: "#FF0000".to_string() })
-        );
-        assert_eq!(
-            parse_command("mark 1 10-20 light blue"),
-            Ok(PogCommand::Mark { line: 1, region: Some((10, 20)), color: "light blue".to_string() })
-        );
-        // Error cases
-        assert!(parse_command("mark 10 0-5 red").is_err());   // column 0 invalid
-        assert!(parse_command("mark 10 5-0 red").is_err());   // column 0 invalid
-        assert!(parse_command("mark 10 5-5 red").is_err());   // start >= end
+        "search-wrap" => {
+            let enabled = match parts.get(1).map(|s| s.to_lowercase()).as_deref() {
+                None => None,
+                Some("on") => Some(true),
+                Some("off") => Some(false),
+                Some(_) => return Err(usage("search-wrap")),
+            };
+            if parts.len() > 2 {
+                return Err(usage("search-wrap"));
+            }
+            Ok(PogCommand::SearchWrap { enabled })
+        }
+        "search-save" => {
+            if parts.len() != 2 {
+                return Err(usage("search-save"));
+            }
+            Ok(PogCommand::SearchSave { name: parts[1].to_string() })
+        }
+        "annotate" => {
+            if parts.len() < 3 {
+                return Err(usage("annotate"));
+            }
+            let line: usize = parts[1]
+                .parse()
+                .map_err(|_| format!("invalid line number: {}", parts[1]))?;
+            if line == 0 {
+                return Err("line number must be >= 1".to_string());
+            }
+            Ok(PogCommand::Annotate {
+                line,
+                text: parts[2..].join(" "),
+            })
+        }
+        "annotations" => match parts.get(1).map(|s| s.to_lowercase()).as_deref() {
+            None => Ok(PogCommand::Annotations),
+            Some("clear") if parts.len() == 2 => Ok(PogCommand::AnnotationsClear),
+            Some("remove") if parts.len() == 3 => {
+                let line: usize = parts[2]
+                    .parse()
+                    .map_err(|_| format!("invalid line number: {}", parts[2]))?;
+                if line == 0 {
+                    return Err("line number must be >= 1".to_string());
+                }
+                Ok(PogCommand::AnnotationRemove { line })
+            }
+            _ => Err(usage("annotations")),
+        },
+        "bookmark" => {
+            match (parts.get(1).map(|s| s.to_lowercase()).as_deref(), parts.len()) {
+                (Some("set"), 3) => Ok(PogCommand::BookmarkSet {
+                    name: parts[2].to_string(),
+                    line: None,
+                }),
+                (Some("set"), 4) => {
+                    let line: usize = parts[3]
+                        .parse()
+                        .map_err(|_| format!("invalid line number: {}", parts[3]))?;
+                    if line == 0 {
+                        return Err("line number must be >= 1".to_string());
+                    }
+                    Ok(PogCommand::BookmarkSet {
+                        name: parts[2].to_string(),
+                        line: Some(line),
+                    })
+                }
+                (Some("goto"), 3) => Ok(PogCommand::BookmarkGoto {
+                    name: parts[2].to_string(),
+                }),
+                (Some("list"), 2) => Ok(PogCommand::BookmarkList),
+                (Some("remove"), 3) => Ok(PogCommand::BookmarkRemove {
+                    name: parts[2].to_string(),
+                }),
+                _ => Err(usage("bookmark")),
+            }
+        }
+        "search-load" => {
+            if parts.len() > 2 {
+                return Err(usage("search-load"));
+            }
+            Ok(PogCommand::SearchLoad { name: parts.get(1).map(|s| s.to_string()) })
+        }
+        "duplicate" => {
+            if parts.len() != 1 {
+                return Err(usage("duplicate"));
+            }
+            Ok(PogCommand::Duplicate)
+        }
+        "view" => match parts.len() {
+            1 => Ok(PogCommand::View { mode: None }),
+            2 => Ok(PogCommand::View {
+                mode: Some(ViewMode::parse(parts[1])?),
+            }),
+            _ => Err(usage("view")),
+        },
+        "wrap" => {
+            let enabled = match parts.get(1).map(|s| s.to_lowercase()).as_deref() {
+                None => None,
+                Some("on") => Some(true),
+                Some("off") => Some(false),
+                Some(_) => return Err(usage("wrap")),
+            };
+            if parts.len() > 2 {
+                return Err(usage("wrap"));
+            }
+            Ok(PogCommand::Wrap { enabled })
+        }
+        "relative-numbers" => {
+            let enabled = match parts[1..] {
+                [] => None,
+                [state] => match state.to_lowercase().as_str() {
+                    "on" => Some(true),
+                    "off" => Some(false),
+                    _ => return Err(usage("relative-numbers")),
+                },
+                _ => return Err(usage("relative-numbers")),
+            };
+            Ok(PogCommand::RelativeNumbers { enabled })
+        }
+        "levels" => match parts.len() {
+            1 => Ok(PogCommand::Levels { style: None }),
+            2 => Ok(PogCommand::Levels {
+                style: Some(LevelStyle::parse(parts[1]).map_err(|_| usage("levels"))?),
+            }),
+            _ => Err(usage("levels")),
+        },
+        "time-column" => {
+            let enabled = match parts.get(1).map(|s| s.to_lowercase()).as_deref() {
+                None => None,
+                Some("on") => Some(true),
+                Some("off") => Some(false),
+                Some(_) => return Err(usage("time-column")),
+            };
+            if parts.len() > 2 {
+                return Err(usage("time-column"));
+            }
+            Ok(PogCommand::TimeColumn { enabled })
+        }
+        "marks-panel" => {
+            let visible = match parts.get(1).map(|s| s.to_lowercase()).as_deref() {
+                None => None,
+                Some("on") => Some(true),
+                Some("off") => Some(false),
+                Some(_) => return Err(usage("marks-panel")),
+            };
+            if parts.len() > 2 {
+                return Err(usage("marks-panel"));
+            }
+            Ok(PogCommand::MarksPanel { visible })
+        }
+        "table" => {
+            let column = |text: &str| match text.parse::<usize>() {
+                Ok(0) => Err("column numbers must be >= 1".to_string()),
+                Ok(column) => Ok(column - 1),
+                Err(_) => Err(format!("invalid column: {}", text)),
+            };
+            let action = match (parts.get(1).map(|s| s.to_lowercase()).as_deref(), parts.len()) {
+                (None, _) => TableAction::Get,
+                (Some("off"), 2) => TableAction::Off,
+                (Some("on"), 2) => TableAction::On { delimiter: None },
+                (Some(name @ ("space" | "tab" | "comma")), 2) => TableAction::On {
+                    delimiter: Some(Delimiter::parse(name)?),
+                },
+                (Some("split"), n) if n >= 3 => TableAction::On {
+                    delimiter: Some(Delimiter::parse(&parts[2..].join(" "))?),
+                },
+                (Some("width"), 4) => {
+                    let width = match parts[3].parse::<usize>() {
+                        Ok(width) if width > 0 => width,
+                        _ => return Err(format!("invalid column width: {}", parts[3])),
+                    };
+                    TableAction::Width { column: column(parts[2])?, width }
+                }
+                (Some("hide"), 3) => TableAction::Hide { column: column(parts[2])? },
+                (Some("show"), 2) => TableAction::Show { column: None },
+                (Some("show"), 3) => TableAction::Show { column: Some(column(parts[2])?) },
+                _ => return Err(usage("table")),
+            };
+            Ok(PogCommand::Table { action })
+        }
+        "logfmt" => {
+            let action = match (parts.get(1).map(|s| s.to_lowercase()).as_deref(), parts.len()) {
+                (None, _) => LogfmtAction::Get,
+                (Some("on"), 2) => LogfmtAction::Enable { enabled: true },
+                (Some("off"), 2) => LogfmtAction::Enable { enabled: false },
+                (Some("hide"), 3) => LogfmtAction::Hide { key: parts[2].to_string() },
+                (Some("show"), 2) => LogfmtAction::Show { key: None },
+                (Some("show"), 3) => LogfmtAction::Show { key: Some(parts[2].to_string()) },
+                _ => return Err(usage("logfmt")),
+            };
+            Ok(PogCommand::Logfmt { action })
+        }
+        "filter" | "filter-out" => {
+            let kind = if parts[0].eq_ignore_ascii_case("filter") {
+                FilterKind::Include
+            } else {
+                FilterKind::Exclude
+            };
+            if parts.len() < 2 {
+                return Err(usage(&parts[0].to_lowercase()));
+            }
+            Ok(PogCommand::Filter {
+                kind,
+                pattern: parts[1..].join(" "),
+            })
+        }
+        "filter-clear" => {
+            if parts.len() != 1 {
+                return Err(usage("filter-clear"));
+            }
+            Ok(PogCommand::FilterClear)
+        }
+        "fold" => {
+            let action = match parts.get(1).map(|s| s.to_lowercase()).as_deref() {
+                None => FoldAction::Get,
+                Some("off") => FoldAction::Off,
+                Some("on") => FoldAction::On { mode: FoldMode::Similar },
+                Some(mode) => FoldAction::On {
+                    mode: FoldMode::parse(mode).map_err(|_| usage("fold"))?,
+                },
+            };
+            if parts.len() > 2 {
+                return Err(usage("fold"));
+            }
+            Ok(PogCommand::Fold { action })
+        }
+        "hide" => {
+            if parts.len() != 3 {
+                return Err(usage("hide"));
+            }
+            let start: usize = parts[1]
+                .parse()
+                .map_err(|_| format!("invalid line number: {}", parts[1]))?;
+            let end: usize = parts[2]
+                .parse()
+                .map_err(|_| format!("invalid line number: {}", parts[2]))?;
+            if start == 0 || end == 0 {
+                return Err("line number must be >= 1".to_string());
+            }
+            if start > end {
+                return Err("start line must not be after end line".to_string());
+            }
+            Ok(PogCommand::Hide { start, end })
+        }
+        "unhide-all" => {
+            if parts.len() != 1 {
+                return Err(usage("unhide-all"));
+            }
+            Ok(PogCommand::UnhideAll)
+        }
+        "pin" | "unpin" => {
+            let name = parts[0].to_lowercase();
+            let [_, line] = parts[..] else {
+                return Err(usage(&name));
+            };
+            let line: usize = line
+                .parse()
+                .map_err(|_| format!("invalid line number: {}", line))?;
+            if line == 0 {
+                return Err("line number must be >= 1".to_string());
+            }
+            Ok(if name == "pin" { PogCommand::Pin { line } } else { PogCommand::Unpin { line } })
+        }
+        "highlight" => {
+            if parts.len() < 3 {
+                return Err(usage("highlight"));
+            }
+            Ok(PogCommand::Highlight {
+                color: parts[1].to_string(),
+                pattern: parts[2..].join(" "),
+            })
+        }
+        "unhighlight" => {
+            if parts.len() < 2 {
+                return Err(usage("unhighlight"));
+            }
+            Ok(PogCommand::Unhighlight {
+                pattern: parts[1..].join(" "),
+            })
+        }
+        "highlight-clear" | "clear-highlights" => {
+            if parts.len() != 1 {
+                return Err(usage(&parts[0].to_lowercase()));
+            }
+            Ok(PogCommand::HighlightClear)
+        }
+        "highlights" => {
+            if parts.len() != 1 {
+                return Err(usage("highlights"));
+            }
+            Ok(PogCommand::Highlights)
+        }
+        "help" => {
+            if parts.len() != 1 {
+                return Err(usage("help"));
+            }
+            Ok(PogCommand::Help)
+        }
+        "version" => {
+            if parts.len() != 1 {
+                return Err(usage("version"));
+            }
+            Ok(PogCommand::Version)
+        }
+        "title" | "status-text" => {
+            // The text is taken verbatim, spaces included
+            let text = input[parts[0].len()..].trim();
+            let text = (!text.is_empty()).then(|| text.to_string());
+            if parts[0].eq_ignore_ascii_case("title") {
+                Ok(PogCommand::Title { text })
+            } else {
+                Ok(PogCommand::StatusText { text })
+            }
+        }
+        cmd => Err(format!("unknown command: {}", cmd)),
+    }
+}
+
+/// Options given to the search commands ahead of the pattern.
+#[derive(Debug, Default)]
+struct SearchOptions {
+    ignore_case: bool,
+    time_range: TimeRange,
+    field: Option<usize>,
+    key: Option<String>,
+}
+
+/// Options of the search commands ahead of the pattern: `-i`, the time
+/// bounds `--after <time>` and `--before <time>`, quoted when the time has a
+/// space, and `--field <n>` or `--key <name>`. Returns them with the rest of the input, from
+/// the pattern on.
+fn parse_search_options(input: &str) -> Result<(SearchOptions, &str), String> {
+    let mut options = SearchOptions::default();
+    let mut rest = input.trim_start();
+    loop {
+        let (word, after_word) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        match word {
+            "-i" => options.ignore_case = true,
+            "--after" | "--before" => {
+                let (value, after_value) = take_value(after_word.trim_start())
+                    .ok_or_else(|| format!("missing time after {}", word))?;
+                let time = Some(parse_time_bound(value)?);
+                if word == "--after" {
+                    options.time_range.after = time;
+                } else {
+                    options.time_range.before = time;
+                }
+                rest = after_value.trim_start();
+                continue;
+            }
+            "--field" => {
+                let (value, after_value) = take_value(after_word.trim_start())
+                    .ok_or("missing field number after --field")?;
+                match value.parse::<usize>() {
+                    Ok(field) if field > 0 => options.field = Some(field),
+                    _ => return Err(format!("invalid field number: {}", value)),
+                }
+                rest = after_value.trim_start();
+                continue;
+            }
+            "--key" => {
+                let (value, after_value) = take_value(after_word.trim_start())
+                    .ok_or("missing key name after --key")?;
+                options.key = Some(value.to_string());
+                rest = after_value.trim_start();
+                continue;
+            }
+            _ if options.field.is_some() && options.key.is_some() => {
+                return Err("--field and --key cannot be combined".to_string());
+            }
+            _ => return Ok((options, rest)),
+        }
+        rest = after_word.trim_start();
+    }
+}
+
+/// The next argument of `input`, a word or a double-quoted string, and the
+/// input after it.
+fn take_value(input: &str) -> Option<(&str, &str)> {
+    if let Some(quoted) = input.strip_prefix('"') {
+        let end = quoted.find('"')?;
+        return Some((&quoted[..end], &quoted[end + 1..]));
+    }
+    let (value, rest) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+    (!value.is_empty()).then_some((value, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_goto() {
+        assert_eq!(
+            parse_command("goto 100"),
+            Ok(PogCommand::Goto { line: 100 })
+        );
+        assert_eq!(
+            parse_command("GOTO 1"),
+            Ok(PogCommand::Goto { line: 1 })
+        );
+        assert_eq!(
+            parse_command("  goto   42  "),
+            Ok(PogCommand::Goto { line: 42 })
+        );
+    }
+
+    #[test]
+    fn test_parse_goto_percent() {
+        assert_eq!(
+            parse_command("goto 50%"),
+            Ok(PogCommand::GotoPercent { percent: 50.0, of: PercentOf::Lines })
+        );
+        assert_eq!(
+            parse_command("goto 12.5% BYTES"),
+            Ok(PogCommand::GotoPercent { percent: 12.5, of: PercentOf::Bytes })
+        );
+        assert_eq!(parse_goto_percent("100% lines"), Ok((100.0, PercentOf::Lines)));
+        assert!(parse_command("goto 101%").is_err());
+        assert!(parse_command("goto -1%").is_err());
+        assert!(parse_command("goto 50% words").is_err());
+        assert!(parse_command("goto 50% bytes extra").is_err());
+        assert!(parse_goto_percent("50").is_err());
+    }
+
+    #[test]
+    fn test_parse_goto_time() {
+        let time = |text| parse_time_bound(text).unwrap();
+        assert_eq!(
+            parse_command("goto-time 2024-05-01 14:32"),
+            Ok(PogCommand::GotoTime { time: time("2024-05-01 14:32") })
+        );
+        assert_eq!(
+            parse_command(r#"GOTO-TIME "2024-05-01T14:32:10Z""#),
+            Ok(PogCommand::GotoTime { time: time("2024-05-01 14:32:10") })
+        );
+        assert!(parse_command("goto-time").is_err());
+        assert!(parse_command("goto-time 14:32").is_err());
+    }
+
+    #[test]
+    fn test_parse_status() {
+        assert_eq!(parse_command("STATUS"), Ok(PogCommand::Status));
+        assert!(parse_command("status all").is_err());
+    }
+
+    #[test]
+    fn test_parse_subscribe() {
+        assert_eq!(parse_command("subscribe"), Ok(PogCommand::Subscribe { topics: vec![] }));
+        assert_eq!(
+            parse_command("SUBSCRIBE viewport Marks"),
+            Ok(PogCommand::Subscribe {
+                topics: vec![EventTopic::Viewport, EventTopic::Marks]
+            })
+        );
+        assert!(parse_command("subscribe scroll").is_err());
+    }
+
+    #[test]
+    fn test_event_format() {
+        let viewport = ViewerEvent::Viewport { top: 101, bottom: 150 };
+        assert_eq!(viewport.to_string(), "EVENT viewport 101 150");
+        assert_eq!(viewport.topic(), EventTopic::Viewport);
+        let started = ViewerEvent::SearchStarted { pattern: "disk full".to_string() };
+        assert_eq!(started.to_string(), "EVENT search started disk full");
+        assert_eq!(ViewerEvent::SearchDone { matches: 7 }.topic(), EventTopic::Search);
+        assert_eq!(
+            ViewerEvent::File { change: SourceChange::Grew, lines: 1200 }.to_string(),
+            "EVENT file grew 1200"
+        );
+        let done = ViewerEvent::ExportDone { lines: 40, path: "/tmp/a b.log".to_string() };
+        assert_eq!(done.to_string(), "EVENT export done 40 /tmp/a b.log");
+        assert_eq!(done.topic(), EventTopic::Export);
+        assert!(PogCommand::UnmarkAll.changes_marks());
+        assert!(!PogCommand::Lines.changes_marks());
+    }
+
+    #[test]
+    fn test_parse_batch() {
+        assert_eq!(parse_command("BEGIN"), Ok(PogCommand::Begin));
+        assert_eq!(parse_command("commit"), Ok(PogCommand::Commit));
+        assert_eq!(parse_command("rollback"), Ok(PogCommand::Rollback));
+        assert!(parse_command("commit now").is_err());
+    }
+
+    #[test]
+    fn test_frame_responses() {
+        let responses = [
+            CommandResponse::Ok(None),
+            CommandResponse::Ok(Some("1\n7 3 abc".to_string())),
+            CommandResponse::Error("line 9 is not marked".to_string()),
+        ];
+        assert_eq!(
+            frame_responses(&responses),
+            "3\n2 OK\n12 OK 1\n7 3 abc\n26 ERROR line 9 is not marked"
+        );
+    }
+
+    #[test]
+    fn test_frame_fields() {
+        let fields = [
+            ("file", "my app.log".to_string()),
+            ("lines", "42".to_string()),
+            ("search", "a\\d+\nb".to_string()),
+        ];
+        assert_eq!(
+            frame_fields(&fields),
+            "3\nfile my app.log\nlines 42\nsearch a\\\\d+\\nb"
+        );
+    }
+
+    #[test]
+    fn test_parse_scroll() {
+        assert_eq!(parse_command("scroll +20"), Ok(PogCommand::Scroll { delta: 20 }));
+        assert_eq!(parse_command("scroll -5"), Ok(PogCommand::Scroll { delta: -5 }));
+        assert_eq!(parse_command("SCROLL 3"), Ok(PogCommand::Scroll { delta: 3 }));
+        assert!(parse_command("scroll").is_err());
+        assert!(parse_command("scroll up").is_err());
+        assert!(parse_command("scroll +1 +2").is_err());
+
+        assert_eq!(parse_command("page-down"), Ok(PogCommand::PageDown));
+        assert_eq!(parse_command("PAGE-UP"), Ok(PogCommand::PageUp));
+        assert_eq!(parse_command("bottom"), Ok(PogCommand::Bottom));
+        assert!(parse_command("bottom 2").is_err());
+    }
+
+    #[test]
+    fn test_parse_lines() {
+        assert_eq!(parse_command("lines"), Ok(PogCommand::Lines));
+        assert_eq!(parse_command("LINES"), Ok(PogCommand::Lines));
+        assert_eq!(parse_command("  lines  "), Ok(PogCommand::Lines));
+        assert!(parse_command("lines extra").is_err());
+    }
+
+    #[test]
+    fn test_parse_line() {
+        assert_eq!(parse_command("line 7"), Ok(PogCommand::Line { line: 7 }));
+        assert_eq!(parse_command("LINE 1"), Ok(PogCommand::Line { line: 1 }));
+        assert!(parse_command("line").is_err());
+        assert!(parse_command("line 0").is_err());
+        assert!(parse_command("line 1 2").is_err());
+    }
+
+    #[test]
+    fn test_parse_get() {
+        assert_eq!(
+            parse_command("get 40 5"),
+            Ok(PogCommand::Get { start: 40, count: 5 })
+        );
+        assert!(parse_command("get 40").is_err());
+        assert!(parse_command("get 0 5").is_err());
+        assert!(parse_command("get 40 0").is_err());
+        assert!(parse_command("get 40 -1").is_err());
+    }
+
+    #[test]
+    fn test_parse_context() {
+        assert_eq!(
+            parse_command("context 1200 5"),
+            Ok(PogCommand::Context { line: 1200, radius: 5 })
+        );
+        assert_eq!(
+            parse_command("CONTEXT 7 0"),
+            Ok(PogCommand::Context { line: 7, radius: 0 })
+        );
+        assert!(parse_command("context 1200").is_err());
+        assert!(parse_command("context 0 5").is_err());
+        assert!(parse_command("context 10 -2").is_err());
+    }
+
+    #[test]
+    fn test_parse_viewport() {
+        assert_eq!(parse_command("viewport"), Ok(PogCommand::Viewport { lines: None }));
+        assert_eq!(
+            parse_command("VIEWPORT 72"),
+            Ok(PogCommand::Viewport { lines: Some(72) })
+        );
+        assert!(parse_command("viewport 0").is_err());
+        assert!(parse_command("viewport tall").is_err());
+        assert!(parse_command("viewport 40 80").is_err());
+    }
+
+    #[test]
+    fn test_parse_tail() {
+        assert_eq!(parse_command("tail 20"), Ok(PogCommand::Tail { count: 20 }));
+        assert_eq!(parse_command("TAIL 1"), Ok(PogCommand::Tail { count: 1 }));
+        assert!(parse_command("tail").is_err());
+        assert!(parse_command("tail 0").is_err());
+        assert!(parse_command("tail many").is_err());
+        assert!(parse_command("tail 5 10").is_err());
+    }
+
+    #[test]
+    fn test_parse_follow() {
+        assert_eq!(parse_command("follow"), Ok(PogCommand::Follow { enabled: None }));
+        assert_eq!(parse_command("follow ON"), Ok(PogCommand::Follow { enabled: Some(true) }));
+        assert_eq!(parse_command("follow maybe"), Err("usage: follow [on|off]".to_string()));
+        assert!(parse_command("follow on off").is_err());
+    }
+
+    #[test]
+    fn test_parse_copy() {
+        assert_eq!(
+            parse_command("COPY 40 42"),
+            Ok(PogCommand::Copy { start: 40, end: 42 })
+        );
+        assert_eq!(parse_command("copy 7 7"), Ok(PogCommand::Copy { start: 7, end: 7 }));
+        assert!(parse_command("copy 40").is_err());
+        assert!(parse_command("copy 0 3").is_err());
+        assert!(parse_command("copy 5 4").is_err());
+    }
+
+    #[test]
+    fn test_frame_lines() {
+        let lines = vec![
+            (41, "plain".to_string()),
+            (42, String::new()),
+            (43, "tab\tand ünïcode\r".to_string()),
+        ];
+        assert_eq!(
+            frame_lines(&lines),
+            "3\n42 5 plain\n43 0 \n44 18 tab\tand ünïcode\r"
+        );
+        assert_eq!(frame_lines(&[]), "0");
+    }
+
+    #[test]
+    fn test_parse_top() {
+        assert_eq!(parse_command("top"), Ok(PogCommand::Top));
+        assert_eq!(parse_command("TOP"), Ok(PogCommand::Top));
+        assert!(parse_command("top extra").is_err());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_command("size"), Ok(PogCommand::Size));
+        assert_eq!(parse_command("SIZE"), Ok(PogCommand::Size));
+        assert!(parse_command("size extra").is_err());
+    }
+
+    #[test]
+    fn test_parse_reload() {
+        assert_eq!(parse_command("reload"), Ok(PogCommand::Reload));
+        assert_eq!(parse_command("RELOAD"), Ok(PogCommand::Reload));
+        assert!(parse_command("reload now").is_err());
+    }
+
+    #[test]
+    fn test_parse_quit() {
+        assert_eq!(parse_command("quit"), Ok(PogCommand::Quit));
+        assert_eq!(parse_command("QUIT"), Ok(PogCommand::Quit));
+        assert!(parse_command("quit now").is_err());
+    }
+
+    #[test]
+    fn test_parse_open() {
+        assert_eq!(
+            parse_command("open /var/log/my app.log"),
+            Ok(PogCommand::Open {
+                path: "/var/log/my app.log".to_string()
+            })
+        );
+        assert_eq!(parse_command("open "), Err("usage: open <path>".to_string()));
+    }
+
+    #[test]
+    fn test_parse_mark() {
+        // Full-line marks
+        assert_eq!(
+            parse_command("mark 10 red"),
+            Ok(PogCommand::Mark { line: 10, region: None, color: "red".to_string() })
+        );
+        assert_eq!(
+            parse_command("MARK 5 #FF0000"),
+            Ok(PogCommand::Mark { line: 5, region: None, color: "#FF0000".to_string() })
+        );
+        assert_eq!(
+            parse_command("mark 1 light blue"),
+            Ok(PogCommand::Mark { line: 1, region: None, color: "light blue".to_string() })
+        );
+        assert!(parse_command("mark").is_err());
+        assert!(parse_command("mark 10").is_err());
+        assert!(parse_command("mark abc red").is_err());
+        assert!(parse_command("mark 0 red").is_err());
+    }
+
+    #[test]
+    fn test_parse_mark_region() {
+        // Region marks
+        assert_eq!(
+            parse_command("mark 10 5-20 red"),
+            Ok(PogCommand::Mark { line: 10, region: Some((5, 20)), color: "red".to_string() })
+        );
+        assert_eq!(
+            parse_command("mark 100 1-50 #FF0000"),
+            Ok(PogCommand::Mark { line: 100, region: Some((1, 50)), color: "#FF0000".to_string() })
+        );
+        assert_eq!(
+            parse_command("mark 1 10-20 light blue"),
+            Ok(PogCommand::Mark { line: 1, region: Some((10, 20)), color: "light blue".to_string() })
+        );
+        // Error cases
+        assert!(parse_command("mark 10 0-5 red").is_err());   // column 0 invalid
+        assert!(parse_command("mark 10 5-0 red").is_err());   // column 0 invalid
+        assert!(parse_command("mark 10 5-5 red").is_err());   // start >= end
         assert!(parse_command("mark 10 10-5 red").is_err());  // start > end
         assert!(parse_command("mark 10 5-20").is_err());      // missing color
     }
@@ -340,23 +1702,355 @@ mod tests {
     fn test_parse_search() {
         assert_eq!(
             parse_command("search error"),
-            Ok(PogCommand::Search { pattern: "error".to_string() })
+            Ok(PogCommand::Search {
+                pattern: "error".to_string(),
+                ignore_case: false,
+                literal: false,
+                multiline: false,
+                time_range: TimeRange::default(),
+                field: None,
+                key: None,
+            })
         );
         assert_eq!(
             parse_command("SEARCH Error"),
-            Ok(PogCommand::Search { pattern: "Error".to_string() })
+            Ok(PogCommand::Search {
+                pattern: "Error".to_string(),
+                ignore_case: false,
+                literal: false,
+                multiline: false,
+                time_range: TimeRange::default(),
+                field: None,
+                key: None,
+            })
         );
         assert_eq!(
             parse_command("search error.*warning"),
-            Ok(PogCommand::Search { pattern: "error.*warning".to_string() })
+            Ok(PogCommand::Search {
+                pattern: "error.*warning".to_string(),
+                ignore_case: false,
+                literal: false,
+                multiline: false,
+                time_range: TimeRange::default(),
+                field: None,
+                key: None,
+            })
         );
         assert_eq!(
             parse_command("search multiple words"),
-            Ok(PogCommand::Search { pattern: "multiple words".to_string() })
+            Ok(PogCommand::Search {
+                pattern: "multiple words".to_string(),
+                ignore_case: false,
+                literal: false,
+                multiline: false,
+                time_range: TimeRange::default(),
+                field: None,
+                key: None,
+            })
         );
         assert!(parse_command("search").is_err());
     }
 
+    #[test]
+    fn test_parse_mark_matches() {
+        assert_eq!(
+            parse_command("mark-matches #FFAAAA"),
+            Ok(PogCommand::MarkMatches { color: "#FFAAAA".to_string(), pattern: None })
+        );
+        assert_eq!(
+            parse_command("MARK-MATCHES orange user=\\w+ id"),
+            Ok(PogCommand::MarkMatches {
+                color: "orange".to_string(),
+                pattern: Some("user=\\w+ id".to_string()),
+            })
+        );
+        assert!(parse_command("mark-matches").is_err());
+    }
+
+    #[test]
+    fn test_parse_mark_pattern() {
+        assert_eq!(
+            parse_command("mark-pattern red ERROR  disk"),
+            Ok(PogCommand::MarkPattern {
+                color: "red".to_string(),
+                pattern: "ERROR disk".to_string(),
+            })
+        );
+        assert!(parse_command("mark-pattern red").is_err());
+    }
+
+    #[test]
+    fn test_parse_grep() {
+        assert_eq!(
+            parse_command("grep ERROR  disk"),
+            Ok(PogCommand::Grep {
+                pattern: "ERROR disk".to_string(),
+                ignore_case: false,
+                from: 1,
+                max: None,
+            })
+        );
+        assert_eq!(
+            parse_command("grep -i --from 500 --max 20 timeout"),
+            Ok(PogCommand::Grep {
+                pattern: "timeout".to_string(),
+                ignore_case: true,
+                from: 500,
+                max: Some(20),
+            })
+        );
+        assert!(parse_command("grep").is_err());
+        assert!(parse_command("grep -i").is_err());
+        assert!(parse_command("grep --from 0 x").is_err());
+        assert!(parse_command("grep --max none x").is_err());
+    }
+
+    #[test]
+    fn test_parse_unmark_subsets() {
+        assert_eq!(parse_command("UNMARK-ALL"), Ok(PogCommand::UnmarkAll));
+        assert!(parse_command("unmark-all 5").is_err());
+        assert_eq!(
+            parse_command("unmark-pattern retry  after"),
+            Ok(PogCommand::UnmarkPattern {
+                pattern: "retry after".to_string()
+            })
+        );
+        assert!(parse_command("unmark-pattern").is_err());
+        assert_eq!(
+            parse_command("unmark-color #FFAAAA"),
+            Ok(PogCommand::UnmarkColor {
+                color: "#FFAAAA".to_string()
+            })
+        );
+        assert!(parse_command("unmark-color").is_err());
+        assert!(parse_command("unmark-color red blue").is_err());
+    }
+
+    #[test]
+    fn test_help_lists_every_command() {
+        for syntax in COMMAND_USAGES {
+            let name = syntax.split(' ').next().unwrap();
+            if let Err(e) = parse_command(name) {
+                assert!(!e.starts_with("unknown command"), "{}", e);
+            }
+        }
+        let help = help_text();
+        assert_eq!(help.lines().next(), Some(COMMAND_USAGES.len().to_string().as_str()));
+        assert!(help.lines().any(|line| line == "get <start_line> <count>"));
+        assert_eq!(
+            parse_command("get 1"),
+            Err("usage: get <start_line> <count>".to_string())
+        );
+        assert_eq!(parse_command("HELP"), Ok(PogCommand::Help));
+        assert_eq!(parse_command("version"), Ok(PogCommand::Version));
+        assert!(parse_command("version 2").is_err());
+    }
+
+    #[test]
+    fn test_parse_title_and_status_text() {
+        assert_eq!(
+            parse_command("title analyzing:  checkout-service crash 14:32"),
+            Ok(PogCommand::Title {
+                text: Some("analyzing:  checkout-service crash 14:32".to_string())
+            })
+        );
+        assert_eq!(parse_command("TITLE"), Ok(PogCommand::Title { text: None }));
+        assert_eq!(
+            parse_command("status-text step 2 of 5"),
+            Ok(PogCommand::StatusText { text: Some("step 2 of 5".to_string()) })
+        );
+        assert_eq!(parse_command("status-text  "), Ok(PogCommand::StatusText { text: None }));
+    }
+
+    #[test]
+    fn test_parse_marks_files() {
+        assert_eq!(
+            parse_command("save-marks /tmp/case 42/marks.json"),
+            Ok(PogCommand::SaveMarks {
+                path: "/tmp/case 42/marks.json".to_string()
+            })
+        );
+        assert_eq!(
+            parse_command("LOAD-MARKS marks.json "),
+            Ok(PogCommand::LoadMarks {
+                path: "marks.json".to_string()
+            })
+        );
+        assert!(parse_command("save-marks").is_err());
+        assert!(parse_command("load-marks  ").is_err());
+    }
+
+    #[test]
+    fn test_parse_export() {
+        assert_eq!(
+            parse_command("export 100  2000 /tmp/slice of it.log"),
+            Ok(PogCommand::Export {
+                start: 100,
+                end: 2000,
+                path: "/tmp/slice of it.log".to_string()
+            })
+        );
+        assert_eq!(parse_command("EXPORT cancel"), Ok(PogCommand::ExportCancel));
+        assert!(parse_command("export 1 10").is_err());
+        assert!(parse_command("export 0 10 out.log").is_err());
+        assert!(parse_command("export 10 9 out.log").is_err());
+        assert!(parse_command("export ten 20 out.log").is_err());
+    }
+
+    #[test]
+    fn test_parse_search_ignore_case() {
+        assert_eq!(
+            parse_command("search -i disk full"),
+            Ok(PogCommand::Search {
+                pattern: "disk full".to_string(),
+                ignore_case: true,
+                literal: false,
+                multiline: false,
+                time_range: TimeRange::default(),
+                field: None,
+                key: None,
+            })
+        );
+        assert!(parse_command("search -i").is_err());
+    }
+
+    #[test]
+    fn test_parse_search_literal() {
+        assert_eq!(
+            parse_command("search-literal GET /api?id=[3]  (retry)"),
+            Ok(PogCommand::Search {
+                pattern: "GET /api?id=[3]  (retry)".to_string(),
+                ignore_case: false,
+                literal: true,
+                multiline: false,
+                time_range: TimeRange::default(),
+                field: None,
+                key: None,
+            })
+        );
+        assert_eq!(
+            parse_command("SEARCH-LITERAL -i a+b"),
+            Ok(PogCommand::Search {
+                pattern: "a+b".to_string(),
+                ignore_case: true,
+                literal: true,
+                multiline: false,
+                time_range: TimeRange::default(),
+                field: None,
+                key: None,
+            })
+        );
+        assert!(parse_command("search-literal").is_err());
+        assert!(parse_command("search-literal -i").is_err());
+    }
+
+    #[test]
+    fn test_parse_search_time_range() {
+        let time = |text| Some(parse_time_bound(text).unwrap());
+        assert_eq!(
+            parse_command(
+                r#"search --after "2024-05-01 12:00" -i --before 2024-05-01T12:30:00 oom  killer"#
+            ),
+            Ok(PogCommand::Search {
+                pattern: "oom killer".to_string(),
+                ignore_case: true,
+                literal: false,
+                multiline: false,
+                time_range: TimeRange {
+                    after: time("2024-05-01 12:00"),
+                    before: time("2024-05-01 12:30"),
+                },
+                field: None,
+                key: None,
+            })
+        );
+        assert_eq!(
+            parse_command(r#"search-literal --before "2024-05-01"  a  b"#),
+            Ok(PogCommand::Search {
+                pattern: "a  b".to_string(),
+                ignore_case: false,
+                literal: true,
+                multiline: false,
+                time_range: TimeRange { after: None, before: time("2024-05-01") },
+                field: None,
+                key: None,
+            })
+        );
+        assert!(parse_command("search --after").is_err());
+        assert!(parse_command("search --after yesterday error").is_err());
+        assert!(parse_command(r#"search --after "2024-05-01 12:00 error"#).is_err());
+        assert!(parse_command("search --before 2024-05-01").is_err());
+    }
+
+    #[test]
+    fn test_parse_search_field() {
+        assert_eq!(
+            parse_command("search --field 3 -i time  out"),
+            Ok(PogCommand::Search {
+                pattern: "time out".to_string(),
+                ignore_case: true,
+                literal: false,
+                multiline: false,
+                time_range: TimeRange::default(),
+                field: Some(3),
+                key: None,
+            })
+        );
+        assert_eq!(
+            parse_command("search-literal --field 1 [db]"),
+            Ok(PogCommand::Search {
+                pattern: "[db]".to_string(),
+                ignore_case: false,
+                literal: true,
+                multiline: false,
+                time_range: TimeRange::default(),
+                field: Some(1),
+                key: None,
+            })
+        );
+        assert!(parse_command("search --field").is_err());
+        assert!(parse_command("search --field 0 error").is_err());
+        assert!(parse_command("search --field two error").is_err());
+        assert!(parse_command("search-multiline --field 2 a\nb").is_err());
+    }
+
+    #[test]
+    fn test_parse_search_key() {
+        assert_eq!(
+            parse_command("search -i --key msg time out"),
+            Ok(PogCommand::Search {
+                pattern: "time out".to_string(),
+                ignore_case: true,
+                literal: false,
+                multiline: false,
+                time_range: TimeRange::default(),
+                field: None,
+                key: Some("msg".to_string()),
+            })
+        );
+        assert_eq!(parse_command("search --key").unwrap_err(), "missing key name after --key");
+        assert!(parse_command("search --key msg --field 1 x").is_err());
+        assert!(parse_command("search-multiline --key msg a").is_err());
+    }
+
+    #[test]
+    fn test_parse_search_multiline() {
+        assert_eq!(
+            parse_command(r"search-multiline -i panic\n\s+at"),
+            Ok(PogCommand::Search {
+                pattern: r"panic\n\s+at".to_string(),
+                ignore_case: true,
+                literal: false,
+                multiline: true,
+                time_range: TimeRange::default(),
+                field: None,
+                key: None,
+            })
+        );
+        assert!(parse_command("search-multiline").is_err());
+        assert!(parse_command("search-multiline -i").is_err());
+    }
+
     #[test]
     fn test_parse_search_next() {
         assert_eq!(parse_command("search-next"), Ok(PogCommand::SearchNext));
@@ -377,4 +2071,317 @@ mod tests {
         assert_eq!(parse_command("SEARCH-CLEAR"), Ok(PogCommand::SearchClear));
         assert!(parse_command("search-clear extra").is_err());
     }
+
+    #[test]
+    fn test_parse_duplicate() {
+        assert_eq!(parse_command("duplicate"), Ok(PogCommand::Duplicate));
+        assert_eq!(parse_command("DUPLICATE"), Ok(PogCommand::Duplicate));
+        assert!(parse_command("duplicate extra").is_err());
+    }
+
+    #[test]
+    fn test_parse_search_wrap() {
+        assert_eq!(parse_command("search-wrap"), Ok(PogCommand::SearchWrap { enabled: None }));
+        assert_eq!(
+            parse_command("search-wrap ON"),
+            Ok(PogCommand::SearchWrap { enabled: Some(true) })
+        );
+        assert_eq!(
+            parse_command("search-wrap off"),
+            Ok(PogCommand::SearchWrap { enabled: Some(false) })
+        );
+        assert!(parse_command("search-wrap yes").is_err());
+        assert!(parse_command("search-wrap on off").is_err());
+    }
+
+    #[test]
+    fn test_parse_saved_searches() {
+        assert_eq!(
+            parse_command("search-save oom"),
+            Ok(PogCommand::SearchSave { name: "oom".to_string() })
+        );
+        assert!(parse_command("search-save").is_err());
+        assert!(parse_command("search-save two words").is_err());
+        assert_eq!(
+            parse_command("SEARCH-LOAD oom"),
+            Ok(PogCommand::SearchLoad { name: Some("oom".to_string()) })
+        );
+        assert_eq!(parse_command("search-load"), Ok(PogCommand::SearchLoad { name: None }));
+        assert!(parse_command("search-load a b").is_err());
+    }
+
+    #[test]
+    fn test_parse_view() {
+        assert_eq!(parse_command("view"), Ok(PogCommand::View { mode: None }));
+        assert_eq!(parse_command("view hex"), Ok(PogCommand::View { mode: Some(ViewMode::Hex) }));
+        assert_eq!(parse_command("VIEW Text"), Ok(PogCommand::View { mode: Some(ViewMode::Text) }));
+        assert!(parse_command("view binary").is_err());
+        assert!(parse_command("view hex text").is_err());
+    }
+
+    #[test]
+    fn test_parse_wrap() {
+        assert_eq!(parse_command("wrap"), Ok(PogCommand::Wrap { enabled: None }));
+        assert_eq!(parse_command("WRAP On"), Ok(PogCommand::Wrap { enabled: Some(true) }));
+        assert_eq!(parse_command("wrap off"), Ok(PogCommand::Wrap { enabled: Some(false) }));
+        assert_eq!(parse_command("wrap yes"), Err("usage: wrap [on|off]".to_string()));
+        assert!(parse_command("wrap on off").is_err());
+    }
+
+    #[test]
+    fn test_parse_relative_numbers() {
+        assert_eq!(
+            parse_command("relative-numbers"),
+            Ok(PogCommand::RelativeNumbers { enabled: None })
+        );
+        assert_eq!(
+            parse_command("relative-numbers ON"),
+            Ok(PogCommand::RelativeNumbers { enabled: Some(true) })
+        );
+        assert_eq!(
+            parse_command("relative-numbers off"),
+            Ok(PogCommand::RelativeNumbers { enabled: Some(false) })
+        );
+        assert_eq!(
+            parse_command("relative-numbers 1"),
+            Err("usage: relative-numbers [on|off]".to_string())
+        );
+        assert!(parse_command("relative-numbers on off").is_err());
+    }
+
+    #[test]
+    fn test_parse_time_column() {
+        assert_eq!(parse_command("time-column"), Ok(PogCommand::TimeColumn { enabled: None }));
+        assert_eq!(
+            parse_command("time-column ON"),
+            Ok(PogCommand::TimeColumn { enabled: Some(true) })
+        );
+        assert_eq!(
+            parse_command("time-column 1"),
+            Err("usage: time-column [on|off]".to_string())
+        );
+        assert!(parse_command("time-column on off").is_err());
+    }
+
+    #[test]
+    fn test_parse_marks_panel() {
+        assert_eq!(parse_command("marks-panel"), Ok(PogCommand::MarksPanel { visible: None }));
+        assert_eq!(
+            parse_command("marks-panel off"),
+            Ok(PogCommand::MarksPanel { visible: Some(false) })
+        );
+        assert_eq!(
+            parse_command("marks-panel show"),
+            Err("usage: marks-panel [on|off]".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_table() {
+        let table = |action| Ok(PogCommand::Table { action });
+        assert_eq!(parse_command("table"), table(TableAction::Get));
+        assert_eq!(parse_command("table ON"), table(TableAction::On { delimiter: None }));
+        assert_eq!(
+            parse_command("table tab"),
+            table(TableAction::On { delimiter: Some(Delimiter::Tab) })
+        );
+        assert_eq!(
+            parse_command("table split \\s*\\| "),
+            table(TableAction::On { delimiter: Some(Delimiter::parse("\\s*\\|").unwrap()) })
+        );
+        assert_eq!(
+            parse_command("table width 3 12"),
+            table(TableAction::Width { column: 2, width: 12 })
+        );
+        assert_eq!(parse_command("table hide 1"), table(TableAction::Hide { column: 0 }));
+        assert_eq!(parse_command("table show"), table(TableAction::Show { column: None }));
+        assert_eq!(parse_command("table show 0").unwrap_err(), "column numbers must be >= 1");
+        assert!(parse_command("table width 2 0").is_err());
+        assert!(parse_command("table split (").is_err());
+        assert!(parse_command("table hide").unwrap_err().starts_with("usage: table "));
+    }
+
+    #[test]
+    fn test_parse_logfmt() {
+        let logfmt = |action| Ok(PogCommand::Logfmt { action });
+        assert_eq!(parse_command("logfmt"), logfmt(LogfmtAction::Get));
+        assert_eq!(parse_command("logfmt Off"), logfmt(LogfmtAction::Enable { enabled: false }));
+        assert_eq!(
+            parse_command("logfmt hide Caller"),
+            logfmt(LogfmtAction::Hide { key: "Caller".to_string() })
+        );
+        assert_eq!(parse_command("logfmt show"), logfmt(LogfmtAction::Show { key: None }));
+        assert_eq!(
+            parse_command("logfmt hide"),
+            Err("usage: logfmt [on|off] | hide <key> | show [key]".to_string())
+        );
+        assert!(parse_command("logfmt on off").is_err());
+    }
+
+    #[test]
+    fn test_parse_levels() {
+        assert_eq!(parse_command("levels"), Ok(PogCommand::Levels { style: None }));
+        assert_eq!(
+            parse_command("levels Word"),
+            Ok(PogCommand::Levels { style: Some(LevelStyle::Word) })
+        );
+        assert_eq!(parse_command("levels bold"), Err("usage: levels [line|word|off]".to_string()));
+        assert!(parse_command("levels line word").is_err());
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        assert_eq!(
+            parse_command("filter ERROR|WARN"),
+            Ok(PogCommand::Filter {
+                kind: FilterKind::Include,
+                pattern: "ERROR|WARN".to_string(),
+            })
+        );
+        assert_eq!(
+            parse_command("FILTER-OUT GET /health"),
+            Ok(PogCommand::Filter {
+                kind: FilterKind::Exclude,
+                pattern: "GET /health".to_string(),
+            })
+        );
+        assert_eq!(parse_command("filter-clear"), Ok(PogCommand::FilterClear));
+        assert!(parse_command("filter").is_err());
+        assert_eq!(
+            parse_command("filter-out"),
+            Err("usage: filter-out <regex_pattern>".to_string())
+        );
+        assert!(parse_command("filter-clear all").is_err());
+    }
+
+    #[test]
+    fn test_parse_fold() {
+        let fold = |action| Ok(PogCommand::Fold { action });
+        assert_eq!(parse_command("fold"), fold(FoldAction::Get));
+        assert_eq!(parse_command("fold off"), fold(FoldAction::Off));
+        assert_eq!(parse_command("fold on"), fold(FoldAction::On { mode: FoldMode::Similar }));
+        assert_eq!(parse_command("FOLD Exact"), fold(FoldAction::On { mode: FoldMode::Exact }));
+        assert_eq!(
+            parse_command("fold fuzzy"),
+            Err("usage: fold [on|off|exact|similar]".to_string())
+        );
+        assert!(parse_command("fold on off").is_err());
+    }
+
+    #[test]
+    fn test_parse_hide() {
+        assert_eq!(parse_command("hide 10 20"), Ok(PogCommand::Hide { start: 10, end: 20 }));
+        assert_eq!(parse_command("HIDE 5 5"), Ok(PogCommand::Hide { start: 5, end: 5 }));
+        assert_eq!(parse_command("hide 10"), Err("usage: hide <start_line> <end_line>".to_string()));
+        assert!(parse_command("hide 20 10").is_err());
+        assert!(parse_command("hide 0 3").is_err());
+        assert_eq!(parse_command("unhide-all"), Ok(PogCommand::UnhideAll));
+        assert!(parse_command("unhide-all now").is_err());
+    }
+
+    #[test]
+    fn test_parse_pin() {
+        assert_eq!(parse_command("pin 42"), Ok(PogCommand::Pin { line: 42 }));
+        assert_eq!(parse_command("UNPIN 42"), Ok(PogCommand::Unpin { line: 42 }));
+        assert_eq!(parse_command("pin"), Err("usage: pin <line_number>".to_string()));
+        assert_eq!(parse_command("unpin 1 2"), Err("usage: unpin <line_number>".to_string()));
+        assert!(parse_command("pin 0").is_err());
+        assert!(parse_command("pin top").is_err());
+    }
+
+    #[test]
+    fn test_parse_annotate() {
+        assert_eq!(
+            parse_command("annotate 1200 first  OOM, see ticket"),
+            Ok(PogCommand::Annotate {
+                line: 1200,
+                text: "first OOM, see ticket".to_string(),
+            })
+        );
+        assert!(parse_command("annotate 1200").is_err());
+        assert!(parse_command("annotate 0 note").is_err());
+        assert!(parse_command("annotate x note").is_err());
+        assert_eq!(parse_command("annotations"), Ok(PogCommand::Annotations));
+        assert_eq!(parse_command("annotations CLEAR"), Ok(PogCommand::AnnotationsClear));
+        assert_eq!(
+            parse_command("annotations remove 7"),
+            Ok(PogCommand::AnnotationRemove { line: 7 })
+        );
+        assert!(parse_command("annotations remove").is_err());
+        assert!(parse_command("annotations remove 0").is_err());
+        assert!(parse_command("annotations list").is_err());
+    }
+
+    #[test]
+    fn test_parse_bookmark() {
+        assert_eq!(
+            parse_command("bookmark set deploy 1200"),
+            Ok(PogCommand::BookmarkSet {
+                name: "deploy".to_string(),
+                line: Some(1200),
+            })
+        );
+        assert_eq!(
+            parse_command("BOOKMARK SET Deploy"),
+            Ok(PogCommand::BookmarkSet {
+                name: "Deploy".to_string(),
+                line: None,
+            })
+        );
+        assert_eq!(
+            parse_command("bookmark goto deploy"),
+            Ok(PogCommand::BookmarkGoto {
+                name: "deploy".to_string(),
+            })
+        );
+        assert_eq!(parse_command("bookmark list"), Ok(PogCommand::BookmarkList));
+        assert_eq!(
+            parse_command("bookmark remove deploy"),
+            Ok(PogCommand::BookmarkRemove {
+                name: "deploy".to_string(),
+            })
+        );
+        assert!(parse_command("bookmark set deploy 0").is_err());
+        assert!(parse_command("bookmark set deploy x").is_err());
+        assert!(parse_command("bookmark set two words 5").is_err());
+        assert!(parse_command("bookmark goto").is_err());
+        assert!(parse_command("bookmark").is_err());
+    }
+
+    #[test]
+    fn test_parse_highlight() {
+        assert_eq!(
+            parse_command("highlight #87CEFA user=[a-z]+ id"),
+            Ok(PogCommand::Highlight {
+                color: "#87CEFA".to_string(),
+                pattern: "user=[a-z]+ id".to_string(),
+            })
+        );
+        assert!(parse_command("highlight red").is_err());
+        assert_eq!(
+            parse_command("UNHIGHLIGHT user=[a-z]+ id"),
+            Ok(PogCommand::Unhighlight {
+                pattern: "user=[a-z]+ id".to_string(),
+            })
+        );
+        assert!(parse_command("unhighlight").is_err());
+        assert_eq!(parse_command("highlight-clear"), Ok(PogCommand::HighlightClear));
+        assert!(parse_command("highlight-clear now").is_err());
+        assert_eq!(parse_command("clear-highlights"), Ok(PogCommand::HighlightClear));
+        assert_eq!(
+            parse_command("clear-highlights red"),
+            Err("usage: clear-highlights".to_string())
+        );
+        assert_eq!(parse_command("Highlights"), Ok(PogCommand::Highlights));
+        assert!(parse_command("highlights red").is_err());
+    }
+
+    #[test]
+    fn test_frame_highlights() {
+        assert_eq!(
+            frame_highlights(&[("#87CEFA", r"user=\w+ id"), ("red", "ERROR")]),
+            "2\n#87CEFA user=\\w+ id\nred ERROR"
+        );
+        assert_eq!(frame_highlights(&[]), "0");
+    }
 }
@@ -0,0 +1,252 @@
+use std::fs::File;
+use std::io::{self, Read, Seek};
+use std::path::Path;
+
+use crate::encoding::{TextEncoding, DETECT_SAMPLE_LEN};
+use crate::error::Result;
+use crate::file_source::FileSource;
+use crate::line_index::SparseLineIndex;
+
+type DecoderFn = fn(File) -> io::Result<Box<dyn Read>>;
+
+/// A compression format recognized by its leading magic bytes.
+pub struct Decoder {
+    pub name: &'static str,
+    magic: &'static [u8],
+    /// Extra check on the bytes after `magic`, for magics short enough to
+    /// start a plain text log.
+    after_magic: Option<fn(&[u8]) -> bool>,
+    open: DecoderFn,
+}
+
+impl Decoder {
+    fn matches(&self, header: &[u8]) -> bool {
+        header.starts_with(self.magic)
+            && self
+                .after_magic
+                .is_none_or(|check| check(&header[self.magic.len()..]))
+    }
+}
+
+/// bzip2 follows "BZh" with the block size digit and either the first block's
+/// magic or, for an empty stream, the end-of-stream magic.
+fn bzip2_after_magic(rest: &[u8]) -> bool {
+    const BLOCK_MAGIC: &[u8] = &[0x31, 0x41, 0x59, 0x26, 0x53, 0x59];
+    const EOS_MAGIC: &[u8] = &[0x17, 0x72, 0x45, 0x38, 0x50, 0x90];
+    match rest.split_first() {
+        Some((b'1'..=b'9', rest)) => rest.starts_with(BLOCK_MAGIC) || rest.starts_with(EOS_MAGIC),
+        _ => false,
+    }
+}
+
+/// Supported formats. Adding a format only needs a new entry here.
+const DECODERS: &[Decoder] = &[
+    Decoder {
+        name: "gzip",
+        magic: &[0x1f, 0x8b],
+        after_magic: None,
+        open: |f| Ok(Box::new(flate2::read::MultiGzDecoder::new(f))),
+    },
+    Decoder {
+        name: "zstd",
+        magic: &[0x28, 0xb5, 0x2f, 0xfd],
+        after_magic: None,
+        open: |f| Ok(Box::new(zstd::stream::read::Decoder::new(f)?)),
+    },
+    Decoder {
+        name: "xz",
+        magic: &[0xfd, b'7', b'z', b'X', b'Z', 0x00],
+        after_magic: None,
+        open: |f| Ok(Box::new(xz2::read::XzDecoder::new_multi_decoder(f))),
+    },
+    Decoder {
+        name: "bzip2",
+        magic: b"BZh",
+        after_magic: Some(bzip2_after_magic),
+        open: |f| Ok(Box::new(bzip2::read::MultiBzDecoder::new(f))),
+    },
+];
+
+const MAGIC_LEN: usize = 10;
+
+/// Open `file`, decompressing it if it starts with a known magic.
+pub fn open_maybe_compressed(mut file: File) -> io::Result<(Box<dyn Read>, Option<&'static Decoder>)> {
+    let mut header = Vec::with_capacity(MAGIC_LEN);
+    (&mut file).take(MAGIC_LEN as u64).read_to_end(&mut header)?;
+    file.seek(io::SeekFrom::Start(0))?;
+    match detect(&header) {
+        Some(decoder) => Ok(((decoder.open)(file)?, Some(decoder))),
+        None => Ok((Box::new(file), None)),
+    }
+}
+
+/// Find the decoder whose magic bytes prefix `header`.
+pub fn detect(header: &[u8]) -> Option<&'static Decoder> {
+    DECODERS.iter().find(|d| d.matches(header))
+}
+
+/// Sniff the first bytes of a file to see if it is compressed.
+pub fn detect_path<P: AsRef<Path>>(path: P) -> io::Result<Option<&'static Decoder>> {
+    let mut header = Vec::with_capacity(MAGIC_LEN);
+    File::open(path)?
+        .take(MAGIC_LEN as u64)
+        .read_to_end(&mut header)?;
+    Ok(detect(&header))
+}
+
+/// A compressed log (or archive member) decompressed into memory and indexed
+/// like `MappedFile`.
+pub struct CompressedFile {
+    data: Vec<u8>,
+    encoding: TextEncoding,
+    index: SparseLineIndex,
+    path_display: String,
+}
+
+impl CompressedFile {
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        decoder: &Decoder,
+        encoding: Option<TextEncoding>,
+    ) -> io::Result<Self> {
+        let file = File::open(&path)?;
+
+        let mut data = Vec::new();
+        (decoder.open)(file)?.read_to_end(&mut data)?;
+        Ok(Self::from_data(
+            data,
+            encoding,
+            &path.as_ref().display().to_string(),
+            decoder.name,
+        ))
+    }
+
+    /// Index already decompressed `data`, shown as `name (format)`.
+    pub fn from_data(
+        data: Vec<u8>,
+        encoding: Option<TextEncoding>,
+        name: &str,
+        format: &str,
+    ) -> Self {
+        let encoding = encoding
+            .unwrap_or_else(|| TextEncoding::detect(&data[..data.len().min(DETECT_SAMPLE_LEN)]));
+        let index = SparseLineIndex::build(&data, encoding);
+
+        let path_display = if encoding.is_utf8() {
+            format!("{} ({})", name, format)
+        } else {
+            format!("{} ({}, {})", name, format, encoding.name())
+        };
+
+        Self {
+            data,
+            encoding,
+            index,
+            path_display,
+        }
+    }
+}
+
+impl FileSource for CompressedFile {
+    fn line_count(&self) -> usize {
+        self.index.line_count()
+    }
+
+    /// Size of the decompressed content
+    fn file_size(&self) -> Result<u64> {
+        Ok(self.data.len() as u64)
+    }
+
+    /// Offsets count decompressed bytes, like `file_size`.
+    fn line_at_offset(&self, offset: u64) -> Option<usize> {
+        Some(self.index.line_at(&self.data, offset as usize, self.encoding))
+    }
+
+    fn get_line(&self, line_num: usize) -> Result<Option<String>> {
+        Ok(self
+            .index
+            .lines(&self.data, line_num, self.encoding)
+            .next()
+            .map(|(_, bytes)| self.encoding.decode_line(bytes)))
+    }
+
+    fn get_lines(&self, start_line: usize, count: usize) -> Result<Vec<(usize, String)>> {
+        Ok(self
+            .index
+            .lines(&self.data, start_line, self.encoding)
+            .take(count)
+            .map(|(i, bytes)| (i, self.encoding.decode_line(bytes)))
+            .collect())
+    }
+
+    fn get_raw_lines(&self, start_line: usize, count: usize) -> Result<Vec<(usize, Vec<u8>)>> {
+        Ok(self
+            .index
+            .lines(&self.data, start_line, self.encoding)
+            .take(count)
+            .map(|(i, bytes)| (i, self.encoding.strip_terminator(bytes).to_vec()))
+            .collect())
+    }
+
+    fn display_name(&self) -> &str {
+        &self.path_display
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const SAMPLE: &[u8] = b"first line\nsecond line\r\nthird line\n";
+
+    fn roundtrip(name: &str, compressed: Vec<u8>) {
+        let path = std::env::temp_dir().join(format!("pog-test-{}-{}", std::process::id(), name));
+        File::create(&path).unwrap().write_all(&compressed).unwrap();
+
+        let decoder = detect_path(&path).unwrap().expect("format not detected");
+        assert_eq!(decoder.name, name);
+
+        let file = CompressedFile::open(&path, decoder, None).unwrap();
+        assert_eq!(file.line_count(), 3);
+        assert_eq!(file.get_line(1).unwrap(), Some("second line".to_string()));
+        assert_eq!(file.file_size().unwrap(), SAMPLE.len() as u64);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_detect_plain_text() {
+        assert!(detect(b"2024-01-01 INFO start").is_none());
+        assert!(detect(b"").is_none());
+        assert!(detect(b"BZh9 started").is_none());
+        assert!(detect(b"BZh91AY&SX").is_none());
+        assert!(detect(b"BZh01AY&SY").is_none());
+    }
+
+    #[test]
+    fn test_gzip() {
+        let mut enc = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        enc.write_all(SAMPLE).unwrap();
+        roundtrip("gzip", enc.finish().unwrap());
+    }
+
+    #[test]
+    fn test_zstd() {
+        roundtrip("zstd", zstd::stream::encode_all(SAMPLE, 0).unwrap());
+    }
+
+    #[test]
+    fn test_xz() {
+        let mut enc = xz2::write::XzEncoder::new(Vec::new(), 6);
+        enc.write_all(SAMPLE).unwrap();
+        roundtrip("xz", enc.finish().unwrap());
+    }
+
+    #[test]
+    fn test_bzip2() {
+        let mut enc = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        enc.write_all(SAMPLE).unwrap();
+        roundtrip("bzip2", enc.finish().unwrap());
+    }
+}
@@ -0,0 +1,202 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::error::Result;
+use crate::file_source::FileSource;
+
+const ROTATED_EXTENSIONS: &[&str] = &["gz", "zst", "xz", "bz2"];
+
+/// Several sources presented back to back as one file with global line numbers.
+pub struct ConcatSource {
+    parts: Vec<Arc<dyn FileSource>>,
+    /// Global line number of the first line of each part
+    part_starts: Vec<usize>,
+    line_count: usize,
+    display_name: String,
+}
+
+impl ConcatSource {
+    pub fn new(parts: Vec<Arc<dyn FileSource>>, display_name: String) -> Self {
+        let mut part_starts = Vec::with_capacity(parts.len());
+        let mut line_count = 0;
+        for part in &parts {
+            // Global line numbers need exact counts of every part
+            part.wait_indexed();
+            part_starts.push(line_count);
+            line_count += part.line_count();
+        }
+
+        Self {
+            parts,
+            part_starts,
+            line_count,
+            display_name,
+        }
+    }
+
+    /// Index of the part containing global line `line_num`
+    fn part_for_line(&self, line_num: usize) -> usize {
+        match self.part_starts.binary_search(&line_num) {
+            // Several parts may start at the same line if some are empty; use the last
+            Ok(mut i) => {
+                while i + 1 < self.part_starts.len() && self.part_starts[i + 1] == line_num {
+                    i += 1;
+                }
+                i
+            }
+            Err(i) => i - 1,
+        }
+    }
+}
+
+impl FileSource for ConcatSource {
+    fn line_count(&self) -> usize {
+        self.line_count
+    }
+
+    fn file_size(&self) -> Result<u64> {
+        let mut total = 0;
+        for part in &self.parts {
+            total += part.file_size()?;
+        }
+        Ok(total)
+    }
+
+    fn get_line(&self, line_num: usize) -> Result<Option<String>> {
+        if line_num >= self.line_count {
+            return Ok(None);
+        }
+        let part = self.part_for_line(line_num);
+        self.parts[part].get_line(line_num - self.part_starts[part])
+    }
+
+    fn get_lines(&self, start_line: usize, count: usize) -> Result<Vec<(usize, String)>> {
+        let end_line = (start_line + count).min(self.line_count);
+        let mut result = Vec::with_capacity(end_line.saturating_sub(start_line));
+
+        let mut current = start_line;
+        while current < end_line {
+            let part = self.part_for_line(current);
+            let part_start = self.part_starts[part];
+            let part_end = part_start + self.parts[part].line_count();
+            let chunk_end = end_line.min(part_end);
+
+            let lines = self.parts[part].get_lines(current - part_start, chunk_end - current)?;
+            result.extend(lines.into_iter().map(|(n, text)| (n + part_start, text)));
+            current = chunk_end;
+        }
+
+        Ok(result)
+    }
+
+    fn display_name(&self) -> &str {
+        &self.display_name
+    }
+}
+
+/// Find rotated siblings of `path` (`app.log.1`, `app.log.2.gz`, ...) and
+/// return them oldest first, followed by `path` itself.
+pub fn discover_rotated(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let file_name = match path.file_name().and_then(|n| n.to_str()) {
+        Some(name) => name.to_string(),
+        None => return Ok(vec![path.to_path_buf()]),
+    };
+    let dir = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    let mut rotated: Vec<(usize, PathBuf)> = Vec::new();
+    for entry in std::fs::read_dir(&dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if let Some(index) = name.to_str().and_then(|n| rotation_index(&file_name, n)) {
+            rotated.push((index, entry.path()));
+        }
+    }
+
+    // Higher rotation numbers are older
+    rotated.sort_by_key(|(index, _)| std::cmp::Reverse(*index));
+    let mut paths: Vec<PathBuf> = rotated.into_iter().map(|(_, p)| p).collect();
+    paths.push(path.to_path_buf());
+    Ok(paths)
+}
+
+/// Rotation number of `candidate` if it is `<base>.<N>` or `<base>.<N>.<ext>`.
+fn rotation_index(base: &str, candidate: &str) -> Option<usize> {
+    let suffix = candidate.strip_prefix(base)?.strip_prefix('.')?;
+    let number = match suffix.split_once('.') {
+        Some((number, ext)) if ROTATED_EXTENSIONS.contains(&ext) => number,
+        Some(_) => return None,
+        None => suffix,
+    };
+    if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    number.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_source::VecSource;
+
+    fn part(prefix: &str, n: usize) -> Arc<dyn FileSource> {
+        Arc::new(VecSource::new((0..n).map(|i| format!("{}{}", prefix, i))))
+    }
+
+    #[test]
+    fn test_concat_line_numbering() {
+        let source = ConcatSource::new(vec![part("a", 3), part("e", 0), part("b", 2)], "x".into());
+        assert_eq!(source.line_count(), 5);
+        assert_eq!(source.get_line(2).unwrap(), Some("a2".to_string()));
+        assert_eq!(source.get_line(3).unwrap(), Some("b0".to_string()));
+        assert_eq!(source.get_line(5).unwrap(), None);
+    }
+
+    #[test]
+    fn test_concat_get_lines_across_boundary() {
+        let source = ConcatSource::new(vec![part("a", 3), part("b", 2)], "x".into());
+        let lines = source.get_lines(1, 10).unwrap();
+        assert_eq!(
+            lines,
+            vec![
+                (1, "a1".to_string()),
+                (2, "a2".to_string()),
+                (3, "b0".to_string()),
+                (4, "b1".to_string()),
+            ]
+        );
+        assert_eq!(source.file_size().unwrap(), 15);
+    }
+
+    #[test]
+    fn test_rotation_index() {
+        assert_eq!(rotation_index("app.log", "app.log.1"), Some(1));
+        assert_eq!(rotation_index("app.log", "app.log.12.gz"), Some(12));
+        assert_eq!(rotation_index("app.log", "app.log.3.zst"), Some(3));
+        assert_eq!(rotation_index("app.log", "app.log"), None);
+        assert_eq!(rotation_index("app.log", "app.log.old"), None);
+        assert_eq!(rotation_index("app.log", "app.log.2.txt"), None);
+        assert_eq!(rotation_index("app.log", "app.logx.1"), None);
+    }
+
+    #[test]
+    fn test_discover_rotated() {
+        let dir = std::env::temp_dir().join(format!("pog-rotated-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["app.log", "app.log.1", "app.log.2.gz", "app.log.10.xz", "other.log.1"] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+
+        let paths = discover_rotated(&dir.join("app.log")).unwrap();
+        let names: Vec<_> = paths
+            .iter()
+            .map(|p| p.file_name().unwrap().to_str().unwrap().to_string())
+            .collect();
+        assert_eq!(names, vec!["app.log.10.xz", "app.log.2.gz", "app.log.1", "app.log"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
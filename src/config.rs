@@ -0,0 +1,377 @@
+use std::path::PathBuf;
+
+use crate::columns::{self, FieldStyle};
+use crate::font;
+use crate::keys::{self, KeyAction, KeyPress};
+use crate::levels::{LevelColors, LevelStyle};
+use crate::search::CaseMode;
+use crate::theme::ThemeChoice;
+use crate::timestamp::TimeFormat;
+
+/// Settings read from `$XDG_CONFIG_HOME/pog/config` (or `~/.config/pog/config`).
+///
+/// The file is a list of `key = value` lines; blank lines and lines starting
+/// with `#` are ignored. Unset keys fall back to CLI flags or built-in defaults.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    pub search_buffer_lines: Option<usize>,
+    pub search_chunk_size: Option<usize>,
+    pub search_research_margin: Option<usize>,
+    pub column_guides: Option<Vec<usize>>,
+    pub fixed_fields: Option<Vec<FieldStyle>>,
+    pub max_line_columns: Option<usize>,
+    pub search_case: Option<CaseMode>,
+    pub search_wrap: Option<bool>,
+    /// Seconds a socket command waits for the file worker before failing
+    pub command_timeout: Option<usize>,
+    /// Port of the command server, and the one `pog ctl` and `pog doctor` use
+    pub port: Option<u16>,
+    /// Lines fetched per remote command
+    pub remote_chunk_size: Option<usize>,
+    /// Whether lines appended to the file are announced in the notice
+    pub notify_new_lines: Option<bool>,
+    /// Whether windows start at the end of the file, following new lines
+    pub follow: Option<bool>,
+    /// Whether windows number lines by their distance from the cursor line
+    pub relative_numbers: Option<bool>,
+    /// Monospace font of the log lines, and its size in points
+    pub font_family: Option<String>,
+    pub font_size: Option<u32>,
+    pub theme: Option<ThemeChoice>,
+    /// Severity coloring of lines (`line`, `word` or `off`) and per-level colors
+    pub levels: Option<LevelStyle>,
+    pub level_colors: Option<LevelColors>,
+    /// Keys of `key=value` lines colored
+    pub logfmt: Option<bool>,
+    /// Timestamp layouts tried before the built-in ones (`time_format`, repeatable)
+    pub time_formats: Vec<TimeFormat>,
+    /// Keys rebinding navigation actions (`key_top = gg`), in file order
+    pub key_bindings: Vec<(KeyAction, Vec<Vec<KeyPress>>)>,
+}
+
+impl Config {
+    pub fn default_path() -> Option<PathBuf> {
+        let base = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(base.join("pog").join("config"))
+    }
+
+    /// Load the config file, treating a missing file as an empty config.
+    pub fn load() -> Result<Self, String> {
+        let Some(path) = Self::default_path() else {
+            return Ok(Self::default());
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => {
+                Self::parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut config = Self::default();
+
+        for (index, line) in contents.lines().enumerate() {
+            let line_no = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected `key = value`", line_no))?;
+            let key = key.trim();
+            let value = value.trim();
+
+            match key {
+                "search_buffer_lines" => {
+                    config.search_buffer_lines = Some(parse_count(key, value, line_no)?)
+                }
+                "search_chunk_size" => {
+                    config.search_chunk_size = Some(parse_count(key, value, line_no)?)
+                }
+                "search_research_margin" => {
+                    config.search_research_margin = Some(parse_count(key, value, line_no)?)
+                }
+                "column_guides" => {
+                    config.column_guides = Some(
+                        columns::parse_guides(value).map_err(|e| format!("line {}: {}", line_no, e))?,
+                    )
+                }
+                "fixed_fields" => {
+                    config.fixed_fields = Some(
+                        columns::parse_fields(value).map_err(|e| format!("line {}: {}", line_no, e))?,
+                    )
+                }
+                "max_line_columns" => {
+                    config.max_line_columns = Some(parse_count(key, value, line_no)?)
+                }
+                "search_case" => {
+                    config.search_case =
+                        Some(CaseMode::parse(value).map_err(|e| format!("line {}: {}", line_no, e))?)
+                }
+                "search_wrap" => config.search_wrap = Some(parse_bool(key, value, line_no)?),
+                "logfmt" => config.logfmt = Some(parse_bool(key, value, line_no)?),
+                "command_timeout" => {
+                    config.command_timeout = Some(parse_count(key, value, line_no)?)
+                }
+                "port" => config.port = Some(parse_port(value, line_no)?),
+                "remote_chunk_size" => {
+                    config.remote_chunk_size = Some(parse_count(key, value, line_no)?)
+                }
+                "notify_new_lines" => {
+                    config.notify_new_lines = Some(parse_bool(key, value, line_no)?)
+                }
+                "follow" => config.follow = Some(parse_bool(key, value, line_no)?),
+                "relative_numbers" => {
+                    config.relative_numbers = Some(parse_bool(key, value, line_no)?)
+                }
+                "font_family" => {
+                    config.font_family =
+                        Some(font::parse_family(value).map_err(|e| format!("line {}: {}", line_no, e))?)
+                }
+                "font_size" => {
+                    config.font_size =
+                        Some(font::parse_size(value).map_err(|e| format!("line {}: {}", line_no, e))?)
+                }
+                "theme" => {
+                    config.theme =
+                        Some(ThemeChoice::parse(value).map_err(|e| format!("line {}: {}", line_no, e))?)
+                }
+                "time_format" => config.time_formats.push(
+                    TimeFormat::parse(value).map_err(|e| format!("line {}: {}", line_no, e))?,
+                ),
+                "levels" => {
+                    config.levels =
+                        Some(LevelStyle::parse(value).map_err(|e| format!("line {}: {}", line_no, e))?)
+                }
+                "level_colors" => {
+                    config.level_colors =
+                        Some(LevelColors::parse(value).map_err(|e| format!("line {}: {}", line_no, e))?)
+                }
+                _ => match KeyAction::from_config_key(key) {
+                    Some(action) => config.key_bindings.push((
+                        action,
+                        keys::parse_sequences(value).map_err(|e| format!("line {}: {}", line_no, e))?,
+                    )),
+                    None => return Err(format!("line {}: unknown key: {}", line_no, key)),
+                },
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Set `settings` in the config file, keeping its other lines and
+    /// comments, and refuse to write a file `load` would reject.
+    pub fn save(settings: &[(&str, String)]) -> Result<(), String> {
+        let path = Self::default_path().ok_or("cannot locate config directory")?;
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+        let updated = update_settings(&contents, settings);
+        Self::parse(&updated)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+        std::fs::write(&path, updated).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+/// `contents` with each of `settings` on the line of its key, the first
+/// one if the key is repeated; keys not there yet are appended.
+fn update_settings(contents: &str, settings: &[(&str, String)]) -> String {
+    let mut written = vec![false; settings.len()];
+    let mut lines = Vec::new();
+    for line in contents.lines() {
+        let key = match line.trim() {
+            trimmed if trimmed.starts_with('#') => None,
+            trimmed => trimmed.split_once('=').map(|(key, _)| key.trim()),
+        };
+        match key.and_then(|key| settings.iter().position(|(name, _)| *name == key)) {
+            Some(index) if written[index] => {}
+            Some(index) => {
+                written[index] = true;
+                lines.push(format!("{} = {}", settings[index].0, settings[index].1));
+            }
+            None => lines.push(line.to_string()),
+        }
+    }
+    for ((key, value), written) in settings.iter().zip(written) {
+        if !written {
+            lines.push(format!("{} = {}", key, value));
+        }
+    }
+    let mut updated = lines.join("\n");
+    updated.push('\n');
+    updated
+}
+
+fn parse_count(key: &str, value: &str, line_no: usize) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(0) | Err(_) => Err(format!(
+            "line {}: {} must be a positive integer, got: {}",
+            line_no, key, value
+        )),
+        Ok(n) => Ok(n),
+    }
+}
+
+fn parse_port(value: &str, line_no: usize) -> Result<u16, String> {
+    match value.parse::<u16>() {
+        Ok(0) | Err(_) => Err(format!(
+            "line {}: port must be from 1 to 65535, got: {}",
+            line_no, value
+        )),
+        Ok(port) => Ok(port),
+    }
+}
+
+fn parse_bool(key: &str, value: &str, line_no: usize) -> Result<bool, String> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(format!(
+            "line {}: {} must be true or false, got: {}",
+            line_no, key, value
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_empty() {
+        assert_eq!(Config::parse(""), Ok(Config::default()));
+        assert_eq!(Config::parse("# comment\n\n   \n"), Ok(Config::default()));
+    }
+
+    #[test]
+    fn test_parse_search_settings() {
+        let config = Config::parse(
+            "search_buffer_lines = 500\nsearch_chunk_size=20000\n  search_research_margin = 100  \n",
+        )
+        .unwrap();
+        assert_eq!(config.search_buffer_lines, Some(500));
+        assert_eq!(config.search_chunk_size, Some(20000));
+        assert_eq!(config.search_research_margin, Some(100));
+
+        let config = Config::parse("search_case = smart").unwrap();
+        assert_eq!(config.search_case, Some(CaseMode::Smart));
+        assert!(Config::parse("search_case = loud").is_err());
+
+        let config = Config::parse("search_wrap = true").unwrap();
+        assert_eq!(config.search_wrap, Some(true));
+        assert!(Config::parse("search_wrap = yes").is_err());
+        assert_eq!(Config::parse("logfmt = false").unwrap().logfmt, Some(false));
+
+        let config = Config::parse("command_timeout = 120").unwrap();
+        assert_eq!(config.command_timeout, Some(120));
+        assert!(Config::parse("command_timeout = 0").is_err());
+    }
+
+    #[test]
+    fn test_parse_server_and_tail_settings() {
+        let config =
+            Config::parse("port = 9900\nremote_chunk_size = 2000\nnotify_new_lines = false\n").unwrap();
+        assert_eq!(config.port, Some(9900));
+        assert_eq!(config.remote_chunk_size, Some(2000));
+        assert_eq!(config.notify_new_lines, Some(false));
+        assert_eq!(Config::parse("follow = true").unwrap().follow, Some(true));
+        assert_eq!(
+            Config::parse("relative_numbers = true").unwrap().relative_numbers,
+            Some(true)
+        );
+        assert!(Config::parse("port = 0").is_err());
+        assert!(Config::parse("port = 70000").is_err());
+    }
+
+    #[test]
+    fn test_update_settings() {
+        let contents = "# pog\ntheme = dark\nkey_top = gg\nfont_size=9\n# font_size = 12\nfont_size = 10\n";
+        let updated = update_settings(
+            contents,
+            &[("font_size", "14".to_string()), ("port", "9900".to_string())],
+        );
+        assert_eq!(
+            updated,
+            "# pog\ntheme = dark\nkey_top = gg\nfont_size = 14\n# font_size = 12\nport = 9900\n"
+        );
+        assert_eq!(update_settings("", &[("logfmt", "false".to_string())]), "logfmt = false\n");
+        assert_eq!(Config::parse(&updated).unwrap().font_size, Some(14));
+    }
+
+    #[test]
+    fn test_parse_appearance_settings() {
+        let config = Config::parse("font_family = Iosevka Term
+font_size = 9
+").unwrap();
+        assert_eq!(config.font_family, Some("Iosevka Term".to_string()));
+        assert_eq!(config.font_size, Some(9));
+        assert!(Config::parse("font_size = 1").is_err());
+        assert!(Config::parse("font_family = a;b").is_err());
+
+        let config = Config::parse("theme = dark").unwrap();
+        assert_eq!(config.theme, Some(ThemeChoice::Dark));
+        assert!(Config::parse("theme = blue").is_err());
+
+        let config = Config::parse("levels = word\nlevel_colors = error:#ff8080\n").unwrap();
+        assert_eq!(config.levels, Some(LevelStyle::Word));
+        assert_eq!(config.level_colors, Some(LevelColors::parse("error:#ff8080").unwrap()));
+        assert!(Config::parse("levels = bold").is_err());
+        assert!(Config::parse("level_colors = verbose:gray").is_err());
+    }
+
+    #[test]
+    fn test_parse_time_formats() {
+        let config = Config::parse("time_format = %d.%m.%Y %H:%M:%S\ntime_format = %s\n").unwrap();
+        let specs: Vec<&str> = config.time_formats.iter().map(|f| f.spec.as_str()).collect();
+        assert_eq!(specs, vec!["%d.%m.%Y %H:%M:%S", "%s"]);
+        assert!(Config::parse("time_format = %Y-%m-%d").is_err());
+    }
+
+    #[test]
+    fn test_parse_key_bindings() {
+        let config = Config::parse("key_top = gg, Ctrl+g\nkey_search =\n").unwrap();
+        assert_eq!(
+            config.key_bindings,
+            vec![
+                (KeyAction::Top, keys::parse_sequences("gg,Ctrl+g").unwrap()),
+                (KeyAction::Search, vec![]),
+            ]
+        );
+        assert!(Config::parse("key_top = g g").is_err());
+    }
+
+    #[test]
+    fn test_parse_column_settings() {
+        let config = Config::parse("column_guides = 20,40,80\nfixed_fields = 1-9:#ccddee\n").unwrap();
+        assert_eq!(config.column_guides, Some(vec![20, 40, 80]));
+        assert_eq!(config.fixed_fields.map(|f| f.len()), Some(1));
+        assert!(Config::parse("fixed_fields = 9-1:red").is_err());
+
+        let config = Config::parse("max_line_columns = 500").unwrap();
+        assert_eq!(config.max_line_columns, Some(500));
+        assert!(Config::parse("max_line_columns = 0").is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Config::parse("search_buffer_lines").is_err());
+        assert!(Config::parse("search_buffer_lines = abc").is_err());
+        assert!(Config::parse("search_chunk_size = 0").is_err());
+        assert_eq!(
+            Config::parse("# ok\nbogus = 1"),
+            Err("line 2: unknown key: bogus".to_string())
+        );
+    }
+}
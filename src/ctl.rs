@@ -0,0 +1,182 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+
+use crate::commands::{parse_command, PogCommand};
+
+/// How a response continues after its first line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Framing {
+    /// The first line is the whole response
+    Single,
+    /// `OK <n>` and `n` more lines (`get`, `context`, `tail`, `grep`, `status`,
+    /// `highlights`, `annotations`, `bookmark list`, `help`)
+    Lines,
+    /// `OK <n>` or `ERROR <n>` and `n` entries `<byte_length> <response>` (`commit`)
+    Entries,
+    /// `OK` and then events until the connection closes (`subscribe`)
+    Stream,
+}
+
+/// The framing of the response to `command`. Commands queued in a batch are
+/// only answered `OK queued`; a line that does not parse is answered with
+/// a one-line error.
+fn framing(command: &str, in_batch: bool) -> Framing {
+    match parse_command(command) {
+        Ok(PogCommand::Commit) => Framing::Entries,
+        Ok(PogCommand::Subscribe { .. }) if !in_batch => Framing::Stream,
+        Ok(
+            PogCommand::Get { .. }
+            | PogCommand::Context { .. }
+            | PogCommand::Tail { .. }
+            | PogCommand::Grep { .. }
+            | PogCommand::Status
+            | PogCommand::Highlights
+            | PogCommand::Annotations
+            | PogCommand::BookmarkList
+            | PogCommand::Help,
+        ) if !in_batch => Framing::Lines,
+        _ => Framing::Single,
+    }
+}
+
+/// Read one response framed as `framing` and return its text, every line
+/// ending in a newline. A stream is not read here.
+fn read_response(reader: &mut impl BufRead, framing: Framing) -> io::Result<String> {
+    let mut response = String::new();
+    if reader.read_line(&mut response)? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed by pog"));
+    }
+    let count = response
+        .trim_end()
+        .split_once(' ')
+        .and_then(|(_, rest)| rest.parse::<usize>().ok());
+    match (framing, count) {
+        (Framing::Lines, Some(count)) if response.starts_with("OK") => {
+            for _ in 0..count {
+                reader.read_line(&mut response)?;
+            }
+        }
+        (Framing::Entries, Some(count)) => {
+            for _ in 0..count {
+                let mut length = Vec::new();
+                reader.read_until(b' ', &mut length)?;
+                let text_length = String::from_utf8_lossy(&length)
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "bad batch entry"))?;
+                // The text and the line feed after it
+                let mut text = vec![0; text_length + 1];
+                reader.read_exact(&mut text)?;
+                response.push_str(&String::from_utf8_lossy(&length));
+                response.push_str(&String::from_utf8_lossy(&text));
+            }
+        }
+        _ => {}
+    }
+    Ok(response)
+}
+
+/// Send `command`, or each line of stdin when it is empty, to the pog
+/// listening on `port` and print the responses. Returns true if none was
+/// an error.
+pub fn run(port: u16, command: &[String]) -> bool {
+    let commands: Vec<String> = if command.is_empty() {
+        io::stdin().lock().lines().map_while(|line| line.ok()).collect()
+    } else {
+        vec![command.join(" ")]
+    };
+
+    let mut stream = match TcpStream::connect(("127.0.0.1", port)) {
+        Ok(stream) => stream,
+        Err(e) => {
+            eprintln!("Failed to connect to pog on port {}: {}", port, e);
+            return false;
+        }
+    };
+    let mut reader = match stream.try_clone() {
+        Ok(s) => BufReader::new(s),
+        Err(e) => {
+            eprintln!("Failed to connect to pog on port {}: {}", port, e);
+            return false;
+        }
+    };
+
+    let mut succeeded = true;
+    let mut in_batch = false;
+    let mut stdout = io::stdout().lock();
+    for command in commands.iter().map(|c| c.trim()).filter(|c| !c.is_empty()) {
+        let framing = framing(command, in_batch);
+        if let Err(e) = stream.write_all(format!("{}\n", command).as_bytes()) {
+            eprintln!("Failed to send command: {}", e);
+            return false;
+        }
+        let response = match read_response(&mut reader, framing) {
+            Ok(response) => response,
+            Err(e) => {
+                eprintln!("Failed to read response: {}", e);
+                return false;
+            }
+        };
+        let _ = stdout.write_all(response.as_bytes());
+        succeeded &= !response.starts_with("ERROR");
+
+        match parse_command(command) {
+            Ok(PogCommand::Begin) => in_batch = true,
+            Ok(PogCommand::Commit | PogCommand::Rollback) => in_batch = false,
+            _ => {}
+        }
+        if framing == Framing::Stream && response.starts_with("OK") {
+            // Events until pog closes the window or the user stops us
+            let _ = stdout.flush();
+            for event in reader.by_ref().lines().map_while(|line| line.ok()) {
+                let _ = writeln!(stdout, "{}", event);
+                let _ = stdout.flush();
+            }
+            break;
+        }
+    }
+    succeeded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_framing() {
+        assert_eq!(framing("get 1 5", false), Framing::Lines);
+        assert_eq!(framing("STATUS", false), Framing::Lines);
+        assert_eq!(framing("tail 20", false), Framing::Lines);
+        assert_eq!(framing("highlights", false), Framing::Lines);
+        assert_eq!(framing("get 1 5", true), Framing::Single);
+        assert_eq!(framing("commit", true), Framing::Entries);
+        assert_eq!(framing("subscribe marks", false), Framing::Stream);
+        assert_eq!(framing("lines", false), Framing::Single);
+        assert_eq!(framing("get nothing", false), Framing::Single);
+    }
+
+    #[test]
+    fn test_read_response() {
+        let mut input = Cursor::new("OK 2\n41 1 a\n42 0 \nOK 7\n");
+        assert_eq!(
+            read_response(&mut input, Framing::Lines).unwrap(),
+            "OK 2\n41 1 a\n42 0 \n"
+        );
+        assert_eq!(read_response(&mut input, Framing::Single).unwrap(), "OK 7\n");
+        assert!(read_response(&mut input, Framing::Single).is_err());
+
+        let mut input = Cursor::new("ERROR line out of range: requested 9, file has 3 lines\n");
+        assert!(read_response(&mut input, Framing::Lines).unwrap().starts_with("ERROR"));
+    }
+
+    #[test]
+    fn test_read_batch_entries() {
+        let mut input = Cursor::new("ERROR 2\n4 OK 1\n18 ERROR no such line\nOK\n");
+        assert_eq!(
+            read_response(&mut input, Framing::Entries).unwrap(),
+            "ERROR 2\n4 OK 1\n18 ERROR no such line\n"
+        );
+        assert_eq!(read_response(&mut input, Framing::Single).unwrap(), "OK\n");
+    }
+}
@@ -0,0 +1,298 @@
+use std::fmt;
+use std::net::TcpListener;
+use std::process::Command;
+
+use crate::config::Config;
+use crate::ssh::SshTarget;
+
+const REMOTE_TOOLS: &[&str] = &["tail", "head", "wc", "stat", "grep", "gzip"];
+/// Remote tools pog does without: chunks are fetched uncompressed
+const OPTIONAL_REMOTE_TOOLS: &[&str] = &["gzip"];
+const SSH_CONNECT_TIMEOUT_SECS: u32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CheckStatus {
+    Ok,
+    Warn,
+    Fail,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckStatus::Ok => write!(f, " OK "),
+            CheckStatus::Warn => write!(f, "WARN"),
+            CheckStatus::Fail => write!(f, "FAIL"),
+        }
+    }
+}
+
+pub struct CheckResult {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+    pub hint: Option<String>,
+}
+
+impl CheckResult {
+    fn ok(name: &str, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status: CheckStatus::Ok,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn problem(status: CheckStatus, name: &str, detail: impl Into<String>, hint: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            detail: detail.into(),
+            hint: Some(hint.to_string()),
+        }
+    }
+}
+
+/// The target `--host` names, `[user@]host[:port]`, reached the way remote
+/// files are: through `jump_host` and with the given ssh options.
+pub fn ssh_target(host: &str, jump_host: Option<String>, options: Vec<String>) -> SshTarget {
+    let (host, port) = match host.rsplit_once(':') {
+        Some((name, port)) if !name.is_empty() => match port.parse() {
+            Ok(port) => (name, Some(port)),
+            Err(_) => (host, None),
+        },
+        _ => (host, None),
+    };
+    SshTarget {
+        host: host.to_string(),
+        port,
+        jump_host,
+        options,
+    }
+}
+
+/// Runs all checks, prints a report and returns true if nothing failed.
+pub fn run(target: Option<&SshTarget>, port: u16) -> bool {
+    let mut results = vec![check_display(), check_config(), check_port(port)];
+
+    if let Some(target) = target {
+        let ssh = check_ssh(target);
+        let connected = ssh.status == CheckStatus::Ok;
+        results.push(ssh);
+        if connected {
+            results.push(check_remote_tools(target));
+            results.push(check_remote_stat(target));
+        }
+    }
+
+    for result in &results {
+        println!("[{}] {}: {}", result.status, result.name, result.detail);
+        if let Some(ref hint) = result.hint {
+            println!("       hint: {}", hint);
+        }
+    }
+
+    !results.iter().any(|r| r.status == CheckStatus::Fail)
+}
+
+fn check_display() -> CheckResult {
+    match gtk4::init() {
+        Ok(()) => CheckResult::ok("display", "GTK initialized"),
+        Err(e) => {
+            let has_display = std::env::var_os("DISPLAY").is_some()
+                || std::env::var_os("WAYLAND_DISPLAY").is_some();
+            let hint = if has_display {
+                "the display server refused the connection; check that it is running and that you may connect to it"
+            } else {
+                "neither DISPLAY nor WAYLAND_DISPLAY is set; run pog from a graphical session or use ssh -X"
+            };
+            CheckResult::problem(CheckStatus::Fail, "display", e.to_string(), hint)
+        }
+    }
+}
+
+fn check_config() -> CheckResult {
+    let path = match Config::default_path() {
+        Some(path) => path,
+        None => {
+            return CheckResult::problem(
+                CheckStatus::Warn,
+                "config",
+                "cannot locate config directory",
+                "set HOME or XDG_CONFIG_HOME",
+            )
+        }
+    };
+    if !path.exists() {
+        return CheckResult::ok("config", format!("{} not present, using defaults", path.display()));
+    }
+    match Config::load() {
+        Ok(_) => CheckResult::ok("config", format!("{} is valid", path.display())),
+        Err(e) => CheckResult::problem(
+            CheckStatus::Fail,
+            "config",
+            e,
+            "fix or remove the offending line; pog refuses to start with an invalid config",
+        ),
+    }
+}
+
+fn check_port(port: u16) -> CheckResult {
+    match TcpListener::bind(format!("127.0.0.1:{}", port)) {
+        Ok(_) => CheckResult::ok("port", format!("127.0.0.1:{} is available", port)),
+        Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => CheckResult::problem(
+            CheckStatus::Warn,
+            "port",
+            format!("127.0.0.1:{} is in use", port),
+            "pog will fall back to the next free port; pass --port to choose one explicitly",
+        ),
+        Err(e) => CheckResult::problem(
+            CheckStatus::Fail,
+            "port",
+            format!("cannot bind 127.0.0.1:{}: {}", port, e),
+            "pass --no-server to run without the command server",
+        ),
+    }
+}
+
+/// Run `remote_cmd` with the port, jump host and options the loader gives
+/// ssh, never prompting.
+fn ssh_command(target: &SshTarget, remote_cmd: &str) -> std::io::Result<std::process::Output> {
+    Command::new("ssh")
+        .arg("-o")
+        .arg("BatchMode=yes")
+        .arg("-o")
+        .arg(format!("ConnectTimeout={}", SSH_CONNECT_TIMEOUT_SECS))
+        .args(target.args())
+        .arg(&target.host)
+        .arg(remote_cmd)
+        .output()
+}
+
+fn check_ssh(target: &SshTarget) -> CheckResult {
+    let name = format!("ssh {}", target);
+    match ssh_command(target, "true") {
+        Ok(output) if output.status.success() => CheckResult::ok(&name, "connected"),
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            let hint = if stderr.contains("Permission denied") {
+                "pog needs non-interactive login; set up key-based auth (ssh-copy-id) or load your key into ssh-agent"
+            } else if stderr.contains("Could not resolve hostname") {
+                "check the host name or add an alias for it in ~/.ssh/config"
+            } else if stderr.contains("Host key verification failed") {
+                "connect once with plain ssh to accept the host key"
+            } else {
+                "run `ssh <host> true` manually to see the full error"
+            };
+            CheckResult::problem(CheckStatus::Fail, &name, stderr, hint)
+        }
+        Err(e) => CheckResult::problem(
+            CheckStatus::Fail,
+            &name,
+            format!("failed to run ssh: {}", e),
+            "install an OpenSSH client and make sure `ssh` is on PATH",
+        ),
+    }
+}
+
+fn check_remote_tools(target: &SshTarget) -> CheckResult {
+    let name = format!("remote tools on {}", target.host);
+    let script = format!(
+        "for t in {}; do command -v $t >/dev/null 2>&1 || echo $t; done",
+        REMOTE_TOOLS.join(" ")
+    );
+    match ssh_command(target, &script) {
+        Ok(output) if output.status.success() => {
+            let missing = parse_missing_tools(&String::from_utf8_lossy(&output.stdout));
+            if missing.is_empty() {
+                CheckResult::ok(&name, REMOTE_TOOLS.join(", "))
+            } else if missing.iter().all(|tool| OPTIONAL_REMOTE_TOOLS.contains(&tool.as_str())) {
+                CheckResult::problem(
+                    CheckStatus::Warn,
+                    &name,
+                    format!("missing: {}", missing.join(", ")),
+                    "remote chunks will be fetched uncompressed; install gzip to speed them up",
+                )
+            } else {
+                CheckResult::problem(
+                    CheckStatus::Fail,
+                    &name,
+                    format!("missing: {}", missing.join(", ")),
+                    "install coreutils, grep and gzip on the remote host",
+                )
+            }
+        }
+        Ok(output) => CheckResult::problem(
+            CheckStatus::Fail,
+            &name,
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            "the remote login shell must be POSIX-compatible",
+        ),
+        Err(e) => CheckResult::problem(
+            CheckStatus::Fail,
+            &name,
+            format!("failed to run ssh: {}", e),
+            "install an OpenSSH client and make sure `ssh` is on PATH",
+        ),
+    }
+}
+
+fn check_remote_stat(target: &SshTarget) -> CheckResult {
+    let name = format!("remote stat on {}", target.host);
+    match ssh_command(target, "stat -c%s /") {
+        Ok(output) if output.status.success() => CheckResult::ok(&name, "supports -c%s"),
+        Ok(_) => CheckResult::problem(
+            CheckStatus::Warn,
+            &name,
+            "`stat -c%s` is not supported",
+            "the remote stat is not GNU coreutils; file sizes will be unavailable",
+        ),
+        Err(e) => CheckResult::problem(
+            CheckStatus::Fail,
+            &name,
+            format!("failed to run ssh: {}", e),
+            "install an OpenSSH client and make sure `ssh` is on PATH",
+        ),
+    }
+}
+
+fn parse_missing_tools(stdout: &str) -> Vec<String> {
+    stdout
+        .lines()
+        .map(str::trim)
+        .filter(|l| REMOTE_TOOLS.contains(l))
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_missing_tools() {
+        assert!(parse_missing_tools("").is_empty());
+        assert_eq!(parse_missing_tools("stat\n"), vec!["stat".to_string()]);
+        assert_eq!(
+            parse_missing_tools("motd banner\nwc\nstat\n"),
+            vec!["wc".to_string(), "stat".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_ssh_target() {
+        let target = ssh_target("deploy@myserver:2222", Some("bastion".to_string()), vec![]);
+        assert_eq!(target.host, "deploy@myserver");
+        assert_eq!(target.port, Some(2222));
+        assert_eq!(target.jump_host.as_deref(), Some("bastion"));
+        assert_eq!(ssh_target("myserver", None, vec![]), SshTarget::new("myserver"));
+        assert_eq!(ssh_target("myserver:ssh", None, vec![]).host, "myserver:ssh");
+    }
+
+    #[test]
+    fn test_check_status_display() {
+        assert_eq!(format!("[{}]", CheckStatus::Ok), "[ OK ]");
+        assert_eq!(format!("[{}]", CheckStatus::Fail), "[FAIL]");
+    }
+}
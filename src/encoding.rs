@@ -0,0 +1,192 @@
+use encoding_rs::{Encoding, REPLACEMENT, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+/// Bytes sniffed from the start of a file to guess its encoding.
+pub const DETECT_SAMPLE_LEN: usize = 4096;
+
+/// Character encoding of a log file.
+///
+/// Lines are decoded lossily: invalid sequences become U+FFFD instead of
+/// hiding the whole line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextEncoding(&'static Encoding);
+
+impl Default for TextEncoding {
+    fn default() -> Self {
+        Self(UTF_8)
+    }
+}
+
+impl TextEncoding {
+    /// Look up an encoding by WHATWG label (`utf-8`, `latin1`, `utf-16le`,
+    /// `shift_jis`, ...). `latin-1` is accepted as an alias for `latin1`.
+    pub fn parse(label: &str) -> Result<Self, String> {
+        let label = label.trim();
+        let normalized = if label.eq_ignore_ascii_case("latin-1") {
+            "latin1"
+        } else {
+            label
+        };
+        match Encoding::for_label(normalized.as_bytes()) {
+            Some(encoding) if encoding != REPLACEMENT => Ok(Self(encoding)),
+            _ => Err(format!("unknown encoding: {}", label)),
+        }
+    }
+
+    /// Guess the encoding of a file from its first bytes: a byte order mark,
+    /// then the NUL pattern of BOM-less UTF-16, then UTF-8 validity. Anything
+    /// else is treated as Latin-1 (windows-1252), which decodes every byte.
+    pub fn detect(sample: &[u8]) -> Self {
+        if let Some((encoding, _)) = Encoding::for_bom(sample) {
+            return Self(encoding);
+        }
+
+        // ASCII text in UTF-16 has a NUL in every other byte
+        let pairs = sample.len() / 2;
+        if pairs > 0 {
+            let even_nuls = sample.iter().step_by(2).filter(|b| **b == 0).count();
+            let odd_nuls = sample.iter().skip(1).step_by(2).filter(|b| **b == 0).count();
+            if odd_nuls * 2 > pairs && even_nuls * 8 < pairs {
+                return Self(UTF_16LE);
+            }
+            if even_nuls * 2 > pairs && odd_nuls * 8 < pairs {
+                return Self(UTF_16BE);
+            }
+        }
+
+        match std::str::from_utf8(sample) {
+            Ok(_) => Self(UTF_8),
+            // The sample may end in the middle of a character
+            Err(e) if e.error_len().is_none() => Self(UTF_8),
+            Err(_) => Self(WINDOWS_1252),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.0.name()
+    }
+
+    pub fn is_utf8(&self) -> bool {
+        self.0 == UTF_8
+    }
+
+    pub fn is_utf16(&self) -> bool {
+        self.0 == UTF_16LE || self.0 == UTF_16BE
+    }
+
+    /// Encoded form of `\n`, which is also the width of one code unit.
+    pub fn newline(&self) -> &'static [u8] {
+        if self.0 == UTF_16LE {
+            b"\n\0"
+        } else if self.0 == UTF_16BE {
+            b"\0\n"
+        } else {
+            b"\n"
+        }
+    }
+
+    /// Raw bytes of a line without its `\n` / `\r\n` terminator.
+    pub fn strip_terminator<'a>(&self, bytes: &'a [u8]) -> &'a [u8] {
+        let newline = self.newline();
+        let bytes = bytes.strip_suffix(newline).unwrap_or(bytes);
+        let carriage_return: &[u8] = match newline {
+            b"\n\0" => b"\r\0",
+            b"\0\n" => b"\0\r",
+            _ => b"\r",
+        };
+        bytes.strip_suffix(carriage_return).unwrap_or(bytes)
+    }
+
+    /// Decode one line, dropping its `\n` / `\r\n` terminator.
+    pub fn decode_line(&self, bytes: &[u8]) -> String {
+        let (text, _) = self.0.decode_with_bom_removal(bytes);
+        let text = text.strip_suffix('\n').unwrap_or(&text);
+        let text = text.strip_suffix('\r').unwrap_or(text);
+        text.to_string()
+    }
+
+    /// Split newline-terminated output into decoded lines, like `str::lines`.
+    pub fn decode_lines(&self, bytes: &[u8]) -> Vec<String> {
+        let newline = self.newline();
+        let bytes = bytes.strip_suffix(newline).unwrap_or(bytes);
+        if bytes.is_empty() {
+            return Vec::new();
+        }
+        if newline.len() == 1 {
+            return bytes.split(|b| *b == b'\n').map(|line| self.decode_line(line)).collect();
+        }
+
+        // Split on aligned code units, as the line index does: a `\n` byte
+        // can also be half of another UTF-16 character
+        let mut lines = Vec::new();
+        let mut start = 0;
+        for (i, unit) in bytes.chunks_exact(newline.len()).enumerate() {
+            if unit == newline {
+                lines.push(self.decode_line(&bytes[start..i * newline.len()]));
+                start = (i + 1) * newline.len();
+            }
+        }
+        lines.push(self.decode_line(&bytes[start..]));
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_labels() {
+        assert_eq!(TextEncoding::parse("UTF-8").unwrap(), TextEncoding::default());
+        assert_eq!(TextEncoding::parse("latin-1").unwrap().name(), "windows-1252");
+        assert_eq!(TextEncoding::parse("utf-16").unwrap().name(), "UTF-16LE");
+        assert_eq!(TextEncoding::parse("shift_jis").unwrap().name(), "Shift_JIS");
+        assert!(TextEncoding::parse("klingon").is_err());
+        // Labels that only map to the replacement decoder are useless for viewing
+        assert!(TextEncoding::parse("iso-2022-kr").is_err());
+    }
+
+    #[test]
+    fn test_detect() {
+        assert!(TextEncoding::detect(b"plain ascii\n").is_utf8());
+        assert!(TextEncoding::detect("caf\u{e9}\n".as_bytes()).is_utf8());
+        // Sample cut inside a two-byte character
+        assert!(TextEncoding::detect(&"caf\u{e9}".as_bytes()[..4]).is_utf8());
+        assert_eq!(TextEncoding::detect(b"caf\xe9\n").name(), "windows-1252");
+        assert_eq!(TextEncoding::detect(b"\xef\xbb\xbfbom").name(), "UTF-8");
+        assert_eq!(TextEncoding::detect(b"\xff\xfea\0").name(), "UTF-16LE");
+        assert_eq!(TextEncoding::detect(b"l\0o\0g\0\n\0").name(), "UTF-16LE");
+        assert_eq!(TextEncoding::detect(b"\0l\0o\0g\0\n").name(), "UTF-16BE");
+    }
+
+    #[test]
+    fn test_decode_line() {
+        let utf8 = TextEncoding::default();
+        assert_eq!(utf8.decode_line(b"ok\r\n"), "ok");
+        assert_eq!(utf8.decode_line(b"bad \xff byte\n"), "bad \u{fffd} byte");
+
+        let latin1 = TextEncoding::parse("latin1").unwrap();
+        assert_eq!(latin1.decode_line(b"caf\xe9"), "caf\u{e9}");
+
+        let utf16 = TextEncoding::parse("utf-16le").unwrap();
+        assert_eq!(utf16.decode_line(b"\xff\xfeh\0i\0\r\0\n\0"), "hi");
+        assert_eq!(utf16.strip_terminator(b"h\0i\0\r\0\n\0"), b"h\0i\0");
+        assert_eq!(utf8.strip_terminator(b"\0\x01\r\n"), b"\0\x01");
+    }
+
+    #[test]
+    fn test_decode_lines() {
+        let latin1 = TextEncoding::parse("latin1").unwrap();
+        assert_eq!(latin1.decode_lines(b"a\xe9\nb\r\n"), vec!["a\u{e9}", "b"]);
+        assert_eq!(latin1.decode_lines(b"a\n\nb"), vec!["a", "", "b"]);
+        assert!(latin1.decode_lines(b"").is_empty());
+
+        // U+010A is 0A 01 in UTF-16LE
+        let utf16 = TextEncoding::parse("utf-16le").unwrap();
+        assert_eq!(
+            utf16.decode_lines(b"a\0\x0a\x01\r\0\n\0\n\0b\0\n\0"),
+            vec!["a\u{10a}", "", "b"]
+        );
+        let utf16be = TextEncoding::parse("utf-16be").unwrap();
+        assert_eq!(utf16be.decode_lines(b"\0a\0\n\0b"), vec!["a", "b"]);
+    }
+}
@@ -5,11 +5,17 @@ use std::io;
 pub enum PogError {
     Io(io::Error),
     Ssh { host: String, message: String },
+    S3 { url: String, message: String },
     Utf8(std::string::FromUtf8Error),
     #[allow(dead_code)]
     ConnectionFailed { host: String },
     FileNotFound { path: String },
     PermissionDenied { path: String },
+    Regex(regex::Error),
+    /// A search gave up because a newer one replaced it
+    Cancelled,
+    /// The source cannot do what was asked of it
+    Unsupported(&'static str),
 }
 
 impl std::error::Error for PogError {
@@ -17,6 +23,7 @@ impl std::error::Error for PogError {
         match self {
             PogError::Io(e) => Some(e),
             PogError::Utf8(e) => Some(e),
+            PogError::Regex(e) => Some(e),
             _ => None,
         }
     }
@@ -29,12 +36,16 @@ impl fmt::Display for PogError {
             PogError::Ssh { host, message } => {
                 write!(f, "SSH error connecting to {}: {}", host, message)
             }
+            PogError::S3 { url, message } => write!(f, "S3 error reading {}: {}", url, message),
             PogError::Utf8(e) => write!(f, "UTF-8 error: {}", e),
             PogError::ConnectionFailed { host } => {
                 write!(f, "Failed to connect to {}", host)
             }
             PogError::FileNotFound { path } => write!(f, "File not found: {}", path),
             PogError::PermissionDenied { path } => write!(f, "Permission denied: {}", path),
+            PogError::Regex(e) => write!(f, "invalid regex: {}", e),
+            PogError::Cancelled => write!(f, "search cancelled"),
+            PogError::Unsupported(message) => write!(f, "{}", message),
         }
     }
 }
@@ -51,4 +62,10 @@ impl From<std::string::FromUtf8Error> for PogError {
     }
 }
 
+impl From<regex::Error> for PogError {
+    fn from(err: regex::Error) -> Self {
+        PogError::Regex(err)
+    }
+}
+
 pub type Result<T> = std::result::Result<T, PogError>;
@@ -1,98 +1,818 @@
 use memmap2::Mmap;
 use std::fs::File;
 use std::io;
-use std::path::Path;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
 
+use regex::Regex;
+
+use crate::encoding::{TextEncoding, DETECT_SAMPLE_LEN};
 use crate::error::Result;
-use crate::file_source::FileSource;
+use crate::file_source::{FileSource, SourceChange};
+use crate::line_index::SparseLineIndex;
+use crate::search::{self, SearchMatch};
+
+/// Bytes indexed per step; the first step runs on open so the first page
+/// shows immediately, the rest in a background thread.
+const INDEX_STEP: usize = 8 << 20;
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
 
+/// A memory-mapped local file.
+///
+/// The line index is built incrementally in the background. Until it is
+/// complete, `line_count()` extrapolates from the indexed part and lines past
+/// it read as missing.
 pub struct MappedFile {
-    mmap: Mmap,
-    line_offsets: Vec<usize>,
+    path: PathBuf,
     path_display: String,
+    mapping: Arc<RwLock<Mapping>>,
+    /// `candidate_regex` of the last pattern searched, keyed by its source
+    candidates: Mutex<Option<(String, Option<regex::bytes::Regex>)>>,
 }
 
-impl MappedFile {
-    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
-        let path_display = path.as_ref().display().to_string();
-        let file = File::open(&path)?;
+struct Mapping {
+    file: File,
+    mmap: Arc<Mmap>,
+    encoding: TextEncoding,
+    index: SparseLineIndex,
+    /// Bytes of `mmap` covered by `index`
+    indexed_len: usize,
+    /// Bumped whenever the index restarts, so stale indexer threads stop
+    generation: u64,
+    /// The background indexer added lines not yet reported by `refresh`
+    index_progressed: bool,
+    /// Remapped after a read found the file shorter; not yet reported by `refresh`
+    truncation_pending: bool,
+}
+
+impl Mapping {
+    /// Map `path`, detecting its encoding from the first bytes if none is given,
+    /// and index its first `INDEX_STEP` bytes.
+    fn open(path: &Path, encoding: Option<TextEncoding>) -> io::Result<Self> {
+        let file = File::open(path)?;
         let mmap = unsafe { Mmap::map(&file)? };
 
-        let mut loader = Self {
-            mmap,
-            line_offsets: vec![0],
-            path_display,
+        let encoding = encoding
+            .unwrap_or_else(|| TextEncoding::detect(&mmap[..mmap.len().min(DETECT_SAMPLE_LEN)]));
+
+        let mut mapping = Self {
+            file,
+            mmap: Arc::new(mmap),
+            encoding,
+            index: SparseLineIndex::default(),
+            indexed_len: 0,
+            generation: 0,
+            index_progressed: false,
+            truncation_pending: false,
         };
+        mapping.index_to(INDEX_STEP.min(mapping.mmap.len()));
+        Ok(mapping)
+    }
 
-        loader.build_line_index();
-        Ok(loader)
+    /// Index `mmap[indexed_len..end]` in place.
+    fn index_to(&mut self, end: usize) {
+        self.index.extend(&self.mmap[..end], self.indexed_len, self.encoding);
+        self.indexed_len = end;
     }
 
-    fn build_line_index(&mut self) {
-        let data = &self.mmap[..];
+    fn is_indexed(&self) -> bool {
+        self.indexed_len >= self.mmap.len()
+    }
 
-        for (i, &byte) in data.iter().enumerate() {
-            if byte == b'\n' {
-                let next_line_start = i + 1;
-                if next_line_start < data.len() {
-                    self.line_offsets.push(next_line_start);
-                }
-            }
+    /// Lines that can be read: while indexing, the last indexed line may
+    /// continue past the indexed bytes.
+    fn available_lines(&self) -> usize {
+        if self.is_indexed() {
+            self.index.line_count()
+        } else {
+            self.index.line_count() - 1
         }
     }
 
-    fn get_line_internal(&self, line_num: usize) -> Option<&str> {
-        if line_num >= self.line_offsets.len() {
-            return None;
+    /// Exact line count once indexed, else extrapolated from the indexed bytes.
+    fn estimated_line_count(&self) -> usize {
+        if self.is_indexed() || self.indexed_len == 0 {
+            return self.index.line_count();
         }
+        let estimate =
+            self.index.line_count() as u128 * self.mmap.len() as u128 / self.indexed_len as u128;
+        estimate as usize
+    }
+
+    /// Touching mapped pages past the end of a truncated file raises SIGBUS.
+    fn is_truncated(&self) -> bool {
+        self.file
+            .metadata()
+            .map(|m| m.len() < self.mmap.len() as u64)
+            .unwrap_or(false)
+    }
+
+    /// Map the (same) file again at its current length and restart the index.
+    fn remap(&mut self) -> io::Result<()> {
+        self.mmap = Arc::new(unsafe { Mmap::map(&self.file)? });
+        self.index = SparseLineIndex::default();
+        self.indexed_len = 0;
+        self.generation += 1;
+        self.index_progressed = false;
+        self.index_to(INDEX_STEP.min(self.mmap.len()));
+        Ok(())
+    }
+
+    /// Bytes of up to `count` readable lines from `start_line`, with terminators.
+    fn lines(&self, start_line: usize, count: usize) -> impl Iterator<Item = (usize, &[u8])> {
+        let count = count.min(self.available_lines().saturating_sub(start_line));
+        self.index
+            .lines(&self.mmap, start_line, self.encoding)
+            .take(count)
+    }
 
-        let start = self.line_offsets[line_num];
-        let end = if line_num + 1 < self.line_offsets.len() {
-            self.line_offsets[line_num + 1]
+    fn get_line_internal(&self, line_num: usize) -> Option<String> {
+        self.lines(line_num, 1)
+            .next()
+            .map(|(_, bytes)| self.encoding.decode_line(bytes))
+    }
+
+    /// Byte offset where line `line_num` starts, or the end of the mapping
+    /// for lines past the index.
+    fn line_offset(&self, line_num: usize) -> usize {
+        if line_num < self.index.line_count() {
+            self.index.line_start(&self.mmap, line_num, self.encoding)
         } else {
             self.mmap.len()
-        };
+        }
+    }
+
+    /// Matches of `pattern` in lines `start_line..end_line` of a UTF-8 file.
+    /// `candidates` runs over the mapped bytes of the whole range, and only
+    /// the lines it hits are decoded and searched with `pattern`, like
+    /// grep does instead of building a string per line.
+    fn search_mapped(
+        &self,
+        pattern: &Regex,
+        candidates: &regex::bytes::Regex,
+        start_line: usize,
+        end_line: usize,
+    ) -> Vec<SearchMatch> {
+        let end_line = end_line.min(self.available_lines());
+        if start_line >= end_line {
+            return Vec::new();
+        }
+        let mut start = self.line_offset(start_line);
+        // Lines are decoded without the BOM, so it must not be searched
+        if start_line == 0 && self.mmap.starts_with(UTF8_BOM) {
+            start = UTF8_BOM.len();
+        }
+        let data = &self.mmap[start..self.line_offset(end_line)];
+
+        let mut matches = Vec::new();
+        let mut line = start_line;
+        let mut line_start = 0;
+        while let Some(candidate) = candidates.find_at(data, line_start) {
+            let skipped = &data[line_start..candidate.start()];
+            if let Some(last_newline) = skipped.iter().rposition(|&b| b == b'\n') {
+                line += skipped.iter().filter(|&&b| b == b'\n').count();
+                line_start += last_newline + 1;
+            }
+            // An empty match at the very end starts the line after the range
+            if line >= end_line {
+                break;
+            }
+            let line_end = data[candidate.start()..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(data.len(), |i| candidate.start() + i + 1);
+            let text = self.encoding.decode_line(&data[line_start..line_end]);
+            matches.extend(search::search_lines(pattern, &[(line, text)]));
+            if line_end >= data.len() {
+                break;
+            }
+            line += 1;
+            line_start = line_end;
+        }
+        matches
+    }
+}
+
+/// A regex over the bytes of many lines at once that hits at least every
+/// line `pattern` matches on: `^` and `$` also match at line breaks (CRLF
+/// included). It may hit more lines, e.g. with a match running across a line
+/// break, which the line-by-line search then rules out. `None` for patterns
+/// it could miss lines of.
+fn candidate_regex(pattern: &Regex) -> Option<regex::bytes::Regex> {
+    let source = pattern.as_str();
+    // Text anchors, or multi-line mode turned off, only match at the ends of
+    // the whole range
+    if source.contains(r"\A") || source.contains(r"\z") || source.contains("-m") {
+        return None;
+    }
+    regex::bytes::RegexBuilder::new(source)
+        .multi_line(true)
+        .crlf(true)
+        .build()
+        .ok()
+}
+
+/// Index the next `INDEX_STEP` bytes of `mapping` without holding its lock
+/// while scanning. Returns false once the caller should stop: the index is
+/// complete, the file was truncated (a reader will remap), or the index was
+/// restarted since `generation`.
+fn index_step(mapping: &RwLock<Mapping>, generation: Option<u64>) -> bool {
+    let (mmap, start, encoding, current_generation) = {
+        let mapping = mapping.read().unwrap();
+        if generation.is_some_and(|g| g != mapping.generation)
+            || mapping.is_indexed()
+            || mapping.is_truncated()
+        {
+            return false;
+        }
+        (
+            mapping.mmap.clone(),
+            mapping.indexed_len,
+            mapping.encoding,
+            mapping.generation,
+        )
+    };
+
+    let end = (start + INDEX_STEP).min(mmap.len());
+    let mut offsets = Vec::new();
+    extend_line_index(&mmap[..end], &mut offsets, start, encoding);
+
+    // Another thread (refresh, `wait_indexed`) may have indexed this step already
+    let mut mapping = mapping.write().unwrap();
+    if mapping.generation == current_generation && mapping.indexed_len == start {
+        for offset in offsets {
+            mapping.index.push(offset);
+        }
+        mapping.indexed_len = end;
+        mapping.index_progressed = true;
+    }
+    true
+}
 
-        let line_bytes = &self.mmap[start..end];
-        let line_bytes = if line_bytes.ends_with(b"\n") {
-            &line_bytes[..line_bytes.len() - 1]
+impl MappedFile {
+    pub fn open<P: AsRef<Path>>(path: P, encoding: Option<TextEncoding>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mapping = Mapping::open(&path, encoding)?;
+        let path_display = if mapping.encoding.is_utf8() {
+            path.display().to_string()
         } else {
-            line_bytes
+            format!("{} ({})", path.display(), mapping.encoding.name())
         };
-        let line_bytes = if line_bytes.ends_with(b"\r") {
-            &line_bytes[..line_bytes.len() - 1]
-        } else {
-            line_bytes
+
+        let file = Self {
+            path,
+            path_display,
+            mapping: Arc::new(RwLock::new(mapping)),
+            candidates: Mutex::new(None),
         };
+        file.start_indexing()?;
+        Ok(file)
+    }
+
+    /// Map the file at the path again and index it from the start.
+    fn reopen(&self) -> io::Result<()> {
+        {
+            let mut mapping = self.mapping.write().unwrap();
+            let generation = mapping.generation + 1;
+            *mapping = Mapping::open(&self.path, Some(mapping.encoding))?;
+            mapping.generation = generation;
+        }
+        self.start_indexing()
+    }
+
+    /// Index the rest of the file in a background thread, which stops when
+    /// the file is dropped or the index restarts.
+    fn start_indexing(&self) -> io::Result<()> {
+        let generation = {
+            let mapping = self.mapping.read().unwrap();
+            if mapping.is_indexed() {
+                return Ok(());
+            }
+            mapping.generation
+        };
+
+        let mapping = Arc::downgrade(&self.mapping);
+        std::thread::Builder::new()
+            .name("pog-indexer".to_string())
+            .spawn(move || {
+                while let Some(mapping) = mapping.upgrade() {
+                    if !index_step(&mapping, Some(generation)) {
+                        break;
+                    }
+                }
+            })?;
+        Ok(())
+    }
+
+    /// Lock the mapping for reading, remapping first if the file was truncated
+    /// since the last refresh. This narrows, but cannot fully close, the window
+    /// in which a concurrent truncation makes reads fault.
+    fn readable_mapping(&self) -> io::Result<RwLockReadGuard<'_, Mapping>> {
+        {
+            let mapping = self.mapping.read().unwrap();
+            if !mapping.is_truncated() {
+                return Ok(mapping);
+            }
+        }
+
+        let mut mapping = self.mapping.write().unwrap();
+        if mapping.is_truncated() {
+            mapping.remap()?;
+            mapping.truncation_pending = true;
+            drop(mapping);
+            self.start_indexing()?;
+        } else {
+            drop(mapping);
+        }
+        Ok(self.mapping.read().unwrap())
+    }
+
+    /// `candidate_regex` for `pattern`, compiled once while the same pattern
+    /// is searched chunk after chunk.
+    fn candidates_for(&self, pattern: &Regex) -> Option<regex::bytes::Regex> {
+        let mut cached = self.candidates.lock().unwrap();
+        match &*cached {
+            Some((source, candidates)) if source == pattern.as_str() => candidates.clone(),
+            _ => {
+                let candidates = candidate_regex(pattern);
+                *cached = Some((pattern.as_str().to_string(), candidates.clone()));
+                candidates
+            }
+        }
+    }
+}
+
+/// Number of lines in a file of `len` bytes holding `newlines` newlines.
+///
+/// This is the rule every loader follows: each newline ends a line, a final
+/// line without one still counts, and an empty file has one empty line.
+/// `wc -l` alone misses the unterminated final line.
+pub fn line_count_for(newlines: usize, len: u64, ends_with_newline: bool) -> usize {
+    if len == 0 || !ends_with_newline {
+        newlines + 1
+    } else {
+        newlines
+    }
+}
+
+/// Byte offset of the start of every line in `data`.
+pub fn build_line_index(data: &[u8], encoding: TextEncoding) -> Vec<usize> {
+    let mut line_offsets = vec![0];
+    extend_line_index(data, &mut line_offsets, 0, encoding);
+    line_offsets
+}
+
+/// Add the lines of `data[indexed_len..]` to an index built for `data[..indexed_len]`.
+pub fn extend_line_index(
+    data: &[u8],
+    line_offsets: &mut Vec<usize>,
+    indexed_len: usize,
+    encoding: TextEncoding,
+) {
+    for_each_line_start(data, indexed_len, encoding, |offset| line_offsets.push(offset));
+}
+
+/// Call `f` with the start of every line of `data[indexed_len..]` that
+/// `data[..indexed_len]` did not have yet, in order.
+pub fn for_each_line_start(
+    data: &[u8],
+    indexed_len: usize,
+    encoding: TextEncoding,
+    mut f: impl FnMut(usize),
+) {
+    let newline = encoding.newline();
+    let unit = newline.len();
+
+    // A newline at the old end had no following line yet
+    if indexed_len >= unit
+        && indexed_len < data.len()
+        && indexed_len.is_multiple_of(unit)
+        && &data[indexed_len - unit..indexed_len] == newline
+    {
+        f(indexed_len);
+    }
+
+    if unit == 1 {
+        for (i, &byte) in data.iter().enumerate().skip(indexed_len) {
+            if byte == b'\n' {
+                let next_line_start = i + 1;
+                if next_line_start < data.len() {
+                    f(next_line_start);
+                }
+            }
+        }
+        return;
+    }
+
+    // Wide encodings: only code-unit aligned newlines count
+    let mut i = indexed_len - indexed_len % unit;
+    while i + unit <= data.len() {
+        if &data[i..i + unit] == newline {
+            let next_line_start = i + unit;
+            if next_line_start < data.len() {
+                f(next_line_start);
+            }
+        }
+        i += unit;
+    }
+}
 
-        std::str::from_utf8(line_bytes).ok()
+/// Bytes of line `line_num`, including its terminator, given an index from `build_line_index`.
+pub fn line_bytes_at<'a>(data: &'a [u8], line_offsets: &[usize], line_num: usize) -> Option<&'a [u8]> {
+    if line_num >= line_offsets.len() {
+        return None;
     }
+
+    let start = line_offsets[line_num];
+    let end = if line_num + 1 < line_offsets.len() {
+        line_offsets[line_num + 1]
+    } else {
+        data.len()
+    };
+
+    Some(&data[start..end])
+}
+
+/// Decoded text of line `line_num` (without its line terminator) given an index
+/// from `build_line_index`. Invalid byte sequences are replaced, not skipped.
+pub fn line_at(
+    data: &[u8],
+    line_offsets: &[usize],
+    line_num: usize,
+    encoding: TextEncoding,
+) -> Option<String> {
+    line_bytes_at(data, line_offsets, line_num).map(|bytes| encoding.decode_line(bytes))
 }
 
 impl FileSource for MappedFile {
     fn line_count(&self) -> usize {
-        self.line_offsets.len()
+        self.mapping.read().unwrap().estimated_line_count()
     }
 
     fn file_size(&self) -> Result<u64> {
-        Ok(self.mmap.len() as u64)
+        Ok(self.mapping.read().unwrap().mmap.len() as u64)
     }
 
     fn get_line(&self, line_num: usize) -> Result<Option<String>> {
-        Ok(self.get_line_internal(line_num).map(|s| s.to_string()))
+        let mapping = self.readable_mapping()?;
+        Ok(mapping.get_line_internal(line_num))
     }
 
     fn get_lines(&self, start_line: usize, count: usize) -> Result<Vec<(usize, String)>> {
-        let mut lines = Vec::with_capacity(count);
-        for i in start_line..(start_line + count).min(self.line_count()) {
-            if let Some(line) = self.get_line_internal(i) {
-                lines.push((i, line.to_string()));
-            }
-        }
-        Ok(lines)
+        let mapping = self.readable_mapping()?;
+        Ok(mapping
+            .lines(start_line, count)
+            .map(|(i, bytes)| (i, mapping.encoding.decode_line(bytes)))
+            .collect())
+    }
+
+    fn get_raw_lines(&self, start_line: usize, count: usize) -> Result<Vec<(usize, Vec<u8>)>> {
+        let mapping = self.readable_mapping()?;
+        Ok(mapping
+            .lines(start_line, count)
+            .map(|(i, bytes)| (i, mapping.encoding.strip_terminator(bytes).to_vec()))
+            .collect())
     }
 
     fn display_name(&self) -> &str {
         &self.path_display
     }
+
+    /// Searches the mapped bytes of UTF-8 files directly, decoding only the
+    /// lines that may match.
+    fn search_range(
+        &self,
+        pattern: &Regex,
+        start_line: usize,
+        end_line: usize,
+    ) -> Result<Vec<SearchMatch>> {
+        let mapping = self.readable_mapping()?;
+        match self.candidates_for(pattern) {
+            Some(candidates) if mapping.encoding.is_utf8() => {
+                Ok(mapping.search_mapped(pattern, &candidates, start_line, end_line))
+            }
+            _ => {
+                let lines: Vec<(usize, String)> = mapping
+                    .lines(start_line, end_line.saturating_sub(start_line))
+                    .map(|(i, bytes)| (i, mapping.encoding.decode_line(bytes)))
+                    .collect();
+                Ok(search::search_lines(pattern, &lines))
+            }
+        }
+    }
+
+    /// Past the indexed bytes the line is extrapolated like the line count.
+    fn line_at_offset(&self, offset: u64) -> Option<usize> {
+        let mapping = self.readable_mapping().ok()?;
+        let offset = offset as usize;
+        if offset < mapping.indexed_len || mapping.is_indexed() {
+            return Some(mapping.index.line_at(&mapping.mmap, offset, mapping.encoding));
+        }
+        let estimate = offset as u128 * mapping.estimated_line_count() as u128
+            / mapping.mmap.len().max(1) as u128;
+        Some((estimate as usize).min(mapping.estimated_line_count().saturating_sub(1)))
+    }
+
+    fn indexing_progress(&self) -> Option<f64> {
+        let mapping = self.mapping.read().unwrap();
+        if mapping.is_indexed() {
+            None
+        } else {
+            Some(mapping.indexed_len as f64 / mapping.mmap.len() as f64)
+        }
+    }
+
+    fn wait_indexed(&self) {
+        while index_step(&self.mapping, None) {}
+    }
+
+    fn refresh(&self) -> Result<SourceChange> {
+        let on_disk = match std::fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            // Rotated away and not recreated yet; keep showing what we have
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(SourceChange::Unchanged),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut mapping = self.mapping.write().unwrap();
+        let mapped = mapping.file.metadata()?;
+        if (on_disk.dev(), on_disk.ino()) != (mapped.dev(), mapped.ino()) {
+            drop(mapping);
+            self.reopen()?;
+            return Ok(SourceChange::Replaced);
+        }
+
+        let mapped_len = mapping.mmap.len();
+        let new_len = mapped.len() as usize;
+        if new_len < mapped_len || mapping.truncation_pending {
+            mapping.remap()?;
+            mapping.truncation_pending = false;
+            drop(mapping);
+            self.start_indexing()?;
+            return Ok(SourceChange::Truncated);
+        }
+
+        let indexed = mapping.is_indexed();
+        let progressed = std::mem::take(&mut mapping.index_progressed);
+        if new_len > mapped_len {
+            mapping.mmap = Arc::new(unsafe { Mmap::map(&mapping.file)? });
+            if indexed {
+                mapping.index_to(new_len);
+                return Ok(SourceChange::Grew);
+            }
+            // The background indexer picks up the new length
+            return Ok(SourceChange::Indexed);
+        }
+
+        if progressed {
+            Ok(SourceChange::Indexed)
+        } else {
+            Ok(SourceChange::Unchanged)
+        }
+    }
+
+    fn reload(&self) -> Result<SourceChange> {
+        self.reopen()?;
+        Ok(SourceChange::Reloaded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_extend_line_index_matches_full_build() {
+        let utf8 = TextEncoding::default();
+        let data = b"one\ntwo\nthree\npartial";
+        for split in 0..=data.len() {
+            let mut offsets = build_line_index(&data[..split], utf8);
+            extend_line_index(data, &mut offsets, split, utf8);
+            assert_eq!(offsets, build_line_index(data, utf8), "split at {}", split);
+        }
+
+        let utf16 = TextEncoding::parse("utf-16le").unwrap();
+        let data = b"a\0\n\0\n\x0a\n\0b\0";
+        assert_eq!(build_line_index(data, utf16), vec![0, 4, 8]);
+        for split in 0..=data.len() {
+            let mut offsets = build_line_index(&data[..split], utf16);
+            extend_line_index(data, &mut offsets, split, utf16);
+            assert_eq!(offsets, build_line_index(data, utf16), "split at {}", split);
+        }
+    }
+
+    #[test]
+    fn test_line_count_for_matches_index() {
+        let utf8 = TextEncoding::default();
+        for data in [&b""[..], b"\n", b"\n\n", b"a", b"a\n", b"a\nb", b"a\nb\n", b"a\n\nb"] {
+            let newlines = data.iter().filter(|b| **b == b'\n').count();
+            let count = line_count_for(newlines, data.len() as u64, data.ends_with(b"\n"));
+            assert_eq!(count, build_line_index(data, utf8).len(), "{:?}", data);
+        }
+    }
+
+    #[test]
+    fn test_last_line_without_newline() {
+        let path = std::env::temp_dir().join(format!("pog-no-newline-{}", std::process::id()));
+        std::fs::write(&path, b"first\nlast").unwrap();
+        let file = MappedFile::open(&path, None).unwrap();
+        assert_eq!(file.line_count(), 2);
+        assert_eq!(file.get_line(1).unwrap(), Some("last".to_string()));
+        assert_eq!(
+            file.get_lines(0, 10).unwrap(),
+            vec![(0, "first".to_string()), (1, "last".to_string())]
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_utf8_lines_are_kept() {
+        let data = b"good\nbad \xff\r\nlast";
+        let offsets = build_line_index(data, TextEncoding::default());
+        assert_eq!(offsets.len(), 3);
+        let line = line_at(data, &offsets, 1, TextEncoding::default());
+        assert_eq!(line, Some("bad \u{fffd}".to_string()));
+
+        let path = std::env::temp_dir().join(format!("pog-invalid-{}", std::process::id()));
+        std::fs::write(&path, data).unwrap();
+        let file = MappedFile::open(&path, None).unwrap();
+        let raw = file.get_raw_lines(1, 5).unwrap();
+        assert_eq!(raw, vec![(1, b"bad \xff".to_vec()), (2, b"last".to_vec())]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_refresh_detects_changes() {
+        let path = std::env::temp_dir().join(format!("pog-refresh-{}", std::process::id()));
+        std::fs::write(&path, b"a\nb\n").unwrap();
+        let file = MappedFile::open(&path, None).unwrap();
+        assert_eq!(file.line_count(), 2);
+        assert_eq!(file.refresh().unwrap(), SourceChange::Unchanged);
+
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"c\nd")
+            .unwrap();
+        assert_eq!(file.refresh().unwrap(), SourceChange::Grew);
+        assert_eq!(file.line_count(), 4);
+        assert_eq!(file.get_line(3).unwrap(), Some("d".to_string()));
+
+        File::create(&path).unwrap().write_all(b"x\n").unwrap();
+        assert_eq!(file.refresh().unwrap(), SourceChange::Truncated);
+        assert_eq!(file.line_count(), 1);
+        assert_eq!(file.get_line(0).unwrap(), Some("x".to_string()));
+
+        let replacement = path.with_extension("new");
+        std::fs::write(&replacement, b"new\nfile\n").unwrap();
+        std::fs::rename(&replacement, &path).unwrap();
+        assert_eq!(file.refresh().unwrap(), SourceChange::Replaced);
+        assert_eq!(file.line_count(), 2);
+        assert_eq!(file.get_line(0).unwrap(), Some("new".to_string()));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_reload_reindexes_file_rewritten_in_place() {
+        let path = std::env::temp_dir().join(format!("pog-reload-{}", std::process::id()));
+        std::fs::write(&path, b"ab
+").unwrap();
+        let file = MappedFile::open(&path, None).unwrap();
+        file.wait_indexed();
+        assert_eq!(file.line_count(), 1);
+
+        // Same file, same size: refresh sees nothing
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"a
+b")
+            .unwrap();
+        assert_eq!(file.refresh().unwrap(), SourceChange::Unchanged);
+        assert_eq!(file.reload().unwrap(), SourceChange::Reloaded);
+        file.wait_indexed();
+        assert_eq!(file.line_count(), 2);
+        assert_eq!(file.get_line(1).unwrap(), Some("b".to_string()));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_partial_index() {
+        let path = std::env::temp_dir().join(format!("pog-partial-{}", std::process::id()));
+        std::fs::write(&path, b"aa\nbb\ncc\ndd\n").unwrap();
+        let mut mapping = Mapping::open(&path, None).unwrap();
+        mapping.index = SparseLineIndex::default();
+        mapping.indexed_len = 0;
+
+        // "aa\nbb\nc": the line starting at "c" is not complete yet
+        mapping.index_to(7);
+        assert_eq!(mapping.available_lines(), 2);
+        assert_eq!(mapping.get_line_internal(1), Some("bb".to_string()));
+        assert_eq!(mapping.get_line_internal(2), None);
+        assert_eq!(mapping.estimated_line_count(), 5);
+
+        mapping.index_to(12);
+        assert!(mapping.is_indexed());
+        assert_eq!(mapping.estimated_line_count(), 4);
+        assert_eq!(mapping.get_line_internal(3), Some("dd".to_string()));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_background_indexing() {
+        let path = std::env::temp_dir().join(format!("pog-lazy-{}", std::process::id()));
+        let line_count = INDEX_STEP / 10 * 2 + 7;
+        let data: String = (0..line_count).map(|i| format!("{:09}\n", i)).collect();
+        std::fs::write(&path, &data).unwrap();
+
+        let file = MappedFile::open(&path, None).unwrap();
+        // The first step is indexed before `open` returns
+        assert_eq!(file.get_line(0).unwrap(), Some(format!("{:09}", 0)));
+
+        file.wait_indexed();
+        assert_eq!(file.indexing_progress(), None);
+        assert_eq!(file.line_count(), line_count);
+        assert_eq!(file.line_at_offset(25), Some(2));
+        assert_eq!(
+            file.get_line(line_count - 1).unwrap(),
+            Some(format!("{:09}", line_count - 1))
+        );
+        assert_eq!(file.refresh().unwrap(), SourceChange::Indexed);
+        assert_eq!(file.refresh().unwrap(), SourceChange::Unchanged);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_after_truncation_remaps() {
+        let path = std::env::temp_dir().join(format!("pog-truncate-{}", std::process::id()));
+        std::fs::write(&path, "line\n".repeat(2000)).unwrap();
+        let file = MappedFile::open(&path, None).unwrap();
+        assert_eq!(file.line_count(), 2000);
+
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(0)
+            .unwrap();
+        // Would fault if the stale mapping were read
+        assert!(file.get_lines(1990, 10).unwrap().is_empty());
+        assert_eq!(file.refresh().unwrap(), SourceChange::Truncated);
+        assert_eq!(file.refresh().unwrap(), SourceChange::Unchanged);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_search_range_agrees_with_line_search() {
+        let path = std::env::temp_dir().join(format!("pog-search-{}", std::process::id()));
+        std::fs::write(
+            &path,
+            "\u{feff}start ok\r\nerror: disk\r\n\nwarn a\nb error\nübung ok\nerror last",
+        )
+        .unwrap();
+        let file = MappedFile::open(&path, None).unwrap();
+        let positions = |matches: Vec<SearchMatch>| -> Vec<(usize, usize, usize)> {
+            matches
+                .iter()
+                .map(|m| (m.line_num, m.start_col, m.end_col))
+                .collect()
+        };
+
+        for pattern in [
+            "error",
+            "^start",
+            "ok$",
+            "^$",
+            r"a\s+b",
+            "x*",
+            r"\Aerror",
+            r"(?i)ÜBUNG",
+            r"\w+$",
+        ] {
+            let regex = Regex::new(pattern).unwrap();
+            for (start, end) in [(0, 7), (1, 5), (4, 100), (6, 7)] {
+                let lines = file.get_lines(start, end - start).unwrap();
+                assert_eq!(
+                    positions(file.search_range(&regex, start, end).unwrap()),
+                    positions(search::search_lines(&regex, &lines)),
+                    "/{}/ in {}..{}",
+                    pattern,
+                    start,
+                    end
+                );
+            }
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
 }
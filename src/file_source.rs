@@ -1,4 +1,43 @@
-use crate::error::Result;
+use regex::Regex;
+
+use crate::error::{PogError, Result};
+use crate::search::{self, CancelToken, SearchDirection, SearchMatch};
+
+/// How a source differs from the file on disk, as found by `FileSource::refresh`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceChange {
+    Unchanged,
+    /// Lines were appended; they are already indexed
+    Grew,
+    /// The file got shorter (truncated in place); it was reindexed from scratch
+    Truncated,
+    /// The path now names a different file (e.g. after rotation); it was reopened
+    Replaced,
+    /// Background indexing went on; line numbers stay valid and `line_count`
+    /// is a better estimate (exact once `indexing_progress` is `None`)
+    Indexed,
+    /// The file was read again from scratch on request, by `FileSource::reload`
+    Reloaded,
+}
+
+impl SourceChange {
+    /// Whether the content under existing line numbers may be different.
+    pub fn is_rewrite(self) -> bool {
+        matches!(self, Self::Truncated | Self::Replaced | Self::Reloaded)
+    }
+}
+
+/// Whether a remote source can reach its host, from `FileSource::connection_state`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connected,
+    /// The connection dropped; it is re-established in the background and
+    /// fetches wait until it is
+    Reconnecting { attempts: u32, error: String },
+    /// Reconnecting gave up, or the host refused in a way retrying cannot
+    /// fix; fetches fail with `error`, and the next one tries again
+    Failed { error: String },
+}
 
 pub trait FileSource: Send + Sync {
     /// Returns total number of lines in the file
@@ -14,6 +53,167 @@ pub trait FileSource: Send + Sync {
     /// Get multiple lines efficiently (batch operation)
     fn get_lines(&self, start_line: usize, count: usize) -> Result<Vec<(usize, String)>>;
 
+    /// Undecoded bytes of lines (without terminators), for hex display.
+    /// Sources without access to the raw data return the decoded text.
+    fn get_raw_lines(&self, start_line: usize, count: usize) -> Result<Vec<(usize, Vec<u8>)>> {
+        Ok(self
+            .get_lines(start_line, count)?
+            .into_iter()
+            .map(|(n, text)| (n, text.into_bytes()))
+            .collect())
+    }
+
     /// Display name for window title
     fn display_name(&self) -> &str;
+
+    /// Line holding byte `offset` of the file, for sources that know where
+    /// their lines start; `None` for the others.
+    fn line_at_offset(&self, _offset: u64) -> Option<usize> {
+        None
+    }
+
+    /// Fraction of the file indexed so far while `line_count` is still an
+    /// estimate; `None` once the count is exact.
+    fn indexing_progress(&self) -> Option<f64> {
+        None
+    }
+
+    /// Block until the whole file is indexed, for callers that need every line.
+    fn wait_indexed(&self) {}
+
+    /// Whether more data may still arrive from a pipe; views poll `refresh`
+    /// until it returns false.
+    fn is_streaming(&self) -> bool {
+        false
+    }
+
+    /// State of the connection to a remote host; `None` for sources that
+    /// do not have one.
+    fn connection_state(&self) -> Option<ConnectionState> {
+        None
+    }
+
+    /// For sources interleaving several files, the index of the file line
+    /// `line_num` comes from and that file's name; `None` for the others.
+    fn line_origin(&self, _line_num: usize) -> Option<(usize, &str)> {
+        None
+    }
+
+    /// Matches of `pattern` in lines `start_line..end_line`. The default
+    /// fetches the lines; sources that can search where the data lives
+    /// (e.g. on a remote host) override it.
+    fn search_range(
+        &self,
+        pattern: &Regex,
+        start_line: usize,
+        end_line: usize,
+    ) -> Result<Vec<SearchMatch>> {
+        let lines = self.get_lines(start_line, end_line.saturating_sub(start_line))?;
+        Ok(search::search_lines(pattern, &lines))
+    }
+
+    /// First match after `from_line` (or last one before it, going backward).
+    /// The default scans `chunk_size` lines at a time with `get_lines`,
+    /// giving up with `PogError::Cancelled` once `cancel` is set.
+    fn find_next(
+        &self,
+        pattern: &Regex,
+        from_line: usize,
+        direction: SearchDirection,
+        chunk_size: usize,
+        cancel: &CancelToken,
+    ) -> Result<Option<SearchMatch>> {
+        search::scan_for_match(self, pattern, from_line, direction, chunk_size, cancel)
+    }
+
+    /// Bring the source up to date with the file on disk. Line numbers stay
+    /// valid after `Grew`; after `Truncated` or `Replaced` the content is new.
+    /// Sources that cannot change return `Unchanged`.
+    fn refresh(&self) -> Result<SourceChange> {
+        Ok(SourceChange::Unchanged)
+    }
+
+    /// Open and index the file again, e.g. after it was rewritten in place
+    /// where `refresh` sees no change. The content is new afterwards.
+    fn reload(&self) -> Result<SourceChange> {
+        Err(PogError::Unsupported("this file cannot be reloaded"))
+    }
+}
+
+/// The source of a window started without a file, until one is dropped on
+/// it or opened.
+pub struct EmptySource;
+
+impl EmptySource {
+    pub const NAME: &'static str = "no file";
+}
+
+impl FileSource for EmptySource {
+    fn line_count(&self) -> usize {
+        0
+    }
+
+    fn file_size(&self) -> Result<u64> {
+        Ok(0)
+    }
+
+    fn get_line(&self, _line_num: usize) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    fn get_lines(&self, _start_line: usize, _count: usize) -> Result<Vec<(usize, String)>> {
+        Ok(Vec::new())
+    }
+
+    fn display_name(&self) -> &str {
+        Self::NAME
+    }
+}
+
+/// Lines held in memory, for the tests of the sources and views built on
+/// `FileSource`.
+#[cfg(test)]
+pub(crate) struct VecSource {
+    pub name: String,
+    pub lines: Vec<String>,
+}
+
+#[cfg(test)]
+impl VecSource {
+    pub fn new<S: Into<String>>(lines: impl IntoIterator<Item = S>) -> Self {
+        Self::named("vec", lines)
+    }
+
+    pub fn named<S: Into<String>>(name: &str, lines: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            name: name.to_string(),
+            lines: lines.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl FileSource for VecSource {
+    fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// As if each line ended with `\n`
+    fn file_size(&self) -> Result<u64> {
+        Ok(self.lines.iter().map(|l| l.len() as u64 + 1).sum())
+    }
+
+    fn get_line(&self, line_num: usize) -> Result<Option<String>> {
+        Ok(self.lines.get(line_num).cloned())
+    }
+
+    fn get_lines(&self, start_line: usize, count: usize) -> Result<Vec<(usize, String)>> {
+        Ok((start_line..(start_line + count).min(self.lines.len()))
+            .map(|i| (i, self.lines[i].clone()))
+            .collect())
+    }
+
+    fn display_name(&self) -> &str {
+        &self.name
+    }
 }
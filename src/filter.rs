@@ -0,0 +1,603 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::RwLock;
+
+use regex::Regex;
+
+use crate::error::Result;
+use crate::file_source::FileSource;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterKind {
+    /// Show only lines matching one of the include filters
+    Include,
+    /// Hide lines matching the filter
+    Exclude,
+}
+
+impl fmt::Display for FilterKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterKind::Include => write!(f, "+"),
+            FilterKind::Exclude => write!(f, "-"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LineFilter {
+    pub kind: FilterKind,
+    pub pattern: String,
+    regex: Regex,
+}
+
+impl LineFilter {
+    pub fn new(kind: FilterKind, pattern: &str) -> std::result::Result<Self, String> {
+        let regex = Regex::new(pattern).map_err(|e| format!("invalid regex: {}", e))?;
+        Ok(Self {
+            kind,
+            pattern: pattern.to_string(),
+            regex,
+        })
+    }
+}
+
+/// Which consecutive lines count as repeats of each other when folding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FoldMode {
+    /// The same text
+    Exact,
+    /// The same text but for its numbers: timestamps, counters, durations
+    Similar,
+}
+
+impl FoldMode {
+    pub fn parse(name: &str) -> std::result::Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "exact" => Ok(Self::Exact),
+            "similar" => Ok(Self::Similar),
+            _ => Err(format!("unknown fold mode: {} (expected exact or similar)", name)),
+        }
+    }
+
+    /// What repeats of `text` have in common.
+    fn key(self, text: &str) -> String {
+        match self {
+            FoldMode::Exact => text.to_string(),
+            FoldMode::Similar => {
+                let mut key = String::with_capacity(text.len());
+                for c in text.chars() {
+                    if !c.is_ascii_digit() {
+                        key.push(c);
+                    } else if !key.ends_with('0') {
+                        key.push('0');
+                    }
+                }
+                key
+            }
+        }
+    }
+}
+
+impl fmt::Display for FoldMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FoldMode::Exact => write!(f, "exact"),
+            FoldMode::Similar => write!(f, "similar"),
+        }
+    }
+}
+
+/// Runs of repeated lines collapsed into the first line of each run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineFold {
+    pub mode: FoldMode,
+    /// Source lines starting runs that are shown in full
+    pub expanded: HashSet<usize>,
+}
+
+impl LineFold {
+    pub fn new(mode: FoldMode) -> Self {
+        LineFold {
+            mode,
+            expanded: HashSet::new(),
+        }
+    }
+}
+
+/// Lines hidden by `hide` next to a kept line, and the ranges they are in.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HiddenLines {
+    pub count: usize,
+    pub ranges: Vec<(usize, usize)>,
+}
+
+/// Add source lines `start..end` to the sorted, disjoint `ranges`, merging
+/// it with the ranges it overlaps or touches.
+pub fn hide_range(ranges: &mut Vec<(usize, usize)>, start: usize, end: usize) {
+    ranges.push((start, end));
+    ranges.sort_unstable();
+    let mut merged: Vec<(usize, usize)> = Vec::with_capacity(ranges.len());
+    for &(start, end) in ranges.iter() {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    *ranges = merged;
+}
+
+/// The lines of a source that a set of filters keeps. A line is kept when it
+/// matches any include filter (or there are none) and no exclude filter.
+///
+/// With a fold, a kept line repeating the one kept before it is left out
+/// and counted on the first line of the run instead. Lines in hidden ranges
+/// are left out before the filters see them, and counted on the kept line
+/// above them (or below, at the start of the file).
+///
+/// The index is built a chunk at a time by the view's worker; until it has
+/// scanned the whole source the view shows the lines kept so far.
+pub struct FilteredLines {
+    filters: Vec<LineFilter>,
+    fold: Option<LineFold>,
+    /// Source line ranges hidden by `hide`, sorted and disjoint
+    hidden: Vec<(usize, usize)>,
+    index: RwLock<FilterIndex>,
+}
+
+#[derive(Default)]
+struct FilterIndex {
+    /// Source line number of every kept line, in order
+    lines: Vec<usize>,
+    /// Source lines examined so far
+    scanned: usize,
+    /// Lines each filter matched, in filter order
+    hits: Vec<usize>,
+    /// The fold key and source line of the run the last kept line belongs to
+    last_run: Option<(String, usize)>,
+    /// Lines in each folded run of more than one, by the view line showing it
+    repeats: HashMap<usize, usize>,
+    /// Hidden lines by the view line they are counted on
+    hidden: HashMap<usize, HiddenLines>,
+    /// Hidden lines above the first kept line, until there is one
+    hidden_above: HiddenLines,
+}
+
+impl FilterIndex {
+    fn keep(&mut self, line_num: usize) {
+        self.lines.push(line_num);
+        if self.hidden_above.count > 0 {
+            let hidden = std::mem::take(&mut self.hidden_above);
+            self.hidden.insert(self.lines.len() - 1, hidden);
+        }
+    }
+
+    fn hide(&mut self, range: (usize, usize)) {
+        let hidden = match self.lines.len() {
+            0 => &mut self.hidden_above,
+            kept => self.hidden.entry(kept - 1).or_default(),
+        };
+        hidden.count += 1;
+        if !hidden.ranges.contains(&range) {
+            hidden.ranges.push(range);
+        }
+    }
+}
+
+impl FilteredLines {
+    pub fn new(
+        filters: Vec<LineFilter>,
+        fold: Option<LineFold>,
+        hidden: Vec<(usize, usize)>,
+    ) -> Self {
+        let index = FilterIndex {
+            hits: vec![0; filters.len()],
+            ..FilterIndex::default()
+        };
+        Self {
+            filters,
+            fold,
+            hidden,
+            index: RwLock::new(index),
+        }
+    }
+
+    pub fn filters(&self) -> &[LineFilter] {
+        &self.filters
+    }
+
+    /// Hidden lines counted on `view_line`, if any.
+    pub fn hidden(&self, view_line: usize) -> Option<HiddenLines> {
+        self.index.read().unwrap().hidden.get(&view_line).cloned()
+    }
+
+    /// Lines folded into `view_line`, itself included, when it shows a run
+    /// of repeats.
+    pub fn repeats(&self, view_line: usize) -> Option<usize> {
+        self.index.read().unwrap().repeats.get(&view_line).copied()
+    }
+
+    /// Lines kept so far.
+    pub fn line_count(&self) -> usize {
+        self.index.read().unwrap().lines.len()
+    }
+
+    /// Line of the source shown at `view_line`.
+    pub fn source_line(&self, view_line: usize) -> Option<usize> {
+        self.index.read().unwrap().lines.get(view_line).copied()
+    }
+
+    /// First view line showing `source_line` or a line after it; the line
+    /// count when every kept line is before it.
+    pub fn view_line(&self, source_line: usize) -> usize {
+        let index = self.index.read().unwrap();
+        index.lines.partition_point(|&line| line < source_line)
+    }
+
+    /// View line showing `source_line`, unless the filters leave it out.
+    pub fn shown_at(&self, source_line: usize) -> Option<usize> {
+        let view_line = self.view_line(source_line);
+        (self.source_line(view_line) == Some(source_line)).then_some(view_line)
+    }
+
+    pub fn scanned(&self) -> usize {
+        self.index.read().unwrap().scanned
+    }
+
+    /// Lines each filter matched so far, in filter order.
+    pub fn hits(&self) -> Vec<usize> {
+        self.index.read().unwrap().hits.clone()
+    }
+
+    /// Examine the next `chunk_size` lines of `source`, returning how many
+    /// were read (none once the index has caught up with the source).
+    pub fn extend(&self, source: &dyn FileSource, chunk_size: usize) -> Result<usize> {
+        let start = self.scanned();
+        let count = chunk_size.min(source.line_count().saturating_sub(start));
+        if count == 0 {
+            return Ok(0);
+        }
+        let lines = source.get_lines(start, count)?;
+
+        let mut index = self.index.write().unwrap();
+        // Reset while the lines were read: they belong to the old content
+        if index.scanned != start {
+            return Ok(0);
+        }
+        let mut matched = vec![false; self.filters.len()];
+        for (line_num, text) in &lines {
+            let after = self.hidden.partition_point(|&(_, end)| end <= *line_num);
+            if let Some(&range) = self.hidden.get(after).filter(|(start, _)| start <= line_num) {
+                index.hide(range);
+                continue;
+            }
+            for (filter, matched) in self.filters.iter().zip(matched.iter_mut()) {
+                *matched = filter.regex.is_match(text);
+            }
+            for (hits, &matched) in index.hits.iter_mut().zip(&matched) {
+                *hits += matched as usize;
+            }
+            if !self.keeps(&matched) {
+                continue;
+            }
+            let Some(fold) = &self.fold else {
+                index.keep(*line_num);
+                continue;
+            };
+            let key = fold.mode.key(text);
+            match index.last_run.take() {
+                Some((last_key, run_start)) if last_key == key => {
+                    index.last_run = Some((last_key, run_start));
+                    if fold.expanded.contains(&run_start) {
+                        index.keep(*line_num);
+                    } else {
+                        let head = index.lines.len() - 1;
+                        *index.repeats.entry(head).or_insert(1) += 1;
+                    }
+                }
+                _ => {
+                    index.last_run = Some((key, *line_num));
+                    index.keep(*line_num);
+                }
+            }
+        }
+        index.scanned = start + lines.len();
+        Ok(lines.len())
+    }
+
+    /// Start over, after the content of the source was replaced.
+    pub fn reset(&self) {
+        *self.index.write().unwrap() = FilterIndex {
+            hits: vec![0; self.filters.len()],
+            ..FilterIndex::default()
+        };
+    }
+
+    /// Whether a line is kept, given which filters it matched.
+    fn keeps(&self, matched: &[bool]) -> bool {
+        let mut has_include = false;
+        let mut included = false;
+        for (filter, &matched) in self.filters.iter().zip(matched) {
+            match filter.kind {
+                FilterKind::Exclude if matched => return false,
+                FilterKind::Exclude => {}
+                FilterKind::Include => {
+                    has_include = true;
+                    included |= matched;
+                }
+            }
+        }
+        included || !has_include
+    }
+}
+
+/// A source seen through its filters: line numbers count the kept lines.
+pub struct FilteredView<'a> {
+    source: &'a dyn FileSource,
+    filter: &'a FilteredLines,
+}
+
+impl<'a> FilteredView<'a> {
+    pub fn new(source: &'a dyn FileSource, filter: &'a FilteredLines) -> Self {
+        Self { source, filter }
+    }
+
+    /// Fetch the source lines behind view lines `start_line..start_line + count`,
+    /// asking `fetch` for runs of consecutive source lines at once.
+    fn fetch_kept<T>(
+        &self,
+        start_line: usize,
+        count: usize,
+        fetch: impl Fn(usize, usize) -> Result<Vec<(usize, T)>>,
+    ) -> Result<Vec<(usize, T)>> {
+        let kept: Vec<usize> = {
+            let index = self.filter.index.read().unwrap();
+            let end_line = (start_line + count).min(index.lines.len());
+            index.lines[start_line.min(end_line)..end_line].to_vec()
+        };
+
+        let mut result = Vec::with_capacity(kept.len());
+        let mut i = 0;
+        while i < kept.len() {
+            let first = kept[i];
+            let mut run = 1;
+            while i + run < kept.len() && kept[i + run] == first + run {
+                run += 1;
+            }
+            let lines = fetch(first, run)?;
+            result.extend(
+                lines
+                    .into_iter()
+                    .map(|(n, line)| (start_line + i + (n - first), line)),
+            );
+            i += run;
+        }
+        Ok(result)
+    }
+}
+
+impl FileSource for FilteredView<'_> {
+    fn line_count(&self) -> usize {
+        self.filter.line_count()
+    }
+
+    fn file_size(&self) -> Result<u64> {
+        self.source.file_size()
+    }
+
+    fn get_line(&self, line_num: usize) -> Result<Option<String>> {
+        match self.filter.source_line(line_num) {
+            Some(line) => self.source.get_line(line),
+            None => Ok(None),
+        }
+    }
+
+    fn get_lines(&self, start_line: usize, count: usize) -> Result<Vec<(usize, String)>> {
+        self.fetch_kept(start_line, count, |first, run| {
+            self.source.get_lines(first, run)
+        })
+    }
+
+    fn get_raw_lines(&self, start_line: usize, count: usize) -> Result<Vec<(usize, Vec<u8>)>> {
+        self.fetch_kept(start_line, count, |first, run| {
+            self.source.get_raw_lines(first, run)
+        })
+    }
+
+    fn display_name(&self) -> &str {
+        self.source.display_name()
+    }
+
+    /// The filter index counts as indexing: lines are still being added to
+    /// the view until it has caught up with the source.
+    fn indexing_progress(&self) -> Option<f64> {
+        let total = self.source.line_count();
+        let scanned = self.filter.scanned();
+        if scanned < total {
+            Some(scanned as f64 / total as f64)
+        } else {
+            self.source.indexing_progress()
+        }
+    }
+
+    fn is_streaming(&self) -> bool {
+        self.source.is_streaming()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_source::VecSource;
+
+    fn source() -> VecSource {
+        VecSource::new([
+            "GET /health 200",
+            "GET /api/users 200",
+            "POST /api/login 500",
+            "GET /health 200",
+            "GET /api/orders 404",
+            "heartbeat",
+        ])
+    }
+
+    fn filter(kind: FilterKind, pattern: &str) -> LineFilter {
+        LineFilter::new(kind, pattern).unwrap()
+    }
+
+    fn build(source: &VecSource, filters: Vec<LineFilter>) -> FilteredLines {
+        let filtered = FilteredLines::new(filters, None, Vec::new());
+        while filtered.extend(source, 4).unwrap() > 0 {}
+        filtered
+    }
+
+    fn view_lines(source: &VecSource, filtered: &FilteredLines) -> Vec<(usize, String)> {
+        FilteredView::new(source, filtered)
+            .get_lines(0, 100)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_exclude_filters_hide_lines() {
+        let source = source();
+        let filtered = build(
+            &source,
+            vec![
+                filter(FilterKind::Exclude, "/health"),
+                filter(FilterKind::Exclude, "^heartbeat$"),
+            ],
+        );
+        assert_eq!(filtered.line_count(), 3);
+        assert_eq!(filtered.hits(), vec![2, 1]);
+        assert_eq!(filtered.source_line(1), Some(2));
+        assert_eq!((filtered.view_line(2), filtered.view_line(3)), (1, 2));
+        assert_eq!(filtered.view_line(6), 3);
+        assert_eq!((filtered.shown_at(2), filtered.shown_at(3)), (Some(1), None));
+        assert_eq!(
+            view_lines(&source, &filtered),
+            vec![
+                (0, "GET /api/users 200".to_string()),
+                (1, "POST /api/login 500".to_string()),
+                (2, "GET /api/orders 404".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_include_and_exclude_stack() {
+        let source = source();
+        let filtered = build(
+            &source,
+            vec![
+                filter(FilterKind::Include, "^GET"),
+                filter(FilterKind::Include, " 500$"),
+                filter(FilterKind::Exclude, "health"),
+            ],
+        );
+        let view = FilteredView::new(&source, &filtered);
+        assert_eq!(filtered.hits(), vec![4, 1, 2]);
+        assert_eq!(view.line_count(), 3);
+        assert_eq!(
+            view.get_line(2).unwrap(),
+            Some("GET /api/orders 404".to_string())
+        );
+        assert_eq!(view.get_line(3).unwrap(), None);
+
+        let tail = view.get_lines(1, 5).unwrap();
+        assert_eq!(tail[0], (1, "POST /api/login 500".to_string()));
+        assert_eq!(tail.len(), 2);
+    }
+
+    #[test]
+    fn test_view_indexes_while_scanning() {
+        let source = source();
+        let filtered = FilteredLines::new(vec![filter(FilterKind::Exclude, "health")], None, Vec::new());
+        let view = FilteredView::new(&source, &filtered);
+        assert_eq!(filtered.extend(&source, 4).unwrap(), 4);
+        assert_eq!(view.line_count(), 2);
+        assert!(view.indexing_progress().is_some());
+
+        assert_eq!(filtered.extend(&source, 4).unwrap(), 2);
+        assert_eq!(filtered.extend(&source, 4).unwrap(), 0);
+        assert_eq!(view.line_count(), 4);
+        assert!(view.indexing_progress().is_none());
+
+        filtered.reset();
+        assert_eq!((filtered.line_count(), filtered.hits()), (0, vec![0]));
+    }
+
+    #[test]
+    fn test_fold_repeats() {
+        let source = VecSource::new([
+            "start",
+            "12:00:01 health ok",
+            "12:00:02 health ok",
+            "12:00:02 health ok",
+            "12:00:03 health ok in 15ms",
+            "error",
+            "error",
+        ]);
+        let fold = |mode, expanded: &[usize]| {
+            let mut fold = LineFold::new(mode);
+            fold.expanded.extend(expanded);
+            let filtered = FilteredLines::new(Vec::new(), Some(fold), Vec::new());
+            while filtered.extend(&source, 2).unwrap() > 0 {}
+            filtered
+        };
+
+        let exact = fold(FoldMode::Exact, &[]);
+        let kept: Vec<usize> = (0..exact.line_count()).filter_map(|l| exact.source_line(l)).collect();
+        assert_eq!(kept, vec![0, 1, 2, 4, 5]);
+        assert_eq!((exact.repeats(2), exact.repeats(4), exact.repeats(0)), (Some(2), Some(2), None));
+
+        let similar = fold(FoldMode::Similar, &[]);
+        assert_eq!(similar.line_count(), 4);
+        assert_eq!(similar.repeats(1), Some(3));
+        assert_eq!(similar.source_line(2), Some(4));
+
+        // An expanded run is shown in full, and still ends at the next other line
+        let expanded = fold(FoldMode::Similar, &[1]);
+        assert_eq!(expanded.line_count(), 6);
+        assert_eq!(expanded.repeats(1), None);
+        assert_eq!(expanded.repeats(5), Some(2));
+
+        assert_eq!(FoldMode::parse("Similar"), Ok(FoldMode::Similar));
+        assert!(FoldMode::parse("fuzzy").is_err());
+    }
+
+    #[test]
+    fn test_hide_ranges() {
+        let mut ranges = vec![(2, 4)];
+        hide_range(&mut ranges, 0, 1);
+        hide_range(&mut ranges, 3, 6);
+        assert_eq!(ranges, vec![(0, 1), (2, 6)]);
+        hide_range(&mut ranges, 1, 2);
+        assert_eq!(ranges, vec![(0, 6)]);
+
+        let source = source();
+        let filtered = FilteredLines::new(
+            vec![filter(FilterKind::Exclude, "^heartbeat$")],
+            None,
+            vec![(0, 1), (2, 4)],
+        );
+        while filtered.extend(&source, 4).unwrap() > 0 {}
+        let kept: Vec<usize> =
+            (0..filtered.line_count()).filter_map(|l| filtered.source_line(l)).collect();
+        assert_eq!(kept, vec![1, 4]);
+        // The first line is counted below it, the others above them
+        let hidden = HiddenLines {
+            count: 3,
+            ranges: vec![(0, 1), (2, 4)],
+        };
+        assert_eq!(filtered.hidden(0), Some(hidden));
+        assert_eq!(filtered.hidden(1), None);
+        assert_eq!(filtered.hits(), vec![1]);
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        assert!(LineFilter::new(FilterKind::Exclude, "(")
+            .unwrap_err()
+            .starts_with("invalid regex"));
+    }
+}
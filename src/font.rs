@@ -0,0 +1,90 @@
+/// Font size of the log lines unless the config file sets one, in points.
+pub const DEFAULT_FONT_SIZE: u32 = 11;
+/// Smallest and largest size zooming goes to, in points.
+pub const MIN_FONT_SIZE: u32 = 6;
+pub const MAX_FONT_SIZE: u32 = 48;
+
+/// The monospace font of the line numbers and log lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FontSettings {
+    pub family: String,
+    /// Size in points
+    pub size: u32,
+}
+
+impl Default for FontSettings {
+    fn default() -> Self {
+        FontSettings {
+            family: "monospace".to_string(),
+            size: DEFAULT_FONT_SIZE,
+        }
+    }
+}
+
+impl FontSettings {
+    /// The same font `steps` points larger (or smaller when negative),
+    /// within MIN_FONT_SIZE..=MAX_FONT_SIZE.
+    pub fn zoomed(&self, steps: i32) -> Self {
+        FontSettings {
+            family: self.family.clone(),
+            size: self.size.saturating_add_signed(steps).clamp(MIN_FONT_SIZE, MAX_FONT_SIZE),
+        }
+    }
+
+    /// CSS setting the font of the `.monospace` widgets under `selector`.
+    pub fn css(&self, selector: &str) -> String {
+        format!(
+            "{} .monospace {{ font-family: \"{}\"; font-size: {}pt; }}",
+            selector, self.family, self.size
+        )
+    }
+}
+
+/// Check a font family name from the config file. Quotes and CSS
+/// punctuation are refused, as the name ends up in a stylesheet.
+pub fn parse_family(value: &str) -> Result<String, String> {
+    if value.is_empty() || value.contains(['"', '\\', ';', '{', '}']) {
+        return Err(format!("invalid font family: {}", value));
+    }
+    Ok(value.to_string())
+}
+
+/// Check a font size from the config file, in points.
+pub fn parse_size(value: &str) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(size) if (MIN_FONT_SIZE..=MAX_FONT_SIZE).contains(&size) => Ok(size),
+        _ => Err(format!(
+            "font size must be from {} to {} points, got: {}",
+            MIN_FONT_SIZE, MAX_FONT_SIZE, value
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zoom_stays_in_range() {
+        let font = FontSettings::default();
+        assert_eq!(font.zoomed(2).size, DEFAULT_FONT_SIZE + 2);
+        assert_eq!(font.zoomed(-100).size, MIN_FONT_SIZE);
+        assert_eq!(font.zoomed(100).size, MAX_FONT_SIZE);
+        assert_eq!(font.zoomed(1).family, "monospace");
+    }
+
+    #[test]
+    fn test_parse_font_settings() {
+        assert_eq!(parse_family("JetBrains Mono"), Ok("JetBrains Mono".to_string()));
+        assert!(parse_family("Mono\"; color: red").is_err());
+        assert!(parse_family("").is_err());
+        assert_eq!(parse_size("14"), Ok(14));
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("200").is_err());
+        assert!(parse_size("big").is_err());
+        assert_eq!(
+            FontSettings { family: "Hack".to_string(), size: 13 }.css(".view-1"),
+            ".view-1 .monospace { font-family: \"Hack\"; font-size: 13pt; }"
+        );
+    }
+}
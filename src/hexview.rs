@@ -0,0 +1,112 @@
+use std::fmt;
+
+/// Longest prefix of a line shown in a hex dump.
+const HEX_DUMP_MAX_BYTES: usize = 4096;
+
+/// How lines containing binary data are rendered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ViewMode {
+    /// Control characters are shown as their Unicode control pictures
+    #[default]
+    Text,
+    /// Binary lines are shown as a hex dump of their bytes
+    Hex,
+}
+
+impl ViewMode {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(ViewMode::Text),
+            "hex" => Ok(ViewMode::Hex),
+            _ => Err(format!("invalid view mode: {} (expected hex or text)", s)),
+        }
+    }
+
+    pub fn toggled(self) -> Self {
+        match self {
+            ViewMode::Text => ViewMode::Hex,
+            ViewMode::Hex => ViewMode::Text,
+        }
+    }
+}
+
+impl fmt::Display for ViewMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ViewMode::Text => write!(f, "text"),
+            ViewMode::Hex => write!(f, "hex"),
+        }
+    }
+}
+
+/// A line holds binary data if it has control characters other than tab,
+/// or bytes that could not be decoded.
+pub fn is_binary(text: &str) -> bool {
+    text.chars()
+        .any(|c| (c.is_control() && c != '\t') || c == char::REPLACEMENT_CHARACTER)
+}
+
+/// Replace control characters with their visible Unicode control pictures
+/// (NUL becomes U+2400 and so on), keeping one character per column.
+pub fn show_control_chars(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\t' => c,
+            '\0'..='\x1f' => char::from_u32(0x2400 + c as u32).unwrap_or(c),
+            '\x7f' => '\u{2421}',
+            c if c.is_control() => '\u{fffd}',
+            c => c,
+        })
+        .collect()
+}
+
+/// Single-line hex dump: byte values followed by their printable ASCII.
+pub fn hex_dump(bytes: &[u8]) -> String {
+    let shown = &bytes[..bytes.len().min(HEX_DUMP_MAX_BYTES)];
+    let hex: Vec<String> = shown.iter().map(|b| format!("{:02x}", b)).collect();
+    let ascii: String = shown
+        .iter()
+        .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+        .collect();
+
+    let mut dump = format!("{}  |{}|", hex.join(" "), ascii);
+    if bytes.len() > shown.len() {
+        dump.push_str(&format!(" ... {} more bytes", bytes.len() - shown.len()));
+    }
+    dump
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_view_mode_parse() {
+        assert_eq!(ViewMode::parse("hex"), Ok(ViewMode::Hex));
+        assert_eq!(ViewMode::parse("TEXT"), Ok(ViewMode::Text));
+        assert!(ViewMode::parse("binary").is_err());
+        assert_eq!(ViewMode::Text.toggled(), ViewMode::Hex);
+        assert_eq!(ViewMode::Hex.to_string(), "hex");
+    }
+
+    #[test]
+    fn test_is_binary() {
+        assert!(!is_binary("plain\ttext"));
+        assert!(!is_binary("caf\u{e9}"));
+        assert!(is_binary("core\0dump"));
+        assert!(is_binary("bad \u{fffd} byte"));
+    }
+
+    #[test]
+    fn test_show_control_chars() {
+        assert_eq!(show_control_chars("a\0b\x1bc\x7f\td"), "a\u{2400}b\u{241b}c\u{2421}\td");
+        assert_eq!(show_control_chars("x\0").chars().count(), 2);
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        assert_eq!(hex_dump(b"AB\0\xff "), "41 42 00 ff 20  |AB.. |");
+        let long = vec![0u8; HEX_DUMP_MAX_BYTES + 3];
+        assert!(hex_dump(&long).ends_with("... 3 more bytes"));
+    }
+}
@@ -0,0 +1,93 @@
+use regex::Regex;
+
+use crate::search::SearchMatch;
+
+/// Colors given to highlights added without one, in order, in the light
+/// theme. Distinct from the gold of search matches.
+pub const HIGHLIGHT_PALETTE: &[&str] = &[
+    "#87CEFA", "#90EE90", "#FFB6C1", "#DDA0DD", "#FFA07A", "#AFEEEE", "#F0E68C", "#D3D3D3",
+];
+
+/// A pattern whose matches keep their background color while scrolling,
+/// independently of the active search.
+#[derive(Debug, Clone)]
+pub struct HighlightRule {
+    pub color: String,
+    pub pattern: String,
+    regex: Regex,
+}
+
+impl HighlightRule {
+    pub fn new(color: &str, pattern: &str) -> Result<Self, String> {
+        let regex = Regex::new(pattern).map_err(|e| format!("invalid regex: {}", e))?;
+        Ok(Self {
+            color: color.to_string(),
+            pattern: pattern.to_string(),
+            regex,
+        })
+    }
+
+    /// Character column ranges (start, exclusive end) of the matches in `text`.
+    pub fn ranges(&self, text: &str) -> Vec<(usize, usize)> {
+        self.regex
+            .find_iter(text)
+            .filter(|mat| !mat.is_empty())
+            .map(|mat| {
+                let m = SearchMatch::from_regex_match(0, text, mat);
+                (m.start_col, m.end_col)
+            })
+            .collect()
+    }
+}
+
+/// The first `palette` color no rule uses yet, cycling once all are taken.
+pub fn next_color(rules: &[HighlightRule], palette: &[&'static str]) -> &'static str {
+    palette
+        .iter()
+        .find(|color| {
+            !rules
+                .iter()
+                .any(|rule| rule.color.eq_ignore_ascii_case(color))
+        })
+        .unwrap_or(&palette[rules.len() % palette.len()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ranges_are_character_columns() {
+        let rule = HighlightRule::new("red", "req-[0-9]+").unwrap();
+        assert_eq!(rule.ranges("req-1 and req-22"), vec![(0, 5), (10, 16)]);
+        assert_eq!(rule.ranges("ünïcode req-7"), vec![(8, 13)]);
+        assert!(rule.ranges("nothing").is_empty());
+
+        // Empty matches would highlight nothing
+        let optional = HighlightRule::new("red", "x*").unwrap();
+        assert_eq!(optional.ranges("abxxc"), vec![(2, 4)]);
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        assert!(HighlightRule::new("red", "(")
+            .unwrap_err()
+            .starts_with("invalid regex"));
+    }
+
+    #[test]
+    fn test_next_color_skips_used_colors() {
+        assert_eq!(next_color(&[], HIGHLIGHT_PALETTE), HIGHLIGHT_PALETTE[0]);
+        let rules = vec![
+            HighlightRule::new(HIGHLIGHT_PALETTE[0], "a").unwrap(),
+            HighlightRule::new("red", "b").unwrap(),
+        ];
+        assert_eq!(next_color(&rules, HIGHLIGHT_PALETTE), HIGHLIGHT_PALETTE[1]);
+
+        let all: Vec<HighlightRule> = HIGHLIGHT_PALETTE
+            .iter()
+            .map(|color| HighlightRule::new(color, "a").unwrap())
+            .collect();
+        assert_eq!(next_color(&all, HIGHLIGHT_PALETTE), HIGHLIGHT_PALETTE[0]);
+    }
+}
@@ -0,0 +1,216 @@
+/// `text` as a quoted JSON string.
+pub fn string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// A parsed JSON value; numbers are kept as written.
+#[derive(Debug)]
+pub enum Json {
+    Null,
+    Bool,
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn string(&self, key: &str) -> Result<&str, String> {
+        match self {
+            Json::String(text) => Ok(text),
+            _ => Err(format!("\"{}\" is not a string", key)),
+        }
+    }
+
+    /// The 1-based line or column number under `key`, made 0-based.
+    pub fn position(&self, key: &str) -> Result<usize, String> {
+        match self.get(key) {
+            Some(Json::Number(number)) => match number.parse::<usize>() {
+                Ok(n) if n > 0 => Ok(n - 1),
+                _ => Err(format!("invalid \"{}\": {}", key, number)),
+            },
+            Some(_) => Err(format!("\"{}\" is not a number", key)),
+            None => Err(format!("missing \"{}\"", key)),
+        }
+    }
+
+    /// The non-negative integer under `key`.
+    pub fn unsigned(&self, key: &str) -> Result<u64, String> {
+        match self.get(key) {
+            Some(Json::Number(number)) => number
+                .parse::<u64>()
+                .map_err(|_| format!("invalid \"{}\": {}", key, number)),
+            Some(_) => Err(format!("\"{}\" is not a number", key)),
+            None => Err(format!("missing \"{}\"", key)),
+        }
+    }
+}
+
+/// Parse `text` as a single JSON value.
+pub fn parse(text: &str) -> Result<Json, String> {
+    let mut parser = Parser {
+        chars: text.chars().peekable(),
+    };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.chars.next().is_some() {
+        return Err("trailing characters after JSON value".to_string());
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.chars.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(format!("expected '{}', found '{}'", expected, c)),
+            None => Err(format!("expected '{}', found end of file", expected)),
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.chars.peek() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some(c) if *c == '-' || c.is_ascii_digit() => {
+                let mut number = String::new();
+                while let Some(c) = self
+                    .chars
+                    .next_if(|c| c.is_ascii_digit() || "+-.eE".contains(*c))
+                {
+                    number.push(c);
+                }
+                Ok(Json::Number(number))
+            }
+            Some(_) => {
+                let mut word = String::new();
+                while let Some(c) = self.chars.next_if(|c| c.is_ascii_alphabetic()) {
+                    word.push(c);
+                }
+                match word.as_str() {
+                    "null" => Ok(Json::Null),
+                    "true" | "false" => Ok(Json::Bool),
+                    _ => Err(format!("unexpected value: {:?}", word)),
+                }
+            }
+            None => Err("unexpected end of file".to_string()),
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.expect('{')?;
+        let mut fields = Vec::new();
+        self.skip_whitespace();
+        if self.chars.next_if_eq(&'}').is_some() {
+            return Ok(Json::Object(fields));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.expect(':')?;
+            fields.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => {}
+                Some('}') => return Ok(Json::Object(fields)),
+                _ => return Err("expected ',' or '}' in object".to_string()),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.chars.next_if_eq(&']').is_some() {
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => {}
+                Some(']') => return Ok(Json::Array(items)),
+                _ => return Err("expected ',' or ']' in array".to_string()),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if self.chars.next() != Some('"') {
+            return Err("expected a string".to_string());
+        }
+        let mut text = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(text),
+                Some('\\') => match self.chars.next() {
+                    Some('n') => text.push('\n'),
+                    Some('r') => text.push('\r'),
+                    Some('t') => text.push('\t'),
+                    Some('b') => text.push('\u{8}'),
+                    Some('f') => text.push('\u{c}'),
+                    Some('u') => {
+                        let hex: String = (0..4).filter_map(|_| self.chars.next()).collect();
+                        let code = u32::from_str_radix(&hex, 16)
+                            .map_err(|_| format!("invalid escape: \\u{}", hex))?;
+                        text.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                    }
+                    Some(c @ ('"' | '\\' | '/')) => text.push(c),
+                    Some(c) => return Err(format!("invalid escape: \\{}", c)),
+                    None => return Err("unterminated string".to_string()),
+                },
+                Some(c) => text.push(c),
+                None => return Err("unterminated string".to_string()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_object() {
+        let value = parse(r#"{"pid": 42, "file": "a \"b\".log", "ok": true}"#).unwrap();
+        assert_eq!(value.unsigned("pid"), Ok(42));
+        assert_eq!(value.get("file").unwrap().string("file"), Ok("a \"b\".log"));
+        assert!(value.unsigned("file").is_err());
+        assert!(value.unsigned("port").is_err());
+        assert!(parse("{} x").is_err());
+        assert_eq!(string("a\tb\u{1}"), r#""a\tb\u0001""#);
+    }
+}
@@ -0,0 +1,286 @@
+/// An action bound to keys in the view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAction {
+    LineDown,
+    LineUp,
+    HalfPageDown,
+    HalfPageUp,
+    Top,
+    Bottom,
+    Search,
+    /// Mark or unmark the cursor line
+    Mark,
+    /// Pin or unpin the selected lines, or the cursor line
+    Pin,
+}
+
+impl KeyAction {
+    /// Every action with the config key that rebinds it.
+    const CONFIG_KEYS: [(KeyAction, &'static str); 9] = [
+        (KeyAction::LineDown, "key_line_down"),
+        (KeyAction::LineUp, "key_line_up"),
+        (KeyAction::HalfPageDown, "key_half_page_down"),
+        (KeyAction::HalfPageUp, "key_half_page_up"),
+        (KeyAction::Top, "key_top"),
+        (KeyAction::Bottom, "key_bottom"),
+        (KeyAction::Search, "key_search"),
+        (KeyAction::Mark, "key_mark"),
+        (KeyAction::Pin, "key_pin"),
+    ];
+
+    pub fn from_config_key(key: &str) -> Option<Self> {
+        Self::CONFIG_KEYS
+            .iter()
+            .find(|(_, name)| *name == key)
+            .map(|(action, _)| *action)
+    }
+}
+
+/// One key press. Shift is part of the character: `G` rather than Shift+g.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyPress {
+    pub ctrl: bool,
+    pub key: char,
+}
+
+impl KeyPress {
+    pub fn plain(key: char) -> Self {
+        KeyPress { ctrl: false, key }
+    }
+
+    pub fn ctrl(key: char) -> Self {
+        KeyPress { ctrl: true, key }
+    }
+}
+
+/// Parse a config value into key sequences: alternatives separated by
+/// commas, each a run of characters pressed in turn, with `Ctrl+` before a
+/// character held with Control, e.g. `gg, Ctrl+g`. Only characters can be
+/// bound, not named keys such as Home. An empty value unbinds the action.
+pub fn parse_sequences(value: &str) -> Result<Vec<Vec<KeyPress>>, String> {
+    let mut sequences = Vec::new();
+    for alternative in value.split(',').map(str::trim).filter(|a| !a.is_empty()) {
+        let mut sequence = Vec::new();
+        let mut rest = alternative;
+        while let Some(c) = rest.chars().next() {
+            let press = match rest.strip_prefix("Ctrl+") {
+                Some(after) => {
+                    let key = after
+                        .chars()
+                        .next()
+                        .ok_or_else(|| format!("missing key after Ctrl+ in {}", alternative))?;
+                    rest = &after[key.len_utf8()..];
+                    KeyPress::ctrl(key.to_ascii_lowercase())
+                }
+                None if c.is_whitespace() => {
+                    return Err(format!("invalid key sequence: {}", alternative))
+                }
+                None => {
+                    rest = &rest[c.len_utf8()..];
+                    KeyPress::plain(c)
+                }
+            };
+            if press.key.is_whitespace() || press.key.is_control() {
+                return Err(format!("invalid key sequence: {}", alternative));
+            }
+            sequence.push(press);
+        }
+        sequences.push(sequence);
+    }
+    Ok(sequences)
+}
+
+/// What a key press did to the sequence being typed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyMatch {
+    /// A sequence was completed, after the count typed before it if any
+    Action(KeyAction, Option<usize>),
+    /// The keys so far start a sequence
+    Pending,
+    /// The key belongs to no sequence and is left to other handlers
+    None,
+}
+
+/// The keys of the view's navigation actions. Defaults follow vim; config
+/// keys such as `key_top` rebind them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyBindings {
+    bindings: Vec<(Vec<KeyPress>, KeyAction)>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let plain = |keys: &str| keys.chars().map(KeyPress::plain).collect();
+        KeyBindings {
+            bindings: vec![
+                (plain("j"), KeyAction::LineDown),
+                (plain("k"), KeyAction::LineUp),
+                (vec![KeyPress::ctrl('d')], KeyAction::HalfPageDown),
+                (vec![KeyPress::ctrl('u')], KeyAction::HalfPageUp),
+                (plain("gg"), KeyAction::Top),
+                (plain("G"), KeyAction::Bottom),
+                (plain("/"), KeyAction::Search),
+                (plain("m"), KeyAction::Mark),
+                (plain("p"), KeyAction::Pin),
+            ],
+        }
+    }
+}
+
+/// Keys typed so far: a vim-style count such as the 17 of `17j`, then the
+/// start of a sequence such as `gg`.
+#[derive(Debug, Clone, Default)]
+pub struct PendingKeys {
+    count: Option<usize>,
+    keys: Vec<KeyPress>,
+}
+
+impl KeyBindings {
+    /// Replace the keys of `action` with `sequences`.
+    pub fn rebind(&mut self, action: KeyAction, sequences: &[Vec<KeyPress>]) {
+        self.bindings.retain(|(_, bound)| *bound != action);
+        self.bindings
+            .extend(sequences.iter().map(|sequence| (sequence.clone(), action)));
+    }
+
+    /// The defaults with the config's bindings applied in order.
+    pub fn with_overrides(mut self, overrides: &[(KeyAction, Vec<Vec<KeyPress>>)]) -> Self {
+        for (action, sequences) in overrides {
+            self.rebind(*action, sequences);
+        }
+        self
+    }
+
+    /// Add `press` to the keys typed so far in `pending`. A completed or
+    /// broken sequence clears it; a key that breaks one may still start
+    /// another. Digits before a sequence are its count, unless a sequence
+    /// starts with that digit; a count does not start with 0.
+    pub fn press(&self, pending: &mut PendingKeys, press: KeyPress) -> KeyMatch {
+        let digit = press.key.to_digit(10).filter(|_| !press.ctrl && pending.keys.is_empty());
+        if let Some(digit) = digit.filter(|&d| d > 0 || pending.count.is_some()) {
+            if !self.bindings.iter().any(|(keys, _)| keys.first() == Some(&press)) {
+                let count = pending.count.unwrap_or(0).saturating_mul(10);
+                pending.count = Some(count.saturating_add(digit as usize));
+                return KeyMatch::Pending;
+            }
+        }
+        pending.keys.push(press);
+        if let Some((_, action)) = self.bindings.iter().find(|(keys, _)| *keys == pending.keys) {
+            pending.keys.clear();
+            return KeyMatch::Action(*action, pending.count.take());
+        }
+        if self.bindings.iter().any(|(keys, _)| keys.starts_with(&pending.keys)) {
+            return KeyMatch::Pending;
+        }
+        let retry = pending.keys.len() > 1;
+        pending.keys.clear();
+        if retry {
+            self.press(pending, press)
+        } else {
+            pending.count = None;
+            KeyMatch::None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_keys(bindings: &KeyBindings, keys: &[KeyPress]) -> Vec<KeyMatch> {
+        let mut pending = PendingKeys::default();
+        keys.iter().map(|&key| bindings.press(&mut pending, key)).collect()
+    }
+
+    #[test]
+    fn test_default_bindings() {
+        let bindings = KeyBindings::default();
+        let p = KeyPress::plain;
+        assert_eq!(
+            type_keys(&bindings, &[p('j'), p('G'), KeyPress::ctrl('u'), p('x')]),
+            vec![
+                KeyMatch::Action(KeyAction::LineDown, None),
+                KeyMatch::Action(KeyAction::Bottom, None),
+                KeyMatch::Action(KeyAction::HalfPageUp, None),
+                KeyMatch::None,
+            ]
+        );
+        assert_eq!(
+            type_keys(&bindings, &[p('g'), p('g')]),
+            vec![KeyMatch::Pending, KeyMatch::Action(KeyAction::Top, None)]
+        );
+        // A broken sequence does not swallow the key that broke it
+        assert_eq!(
+            type_keys(&bindings, &[p('g'), p('k')]),
+            vec![KeyMatch::Pending, KeyMatch::Action(KeyAction::LineUp, None)]
+        );
+        assert_eq!(
+            type_keys(&bindings, &[KeyPress::ctrl('j')]),
+            vec![KeyMatch::None]
+        );
+    }
+
+    #[test]
+    fn test_counts() {
+        let bindings = KeyBindings::default();
+        let p = KeyPress::plain;
+        assert_eq!(
+            type_keys(&bindings, &[p('1'), p('0'), p('7'), p('j'), p('k')]),
+            vec![
+                KeyMatch::Pending,
+                KeyMatch::Pending,
+                KeyMatch::Pending,
+                KeyMatch::Action(KeyAction::LineDown, Some(107)),
+                KeyMatch::Action(KeyAction::LineUp, None),
+            ]
+        );
+        assert_eq!(
+            type_keys(&bindings, &[p('5'), p('g'), p('g')]).last(),
+            Some(&KeyMatch::Action(KeyAction::Top, Some(5)))
+        );
+        // A count does not start with 0, and a key no sequence has drops it
+        assert_eq!(type_keys(&bindings, &[p('0')]), vec![KeyMatch::None]);
+        assert_eq!(
+            type_keys(&bindings, &[p('3'), p('x'), p('j')]),
+            vec![KeyMatch::Pending, KeyMatch::None, KeyMatch::Action(KeyAction::LineDown, None)]
+        );
+        // A digit bound to an action is that action
+        let bindings = bindings.with_overrides(&[(KeyAction::Top, parse_sequences("1").unwrap())]);
+        assert_eq!(
+            type_keys(&bindings, &[p('2'), p('1')]),
+            vec![KeyMatch::Pending, KeyMatch::Action(KeyAction::Top, Some(2))]
+        );
+    }
+
+    #[test]
+    fn test_parse_sequences() {
+        assert_eq!(
+            parse_sequences("gg, Ctrl+G,Ctrl+gx"),
+            Ok(vec![
+                vec![KeyPress::plain('g'), KeyPress::plain('g')],
+                vec![KeyPress::ctrl('g')],
+                vec![KeyPress::ctrl('g'), KeyPress::plain('x')],
+            ])
+        );
+        assert_eq!(parse_sequences(""), Ok(vec![]));
+        assert!(parse_sequences("Ctrl+").is_err());
+        assert!(parse_sequences("g g").is_err());
+    }
+
+    #[test]
+    fn test_rebind() {
+        let bindings = KeyBindings::default().with_overrides(&[
+            (KeyAction::LineDown, parse_sequences("n").unwrap()),
+            (KeyAction::Search, vec![]),
+        ]);
+        let p = KeyPress::plain;
+        assert_eq!(
+            type_keys(&bindings, &[p('n'), p('j'), p('/')]),
+            vec![KeyMatch::Action(KeyAction::LineDown, None), KeyMatch::None, KeyMatch::None]
+        );
+        assert_eq!(KeyAction::from_config_key("key_top"), Some(KeyAction::Top));
+        assert_eq!(KeyAction::from_config_key("key_mark"), Some(KeyAction::Mark));
+        assert_eq!(KeyAction::from_config_key("key_pin"), Some(KeyAction::Pin));
+        assert_eq!(KeyAction::from_config_key("key_sideways"), None);
+    }
+}
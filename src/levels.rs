@@ -0,0 +1,203 @@
+use std::fmt;
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::search::SearchMatch;
+use crate::theme::Theme;
+
+/// Severity words are only looked for near the start of a line, where the
+/// level of a log line is written, not in its message.
+const LEVEL_SEARCH_PREFIX: usize = 120;
+
+/// Severity of a log line, from its level word.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    Fatal,
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl LogLevel {
+    /// The level a severity word stands for, in any case.
+    pub fn from_word(word: &str) -> Option<Self> {
+        match word.to_lowercase().as_str() {
+            "fatal" | "critical" | "crit" | "panic" => Some(LogLevel::Fatal),
+            "error" | "err" | "severe" => Some(LogLevel::Error),
+            "warn" | "warning" => Some(LogLevel::Warn),
+            "info" | "notice" => Some(LogLevel::Info),
+            "debug" | "trace" => Some(LogLevel::Debug),
+            _ => None,
+        }
+    }
+
+    /// Background given to lines or words of this level unless the config
+    /// sets one. Info and debug lines, the bulk of most logs, are left alone.
+    fn default_color(self, theme: Theme) -> Option<&'static str> {
+        match (self, theme) {
+            (LogLevel::Fatal, Theme::Light) => Some("#F28B82"),
+            (LogLevel::Error, Theme::Light) => Some("#FAD2CF"),
+            (LogLevel::Warn, Theme::Light) => Some("#FEEFC3"),
+            (LogLevel::Fatal, Theme::Dark) => Some("#8C1D18"),
+            (LogLevel::Error, Theme::Dark) => Some("#5C2B29"),
+            (LogLevel::Warn, Theme::Dark) => Some("#594A1A"),
+            (LogLevel::Info | LogLevel::Debug, _) => None,
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogLevel::Fatal => write!(f, "fatal"),
+            LogLevel::Error => write!(f, "error"),
+            LogLevel::Warn => write!(f, "warn"),
+            LogLevel::Info => write!(f, "info"),
+            LogLevel::Debug => write!(f, "debug"),
+        }
+    }
+}
+
+fn level_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        // Upper-case words anywhere, any case in `[error]` or `level=error`
+        Regex::new(
+            r#"\b(FATAL|CRITICAL|CRIT|PANIC|ERROR|ERR|SEVERE|WARNING|WARN|NOTICE|INFO|DEBUG|TRACE)\b|(?i:\[|\blevel=|\bseverity=)"?((?i:fatal|critical|crit|panic|error|err|severe|warning|warn|notice|info|debug|trace))\b"#,
+        )
+        .unwrap()
+    })
+}
+
+/// The level of a line and the character columns (start, exclusive end) of
+/// its severity word, if one starts near the beginning of the line.
+pub fn detect(text: &str) -> Option<(LogLevel, usize, usize)> {
+    let caps = level_regex().captures(text)?;
+    let word = caps.get(1).or_else(|| caps.get(2))?;
+    if word.start() > LEVEL_SEARCH_PREFIX {
+        return None;
+    }
+    let level = LogLevel::from_word(word.as_str())?;
+    let m = SearchMatch::from_regex_match(0, text, word);
+    Some((level, m.start_col, m.end_col))
+}
+
+/// What severity coloring covers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LevelStyle {
+    /// The whole line is tinted
+    #[default]
+    Line,
+    /// Only the severity word is colored
+    Word,
+    Off,
+}
+
+impl LevelStyle {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "line" => Ok(LevelStyle::Line),
+            "word" => Ok(LevelStyle::Word),
+            "off" => Ok(LevelStyle::Off),
+            _ => Err(format!("invalid level style: {} (expected line, word or off)", s)),
+        }
+    }
+}
+
+impl fmt::Display for LevelStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelStyle::Line => write!(f, "line"),
+            LevelStyle::Word => write!(f, "word"),
+            LevelStyle::Off => write!(f, "off"),
+        }
+    }
+}
+
+/// Per-level colors from the config file (`level_colors = error:#ffcccc,
+/// info:none`), over the theme's defaults. `None` leaves a level uncolored.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LevelColors {
+    overrides: Vec<(LogLevel, Option<String>)>,
+}
+
+impl LevelColors {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut overrides = Vec::new();
+        for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (level, color) = entry
+                .split_once(':')
+                .ok_or_else(|| format!("invalid level color: {} (expected level:color)", entry))?;
+            let level = LogLevel::from_word(level.trim())
+                .ok_or_else(|| format!("unknown log level: {}", level.trim()))?;
+            let color = match color.trim() {
+                "" => return Err(format!("missing color for {}", level)),
+                "none" => None,
+                color => Some(color.to_string()),
+            };
+            overrides.push((level, color));
+        }
+        Ok(Self { overrides })
+    }
+
+    /// The color of `level` lines in `theme`, the config's last word on it first.
+    pub fn color(&self, level: LogLevel, theme: Theme) -> Option<&str> {
+        match self.overrides.iter().rev().find(|(l, _)| *l == level) {
+            Some((_, color)) => color.as_deref(),
+            None => level.default_color(theme),
+        }
+    }
+
+    /// The columns of `text` to color for its level, and their color.
+    pub fn span(&self, text: &str, style: LevelStyle, theme: Theme) -> Option<(usize, usize, &str)> {
+        if style == LevelStyle::Off {
+            return None;
+        }
+        let (level, start_col, end_col) = detect(text)?;
+        let color = self.color(level, theme)?;
+        match style {
+            LevelStyle::Word => Some((start_col, end_col, color)),
+            _ => Some((0, text.chars().count(), color)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_levels() {
+        assert_eq!(
+            detect("2024-01-15 10:00:00 ERROR db: timeout"),
+            Some((LogLevel::Error, 20, 25))
+        );
+        assert_eq!(detect("[warning] disk at 91%"), Some((LogLevel::Warn, 1, 8)));
+        assert_eq!(detect("ts=1 level=debug msg=hi"), Some((LogLevel::Debug, 11, 16)));
+        assert_eq!(detect("é CRIT x"), Some((LogLevel::Fatal, 2, 6)));
+        // Lower-case words in a message are not levels
+        assert_eq!(detect("request finished without error"), None);
+        assert_eq!(detect("INFORMATION"), None);
+        let late = format!("{} ERROR", "x".repeat(200));
+        assert_eq!(detect(&late), None);
+    }
+
+    #[test]
+    fn test_level_colors() {
+        let colors = LevelColors::parse("error:#ff0000, info: lightblue ,warn:none").unwrap();
+        assert_eq!(colors.color(LogLevel::Error, Theme::Dark), Some("#ff0000"));
+        assert_eq!(colors.color(LogLevel::Info, Theme::Light), Some("lightblue"));
+        assert_eq!(colors.color(LogLevel::Warn, Theme::Light), None);
+        assert_eq!(colors.color(LogLevel::Fatal, Theme::Light), Some("#F28B82"));
+        assert!(LevelColors::parse("loud:red").is_err());
+        assert!(LevelColors::parse("error").is_err());
+
+        let text = "E: ERROR bad";
+        assert_eq!(colors.span(text, LevelStyle::Line, Theme::Dark), Some((0, 12, "#ff0000")));
+        assert_eq!(colors.span(text, LevelStyle::Word, Theme::Dark), Some((3, 8, "#ff0000")));
+        assert_eq!(colors.span(text, LevelStyle::Off, Theme::Dark), None);
+        assert_eq!(colors.span("DEBUG x", LevelStyle::Line, Theme::Dark), None);
+    }
+}
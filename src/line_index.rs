@@ -0,0 +1,209 @@
+use crate::encoding::TextEncoding;
+use crate::file_loader::for_each_line_start;
+
+/// Lines between two recorded line starts in a `SparseLineIndex`.
+pub const SPARSE_INDEX_STRIDE: usize = 64;
+
+/// Line index that records where every `SPARSE_INDEX_STRIDE`th line starts
+/// and finds the lines in between by scanning forward from the nearest one.
+///
+/// Costs 8 bytes per `SPARSE_INDEX_STRIDE` lines instead of 8 bytes per line,
+/// for a scan of at most that many lines per lookup. Sequential reads through
+/// `lines` scan each line only once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseLineIndex {
+    /// Byte offset of lines 0, STRIDE, 2 * STRIDE, ...
+    checkpoints: Vec<usize>,
+    line_count: usize,
+}
+
+impl Default for SparseLineIndex {
+    /// Index of empty data, which has one (empty) line.
+    fn default() -> Self {
+        Self {
+            checkpoints: vec![0],
+            line_count: 1,
+        }
+    }
+}
+
+impl SparseLineIndex {
+    pub fn build(data: &[u8], encoding: TextEncoding) -> Self {
+        let mut index = Self::default();
+        index.extend(data, 0, encoding);
+        index
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_count
+    }
+
+    /// Record the next line, which starts at byte `offset`.
+    pub fn push(&mut self, offset: usize) {
+        if self.line_count.is_multiple_of(SPARSE_INDEX_STRIDE) {
+            self.checkpoints.push(offset);
+        }
+        self.line_count += 1;
+    }
+
+    /// Add the lines of `data[indexed_len..]` to an index built for `data[..indexed_len]`.
+    pub fn extend(&mut self, data: &[u8], indexed_len: usize, encoding: TextEncoding) {
+        for_each_line_start(data, indexed_len, encoding, |offset| self.push(offset));
+    }
+
+    /// Byte offset where line `line_num` (below `line_count`) starts.
+    pub fn line_start(&self, data: &[u8], line_num: usize, encoding: TextEncoding) -> usize {
+        let mut offset = self.checkpoints[line_num / SPARSE_INDEX_STRIDE];
+        for _ in 0..line_num % SPARSE_INDEX_STRIDE {
+            offset = next_line_start(data, offset, encoding);
+        }
+        offset
+    }
+
+    /// Line holding byte `offset`, or the last line for offsets past it.
+    pub fn line_at(&self, data: &[u8], offset: usize, encoding: TextEncoding) -> usize {
+        let checkpoint = self.checkpoints.partition_point(|&start| start <= offset).max(1) - 1;
+        let mut line = checkpoint * SPARSE_INDEX_STRIDE;
+        let mut start = self.checkpoints[checkpoint];
+        while line + 1 < self.line_count {
+            start = next_line_start(data, start, encoding);
+            if start > offset {
+                break;
+            }
+            line += 1;
+        }
+        line
+    }
+
+    /// Bytes of lines `start_line..`, each including its terminator.
+    pub fn lines<'a>(
+        &self,
+        data: &'a [u8],
+        start_line: usize,
+        encoding: TextEncoding,
+    ) -> SparseLines<'a> {
+        let pos = if start_line < self.line_count {
+            self.line_start(data, start_line, encoding)
+        } else {
+            data.len()
+        };
+        SparseLines {
+            data,
+            encoding,
+            line: start_line,
+            end_line: self.line_count,
+            pos,
+        }
+    }
+}
+
+/// Offset just past the first newline at or after `from`, or the end of `data`.
+fn next_line_start(data: &[u8], from: usize, encoding: TextEncoding) -> usize {
+    let newline = encoding.newline();
+    let rest = &data[from.min(data.len())..];
+    let found = if newline.len() == 1 {
+        rest.iter().position(|&b| b == b'\n')
+    } else {
+        // Line starts are code-unit aligned, so aligned chunks are code units
+        rest.chunks_exact(newline.len())
+            .position(|unit| unit == newline)
+            .map(|i| i * newline.len())
+    };
+    match found {
+        Some(i) => from + i + newline.len(),
+        None => data.len(),
+    }
+}
+
+/// Iterator over `(line number, bytes)` returned by `SparseLineIndex::lines`.
+pub struct SparseLines<'a> {
+    data: &'a [u8],
+    encoding: TextEncoding,
+    line: usize,
+    end_line: usize,
+    pos: usize,
+}
+
+impl<'a> Iterator for SparseLines<'a> {
+    type Item = (usize, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.line >= self.end_line {
+            return None;
+        }
+        let start = self.pos;
+        self.pos = next_line_start(self.data, start, self.encoding);
+        self.line += 1;
+        Some((self.line - 1, &self.data[start..self.pos]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_loader::{build_line_index, line_bytes_at};
+
+    fn assert_matches_dense(data: &[u8], encoding: TextEncoding) {
+        let dense = build_line_index(data, encoding);
+        let sparse = SparseLineIndex::build(data, encoding);
+        assert_eq!(sparse.line_count(), dense.len());
+        for start in 0..dense.len() {
+            let (line_num, bytes) = sparse.lines(data, start, encoding).next().unwrap();
+            assert_eq!(line_num, start);
+            assert_eq!(
+                Some(bytes),
+                line_bytes_at(data, &dense, start),
+                "line {}",
+                start
+            );
+        }
+        let all: Vec<&[u8]> = sparse.lines(data, 0, encoding).map(|(_, b)| b).collect();
+        assert_eq!(all.len(), dense.len());
+    }
+
+    #[test]
+    fn test_sparse_index_matches_dense() {
+        let utf8 = TextEncoding::default();
+        assert_matches_dense(b"", utf8);
+        assert_matches_dense(b"one\ntwo\n\nthree", utf8);
+        let many: String = (0..SPARSE_INDEX_STRIDE * 3 + 5)
+            .map(|i| format!("{}\n", i))
+            .collect();
+        assert_matches_dense(many.as_bytes(), utf8);
+
+        let utf16 = TextEncoding::parse("utf-16le").unwrap();
+        assert_matches_dense(b"a\0\n\0\n\x0a\n\0b\0", utf16);
+    }
+
+    #[test]
+    fn test_line_at_offset() {
+        let utf8 = TextEncoding::default();
+        let data: String = (0..SPARSE_INDEX_STRIDE * 2 + 3)
+            .map(|i| format!("{}\n", i))
+            .collect();
+        let data = data.as_bytes();
+        let index = SparseLineIndex::build(data, utf8);
+        let stride = SPARSE_INDEX_STRIDE;
+        for line in [0, 1, stride - 1, stride, stride * 2 + 2] {
+            let start = index.line_start(data, line, utf8);
+            assert_eq!(index.line_at(data, start, utf8), line);
+            assert_eq!(index.line_at(data, start + 1, utf8), line, "line {}", line);
+        }
+        // The end of the data and anything past it fall in the last line
+        assert_eq!(index.line_at(data, data.len(), utf8), index.line_count() - 1);
+        assert_eq!(index.line_at(data, data.len() + 10, utf8), index.line_count() - 1);
+        assert_eq!(SparseLineIndex::default().line_at(b"", 0, utf8), 0);
+    }
+
+    #[test]
+    fn test_sparse_index_memory() {
+        let data = "x\n".repeat(SPARSE_INDEX_STRIDE * 10);
+        let index = SparseLineIndex::build(data.as_bytes(), TextEncoding::default());
+        assert_eq!(index.line_count(), SPARSE_INDEX_STRIDE * 10);
+        assert_eq!(index.checkpoints.len(), 10);
+        assert!(index
+            .lines(data.as_bytes(), index.line_count(), TextEncoding::default())
+            .next()
+            .is_none());
+    }
+}
@@ -0,0 +1,256 @@
+use regex::Regex;
+
+use crate::error;
+use crate::file_source::FileSource;
+use crate::search::{self, SearchMatch};
+use crate::table::TableCell;
+
+/// Pairs a line needs to be taken for a logfmt line, so a stray `a=b` in a
+/// plain message is left alone.
+const MIN_PAIRS: usize = 2;
+
+/// A `key=value` pair of a logfmt line, in character columns (start,
+/// exclusive end). Quotes around a value are outside its columns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pair {
+    pub key: String,
+    pub key_cols: (usize, usize),
+    pub value_cols: (usize, usize),
+    /// The whole pair, quotes included
+    pub cols: (usize, usize),
+}
+
+fn is_key_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '/' | '@')
+}
+
+/// Every `key=value` pair of `text`, wherever it is on the line. Words
+/// that are not pairs, like a leading timestamp, are skipped.
+fn parse_pairs(text: &str) -> Vec<Pair> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut pairs = Vec::new();
+    let mut col = 0;
+    while col < chars.len() {
+        if chars[col].is_whitespace() {
+            col += 1;
+            continue;
+        }
+        let start = col;
+        while col < chars.len() && is_key_char(chars[col]) {
+            col += 1;
+        }
+        if col == start || chars.get(col) != Some(&'=') {
+            // Not a pair: on to the next word
+            while col < chars.len() && !chars[col].is_whitespace() {
+                col += 1;
+            }
+            continue;
+        }
+        let key_cols = (start, col);
+        col += 1;
+        let value_cols = if chars.get(col) == Some(&'"') {
+            let value_start = col + 1;
+            col = value_start;
+            while col < chars.len() && chars[col] != '"' {
+                // A backslash escapes the next character, quotes included
+                col += if chars[col] == '\\' { 2 } else { 1 };
+            }
+            let value_end = col.min(chars.len());
+            col = (col + 1).min(chars.len());
+            (value_start, value_end)
+        } else {
+            let value_start = col;
+            while col < chars.len() && !chars[col].is_whitespace() {
+                col += 1;
+            }
+            (value_start, col)
+        };
+        pairs.push(Pair {
+            key: chars[key_cols.0..key_cols.1].iter().collect(),
+            key_cols,
+            value_cols,
+            cols: (start, col),
+        });
+    }
+    pairs
+}
+
+/// The pairs of `text` if it is a logfmt line, with at least MIN_PAIRS of
+/// them; empty otherwise.
+pub fn pairs(text: &str) -> Vec<Pair> {
+    let pairs = parse_pairs(text);
+    if pairs.len() < MIN_PAIRS {
+        return Vec::new();
+    }
+    pairs
+}
+
+/// Field-aware display of logfmt lines in a window: keys colored, and
+/// chosen keys hidden along with their values.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogfmtView {
+    pub enabled: bool,
+    hidden_keys: Vec<String>,
+}
+
+impl LogfmtView {
+    pub fn new(enabled: bool) -> Self {
+        LogfmtView {
+            enabled,
+            hidden_keys: Vec::new(),
+        }
+    }
+
+    pub fn hide(&mut self, key: &str) {
+        if !self.hidden_keys.iter().any(|k| k == key) {
+            self.hidden_keys.push(key.to_string());
+        }
+    }
+
+    /// Show `key` again, or every hidden key; returns whether any was hidden.
+    pub fn show(&mut self, key: Option<&str>) -> bool {
+        let before = self.hidden_keys.len();
+        match key {
+            Some(key) => self.hidden_keys.retain(|k| k != key),
+            None => self.hidden_keys.clear(),
+        }
+        self.hidden_keys.len() != before
+    }
+
+    pub fn hidden_keys(&self) -> &[String] {
+        &self.hidden_keys
+    }
+
+    /// Columns of the keys of `text` to color, none when off or when the
+    /// line is not a logfmt line.
+    pub fn key_ranges(&self, text: &str) -> Vec<(usize, usize)> {
+        if !self.enabled {
+            return Vec::new();
+        }
+        pairs(text).into_iter().map(|pair| pair.key_cols).collect()
+    }
+
+    /// `text` without the pairs of hidden keys and the spaces after them,
+    /// as cells of the characters still shown; None when nothing is hidden
+    /// on this line.
+    pub fn render(&self, text: &str) -> Option<Vec<TableCell>> {
+        if !self.enabled || self.hidden_keys.is_empty() {
+            return None;
+        }
+        let chars: Vec<char> = text.chars().collect();
+        let mut hidden = vec![false; chars.len()];
+        let mut any = false;
+        for pair in pairs(text) {
+            if self.hidden_keys.contains(&pair.key) {
+                any = true;
+                let mut end = pair.cols.1;
+                while end < chars.len() && chars[end].is_whitespace() {
+                    end += 1;
+                }
+                hidden[pair.cols.0..end].fill(true);
+            }
+        }
+        any.then(|| {
+            chars
+                .iter()
+                .zip(&hidden)
+                .enumerate()
+                .filter(|(_, (_, &hidden))| !hidden)
+                .map(|(col, (&ch, _))| TableCell { ch, source_col: Some(col) })
+                .collect()
+        })
+    }
+}
+
+/// A source searched only in the value of one logfmt key: lines without
+/// the key have no matches, and match columns count from the line's start.
+pub struct KeyView<'a> {
+    source: &'a dyn FileSource,
+    key: &'a str,
+}
+
+impl<'a> KeyView<'a> {
+    pub fn new(source: &'a dyn FileSource, key: &'a str) -> Self {
+        Self { source, key }
+    }
+}
+
+impl FileSource for KeyView<'_> {
+    fn line_count(&self) -> usize {
+        self.source.line_count()
+    }
+
+    fn file_size(&self) -> error::Result<u64> {
+        self.source.file_size()
+    }
+
+    fn get_line(&self, line_num: usize) -> error::Result<Option<String>> {
+        self.source.get_line(line_num)
+    }
+
+    fn get_lines(&self, start_line: usize, count: usize) -> error::Result<Vec<(usize, String)>> {
+        self.source.get_lines(start_line, count)
+    }
+
+    fn display_name(&self) -> &str {
+        self.source.display_name()
+    }
+
+    fn search_range(
+        &self,
+        pattern: &Regex,
+        start_line: usize,
+        end_line: usize,
+    ) -> error::Result<Vec<SearchMatch>> {
+        let lines = self.source.get_lines(start_line, end_line.saturating_sub(start_line))?;
+        let mut matches = Vec::new();
+        for (line_num, text) in lines {
+            // Any pair counts, also on lines with too few to color
+            let Some(pair) = parse_pairs(&text).into_iter().find(|pair| pair.key == self.key)
+            else {
+                continue;
+            };
+            let (start, end) = pair.value_cols;
+            let value: String = text.chars().skip(start).take(end - start).collect();
+            for mut m in search::search_lines(pattern, &[(line_num, value)]) {
+                m.shift_columns(start);
+                matches.push(m);
+            }
+        }
+        Ok(matches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pairs() {
+        let text = "2024-05-01 ts=1 msg=\"a \\\"b\\\" c\" level=info é=x";
+        let pairs = pairs(text);
+        let keys: Vec<&str> = pairs.iter().map(|pair| pair.key.as_str()).collect();
+        assert_eq!(keys, vec!["ts", "msg", "level", "é"]);
+        assert_eq!(pairs[0].key_cols, (11, 13));
+        assert_eq!(pairs[1].value_cols, (21, 30));
+        assert_eq!(pairs[1].cols, (16, 31));
+        assert_eq!(pairs[3].value_cols, (45, 46));
+        assert!(super::pairs("retry with a=b").is_empty());
+        assert_eq!(super::pairs("x= y=").len(), 2);
+    }
+
+    #[test]
+    fn test_hide_keys() {
+        let mut view = LogfmtView::new(true);
+        let text = "at=1 caller=main.go:12 msg=hi";
+        assert_eq!(view.render(text), None);
+        assert_eq!(view.key_ranges(text), vec![(0, 2), (5, 11), (23, 26)]);
+        view.hide("caller");
+        let cells = view.render(text).unwrap();
+        assert_eq!(cells.iter().map(|cell| cell.ch).collect::<String>(), "at=1 msg=hi");
+        assert_eq!(cells[5].source_col, Some(23));
+        assert!(view.show(None));
+        assert!(!view.show(Some("caller")));
+        assert!(LogfmtView::new(false).key_ranges(text).is_empty());
+    }
+}
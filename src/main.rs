@@ -1,14 +1,53 @@
+mod archive_loader;
+mod bench;
+mod bidi;
 mod cache;
+mod columns;
 mod commands;
+mod compressed_loader;
+mod concat_source;
+mod config;
+mod ctl;
+mod doctor;
+mod encoding;
 mod error;
 mod file_loader;
 mod file_source;
+mod filter;
+mod font;
+mod hexview;
+mod highlight;
+mod json;
+mod keys;
+mod levels;
+mod line_index;
+mod logfmt;
+mod marks;
+mod merge_source;
+mod pread_loader;
+mod registry;
+mod remote_command;
 mod remote_loader;
+mod rules;
+mod s3_loader;
+mod saved_search;
 mod search;
 mod server;
+mod sftp;
+mod sftp_loader;
+mod ssh;
+mod stream_loader;
+mod table;
+mod theme;
+mod timestamp;
+mod watcher;
+mod websocket;
+mod worker;
 
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
@@ -19,53 +58,313 @@ use gtk4::glib;
 use gtk4::prelude::*;
 use gtk4::{
     Adjustment, Application, ApplicationWindow, Button, CssProvider, Entry, Label, Orientation,
-    Overlay, PolicyType, ScrolledWindow, Box as GtkBox, Scrollbar, STYLE_PROVIDER_PRIORITY_APPLICATION,
+    Overlay, PolicyType, ScrolledWindow, Box as GtkBox, Scrollbar, ToggleButton,
+    STYLE_PROVIDER_PRIORITY_APPLICATION,
 };
 
-use commands::{CommandResponse, PogCommand};
+use commands::{
+    frame_fields, frame_highlights, frame_lines, frame_responses, help_text, parse_goto_percent,
+    CommandResponse, FoldAction, LogfmtAction, PercentOf, PogCommand, TableAction, ViewerEvent,
+    PROTOCOL_VERSION,
+};
+use compressed_loader::CompressedFile;
+use concat_source::ConcatSource;
+use merge_source::MergedSource;
 use file_loader::MappedFile;
-use file_source::FileSource;
-use remote_loader::RemoteFile;
-use search::{SearchDirection, SearchMatch, SearchState};
-use server::CommandRequest;
-
-#[derive(Debug, Clone, PartialEq)]
-pub struct Region {
-    pub start_col: usize,  // 0-based
-    pub end_col: usize,    // exclusive
-    pub color: String,
+use file_source::{ConnectionState, EmptySource, FileSource, SourceChange};
+use filter::{FilteredLines, FilteredView, FoldMode, LineFilter, LineFold};
+use hexview::ViewMode;
+use highlight::HighlightRule;
+use marks::{Annotations, Bookmarks, LineMarkings, MarksFile};
+use pread_loader::PreadFile;
+use remote_loader::{RemoteFile, RemoteOptions, RemoteTransport};
+use rules::{ColorRule, RuleSet, TextStyle};
+use s3_loader::S3File;
+use saved_search::{SavedSearch, SavedSearches};
+use sftp_loader::SftpFile;
+use ssh::SshTarget;
+use stream_loader::StreamSource;
+use columns::{ColumnLayout, FieldStyle};
+use config::Config;
+use encoding::TextEncoding;
+use font::FontSettings;
+use keys::{KeyAction, KeyBindings, KeyMatch, KeyPress, PendingKeys};
+use levels::{LevelColors, LevelStyle};
+use logfmt::LogfmtView;
+use search::{
+    describe_match_count, CaseMode, SearchConfig, SearchDirection, SearchMatch, SearchState,
+    MULTILINE_WINDOW,
+};
+use server::{CommandRequest, CommandServer, EventHub};
+use table::{Delimiter, TableCell, TableLayout};
+use theme::{Theme, ThemeChoice};
+use timestamp::Timestamp;
+use worker::{FileRequest, FileResponse, SearchField, SearchScope, WorkerRegistry};
+
+/// How a view shows lines longer than `max_columns` characters.
+struct LongLines {
+    max_columns: usize,
+    /// Lines shown in full anyway
+    expanded: RefCell<HashSet<usize>>,
+    /// Re-requests the viewport
+    redraw: Box<dyn Fn()>,
+}
+
+impl LongLines {
+    /// Expand `line_num` if it is truncated, otherwise collapse it again.
+    fn toggle(&self, line_num: usize) {
+        {
+            let mut expanded = self.expanded.borrow_mut();
+            if !expanded.remove(&line_num) {
+                expanded.insert(line_num);
+            }
+        }
+        (self.redraw)();
+    }
+}
+
+/// Follows a link after a view line's text, given its name and the line
+type FoldLink = Rc<dyn Fn(&str, usize)>;
+
+/// Shows an error toast, with an action retrying what failed if given
+type ShowError = Rc<dyn Fn(&str, Option<Rc<dyn Fn()>>)>;
+
+/// Opens a file in a window's place
+type OpenFile = Rc<dyn Fn(&FilePath) -> Result<(), String>>;
+
+/// Puts a view's lines on the clipboard, given the first and how many,
+/// resolving to how many were copied
+type CopyLines = Rc<dyn Fn(usize, usize) -> Pin<Box<dyn Future<Output = Result<usize, String>>>>>;
+
+/// Handles a key pressed in a window, as its key controller does
+type KeyHandler = Rc<dyn Fn(gtk4::gdk::Key, gtk4::gdk::ModifierType) -> glib::Propagation>;
+
+/// Keys pressed while a socket request holds a window's input, handled in
+/// order once it is done.
+#[derive(Default)]
+struct HeldKeys {
+    held: Cell<bool>,
+    keys: RefCell<Vec<(gtk4::gdk::Key, gtk4::gdk::ModifierType)>>,
+}
+
+/// The labels showing the visible lines, kept from one redraw to the next:
+/// new lines are bound to the labels already there instead of rebuilding
+/// them, so scrolling neither flickers nor churns widgets.
+struct LineRows {
+    line_numbers_box: GtkBox,
+    content_box: GtkBox,
+    long_lines: Rc<LongLines>,
+    /// Follows an `unfold` or `unhide` link after a view line's text
+    fold_link: FoldLink,
+    rows: RefCell<Vec<LineRow>>,
+    /// Long lines wrap onto more rows instead of scrolling sideways
+    wrap: Cell<bool>,
+    /// Rows other than the cursor line's are numbered by their distance
+    /// from it, as the count of `17j` or `5k` takes them
+    relative: Cell<bool>,
+    /// The other end of the lines selected up to the cursor line
+    anchor: Cell<Option<usize>>,
+}
+
+/// One visible line: its number in the sidebar and its text.
+#[derive(Clone)]
+struct LineRow {
+    number: Label,
+    content: Label,
+    /// The view line the row shows, for the links after its text
+    line_num: Rc<Cell<usize>>,
+    /// The sidebar's text around the line number, which alone changes as
+    /// the cursor moves
+    number_text: Rc<RefCell<NumberText>>,
 }
 
-#[derive(Debug, Clone, Default)]
-pub struct LineMarkings {
-    pub full_line_color: Option<String>,
-    pub regions: Vec<Region>,
+/// A row's sidebar text: the time column and annotation mark, the line's
+/// number in the file, and the padding of a wrapped line's extra rows. In a
+/// merged view, the color of the file the line comes from goes first.
+#[derive(Default)]
+struct NumberText {
+    origin_color: Option<&'static str>,
+    prefix: String,
+    file_line: usize,
+    padding: String,
 }
 
-impl LineMarkings {
-    pub fn is_empty(&self) -> bool {
-        self.full_line_color.is_none() && self.regions.is_empty()
+impl LineRows {
+    fn new(
+        line_numbers_box: &GtkBox,
+        content_box: &GtkBox,
+        long_lines: Rc<LongLines>,
+        fold_link: FoldLink,
+    ) -> Self {
+        LineRows {
+            line_numbers_box: line_numbers_box.clone(),
+            content_box: content_box.clone(),
+            long_lines,
+            fold_link,
+            rows: RefCell::new(Vec::new()),
+            wrap: Cell::new(false),
+            relative: Cell::new(false),
+            anchor: Cell::new(None),
+        }
+    }
+
+    /// Wrap long lines onto more rows, or show each on one row again.
+    fn set_wrap(&self, wrap: bool) {
+        self.wrap.set(wrap);
+        for row in self.rows.borrow().iter() {
+            row.content.set_wrap(wrap);
+        }
+    }
+
+    /// Characters fitting on one row of the content box while wrapping, or
+    /// None when not wrapping or before the box has its size.
+    fn wrap_columns(&self) -> Option<usize> {
+        if !self.wrap.get() {
+            return None;
+        }
+        let width = self.content_box.width();
+        let rows = self.rows.borrow();
+        // Measured on a line label, so the font is the lines' monospace one
+        let char_width = rows.first()?.content.create_pango_layout(Some("0")).pixel_size().0;
+        (width > 0 && char_width > 0).then(|| (width / char_width) as usize)
+    }
+
+    /// The row at `index`, shown, and added to the boxes the first time
+    /// the view holds that many lines.
+    fn row(&self, index: usize) -> LineRow {
+        let mut rows = self.rows.borrow_mut();
+        while rows.len() <= index {
+            let number = Label::new(None);
+            number.set_halign(gtk4::Align::End);
+            self.line_numbers_box.append(&number);
+
+            let content = Label::new(None);
+            // The full width, so the cursor line's background spans the view
+            content.set_halign(gtk4::Align::Fill);
+            // Right-to-left lines keep their bidi reordering but stay left-aligned
+            content.set_xalign(0.0);
+            content.set_selectable(true);
+            // Wrapped at any character, so a row holds exactly `wrap_columns`
+            content.set_wrap_mode(gtk4::pango::WrapMode::Char);
+            content.set_wrap(self.wrap.get());
+            let line_num = Rc::new(Cell::new(0));
+            let long_lines = self.long_lines.clone();
+            let fold_link = self.fold_link.clone();
+            let link_line = line_num.clone();
+            content.connect_activate_link(move |_, uri| {
+                match uri {
+                    "expand" | "collapse" => long_lines.toggle(link_line.get()),
+                    _ => fold_link(uri, link_line.get()),
+                }
+                glib::Propagation::Stop
+            });
+            self.content_box.append(&content);
+
+            rows.push(LineRow {
+                number,
+                content,
+                line_num,
+                number_text: Rc::new(RefCell::new(NumberText::default())),
+            });
+        }
+        let row = rows[index].clone();
+        row.number.set_visible(true);
+        row.content.set_visible(true);
+        row
+    }
+
+    /// Highlight the row showing view line `cursor`, if it is on screen,
+    /// and the selected rows from the anchor to it, and number the rows.
+    fn show_cursor(&self, cursor: usize) {
+        self.show_numbers(cursor);
+        let selected = self.selection(cursor);
+        for row in self.rows.borrow().iter() {
+            let line = row.line_num.get();
+            let shown = row.content.is_visible();
+            for (class, on) in [
+                ("cursor-line", line == cursor),
+                ("selected-line", selected.as_ref().is_some_and(|lines| lines.contains(&line))),
+            ] {
+                if on && shown {
+                    row.number.add_css_class(class);
+                    row.content.add_css_class(class);
+                } else {
+                    row.number.remove_css_class(class);
+                    row.content.remove_css_class(class);
+                }
+            }
+        }
+    }
+
+    /// Number the shown rows by their lines in the file or, when relative,
+    /// by their distance from the cursor line. With the cursor scrolled
+    /// away, distances count from the nearest row, where the keys move it
+    /// from.
+    fn show_numbers(&self, cursor: usize) {
+        let rows = self.rows.borrow();
+        let shown: Vec<&LineRow> = rows.iter().filter(|row| row.content.is_visible()).collect();
+        let (Some(first), Some(last)) = (shown.first(), shown.last()) else {
+            return;
+        };
+        let cursor = cursor.clamp(first.line_num.get(), last.line_num.get());
+        for row in shown {
+            let text = row.number_text.borrow();
+            let line = row.line_num.get();
+            let number = if self.relative.get() && line != cursor {
+                line.abs_diff(cursor)
+            } else {
+                text.file_line + 1
+            };
+            let number = format!("{}{:>8}{}", text.prefix, number, text.padding);
+            match text.origin_color {
+                Some(color) => row.number.set_markup(&format!(
+                    "<span background=\"{}\"> </span>{}",
+                    color,
+                    glib::markup_escape_text(&number)
+                )),
+                None => row.number.set_text(&number),
+            }
+        }
+    }
+
+    /// The view lines selected from the anchor to `cursor`, if any are.
+    fn selection(&self, cursor: usize) -> Option<std::ops::RangeInclusive<usize>> {
+        self.anchor.get().map(|anchor| anchor.min(cursor)..=anchor.max(cursor))
+    }
+
+    /// Hide the rows from `count` on, left over from a longer page.
+    fn hide_from(&self, count: usize) {
+        for row in self.rows.borrow().iter().skip(count) {
+            row.number.set_visible(false);
+            row.content.set_visible(false);
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 pub enum FilePath {
     Local(std::path::PathBuf),
-    Remote { host: String, path: String },
+    Remote { host: String, port: Option<u16>, path: String },
+    S3 { bucket: String, key: String },
+    /// A member of a local zip or tar archive, `archive::member`
+    Archive { archive: std::path::PathBuf, member: String },
 }
 
 impl FilePath {
     pub fn parse(input: &str) -> Self {
-        if let Some(colon_pos) = input.find(':') {
-            let potential_host = &input[..colon_pos];
-            let potential_path = &input[colon_pos + 1..];
-
-            if potential_path.starts_with('/') && !potential_host.contains('/') {
-                return FilePath::Remote {
-                    host: potential_host.to_string(),
-                    path: potential_path.to_string(),
-                };
-            }
+        if let Some((bucket, key)) = s3_loader::parse_s3_url(input) {
+            return FilePath::S3 { bucket, key };
+        }
+
+        if let Some((archive, member)) = archive_loader::parse_member_path(input) {
+            return FilePath::Archive {
+                archive: std::path::PathBuf::from(archive),
+                member: member.to_string(),
+            };
+        }
+
+        if let Some((host, port, path)) = ssh::parse_remote_path(input) {
+            return FilePath::Remote { host, port, path };
         }
 
         FilePath::Local(std::path::PathBuf::from(input))
@@ -79,300 +378,774 @@ fn parse_file_path(s: &str) -> Result<FilePath, String> {
 #[derive(Parser)]
 #[command(name = "pog")]
 #[command(about = "A fast log file viewer")]
+#[command(subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
 struct Args {
-    #[arg(value_parser = parse_file_path)]
-    file: FilePath,
+    #[command(subcommand)]
+    command: Option<Subcommand>,
 
-    #[arg(long, default_value = "9876", help = "Port for the command server")]
-    port: u16,
+    #[arg(value_parser = parse_file_path, help = "File to view; without one, the window waits for a file to be dropped on it or opened with Ctrl+O")]
+    file: Option<FilePath>,
+
+    #[arg(long, help = "Port for the command server [default: 9876, or `port` in the config file]")]
+    port: Option<u16>,
 
     #[arg(long, help = "Disable the command server")]
     no_server: bool,
+
+    #[arg(long, help = "Also open rotated siblings (file.1, file.2.gz, ...) as one continuous view")]
+    rotated: bool,
+
+    #[arg(long, value_name = "FILE", value_parser = parse_file_path, help = "Interleave another log by timestamp (repeatable)")]
+    merge: Vec<FilePath>,
+
+    #[arg(long, value_parser = TextEncoding::parse, help = "Text encoding, e.g. utf-8, latin1, utf-16le, shift_jis [default: detected]")]
+    encoding: Option<TextEncoding>,
+
+    #[arg(long, help = "Read local files with pread instead of memory-mapping them [default: only on network filesystems]")]
+    no_mmap: bool,
+
+    #[arg(long, value_parser = RemoteTransport::parse, default_value = "shell", help = "How remote files are read: shell (tail/head over ssh) or sftp (byte ranges)")]
+    remote_transport: RemoteTransport,
+
+    #[arg(long, value_name = "LINES", help = "Lines fetched per remote command, rounded up to a multiple of 500 [default: adapted to the link]")]
+    chunk_size: Option<std::num::NonZeroUsize>,
+
+    #[arg(long, help = "Fetch remote chunks uncompressed [default: gzip them on the host when it has gzip]")]
+    no_remote_compression: bool,
+
+    #[arg(long, value_name = "OPTION", help = "Pass an option to ssh, e.g. IdentityFile=~/.ssh/prod (repeatable)")]
+    ssh_option: Vec<String>,
+
+    #[arg(long, value_name = "HOST", help = "Reach remote hosts through this jump host (ssh -J)")]
+    jump_host: Option<String>,
+
+    #[arg(long, help = "Lines searched above and below the viewport [default: tuned to source latency]")]
+    search_buffer: Option<usize>,
+
+    #[arg(long, help = "Lines scanned per step when looking for the next match [default: tuned to source latency]")]
+    search_chunk: Option<usize>,
+
+    #[arg(long, help = "Re-search when the viewport gets this close to the searched range's edge")]
+    search_margin: Option<usize>,
+
+    #[arg(long, value_name = "COLS", help = "Draw vertical guides after these columns, e.g. 20,40,80")]
+    column_guides: Option<String>,
+
+    #[arg(long, value_name = "SPECS", help = "Color fixed-width fields, e.g. 1-9:#ccddee,9-17:lightyellow")]
+    fields: Option<String>,
+
+    #[arg(long, value_name = "COLS", help = "Truncate displayed lines after this many characters [default: 2000]")]
+    max_columns: Option<std::num::NonZeroUsize>,
+
+    #[arg(long, value_name = "SECS", help = "Fail socket commands that wait on the file longer than this with ERROR timeout [default: 30]")]
+    command_timeout: Option<std::num::NonZeroUsize>,
+
+    #[arg(long, value_parser = ThemeChoice::parse, help = "Color theme: system, light or dark [default: system]")]
+    theme: Option<ThemeChoice>,
+
+    #[arg(long, value_name = "PATH", help = "Load marks from this JSON file, and save them there when the window closes")]
+    marks: Option<std::path::PathBuf>,
+
+    #[arg(long, value_name = "PORT", help = "Also serve commands over WebSocket on this port, for browser clients")]
+    websocket_port: Option<u16>,
+
+    #[arg(long, value_name = "ORIGIN", help = "Accept WebSocket connections from pages of this origin, e.g. https://dash.example.com (repeatable) [default: local pages only]")]
+    websocket_origin: Vec<String>,
 }
 
-const LINES_PER_PAGE: usize = 50;
-const SEARCH_BUFFER_LINES: usize = 100;
-const SEARCH_HIGHLIGHT_COLOR: &str = "#FFD700";
-const SEARCH_CHUNK_SIZE: usize = 1000;
-
-enum FileRequest {
-    GetLines {
-        start: usize,
-        count: usize,
-        request_id: u64,
-    },
-    SearchRange {
-        pattern: String,
-        start_line: usize,
-        end_line: usize,
-        request_id: u64,
-        navigate_to_first: bool,  // Only navigate to first match on initial search
-    },
-    FindNextMatch {
+#[derive(clap::Subcommand)]
+enum Subcommand {
+    /// Measure indexing, viewport fetch and search performance on a file
+    Bench {
+        #[arg(value_parser = parse_file_path)]
+        file: FilePath,
+
+        #[arg(long, default_value = "200", help = "Number of random viewport fetches")]
+        iterations: usize,
+
+        #[arg(long, default_value = "error", help = "Regex used for the search benchmark")]
         pattern: String,
-        from_line: usize,
-        direction: SearchDirection,
-        request_id: u64,
-        // Channel to send back match info (line, col, len) for synchronous socket response
-        result_tx: Option<std::sync::mpsc::Sender<Option<(usize, usize, usize)>>>,
-    },
-}
 
-#[derive(Debug)]
-enum FileResponse {
-    Lines {
-        lines: Vec<(usize, String)>,
-        request_id: u64,
-        start: usize,
-    },
-    Error {
-        message: String,
+        #[arg(long, help = "Limit the search benchmark to the first N lines")]
+        search_lines: Option<usize>,
     },
-    SearchResults {
-        matches: Vec<SearchMatch>,
-        #[allow(dead_code)]
-        request_id: u64,
-        searched_range: (usize, usize),
-        navigate_to_first: bool,
+    /// Check display, config file, port availability, ssh and remote tools
+    Doctor {
+        #[arg(long, value_name = "[USER@]HOST[:PORT]", help = "Remote host to check ssh connectivity and tools against, reached with --jump-host and --ssh-option")]
+        host: Option<String>,
+
+        #[arg(long, help = "Command server port to check [default: the configured one]")]
+        port: Option<u16>,
     },
-    FoundMatch {
-        #[allow(dead_code)]
-        match_info: Option<SearchMatch>,
-        line_num: Option<usize>,
-        #[allow(dead_code)]
-        request_id: u64,
+    /// List the running pog windows with their command server ports
+    List,
+    /// Send a command to a running pog and print the response
+    Ctl {
+        #[arg(long, help = "Command server port of the pog to control [default: the configured one]")]
+        port: Option<u16>,
+
+        #[arg(
+            trailing_var_arg = true,
+            allow_hyphen_values = true,
+            help = "Command to send; without one, commands are read from stdin, one per line"
+        )]
+        command: Vec<String>,
     },
 }
 
+/// Lines shown at once until the window has its size; see `page_lines`
+const LINES_PER_PAGE: usize = 50;
+/// Most lines `viewport` lets a page hold
+const MAX_PAGE_LINES: usize = 1000;
+/// Characters shown of a line before the rest is hidden behind an expand link
+const DEFAULT_MAX_LINE_COLUMNS: usize = 2000;
+const COLUMN_GUIDE_COLOR: (f64, f64, f64, f64) = (0.5, 0.5, 0.5, 0.35);
+/// Gold of search matches, for the match ticks beside the scrollbar in either theme
+const MATCH_TICK_COLOR: (f64, f64, f64) = (1.0, 0.843, 0.0);
+const OVERVIEW_WIDTH: i32 = 14;
+/// The frame of the lines on screen in the overview, over either theme
+const VIEWPORT_FRAME_COLOR: (f64, f64, f64, f64) = (0.5, 0.5, 0.5, 0.6);
+const H_SCROLL_STEP_PX: f64 = 40.0;
+/// Lines a mouse wheel notch scrolls
+const WHEEL_STEP_LINES: f64 = 3.0;
+/// Most lines one `get` returns
+const MAX_GET_LINES: usize = 10_000;
+/// Matching lines one `grep` returns unless given `--max`
+const DEFAULT_GREP_LINES: usize = 1000;
+/// Most lines one `copy` puts on the clipboard; `export` writes more
+const MAX_COPY_LINES: usize = 100_000;
+/// Most matches `mark-matches`, or lines `mark-pattern`, marks at once
+const MAX_MARKED_MATCHES: usize = 100_000;
+/// How long a socket command waits for the file worker, unless configured
+const DEFAULT_COMMAND_TIMEOUT_SECS: usize = 30;
+/// Command server port unless `--port` or the config file sets one
+const DEFAULT_PORT: u16 = 9876;
+/// Most marked lines the marks panel lists; the rest are counted below them
+const MAX_MARKS_PANEL_ROWS: usize = 1000;
+/// Characters of a marked line's text shown in the marks panel
+const MARK_SNIPPET_CHARS: usize = 200;
+const MARKS_PANEL_WIDTH: i32 = 320;
+/// Most lines pinned above the view, so the strip leaves room for the lines
+const MAX_PINNED_LINES: usize = 8;
+/// How long the "new lines" notice stays up
+const NOTICE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+/// How long an error toast without a retry action stays up
+const TOAST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+/// Most error toasts shown at once; a new one replaces the oldest
+const MAX_TOASTS: usize = 3;
+
+/// How often a window asks for the line count while a file is indexed or a pipe is read.
+const INDEX_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// How often a window of a remote file checks its connection state.
+const CONNECTION_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Starting state for a window; duplicated windows copy it from their source view.
+#[derive(Clone, Default)]
+struct ViewState {
+    top_line: usize,
+    marks: HashMap<usize, LineMarkings>,
+    annotations: Annotations,
+    bookmarks: Bookmarks,
+    view_mode: ViewMode,
+    /// Long lines wrap instead of scrolling sideways
+    wrap: bool,
+    /// Line numbers count from the cursor line
+    relative_numbers: bool,
+    /// How lines are colored by their severity word
+    level_style: LevelStyle,
+    /// The sidebar shows the time since the previous stamped line
+    time_column: bool,
+    /// Lines split into aligned columns, when the column view is on
+    table: Option<TableLayout>,
+    /// Keys of `key=value` lines colored, and the keys hidden
+    logfmt: LogfmtView,
+    /// The side panel lists the marked lines
+    marks_panel: bool,
+    /// New lines scroll into view while the view is at the end of the file
+    follow: bool,
+    filters: Vec<LineFilter>,
+    /// Runs of repeated lines folded into one, and the runs expanded again
+    fold: Option<LineFold>,
+    /// Ranges of file lines taken out of the view by `hide`
+    hidden: Vec<(usize, usize)>,
+    /// File lines kept in the strip above the lines, in order
+    pinned: Vec<usize>,
+    highlights: Vec<HighlightRule>,
+}
+
+/// Settings shared by every window, resolved from CLI flags and the config file.
+#[derive(Clone)]
+struct UiOptions {
+    search_config: SearchConfig,
+    columns: ColumnLayout,
+    max_line_columns: usize,
+    /// Case handling while the search bar's "Aa" toggle is off
+    search_case: CaseMode,
+    /// Initial state of the search bar's wrap-around toggle
+    search_wrap: bool,
+    /// How long a socket command waits for a search or scan by the file worker
+    command_timeout: std::time::Duration,
+    /// Keys of the navigation actions, vim-style unless the config rebinds them
+    key_bindings: KeyBindings,
+    /// Font of the lines before zooming, and what Ctrl+0 goes back to
+    font: FontSettings,
+    /// Light or dark colors, or whichever the desktop prefers
+    theme: ThemeChoice,
+    /// Colors of error, warning, ... lines over the theme's defaults
+    level_colors: LevelColors,
+    /// Announce lines appended to the file in the notice
+    notify_new_lines: bool,
+    /// The rules file; a window colors with the rules whose file pattern
+    /// matches its file
+    rule_set: RuleSet,
+    port: u16,
+    no_server: bool,
+    /// Port of the WebSocket server, off when unset
+    websocket_port: Option<u16>,
+    /// Page origins besides local ones allowed to connect over WebSocket
+    websocket_origins: Vec<String>,
+    /// Local file to watch for appends, truncation and replacement
+    watch_path: Option<std::path::PathBuf>,
+    /// JSON file the marks are saved to when a window closes
+    marks_path: Option<std::path::PathBuf>,
+}
+
 static REQUEST_COUNTER: AtomicU64 = AtomicU64::new(0);
+/// Numbers the windows, so each has a CSS class of its own for its font
+static WINDOW_COUNTER: AtomicU64 = AtomicU64::new(0);
 
 fn next_request_id() -> u64 {
     REQUEST_COUNTER.fetch_add(1, Ordering::SeqCst)
 }
 
-fn spawn_file_worker(
-    source: Arc<dyn FileSource>,
-    request_rx: async_channel::Receiver<FileRequest>,
-    response_tx: async_channel::Sender<FileResponse>,
-) {
-    std::thread::spawn(move || {
-        while let Ok(request) = request_rx.recv_blocking() {
-            match request {
-                FileRequest::GetLines {
-                    start,
-                    count,
-                    request_id,
-                } => match source.get_lines(start, count) {
-                    Ok(lines) => {
-                        let _ = response_tx.send_blocking(FileResponse::Lines {
-                            lines,
-                            request_id,
-                            start,
-                        });
+fn parse_cli_list<T>(value: Option<&str>, parse: fn(&str) -> Result<T, String>) -> Option<T> {
+    value.map(|v| match parse(v) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("Invalid argument: {}", e);
+            std::process::exit(2);
+        }
+    })
+}
+
+/// The command server port of the config file, for subcommands reaching a
+/// running pog; an unreadable config file falls back to the default.
+fn configured_port() -> u16 {
+    Config::load().ok().and_then(|config| config.port).unwrap_or(DEFAULT_PORT)
+}
+
+/// Open a local file, transparently decompressing gzip/zstd/xz/bzip2 logs.
+/// The text encoding is detected when `encoding` is `None`. Plain files are
+/// memory-mapped unless `no_mmap` is set or mapping them is unsafe or fails.
+fn open_local_source(
+    path: &std::path::Path,
+    encoding: Option<TextEncoding>,
+    no_mmap: bool,
+) -> std::io::Result<Arc<dyn FileSource>> {
+    // Checked first: reading a pipe's magic bytes would consume them
+    if stream_loader::is_fifo(path) {
+        return Ok(Arc::new(StreamSource::open_fifo(path, encoding)?));
+    }
+    match compressed_loader::detect_path(path)? {
+        Some(decoder) => Ok(Arc::new(CompressedFile::open(path, decoder, encoding)?)),
+        None if no_mmap || pread_loader::mmap_unsuitable(path) => {
+            Ok(Arc::new(PreadFile::open(path, encoding)?))
+        }
+        None => match MappedFile::open(path, encoding) {
+            Ok(file) => Ok(Arc::new(file)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(e),
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => Err(e),
+            // Some special files and filesystems cannot be mapped
+            Err(_) => Ok(Arc::new(PreadFile::open(path, encoding)?)),
+        },
+    }
+}
+
+/// Open `path` together with its rotated siblings, oldest first.
+fn open_rotated_source(
+    path: &std::path::Path,
+    encoding: Option<TextEncoding>,
+    no_mmap: bool,
+) -> std::io::Result<Arc<dyn FileSource>> {
+    let paths = concat_source::discover_rotated(path)?;
+    if paths.len() == 1 {
+        return open_local_source(path, encoding, no_mmap);
+    }
+    let parts = paths
+        .iter()
+        .map(|p| open_local_source(p, encoding, no_mmap))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    let display_name = format!("{} (+{} rotated)", path.display(), paths.len() - 1);
+    Ok(Arc::new(ConcatSource::new(parts, display_name)))
+}
+
+fn open_remote_source(
+    target: &SshTarget,
+    path: &str,
+    encoding: Option<TextEncoding>,
+    options: &RemoteOptions,
+) -> error::Result<Arc<dyn FileSource>> {
+    match options.transport {
+        RemoteTransport::Shell => Ok(Arc::new(RemoteFile::open(target, path, encoding, options)?)),
+        RemoteTransport::Sftp => Ok(Arc::new(SftpFile::open(target, path, encoding)?)),
+    }
+}
+
+fn open_file_source(
+    file: &FilePath,
+    encoding: Option<TextEncoding>,
+) -> error::Result<Arc<dyn FileSource>> {
+    match file {
+        FilePath::Local(path) => Ok(open_local_source(path, encoding, false)?),
+        FilePath::Remote { host, port, path } => {
+            let target = SshTarget {
+                port: *port,
+                ..SshTarget::new(host)
+            };
+            open_remote_source(&target, path, encoding, &RemoteOptions::default())
+        }
+        FilePath::S3 { bucket, key } => Ok(Arc::new(S3File::open(bucket, key, encoding)?)),
+        FilePath::Archive { archive, member } => {
+            Ok(Arc::new(archive_loader::open_member(archive, member, encoding)?))
+        }
+    }
+}
+
+fn main() -> glib::ExitCode {
+    let args = Args::parse();
+
+    match args.command {
+        Some(Subcommand::Bench {
+            file,
+            iterations,
+            pattern,
+            search_lines,
+        }) => {
+            let options = bench::BenchOptions {
+                iterations,
+                pattern,
+                search_lines,
+            };
+            return match bench::run(&file, &options) {
+                Ok(()) => glib::ExitCode::SUCCESS,
+                Err(e) => {
+                    eprintln!("Benchmark failed: {}", e);
+                    glib::ExitCode::FAILURE
+                }
+            };
+        }
+        Some(Subcommand::Doctor { host, port }) => {
+            let target = host.map(|host| {
+                doctor::ssh_target(&host, args.jump_host.clone(), args.ssh_option.clone())
+            });
+            return if doctor::run(target.as_ref(), port.unwrap_or_else(configured_port)) {
+                glib::ExitCode::SUCCESS
+            } else {
+                glib::ExitCode::FAILURE
+            };
+        }
+        Some(Subcommand::List) => {
+            return if registry::run_list() {
+                glib::ExitCode::SUCCESS
+            } else {
+                glib::ExitCode::FAILURE
+            };
+        }
+        Some(Subcommand::Ctl { port, command }) => {
+            return if ctl::run(port.unwrap_or_else(configured_port), &command) {
+                glib::ExitCode::SUCCESS
+            } else {
+                glib::ExitCode::FAILURE
+            };
+        }
+        None => {}
+    }
+
+    let file_config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Invalid config file: {}", e);
+            std::process::exit(1);
+        }
+    };
+    // Before any file is opened: merging reads the lines' timestamps
+    timestamp::set_custom_formats(file_config.time_formats.clone());
+
+    let rotated = args.rotated;
+    let encoding = args.encoding;
+    let no_mmap = args.no_mmap;
+    let remote_options = RemoteOptions {
+        transport: args.remote_transport,
+        chunk_size: args
+            .chunk_size
+            .map(std::num::NonZeroUsize::get)
+            .or(file_config.remote_chunk_size),
+        no_compression: args.no_remote_compression,
+    };
+    let jump_host = args.jump_host.clone();
+    let ssh_options = args.ssh_option.clone();
+    let open_or_exit = |file: &FilePath| -> Arc<dyn FileSource> {
+        match file {
+            FilePath::Local(path) => {
+                let opened = if rotated {
+                    open_rotated_source(path, encoding, no_mmap)
+                } else {
+                    open_local_source(path, encoding, no_mmap)
+                };
+                match opened {
+                    Ok(f) => f,
+                    Err(e) => {
+                        eprintln!("Failed to open file: {}", e);
+                        std::process::exit(1);
                     }
+                }
+            }
+            FilePath::Remote { host, port, path } => {
+                let target = SshTarget {
+                    host: host.clone(),
+                    port: *port,
+                    jump_host: jump_host.clone(),
+                    options: ssh_options.clone(),
+                };
+                match open_remote_source(&target, path, encoding, &remote_options) {
+                    Ok(f) => f,
                     Err(e) => {
-                        let _ = response_tx.send_blocking(FileResponse::Error {
-                            message: e.to_string(),
-                        });
+                        eprintln!("Failed to open remote file: {}", e);
+                        std::process::exit(1);
                     }
-                },
-                FileRequest::SearchRange {
-                    pattern,
-                    start_line,
-                    end_line,
-                    request_id,
-                    navigate_to_first,
-                } => {
-                    match regex::Regex::new(&pattern) {
-                        Ok(regex) => {
-                            let count = end_line.saturating_sub(start_line);
-                            match source.get_lines(start_line, count) {
-                                Ok(lines) => {
-                                    let matches = search::search_lines(&regex, &lines);
-                                    let _ = response_tx.send_blocking(FileResponse::SearchResults {
-                                        matches,
-                                        request_id,
-                                        searched_range: (start_line, end_line),
-                                        navigate_to_first,
-                                    });
-                                }
-                                Err(e) => {
-                                    let _ = response_tx.send_blocking(FileResponse::Error {
-                                        message: e.to_string(),
-                                    });
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            let _ = response_tx.send_blocking(FileResponse::Error {
-                                message: format!("invalid regex: {}", e),
-                            });
-                        }
+                }
+            }
+            FilePath::S3 { bucket, key } => match S3File::open(bucket, key, encoding) {
+                Ok(f) => Arc::new(f),
+                Err(e) => {
+                    eprintln!("Failed to open S3 object: {}", e);
+                    std::process::exit(1);
+                }
+            },
+            FilePath::Archive { archive, member } => {
+                match archive_loader::open_member(archive, member, encoding) {
+                    Ok(f) => Arc::new(f),
+                    Err(e) => {
+                        eprintln!("Failed to open archive member: {}", e);
+                        std::process::exit(1);
                     }
                 }
-                FileRequest::FindNextMatch {
-                    pattern,
-                    from_line,
-                    direction,
-                    request_id,
-                    result_tx,
-                } => {
-                    match regex::Regex::new(&pattern) {
-                        Ok(regex) => {
-                            let total_lines = source.line_count();
-                            let mut found: Option<SearchMatch> = None;
-                            let mut found_line: Option<usize> = None;
-
-                            match direction {
-                                SearchDirection::Forward => {
-                                    let mut current = from_line + 1;
-                                    while current < total_lines && found.is_none() {
-                                        let end = (current + SEARCH_CHUNK_SIZE).min(total_lines);
-                                        if let Ok(lines) = source.get_lines(current, end - current) {
-                                            for (line_num, line) in &lines {
-                                                if let Some(mat) = regex.find(line) {
-                                                    found = Some(SearchMatch {
-                                                        line_num: *line_num,
-                                                        start_col: mat.start(),
-                                                        end_col: mat.end(),
-                                                    });
-                                                    found_line = Some(*line_num);
-                                                    break;
-                                                }
-                                            }
-                                        }
-                                        current = end;
-                                    }
-                                }
-                                SearchDirection::Backward => {
-                                    let mut current_end = from_line;
-                                    while found.is_none() && current_end > 0 {
-                                        let start = current_end.saturating_sub(SEARCH_CHUNK_SIZE);
-                                        if let Ok(lines) = source.get_lines(start, current_end - start) {
-                                            for (line_num, line) in lines.iter().rev() {
-                                                if let Some(mat) = regex.find(line) {
-                                                    found = Some(SearchMatch {
-                                                        line_num: *line_num,
-                                                        start_col: mat.start(),
-                                                        end_col: mat.end(),
-                                                    });
-                                                    found_line = Some(*line_num);
-                                                    break;
-                                                }
-                                            }
-                                        }
-                                        if start == 0 {
-                                            break;
-                                        }
-                                        current_end = start;
-                                    }
-                                }
-                            }
-
-                            // Send result through sync channel if provided (for socket commands)
-                            if let Some(tx) = result_tx {
-                                let result = found.as_ref().map(|m| {
-                                    (m.line_num, m.start_col, m.end_col - m.start_col)
-                                });
-                                let _ = tx.send(result);
-                            }
+            }
+        }
+    };
 
-                            let _ = response_tx.send_blocking(FileResponse::FoundMatch {
-                                match_info: found,
-                                line_num: found_line,
-                                request_id,
-                            });
-                        }
-                        Err(e) => {
-                            // Send error through sync channel if provided
-                            if let Some(tx) = result_tx {
-                                let _ = tx.send(None);
-                            }
-                            let _ = response_tx.send_blocking(FileResponse::Error {
-                                message: format!("invalid regex: {}", e),
-                            });
-                        }
-                    }
+    let file_source: Arc<dyn FileSource> = if let Some(file) = &args.file {
+        let primary = open_or_exit(file);
+        if args.merge.is_empty() {
+            primary
+        } else {
+            let mut parts = vec![primary];
+            parts.extend(args.merge.iter().map(open_or_exit));
+            match MergedSource::new(parts) {
+                Ok(merged) => Arc::new(merged),
+                Err(e) => {
+                    eprintln!("Failed to merge files: {}", e);
+                    std::process::exit(1);
                 }
             }
         }
-    });
-}
+    } else if args.merge.is_empty() {
+        Arc::new(EmptySource)
+    } else {
+        eprintln!("--merge needs a file to merge the others into");
+        std::process::exit(1);
+    };
 
-fn main() -> glib::ExitCode {
-    let args = Args::parse();
+    let rule_set = match RuleSet::load() {
+        Ok(rule_set) => rule_set,
+        Err(e) => {
+            eprintln!("Invalid rules file: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(color) = rule_set.colors().find(|color| gtk4::gdk::RGBA::parse(*color).is_err()) {
+        eprintln!("Invalid rules file: invalid color: {}", color);
+        std::process::exit(1);
+    }
+    let cli_config = Config {
+        search_buffer_lines: args.search_buffer,
+        search_chunk_size: args.search_chunk,
+        search_research_margin: args.search_margin,
+        column_guides: parse_cli_list(args.column_guides.as_deref(), columns::parse_guides),
+        fixed_fields: parse_cli_list(args.fields.as_deref(), columns::parse_fields),
+        max_line_columns: args.max_columns.map(std::num::NonZeroUsize::get),
+        search_case: None,
+        search_wrap: None,
+        command_timeout: args.command_timeout.map(std::num::NonZeroUsize::get),
+        port: args.port,
+        remote_chunk_size: args.chunk_size.map(std::num::NonZeroUsize::get),
+        notify_new_lines: None,
+        follow: None,
+        relative_numbers: None,
+        font_family: None,
+        font_size: None,
+        theme: args.theme,
+        levels: None,
+        level_colors: None,
+        logfmt: None,
+        time_formats: Vec::new(),
+        key_bindings: Vec::new(),
+    };
 
-    let file_source: Arc<dyn FileSource> = match &args.file {
-        FilePath::Local(path) => match MappedFile::open(path) {
-            Ok(f) => Arc::new(f),
-            Err(e) => {
-                eprintln!("Failed to open file: {}", e);
-                std::process::exit(1);
-            }
+    // Time one viewport fetch to tune search sizes; this also warms the remote cache
+    let fetch_start = std::time::Instant::now();
+    let _ = file_source.get_lines(0, LINES_PER_PAGE);
+    let search_config = SearchConfig::for_latency(fetch_start.elapsed())
+        .with_overrides(&file_config)
+        .with_overrides(&cli_config);
+
+    let options = UiOptions {
+        search_config,
+        columns: ColumnLayout {
+            guides: cli_config
+                .column_guides
+                .or(file_config.column_guides)
+                .unwrap_or_default(),
+            fields: cli_config
+                .fixed_fields
+                .or(file_config.fixed_fields)
+                .unwrap_or_default(),
         },
-        FilePath::Remote { host, path } => match RemoteFile::open(host, path) {
-            Ok(f) => Arc::new(f),
-            Err(e) => {
-                eprintln!("Failed to open remote file: {}", e);
-                std::process::exit(1);
-            }
+        max_line_columns: cli_config
+            .max_line_columns
+            .or(file_config.max_line_columns)
+            .unwrap_or(DEFAULT_MAX_LINE_COLUMNS),
+        search_case: file_config.search_case.unwrap_or_default(),
+        search_wrap: file_config.search_wrap.unwrap_or(false),
+        command_timeout: std::time::Duration::from_secs(
+            cli_config
+                .command_timeout
+                .or(file_config.command_timeout)
+                .unwrap_or(DEFAULT_COMMAND_TIMEOUT_SECS) as u64,
+        ),
+        key_bindings: KeyBindings::default().with_overrides(&file_config.key_bindings),
+        font: FontSettings {
+            family: file_config.font_family.unwrap_or_else(|| FontSettings::default().family),
+            size: file_config.font_size.unwrap_or(font::DEFAULT_FONT_SIZE),
+        },
+        theme: cli_config.theme.or(file_config.theme).unwrap_or_default(),
+        level_colors: file_config.level_colors.unwrap_or_default(),
+        notify_new_lines: file_config.notify_new_lines.unwrap_or(true),
+        rule_set,
+        port: cli_config.port.or(file_config.port).unwrap_or(DEFAULT_PORT),
+        no_server: args.no_server,
+        websocket_port: args.websocket_port,
+        websocket_origins: args.websocket_origin,
+        watch_path: match &args.file {
+            Some(FilePath::Local(path)) if args.merge.is_empty() && !rotated => Some(path.clone()),
+            _ => None,
         },
+        marks_path: args.marks,
     };
 
-    let port = args.port;
-    let no_server = args.no_server;
-
     let app = Application::builder()
         .application_id("com.github.pog")
         .flags(gtk4::gio::ApplicationFlags::NON_UNIQUE)
         .build();
 
+    // A marks file that does not exist yet is created on close
+    let saved = match &options.marks_path {
+        Some(path) if path.exists() => match marks::load(path) {
+            Ok(saved) => saved,
+            Err(e) => {
+                eprintln!("Invalid marks file: {}", e);
+                std::process::exit(1);
+            }
+        },
+        _ => MarksFile::default(),
+    };
+    // Following starts at the end, like `tail -f`
+    let follow = file_config.follow.unwrap_or(false);
+    let initial = ViewState {
+        top_line: if follow { file_source.line_count().saturating_sub(LINES_PER_PAGE) } else { 0 },
+        marks: saved.marks,
+        annotations: saved.annotations,
+        bookmarks: saved.bookmarks,
+        level_style: file_config.levels.unwrap_or_default(),
+        logfmt: LogfmtView::new(file_config.logfmt.unwrap_or(true)),
+        follow,
+        relative_numbers: file_config.relative_numbers.unwrap_or(false),
+        ..ViewState::default()
+    };
+
     let file_source_clone = file_source.clone();
+    let workers = Rc::new(RefCell::new(WorkerRegistry::default()));
 
     app.connect_activate(move |app| {
-        build_ui(app, file_source_clone.clone(), port, no_server);
+        build_ui(app, file_source_clone.clone(), &workers, &options, initial.clone());
     });
 
     app.run_with_args::<&str>(&[])
 }
 
-fn build_ui(app: &Application, file_source: Arc<dyn FileSource>, port: u16, no_server: bool) {
+/// Build a window viewing `file_source`. Returns the command server port
+/// bound for this window, if the server is enabled.
+fn build_ui(
+    app: &Application,
+    file_source: Arc<dyn FileSource>,
+    workers: &Rc<RefCell<WorkerRegistry>>,
+    options: &UiOptions,
+    initial: ViewState,
+) -> Option<u16> {
+    let search_config = options.search_config;
+    let notify_new_lines = options.notify_new_lines;
+    let field_styles: Rc<Vec<FieldStyle>> = Rc::new(options.columns.fields.clone());
+
+    let default_title = format!("pog - {}", file_source.display_name());
     let window = ApplicationWindow::builder()
         .application(app)
-        .title(&format!("pog - {}", file_source.display_name()))
+        .title(&default_title)
         .default_width(1200)
         .default_height(800)
         .build();
 
-    let total_lines = file_source.line_count();
-    let file_size = file_source.file_size().unwrap_or(0);
+    // Grows as the file is appended to; see FileResponse::SourceChanged
+    let total_lines: Rc<Cell<usize>> = Rc::new(Cell::new(file_source.line_count()));
 
     let (command_tx, command_rx) = async_channel::unbounded::<CommandRequest>();
+    // Changes pushed to connections that sent `subscribe`
+    let events = EventHub::default();
 
-    if !no_server {
-        if let Err(e) = server::start_server(port, command_tx) {
-            eprintln!("Failed to start command server: {}", e);
+    // Shown in the window once it is built, as well as on stderr
+    let mut startup_errors = Vec::new();
+    let server = if options.no_server {
+        None
+    } else {
+        match server::start_server(options.port, command_tx.clone(), events.clone()) {
+            Ok(server) => Some(server),
+            Err(e) => {
+                eprintln!("Failed to start command server: {}", e);
+                startup_errors.push(format!("Failed to start command server: {}", e));
+                None
+            }
+        }
+    };
+    // Browser clients, only when a port is given for them
+    let websocket_server = options.websocket_port.and_then(|port| {
+        let origins = options.websocket_origins.clone();
+        match websocket::start_websocket_server(port, command_tx, events.clone(), origins) {
+            Ok(server) => Some(server),
+            Err(e) => {
+                eprintln!("Failed to start WebSocket server: {}", e);
+                startup_errors.push(format!("Failed to start WebSocket server: {}", e));
+                None
+            }
+        }
+    });
+    let server_port = server.as_ref().map(|server| server.port());
+    // Let `pog list` and clients find the port, which may not be the one asked for
+    let registration = server_port.and_then(|port| {
+        match registry::register(port, file_source.display_name()) {
+            Ok(registration) => Some(registration),
+            Err(e) => {
+                eprintln!("Failed to register instance: {}", e);
+                startup_errors.push(format!("Failed to register instance: {}", e));
+                None
+            }
         }
+    });
+    let servers: Vec<CommandServer> = server.into_iter().chain(websocket_server).collect();
+    if !servers.is_empty() {
+        let registration = Cell::new(registration);
+        // Stop the servers with the window, once every command sent to them is answered
+        let servers = Cell::new(servers);
+        let command_rx_destroy = command_rx.clone();
+        window.connect_destroy(move |_| {
+            command_rx_destroy.close();
+            while let Ok(request) = command_rx_destroy.try_recv() {
+                let _ = request
+                    .response_tx
+                    .send(CommandResponse::Error("window closed".to_string()));
+            }
+            for server in servers.take() {
+                server.shutdown();
+            }
+            drop(registration.take());
+        });
     }
 
-    // CSS provider for styling
+    // CSS provider for styling, in the light or dark theme
+    let gtk_settings = gtk4::Settings::default().expect("Could not get GTK settings");
+    if options.theme != ThemeChoice::System {
+        // GTK's own widgets (entries, buttons, dialogs) follow suit
+        gtk_settings.set_gtk_application_prefer_dark_theme(options.theme == ThemeChoice::Dark);
+    }
+    let theme: Rc<Cell<Theme>> =
+        Rc::new(Cell::new(options.theme.resolve(prefers_dark_theme(&gtk_settings))));
     let css_provider = CssProvider::new();
-    css_provider.load_from_string(
-        ".line-numbers-sidebar { background-color: #2a2a2a; padding-right: 8px; }
-         .line-number { color: #888; }
-         .search-bar { background-color: rgba(50, 50, 50, 0.95); padding: 8px 16px; border-radius: 0 0 8px 8px; }
-         .search-entry { min-width: 300px; }
-         .search-info { color: #aaa; margin-left: 8px; margin-right: 8px; }
-         .search-close { padding: 4px 8px; }"
-    );
+    css_provider.load_from_string(&theme.get().css());
     gtk4::style_context_add_provider_for_display(
         &Display::default().expect("Could not get default display"),
         &css_provider,
         STYLE_PROVIDER_PRIORITY_APPLICATION,
     );
 
-    // Marked lines: line_num (0-based) -> markings (full-line color and/or regions)
-    let marked_lines: Rc<RefCell<HashMap<usize, LineMarkings>>> = Rc::new(RefCell::new(HashMap::new()));
+    // The lines' font, zoomed with Ctrl+= / Ctrl+- / Ctrl+0; the stylesheet
+    // is regenerated on each change and only applies to this window
+    let window_class = format!("pog-window-{}", WINDOW_COUNTER.fetch_add(1, Ordering::SeqCst));
+    window.add_css_class(&window_class);
+    let font_provider = CssProvider::new();
+    font_provider.load_from_string(&options.font.css(&format!(".{}", window_class)));
+    gtk4::style_context_add_provider_for_display(
+        &Display::default().expect("Could not get default display"),
+        &font_provider,
+        STYLE_PROVIDER_PRIORITY_APPLICATION,
+    );
+    let font_provider_destroy = font_provider.clone();
+    window.connect_destroy(move |_| {
+        if let Some(display) = Display::default() {
+            gtk4::style_context_remove_provider_for_display(&display, &font_provider_destroy);
+        }
+    });
+
+    // Line filters, applied by the worker: while any are set, the view's line
+    // numbers count the kept lines and `view_filter` maps them back to the file
+    let view_filter: Rc<RefCell<Option<Arc<FilteredLines>>>> = Rc::new(RefCell::new(None));
+
+    // Marked lines: file line (0-based) -> markings (full-line color and/or
+    // regions). Marks, annotations and bookmarks are kept by file line, so
+    // they stay with their text when the filters change
+    let marked_lines: Rc<RefCell<HashMap<usize, LineMarkings>>> = Rc::new(RefCell::new(initial.marks));
+    // Notes attached to lines with `annotate`, shown as a gutter icon
+    let annotations: Rc<RefCell<Annotations>> = Rc::new(RefCell::new(initial.annotations));
+    // Named lines to jump back to, with `bookmark` or Ctrl+B
+    let bookmarks: Rc<RefCell<Bookmarks>> = Rc::new(RefCell::new(initial.bookmarks));
+    // Everything `save-marks` and `--marks` write
+    let marked_lines_file = marked_lines.clone();
+    let annotations_file = annotations.clone();
+    let bookmarks_file = bookmarks.clone();
+    let marks_file = Rc::new(move || MarksFile {
+        marks: marked_lines_file.borrow().clone(),
+        annotations: annotations_file.borrow().clone(),
+        bookmarks: bookmarks_file.borrow().clone(),
+    });
+    if let Some(path) = options.marks_path.clone() {
+        let marks_file_close = marks_file.clone();
+        let file_source_close = file_source.clone();
+        window.connect_destroy(move |_| {
+            let file = file_source_close.display_name();
+            if let Err(e) = marks::save(&path, &marks_file_close(), file) {
+                eprintln!("Failed to save marks: {}", e);
+            }
+        });
+    }
+
+    // Highlight rules: patterns colored on every line shown, whatever the search
+    let highlights: Rc<RefCell<Vec<HighlightRule>>> = Rc::new(RefCell::new(initial.highlights));
+    // Severity coloring: error, warning, ... lines tinted, or only their level word
+    let level_style: Rc<Cell<LevelStyle>> = Rc::new(Cell::new(initial.level_style));
+    // Time column: how long after the previous stamped line each line was logged
+    let time_column: Rc<Cell<bool>> = Rc::new(Cell::new(false));
+    // Column view: lines split on a delimiter and laid out in aligned columns
+    let table: Rc<RefCell<Option<TableLayout>>> = Rc::new(RefCell::new(initial.table.clone()));
+    // Field-aware display of logfmt lines: keys colored, chosen keys hidden
+    let logfmt: Rc<RefCell<LogfmtView>> = Rc::new(RefCell::new(initial.logfmt.clone()));
 
     // Search state
     let search_state: Rc<RefCell<SearchState>> = Rc::new(RefCell::new(SearchState::new()));
@@ -392,10 +1165,35 @@ fn build_ui(app: &Application, file_source: Arc<dyn FileSource>, port: u16, no_s
     let content_box = GtkBox::new(Orientation::Vertical, 0);
     content_box.set_hexpand(true);
 
-    // Horizontal scroll for long lines only
+    // Column numbers over the lines in the column view, dragged at a
+    // column's right edge to resize it; the sidebar keeps a blank row
+    // beside them so line numbers stay level with their lines
+    let table_header = Label::new(None);
+    table_header.set_halign(gtk4::Align::Start);
+    table_header.set_css_classes(&["monospace", "table-header"]);
+    content_box.append(&table_header);
+    let table_header_spacer = Label::new(None);
+    table_header_spacer.set_css_classes(&["monospace", "table-header"]);
+    line_numbers_box.append(&table_header_spacer);
+    let show_table_header = {
+        let table_header = table_header.clone();
+        let table_header_spacer = table_header_spacer.clone();
+        move |table: Option<&TableLayout>| {
+            table_header.set_visible(table.is_some());
+            table_header_spacer.set_visible(table.is_some());
+            if let Some(table) = table {
+                table_header.set_text(&table.header());
+            }
+        }
+    };
+    show_table_header(initial.table.as_ref());
+
+    // Horizontal scroll for long lines only. The lines are clipped to the
+    // height they are given rather than sizing the window; `fit_page` then
+    // shows as many as fit
     let h_scroll = ScrolledWindow::builder()
         .hscrollbar_policy(PolicyType::Automatic)
-        .vscrollbar_policy(PolicyType::Never)
+        .vscrollbar_policy(PolicyType::External)
         .child(&content_box)
         .hexpand(true)
         .vexpand(true)
@@ -404,9 +1202,9 @@ fn build_ui(app: &Application, file_source: Arc<dyn FileSource>, port: u16, no_s
     // Vertical scrollbar - maps directly to line numbers
     // value = first visible line, upper = total lines, page_size = visible lines
     let v_adjustment = Adjustment::new(
-        0.0,                           // value (current line)
+        initial.top_line as f64,       // value (current line)
         0.0,                           // lower
-        total_lines as f64,            // upper
+        total_lines.get() as f64,      // upper
         1.0,                           // step increment (1 line)
         LINES_PER_PAGE as f64,         // page increment
         LINES_PER_PAGE as f64,         // page size
@@ -414,13 +1212,53 @@ fn build_ui(app: &Application, file_source: Arc<dyn FileSource>, port: u16, no_s
     let v_scrollbar = Scrollbar::new(Orientation::Vertical, Some(&v_adjustment));
     v_scrollbar.set_vexpand(true);
 
-    // Layout
+    // Layout: the line numbers and lines take the wheel (see
+    // `scroll_controller`), and the numbers are clipped like the lines
+    let line_numbers_scroll = ScrolledWindow::builder()
+        .hscrollbar_policy(PolicyType::Never)
+        .vscrollbar_policy(PolicyType::External)
+        .child(&line_numbers_box)
+        .build();
+    let lines_box = GtkBox::new(Orientation::Horizontal, 0);
+    lines_box.set_hexpand(true);
+    lines_box.append(&line_numbers_scroll);
+    lines_box.append(&separator);
+    lines_box.append(&build_column_guides(&h_scroll, &options.columns.guides));
+    // Pinned lines (`p`, `pin`) stay in a strip above the lines, listed by
+    // `refresh_pinned`
+    let pinned_strip = GtkBox::new(Orientation::Vertical, 0);
+    pinned_strip.set_css_classes(&["pinned-lines"]);
+    pinned_strip.set_visible(false);
+    let view_box = GtkBox::new(Orientation::Vertical, 0);
+    view_box.set_hexpand(true);
+    view_box.append(&pinned_strip);
+    view_box.append(&lines_box);
     let hbox = GtkBox::new(Orientation::Horizontal, 0);
-    hbox.append(&line_numbers_box);
-    hbox.append(&separator);
-    hbox.append(&h_scroll);
+    hbox.add_css_class("log-view");
+    hbox.append(&view_box);
+    let overview = build_overview(&v_adjustment, &search_state, &marked_lines, &view_filter);
+    hbox.append(&overview);
     hbox.append(&v_scrollbar);
 
+    // Marked lines (side panel, Alt+M), listed by `refresh_marks_panel`
+    let marks_panel = GtkBox::new(Orientation::Vertical, 4);
+    marks_panel.set_css_classes(&["marks-panel"]);
+    marks_panel.set_width_request(MARKS_PANEL_WIDTH);
+    marks_panel.set_visible(false);
+    let marks_panel_title = Label::new(Some("Marks"));
+    marks_panel_title.set_halign(gtk4::Align::Start);
+    marks_panel_title.set_css_classes(&["marks-panel-title"]);
+    let marks_list = GtkBox::new(Orientation::Vertical, 0);
+    let marks_scroll = ScrolledWindow::builder()
+        .hscrollbar_policy(PolicyType::Never)
+        .vscrollbar_policy(PolicyType::Automatic)
+        .child(&marks_list)
+        .vexpand(true)
+        .build();
+    marks_panel.append(&marks_panel_title);
+    marks_panel.append(&marks_scroll);
+    hbox.append(&marks_panel);
+
     // Search bar UI (overlay)
     let search_box = GtkBox::new(Orientation::Horizontal, 8);
     search_box.set_halign(gtk4::Align::Center);
@@ -436,121 +1274,1791 @@ fn build_ui(app: &Application, file_source: Arc<dyn FileSource>, port: u16, no_s
     let search_info = Label::new(Some(""));
     search_info.set_css_classes(&["search-info"]);
 
+    let search_case_button = ToggleButton::with_label("Aa");
+    search_case_button.set_tooltip_text(Some("Ignore case"));
+    search_case_button.set_css_classes(&["search-close"]);
+    search_case_button.set_active(options.search_case == CaseMode::Insensitive);
+    // The toggle forces case-insensitive matching; off, the configured mode applies
+    let default_case = match options.search_case {
+        CaseMode::Insensitive => CaseMode::Sensitive,
+        mode => mode,
+    };
+    let search_case_button_mode = search_case_button.clone();
+    let search_case = move || {
+        if search_case_button_mode.is_active() {
+            CaseMode::Insensitive
+        } else {
+            default_case
+        }
+    };
+
     let search_close_button = Button::with_label("x");
     search_close_button.set_css_classes(&["search-close"]);
 
+    let search_literal_button = ToggleButton::with_label("Plain");
+    search_literal_button.set_tooltip_text(Some("Match plain text instead of a regex"));
+    search_literal_button.set_css_classes(&["search-close"]);
+    let search_multiline_button = ToggleButton::with_label("\\n");
+    search_multiline_button.set_tooltip_text(Some(&format!(
+        "Let matches span lines (up to {})",
+        MULTILINE_WINDOW
+    )));
+    search_multiline_button.set_css_classes(&["search-close"]);
+    let search_wrap_button = ToggleButton::with_label("Wrap");
+    search_wrap_button.set_tooltip_text(Some("Wrap around to the other end after the last match"));
+    search_wrap_button.set_css_classes(&["search-close"]);
+    search_wrap_button.set_active(options.search_wrap);
+    // Set while commands update the toggles, so they do not re-run the search
+    let syncing_search_toggles = Rc::new(Cell::new(false));
+
+    // The active search as it was entered, to save it under a name
+    let search_state_current = search_state.clone();
+    let search_entry_current = search_entry.clone();
+    let search_case_button_current = search_case_button.clone();
+    let search_literal_button_current = search_literal_button.clone();
+    let search_multiline_button_current = search_multiline_button.clone();
+    let current_search = Rc::new(move || {
+        search_state_current.borrow().is_active.then(|| SavedSearch {
+            pattern: search_entry_current.text().to_string(),
+            ignore_case: search_case_button_current.is_active(),
+            literal: search_literal_button_current.is_active(),
+            multiline: search_multiline_button_current.is_active(),
+        })
+    });
+
+    // Run a saved search as if it had been typed into the search bar
+    let search_box_load = search_box.clone();
+    let search_entry_load = search_entry.clone();
+    let search_case_button_load = search_case_button.clone();
+    let search_literal_button_load = search_literal_button.clone();
+    let search_multiline_button_load = search_multiline_button.clone();
+    let syncing_search_toggles_load = syncing_search_toggles.clone();
+    let load_search = Rc::new(move |search: &SavedSearch| {
+        syncing_search_toggles_load.set(true);
+        search_case_button_load.set_active(search.ignore_case);
+        search_literal_button_load.set_active(search.literal);
+        search_multiline_button_load.set_active(search.multiline);
+        syncing_search_toggles_load.set(false);
+        search_box_load.set_visible(true);
+        search_entry_load.set_text(&search.pattern);
+        search_entry_load.emit_activate();
+    });
+
     search_box.append(&search_entry);
+    search_box.append(&search_case_button);
+    search_box.append(&search_literal_button);
+    search_box.append(&search_multiline_button);
+    search_box.append(&search_wrap_button);
+    search_box.append(&build_saved_search_menu(
+        current_search.clone(),
+        load_search.clone(),
+        &search_info,
+    ));
     search_box.append(&search_info);
     search_box.append(&search_close_button);
 
+    // File change notice (overlay, bottom right)
+    let notice_box = GtkBox::new(Orientation::Horizontal, 8);
+    notice_box.set_halign(gtk4::Align::End);
+    notice_box.set_valign(gtk4::Align::End);
+    notice_box.set_margin_end(24);
+    notice_box.set_margin_bottom(10);
+    notice_box.set_css_classes(&["file-notice"]);
+    notice_box.set_visible(false);
+
+    let notice_label = Label::new(None);
+    let notice_close_button = Button::with_label("x");
+    notice_close_button.set_css_classes(&["search-close"]);
+    notice_box.append(&notice_label);
+    notice_box.append(&notice_close_button);
+
+    // Remote connection state (overlay, bottom left), shown while reconnecting
+    let connection_banner = GtkBox::new(Orientation::Horizontal, 8);
+    connection_banner.set_halign(gtk4::Align::Start);
+    connection_banner.set_valign(gtk4::Align::End);
+    connection_banner.set_margin_start(24);
+    connection_banner.set_margin_bottom(10);
+    connection_banner.set_css_classes(&["connection-banner"]);
+    connection_banner.set_visible(false);
+    let connection_label = Label::new(None);
+    connection_banner.append(&connection_label);
+
+    // Errors (overlay, top right, see `top_right`), stacked oldest first;
+    // see `show_error`
+    let toasts = GtkBox::new(Orientation::Vertical, 6);
+    toasts.set_halign(gtk4::Align::End);
+
+    // Text set by `status-text` (overlay, top left), e.g. what a driving script is doing
+    let status_text_label = Label::new(None);
+    status_text_label.set_halign(gtk4::Align::Start);
+    status_text_label.set_valign(gtk4::Align::Start);
+    status_text_label.set_margin_start(24);
+    status_text_label.set_margin_top(10);
+    status_text_label.set_css_classes(&["status-text"]);
+    status_text_label.set_visible(false);
+
+    // Badge counting the lines appended while following but scrolled up from
+    // the end (overlay, bottom center)
+    let new_lines_badge = Button::new();
+    new_lines_badge.set_halign(gtk4::Align::Center);
+    new_lines_badge.set_valign(gtk4::Align::End);
+    new_lines_badge.set_margin_bottom(10);
+    new_lines_badge.set_tooltip_text(Some("Jump to the end"));
+    new_lines_badge.set_css_classes(&["new-lines-badge"]);
+    new_lines_badge.set_visible(false);
+    // Lines appended since the view left the end, while following
+    let unseen_lines: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+
+    // Active line filters (overlay, top right, above the toasts), one chip
+    // each with its hit count
+    let filter_bar = GtkBox::new(Orientation::Horizontal, 6);
+    filter_bar.set_halign(gtk4::Align::End);
+    filter_bar.set_visible(false);
+    let top_right = GtkBox::new(Orientation::Vertical, 6);
+    top_right.set_halign(gtk4::Align::End);
+    top_right.set_valign(gtk4::Align::Start);
+    top_right.set_margin_end(24);
+    top_right.set_margin_top(10);
+    top_right.append(&filter_bar);
+    top_right.append(&toasts);
+
+    // Title bar: the file's name and line count, an Open button, toggles
+    // for wrapping, following and the filters, and the application menu
+    let title_label = Label::new(None);
+    title_label.set_css_classes(&["title"]);
+    title_label.set_ellipsize(gtk4::pango::EllipsizeMode::Middle);
+    // Kept as the window title, which `title` changes
+    window.bind_property("title", &title_label, "label").sync_create().build();
+    let file_info_label = Label::new(None);
+    file_info_label.set_css_classes(&["subtitle"]);
+    let title_box = GtkBox::new(Orientation::Vertical, 0);
+    title_box.set_valign(gtk4::Align::Center);
+    title_box.append(&title_label);
+    title_box.append(&file_info_label);
+    let open_button = Button::from_icon_name("document-open-symbolic");
+    open_button.set_tooltip_text(Some("Open a file (Ctrl+O)"));
+    let wrap_button = ToggleButton::with_label("Wrap");
+    wrap_button.set_tooltip_text(Some("Wrap long lines (Alt+Z)"));
+    let follow_button = ToggleButton::with_label("Follow");
+    follow_button.set_tooltip_text(Some("Scroll to new lines while at the end of the file (Alt+F)"));
+    follow_button.set_active(initial.follow);
+    let filters_button = ToggleButton::with_label("Filters");
+    filters_button.set_tooltip_text(Some("Apply the filters, or set them aside to see every line"));
+    filters_button.set_sensitive(false);
+    // The filters set aside with the toggle, applied again when it is pressed
+    let paused_filters: Rc<RefCell<Vec<LineFilter>>> = Rc::new(RefCell::new(Vec::new()));
+    let menu_button = gtk4::MenuButton::new();
+    menu_button.set_icon_name("open-menu-symbolic");
+    menu_button.set_tooltip_text(Some("Menu"));
+    menu_button.set_menu_model(Some(&build_app_menu()));
+    let header = gtk4::HeaderBar::new();
+    header.set_title_widget(Some(&title_box));
+    header.pack_start(&open_button);
+    header.pack_end(&menu_button);
+    header.pack_end(&filters_button);
+    header.pack_end(&follow_button);
+    header.pack_end(&wrap_button);
+    window.set_titlebar(Some(&header));
+
+    // The line count in the title bar, with the size of a local file
+    let watch_path_info = options.watch_path.clone();
+    let show_file_info = Rc::new(move |line_count: usize| {
+        let lines = format!("{} line{}", line_count, if line_count == 1 { "" } else { "s" });
+        let size = watch_path_info.as_ref().and_then(|path| std::fs::metadata(path).ok());
+        match size {
+            Some(metadata) => {
+                file_info_label.set_text(&format!("{} · {}", lines, format_size(metadata.len())))
+            }
+            None => file_info_label.set_text(&lines),
+        }
+    });
+    show_file_info(total_lines.get());
+
     // Overlay to layer search bar over content
     let overlay = Overlay::new();
     overlay.set_child(Some(&hbox));
     overlay.add_overlay(&search_box);
+    overlay.add_overlay(&notice_box);
+    overlay.add_overlay(&connection_banner);
+    overlay.add_overlay(&status_text_label);
+    overlay.add_overlay(&top_right);
+    overlay.add_overlay(&new_lines_badge);
+
+    // Back at the end, the lines counted by the badge are in view
+    let new_lines_badge_end = new_lines_badge.clone();
+    let unseen_lines_end = unseen_lines.clone();
+    v_adjustment.connect_value_changed(move |adj| {
+        if at_end(adj) {
+            unseen_lines_end.set(0);
+            new_lines_badge_end.set_visible(false);
+        }
+    });
+    let v_adjustment_badge = v_adjustment.clone();
+    new_lines_badge.connect_clicked(move |_| {
+        scroll_by(&v_adjustment_badge, v_adjustment_badge.upper());
+    });
+    // Turning following on goes to the end; off, the badge goes away
+    let v_adjustment_follow = v_adjustment.clone();
+    let new_lines_badge_follow = new_lines_badge.clone();
+    let unseen_lines_follow = unseen_lines.clone();
+    follow_button.connect_toggled(move |button| {
+        if button.is_active() {
+            scroll_by(&v_adjustment_follow, v_adjustment_follow.upper());
+        } else {
+            unseen_lines_follow.set(0);
+            new_lines_badge_follow.set_visible(false);
+        }
+    });
+
+    // Show `text` in the notice; a `sticky` notice stays up until closed,
+    // otherwise it hides itself after NOTICE_TIMEOUT
+    let notice_generation: Rc<Cell<u64>> = Rc::new(Cell::new(0));
+    let notice_box_show = notice_box.clone();
+    let notice_close_button_show = notice_close_button.clone();
+    let show_notice = Rc::new(move |text: &str, sticky: bool| {
+        let generation = notice_generation.get() + 1;
+        notice_generation.set(generation);
+        notice_label.set_text(text);
+        notice_close_button_show.set_visible(sticky);
+        notice_box_show.set_visible(true);
+
+        if !sticky {
+            let notice_box_timeout = notice_box_show.clone();
+            let notice_generation_timeout = notice_generation.clone();
+            glib::timeout_add_local_once(NOTICE_TIMEOUT, move || {
+                if notice_generation_timeout.get() == generation {
+                    notice_box_timeout.set_visible(false);
+                }
+            });
+        }
+    });
+
+    // Show an error as a toast. One with a `retry` action stays until it is
+    // retried or closed, others close after TOAST_TIMEOUT; an error already
+    // shown, e.g. each page failing while scrolling, is not shown again
+    let show_error: ShowError = Rc::new(move |text, retry| {
+        let mut shown = 0;
+        let mut child = toasts.first_child();
+        while let Some(toast) = child {
+            let label = toast.first_child().and_downcast::<Label>();
+            if label.is_some_and(|label| label.text() == text) {
+                return;
+            }
+            shown += 1;
+            child = toast.next_sibling();
+        }
+        if shown >= MAX_TOASTS {
+            if let Some(oldest) = toasts.first_child() {
+                toasts.remove(&oldest);
+            }
+        }
+
+        let toast = GtkBox::new(Orientation::Horizontal, 8);
+        toast.set_css_classes(&["error-toast"]);
+        let label = Label::new(Some(text));
+        label.set_wrap(true);
+        label.set_max_width_chars(60);
+        label.set_xalign(0.0);
+        toast.append(&label);
+        let sticky = retry.is_some();
+        if let Some(retry) = retry {
+            let retry_button = Button::with_label("Retry");
+            retry_button.set_css_classes(&["search-close"]);
+            let toasts_retry = toasts.clone();
+            let toast_retry = toast.clone();
+            retry_button.connect_clicked(move |_| {
+                toasts_retry.remove(&toast_retry);
+                retry();
+            });
+            toast.append(&retry_button);
+        }
+        let close_button = Button::with_label("x");
+        close_button.set_css_classes(&["search-close"]);
+        let toasts_close = toasts.clone();
+        let toast_close = toast.clone();
+        close_button.connect_clicked(move |_| toasts_close.remove(&toast_close));
+        toast.append(&close_button);
+        toasts.append(&toast);
+
+        if !sticky {
+            let toasts_timeout = toasts.downgrade();
+            let toast_timeout = toast.downgrade();
+            glib::timeout_add_local_once(TOAST_TIMEOUT, move || {
+                let (Some(toasts), Some(toast)) =
+                    (toasts_timeout.upgrade(), toast_timeout.upgrade())
+                else {
+                    return;
+                };
+                // Unless closed or replaced meanwhile
+                if toast.parent().as_ref() == Some(toasts.upcast_ref()) {
+                    toasts.remove(&toast);
+                }
+            });
+        }
+    });
+    for error in &startup_errors {
+        show_error(error, None);
+    }
+
+    let current_line: Rc<RefCell<usize>> = Rc::new(RefCell::new(initial.top_line));
+    let latest_request_id: Rc<RefCell<u64>> = Rc::new(RefCell::new(0));
+    // An `export` is being written by the worker
+    let exporting: Rc<Cell<bool>> = Rc::new(Cell::new(false));
+
+    let (request_tx, response_rx) = workers
+        .borrow_mut()
+        .open_view(&file_source, search_config.chunk_size);
+
+    let request_tx_destroy = request_tx.clone();
+    window.connect_destroy(move |_| request_tx_destroy.detach());
+
+    // Text or hex rendering of binary lines, applied by the worker
+    let view_mode: Rc<Cell<ViewMode>> = Rc::new(Cell::new(initial.view_mode));
+    if initial.view_mode != ViewMode::Text {
+        request_tx.set_view_mode(initial.view_mode);
+    }
+    let binary_notice_shown = Rc::new(Cell::new(false));
+
+    // Folding repeated lines goes through the same index as the filters
+    let line_fold: Rc<RefCell<Option<LineFold>>> = Rc::new(RefCell::new(initial.fold));
+    // And so does leaving out the file lines hidden by `hide`
+    let hidden_lines: Rc<RefCell<Vec<(usize, usize)>>> = Rc::new(RefCell::new(initial.hidden));
+    let filter_chip_labels: Rc<RefCell<Vec<Label>>> = Rc::new(RefCell::new(Vec::new()));
+    // Chips send the index of the filter to remove
+    let (filter_remove_tx, filter_remove_rx) = async_channel::unbounded::<usize>();
+    let filter_remove_rx_destroy = filter_remove_rx.clone();
+    window.connect_destroy(move |_| {
+        filter_remove_rx_destroy.close();
+    });
+
+    let view_filter_counts = view_filter.clone();
+    let filter_chip_labels_counts = filter_chip_labels.clone();
+    // Counts are marked partial until the filter index has scanned the whole file
+    let update_filter_counts = Rc::new(move |done: bool| {
+        if let Some(filter) = view_filter_counts.borrow().as_ref() {
+            let labels = filter_chip_labels_counts.borrow();
+            let partial = if done { "" } else { "…" };
+            for ((label, rule), hits) in labels.iter().zip(filter.filters()).zip(filter.hits()) {
+                label.set_text(&format!("{} {}  {}{}", rule.kind, rule.pattern, hits, partial));
+            }
+        }
+    });
+
+    let view_filter_set = view_filter.clone();
+    let request_tx_filters = request_tx.clone();
+    let search_state_filters = search_state.clone();
+    let update_filter_counts_set = update_filter_counts.clone();
+    let line_fold_set = line_fold.clone();
+    let hidden_lines_set = hidden_lines.clone();
+    let filters_button_set = filters_button.clone();
+    let paused_filters_set = paused_filters.clone();
+    let set_filters = Rc::new(move |filters: Vec<LineFilter>| {
+        let filter = request_tx_filters.set_filters(
+            filters,
+            line_fold_set.borrow().clone(),
+            hidden_lines_set.borrow().clone(),
+        );
+        search_state_filters.borrow_mut().invalidate_matches();
+
+        while let Some(child) = filter_bar.first_child() {
+            filter_bar.remove(&child);
+        }
+        let mut labels = filter_chip_labels.borrow_mut();
+        labels.clear();
+        for i in 0..filter.as_ref().map_or(0, |filter| filter.filters().len()) {
+            let chip = GtkBox::new(Orientation::Horizontal, 4);
+            chip.set_css_classes(&["filter-chip"]);
+            let label = Label::new(None);
+            let remove_button = Button::with_label("x");
+            remove_button.set_css_classes(&["search-close"]);
+            let filter_remove_tx = filter_remove_tx.clone();
+            remove_button.connect_clicked(move |_| {
+                let _ = filter_remove_tx.send_blocking(i);
+            });
+            chip.append(&label);
+            chip.append(&remove_button);
+            filter_bar.append(&chip);
+            labels.push(label);
+        }
+        drop(labels);
+        let filtered = filter.as_ref().is_some_and(|filter| !filter.filters().is_empty());
+        filter_bar.set_visible(filtered);
+
+        *view_filter_set.borrow_mut() = filter;
+        update_filter_counts_set(false);
+        // After the filters are in place, as the toggle reads them
+        filters_button_set.set_active(filtered);
+        filters_button_set.set_sensitive(filtered || !paused_filters_set.borrow().is_empty());
+    });
+    // The filters currently applied, for adding or removing one
+    let view_filter_list = view_filter.clone();
+    let current_filters = move || {
+        view_filter_list
+            .borrow()
+            .as_ref()
+            .map(|filter| filter.filters().to_vec())
+            .unwrap_or_default()
+    };
+
+    // Set the filters aside to see every line, or apply them again
+    let set_filters_toggle = set_filters.clone();
+    let current_filters_toggle = current_filters.clone();
+    filters_button.connect_toggled(move |button| {
+        let filters = current_filters_toggle();
+        if button.is_active() && filters.is_empty() {
+            let paused = paused_filters.take();
+            if !paused.is_empty() {
+                set_filters_toggle(paused);
+            }
+        } else if !button.is_active() && !filters.is_empty() {
+            *paused_filters.borrow_mut() = filters;
+            set_filters_toggle(Vec::new());
+        }
+    });
+
+    let set_filters_remove = set_filters.clone();
+    let current_filters_remove = current_filters.clone();
+    glib::spawn_future_local(async move {
+        while let Ok(i) = filter_remove_rx.recv().await {
+            let mut filters = current_filters_remove();
+            if i < filters.len() {
+                filters.remove(i);
+                set_filters_remove(filters);
+            }
+        }
+    });
+    if !initial.filters.is_empty()
+        || line_fold.borrow().is_some()
+        || !hidden_lines.borrow().is_empty()
+    {
+        set_filters(initial.filters);
+    }
+
+    // Fold runs of repeated lines, or show them all again
+    let line_fold_set = line_fold.clone();
+    let set_filters_fold = set_filters.clone();
+    let current_filters_fold = current_filters.clone();
+    let set_fold = Rc::new(move |fold: Option<LineFold>| {
+        *line_fold_set.borrow_mut() = fold;
+        set_filters_fold(current_filters_fold());
+    });
+    // Hide view lines `start..=end`, by the file lines they show
+    let view_filter_hide = view_filter.clone();
+    let hidden_lines_hide = hidden_lines.clone();
+    let set_filters_hide = set_filters.clone();
+    let current_filters_hide = current_filters.clone();
+    let hide_lines: Rc<dyn Fn(usize, usize)> = Rc::new(move |start, end| {
+        let file_line = |line| {
+            view_filter_hide.borrow().as_ref().map_or(Some(line), |f| f.source_line(line))
+        };
+        if let (Some(start), Some(end)) = (file_line(start), file_line(end)) {
+            filter::hide_range(&mut hidden_lines_hide.borrow_mut(), start, end + 1);
+            set_filters_hide(current_filters_hide());
+        }
+    });
+
+    // Clicking a folded row's count shows its run in full, and clicking the
+    // hidden lines' count after a row shows them again
+    let view_filter_link = view_filter.clone();
+    let line_fold_link = line_fold.clone();
+    let hidden_lines_link = hidden_lines.clone();
+    let set_fold_link = set_fold.clone();
+    let set_filters_link = set_filters.clone();
+    let current_filters_link = current_filters.clone();
+    let fold_link = move |link: &str, view_line: usize| {
+        let filter = view_filter_link.borrow().clone();
+        let Some(filter) = filter else {
+            return;
+        };
+        match link {
+            "unfold" => {
+                let fold = line_fold_link.borrow().clone();
+                if let (Some(head), Some(mut fold)) = (filter.source_line(view_line), fold) {
+                    fold.expanded.insert(head);
+                    set_fold_link(Some(fold));
+                }
+            }
+            "unhide" => {
+                if let Some(hidden) = filter.hidden(view_line) {
+                    hidden_lines_link.borrow_mut().retain(|range| !hidden.ranges.contains(range));
+                    set_filters_link(current_filters_link());
+                }
+            }
+            _ => {}
+        }
+    };
+
+    // Fetch the visible lines again, to show changed decorations, and draw
+    // the overview's marks again
+    let v_adjustment_redraw = v_adjustment.clone();
+    let latest_request_id_redraw = latest_request_id.clone();
+    let request_tx_redraw = request_tx.clone();
+    let overview_redraw = overview.clone();
+    let redraw: Rc<dyn Fn()> = Rc::new(move || {
+        overview_redraw.queue_draw();
+        let request_id = next_request_id();
+        *latest_request_id_redraw.borrow_mut() = request_id;
+        let _ = request_tx_redraw.send_blocking(FileRequest::GetLines {
+            start: v_adjustment_redraw.value() as usize,
+            count: fetch_lines(&v_adjustment_redraw),
+            request_id,
+        });
+    });
+
+    // Lines longer than max_line_columns are truncated unless expanded
+    let redraw_long_lines = redraw.clone();
+    let long_lines = Rc::new(LongLines {
+        max_columns: options.max_line_columns,
+        expanded: RefCell::new(HashSet::new()),
+        redraw: Box::new(move || redraw_long_lines()),
+    });
+
+    let line_rows = Rc::new(LineRows::new(
+        &line_numbers_box,
+        &content_box,
+        long_lines.clone(),
+        Rc::new(fold_link),
+    ));
+
+    // Scrolled part way into the top line, the rows move up by that part
+    // of a row, once they show that line; wrapped lines, whose rows differ
+    // in height, scroll by whole lines
+    let v_adjustment_offset = v_adjustment.clone();
+    let line_rows_offset = line_rows.clone();
+    let current_line_offset = current_line.clone();
+    let table_header_offset = table_header.clone();
+    let h_scroll_offset = h_scroll.clone();
+    let line_numbers_scroll_offset = line_numbers_scroll.clone();
+    let show_offset: Rc<dyn Fn()> = Rc::new(move || {
+        let value = v_adjustment_offset.value();
+        let top = value.floor();
+        let offset = if line_rows_offset.wrap.get() || *current_line_offset.borrow() != top as usize
+        {
+            0.0
+        } else {
+            (value - top) * row_height(&table_header_offset) as f64
+        };
+        h_scroll_offset.vadjustment().set_value(offset);
+        line_numbers_scroll_offset.vadjustment().set_value(offset);
+    });
+
+    // Make view line `line` the cursor line, scrolling only as far as it
+    // takes to bring it on screen
+    let v_adjustment_cursor = v_adjustment.clone();
+    let cursor_position_move = cursor_position.clone();
+    let line_rows_cursor = line_rows.clone();
+    let total_lines_cursor = total_lines.clone();
+    let move_cursor: Rc<dyn Fn(usize)> = Rc::new(move |line| {
+        let line = line.min(total_lines_cursor.get().saturating_sub(1));
+        *cursor_position_move.borrow_mut() = line;
+        let top = v_adjustment_cursor.value() as usize;
+        let page = page_lines(&v_adjustment_cursor).max(1);
+        if line < top {
+            v_adjustment_cursor.set_value(line as f64);
+        } else if line >= top + page {
+            v_adjustment_cursor.set_value((line + 1 - page) as f64);
+        }
+        line_rows_cursor.show_cursor(line);
+    });
+
+    // Fill the marks panel with the marked lines in file line order; rows jump
+    // to their line, and their buttons send the line to unmark
+    let (mark_remove_tx, mark_remove_rx) = async_channel::unbounded::<usize>();
+    let mark_remove_rx_destroy = mark_remove_rx.clone();
+    window.connect_destroy(move |_| {
+        mark_remove_rx_destroy.close();
+    });
+    // Rows of the marks panel and the pinned strip jump to their file line,
+    // or the first one kept after it
+    let jump_to_mark: Rc<dyn Fn(usize)> = {
+        let v_adjustment = v_adjustment.clone();
+        let move_cursor = move_cursor.clone();
+        let view_filter = view_filter.clone();
+        Rc::new(move |file_line| {
+            let line = view_filter.borrow().as_ref().map_or(file_line, |f| f.view_line(file_line));
+            v_adjustment.set_value(line as f64);
+            move_cursor(line);
+        })
+    };
+    let marks_panel_refresh = marks_panel.clone();
+    let marked_lines_panel = marked_lines.clone();
+    let file_source_panel = file_source.clone();
+    let jump_to_mark_panel = jump_to_mark.clone();
+    let refresh_marks_panel: Rc<dyn Fn()> = Rc::new(move || {
+        if !marks_panel_refresh.is_visible() {
+            return;
+        }
+        while let Some(child) = marks_list.first_child() {
+            marks_list.remove(&child);
+        }
+        let marks = marked_lines_panel.borrow();
+        let mut lines: Vec<(usize, &str)> = marks
+            .iter()
+            .filter_map(|(line, markings)| markings.color().map(|color| (*line, color)))
+            .collect();
+        lines.sort_unstable();
+        marks_panel_title.set_text(&format!("Marks ({})", lines.len()));
+
+        for &(line, color) in lines.iter().take(MAX_MARKS_PANEL_ROWS) {
+            let text = file_source_panel.get_line(line).ok().flatten().unwrap_or_default();
+            append_mark_row(&marks_list, line, color, &text, &jump_to_mark_panel, &mark_remove_tx);
+        }
+        if lines.len() > MAX_MARKS_PANEL_ROWS {
+            let more = Label::new(Some(&format!("{} more", lines.len() - MAX_MARKS_PANEL_ROWS)));
+            more.set_css_classes(&["line-number"]);
+            marks_list.append(&more);
+        }
+    });
+
+    // After marks change outside a command: redraw them, list them and
+    // tell `marks` subscribers, as a command batch would
+    let marked_lines_changed = marked_lines.clone();
+    let redraw_marks = redraw.clone();
+    let refresh_marks_panel_changed = refresh_marks_panel.clone();
+    let events_marks = events.clone();
+    let marks_changed = Rc::new(move || {
+        redraw_marks();
+        refresh_marks_panel_changed();
+        let count = marked_lines_changed.borrow().len();
+        events_marks.publish(&ViewerEvent::Marks { count });
+    });
+
+    let marked_lines_remove = marked_lines.clone();
+    let marks_changed_remove = marks_changed.clone();
+    glib::spawn_future_local(async move {
+        while let Ok(line) = mark_remove_rx.recv().await {
+            if marked_lines_remove.borrow_mut().remove(&line).is_some() {
+                marks_changed_remove();
+            }
+        }
+    });
+
+    // Mark a view line in the theme's first highlight color, or unmark it
+    let marked_lines_toggle = marked_lines.clone();
+    let view_filter_toggle = view_filter.clone();
+    let theme_toggle = theme.clone();
+    let toggle_mark = Rc::new(move |line: usize| {
+        let filter = view_filter_toggle.borrow().clone();
+        let Some(line) = filter.as_ref().map_or(Some(line), |f| f.source_line(line)) else {
+            return;
+        };
+        {
+            let mut marks = marked_lines_toggle.borrow_mut();
+            if marks.remove(&line).is_none() {
+                let color = theme_toggle.get().highlight_palette()[0];
+                let markings = LineMarkings {
+                    full_line_color: Some(color.to_string()),
+                    regions: Vec::new(),
+                };
+                marks.insert(line, markings);
+            }
+        }
+        marks_changed();
+    });
+
+    // Show or hide the marks panel, listing the marks as they are now
+    let marks_panel_set = marks_panel.clone();
+    let refresh_marks_panel_set = refresh_marks_panel.clone();
+    let set_marks_panel = Rc::new(move |visible: bool| {
+        marks_panel_set.set_visible(visible);
+        refresh_marks_panel_set();
+    });
+    if initial.marks_panel {
+        set_marks_panel(true);
+    }
+
+    // List the pinned lines in the strip above the view, read from the file
+    // so filters do not drop them; rows jump to their line, or the first
+    // one kept after it, and their buttons send the line to unpin
+    let pinned_lines: Rc<RefCell<Vec<usize>>> = Rc::new(RefCell::new(initial.pinned));
+    let (pin_remove_tx, pin_remove_rx) = async_channel::unbounded::<usize>();
+    let pin_remove_rx_destroy = pin_remove_rx.clone();
+    window.connect_destroy(move |_| {
+        pin_remove_rx_destroy.close();
+    });
+    let jump_to_pinned = jump_to_mark.clone();
+    let pinned_lines_refresh = pinned_lines.clone();
+    let file_source_pinned = file_source.clone();
+    let refresh_pinned: Rc<dyn Fn()> = Rc::new(move || {
+        while let Some(child) = pinned_strip.first_child() {
+            pinned_strip.remove(&child);
+        }
+        let pinned = pinned_lines_refresh.borrow();
+        for &line in pinned.iter() {
+            let text = file_source_pinned.get_line(line).ok().flatten().unwrap_or_default();
+            append_pinned_row(&pinned_strip, line, &text, &jump_to_pinned, &pin_remove_tx);
+        }
+        pinned_strip.set_visible(!pinned.is_empty());
+    });
+    refresh_pinned();
+
+    // Pin file line `line`, or unpin it; returns whether it changed
+    let pinned_lines_pin = pinned_lines.clone();
+    let refresh_pinned_pin = refresh_pinned.clone();
+    let pin_line: Rc<dyn Fn(usize, bool) -> Result<bool, String>> = Rc::new(move |line, pin| {
+        {
+            let mut pinned = pinned_lines_pin.borrow_mut();
+            match (pinned.binary_search(&line), pin) {
+                (Err(at), true) => {
+                    if pinned.len() >= MAX_PINNED_LINES {
+                        return Err(format!(
+                            "too many pinned lines: at most {}",
+                            MAX_PINNED_LINES
+                        ));
+                    }
+                    pinned.insert(at, line);
+                }
+                (Ok(at), false) => {
+                    pinned.remove(at);
+                }
+                _ => return Ok(false),
+            }
+        }
+        refresh_pinned_pin();
+        Ok(true)
+    });
+    let pin_line_remove = pin_line.clone();
+    glib::spawn_future_local(async move {
+        while let Ok(line) = pin_remove_rx.recv().await {
+            let _ = pin_line_remove(line, false);
+        }
+    });
+
+    // Response handler
+    let line_rows_response = line_rows.clone();
+    let long_lines_response = long_lines.clone();
+    let current_line_response = current_line.clone();
+    let show_offset_response = show_offset.clone();
+    let latest_request_id_response = latest_request_id.clone();
+    let marked_lines_response = marked_lines.clone();
+    let refresh_marks_panel_response = refresh_marks_panel.clone();
+    let refresh_pinned_response = refresh_pinned.clone();
+    let annotations_response = annotations.clone();
+    let bookmarks_response = bookmarks.clone();
+    let search_state_response = search_state.clone();
+    let field_styles_response = field_styles.clone();
+    let highlights_response = highlights.clone();
+    let level_style_response = level_style.clone();
+    let time_column_response = time_column.clone();
+    let table_response = table.clone();
+    let logfmt_response = logfmt.clone();
+    let show_table_header_response = show_table_header.clone();
+    let level_colors = options.level_colors.clone();
+    let color_rules = options.rule_set.for_file(file_source.display_name());
+    let search_info_response = search_info.clone();
+    let v_adjustment_response = v_adjustment.clone();
+    let request_tx_response = request_tx.clone();
+    let total_lines_response = total_lines.clone();
+    let show_notice_response = show_notice.clone();
+    let show_error_response = show_error.clone();
+    let show_file_info_response = show_file_info.clone();
+    let redraw_response = redraw.clone();
+    let cursor_position_response = cursor_position.clone();
+    let view_mode_response = view_mode.clone();
+    let file_source_response = file_source.clone();
+    let view_filter_response = view_filter.clone();
+    let update_filter_counts_response = update_filter_counts.clone();
+    let overview_response = overview.clone();
+    let events_response = events.clone();
+    let exporting_response = exporting.clone();
+    let theme_response = theme.clone();
+    let follow_button_response = follow_button.clone();
+    let new_lines_badge_response = new_lines_badge.clone();
+    let unseen_lines_response = unseen_lines.clone();
+
+    glib::spawn_future_local(async move {
+        // Drop state that points past the end of rewritten content. While the
+        // file is still being indexed, wait for the exact count
+        let drop_past_end = |line_count: usize| {
+            marked_lines_response.borrow_mut().retain(|line, _| *line < line_count);
+            refresh_marks_panel_response();
+            annotations_response.borrow_mut().retain(|line, _| *line < line_count);
+            bookmarks_response.borrow_mut().retain(|_, line| *line < line_count);
+        };
+        let mut drop_past_end_pending = false;
+        while let Ok(response) = response_rx.recv().await {
+            match response {
+                FileResponse::Lines {
+                    lines,
+                    request_id,
+                    start,
+                    has_binary,
+                    hex_dumps,
+                    previous_time,
+                } => {
+                    let latest = *latest_request_id_response.borrow();
+                    // Only display if this is the most recent request
+                    if request_id == latest {
+                        if has_binary
+                            && view_mode_response.get() == ViewMode::Text
+                            && !binary_notice_shown.replace(true)
+                        {
+                            show_notice_response("Binary data: Ctrl+H shows it as hex", false);
+                        }
+                        let wrapped_rows = populate_lines(
+                            &line_rows_response,
+                            file_source_response.as_ref(),
+                            &lines,
+                            &marked_lines_response.borrow(),
+                            &annotations_response.borrow(),
+                            &search_state_response.borrow(),
+                            &field_styles_response,
+                            &highlights_response.borrow(),
+                            &color_rules,
+                            &level_colors,
+                            level_style_response.get(),
+                            time_column_response.get().then_some(previous_time),
+                            table_response.borrow_mut().as_mut(),
+                            &logfmt_response.borrow(),
+                            view_filter_response.borrow().as_deref(),
+                            &hex_dumps,
+                            theme_response.get(),
+                            *cursor_position_response.borrow(),
+                        );
+                        // The columns may have widened to fit the new lines
+                        show_table_header_response(table_response.borrow().as_ref());
+                        if let Some(rows) = wrapped_rows {
+                            fit_wrapped_page(
+                                &line_rows_response,
+                                &v_adjustment_response,
+                                &rows,
+                                start,
+                                total_lines_response.get(),
+                            );
+                        }
+                        *current_line_response.borrow_mut() = start;
+                        show_offset_response();
+                        // Searches changed or cleared always redraw the lines
+                        overview_response.queue_draw();
+                    }
+                }
+                FileResponse::Error { message, retry } => {
+                    eprintln!("Error: {}", message);
+                    // A page that failed to load is fetched again by redrawing
+                    let retry = retry.then(|| redraw_response.clone());
+                    show_error_response(&message, retry);
+                }
+                FileResponse::SearchResults {
+                    matches,
+                    searched_range,
+                    navigate_to_first,
+                    ..
+                } => {
+                    // The match count in the search bar comes from the whole-file count
+                    let first_match_line = {
+                        let mut state = search_state_response.borrow_mut();
+                        state.update_matches(matches, searched_range);
+                        state.current_match().map(|m| m.line_num)
+                    };
+
+                    // Only navigate to first match on initial search, not on re-search
+                    if navigate_to_first {
+                        if let Some(line) = first_match_line {
+                            v_adjustment_response.set_value(line as f64);
+                        }
+                    }
+
+                    // Trigger redraw with highlights
+                    let start = v_adjustment_response.value() as usize;
+                    let request_id = next_request_id();
+                    *latest_request_id_response.borrow_mut() = request_id;
+                    let _ = request_tx_response.send_blocking(FileRequest::GetLines {
+                        start,
+                        count: fetch_lines(&v_adjustment_response),
+                        request_id,
+                    });
+                }
+                FileResponse::MatchCountProgress {
+                    matches,
+                    searched_lines,
+                    line_count,
+                    request_id,
+                } => {
+                    if search_state_response.borrow().count_request_id == Some(request_id) {
+                        let percent = searched_lines * 100 / line_count.max(1);
+                        search_info_response.set_text(&format!(
+                            "Counting... {} so far ({}%)",
+                            describe_match_count(matches),
+                            percent
+                        ));
+                    }
+                }
+                FileResponse::MatchCountDone {
+                    matches,
+                    match_positions,
+                    match_density,
+                    request_id,
+                } => {
+                    let mut state = search_state_response.borrow_mut();
+                    if state.count_request_id == Some(request_id) {
+                        state.update_match_count(
+                            request_id,
+                            matches,
+                            match_positions,
+                            match_density,
+                        );
+                        search_info_response.set_text(&describe_match_count(matches));
+                        overview_response.queue_draw();
+                        events_response.publish(&ViewerEvent::SearchDone { matches });
+                    }
+                }
+                FileResponse::ExportProgress { written, total } => {
+                    show_notice_response(
+                        &format!(
+                            "Exporting... {} of {} lines ({}%)",
+                            written,
+                            total,
+                            written * 100 / total.max(1)
+                        ),
+                        false,
+                    );
+                    events_response.publish(&ViewerEvent::ExportProgress { written, total });
+                }
+                FileResponse::ExportDone { lines, path } => {
+                    exporting_response.set(false);
+                    let path = path.display().to_string();
+                    let plural = if lines == 1 { "" } else { "s" };
+                    show_notice_response(
+                        &format!("Exported {} line{} to {}", lines, plural, path),
+                        false,
+                    );
+                    events_response.publish(&ViewerEvent::ExportDone { lines, path });
+                }
+                FileResponse::ExportFailed { message } => {
+                    exporting_response.set(false);
+                    show_notice_response(&format!("Export failed: {}", message), true);
+                    events_response.publish(&ViewerEvent::ExportFailed { message });
+                }
+                FileResponse::Filtered { line_count, done } => {
+                    total_lines_response.set(line_count);
+                    show_file_info_response(line_count);
+                    v_adjustment_response.set_upper(line_count as f64);
+                    update_filter_counts_response(done);
+                    {
+                        let mut cursor = cursor_position_response.borrow_mut();
+                        *cursor = (*cursor).min(line_count.saturating_sub(1));
+                    }
+
+                    let start = v_adjustment_response.value() as usize;
+                    let request_id = next_request_id();
+                    *latest_request_id_response.borrow_mut() = request_id;
+                    let _ = request_tx_response.send_blocking(FileRequest::GetLines {
+                        start,
+                        count: fetch_lines(&v_adjustment_response),
+                        request_id,
+                    });
+
+                    // Lines appeared under the viewport or were filtered away
+                    let state = search_state_response.borrow();
+                    if state.is_active && state.pattern.is_some() {
+                        let pattern = state.pattern_str.clone();
+                        let multiline = state.multiline;
+                        drop(state);
+                        let page = page_lines(&v_adjustment_response);
+                        let (search_start, search_end) =
+                            search_config.window(start, page, line_count);
+                        let _ = request_tx_response.send_blocking(FileRequest::SearchRange {
+                            pattern,
+                            start_line: search_start,
+                            end_line: search_end,
+                            request_id: next_request_id(),
+                            navigate_to_first: false,
+                            multiline,
+                        });
+                    }
+                }
+                FileResponse::FoundMatch { match_info, line_num, direction, wrapped, .. } => {
+                    if let Some(line) = line_num {
+                        let state = search_state_response.borrow();
+                        let ordinal = match_info.as_ref().and_then(|m| state.match_ordinal(m));
+                        let position = match (ordinal, state.total_matches) {
+                            (Some(n), Some(total)) => format!("{} of {}", n, total),
+                            _ => format!("Match at line {}", line + 1),
+                        };
+                        let wrap_note = match (wrapped, direction) {
+                            (false, _) => "",
+                            (true, SearchDirection::Forward) => " (wrapped to top)",
+                            (true, SearchDirection::Backward) => " (wrapped to bottom)",
+                        };
+                        search_info_response.set_text(&format!("{}{}", position, wrap_note));
+                        v_adjustment_response.set_value(line as f64);
+                        *cursor_position_response.borrow_mut() = line;
+                        line_rows_response.show_cursor(line);
+                    } else {
+                        search_info_response.set_text("No more matches");
+                    }
+                }
+                FileResponse::SourceChanged { change, line_count } => {
+                    let previous = total_lines_response.replace(line_count);
+                    show_file_info_response(line_count);
+                    let was_at_end = at_end(&v_adjustment_response);
+                    v_adjustment_response.set_upper(line_count as f64);
+
+                    match change {
+                        SourceChange::Truncated
+                        | SourceChange::Replaced
+                        | SourceChange::Reloaded => {
+                            // The content under every line number is new: drop state that
+                            // points past the end and matches that no longer apply
+                            if file_source_response.indexing_progress().is_none() {
+                                drop_past_end(line_count);
+                            } else {
+                                drop_past_end_pending = true;
+                            }
+                            long_lines_response.expanded.borrow_mut().clear();
+                            {
+                                let mut cursor = cursor_position_response.borrow_mut();
+                                *cursor = (*cursor).min(line_count.saturating_sub(1));
+                            }
+                            search_state_response.borrow_mut().invalidate_matches();
+
+                            let last_top = (line_count as f64 - v_adjustment_response.page_size()).max(0.0);
+                            if v_adjustment_response.value() > last_top {
+                                v_adjustment_response.set_value(last_top);
+                            }
+
+                            let message = match change {
+                                SourceChange::Truncated => {
+                                    "File was truncated; showing its new content"
+                                }
+                                SourceChange::Reloaded => "File reloaded",
+                                _ => "File was rotated; showing the new file",
+                            };
+                            show_notice_response(message, true);
+                        }
+                        SourceChange::Grew | SourceChange::Unchanged => {
+                            if line_count == previous {
+                                continue;
+                            }
+                            let added = line_count.saturating_sub(previous);
+                            if follow_button_response.is_active() {
+                                if was_at_end {
+                                    scroll_by(&v_adjustment_response, v_adjustment_response.upper());
+                                } else {
+                                    let unseen = unseen_lines_response.get() + added;
+                                    unseen_lines_response.set(unseen);
+                                    new_lines_badge_response.set_label(&describe_new_lines(unseen));
+                                    new_lines_badge_response.set_visible(true);
+                                }
+                            } else if notify_new_lines {
+                                show_notice_response(&describe_new_lines(added), false);
+                            }
+                        }
+                        SourceChange::Indexed => {
+                            let message = match file_source_response.indexing_progress() {
+                                Some(progress) => format!("Indexing... {:.0}%", progress * 100.0),
+                                None => {
+                                    if std::mem::take(&mut drop_past_end_pending) {
+                                        drop_past_end(line_count);
+                                    }
+                                    format!("Indexed {} lines", line_count)
+                                }
+                            };
+                            show_notice_response(&message, false);
+                        }
+                    }
+
+                    events_response.publish(&ViewerEvent::File { change, lines: line_count });
+
+                    // Redraw if the viewport shows lines that changed or just appeared;
+                    // while indexing, any line past the indexed part may have appeared
+                    let start = v_adjustment_response.value() as usize;
+                    let rewritten = change.is_rewrite();
+                    if rewritten {
+                        // Pinned lines keep their numbers but show the new text
+                        refresh_pinned_response();
+                    }
+                    let indexed = change == SourceChange::Indexed;
+                    let page = page_lines(&v_adjustment_response);
+                    if rewritten || indexed || start + page > previous {
+                        let request_id = next_request_id();
+                        *latest_request_id_response.borrow_mut() = request_id;
+                        let _ = request_tx_response.send_blocking(FileRequest::GetLines {
+                            start,
+                            count: fetch_lines(&v_adjustment_response),
+                            request_id,
+                        });
+                    }
+
+                    let state = search_state_response.borrow();
+                    if rewritten && state.is_active && state.pattern.is_some() {
+                        let pattern = state.pattern_str.clone();
+                        let multiline = state.multiline;
+                        drop(state);
+                        let page = page_lines(&v_adjustment_response);
+                        let (search_start, search_end) =
+                            search_config.window(start, page, line_count);
+                        let _ = request_tx_response.send_blocking(FileRequest::SearchRange {
+                            pattern,
+                            start_line: search_start,
+                            end_line: search_end,
+                            request_id: next_request_id(),
+                            navigate_to_first: false,
+                            multiline,
+                        });
+                    }
+                }
+            }
+        }
+    });
+
+    let notice_box_close = notice_box.clone();
+    notice_close_button.connect_clicked(move |_| notice_box_close.set_visible(false));
+
+    if let Some(path) = &options.watch_path {
+        match watcher::watch(path) {
+            Ok(watch) => {
+                let changes = watch.changes.clone();
+                let request_tx_watch = request_tx.clone();
+                glib::spawn_future_local(async move {
+                    // Ends when the watch is dropped with the window
+                    while changes.recv().await.is_ok() {
+                        request_tx_watch.refresh();
+                    }
+                });
+                let watch = Cell::new(Some(watch));
+                window.connect_destroy(move |_| drop(watch.take()));
+            }
+            Err(e) => {
+                let message = format!("Failed to watch {} for changes: {}", path.display(), e);
+                eprintln!("{}", message);
+                show_error(&message, None);
+            }
+        }
+    }
+
+    // Poll background indexing or a pipe so the scroll range grows until the
+    // line count is exact
+    let needs_polling = |source: &Arc<dyn FileSource>| {
+        source.indexing_progress().is_some() || source.is_streaming()
+    };
+    if needs_polling(&file_source) {
+        let window_index = window.downgrade();
+        let file_source_index = file_source.clone();
+        let request_tx_index = request_tx.clone();
+        glib::timeout_add_local(INDEX_POLL_INTERVAL, move || {
+            if window_index.upgrade().is_none() {
+                return glib::ControlFlow::Break;
+            }
+            // Checked before refreshing so the last refresh sees the final state
+            let polling = needs_polling(&file_source_index);
+            request_tx_index.refresh();
+            if polling {
+                glib::ControlFlow::Continue
+            } else {
+                glib::ControlFlow::Break
+            }
+        });
+    }
+
+    // Show when a remote source lost its connection; fetches wait meanwhile
+    // and complete once it is back
+    if file_source.connection_state().is_some() {
+        let window_connection = window.downgrade();
+        let file_source_connection = file_source.clone();
+        let show_notice_connection = show_notice.clone();
+        glib::timeout_add_local(CONNECTION_POLL_INTERVAL, move || {
+            if window_connection.upgrade().is_none() {
+                return glib::ControlFlow::Break;
+            }
+            match file_source_connection.connection_state() {
+                Some(ConnectionState::Reconnecting { attempts, error }) => {
+                    let mut text = format!("Connection lost, reconnecting (attempt {})", attempts + 1);
+                    if !error.is_empty() {
+                        text.push_str(&format!(": {}", error));
+                    }
+                    connection_label.set_text(&text);
+                    connection_banner.set_visible(true);
+                }
+                // The fetches that were waiting fail and show their error
+                Some(ConnectionState::Failed { .. }) => connection_banner.set_visible(false),
+                _ if connection_banner.is_visible() => {
+                    connection_banner.set_visible(false);
+                    show_notice_connection("Reconnected", false);
+                }
+                _ => {}
+            }
+            glib::ControlFlow::Continue
+        });
+    }
+
+    // Open a second window on the same source (and worker/cache) at the current position
+    let app_dup = app.clone();
+    let file_source_dup = file_source.clone();
+    let workers_dup = workers.clone();
+    let options_dup = options.clone();
+    let v_adjustment_dup = v_adjustment.clone();
+    let marked_lines_dup = marked_lines.clone();
+    let annotations_dup = annotations.clone();
+    let bookmarks_dup = bookmarks.clone();
+    let view_mode_dup = view_mode.clone();
+    let line_rows_dup = line_rows.clone();
+    let current_filters_dup = current_filters.clone();
+    let line_fold_dup = line_fold.clone();
+    let hidden_lines_dup = hidden_lines.clone();
+    let pinned_lines_dup = pinned_lines.clone();
+    let highlights_dup = highlights.clone();
+    let level_style_dup = level_style.clone();
+    let time_column_dup = time_column.clone();
+    let table_dup = table.clone();
+    let logfmt_dup = logfmt.clone();
+    let marks_panel_dup = marks_panel.clone();
+    let follow_button_dup = follow_button.clone();
+    let duplicate_view = Rc::new(move || {
+        let state = ViewState {
+            top_line: v_adjustment_dup.value() as usize,
+            marks: marked_lines_dup.borrow().clone(),
+            annotations: annotations_dup.borrow().clone(),
+            bookmarks: bookmarks_dup.borrow().clone(),
+            view_mode: view_mode_dup.get(),
+            wrap: line_rows_dup.wrap.get(),
+            relative_numbers: line_rows_dup.relative.get(),
+            level_style: level_style_dup.get(),
+            time_column: time_column_dup.get(),
+            table: table_dup.borrow().clone(),
+            logfmt: logfmt_dup.borrow().clone(),
+            marks_panel: marks_panel_dup.is_visible(),
+            follow: follow_button_dup.is_active(),
+            filters: current_filters_dup(),
+            fold: line_fold_dup.borrow().clone(),
+            hidden: hidden_lines_dup.borrow().clone(),
+            pinned: pinned_lines_dup.borrow().clone(),
+            highlights: highlights_dup.borrow().clone(),
+        };
+        build_ui(&app_dup, file_source_dup.clone(), &workers_dup, &options_dup, state)
+    });
+
+    // Show another file in this window's place: a window on it opens with
+    // this one's display settings, and this one closes first, freeing its
+    // command port for the new window. The marks stay with the old file,
+    // saved to the `--marks` file as on any close.
+    let app_open = app.clone();
+    let workers_open = workers.clone();
+    let options_open = options.clone();
+    let window_open = window.downgrade();
+    let line_rows_open = line_rows.clone();
+    let level_style_open = level_style.clone();
+    let logfmt_open = logfmt.clone();
+    let highlights_open = highlights.clone();
+    let follow_button_open = follow_button.clone();
+    let line_fold_open = line_fold.clone();
+    let open_file: OpenFile = Rc::new(move |file| {
+        let source = open_file_source(file, None).map_err(|e| e.to_string())?;
+        let options = UiOptions {
+            watch_path: match file {
+                FilePath::Local(path) => Some(path.clone()),
+                _ => None,
+            },
+            marks_path: None,
+            ..options_open.clone()
+        };
+        let follow = follow_button_open.is_active();
+        let initial = ViewState {
+            top_line: if follow { source.line_count().saturating_sub(LINES_PER_PAGE) } else { 0 },
+            wrap: line_rows_open.wrap.get(),
+            level_style: level_style_open.get(),
+            logfmt: LogfmtView::new(logfmt_open.borrow().enabled),
+            highlights: highlights_open.borrow().clone(),
+            follow,
+            // The expanded runs were lines of the old file
+            fold: line_fold_open.borrow().as_ref().map(|fold| LineFold::new(fold.mode)),
+            ..ViewState::default()
+        };
+        let app = app_open.clone();
+        let workers = workers_open.clone();
+        let window = window_open.clone();
+        // After the handler asking for it returns, e.g. once a command's
+        // response is sent
+        glib::idle_add_local_once(move || {
+            // The application keeps running while it has no window
+            let _hold = app.hold();
+            if let Some(window) = window.upgrade() {
+                window.close();
+            }
+            build_ui(&app, source, &workers, &options, initial);
+        });
+        Ok(())
+    });
+
+    // Open a local file from the window, reporting a failure in the notice
+    let open_file_local = open_file.clone();
+    let show_notice_open = show_notice.clone();
+    let open_path = Rc::new(move |path: std::path::PathBuf| {
+        if let Err(e) = open_file_local(&FilePath::Local(path.clone())) {
+            show_notice_open(&format!("Failed to open {}: {}", path.display(), e), true);
+        }
+    });
+
+    // A file dropped on the window is opened in its place
+    let drop_target =
+        gtk4::DropTarget::new(gtk4::gio::File::static_type(), gtk4::gdk::DragAction::COPY);
+    let open_path_drop = open_path.clone();
+    drop_target.connect_drop(move |_, value, _, _| {
+        match value.get::<gtk4::gio::File>().ok().and_then(|file| file.path()) {
+            Some(path) => {
+                open_path_drop(path);
+                true
+            }
+            None => false,
+        }
+    });
+    window.add_controller(drop_target);
+    if file_source.display_name() == EmptySource::NAME {
+        show_notice("Drop a log file here, or press Ctrl+O to open one", true);
+    }
+
+    // Switch between text and hex rendering of binary lines and redraw
+    let view_mode_set = view_mode.clone();
+    let request_tx_mode = request_tx.clone();
+    let latest_request_id_mode = latest_request_id.clone();
+    let v_adjustment_mode = v_adjustment.clone();
+    let set_view_mode = Rc::new(move |mode: ViewMode| {
+        view_mode_set.set(mode);
+        request_tx_mode.set_view_mode(mode);
+
+        let request_id = next_request_id();
+        *latest_request_id_mode.borrow_mut() = request_id;
+        let _ = request_tx_mode.send_blocking(FileRequest::GetLines {
+            start: v_adjustment_mode.value() as usize,
+            count: fetch_lines(&v_adjustment_mode),
+            request_id,
+        });
+    });
+
+    // Wrap long lines onto more rows, or scroll them sideways again, and redraw
+    let line_rows_wrap = line_rows.clone();
+    let h_scroll_wrap = h_scroll.clone();
+    let v_adjustment_wrap = v_adjustment.clone();
+    let total_lines_wrap = total_lines.clone();
+    let redraw_wrap = redraw.clone();
+    let wrap_button_set = wrap_button.clone();
+    let set_wrap = Rc::new(move |wrap: bool| {
+        line_rows_wrap.set_wrap(wrap);
+        wrap_button_set.set_active(wrap);
+        if wrap {
+            // Rows as wide as the view, so labels wrap at its edge
+            h_scroll_wrap.hadjustment().set_value(0.0);
+            h_scroll_wrap.set_hscrollbar_policy(PolicyType::Never);
+        } else {
+            h_scroll_wrap.set_hscrollbar_policy(PolicyType::Automatic);
+            // Every line takes one row again
+            v_adjustment_wrap.set_page_increment(v_adjustment_wrap.page_size());
+            v_adjustment_wrap.set_upper(total_lines_wrap.get() as f64);
+        }
+        redraw_wrap();
+    });
+    if initial.wrap {
+        set_wrap(true);
+    }
+    let line_rows_wrap_button = line_rows.clone();
+    let set_wrap_button = set_wrap.clone();
+    wrap_button.connect_toggled(move |button| {
+        if button.is_active() != line_rows_wrap_button.wrap.get() {
+            set_wrap_button(button.is_active());
+        }
+    });
+
+    // Number lines by their distance from the cursor line, or by their
+    // lines in the file again
+    let line_rows_relative = line_rows.clone();
+    let cursor_position_relative = cursor_position.clone();
+    let set_relative_numbers = Rc::new(move |relative: bool| {
+        line_rows_relative.relative.set(relative);
+        line_rows_relative.show_numbers(*cursor_position_relative.borrow());
+    });
+    set_relative_numbers(initial.relative_numbers);
+
+    // Show or hide the time column; the file worker then sends each page
+    // with the stamp before it and indexes the file's stamps
+    let time_column_set = time_column.clone();
+    let request_tx_time_column = request_tx.clone();
+    let redraw_time_column = redraw.clone();
+    let set_time_column = Rc::new(move |enabled: bool| {
+        time_column_set.set(enabled);
+        request_tx_time_column.set_time_column(enabled);
+        redraw_time_column();
+    });
+    if initial.time_column {
+        set_time_column(true);
+    }
+
+    // Turn the column view on with a delimiter, or the one most lines on
+    // screen have, or off; resizing and hiding columns go through `table`
+    let table_set = table.clone();
+    let line_rows_table = line_rows.clone();
+    let show_table_header_set = show_table_header.clone();
+    let redraw_table = redraw.clone();
+    let set_table = Rc::new(move |delimiter: Option<Option<Delimiter>>| {
+        let layout = delimiter.map(|delimiter| {
+            let delimiter = delimiter.unwrap_or_else(|| {
+                let shown: Vec<String> = line_rows_table
+                    .rows
+                    .borrow()
+                    .iter()
+                    .filter(|row| row.content.is_visible())
+                    .map(|row| row.content.text().to_string())
+                    .collect();
+                Delimiter::detect(shown.iter().map(String::as_str))
+            });
+            TableLayout::new(delimiter)
+        });
+        show_table_header_set(layout.as_ref());
+        *table_set.borrow_mut() = layout;
+        redraw_table();
+    });
+
+    // Resize a column by dragging its right edge in the header
+    let table_drag = table.clone();
+    let redraw_drag = redraw.clone();
+    let resizing: Rc<Cell<Option<(usize, usize)>>> = Rc::new(Cell::new(None));
+    let resizing_begin = resizing.clone();
+    // Measured on the header, in the lines' monospace font
+    let char_width = {
+        let table_header = table_header.clone();
+        move || table_header.create_pango_layout(Some("0")).pixel_size().0.max(1) as f64
+    };
+    let char_width_begin = char_width.clone();
+    let header_drag = gtk4::GestureDrag::new();
+    header_drag.connect_drag_begin(move |gesture, x, _| {
+        let col = (x / char_width_begin()) as usize;
+        let table = table_drag.borrow();
+        let edge = table.as_ref().and_then(|table| {
+            table.edge_at(col).map(|column| (column, table.width(column)))
+        });
+        resizing_begin.set(edge);
+        if edge.is_none() {
+            gesture.set_state(gtk4::EventSequenceState::Denied);
+        }
+    });
+    let table_drag = table.clone();
+    let resizing_update = resizing.clone();
+    let char_width_update = char_width.clone();
+    header_drag.connect_drag_update(move |_, dx, _| {
+        let Some((column, width)) = resizing_update.get() else {
+            return;
+        };
+        let resized = (width as f64 + dx / char_width_update()).round().max(1.0);
+        if let Some(table) = table_drag.borrow_mut().as_mut() {
+            if table.width(column) == resized as usize {
+                return;
+            }
+            table.set_width(column, resized as usize);
+        }
+        redraw_drag();
+    });
+    header_drag.connect_drag_end(move |_, _, _| resizing.set(None));
+    table_header.add_controller(header_drag);
 
-    let current_line: Rc<RefCell<usize>> = Rc::new(RefCell::new(0));
-    let latest_request_id: Rc<RefCell<u64>> = Rc::new(RefCell::new(0));
+    // The resize cursor over a column's edge
+    let table_hover = table.clone();
+    let table_header_hover = table_header.clone();
+    let header_motion = gtk4::EventControllerMotion::new();
+    header_motion.connect_motion(move |_, x, _| {
+        let col = (x / char_width()) as usize;
+        let on_edge = table_hover.borrow().as_ref().and_then(|table| table.edge_at(col)).is_some();
+        table_header_hover.set_cursor_from_name(on_edge.then_some("col-resize"));
+    });
+    table_header.add_controller(header_motion);
 
-    let (request_tx, request_rx) = async_channel::unbounded::<FileRequest>();
-    let (response_tx, response_rx) = async_channel::unbounded::<FileResponse>();
+    // Fit the page to the lines' height: as many lines as it holds in their
+    // font, less the column header's row. Showing the last line, the view
+    // stays at the end.
+    let v_adjustment_fit = v_adjustment.clone();
+    let h_scroll_fit = h_scroll.clone();
+    let table_header_fit = table_header.clone();
+    let fit_page = Rc::new(move || {
+        let mut height = h_scroll_fit.vadjustment().page_size() as i32;
+        if table_header_fit.is_visible() {
+            height -= table_header_fit.height();
+        }
+        let row_height = row_height(&table_header_fit);
+        if height <= 0 || row_height <= 0 {
+            return;
+        }
+        let lines = (height / row_height).clamp(1, MAX_PAGE_LINES as i32) as f64;
+        if lines != v_adjustment_fit.page_size() {
+            let following = at_end(&v_adjustment_fit);
+            v_adjustment_fit.set_page_increment(lines);
+            v_adjustment_fit.set_page_size(lines);
+            let delta = if following { v_adjustment_fit.upper() } else { 0.0 };
+            scroll_by(&v_adjustment_fit, delta);
+        }
+    });
+    let fit_page_resize = fit_page.clone();
+    h_scroll.vadjustment().connect_page_size_notify(move |_| fit_page_resize());
+    let fit_page_header = fit_page.clone();
+    table_header.connect_visible_notify(move |_| fit_page_header());
 
-    spawn_file_worker(file_source, request_rx, response_tx);
+    // Zoom the lines' font; wrapped lines are redrawn, as their rows change,
+    // and the page refitted once the new font is in place
+    let font_zoom = RefCell::new(options.font.clone());
+    let redraw_zoom = redraw.clone();
+    let fit_page_zoom = fit_page.clone();
+    let show_notice_zoom = show_notice.clone();
+    let configured_font = options.font.clone();
+    let zoom = move |steps: Option<i32>| {
+        let zoomed = match steps {
+            Some(steps) => font_zoom.borrow().zoomed(steps),
+            None => configured_font.clone(),
+        };
+        font_provider.load_from_string(&zoomed.css(&format!(".{}", window_class)));
+        show_notice_zoom(&format!("Font size {} pt", zoomed.size), false);
+        *font_zoom.borrow_mut() = zoomed;
+        redraw_zoom();
+        let fit_page = fit_page_zoom.clone();
+        glib::idle_add_local_once(move || fit_page());
+    };
 
-    // Response handler
-    let line_numbers_box_response = line_numbers_box.clone();
-    let content_box_response = content_box.clone();
-    let current_line_response = current_line.clone();
-    let latest_request_id_response = latest_request_id.clone();
-    let marked_lines_response = marked_lines.clone();
-    let search_state_response = search_state.clone();
-    let search_info_response = search_info.clone();
-    let v_adjustment_response = v_adjustment.clone();
-    let request_tx_response = request_tx.clone();
+    // Follow the desktop switching between light and dark, restyling the
+    // window and redrawing the lines in the theme's search colors
+    if options.theme == ThemeChoice::System {
+        let theme_change = theme.clone();
+        let css_provider_change = css_provider.clone();
+        let redraw_change = redraw.clone();
+        let on_change = Rc::new(move |settings: &gtk4::Settings| {
+            let changed = ThemeChoice::System.resolve(prefers_dark_theme(settings));
+            if theme_change.replace(changed) != changed {
+                css_provider_change.load_from_string(&changed.css());
+                redraw_change();
+            }
+        });
+        let on_theme_name = on_change.clone();
+        let handlers = vec![
+            gtk_settings.connect_gtk_theme_name_notify(move |settings| on_theme_name(settings)),
+            gtk_settings.connect_gtk_application_prefer_dark_theme_notify(move |settings| {
+                on_change(settings)
+            }),
+        ];
+        let handlers = Cell::new(handlers);
+        let gtk_settings_destroy = gtk_settings.clone();
+        window.connect_destroy(move |_| {
+            for handler in handlers.take() {
+                gtk_settings_destroy.disconnect(handler);
+            }
+        });
+    }
 
-    glib::spawn_future_local(async move {
-        while let Ok(response) = response_rx.recv().await {
-            match response {
-                FileResponse::Lines {
-                    lines,
-                    request_id,
-                    start,
-                } => {
-                    let latest = *latest_request_id_response.borrow();
-                    // Only display if this is the most recent request
-                    if request_id == latest {
-                        populate_lines(
-                            &line_numbers_box_response,
-                            &content_box_response,
-                            &lines,
-                            &marked_lines_response.borrow(),
-                            &search_state_response.borrow(),
-                        );
-                        *current_line_response.borrow_mut() = start;
-                    }
-                }
-                FileResponse::Error { message } => {
-                    eprintln!("Error: {}", message);
-                }
-                FileResponse::SearchResults {
-                    matches,
-                    searched_range,
-                    navigate_to_first,
-                    ..
-                } => {
-                    let match_count = matches.len();
-                    let first_match_line = {
-                        let mut state = search_state_response.borrow_mut();
-                        state.update_matches(matches, searched_range);
-                        state.current_match().map(|m| m.line_num)
-                    };
+    // A wider or narrower view wraps lines onto a different number of rows
+    let line_rows_resize = line_rows.clone();
+    let redraw_resize = redraw.clone();
+    h_scroll.hadjustment().connect_page_size_notify(move |_| {
+        if line_rows_resize.wrap.get() {
+            redraw_resize();
+        }
+    });
 
-                    if match_count == 0 {
-                        search_info_response.set_text("No matches");
-                    } else {
-                        search_info_response.set_text(&format!("{} matches", match_count));
-                        // Only navigate to first match on initial search, not on re-search
-                        if navigate_to_first {
-                            if let Some(line) = first_match_line {
-                                v_adjustment_response.set_value(line as f64);
-                            }
-                        }
-                    }
+    // Scroll to the first line stamped at or after `time`, found with the
+    // file's time index when unfiltered, else by binary search over the
+    // view's lines; returns its 0-based number
+    let view_filter_time = view_filter.clone();
+    let request_tx_time = request_tx.clone();
+    let file_source_time = file_source.clone();
+    let v_adjustment_time = v_adjustment.clone();
+    let cursor_position_time = cursor_position.clone();
+    let goto_time: Rc<dyn Fn(Timestamp) -> Result<usize, String>> = Rc::new(move |time| {
+        let filter = view_filter_time.borrow().clone();
+        let filtered = filter
+            .as_deref()
+            .map(|filter| FilteredView::new(file_source_time.as_ref(), filter));
+        let source: &dyn FileSource = match &filtered {
+            Some(view) => view,
+            None => file_source_time.as_ref(),
+        };
+        let index = filter.is_none().then(|| request_tx_time.time_index());
+        let line = timestamp::first_line_at_indexed(source, index, time)
+            .map_err(|e| e.to_string())?;
+        if line >= source.line_count() {
+            return Err("no line at or after that time".to_string());
+        }
+        v_adjustment_time.set_value(line as f64);
+        *cursor_position_time.borrow_mut() = line;
+        Ok(line)
+    });
 
-                    // Trigger redraw with highlights
-                    let start = v_adjustment_response.value() as usize;
-                    let request_id = next_request_id();
-                    *latest_request_id_response.borrow_mut() = request_id;
-                    let _ = request_tx_response.send_blocking(FileRequest::GetLines {
-                        start,
-                        count: LINES_PER_PAGE,
-                        request_id,
-                    });
-                }
-                FileResponse::FoundMatch { line_num, .. } => {
-                    if let Some(line) = line_num {
-                        search_info_response.set_text(&format!("Match at line {}", line + 1));
-                        v_adjustment_response.set_value(line as f64);
-                    } else {
-                        search_info_response.set_text("No more matches");
+    // Scroll to the line `percent` of the way through the view's lines, or
+    // through the file's bytes, where the view line is the first kept line
+    // at or after the source line holding that byte; returns its 0-based
+    // number
+    let view_filter_percent = view_filter.clone();
+    let file_source_percent = file_source.clone();
+    let total_lines_percent = total_lines.clone();
+    let v_adjustment_percent = v_adjustment.clone();
+    let cursor_position_percent = cursor_position.clone();
+    let goto_percent: Rc<dyn Fn(f64, PercentOf) -> Result<usize, String>> =
+        Rc::new(move |percent, of| {
+            let last = total_lines_percent.get().saturating_sub(1);
+            let line = match of {
+                PercentOf::Lines => (last as f64 * percent / 100.0).round() as usize,
+                PercentOf::Bytes => {
+                    let size = file_source_percent.file_size().map_err(|e| e.to_string())?;
+                    let offset = (size as f64 * percent / 100.0) as u64;
+                    let source_line = file_source_percent
+                        .line_at_offset(offset)
+                        .ok_or("byte offsets are not known for this file")?;
+                    match view_filter_percent.borrow().as_deref() {
+                        Some(filter) => filter.view_line(source_line),
+                        None => source_line,
                     }
                 }
             }
-        }
+            .min(last);
+            v_adjustment_percent.set_value(line as f64);
+            *cursor_position_percent.borrow_mut() = line;
+            Ok(line)
+        });
+
+    // Put the view's lines `start..start + count` on the clipboard, each
+    // ending in a line feed. The worker reads them, so a slow source leaves
+    // the window responsive, for up to the command timeout.
+    let request_tx_copy = request_tx.clone();
+    let clipboard = window.clipboard();
+    let command_timeout = options.command_timeout;
+    let copy_lines: CopyLines = Rc::new(move |start, count| {
+        let request_tx = request_tx_copy.clone();
+        let clipboard = clipboard.clone();
+        Box::pin(async move {
+            if count > MAX_COPY_LINES {
+                return Err(format!("too many lines: at most {} per copy", MAX_COPY_LINES));
+            }
+            let lines = request_tx
+                .call(
+                    |result_tx| FileRequest::ReadLines {
+                        start,
+                        count,
+                        result_tx,
+                    },
+                    glib::timeout_future(command_timeout),
+                )
+                .await??;
+            let text: String = lines.iter().map(|(_, text)| format!("{}\n", text)).collect();
+            clipboard.set_text(&text);
+            Ok(lines.len())
+        })
     });
 
     // Command handler for socket server
     let v_adjustment_cmd = v_adjustment.clone();
     let marked_lines_cmd = marked_lines.clone();
+    let annotations_cmd = annotations.clone();
+    let bookmarks_cmd = bookmarks.clone();
+    let marks_file_cmd = marks_file.clone();
     let request_tx_cmd = request_tx.clone();
-    let latest_request_id_cmd = latest_request_id.clone();
     let search_state_cmd = search_state.clone();
     let search_box_cmd = search_box.clone();
     let search_entry_cmd = search_entry.clone();
+    let search_case_button_cmd = search_case_button.clone();
+    let search_literal_button_cmd = search_literal_button.clone();
+    let search_multiline_button_cmd = search_multiline_button.clone();
+    let search_wrap_button_cmd = search_wrap_button.clone();
+    let syncing_search_toggles_cmd = syncing_search_toggles.clone();
+    let search_case_cmd = search_case.clone();
     let search_info_cmd = search_info.clone();
+    let field_styles_cmd = field_styles.clone();
     let cursor_position_cmd = cursor_position.clone();
+    let duplicate_view_cmd = duplicate_view.clone();
+    let view_mode_cmd = view_mode.clone();
+    let set_view_mode_cmd = set_view_mode.clone();
+    let line_rows_cmd = line_rows.clone();
+    let theme_cmd = theme.clone();
+    let set_wrap_cmd = set_wrap.clone();
+    let set_relative_numbers_cmd = set_relative_numbers.clone();
+    let total_lines_cmd = total_lines.clone();
+    let file_source_cmd = file_source.clone();
+    let view_filter_cmd = view_filter.clone();
+    let set_filters_cmd = set_filters.clone();
+    let line_fold_cmd = line_fold.clone();
+    let set_fold_cmd = set_fold.clone();
+    let hidden_lines_cmd = hidden_lines.clone();
+    let hide_lines_cmd = hide_lines.clone();
+    let pin_line_cmd = pin_line.clone();
+    let pinned_lines_cmd = pinned_lines.clone();
+    let current_filters_cmd = current_filters.clone();
+    let highlights_cmd = highlights.clone();
+    let level_style_cmd = level_style.clone();
+    let time_column_cmd = time_column.clone();
+    let set_time_column_cmd = set_time_column.clone();
+    let table_cmd = table.clone();
+    let logfmt_cmd = logfmt.clone();
+    let set_table_cmd = set_table.clone();
+    let marks_panel_cmd = marks_panel.clone();
+    let follow_button_cmd = follow_button.clone();
+    let open_file_cmd = open_file.clone();
+    let set_marks_panel_cmd = set_marks_panel.clone();
+    let refresh_marks_panel_cmd = refresh_marks_panel.clone();
+    let redraw_cmd = redraw.clone();
+    let app_quit = app.downgrade();
+    let goto_time_cmd = goto_time.clone();
+    let goto_percent_cmd = goto_percent.clone();
+    let events_cmd = events.clone();
+    let exporting_cmd = exporting.clone();
+    let copy_lines_cmd = copy_lines.clone();
+    let window_cmd = window.downgrade();
+    let status_text_label_cmd = status_text_label.clone();
+    let held_keys: Rc<HeldKeys> = Rc::default();
+    // Set once the key controller's handler exists, further down
+    let handle_key: Rc<OnceCell<KeyHandler>> = Rc::default();
+    // While a socket request runs, clicks on the view and the title bar's
+    // buttons are ignored and keys wait until it is done, so the commands of
+    // a batch see no changes but their own, however long they await the worker
+    let input_widgets: [gtk4::Widget; 5] = [
+        overlay.clone().upcast(),
+        open_button.clone().upcast(),
+        wrap_button.clone().upcast(),
+        follow_button.clone().upcast(),
+        filters_button.clone().upcast(),
+    ];
+    let held_keys_cmd = held_keys.clone();
+    let handle_key_cmd = handle_key.clone();
     glib::spawn_future_local(async move {
-        while let Ok(request) = command_rx.recv().await {
-            let response = match request.command {
+        let hold_input = |held: bool| {
+            held_keys_cmd.held.set(held);
+            for widget in &input_widgets {
+                widget.set_can_target(!held);
+            }
+        };
+        // Read by the worker through the view's filters, so line numbers
+        // match `line` and the view
+        let read_view_lines = async |start: usize, count: usize| {
+            request_tx_cmd
+                .call(
+                    |result_tx| FileRequest::ReadLines {
+                        start,
+                        count,
+                        result_tx,
+                    },
+                    glib::timeout_future(command_timeout),
+                )
+                .await?
+        };
+        // Set by commands that change what the lines look like; the view is
+        // redrawn once per request, however many commands a batch holds
+        let needs_redraw = Cell::new(false);
+        // Commands count the lines of the view; marks, annotations, bookmarks
+        // and pins are kept by the file line shown there
+        let file_line = |line: usize| match view_filter_cmd.borrow().as_ref() {
+            Some(filter) => filter.source_line(line),
+            None => Some(line),
+        };
+        // The view line showing a file line, or the first one kept after it
+        let view_line = |file_line: usize| match view_filter_cmd.borrow().as_ref() {
+            Some(filter) => filter.view_line(file_line),
+            None => file_line,
+        };
+        // The pattern and multiline flag of the search `search-next` and
+        // `search-prev` continue.
+        let active_search = || {
+            let state = search_state_cmd.borrow();
+            if !state.is_active {
+                Err("no active search".to_string())
+            } else if state.pattern.is_none() {
+                Err("no search pattern".to_string())
+            } else {
+                Ok((state.pattern_str.clone(), state.multiline))
+            }
+        };
+        let run_command = async |command: PogCommand| {
+            let total_lines = total_lines_cmd.get();
+            match command {
                 PogCommand::Goto { line } => {
                     if line == 0 || line > total_lines {
                         CommandResponse::Error(format!(
@@ -564,6 +3072,30 @@ fn build_ui(app: &Application, file_source: Arc<dyn FileSource>, port: u16, no_s
                         CommandResponse::Ok(None)
                     }
                 }
+                PogCommand::GotoPercent { percent, of } => match goto_percent_cmd(percent, of) {
+                    Ok(line) => CommandResponse::Ok(Some((line + 1).to_string())),
+                    Err(message) => CommandResponse::Error(message),
+                },
+                PogCommand::GotoTime { time } => match goto_time_cmd(time) {
+                    Ok(line) => CommandResponse::Ok(Some((line + 1).to_string())),
+                    Err(message) => CommandResponse::Error(message),
+                },
+                PogCommand::Scroll { delta } => {
+                    scroll_by(&v_adjustment_cmd, delta as f64);
+                    CommandResponse::Ok(Some((v_adjustment_cmd.value() as usize + 1).to_string()))
+                }
+                PogCommand::PageDown => {
+                    scroll_by(&v_adjustment_cmd, v_adjustment_cmd.page_increment());
+                    CommandResponse::Ok(Some((v_adjustment_cmd.value() as usize + 1).to_string()))
+                }
+                PogCommand::PageUp => {
+                    scroll_by(&v_adjustment_cmd, -v_adjustment_cmd.page_increment());
+                    CommandResponse::Ok(Some((v_adjustment_cmd.value() as usize + 1).to_string()))
+                }
+                PogCommand::Bottom => {
+                    scroll_by(&v_adjustment_cmd, v_adjustment_cmd.upper());
+                    CommandResponse::Ok(Some((v_adjustment_cmd.value() as usize + 1).to_string()))
+                }
                 PogCommand::Lines => {
                     CommandResponse::Ok(Some(total_lines.to_string()))
                 }
@@ -572,8 +3104,186 @@ fn build_ui(app: &Application, file_source: Arc<dyn FileSource>, port: u16, no_s
                     CommandResponse::Ok(Some(top_line.to_string()))
                 }
                 PogCommand::Size => {
+                    let file_size = file_source_cmd.file_size().unwrap_or(0);
                     CommandResponse::Ok(Some(file_size.to_string()))
                 }
+                PogCommand::Viewport { lines: None } => {
+                    CommandResponse::Ok(Some(page_lines(&v_adjustment_cmd).to_string()))
+                }
+                PogCommand::Viewport { lines: Some(lines) } => {
+                    if lines > MAX_PAGE_LINES {
+                        CommandResponse::Error(format!(
+                            "too many lines: at most {} per page",
+                            MAX_PAGE_LINES
+                        ))
+                    } else {
+                        v_adjustment_cmd.set_page_increment(lines as f64);
+                        v_adjustment_cmd.set_page_size(lines as f64);
+                        CommandResponse::Ok(Some(lines.to_string()))
+                    }
+                }
+                PogCommand::Quit => CommandResponse::Ok(None),
+                // Taken by the server: the connection turns into an event
+                // stream, or queues commands until they are sent as a batch
+                PogCommand::Subscribe { .. }
+                | PogCommand::Begin
+                | PogCommand::Commit
+                | PogCommand::Rollback
+                | PogCommand::Batch { .. } => {
+                    CommandResponse::Error("handled by the server".to_string())
+                }
+                PogCommand::Open { path } => match open_file_cmd(&FilePath::parse(&path)) {
+                    Ok(()) => CommandResponse::Ok(None),
+                    Err(e) => CommandResponse::Error(format!("failed to open {}: {}", path, e)),
+                },
+                PogCommand::Reload => match request_tx_cmd
+                    .reload(glib::timeout_future(command_timeout))
+                    .await
+                {
+                    Ok(lines) => CommandResponse::Ok(Some(lines.to_string())),
+                    Err(message) => CommandResponse::Error(message),
+                },
+                PogCommand::Status => {
+                    let top = v_adjustment_cmd.value() as usize;
+                    let bottom = (top + page_lines(&v_adjustment_cmd)).min(total_lines);
+                    let mut fields = vec![
+                        ("file", file_source_cmd.display_name().to_string()),
+                        ("lines", total_lines.to_string()),
+                        ("size", file_source_cmd.file_size().unwrap_or(0).to_string()),
+                        ("top", (top + 1).to_string()),
+                        ("bottom", bottom.to_string()),
+                        ("cursor", (*cursor_position_cmd.borrow() + 1).to_string()),
+                        ("view", view_mode_cmd.get().to_string()),
+                        ("wrap", if line_rows_cmd.wrap.get() { "on" } else { "off" }.to_string()),
+                        (
+                            "relative-numbers",
+                            if line_rows_cmd.relative.get() { "on" } else { "off" }.to_string(),
+                        ),
+                        ("theme", theme_cmd.get().to_string()),
+                        ("levels", level_style_cmd.get().to_string()),
+                        (
+                            "time-column",
+                            if time_column_cmd.get() { "on" } else { "off" }.to_string(),
+                        ),
+                        ("table", describe_table(table_cmd.borrow().as_ref())),
+                        ("logfmt", describe_logfmt(&logfmt_cmd.borrow())),
+                        (
+                            "marks-panel",
+                            if marks_panel_cmd.is_visible() { "on" } else { "off" }.to_string(),
+                        ),
+                        (
+                            "follow",
+                            if follow_button_cmd.is_active() { "on" } else { "off" }.to_string(),
+                        ),
+                    ];
+                    let state = search_state_cmd.borrow();
+                    if state.is_active && state.pattern.is_some() {
+                        fields.push(("search", state.pattern_str.clone()));
+                        if let Some(total) = state.total_matches {
+                            fields.push(("search-matches", total.to_string()));
+                        }
+                    }
+                    drop(state);
+                    for filter in current_filters_cmd() {
+                        fields.push(("filter", format!("{} {}", filter.kind, filter.pattern)));
+                    }
+                    fields.push(("fold", describe_fold(line_fold_cmd.borrow().as_ref())));
+                    let hidden: usize =
+                        hidden_lines_cmd.borrow().iter().map(|(start, end)| end - start).sum();
+                    fields.push(("hidden", hidden.to_string()));
+                    fields.push(("pinned", pinned_lines_cmd.borrow().len().to_string()));
+                    fields.push(("marks", marked_lines_cmd.borrow().len().to_string()));
+                    CommandResponse::Ok(Some(frame_fields(&fields)))
+                }
+                PogCommand::Line { line } => {
+                    if line == 0 || line > total_lines {
+                        CommandResponse::Error(format!(
+                            "line out of range: requested {}, file has {} lines",
+                            line, total_lines
+                        ))
+                    } else {
+                        // The full text, however long the line is on screen
+                        match read_view_lines(line - 1, 1).await {
+                            Ok(lines) => match lines.into_iter().next() {
+                                Some((_, text)) => CommandResponse::Ok(Some(text)),
+                                None => CommandResponse::Error(format!(
+                                    "line {} not available",
+                                    line
+                                )),
+                            },
+                            Err(e) => CommandResponse::Error(e),
+                        }
+                    }
+                }
+                PogCommand::Get { start, count } => {
+                    if start > total_lines {
+                        CommandResponse::Error(format!(
+                            "line out of range: requested {}, file has {} lines",
+                            start, total_lines
+                        ))
+                    } else if count > MAX_GET_LINES {
+                        CommandResponse::Error(format!(
+                            "too many lines: at most {} per get",
+                            MAX_GET_LINES
+                        ))
+                    } else {
+                        match read_view_lines(start - 1, count).await {
+                            Ok(lines) => CommandResponse::Ok(Some(frame_lines(&lines))),
+                            Err(e) => CommandResponse::Error(e),
+                        }
+                    }
+                }
+                PogCommand::Copy { start, end } => {
+                    if end > total_lines {
+                        CommandResponse::Error(format!(
+                            "line out of range: requested {}, file has {} lines",
+                            end, total_lines
+                        ))
+                    } else {
+                        match copy_lines_cmd(start - 1, end - start + 1).await {
+                            Ok(count) => CommandResponse::Ok(Some(count.to_string())),
+                            Err(e) => CommandResponse::Error(e),
+                        }
+                    }
+                }
+                PogCommand::Context { line, radius } => {
+                    if line == 0 || line > total_lines {
+                        CommandResponse::Error(format!(
+                            "line out of range: requested {}, file has {} lines",
+                            line, total_lines
+                        ))
+                    } else if radius.saturating_mul(2) >= MAX_GET_LINES {
+                        CommandResponse::Error(format!(
+                            "too many lines: at most {} per context",
+                            MAX_GET_LINES
+                        ))
+                    } else {
+                        // Fewer lines before the first line and after the last
+                        let start = (line - 1).saturating_sub(radius);
+                        let count = (line - 1 - start) + 1 + radius;
+                        match read_view_lines(start, count).await {
+                            Ok(lines) => CommandResponse::Ok(Some(frame_lines(&lines))),
+                            Err(e) => CommandResponse::Error(e),
+                        }
+                    }
+                }
+                PogCommand::Tail { count } => {
+                    if count > MAX_GET_LINES {
+                        CommandResponse::Error(format!(
+                            "too many lines: at most {} per tail",
+                            MAX_GET_LINES
+                        ))
+                    } else {
+                        let start = total_lines.saturating_sub(count);
+                        match read_view_lines(start, total_lines - start).await {
+                            Ok(lines) => {
+                                scroll_by(&v_adjustment_cmd, v_adjustment_cmd.upper());
+                                CommandResponse::Ok(Some(frame_lines(&lines)))
+                            }
+                            Err(e) => CommandResponse::Error(e),
+                        }
+                    }
+                }
                 PogCommand::Cursor { line } => {
                     match line {
                         None => {
@@ -599,10 +3309,9 @@ fn build_ui(app: &Application, file_source: Arc<dyn FileSource>, port: u16, no_s
                             "line out of range: requested {}, file has {} lines",
                             line, total_lines
                         ))
-                    } else {
-                        let line_0based = line - 1;
+                    } else if let Some(file_line) = file_line(line - 1) {
                         let mut marks = marked_lines_cmd.borrow_mut();
-                        let entry = marks.entry(line_0based).or_default();
+                        let entry = marks.entry(file_line).or_default();
 
                         match region {
                             None => {
@@ -611,31 +3320,15 @@ fn build_ui(app: &Application, file_source: Arc<dyn FileSource>, port: u16, no_s
                             }
                             Some((start, end)) => {
                                 // Region mark - convert to 0-based
-                                let start_0based = start - 1;
-                                let end_0based = end - 1;
-                                // Remove overlapping regions
-                                entry.regions.retain(|r| r.end_col <= start_0based || r.start_col >= end_0based);
-                                entry.regions.push(Region {
-                                    start_col: start_0based,
-                                    end_col: end_0based,
-                                    color,
-                                });
-                                // Sort regions by start column
-                                entry.regions.sort_by_key(|r| r.start_col);
+                                entry.add_region(start - 1, end - 1, color);
                             }
                         }
                         drop(marks);
 
-                        // Trigger redraw
-                        let start = v_adjustment_cmd.value() as usize;
-                        let request_id = next_request_id();
-                        *latest_request_id_cmd.borrow_mut() = request_id;
-                        let _ = request_tx_cmd.send_blocking(FileRequest::GetLines {
-                            start,
-                            count: LINES_PER_PAGE,
-                            request_id,
-                        });
+                        needs_redraw.set(true);
                         CommandResponse::Ok(None)
+                    } else {
+                        CommandResponse::Error(format!("line {} not available", line))
                     }
                 }
                 PogCommand::Unmark { line, region } => {
@@ -644,8 +3337,7 @@ fn build_ui(app: &Application, file_source: Arc<dyn FileSource>, port: u16, no_s
                             "line out of range: requested {}, file has {} lines",
                             line, total_lines
                         ))
-                    } else {
-                        let line_0based = line - 1;
+                    } else if let Some(line_0based) = file_line(line - 1) {
                         let mut marks = marked_lines_cmd.borrow_mut();
 
                         let removed = match region {
@@ -674,24 +3366,337 @@ fn build_ui(app: &Application, file_source: Arc<dyn FileSource>, port: u16, no_s
                         drop(marks);
 
                         if removed {
-                            // Trigger redraw
-                            let start = v_adjustment_cmd.value() as usize;
-                            let request_id = next_request_id();
-                            *latest_request_id_cmd.borrow_mut() = request_id;
-                            let _ = request_tx_cmd.send_blocking(FileRequest::GetLines {
-                                start,
-                                count: LINES_PER_PAGE,
-                                request_id,
-                            });
+                            needs_redraw.set(true);
                             CommandResponse::Ok(None)
                         } else {
                             CommandResponse::Error(format!("line {} is not marked", line))
                         }
+                    } else {
+                        CommandResponse::Error(format!("line {} is not marked", line))
+                    }
+                }
+                PogCommand::UnmarkAll => {
+                    let count = std::mem::take(&mut *marked_lines_cmd.borrow_mut()).len();
+
+                    needs_redraw.set(true);
+                    CommandResponse::Ok(Some(count.to_string()))
+                }
+                PogCommand::UnmarkPattern { pattern } => {
+                    let result = request_tx_cmd
+                        .call(
+                            |result_tx| FileRequest::FindMatchingLines {
+                                pattern,
+                                limit: MAX_MARKED_MATCHES,
+                                result_tx,
+                            },
+                            glib::timeout_future(command_timeout),
+                        )
+                        .await;
+                    match result {
+                        Ok(Ok(lines)) => {
+                            let mut marks = marked_lines_cmd.borrow_mut();
+                            let count = lines
+                                .iter()
+                                .filter_map(|&line_num| file_line(line_num))
+                                .filter(|line_num| marks.remove(line_num).is_some())
+                                .count();
+                            drop(marks);
+
+                            needs_redraw.set(true);
+                            CommandResponse::Ok(Some(count.to_string()))
+                        }
+                        Ok(Err(message)) => CommandResponse::Error(message),
+                        Err(e) => CommandResponse::Error(e),
+                    }
+                }
+                PogCommand::UnmarkColor { color } => {
+                    let mut marks = marked_lines_cmd.borrow_mut();
+                    let mut count = 0;
+                    marks.retain(|_, entry| {
+                        count += entry.remove_color(&color) as usize;
+                        !entry.is_empty()
+                    });
+                    drop(marks);
+
+                    needs_redraw.set(true);
+                    CommandResponse::Ok(Some(count.to_string()))
+                }
+                PogCommand::Annotate { line, text } => {
+                    if line > total_lines {
+                        CommandResponse::Error(format!(
+                            "line out of range: requested {}, file has {} lines",
+                            line, total_lines
+                        ))
+                    } else if let Some(file_line) = file_line(line - 1) {
+                        annotations_cmd.borrow_mut().insert(file_line, text);
+                        needs_redraw.set(true);
+                        CommandResponse::Ok(None)
+                    } else {
+                        CommandResponse::Error(format!("line {} not available", line))
+                    }
+                }
+                PogCommand::Annotations => {
+                    // Only the notes on lines the filters keep, by view line
+                    let filter = view_filter_cmd.borrow().clone();
+                    let notes: Vec<(usize, String)> = annotations_cmd
+                        .borrow()
+                        .iter()
+                        .filter_map(|(&line, note)| {
+                            let line = filter.as_ref().map_or(Some(line), |f| f.shown_at(line))?;
+                            Some((line, note.clone()))
+                        })
+                        .collect();
+                    CommandResponse::Ok(Some(frame_lines(&notes)))
+                }
+                PogCommand::AnnotationRemove { line } => {
+                    let removed = file_line(line - 1)
+                        .is_some_and(|line| annotations_cmd.borrow_mut().remove(&line).is_some());
+                    if removed {
+                        needs_redraw.set(true);
+                        CommandResponse::Ok(None)
+                    } else {
+                        CommandResponse::Error(format!("line {} has no annotation", line))
+                    }
+                }
+                PogCommand::AnnotationsClear => {
+                    let count = std::mem::take(&mut *annotations_cmd.borrow_mut()).len();
+                    needs_redraw.set(true);
+                    CommandResponse::Ok(Some(count.to_string()))
+                }
+                PogCommand::BookmarkSet { name, line } => {
+                    let line = line.unwrap_or(*cursor_position_cmd.borrow() + 1);
+                    if line > total_lines {
+                        CommandResponse::Error(format!(
+                            "line out of range: requested {}, file has {} lines",
+                            line, total_lines
+                        ))
+                    } else if let Some(file_line) = file_line(line - 1) {
+                        bookmarks_cmd.borrow_mut().insert(name, file_line);
+                        CommandResponse::Ok(Some(line.to_string()))
+                    } else {
+                        CommandResponse::Error(format!("line {} not available", line))
+                    }
+                }
+                PogCommand::BookmarkGoto { name } => {
+                    let line = bookmarks_cmd.borrow().get(&name).copied();
+                    match line.map(view_line) {
+                        Some(line) => {
+                            v_adjustment_cmd.set_value(line as f64);
+                            *cursor_position_cmd.borrow_mut() = line;
+                            CommandResponse::Ok(Some((line + 1).to_string()))
+                        }
+                        None => CommandResponse::Error(format!("no bookmark: {}", name)),
+                    }
+                }
+                PogCommand::BookmarkList => {
+                    let bookmarks = bookmarks_cmd.borrow();
+                    let fields: Vec<(&str, String)> = bookmarks
+                        .iter()
+                        .map(|(name, &line)| (name.as_str(), (view_line(line) + 1).to_string()))
+                        .collect();
+                    CommandResponse::Ok(Some(frame_fields(&fields)))
+                }
+                PogCommand::BookmarkRemove { name } => {
+                    if bookmarks_cmd.borrow_mut().remove(&name).is_some() {
+                        CommandResponse::Ok(None)
+                    } else {
+                        CommandResponse::Error(format!("no bookmark: {}", name))
+                    }
+                }
+                PogCommand::SaveMarks { path } => {
+                    let contents = marks_file_cmd();
+                    let path = std::path::Path::new(&path);
+                    match marks::save(path, &contents, file_source_cmd.display_name()) {
+                        Ok(()) => CommandResponse::Ok(Some(contents.marks.len().to_string())),
+                        Err(e) => CommandResponse::Error(e),
+                    }
+                }
+                PogCommand::LoadMarks { path } => match marks::load(std::path::Path::new(&path)) {
+                    Ok(loaded) => {
+                        let count = loaded.marks.len();
+                        *marked_lines_cmd.borrow_mut() = loaded.marks;
+                        *annotations_cmd.borrow_mut() = loaded.annotations;
+                        *bookmarks_cmd.borrow_mut() = loaded.bookmarks;
+
+                        needs_redraw.set(true);
+                        CommandResponse::Ok(Some(count.to_string()))
+                    }
+                    Err(e) => CommandResponse::Error(e),
+                },
+                PogCommand::Export { start, end, path } => {
+                    if end > total_lines {
+                        CommandResponse::Error(format!(
+                            "line out of range: requested {}, file has {} lines",
+                            end, total_lines
+                        ))
+                    } else if exporting_cmd.get() {
+                        CommandResponse::Error("an export is already running".to_string())
+                    } else {
+                        match std::fs::File::create(&path) {
+                            Ok(file) => {
+                                request_tx_cmd.export(start - 1, end, file, path.into());
+                                exporting_cmd.set(true);
+                                CommandResponse::Ok(Some((end - start + 1).to_string()))
+                            }
+                            Err(e) => CommandResponse::Error(format!("{}: {}", path, e)),
+                        }
                     }
                 }
-                PogCommand::Search { pattern } => {
+                PogCommand::ExportCancel => {
+                    if exporting_cmd.replace(false) {
+                        request_tx_cmd.cancel_export();
+                        CommandResponse::Ok(None)
+                    } else {
+                        CommandResponse::Error("no export is running".to_string())
+                    }
+                }
+                PogCommand::MarkMatches { color, pattern } => {
+                    let (pattern, multiline) = match pattern {
+                        Some(pattern) => (Some(pattern), false),
+                        None => {
+                            let state = search_state_cmd.borrow();
+                            let pattern = (state.is_active && state.pattern.is_some())
+                                .then(|| state.pattern_str.clone());
+                            (pattern, state.multiline)
+                        }
+                    };
+                    match pattern {
+                        None => CommandResponse::Error("no active search".to_string()),
+                        Some(pattern) => {
+                            let result = request_tx_cmd
+                                .call(
+                                    |result_tx| FileRequest::FindAllMatches {
+                                        pattern,
+                                        multiline,
+                                        limit: MAX_MARKED_MATCHES,
+                                        result_tx,
+                                    },
+                                    glib::timeout_future(command_timeout),
+                                )
+                                .await;
+                            match result {
+                                Ok(Ok(matches)) => {
+                                    let mut marks = marked_lines_cmd.borrow_mut();
+                                    for m in &matches {
+                                        // A multiline match marks each line it covers
+                                        for line_num in m.line_num..=m.end_line {
+                                            let columns = m.columns_on(line_num);
+                                            if let Some(((start, end), line_num)) =
+                                                columns.zip(file_line(line_num))
+                                            {
+                                                marks.entry(line_num).or_default().add_region(
+                                                    start,
+                                                    end,
+                                                    color.clone(),
+                                                );
+                                            }
+                                        }
+                                    }
+                                    drop(marks);
+
+                                    needs_redraw.set(true);
+                                    CommandResponse::Ok(Some(matches.len().to_string()))
+                                }
+                                Ok(Err(message)) => CommandResponse::Error(message),
+                                Err(e) => CommandResponse::Error(e),
+                            }
+                        }
+                    }
+                }
+                PogCommand::MarkPattern { color, pattern } => {
+                    let result = request_tx_cmd
+                        .call(
+                            |result_tx| FileRequest::FindMatchingLines {
+                                pattern,
+                                limit: MAX_MARKED_MATCHES,
+                                result_tx,
+                            },
+                            glib::timeout_future(command_timeout),
+                        )
+                        .await;
+                    match result {
+                        Ok(Ok(lines)) => {
+                            let mut marks = marked_lines_cmd.borrow_mut();
+                            for line_num in lines.iter().filter_map(|&line| file_line(line)) {
+                                marks.entry(line_num).or_default().full_line_color =
+                                    Some(color.clone());
+                            }
+                            drop(marks);
+
+                            needs_redraw.set(true);
+                            CommandResponse::Ok(Some(lines.len().to_string()))
+                        }
+                        Ok(Err(message)) => CommandResponse::Error(message),
+                        Err(e) => CommandResponse::Error(e),
+                    }
+                }
+                PogCommand::Grep { pattern, ignore_case, from, max } => {
+                    let limit = max.unwrap_or(DEFAULT_GREP_LINES);
+                    if limit > MAX_GET_LINES {
+                        CommandResponse::Error(format!(
+                            "too many lines: at most {} per grep",
+                            MAX_GET_LINES
+                        ))
+                    } else {
+                        let pattern =
+                            if ignore_case { format!("(?i){}", pattern) } else { pattern };
+                        let result = request_tx_cmd
+                            .call(
+                                |result_tx| FileRequest::GrepLines {
+                                    pattern,
+                                    from_line: from - 1,
+                                    limit,
+                                    result_tx,
+                                },
+                                glib::timeout_future(command_timeout),
+                            )
+                            .await;
+                        match result {
+                            Ok(Ok(lines)) => CommandResponse::Ok(Some(frame_lines(&lines))),
+                            Ok(Err(message)) => CommandResponse::Error(message),
+                            Err(e) => CommandResponse::Error(e),
+                        }
+                    }
+                }
+                PogCommand::Search { field: Some(n), .. }
+                    if table_cmd.borrow().is_none() && n > field_styles_cmd.len() =>
+                {
+                    CommandResponse::Error(format!(
+                        "no field {} ({} fields configured)",
+                        n,
+                        field_styles_cmd.len()
+                    ))
+                }
+                PogCommand::Search {
+                    pattern,
+                    ignore_case,
+                    literal,
+                    multiline,
+                    time_range,
+                    field,
+                    key,
+                } => {
+                    let field = match (field, key) {
+                        // In the column view, fields are its delimited columns
+                        (Some(n), _) => match table_cmd.borrow().as_ref() {
+                            Some(table) => {
+                                Some(SearchField::Delimited(table.delimiter.clone(), n - 1))
+                            }
+                            None => {
+                                let style = &field_styles_cmd[n - 1];
+                                Some(SearchField::Columns(style.start_col, style.end_col))
+                            }
+                        },
+                        (None, key) => key.map(SearchField::Key),
+                    };
+                    syncing_search_toggles_cmd.set(true);
+                    search_case_button_cmd.set_active(ignore_case);
+                    search_literal_button_cmd.set_active(literal);
+                    search_multiline_button_cmd.set_active(multiline);
+                    syncing_search_toggles_cmd.set(false);
+
                     let mut state = search_state_cmd.borrow_mut();
-                    match state.set_pattern(&pattern) {
+                    match state.set_pattern(&pattern, search_case_cmd(), literal, multiline) {
                         Ok(()) => {
                             // Sync UI with socket-initiated search
                             search_box_cmd.set_visible(true);
@@ -699,17 +3704,40 @@ fn build_ui(app: &Application, file_source: Arc<dyn FileSource>, port: u16, no_s
                             search_info_cmd.set_text("Searching...");
 
                             let viewport_start = v_adjustment_cmd.value() as usize;
-                            let search_start = viewport_start.saturating_sub(SEARCH_BUFFER_LINES);
-                            let search_end = (viewport_start + LINES_PER_PAGE + SEARCH_BUFFER_LINES).min(total_lines);
+                            let page = page_lines(&v_adjustment_cmd);
+                            let (search_start, search_end) =
+                                search_config.window(viewport_start, page, total_lines);
+                            let pattern = state.pattern_str.clone();
+                            let multiline = state.multiline;
+                            let count_request_id = next_request_id();
+                            state.count_request_id = Some(count_request_id);
                             drop(state);
 
+                            events_cmd.publish(&ViewerEvent::SearchStarted {
+                                pattern: pattern.clone(),
+                            });
+                            request_tx_cmd.set_search_scope(SearchScope { time_range, field });
                             let _ = request_tx_cmd.send_blocking(FileRequest::SearchRange {
-                                pattern,
+                                pattern: pattern.clone(),
                                 start_line: search_start,
                                 end_line: search_end,
                                 request_id: next_request_id(),
                                 navigate_to_first: true,
+                                multiline,
                             });
+                            if !time_range.is_unbounded() {
+                                // The times may lie far from the viewport: go to their first match
+                                let _ = request_tx_cmd.send_blocking(FileRequest::FindNextMatch {
+                                    pattern: pattern.clone(),
+                                    multiline,
+                                    from_line: 0,
+                                    direction: SearchDirection::Forward,
+                                    wrap: true,
+                                    request_id: next_request_id(),
+                                    result_tx: None,
+                                });
+                            }
+                            request_tx_cmd.count_matches(pattern, multiline, count_request_id);
 
                             // Return OK since search was initiated (results come async)
                             CommandResponse::Ok(None)
@@ -717,87 +3745,433 @@ fn build_ui(app: &Application, file_source: Arc<dyn FileSource>, port: u16, no_s
                         Err(e) => CommandResponse::Error(e),
                     }
                 }
-                PogCommand::SearchNext => {
-                    let state = search_state_cmd.borrow();
-                    if !state.is_active {
-                        CommandResponse::Error("no active search".to_string())
-                    } else if state.pattern.is_none() {
-                        CommandResponse::Error("no search pattern".to_string())
-                    } else {
-                        let pattern = state.pattern_str.clone();
+                PogCommand::SearchNext => match active_search() {
+                    Err(e) => CommandResponse::Error(e),
+                    Ok((pattern, multiline)) => {
                         let current_line = *cursor_position_cmd.borrow();
-                        drop(state);
+                        let wrap = search_wrap_button_cmd.is_active();
 
-                        let (result_tx, result_rx) = std::sync::mpsc::channel();
-                        let _ = request_tx_cmd.send_blocking(FileRequest::FindNextMatch {
-                            pattern,
-                            from_line: current_line,
-                            direction: SearchDirection::Forward,
-                            request_id: next_request_id(),
-                            result_tx: Some(result_tx),
-                        });
-                        match result_rx.recv() {
-                            Ok(Some((line, col, len))) => {
+                        let result = request_tx_cmd
+                            .call(
+                                |result_tx| FileRequest::FindNextMatch {
+                                    pattern,
+                                    multiline,
+                                    from_line: current_line,
+                                    direction: SearchDirection::Forward,
+                                    wrap,
+                                    request_id: next_request_id(),
+                                    result_tx: Some(result_tx),
+                                },
+                                glib::timeout_future(command_timeout),
+                            )
+                            .await;
+                        match result {
+                            Ok(Some((line, col, len, wrapped))) => {
                                 *cursor_position_cmd.borrow_mut() = line;
-                                CommandResponse::Ok(Some(format!("{} {} {}", line + 1, col + 1, len)))
+                                let wrapped = if wrapped { " wrapped" } else { "" };
+                                CommandResponse::Ok(Some(format!(
+                                    "{} {} {}{}",
+                                    line + 1,
+                                    col + 1,
+                                    len,
+                                    wrapped
+                                )))
                             }
                             Ok(None) => CommandResponse::Error("no more matches".to_string()),
-                            Err(_) => CommandResponse::Error("search failed".to_string()),
+                            Err(e) => CommandResponse::Error(e),
                         }
                     }
-                }
-                PogCommand::SearchPrev => {
-                    let state = search_state_cmd.borrow();
-                    if !state.is_active {
-                        CommandResponse::Error("no active search".to_string())
-                    } else if state.pattern.is_none() {
-                        CommandResponse::Error("no search pattern".to_string())
-                    } else {
-                        let pattern = state.pattern_str.clone();
+                },
+                PogCommand::SearchPrev => match active_search() {
+                    Err(e) => CommandResponse::Error(e),
+                    Ok((pattern, multiline)) => {
                         let current_line = *cursor_position_cmd.borrow();
-                        drop(state);
+                        let wrap = search_wrap_button_cmd.is_active();
 
-                        let (result_tx, result_rx) = std::sync::mpsc::channel();
-                        let _ = request_tx_cmd.send_blocking(FileRequest::FindNextMatch {
-                            pattern,
-                            from_line: current_line,
-                            direction: SearchDirection::Backward,
-                            request_id: next_request_id(),
-                            result_tx: Some(result_tx),
-                        });
-                        match result_rx.recv() {
-                            Ok(Some((line, col, len))) => {
+                        let result = request_tx_cmd
+                            .call(
+                                |result_tx| FileRequest::FindNextMatch {
+                                    pattern,
+                                    multiline,
+                                    from_line: current_line,
+                                    direction: SearchDirection::Backward,
+                                    wrap,
+                                    request_id: next_request_id(),
+                                    result_tx: Some(result_tx),
+                                },
+                                glib::timeout_future(command_timeout),
+                            )
+                            .await;
+                        match result {
+                            Ok(Some((line, col, len, wrapped))) => {
                                 *cursor_position_cmd.borrow_mut() = line;
-                                CommandResponse::Ok(Some(format!("{} {} {}", line + 1, col + 1, len)))
+                                let wrapped = if wrapped { " wrapped" } else { "" };
+                                CommandResponse::Ok(Some(format!(
+                                    "{} {} {}{}",
+                                    line + 1,
+                                    col + 1,
+                                    len,
+                                    wrapped
+                                )))
                             }
                             Ok(None) => CommandResponse::Error("no more matches".to_string()),
-                            Err(_) => CommandResponse::Error("search failed".to_string()),
+                            Err(e) => CommandResponse::Error(e),
+                        }
+                    }
+                },
+                PogCommand::Duplicate => {
+                    let port = duplicate_view_cmd();
+                    CommandResponse::Ok(port.map(|p| p.to_string()))
+                }
+                PogCommand::SearchWrap { enabled } => match enabled {
+                    None => {
+                        let state = if search_wrap_button_cmd.is_active() { "on" } else { "off" };
+                        CommandResponse::Ok(Some(state.to_string()))
+                    }
+                    Some(enabled) => {
+                        search_wrap_button_cmd.set_active(enabled);
+                        CommandResponse::Ok(None)
+                    }
+                },
+                PogCommand::SearchSave { name } => match current_search() {
+                    None => CommandResponse::Error("no active search".to_string()),
+                    Some(search) => match SavedSearches::save(&name, search) {
+                        Ok(()) => CommandResponse::Ok(None),
+                        Err(e) => CommandResponse::Error(e),
+                    },
+                },
+                PogCommand::SearchLoad { name } => match SavedSearches::load() {
+                    Err(e) => CommandResponse::Error(e),
+                    Ok(saved) => match name {
+                        None => {
+                            let names: Vec<&str> = saved.names().collect();
+                            CommandResponse::Ok(Some(names.join(" ")))
+                        }
+                        Some(name) => match saved.get(&name) {
+                            Some(search) => {
+                                load_search(search);
+                                if search_state_cmd.borrow().is_active {
+                                    CommandResponse::Ok(None)
+                                } else {
+                                    // The pattern no longer compiles; the search bar says why
+                                    CommandResponse::Error(search_info_cmd.text().to_string())
+                                }
+                            }
+                            None => CommandResponse::Error(format!("no saved search: {}", name)),
+                        },
+                    },
+                },
+                PogCommand::View { mode } => match mode {
+                    None => CommandResponse::Ok(Some(view_mode_cmd.get().to_string())),
+                    Some(mode) => {
+                        set_view_mode_cmd(mode);
+                        CommandResponse::Ok(None)
+                    }
+                },
+                PogCommand::Wrap { enabled } => match enabled {
+                    None => {
+                        let state = if line_rows_cmd.wrap.get() { "on" } else { "off" };
+                        CommandResponse::Ok(Some(state.to_string()))
+                    }
+                    Some(enabled) => {
+                        set_wrap_cmd(enabled);
+                        CommandResponse::Ok(None)
+                    }
+                },
+                PogCommand::RelativeNumbers { enabled } => match enabled {
+                    None => {
+                        let state = if line_rows_cmd.relative.get() { "on" } else { "off" };
+                        CommandResponse::Ok(Some(state.to_string()))
+                    }
+                    Some(enabled) => {
+                        set_relative_numbers_cmd(enabled);
+                        CommandResponse::Ok(None)
+                    }
+                },
+                PogCommand::Levels { style } => match style {
+                    None => CommandResponse::Ok(Some(level_style_cmd.get().to_string())),
+                    Some(style) => {
+                        level_style_cmd.set(style);
+                        needs_redraw.set(true);
+                        CommandResponse::Ok(None)
+                    }
+                },
+                PogCommand::Table { action } => match action {
+                    TableAction::Get => {
+                        CommandResponse::Ok(Some(describe_table(table_cmd.borrow().as_ref())))
+                    }
+                    TableAction::Off => {
+                        set_table_cmd(None);
+                        CommandResponse::Ok(None)
+                    }
+                    TableAction::On { delimiter } => {
+                        set_table_cmd(Some(delimiter));
+                        CommandResponse::Ok(None)
+                    }
+                    action => {
+                        let mut table = table_cmd.borrow_mut();
+                        match table.as_mut() {
+                            None => CommandResponse::Error("table view is off".to_string()),
+                            Some(table) => {
+                                match action {
+                                    TableAction::Width { column, width } => {
+                                        table.set_width(column, width)
+                                    }
+                                    TableAction::Hide { column } => table.hide(column),
+                                    TableAction::Show { column } => table.show(column),
+                                    _ => {}
+                                }
+                                needs_redraw.set(true);
+                                CommandResponse::Ok(None)
+                            }
                         }
                     }
+                },
+                PogCommand::Logfmt { action } => {
+                    let mut logfmt = logfmt_cmd.borrow_mut();
+                    match action {
+                        LogfmtAction::Get => CommandResponse::Ok(Some(describe_logfmt(&logfmt))),
+                        LogfmtAction::Enable { enabled } => {
+                            logfmt.enabled = enabled;
+                            needs_redraw.set(true);
+                            CommandResponse::Ok(None)
+                        }
+                        LogfmtAction::Hide { key } => {
+                            logfmt.hide(&key);
+                            needs_redraw.set(true);
+                            CommandResponse::Ok(None)
+                        }
+                        LogfmtAction::Show { key } => {
+                            let shown = logfmt.show(key.as_deref());
+                            match key {
+                                Some(key) if !shown => {
+                                    CommandResponse::Error(format!("key not hidden: {}", key))
+                                }
+                                _ => {
+                                    needs_redraw.set(true);
+                                    CommandResponse::Ok(None)
+                                }
+                            }
+                        }
+                    }
+                }
+                PogCommand::TimeColumn { enabled } => match enabled {
+                    None => {
+                        let state = if time_column_cmd.get() { "on" } else { "off" };
+                        CommandResponse::Ok(Some(state.to_string()))
+                    }
+                    Some(enabled) => {
+                        set_time_column_cmd(enabled);
+                        CommandResponse::Ok(None)
+                    }
+                },
+                PogCommand::MarksPanel { visible } => match visible {
+                    None => {
+                        let state = if marks_panel_cmd.is_visible() { "on" } else { "off" };
+                        CommandResponse::Ok(Some(state.to_string()))
+                    }
+                    Some(visible) => {
+                        set_marks_panel_cmd(visible);
+                        CommandResponse::Ok(None)
+                    }
+                },
+                PogCommand::Follow { enabled } => match enabled {
+                    None => {
+                        let state = if follow_button_cmd.is_active() { "on" } else { "off" };
+                        CommandResponse::Ok(Some(state.to_string()))
+                    }
+                    Some(enabled) => {
+                        follow_button_cmd.set_active(enabled);
+                        CommandResponse::Ok(None)
+                    }
+                },
+                PogCommand::Filter { kind, pattern } => match LineFilter::new(kind, &pattern) {
+                    Ok(filter) => {
+                        let mut filters = current_filters_cmd();
+                        filters.push(filter);
+                        set_filters_cmd(filters);
+                        CommandResponse::Ok(None)
+                    }
+                    Err(e) => CommandResponse::Error(e),
+                },
+                PogCommand::FilterClear => {
+                    set_filters_cmd(Vec::new());
+                    CommandResponse::Ok(None)
+                }
+                PogCommand::Fold { action } => match action {
+                    FoldAction::Get => {
+                        CommandResponse::Ok(Some(describe_fold(line_fold_cmd.borrow().as_ref())))
+                    }
+                    FoldAction::Off => {
+                        set_fold_cmd(None);
+                        CommandResponse::Ok(None)
+                    }
+                    FoldAction::On { mode } => {
+                        set_fold_cmd(Some(LineFold::new(mode)));
+                        CommandResponse::Ok(None)
+                    }
+                },
+                PogCommand::Hide { start, end } => {
+                    if end > total_lines {
+                        CommandResponse::Error(format!(
+                            "line out of range: requested {}, file has {} lines",
+                            end, total_lines
+                        ))
+                    } else {
+                        hide_lines_cmd(start - 1, end - 1);
+                        CommandResponse::Ok(Some((end - start + 1).to_string()))
+                    }
+                }
+                PogCommand::UnhideAll => {
+                    hidden_lines_cmd.borrow_mut().clear();
+                    set_filters_cmd(current_filters_cmd());
+                    CommandResponse::Ok(None)
+                }
+                PogCommand::Pin { line } | PogCommand::Unpin { line } if line > total_lines => {
+                    CommandResponse::Error(format!(
+                        "line out of range: requested {}, file has {} lines",
+                        line, total_lines
+                    ))
+                }
+                PogCommand::Pin { line } | PogCommand::Unpin { line } => {
+                    let pin = matches!(command, PogCommand::Pin { .. });
+                    match file_line(line - 1).map(|file_line| pin_line_cmd(file_line, pin)) {
+                        Some(Ok(false)) if !pin => {
+                            CommandResponse::Error(format!("line {} is not pinned", line))
+                        }
+                        Some(Err(message)) => CommandResponse::Error(message),
+                        _ => CommandResponse::Ok(None),
+                    }
+                }
+                PogCommand::Highlight { color, pattern } => {
+                    if gtk4::gdk::RGBA::parse(&color).is_err() {
+                        CommandResponse::Error(format!("invalid color: {}", color))
+                    } else {
+                        match HighlightRule::new(&color, &pattern) {
+                            Ok(rule) => {
+                                highlights_cmd.borrow_mut().push(rule);
+                                needs_redraw.set(true);
+                                CommandResponse::Ok(None)
+                            }
+                            Err(e) => CommandResponse::Error(e),
+                        }
+                    }
+                }
+                PogCommand::Unhighlight { pattern } => {
+                    let mut highlights = highlights_cmd.borrow_mut();
+                    let before = highlights.len();
+                    highlights.retain(|rule| rule.pattern != pattern);
+                    let removed = highlights.len() != before;
+                    drop(highlights);
+                    if removed {
+                        needs_redraw.set(true);
+                        CommandResponse::Ok(None)
+                    } else {
+                        CommandResponse::Error(format!("no highlight for {}", pattern))
+                    }
+                }
+                PogCommand::HighlightClear => {
+                    highlights_cmd.borrow_mut().clear();
+                    needs_redraw.set(true);
+                    CommandResponse::Ok(None)
+                }
+                PogCommand::Highlights => {
+                    let highlights = highlights_cmd.borrow();
+                    let rules: Vec<(&str, &str)> = highlights
+                        .iter()
+                        .map(|rule| (rule.color.as_str(), rule.pattern.as_str()))
+                        .collect();
+                    CommandResponse::Ok(Some(frame_highlights(&rules)))
+                }
+                PogCommand::Help => CommandResponse::Ok(Some(help_text())),
+                PogCommand::Version => CommandResponse::Ok(Some(format!(
+                    "pog {} protocol {}",
+                    env!("CARGO_PKG_VERSION"),
+                    PROTOCOL_VERSION
+                ))),
+                PogCommand::Title { text } => {
+                    if let Some(window) = window_cmd.upgrade() {
+                        window.set_title(Some(text.as_deref().unwrap_or(&default_title)));
+                    }
+                    CommandResponse::Ok(None)
+                }
+                PogCommand::StatusText { text } => {
+                    status_text_label_cmd.set_text(text.as_deref().unwrap_or(""));
+                    status_text_label_cmd.set_visible(text.is_some());
+                    CommandResponse::Ok(None)
                 }
                 PogCommand::SearchClear => {
                     let mut state = search_state_cmd.borrow_mut();
                     state.clear();
                     drop(state);
+                    request_tx_cmd.cancel_match_count();
 
                     // Sync UI with socket-initiated clear
                     search_box_cmd.set_visible(false);
                     search_entry_cmd.set_text("");
                     search_info_cmd.set_text("");
 
-                    // Trigger redraw to clear highlights
-                    let start = v_adjustment_cmd.value() as usize;
-                    let request_id = next_request_id();
-                    *latest_request_id_cmd.borrow_mut() = request_id;
-                    let _ = request_tx_cmd.send_blocking(FileRequest::GetLines {
-                        start,
-                        count: LINES_PER_PAGE,
-                        request_id,
-                    });
+                    // Redraw to clear highlights
+                    needs_redraw.set(true);
                     CommandResponse::Ok(None)
                 }
+            }
+        };
+        while let Ok(request) = command_rx.recv().await {
+            hold_input(true);
+            let (commands, is_batch) = match request.command {
+                PogCommand::Batch { commands } => (commands, true),
+                command => (vec![command], false),
+            };
+            // A batch runs in one go, and stops at the first command that fails
+            let mut responses = Vec::with_capacity(commands.len());
+            let mut quit = false;
+            let mut marks_changed = false;
+            for command in commands {
+                quit |= command == PogCommand::Quit;
+                let changes_marks = command.changes_marks();
+                let response = run_command(command).await;
+                let failed = matches!(response, CommandResponse::Error(_));
+                marks_changed |= changes_marks && !failed;
+                responses.push(response);
+                if failed {
+                    break;
+                }
+            }
+            if needs_redraw.replace(false) {
+                redraw_cmd();
+            }
+            // `goto`, `cursor` and searches may have moved the cursor line
+            line_rows_cmd.show_cursor(*cursor_position_cmd.borrow());
+            if marks_changed {
+                refresh_marks_panel_cmd();
+                let count = marked_lines_cmd.borrow().len();
+                events_cmd.publish(&ViewerEvent::Marks { count });
+            }
+            let response = if !is_batch {
+                responses.pop().expect("one command")
+            } else if matches!(responses.last(), Some(CommandResponse::Error(_))) {
+                CommandResponse::Error(frame_responses(&responses))
+            } else {
+                CommandResponse::Ok(Some(frame_responses(&responses)))
             };
             let _ = request.response_tx.send(response);
+            hold_input(false);
+            if let Some(handle_key) = handle_key_cmd.get() {
+                for (key, modifier) in held_keys_cmd.keys.take() {
+                    handle_key(key, modifier);
+                }
+            }
+
+            // Close every window, so each saves its marks and stops its
+            // server; the application exits with the last one
+            if quit {
+                if let Some(app) = app_quit.upgrade() {
+                    for window in app.windows() {
+                        window.close();
+                    }
+                }
+            }
         }
     });
 
@@ -805,8 +4179,8 @@ fn build_ui(app: &Application, file_source: Arc<dyn FileSource>, port: u16, no_s
     let initial_id = next_request_id();
     *latest_request_id.borrow_mut() = initial_id;
     let _ = request_tx.send_blocking(FileRequest::GetLines {
-        start: 0,
-        count: LINES_PER_PAGE,
+        start: initial.top_line,
+        count: fetch_lines(&v_adjustment),
         request_id: initial_id,
     });
 
@@ -814,26 +4188,41 @@ fn build_ui(app: &Application, file_source: Arc<dyn FileSource>, port: u16, no_s
     let request_tx_scroll = request_tx.clone();
     let latest_request_id_scroll = latest_request_id.clone();
     let search_state_scroll = search_state.clone();
+    let total_lines_scroll = total_lines.clone();
+    let events_scroll = events.clone();
+    let show_offset_scroll = show_offset.clone();
+    // The top line last fetched here: scrolling within it only moves the rows
+    let scrolled_top: Rc<Cell<Option<usize>>> = Rc::new(Cell::new(None));
+    let scrolled_top_page = scrolled_top.clone();
 
     v_adjustment.connect_value_changed(move |adj| {
         let start_line = adj.value() as usize;
+        if scrolled_top.replace(Some(start_line)) == Some(start_line) {
+            show_offset_scroll();
+            return;
+        }
         let request_id = next_request_id();
         *latest_request_id_scroll.borrow_mut() = request_id;
+        events_scroll.publish(&ViewerEvent::Viewport {
+            top: start_line + 1,
+            bottom: (start_line + page_lines(adj)).min(total_lines_scroll.get()),
+        });
 
         let _ = request_tx_scroll.send_blocking(FileRequest::GetLines {
             start: start_line,
-            count: LINES_PER_PAGE,
+            count: fetch_lines(adj),
             request_id,
         });
 
         // Re-search if search is active and viewport moved outside searched range
         let state = search_state_scroll.borrow();
-        if state.needs_research(start_line, LINES_PER_PAGE, SEARCH_BUFFER_LINES) {
+        if state.needs_research(start_line, page_lines(adj), search_config.research_margin) {
             let pattern = state.pattern_str.clone();
+            let multiline = state.multiline;
             drop(state);
 
-            let search_start = start_line.saturating_sub(SEARCH_BUFFER_LINES);
-            let search_end = (start_line + LINES_PER_PAGE + SEARCH_BUFFER_LINES).min(total_lines);
+            let (search_start, search_end) =
+                search_config.window(start_line, page_lines(adj), total_lines_scroll.get());
 
             let _ = request_tx_scroll.send_blocking(FileRequest::SearchRange {
                 pattern,
@@ -841,26 +4230,50 @@ fn build_ui(app: &Application, file_source: Arc<dyn FileSource>, port: u16, no_s
                 end_line: search_end,
                 request_id: next_request_id(),
                 navigate_to_first: false,  // Don't navigate on re-search while scrolling
+                multiline,
             });
         }
     });
 
-    // Handle mouse wheel scrolling on the content area
+    // A new page size (`viewport`) fetches and announces the lines it now shows
+    v_adjustment.connect_page_size_notify(move |adj| {
+        scrolled_top_page.set(None);
+        adj.emit_by_name::<()>("value-changed", &[]);
+    });
+
+    // Handle mouse wheel scrolling on the content area.
+    // Shift+wheel scrolls horizontally; touchpads may also send dx directly.
     let scroll_controller = gtk4::EventControllerScroll::new(
-        gtk4::EventControllerScrollFlags::VERTICAL,
+        gtk4::EventControllerScrollFlags::BOTH_AXES | gtk4::EventControllerScrollFlags::KINETIC,
     );
+    let table_header_scroll = table_header.clone();
     let v_adjustment_scroll = v_adjustment.clone();
-    scroll_controller.connect_scroll(move |_, _, dy| {
-        let current = v_adjustment_scroll.value();
-        let step = 3.0; // lines per scroll tick
-        let new_value = (current + dy * step).clamp(
-            v_adjustment_scroll.lower(),
-            v_adjustment_scroll.upper() - v_adjustment_scroll.page_size(),
-        );
-        v_adjustment_scroll.set_value(new_value);
+    let h_adjustment_scroll = h_scroll.hadjustment();
+    scroll_controller.connect_scroll(move |controller, dx, dy| {
+        let shift = controller
+            .current_event_state()
+            .contains(gtk4::gdk::ModifierType::SHIFT_MASK);
+        let (dx, dy) = if shift && dx == 0.0 { (dy, 0.0) } else { (dx, dy) };
+
+        // A wheel moves by steps per notch; touchpads (and their kinetic
+        // glide after the fingers lift) move by pixels, so by parts of a line
+        let pixels = controller.unit() == gtk4::gdk::ScrollUnit::Surface;
+        if dx != 0.0 {
+            scroll_by(&h_adjustment_scroll, if pixels { dx } else { dx * H_SCROLL_STEP_PX });
+        }
+        if dy != 0.0 {
+            let lines = if pixels {
+                dy / row_height(&table_header_scroll).max(1) as f64
+            } else {
+                dy * WHEEL_STEP_LINES
+            };
+            scroll_by(&v_adjustment_scroll, lines);
+        }
         glib::Propagation::Stop
     });
-    h_scroll.add_controller(scroll_controller);
+    // Ahead of the scrolled windows, which would move their clipped rows
+    scroll_controller.set_propagation_phase(gtk4::PropagationPhase::Capture);
+    lines_box.add_controller(scroll_controller);
 
     // Close button handler
     let search_box_close = search_box.clone();
@@ -872,6 +4285,7 @@ fn build_ui(app: &Application, file_source: Arc<dyn FileSource>, port: u16, no_s
     search_close_button.connect_clicked(move |_| {
         search_box_close.set_visible(false);
         search_state_close.borrow_mut().clear();
+        request_tx_close.cancel_match_count();
         search_info_close.set_text("");
         // Trigger redraw to clear highlights
         let start = v_adjustment_close.value() as usize;
@@ -879,24 +4293,270 @@ fn build_ui(app: &Application, file_source: Arc<dyn FileSource>, port: u16, no_s
         *latest_request_id_close.borrow_mut() = request_id;
         let _ = request_tx_close.send_blocking(FileRequest::GetLines {
             start,
-            count: LINES_PER_PAGE,
+            count: fetch_lines(&v_adjustment_close),
             request_id,
         });
     });
 
+    // Pick a file to open in this window's place, starting in the folder of
+    // the one shown
+    let window_open_dialog = window.clone();
+    let watch_path_open_dialog = options.watch_path.clone();
+    let show_open_dialog: Rc<dyn Fn()> = Rc::new(move || {
+        let dialog = gtk4::FileDialog::builder().title("Open File").modal(true).build();
+        if let Some(folder) = watch_path_open_dialog.as_deref().and_then(|path| path.parent()) {
+            dialog.set_initial_folder(Some(&gtk4::gio::File::for_path(folder)));
+        }
+        let open_path = open_path.clone();
+        dialog.open(Some(&window_open_dialog), gtk4::gio::Cancellable::NONE, move |result| {
+            if let Some(path) = result.ok().and_then(|file| file.path()) {
+                open_path(path);
+            }
+        });
+    });
+    let show_open_dialog_button = show_open_dialog.clone();
+    open_button.connect_clicked(move |_| show_open_dialog_button());
+
+    // The application menu's actions (see `build_app_menu`)
+    let preferences_action = gtk4::gio::SimpleAction::new("preferences", None);
+    let window_preferences = window.clone();
+    let show_notice_preferences = show_notice.clone();
+    preferences_action.connect_activate(move |_, _| {
+        let show_notice = show_notice_preferences.clone();
+        show_preferences_dialog(&window_preferences, move || {
+            show_notice("Preferences saved; they apply when pog is started again", false)
+        });
+    });
+    window.add_action(&preferences_action);
+
+    // Write every line of the view, as `export` does, to a file picked in a dialog
+    let export_action = gtk4::gio::SimpleAction::new("export", None);
+    let window_export = window.clone();
+    let request_tx_export = request_tx.clone();
+    let exporting_export = exporting.clone();
+    let total_lines_export = total_lines.clone();
+    let show_error_export = show_error.clone();
+    export_action.connect_activate(move |_, _| {
+        if exporting_export.get() {
+            show_error_export("An export is already running", None);
+            return;
+        }
+        let dialog = gtk4::FileDialog::builder().title("Export View").modal(true).build();
+        let request_tx = request_tx_export.clone();
+        let exporting = exporting_export.clone();
+        let total_lines = total_lines_export.get();
+        let show_error = show_error_export.clone();
+        dialog.save(Some(&window_export), gtk4::gio::Cancellable::NONE, move |result| {
+            let Some(path) = result.ok().and_then(|file| file.path()) else {
+                return;
+            };
+            match std::fs::File::create(&path) {
+                Ok(file) => {
+                    request_tx.export(0, total_lines, file, path);
+                    exporting.set(true);
+                }
+                Err(e) => show_error(&format!("{}: {}", path.display(), e), None),
+            }
+        });
+    });
+    window.add_action(&export_action);
+
+    let about_action = gtk4::gio::SimpleAction::new("about", None);
+    let window_about = window.clone();
+    about_action.connect_activate(move |_, _| {
+        gtk4::AboutDialog::builder()
+            .transient_for(&window_about)
+            .modal(true)
+            .program_name("pog")
+            .version(env!("CARGO_PKG_VERSION"))
+            .comments("A log viewer for files of any size, local or remote")
+            .build()
+            .present();
+    });
+    window.add_action(&about_action);
+
     // Keyboard controller for search shortcuts
     let key_controller = gtk4::EventControllerKey::new();
     let search_box_key = search_box.clone();
     let search_entry_key = search_entry.clone();
     let search_state_key = search_state.clone();
+    let search_wrap_button_key = search_wrap_button.clone();
     let search_info_key = search_info.clone();
     let request_tx_key = request_tx.clone();
     let latest_request_id_key = latest_request_id.clone();
     let v_adjustment_key = v_adjustment.clone();
+    let h_adjustment_key = h_scroll.hadjustment();
+    let duplicate_view_key = duplicate_view.clone();
+    let view_mode_key = view_mode.clone();
+    let line_rows_key = line_rows.clone();
+    let window_key = window.clone();
+    let highlights_key = highlights.clone();
+    let theme_key = theme.clone();
+    let redraw_key = redraw.clone();
+    let show_notice_key = show_notice.clone();
+    let goto_time_key = goto_time.clone();
+    let goto_percent_key = goto_percent.clone();
+    let time_column_key = time_column.clone();
+    let table_key = table.clone();
+    let set_table_key = set_table.clone();
+    let set_time_column_key = set_time_column.clone();
+    let marks_panel_key = marks_panel.clone();
+    let follow_button_key = follow_button.clone();
+    let line_fold_key = line_fold.clone();
+    let set_fold_key = set_fold.clone();
+    let hide_lines_key = hide_lines.clone();
+    let set_marks_panel_key = set_marks_panel.clone();
+    let show_open_dialog_key = show_open_dialog.clone();
+    let preferences_action_key = preferences_action.clone();
+    let bookmarks_key = bookmarks.clone();
+    let cursor_position_bookmark_key = cursor_position.clone();
+    let copy_lines_key = copy_lines.clone();
+    let cursor_position_copy_key = cursor_position.clone();
+    let cursor_position_key = cursor_position.clone();
+    let move_cursor_key = move_cursor.clone();
+    let toggle_mark_key = toggle_mark.clone();
+    let view_filter_key = view_filter.clone();
+    let pinned_lines_key = pinned_lines.clone();
+    let pin_line_key = pin_line.clone();
+    let total_lines_key = total_lines.clone();
+    let set_relative_numbers_key = set_relative_numbers.clone();
+    let key_bindings = options.key_bindings.clone();
+    // Keys typed so far of a sequence such as `17j` or `gg`
+    let pending_keys: RefCell<PendingKeys> = RefCell::new(PendingKeys::default());
 
-    key_controller.connect_key_pressed(move |_, key, _code, modifier| {
+    let handle_key_set = handle_key.clone();
+    let handle_key_fn: KeyHandler = Rc::new(move |key, modifier| {
         use gtk4::gdk::{Key, ModifierType};
 
+        // Ctrl+Shift+N to duplicate the view into a new window
+        if modifier.contains(ModifierType::CONTROL_MASK | ModifierType::SHIFT_MASK)
+            && (key == Key::N || key == Key::n)
+        {
+            duplicate_view_key();
+            return glib::Propagation::Stop;
+        }
+
+        // Ctrl+Shift+H to edit the highlight rules
+        if modifier.contains(ModifierType::CONTROL_MASK | ModifierType::SHIFT_MASK)
+            && (key == Key::H || key == Key::h)
+        {
+            let palette = theme_key.get().highlight_palette();
+            show_highlight_dialog(&window_key, &highlights_key, &redraw_key, palette);
+            return glib::Propagation::Stop;
+        }
+
+        // Ctrl+Shift+C to copy lines from the cursor line on
+        if modifier.contains(ModifierType::CONTROL_MASK | ModifierType::SHIFT_MASK)
+            && (key == Key::C || key == Key::c)
+        {
+            let show_notice = show_notice_key.clone();
+            show_copy_lines_dialog(
+                &window_key,
+                &copy_lines_key,
+                *cursor_position_copy_key.borrow(),
+                total_lines_key.get(),
+                move |count| {
+                    let plural = if count == 1 { "" } else { "s" };
+                    show_notice(&format!("Copied {} line{}", count, plural), false);
+                },
+            );
+            return glib::Propagation::Stop;
+        }
+
+        // Ctrl+H to toggle hex rendering of binary lines
+        if modifier.contains(ModifierType::CONTROL_MASK) && key == Key::h {
+            set_view_mode(view_mode_key.get().toggled());
+            return glib::Propagation::Stop;
+        }
+
+        // Ctrl+= / Ctrl+- to zoom the lines' font in or out, Ctrl+0 back to its size
+        if modifier.contains(ModifierType::CONTROL_MASK) {
+            let steps = match key {
+                Key::equal | Key::plus | Key::KP_Add => Some(Some(1)),
+                Key::minus | Key::KP_Subtract => Some(Some(-1)),
+                Key::_0 | Key::KP_0 => Some(None),
+                _ => None,
+            };
+            if let Some(steps) = steps {
+                zoom(steps);
+                return glib::Propagation::Stop;
+            }
+        }
+
+        // Alt+Z to toggle wrapping long lines
+        if modifier.contains(ModifierType::ALT_MASK) && (key == Key::z || key == Key::Z) {
+            set_wrap(!line_rows_key.wrap.get());
+            return glib::Propagation::Stop;
+        }
+
+        // Alt+N to toggle relative line numbers
+        if modifier.contains(ModifierType::ALT_MASK) && (key == Key::n || key == Key::N) {
+            set_relative_numbers_key(!line_rows_key.relative.get());
+            return glib::Propagation::Stop;
+        }
+
+        // Alt+C to toggle the column view
+        if modifier.contains(ModifierType::ALT_MASK) && (key == Key::c || key == Key::C) {
+            let enabled = table_key.borrow().is_some();
+            set_table_key((!enabled).then_some(None));
+            return glib::Propagation::Stop;
+        }
+
+        // Alt+T to toggle the time column
+        if modifier.contains(ModifierType::ALT_MASK) && (key == Key::t || key == Key::T) {
+            set_time_column_key(!time_column_key.get());
+            return glib::Propagation::Stop;
+        }
+
+        // Alt+M to show or hide the marks panel
+        if modifier.contains(ModifierType::ALT_MASK) && (key == Key::m || key == Key::M) {
+            set_marks_panel_key(!marks_panel_key.is_visible());
+            return glib::Propagation::Stop;
+        }
+
+        // Alt+F to follow new lines, or stop
+        if modifier.contains(ModifierType::ALT_MASK) && (key == Key::f || key == Key::F) {
+            follow_button_key.set_active(!follow_button_key.is_active());
+            return glib::Propagation::Stop;
+        }
+
+        // Alt+R to fold runs of repeated lines, or unfold them
+        if modifier.contains(ModifierType::ALT_MASK) && (key == Key::r || key == Key::R) {
+            let folded = line_fold_key.borrow().is_some();
+            set_fold_key((!folded).then(|| LineFold::new(FoldMode::Similar)));
+            return glib::Propagation::Stop;
+        }
+
+        // Ctrl+O to open another file in this window's place
+        if modifier.contains(ModifierType::CONTROL_MASK) && key == Key::o {
+            show_open_dialog_key();
+            return glib::Propagation::Stop;
+        }
+
+        // Ctrl+, to edit the config file's common settings
+        if modifier.contains(ModifierType::CONTROL_MASK) && key == Key::comma {
+            preferences_action_key.activate(None);
+            return glib::Propagation::Stop;
+        }
+
+        // Ctrl+T to jump to a time or a percentage
+        if modifier.contains(ModifierType::CONTROL_MASK) && key == Key::t {
+            show_goto_dialog(&window_key, &goto_time_key, &goto_percent_key);
+            return glib::Propagation::Stop;
+        }
+
+        // Ctrl+B to jump to or set a bookmark
+        if modifier.contains(ModifierType::CONTROL_MASK) && key == Key::b {
+            show_bookmarks_dialog(
+                &window_key,
+                &bookmarks_key,
+                &cursor_position_bookmark_key,
+                &v_adjustment_key,
+                view_filter_key.borrow().clone(),
+            );
+            return glib::Propagation::Stop;
+        }
+
         // Ctrl+F to open search
         if modifier.contains(ModifierType::CONTROL_MASK) && key == Key::f {
             search_box_key.set_visible(true);
@@ -908,6 +4568,7 @@ fn build_ui(app: &Application, file_source: Arc<dyn FileSource>, port: u16, no_s
         if key == Key::Escape && search_box_key.is_visible() {
             search_box_key.set_visible(false);
             search_state_key.borrow_mut().clear();
+            request_tx_key.cancel_match_count();
             search_info_key.set_text("");
             // Trigger redraw to clear highlights
             let start = v_adjustment_key.value() as usize;
@@ -915,7 +4576,7 @@ fn build_ui(app: &Application, file_source: Arc<dyn FileSource>, port: u16, no_s
             *latest_request_id_key.borrow_mut() = request_id;
             let _ = request_tx_key.send_blocking(FileRequest::GetLines {
                 start,
-                count: LINES_PER_PAGE,
+                count: fetch_lines(&v_adjustment_key),
                 request_id,
             });
             return glib::Propagation::Stop;
@@ -926,7 +4587,9 @@ fn build_ui(app: &Application, file_source: Arc<dyn FileSource>, port: u16, no_s
             let state = search_state_key.borrow();
             if state.is_active && state.pattern.is_some() {
                 let pattern = state.pattern_str.clone();
-                let current_line = v_adjustment_key.value() as usize;
+                let multiline = state.multiline;
+                let current_line = *cursor_position_key.borrow();
+                let wrap = search_wrap_button_key.is_active();
                 drop(state);
 
                 let direction = if modifier.contains(ModifierType::SHIFT_MASK) {
@@ -938,8 +4601,10 @@ fn build_ui(app: &Application, file_source: Arc<dyn FileSource>, port: u16, no_s
                 let request_id = next_request_id();
                 let _ = request_tx_key.send_blocking(FileRequest::FindNextMatch {
                     pattern,
+                    multiline,
                     from_line: current_line,
                     direction,
+                    wrap,
                     request_id,
                     result_tx: None,  // UI doesn't need sync response
                 });
@@ -947,15 +4612,158 @@ fn build_ui(app: &Application, file_source: Arc<dyn FileSource>, port: u16, no_s
             return glib::Propagation::Stop;
         }
 
+        // F5 to reload the file, e.g. after it was rewritten in place
+        if key == Key::F5 {
+            let request_tx = request_tx_key.clone();
+            let show_notice = show_notice_key.clone();
+            glib::spawn_future_local(async move {
+                let timeout = glib::timeout_future(command_timeout);
+                if let Err(message) = request_tx.reload(timeout).await {
+                    show_notice(&format!("Reload failed: {}", message), true);
+                }
+            });
+            return glib::Propagation::Stop;
+        }
+
+        // Left/Right scroll long lines horizontally
+        if key == Key::Left || key == Key::Right {
+            let delta = if key == Key::Left { -H_SCROLL_STEP_PX } else { H_SCROLL_STEP_PX };
+            scroll_by(&h_adjustment_key, delta);
+            return glib::Propagation::Stop;
+        }
+
+        // The keys below move the view unless typing into the search bar
+        // or another text field
+        let typing = GtkWindowExt::focus(&window_key).is_some_and(|w| w.is::<gtk4::Text>());
+
+        // The keys move the cursor line from where it is on screen; scrolled
+        // away from it, they start from the nearest line shown
+        let top = v_adjustment_key.value() as usize;
+        let shown = page_lines(&v_adjustment_key).max(1);
+        let cursor = (*cursor_position_key.borrow()).clamp(top, top + shown - 1);
+
+        // Paging keys as in any pager: Up/Down move the cursor a line,
+        // PageUp/PageDown a page along with the view, Home/End go to the
+        // start or end of the file
+        let page = v_adjustment_key.page_increment();
+        let v_move = match key {
+            Key::Up | Key::KP_Up => Some((0.0, cursor.saturating_sub(1))),
+            Key::Down | Key::KP_Down => Some((0.0, cursor + 1)),
+            Key::Page_Up | Key::KP_Page_Up => Some((-page, cursor.saturating_sub(page as usize))),
+            Key::Page_Down | Key::KP_Page_Down => Some((page, cursor + page as usize)),
+            Key::Home | Key::KP_Home => Some((0.0, 0)),
+            Key::End | Key::KP_End => Some((0.0, usize::MAX)),
+            _ => None,
+        };
+        if let Some((delta, line)) = v_move.filter(|_| !typing) {
+            // With Shift held the lines passed over are selected, from where
+            // the cursor was when the selection started
+            let anchor = if modifier.contains(ModifierType::SHIFT_MASK) {
+                line_rows_key.anchor.get().or(Some(cursor))
+            } else {
+                None
+            };
+            line_rows_key.anchor.set(anchor);
+            scroll_by(&v_adjustment_key, delta);
+            move_cursor_key(line);
+            return glib::Propagation::Stop;
+        }
+
+        // Delete hides the selected lines, or the cursor line, behind a
+        // marker with their count; Escape drops the selection
+        if key == Key::Delete && !typing {
+            let lines = line_rows_key.selection(cursor).unwrap_or(cursor..=cursor);
+            line_rows_key.anchor.set(None);
+            hide_lines_key(*lines.start(), *lines.end());
+            return glib::Propagation::Stop;
+        }
+        if key == Key::Escape && line_rows_key.anchor.take().is_some() {
+            line_rows_key.show_cursor(cursor);
+            return glib::Propagation::Stop;
+        }
+
+        // Vim-style navigation (j/k, Ctrl+D/Ctrl+U, gg/G, /), marking (m) and
+        // pinning (p); a count before j/k moves that many lines, before gg/G
+        // to that line
+        let other_modifiers = ModifierType::ALT_MASK | ModifierType::SUPER_MASK;
+        let navigating = !typing && !modifier.intersects(other_modifiers);
+        if let Some(c) = key.to_unicode().filter(|_| navigating) {
+            let press = KeyPress {
+                ctrl: modifier.contains(ModifierType::CONTROL_MASK),
+                key: c,
+            };
+            let half_page = (shown / 2).max(1);
+            match key_bindings.press(&mut pending_keys.borrow_mut(), press) {
+                KeyMatch::Action(KeyAction::LineDown, count) => {
+                    move_cursor_key(cursor.saturating_add(count.unwrap_or(1)))
+                }
+                KeyMatch::Action(KeyAction::LineUp, count) => {
+                    move_cursor_key(cursor.saturating_sub(count.unwrap_or(1)))
+                }
+                KeyMatch::Action(KeyAction::Top | KeyAction::Bottom, Some(line)) => {
+                    move_cursor_key(line.saturating_sub(1))
+                }
+                KeyMatch::Action(KeyAction::HalfPageDown, _) => {
+                    scroll_by(&v_adjustment_key, half_page as f64);
+                    move_cursor_key(cursor + half_page);
+                }
+                KeyMatch::Action(KeyAction::HalfPageUp, _) => {
+                    scroll_by(&v_adjustment_key, -(half_page as f64));
+                    move_cursor_key(cursor.saturating_sub(half_page));
+                }
+                KeyMatch::Action(KeyAction::Top, None) => move_cursor_key(0),
+                KeyMatch::Action(KeyAction::Bottom, None) => move_cursor_key(usize::MAX),
+                KeyMatch::Action(KeyAction::Search, _) => {
+                    search_box_key.set_visible(true);
+                    search_entry_key.grab_focus();
+                }
+                KeyMatch::Action(KeyAction::Mark, _) => {
+                    move_cursor_key(cursor);
+                    toggle_mark_key(cursor);
+                }
+                KeyMatch::Action(KeyAction::Pin, _) => {
+                    // Pin the selected lines, or unpin them if all are pinned
+                    let lines = line_rows_key.selection(cursor).unwrap_or(cursor..=cursor);
+                    let filter = view_filter_key.borrow().clone();
+                    let file_line =
+                        |line| filter.as_ref().map_or(Some(line), |f| f.source_line(line));
+                    let file_lines: Vec<usize> = lines.filter_map(file_line).collect();
+                    let pinned = pinned_lines_key.borrow().clone();
+                    let pin = !file_lines.iter().all(|line| pinned.binary_search(line).is_ok());
+                    for line in file_lines {
+                        if let Err(message) = pin_line_key(line, pin) {
+                            show_notice_key(&message, false);
+                            break;
+                        }
+                    }
+                }
+                KeyMatch::Pending => {}
+                KeyMatch::None => return glib::Propagation::Proceed,
+            }
+            return glib::Propagation::Stop;
+        }
+
         glib::Propagation::Proceed
     });
+    let _ = handle_key_set.set(handle_key_fn.clone());
+    key_controller.connect_key_pressed(move |_, key, _code, modifier| {
+        if held_keys.held.get() {
+            held_keys.keys.borrow_mut().push((key, modifier));
+            return glib::Propagation::Stop;
+        }
+        handle_key_fn(key, modifier)
+    });
     window.add_controller(key_controller);
 
     // Search entry activate handler (Enter key)
     let search_state_entry = search_state.clone();
+    let search_literal_button_entry = search_literal_button.clone();
+    let search_multiline_button_entry = search_multiline_button.clone();
     let search_info_entry = search_info.clone();
     let request_tx_entry = request_tx.clone();
     let v_adjustment_entry = v_adjustment.clone();
+    let total_lines_entry = total_lines.clone();
+    let events_entry = events.clone();
     search_entry.connect_activate(move |entry| {
         let pattern = entry.text().to_string();
         if pattern.is_empty() {
@@ -963,22 +4771,34 @@ fn build_ui(app: &Application, file_source: Arc<dyn FileSource>, port: u16, no_s
         }
 
         let mut state = search_state_entry.borrow_mut();
-        match state.set_pattern(&pattern) {
+        let literal = search_literal_button_entry.is_active();
+        let multiline = search_multiline_button_entry.is_active();
+        match state.set_pattern(&pattern, search_case(), literal, multiline) {
             Ok(()) => {
                 search_info_entry.set_text("Searching...");
                 let viewport_start = v_adjustment_entry.value() as usize;
-                let search_start = viewport_start.saturating_sub(SEARCH_BUFFER_LINES);
-                let search_end = (viewport_start + LINES_PER_PAGE + SEARCH_BUFFER_LINES).min(total_lines);
+                let page = page_lines(&v_adjustment_entry);
+                let (search_start, search_end) =
+                    search_config.window(viewport_start, page, total_lines_entry.get());
+                let pattern = state.pattern_str.clone();
+                let multiline = state.multiline;
+                let count_request_id = next_request_id();
+                state.count_request_id = Some(count_request_id);
                 drop(state);
 
+                events_entry.publish(&ViewerEvent::SearchStarted { pattern: pattern.clone() });
+                // Time bounds and fields only come with the search command
+                request_tx_entry.set_search_scope(SearchScope::default());
                 let request_id = next_request_id();
                 let _ = request_tx_entry.send_blocking(FileRequest::SearchRange {
-                    pattern,
+                    pattern: pattern.clone(),
                     start_line: search_start,
                     end_line: search_end,
                     request_id,
                     navigate_to_first: true,
+                    multiline,
                 });
+                request_tx_entry.count_matches(pattern, multiline, count_request_id);
             }
             Err(e) => {
                 search_info_entry.set_text(&e);
@@ -986,8 +4806,340 @@ fn build_ui(app: &Application, file_source: Arc<dyn FileSource>, port: u16, no_s
         }
     });
 
+    // Re-run an active search when the case, plain text or multiline toggle changes
+    for toggle in [&search_case_button, &search_literal_button, &search_multiline_button] {
+        let search_entry_toggle = search_entry.clone();
+        let search_state_toggle = search_state.clone();
+        let syncing_search_toggles_toggle = syncing_search_toggles.clone();
+        toggle.connect_toggled(move |_| {
+            if !syncing_search_toggles_toggle.get() && search_state_toggle.borrow().is_active {
+                search_entry_toggle.emit_activate();
+            }
+        });
+    }
+
     window.set_child(Some(&overlay));
     window.present();
+
+    server_port
+}
+
+/// The title bar's application menu, whose items run the window's actions
+/// added in `build_ui`.
+fn build_app_menu() -> gtk4::gio::Menu {
+    let menu = gtk4::gio::Menu::new();
+    menu.append(Some("Preferences"), Some("win.preferences"));
+    menu.append(Some("Export View..."), Some("win.export"));
+    menu.append(Some("About pog"), Some("win.about"));
+    menu
+}
+
+/// The search bar's menu of saved searches: an entry to save the active search
+/// under a name, and a button per saved search to run it again.
+fn build_saved_search_menu(
+    current_search: Rc<dyn Fn() -> Option<SavedSearch>>,
+    load_search: Rc<dyn Fn(&SavedSearch)>,
+    search_info: &Label,
+) -> gtk4::MenuButton {
+    let menu_button = gtk4::MenuButton::new();
+    menu_button.set_label("Saved");
+    menu_button.set_tooltip_text(Some("Save the search or run a saved one"));
+    menu_button.set_css_classes(&["search-close"]);
+
+    let popover = gtk4::Popover::new();
+    let content = GtkBox::new(Orientation::Vertical, 4);
+    let name_entry = Entry::new();
+    name_entry.set_placeholder_text(Some("Save as..."));
+    let saved_list = GtkBox::new(Orientation::Vertical, 2);
+    content.append(&name_entry);
+    content.append(&gtk4::Separator::new(Orientation::Horizontal));
+    content.append(&saved_list);
+    popover.set_child(Some(&content));
+    menu_button.set_popover(Some(&popover));
+
+    let popover_save = popover.clone();
+    let search_info_save = search_info.clone();
+    name_entry.connect_activate(move |entry| {
+        let result = match current_search() {
+            Some(search) => SavedSearches::save(entry.text().trim(), search),
+            None => Err("no active search".to_string()),
+        };
+        match result {
+            Ok(()) => {
+                search_info_save.set_text(&format!("Saved as {}", entry.text().trim()));
+                entry.set_text("");
+                popover_save.popdown();
+            }
+            Err(e) => search_info_save.set_text(&e),
+        }
+    });
+
+    // Read the file each time, so searches saved by other windows show up
+    let popover_list = popover.clone();
+    popover.connect_show(move |_| {
+        while let Some(child) = saved_list.first_child() {
+            saved_list.remove(&child);
+        }
+        let saved = match SavedSearches::load() {
+            Ok(saved) => saved,
+            Err(e) => {
+                saved_list.append(&Label::new(Some(&e)));
+                return;
+            }
+        };
+        for name in saved.names() {
+            let Some(search) = saved.get(name).cloned() else {
+                continue;
+            };
+            let button = Button::with_label(name);
+            button.set_has_frame(false);
+            button.set_tooltip_text(Some(&search.pattern));
+            let popover_load = popover_list.clone();
+            let load_search = load_search.clone();
+            button.connect_clicked(move |_| {
+                popover_load.popdown();
+                load_search(&search);
+            });
+            saved_list.append(&button);
+        }
+        if saved_list.first_child().is_none() {
+            saved_list.append(&Label::new(Some("No saved searches")));
+        }
+    });
+    menu_button
+}
+
+/// An overview of the whole file beside the scrollbar: the marked lines in
+/// their colors on the left half, a tick wherever the whole-file search count
+/// found matches on the right half (more opaque where they are denser), and
+/// the lines on screen as a frame. Clicking or dragging scrolls to center
+/// the line under the pointer.
+fn build_overview(
+    v_adjustment: &Adjustment,
+    search_state: &Rc<RefCell<SearchState>>,
+    marked_lines: &Rc<RefCell<HashMap<usize, LineMarkings>>>,
+    view_filter: &Rc<RefCell<Option<Arc<FilteredLines>>>>,
+) -> gtk4::DrawingArea {
+    let area = gtk4::DrawingArea::new();
+    area.set_content_width(OVERVIEW_WIDTH);
+    area.set_vexpand(true);
+    area.set_cursor_from_name(Some("pointer"));
+
+    let adjustment = v_adjustment.clone();
+    let search_state = search_state.clone();
+    let marked_lines = marked_lines.clone();
+    let view_filter = view_filter.clone();
+    area.set_draw_func(move |_, cr, width, height| {
+        let total = adjustment.upper();
+        if total < 1.0 {
+            return;
+        }
+        let scale = height as f64 / total;
+        let half = width as f64 / 2.0;
+
+        // One tick per pixel row holding marks, in the color of the last one;
+        // marks on lines the filters leave out are not drawn
+        let filter = view_filter.borrow().clone();
+        let mut rows: HashMap<i32, gtk4::gdk::RGBA> = HashMap::new();
+        for (line, markings) in marked_lines.borrow().iter() {
+            let Some(rgba) = markings.color().and_then(|c| gtk4::gdk::RGBA::parse(c).ok()) else {
+                continue;
+            };
+            let Some(line) = filter.as_ref().map_or(Some(*line), |f| f.shown_at(*line)) else {
+                continue;
+            };
+            rows.insert((line as f64 * scale) as i32, rgba);
+        }
+        for (y, rgba) in rows {
+            cr.set_source_rgba(
+                rgba.red() as f64,
+                rgba.green() as f64,
+                rgba.blue() as f64,
+                rgba.alpha() as f64,
+            );
+            cr.rectangle(0.0, y as f64, half, 2.0);
+            let _ = cr.fill();
+        }
+
+        let state = search_state.borrow();
+        let busiest = state.match_density.max();
+        if state.is_active && busiest > 0 {
+            let (r, g, b) = MATCH_TICK_COLOR;
+            for (first_line, lines, matches) in state.match_density.spans() {
+                let y = (first_line as f64 * scale).floor();
+                let tick_height = (lines as f64 * scale).max(2.0);
+                cr.set_source_rgba(r, g, b, 0.4 + 0.6 * matches as f64 / busiest as f64);
+                cr.rectangle(half, y, half, tick_height);
+                let _ = cr.fill();
+            }
+        }
+        drop(state);
+
+        let (r, g, b, a) = VIEWPORT_FRAME_COLOR;
+        let y = (adjustment.value() * scale).floor();
+        let frame_height = (adjustment.page_size() * scale).max(4.0);
+        cr.set_source_rgba(r, g, b, a * 0.4);
+        cr.rectangle(0.0, y, width as f64, frame_height);
+        let _ = cr.fill();
+        cr.set_source_rgba(r, g, b, a);
+        cr.set_line_width(1.0);
+        cr.rectangle(0.5, y + 0.5, width as f64 - 1.0, frame_height - 1.0);
+        let _ = cr.stroke();
+    });
+
+    // Scroll so the line under the pointer is in the middle of the view
+    let adjustment = v_adjustment.clone();
+    let area_jump = area.clone();
+    let jump = move |y: f64| {
+        let height = area_jump.height().max(1) as f64;
+        let line = (y / height).clamp(0.0, 1.0) * adjustment.upper();
+        adjustment.set_value((line - adjustment.page_size() / 2.0).floor());
+    };
+    let drag = gtk4::GestureDrag::new();
+    let jump_begin = jump.clone();
+    drag.connect_drag_begin(move |_, _, y| jump_begin(y));
+    drag.connect_drag_update(move |gesture, _, dy| {
+        if let Some((_, start_y)) = gesture.start_point() {
+            jump(start_y + dy);
+        }
+    });
+    area.add_controller(drag);
+
+    // The line count changes as the file grows or is filtered, and the
+    // frame moves with the view
+    let area_redraw = area.clone();
+    v_adjustment.connect_changed(move |_| area_redraw.queue_draw());
+    let area_redraw = area.clone();
+    v_adjustment.connect_value_changed(move |_| area_redraw.queue_draw());
+    area
+}
+
+/// Wrap the content scroller in an overlay that draws vertical guides after
+/// the given (1-based) columns, following the horizontal scroll offset.
+fn build_column_guides(h_scroll: &ScrolledWindow, guides: &[usize]) -> Overlay {
+    let overlay = Overlay::new();
+    overlay.set_child(Some(h_scroll));
+    overlay.set_hexpand(true);
+    if guides.is_empty() {
+        return overlay;
+    }
+
+    let area = gtk4::DrawingArea::new();
+    area.set_can_target(false);
+    // Same font as the log lines so the measured cell width matches
+    area.set_css_classes(&["monospace"]);
+
+    let guides = guides.to_vec();
+    let h_adjustment = h_scroll.hadjustment();
+    area.set_draw_func(move |area, cr, _width, height| {
+        let layout = area.create_pango_layout(Some("0"));
+        let char_width = layout.pixel_size().0 as f64;
+        let offset = h_adjustment.value();
+        let (r, g, b, a) = COLUMN_GUIDE_COLOR;
+        cr.set_source_rgba(r, g, b, a);
+        cr.set_line_width(1.0);
+        for &col in &guides {
+            let x = (col as f64 * char_width - offset).floor() + 0.5;
+            cr.move_to(x, 0.0);
+            cr.line_to(x, height as f64);
+        }
+        let _ = cr.stroke();
+    });
+
+    let area_redraw = area.clone();
+    h_scroll
+        .hadjustment()
+        .connect_value_changed(move |_| area_redraw.queue_draw());
+    overlay.add_overlay(&area);
+    overlay
+}
+
+/// While wrapping, show only the lines whose rows fit in the page and page
+/// by that many lines. Near the end of the file, the scroll range grows so
+/// the view can scroll on until the last line's rows fit as well.
+fn fit_wrapped_page(
+    line_rows: &LineRows,
+    adjustment: &Adjustment,
+    rows: &[usize],
+    start: usize,
+    total_lines: usize,
+) {
+    let page_rows = page_lines(adjustment);
+    let shown = columns::lines_fitting(rows.iter().copied(), page_rows);
+    line_rows.hide_from(shown);
+    adjustment.set_page_increment(shown.max(1) as f64);
+    if !rows.is_empty() && start + rows.len() >= total_lines {
+        let last_page = columns::lines_fitting(rows.iter().rev().copied(), page_rows);
+        adjustment.set_upper((total_lines + page_rows).saturating_sub(last_page) as f64);
+    }
+}
+
+/// Whether the desktop asks for dark colors: a dark GTK theme, such as
+/// `Adwaita-dark`, or the dark variant of the current one.
+fn prefers_dark_theme(settings: &gtk4::Settings) -> bool {
+    settings.is_gtk_application_prefer_dark_theme()
+        || settings
+            .gtk_theme_name()
+            .is_some_and(|name| name.to_lowercase().ends_with("-dark"))
+}
+
+/// Lines of the view shown at once: the page size of its vertical adjustment.
+fn page_lines(adjustment: &Adjustment) -> usize {
+    adjustment.page_size() as usize
+}
+
+/// Lines fetched for a page: its lines, and the one below them that shows
+/// in part while scrolled part way into the top line.
+fn fetch_lines(adjustment: &Adjustment) -> usize {
+    page_lines(adjustment) + 1
+}
+
+/// Height of a row of the lines, measured on `widget` in their monospace
+/// font.
+fn row_height(widget: &impl IsA<gtk4::Widget>) -> i32 {
+    widget.create_pango_layout(Some("0")).pixel_size().1
+}
+
+/// Move `adjustment` by `delta`, keeping its page within its bounds.
+fn scroll_by(adjustment: &Adjustment, delta: f64) {
+    let max = (adjustment.upper() - adjustment.page_size()).max(adjustment.lower());
+    adjustment.set_value((adjustment.value() + delta).clamp(adjustment.lower(), max));
+}
+
+/// Whether the last line is on screen: the view cannot scroll further down.
+fn at_end(adjustment: &Adjustment) -> bool {
+    adjustment.value() + adjustment.page_size() >= adjustment.upper() - 0.5
+}
+
+/// The `fold` command's answer: `off`, or the fold mode and how many runs
+/// were expanded.
+fn describe_fold(fold: Option<&LineFold>) -> String {
+    match fold {
+        None => "off".to_string(),
+        Some(fold) if fold.expanded.is_empty() => fold.mode.to_string(),
+        Some(fold) => format!("{} {} expanded", fold.mode, fold.expanded.len()),
+    }
+}
+
+/// "1 new line", "243 new lines"
+fn describe_new_lines(count: usize) -> String {
+    format!("{} new line{}", count, if count == 1 { "" } else { "s" })
+}
+
+/// "812 B", "4.0 GB": a file size in the title bar, in powers of 1000.
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "kB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
 }
 
 #[allow(dead_code)]
@@ -1010,15 +5162,14 @@ fn apply_markings(text: &str, markings: &LineMarkings) -> String {
 
     // Full line color applies to all characters first (as background)
     if let Some(ref color) = markings.full_line_color {
-        for slot in &mut char_colors {
-            *slot = Some(color.as_str());
-        }
+        char_colors.fill(Some(color.as_str()));
     }
 
     // Region colors override (regions are sorted by start_col)
     for region in &markings.regions {
-        for i in region.start_col..region.end_col.min(chars.len()) {
-            char_colors[i] = Some(&region.color);
+        let end = region.end_col.min(chars.len());
+        if let Some(slots) = char_colors.get_mut(region.start_col..end) {
+            slots.fill(Some(&region.color));
         }
     }
 
@@ -1052,10 +5203,19 @@ fn apply_markings(text: &str, markings: &LineMarkings) -> String {
     result
 }
 
+#[allow(clippy::too_many_arguments)]
 fn apply_all_markings(
     text: &str,
     manual_markings: Option<&LineMarkings>,
+    line_num: usize,
     search_matches: &[&SearchMatch],
+    highlights: &[HighlightRule],
+    color_rules: &[ColorRule],
+    level_span: Option<(usize, usize, &str)>,
+    field_styles: &[FieldStyle],
+    key_ranges: &[(usize, usize)],
+    table_cells: Option<&[TableCell]>,
+    theme: Theme,
 ) -> String {
     let chars: Vec<char> = text.chars().collect();
     if chars.is_empty() {
@@ -1064,11 +5224,53 @@ fn apply_all_markings(
 
     // Build character-level color map with priority:
     // 1. Manual region marks (highest - user explicit)
-    // 2. Search highlights (middle)
-    // 3. Manual full-line color (lowest - background)
+    // 2. Search highlights, capture groups over the rest of their match
+    // 3. Highlight rules, later rules over earlier ones
+    // 4. Manual full-line color
+    // 5. Rules of the rules file, later rules over earlier ones
+    // 6. Severity color of the line or its level word
+    // 7. Fixed-width field colors (lowest - configured background)
+    // Text styles only come from the rules file, and add up. Keys of logfmt
+    // lines get the theme's key color as their text color where they have
+    // no background.
     let mut char_colors: Vec<Option<String>> = vec![None; chars.len()];
+    let mut char_styles: Vec<Option<TextStyle>> = vec![None; chars.len()];
+    let mut char_keys = vec![false; chars.len()];
+    for &(start_col, end_col) in key_ranges {
+        let end = end_col.min(chars.len());
+        for slot in char_keys.iter_mut().take(end).skip(start_col) {
+            *slot = true;
+        }
+    }
 
-    // Full line color applies to all characters first (as background)
+    for field in field_styles {
+        let end = field.end_col.min(chars.len());
+        for slot in char_colors.iter_mut().take(end).skip(field.start_col) {
+            *slot = Some(field.color.clone());
+        }
+    }
+
+    if let Some((start_col, end_col, color)) = level_span {
+        let end = end_col.min(chars.len());
+        for slot in char_colors.iter_mut().take(end).skip(start_col) {
+            *slot = Some(color.to_string());
+        }
+    }
+
+    for rule in color_rules {
+        for (start_col, end_col) in rule.ranges(text) {
+            let end = end_col.min(chars.len());
+            let slots = char_colors.iter_mut().zip(char_styles.iter_mut());
+            for (color_slot, style_slot) in slots.take(end).skip(start_col) {
+                if let Some(color) = &rule.color {
+                    *color_slot = Some(color.clone());
+                }
+                *style_slot = Some(style_slot.unwrap_or_default().union(rule.style));
+            }
+        }
+    }
+
+    // Full line color applies to all characters (as background)
     if let Some(markings) = manual_markings {
         if let Some(ref color) = markings.full_line_color {
             for slot in &mut char_colors {
@@ -1077,40 +5279,83 @@ fn apply_all_markings(
         }
     }
 
+    for rule in highlights {
+        for (start_col, end_col) in rule.ranges(text) {
+            let end = end_col.min(chars.len());
+            for slot in char_colors.iter_mut().take(end).skip(start_col) {
+                *slot = Some(rule.color.clone());
+            }
+        }
+    }
+
     // Apply search highlights
-    for search_match in search_matches {
-        for i in search_match.start_col..search_match.end_col.min(chars.len()) {
-            char_colors[i] = Some(SEARCH_HIGHLIGHT_COLOR.to_string());
+    for &search_match in search_matches {
+        let mut spans = vec![(search_match, theme.search_color())];
+        let group_colors = theme.search_group_colors().iter().cycle();
+        for (group, &color) in search_match.groups.iter().zip(group_colors) {
+            if let Some(group) = group {
+                spans.push((group, color));
+            }
+        }
+        for (span, color) in spans {
+            if let Some((start_col, end_col)) = span.columns_on(line_num) {
+                let end = end_col.min(chars.len());
+                for slot in char_colors.iter_mut().take(end).skip(start_col) {
+                    *slot = Some(color.to_string());
+                }
+            }
         }
     }
 
     // Manual region marks override search highlights
     if let Some(markings) = manual_markings {
         for region in &markings.regions {
-            for i in region.start_col..region.end_col.min(chars.len()) {
-                char_colors[i] = Some(region.color.clone());
+            let end = region.end_col.min(chars.len());
+            for slot in char_colors.iter_mut().take(end).skip(region.start_col) {
+                *slot = Some(region.color.clone());
             }
         }
     }
 
-    // Generate markup by grouping consecutive characters with same color
+    // Keep combining marks in the same span as their base character so
+    // Arabic/Hebrew shaping is not broken at highlight boundaries
+    bidi::snap_to_clusters(&chars, &mut char_colors);
+    bidi::snap_to_clusters(&chars, &mut char_styles);
+
+    // In the column view, the line's characters move to their cells and
+    // keep their colors; padding and separators stay plain
+    let (chars, char_colors, char_styles, char_keys) = match table_cells {
+        Some(cells) => (
+            cells.iter().map(|cell| cell.ch).collect(),
+            cells.iter().map(|cell| cell.source_col.and_then(|col| char_colors[col].clone())).collect(),
+            cells.iter().map(|cell| cell.source_col.and_then(|col| char_styles[col])).collect(),
+            cells.iter().map(|cell| cell.source_col.is_some_and(|col| char_keys[col])).collect(),
+        ),
+        None => (chars, char_colors, char_styles, char_keys),
+    };
+
+    // Generate markup by grouping consecutive characters with same color and style
     let mut result = String::new();
     let mut i = 0;
     while i < chars.len() {
         let current_color = &char_colors[i];
+        let current_style = char_styles[i].unwrap_or_default();
+        let current_key = char_keys[i];
         let mut end = i + 1;
-        while end < chars.len() && char_colors[end] == *current_color {
+        while end < chars.len()
+            && char_colors[end] == *current_color
+            && char_styles[end].unwrap_or_default() == current_style
+            && char_keys[end] == current_key
+        {
             end += 1;
         }
 
         let segment: String = chars[i..end].iter().collect();
         let escaped = glib::markup_escape_text(&segment);
 
-        if let Some(color) = current_color {
-            result.push_str(&format!(
-                "<span background=\"{}\">",
-                glib::markup_escape_text(color)
-            ));
+        let key_color = (current_key && current_color.is_none()).then(|| theme.key_color());
+        if current_color.is_some() || key_color.is_some() || !current_style.is_plain() {
+            result.push_str(&span_start(current_color.as_deref(), key_color, current_style));
             result.push_str(&escaped);
             result.push_str("</span>");
         } else {
@@ -1123,52 +5368,718 @@ fn apply_all_markings(
     result
 }
 
+/// Markup starting a span with `color` as its background, and text in black
+/// or white, whichever reads on it: marks and highlights stay readable
+/// whatever the theme's own text color is. Without a background, the text
+/// is in `foreground` if given. `style` adds the bold, italic or underline
+/// of the rules file.
+fn span_start(color: Option<&str>, foreground: Option<&str>, style: TextStyle) -> String {
+    let mut span = String::from("<span");
+    if let (None, Some(foreground)) = (color, foreground) {
+        span.push_str(&format!(" foreground=\"{}\"", foreground));
+    }
+    if let Some(color) = color {
+        span.push_str(&format!(" background=\"{}\"", glib::markup_escape_text(color)));
+        if let Ok(rgba) = gtk4::gdk::RGBA::parse(color) {
+            let foreground = theme::contrast_foreground(
+                rgba.red() as f64,
+                rgba.green() as f64,
+                rgba.blue() as f64,
+            );
+            span.push_str(&format!(" foreground=\"{}\"", foreground));
+        }
+    }
+    span.push_str(&style.markup_attributes());
+    span.push('>');
+    span
+}
+
+/// The highlight rules dialog: lists the rules of a view with their colors,
+/// removes them, and adds new ones with the next unused color of the
+/// theme's `palette` suggested.
+fn show_highlight_dialog(
+    parent: &ApplicationWindow,
+    highlights: &Rc<RefCell<Vec<HighlightRule>>>,
+    redraw: &Rc<dyn Fn()>,
+    palette: &'static [&'static str],
+) {
+    let dialog = gtk4::Window::builder()
+        .title("Highlights")
+        .transient_for(parent)
+        .modal(true)
+        .default_width(460)
+        .build();
+
+    let rows = GtkBox::new(Orientation::Vertical, 4);
+    for rule in highlights.borrow().iter() {
+        append_highlight_row(&rows, rule, highlights, redraw);
+    }
+
+    let pattern_entry = Entry::new();
+    pattern_entry.set_placeholder_text(Some("Regex..."));
+    pattern_entry.set_hexpand(true);
+    let color_entry = Entry::new();
+    color_entry.set_placeholder_text(Some("Color"));
+    color_entry.set_width_chars(10);
+    color_entry.set_text(highlight::next_color(&highlights.borrow(), palette));
+    let add_button = Button::with_label("Add");
+    let error_label = Label::new(None);
+    error_label.set_halign(gtk4::Align::Start);
+
+    let add_box = GtkBox::new(Orientation::Horizontal, 6);
+    add_box.append(&pattern_entry);
+    add_box.append(&color_entry);
+    add_box.append(&add_button);
+
+    let content = GtkBox::new(Orientation::Vertical, 8);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    content.append(&rows);
+    content.append(&add_box);
+    content.append(&error_label);
+    dialog.set_child(Some(&content));
+
+    let highlights = highlights.clone();
+    let redraw = redraw.clone();
+    let pattern_entry_add = pattern_entry.clone();
+    let add = move || {
+        let pattern = pattern_entry_add.text().to_string();
+        if pattern.is_empty() {
+            return;
+        }
+        let color = color_entry.text().to_string();
+        if gtk4::gdk::RGBA::parse(&color).is_err() {
+            error_label.set_text(&format!("invalid color: {}", color));
+            return;
+        }
+        match HighlightRule::new(&color, &pattern) {
+            Ok(rule) => {
+                append_highlight_row(&rows, &rule, &highlights, &redraw);
+                highlights.borrow_mut().push(rule);
+                redraw();
+                pattern_entry_add.set_text("");
+                color_entry.set_text(highlight::next_color(&highlights.borrow(), palette));
+                error_label.set_text("");
+            }
+            Err(e) => error_label.set_text(&e),
+        }
+    };
+    let add = Rc::new(add);
+    let add_clicked = add.clone();
+    add_button.connect_clicked(move |_| add_clicked());
+    pattern_entry.connect_activate(move |_| add());
+
+    dialog.present();
+}
+
+/// The Ctrl+T dialog: an entry taking a time as `goto-time` does, or a
+/// percentage as `goto` does, jumping there and closing, or saying why not.
+fn show_goto_dialog(
+    parent: &ApplicationWindow,
+    goto_time: &Rc<dyn Fn(Timestamp) -> Result<usize, String>>,
+    goto_percent: &Rc<dyn Fn(f64, PercentOf) -> Result<usize, String>>,
+) {
+    let dialog = gtk4::Window::builder()
+        .title("Go to")
+        .transient_for(parent)
+        .modal(true)
+        .default_width(320)
+        .build();
+
+    let time_entry = Entry::new();
+    time_entry.set_placeholder_text(Some("2024-05-01 14:32, 50% or 50% bytes"));
+    let error_label = Label::new(None);
+    error_label.set_halign(gtk4::Align::Start);
+
+    let content = GtkBox::new(Orientation::Vertical, 8);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    content.append(&time_entry);
+    content.append(&error_label);
+    dialog.set_child(Some(&content));
+
+    let goto_time = goto_time.clone();
+    let goto_percent = goto_percent.clone();
+    let dialog_goto = dialog.clone();
+    time_entry.connect_activate(move |entry| {
+        let text = entry.text();
+        let result = if text.contains('%') {
+            parse_goto_percent(&text).and_then(|(percent, of)| goto_percent(percent, of))
+        } else {
+            timestamp::parse_time_bound(&text).and_then(|time| goto_time(time))
+        };
+        match result {
+            Ok(_) => dialog_goto.close(),
+            Err(e) => error_label.set_text(&e),
+        }
+    });
+
+    dialog.present();
+}
+
+/// A drop-down of `names`, with the one `parse` reads as `current` selected.
+fn choice_drop_down<T: PartialEq>(
+    names: &'static [&'static str],
+    parse: fn(&str) -> Result<T, String>,
+    current: T,
+) -> gtk4::DropDown {
+    let drop_down = gtk4::DropDown::from_strings(names);
+    let selected = names.iter().position(|name| parse(name).as_ref() == Ok(&current)).unwrap_or(0);
+    drop_down.set_selected(selected as u32);
+    drop_down
+}
+
+/// Ctrl+,: the common settings of the config file, with unset ones showing
+/// their defaults. Saving writes them back, leaving the file's other lines
+/// alone, and calls `on_saved`.
+fn show_preferences_dialog(parent: &ApplicationWindow, on_saved: impl Fn() + 'static) {
+    const THEMES: &[&str] = &["system", "light", "dark"];
+    const CASE_MODES: &[&str] = &["sensitive", "insensitive", "smart"];
+    const LEVEL_STYLES: &[&str] = &["line", "word", "off"];
+
+    let dialog = gtk4::Window::builder()
+        .title("Preferences")
+        .transient_for(parent)
+        .modal(true)
+        .default_width(420)
+        .build();
+    let error_label = Label::new(None);
+    error_label.set_halign(gtk4::Align::Start);
+    let config = Config::load().unwrap_or_else(|e| {
+        error_label.set_text(&e);
+        Config::default()
+    });
+
+    let theme = choice_drop_down(THEMES, ThemeChoice::parse, config.theme.unwrap_or_default());
+    let font_family = Entry::new();
+    font_family.set_text(&config.font_family.unwrap_or_else(|| FontSettings::default().family));
+    let font_size =
+        gtk4::SpinButton::with_range(font::MIN_FONT_SIZE as f64, font::MAX_FONT_SIZE as f64, 1.0);
+    font_size.set_value(config.font_size.unwrap_or(font::DEFAULT_FONT_SIZE) as f64);
+    let search_case =
+        choice_drop_down(CASE_MODES, CaseMode::parse, config.search_case.unwrap_or_default());
+    let levels = choice_drop_down(LEVEL_STYLES, LevelStyle::parse, config.levels.unwrap_or_default());
+    let port = gtk4::SpinButton::with_range(1.0, u16::MAX as f64, 1.0);
+    port.set_value(config.port.unwrap_or(DEFAULT_PORT) as f64);
+    let search_wrap = gtk4::CheckButton::with_label("Wrap searches around the file's ends");
+    search_wrap.set_active(config.search_wrap.unwrap_or(false));
+    let logfmt = gtk4::CheckButton::with_label("Color the keys of key=value lines");
+    logfmt.set_active(config.logfmt.unwrap_or(true));
+    let notify_new_lines = gtk4::CheckButton::with_label("Announce new lines at the end of the file");
+    notify_new_lines.set_active(config.notify_new_lines.unwrap_or(true));
+
+    let grid = gtk4::Grid::new();
+    grid.set_row_spacing(6);
+    grid.set_column_spacing(12);
+    let rows: [(&str, &gtk4::Widget); 6] = [
+        ("Theme", theme.upcast_ref()),
+        ("Font", font_family.upcast_ref()),
+        ("Font size", font_size.upcast_ref()),
+        ("Search case", search_case.upcast_ref()),
+        ("Level colors", levels.upcast_ref()),
+        ("Command port", port.upcast_ref()),
+    ];
+    for (row, (title, widget)) in rows.into_iter().enumerate() {
+        let label = Label::new(Some(title));
+        label.set_halign(gtk4::Align::Start);
+        widget.set_hexpand(true);
+        grid.attach(&label, 0, row as i32, 1, 1);
+        grid.attach(widget, 1, row as i32, 1, 1);
+    }
+
+    let cancel_button = Button::with_label("Cancel");
+    let save_button = Button::with_label("Save");
+    let buttons = GtkBox::new(Orientation::Horizontal, 6);
+    buttons.set_halign(gtk4::Align::End);
+    buttons.append(&cancel_button);
+    buttons.append(&save_button);
+
+    let content = GtkBox::new(Orientation::Vertical, 8);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    content.append(&grid);
+    content.append(&search_wrap);
+    content.append(&logfmt);
+    content.append(&notify_new_lines);
+    content.append(&error_label);
+    content.append(&buttons);
+    dialog.set_child(Some(&content));
+
+    let dialog_cancel = dialog.clone();
+    cancel_button.connect_clicked(move |_| dialog_cancel.close());
+
+    let dialog_save = dialog.clone();
+    save_button.connect_clicked(move |_| {
+        let settings = [
+            ("theme", THEMES[theme.selected() as usize].to_string()),
+            ("font_family", font_family.text().trim().to_string()),
+            ("font_size", font_size.value_as_int().to_string()),
+            ("search_case", CASE_MODES[search_case.selected() as usize].to_string()),
+            ("search_wrap", search_wrap.is_active().to_string()),
+            ("levels", LEVEL_STYLES[levels.selected() as usize].to_string()),
+            ("logfmt", logfmt.is_active().to_string()),
+            ("notify_new_lines", notify_new_lines.is_active().to_string()),
+            ("port", port.value_as_int().to_string()),
+        ];
+        match Config::save(&settings) {
+            Ok(()) => {
+                on_saved();
+                dialog_save.close();
+            }
+            Err(e) => error_label.set_text(&e),
+        }
+    });
+
+    dialog.present();
+}
+
+/// Ctrl+Shift+C: type how many lines to copy, from the cursor line on.
+fn show_copy_lines_dialog(
+    parent: &ApplicationWindow,
+    copy_lines: &CopyLines,
+    cursor: usize,
+    total_lines: usize,
+    on_copied: impl Fn(usize) + 'static,
+) {
+    let dialog = gtk4::Window::builder()
+        .title(format!("Copy lines from line {}", cursor + 1))
+        .transient_for(parent)
+        .modal(true)
+        .default_width(320)
+        .build();
+
+    let count_entry = Entry::new();
+    count_entry.set_placeholder_text(Some("Number of lines to copy"));
+    let error_label = Label::new(None);
+    error_label.set_halign(gtk4::Align::Start);
+
+    let content = GtkBox::new(Orientation::Vertical, 8);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    content.append(&count_entry);
+    content.append(&error_label);
+    dialog.set_child(Some(&content));
+
+    let copy_lines = copy_lines.clone();
+    let dialog_copy = dialog.clone();
+    let on_copied = Rc::new(on_copied);
+    count_entry.connect_activate(move |entry| {
+        let count = match entry.text().trim().parse::<usize>() {
+            Ok(count) if count > 0 => count.min(total_lines.saturating_sub(cursor)),
+            _ => {
+                error_label.set_text("Type a number of lines");
+                return;
+            }
+        };
+        let copied = copy_lines(cursor, count);
+        let on_copied = on_copied.clone();
+        let dialog = dialog_copy.clone();
+        let error_label = error_label.clone();
+        glib::spawn_future_local(async move {
+            match copied.await {
+                Ok(copied) => {
+                    on_copied(copied);
+                    dialog.close();
+                }
+                Err(e) => error_label.set_text(&e),
+            }
+        });
+    });
+
+    dialog.present();
+}
+
+/// Ctrl+B: type a bookmark's name to jump to it, or a new name to bookmark
+/// the cursor line; the existing bookmarks are listed as buttons. Bookmarks
+/// hold file lines, found in the view through `filter`.
+fn show_bookmarks_dialog(
+    parent: &ApplicationWindow,
+    bookmarks: &Rc<RefCell<Bookmarks>>,
+    cursor_position: &Rc<RefCell<usize>>,
+    v_adjustment: &Adjustment,
+    filter: Option<Arc<FilteredLines>>,
+) {
+    let dialog = gtk4::Window::builder()
+        .title("Bookmarks")
+        .transient_for(parent)
+        .modal(true)
+        .default_width(320)
+        .build();
+
+    let name_entry = Entry::new();
+    name_entry.set_placeholder_text(Some("Name to jump to, or to bookmark the cursor line"));
+    let error_label = Label::new(None);
+    error_label.set_halign(gtk4::Align::Start);
+
+    let content = GtkBox::new(Orientation::Vertical, 8);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    content.append(&name_entry);
+    content.append(&error_label);
+
+    let jump = {
+        let cursor_position = cursor_position.clone();
+        let v_adjustment = v_adjustment.clone();
+        let dialog = dialog.clone();
+        let filter = filter.clone();
+        Rc::new(move |file_line: usize| {
+            let line = filter.as_ref().map_or(file_line, |f| f.view_line(file_line));
+            v_adjustment.set_value(line as f64);
+            *cursor_position.borrow_mut() = line;
+            dialog.close();
+        })
+    };
+    for (name, line) in bookmarks.borrow().iter() {
+        let button = Button::with_label(&format!("{}  (line {})", name, line + 1));
+        let jump = jump.clone();
+        let line = *line;
+        button.connect_clicked(move |_| jump(line));
+        content.append(&button);
+    }
+    dialog.set_child(Some(&content));
+
+    let bookmarks = bookmarks.clone();
+    let cursor_position = cursor_position.clone();
+    let dialog_set = dialog.clone();
+    name_entry.connect_activate(move |entry| {
+        let name = entry.text().trim().to_string();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            error_label.set_text("Bookmark names are one word");
+            return;
+        }
+        let existing = bookmarks.borrow().get(&name).copied();
+        match existing {
+            Some(line) => jump(line),
+            None => {
+                let line = *cursor_position.borrow();
+                if let Some(line) = filter.as_ref().map_or(Some(line), |f| f.source_line(line)) {
+                    bookmarks.borrow_mut().insert(name, line);
+                }
+                dialog_set.close();
+            }
+        }
+    });
+
+    dialog.present();
+}
+
+/// One rule in the highlight dialog: a color swatch, the pattern and a
+/// button removing the rule.
+fn append_highlight_row(
+    rows: &GtkBox,
+    rule: &HighlightRule,
+    highlights: &Rc<RefCell<Vec<HighlightRule>>>,
+    redraw: &Rc<dyn Fn()>,
+) {
+    let row = GtkBox::new(Orientation::Horizontal, 8);
+    let swatch = Label::new(None);
+    swatch.set_markup(&format!(
+        "<span background=\"{}\">      </span>",
+        glib::markup_escape_text(&rule.color)
+    ));
+    let pattern = Label::new(Some(&rule.pattern));
+    pattern.set_hexpand(true);
+    pattern.set_halign(gtk4::Align::Start);
+    pattern.set_css_classes(&["monospace"]);
+    let remove_button = Button::with_label("x");
+    remove_button.set_css_classes(&["search-close"]);
+    row.append(&swatch);
+    row.append(&pattern);
+    row.append(&remove_button);
+    rows.append(&row);
+
+    let rows = rows.clone();
+    let highlights = highlights.clone();
+    let redraw = redraw.clone();
+    let (color, pattern) = (rule.color.clone(), rule.pattern.clone());
+    remove_button.connect_clicked(move |_| {
+        let mut rules = highlights.borrow_mut();
+        if let Some(i) = rules.iter().position(|r| r.color == color && r.pattern == pattern) {
+            rules.remove(i);
+        }
+        drop(rules);
+        rows.remove(&row);
+        redraw();
+    });
+}
+
+/// One marked line in the marks panel: a button with its color swatch,
+/// number and text that jumps to it, and a button sending it to unmark.
+fn append_mark_row(
+    rows: &GtkBox,
+    line: usize,
+    color: &str,
+    text: &str,
+    jump: &Rc<dyn Fn(usize)>,
+    remove_tx: &async_channel::Sender<usize>,
+) {
+    let row = GtkBox::new(Orientation::Horizontal, 4);
+    let swatch = Label::new(None);
+    swatch.set_markup(&format!(
+        "<span background=\"{}\">   </span>",
+        glib::markup_escape_text(color)
+    ));
+    let number = Label::new(Some(&(line + 1).to_string()));
+    number.set_css_classes(&["monospace", "line-number"]);
+    let snippet: String = text.chars().take(MARK_SNIPPET_CHARS).collect();
+    let snippet = Label::new(Some(&snippet));
+    snippet.set_halign(gtk4::Align::Start);
+    snippet.set_hexpand(true);
+    snippet.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+    snippet.set_max_width_chars(1);
+    snippet.set_css_classes(&["monospace"]);
+    let label = GtkBox::new(Orientation::Horizontal, 8);
+    label.append(&swatch);
+    label.append(&number);
+    label.append(&snippet);
+    let jump_button = Button::builder().child(&label).has_frame(false).hexpand(true).build();
+    jump_button.set_tooltip_text(Some(&format!("Go to line {}", line + 1)));
+    let remove_button = Button::with_label("x");
+    remove_button.set_tooltip_text(Some("Unmark"));
+    remove_button.set_css_classes(&["search-close"]);
+    row.append(&jump_button);
+    row.append(&remove_button);
+    rows.append(&row);
+
+    let jump = jump.clone();
+    jump_button.connect_clicked(move |_| jump(line));
+    let remove_tx = remove_tx.clone();
+    remove_button.connect_clicked(move |_| {
+        let _ = remove_tx.send_blocking(line);
+    });
+}
+
+/// A row of the pinned strip showing file line `line`: its number in a
+/// column as wide as the sidebar, its text on one row, and an `x` to unpin it.
+fn append_pinned_row(
+    rows: &GtkBox,
+    line: usize,
+    text: &str,
+    jump: &Rc<dyn Fn(usize)>,
+    remove_tx: &async_channel::Sender<usize>,
+) {
+    let row = GtkBox::new(Orientation::Horizontal, 4);
+    let number = Label::new(Some(&(line + 1).to_string()));
+    number.set_width_request(80);
+    number.set_xalign(1.0);
+    number.set_css_classes(&["monospace", "line-number"]);
+    let snippet: String = text.chars().take(MARK_SNIPPET_CHARS).collect();
+    let snippet = Label::new(Some(&snippet));
+    snippet.set_halign(gtk4::Align::Start);
+    snippet.set_hexpand(true);
+    snippet.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+    snippet.set_max_width_chars(1);
+    snippet.set_css_classes(&["monospace"]);
+    let label = GtkBox::new(Orientation::Horizontal, 8);
+    label.append(&number);
+    label.append(&snippet);
+    let jump_button = Button::builder().child(&label).has_frame(false).hexpand(true).build();
+    jump_button.set_tooltip_text(Some(&format!("Go to line {}", line + 1)));
+    let remove_button = Button::with_label("x");
+    remove_button.set_tooltip_text(Some("Unpin"));
+    remove_button.set_css_classes(&["search-close"]);
+    row.append(&jump_button);
+    row.append(&remove_button);
+    rows.append(&row);
+
+    let jump = jump.clone();
+    jump_button.connect_clicked(move |_| jump(line));
+    let remove_tx = remove_tx.clone();
+    remove_button.connect_clicked(move |_| {
+        let _ = remove_tx.send_blocking(line);
+    });
+}
+
+/// The `table` status and reply: `off`, or the delimiter, then the hidden
+/// columns.
+fn describe_table(table: Option<&TableLayout>) -> String {
+    let Some(table) = table else {
+        return "off".to_string();
+    };
+    let hidden: Vec<String> = table.hidden().map(|column| (column + 1).to_string()).collect();
+    if hidden.is_empty() {
+        table.delimiter.to_string()
+    } else {
+        format!("{} hidden {}", table.delimiter, hidden.join(","))
+    }
+}
+
+/// The `logfmt` status and reply: `on` or `off`, then the hidden keys.
+fn describe_logfmt(logfmt: &LogfmtView) -> String {
+    let state = if logfmt.enabled { "on" } else { "off" };
+    if logfmt.hidden_keys().is_empty() {
+        state.to_string()
+    } else {
+        format!("{} hidden {}", state, logfmt.hidden_keys().join(","))
+    }
+}
+
+/// Show `lines` in the rows of the view, rebinding the labels already there.
+/// While wrapping, returns the rows each line takes.
+#[allow(clippy::too_many_arguments)]
 fn populate_lines(
-    line_numbers_box: &GtkBox,
-    content_box: &GtkBox,
+    line_rows: &LineRows,
+    source: &dyn FileSource,
     lines: &[(usize, String)],
     marked_lines: &HashMap<usize, LineMarkings>,
+    annotations: &Annotations,
     search_state: &SearchState,
-) {
-    // Clear both boxes
-    while let Some(child) = line_numbers_box.first_child() {
-        line_numbers_box.remove(&child);
+    field_styles: &[FieldStyle],
+    highlights: &[HighlightRule],
+    color_rules: &[ColorRule],
+    level_colors: &LevelColors,
+    level_style: LevelStyle,
+    time_column: Option<Option<Timestamp>>,
+    mut table: Option<&mut TableLayout>,
+    logfmt: &LogfmtView,
+    filter: Option<&FilteredLines>,
+    hex_dumps: &[usize],
+    theme: Theme,
+    cursor: usize,
+) -> Option<Vec<usize>> {
+    // Columns widen to the page's values before any line is laid out, so
+    // the page's rows line up
+    if let Some(table) = table.as_deref_mut() {
+        table.fit(lines.iter().map(|(_, text)| text.as_str()));
     }
-    while let Some(child) = content_box.first_child() {
-        content_box.remove(&child);
-    }
-
-    // Add lines
-    for (line_num, text) in lines {
-        // Line number label (sidebar)
-        let num_label = Label::new(Some(&format!("{:>8}", line_num + 1)));
-        num_label.set_halign(gtk4::Align::End);
-        num_label.set_css_classes(&["monospace", "line-number"]);
-        line_numbers_box.append(&num_label);
+    let table = table.as_deref();
+    let long_lines = &line_rows.long_lines;
+    let wrap_columns = line_rows.wrap_columns();
+    let mut wrapped_rows = Vec::new();
+    // With the time column, the stamp of the last stamped line so far,
+    // starting from the one the worker found above the page
+    let mut previous_time = time_column.flatten();
+    for (index, (line_num, text)) in lines.iter().enumerate() {
+        let row = line_rows.row(index);
+        row.line_num.set(*line_num);
 
-        // Collect search matches for this line
-        let search_matches: Vec<&SearchMatch> = if search_state.is_active {
+        // Search matches on this line, including multiline matches that
+        // started above it. Their columns are the text's, not a hex dump's
+        let search_matches: Vec<&SearchMatch> = if search_state.is_active
+            && !hex_dumps.contains(line_num)
+        {
             search_state.viewport_matches
                 .iter()
-                .filter(|m| m.line_num == *line_num)
+                .filter(|m| m.columns_on(*line_num).is_some())
                 .collect()
         } else {
             Vec::new()
         };
 
-        // Content label with combined markings
-        let display_text = apply_all_markings(text, marked_lines.get(line_num), &search_matches);
+        // Very long lines are cut short unless expanded; the link after the
+        // text toggles between the two
+        let truncated = columns::truncate_line(text, long_lines.max_columns);
+        let expanded = long_lines.expanded.borrow().contains(line_num);
+        let shown = match truncated {
+            Some((head, _)) if !expanded => head,
+            _ => text.as_str(),
+        };
+
+        // Content label with combined markings, laid out in columns in the
+        // column view, or without the pairs of hidden logfmt keys
+        let cells = match table {
+            Some(table) => Some(table.render(shown)),
+            None => logfmt.render(shown),
+        };
+        // Marks and annotations are kept by the line's number in the file
+        let file_line = filter.and_then(|f| f.source_line(*line_num)).unwrap_or(*line_num);
+        let mut display_text = apply_all_markings(
+            shown,
+            marked_lines.get(&file_line),
+            *line_num,
+            &search_matches,
+            highlights,
+            color_rules,
+            level_colors.span(shown, level_style, theme),
+            field_styles,
+            &logfmt.key_ranges(shown),
+            cells.as_deref(),
+            theme,
+        );
+        let link = match truncated {
+            Some((_, hidden)) if !expanded => Some(("expand", format!("… +{} chars", hidden))),
+            Some(_) => Some(("collapse", "(collapse)".to_string())),
+            None => None,
+        };
+        // A folded run of repeats is counted after its first line, and the
+        // lines hidden after a line (or before the first one) after it
+        let repeats = filter.and_then(|f| f.repeats(*line_num));
+        let hidden = filter.and_then(|f| f.hidden(*line_num));
+        let links: Vec<(&str, String)> = link
+            .into_iter()
+            .chain(repeats.map(|n| ("unfold", format!("×{}", n))))
+            .chain(hidden.map(|hidden| ("unhide", format!("⋯ {} hidden", hidden.count))))
+            .collect();
+        for (href, text) in &links {
+            display_text.push_str(&format!(" <a href=\"{}\">{}</a>", href, text));
+        }
+
+        // A wrapped line's number is padded to as many rows as its text takes
+        let rows = wrap_columns.map_or(1, |columns| {
+            let link_chars: usize = links.iter().map(|(_, text)| text.chars().count() + 1).sum();
+            let chars = cells.as_ref().map_or_else(|| shown.chars().count(), Vec::len);
+            columns::wrapped_rows(chars + link_chars, columns)
+        });
+        wrapped_rows.push(rows);
+        let padding = "\n".repeat(rows - 1);
 
-        let label = Label::new(None);
+        // Line number label (sidebar): the line's number in the file, also
+        // when filters renumber the view
+        let time_text = match time_column {
+            Some(_) => {
+                let time = timestamp::parse_timestamp(text);
+                let delta = time
+                    .zip(previous_time)
+                    .map(|(time, previous)| timestamp::format_delta(time - previous));
+                previous_time = time.or(previous_time);
+                format!("{:>8} ", delta.unwrap_or_default())
+            }
+            None => String::new(),
+        };
+        // The number itself is set by `show_cursor` below
+        row.number.set_css_classes(&["monospace", "line-number"]);
+        // Lines of merged files are tagged with their file's color, and its
+        // name shows on hover unless the line has a note
+        let origin = source.line_origin(file_line);
+        let palette = theme.highlight_palette();
+        let origin_color = origin.map(|(part, _)| palette[part % palette.len()]);
+        let prefix = match annotations.get(&file_line) {
+            Some(note) => {
+                row.number.add_css_class("annotated");
+                row.number.set_tooltip_text(Some(note));
+                format!("{}✎ ", time_text)
+            }
+            None => {
+                row.number.set_tooltip_text(origin.map(|(_, name)| name));
+                time_text
+            }
+        };
+        *row.number_text.borrow_mut() = NumberText {
+            origin_color,
+            prefix,
+            file_line,
+            padding,
+        };
+
+        // Setting the text also turns markup off again
         if display_text.is_empty() {
-            label.set_text("");
+            row.content.set_text("");
         } else {
-            label.set_markup(&display_text);
-            label.set_use_markup(true);
+            row.content.set_markup(&display_text);
         }
-        label.set_halign(gtk4::Align::Start);
-        label.set_selectable(true);
-        label.set_css_classes(&["monospace"]);
-        content_box.append(&label);
+        row.content.set_css_classes(&["monospace", "line-content"]);
     }
+    line_rows.hide_from(lines.len());
+    line_rows.show_cursor(cursor);
+    wrap_columns.map(|_| wrapped_rows)
 }
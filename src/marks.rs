@@ -0,0 +1,327 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use crate::json::{self, Json};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub start_col: usize, // 0-based
+    pub end_col: usize,   // exclusive
+    pub color: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LineMarkings {
+    pub full_line_color: Option<String>,
+    pub regions: Vec<Region>,
+}
+
+impl LineMarkings {
+    pub fn is_empty(&self) -> bool {
+        self.full_line_color.is_none() && self.regions.is_empty()
+    }
+
+    /// The color the line is listed with: its full-line color, or that of
+    /// its first region.
+    pub fn color(&self) -> Option<&str> {
+        self.full_line_color
+            .as_deref()
+            .or_else(|| self.regions.first().map(|r| r.color.as_str()))
+    }
+
+    /// Mark columns `start_col..end_col`, replacing the regions it overlaps.
+    pub fn add_region(&mut self, start_col: usize, end_col: usize, color: String) {
+        self.regions
+            .retain(|r| r.end_col <= start_col || r.start_col >= end_col);
+        self.regions.push(Region {
+            start_col,
+            end_col,
+            color,
+        });
+        self.regions.sort_by_key(|r| r.start_col);
+    }
+
+    /// Remove the full-line mark and regions of `color`, returning whether
+    /// any were marked with it.
+    pub fn remove_color(&mut self, color: &str) -> bool {
+        let before = (self.full_line_color.is_some(), self.regions.len());
+        if self
+            .full_line_color
+            .as_ref()
+            .is_some_and(|c| c.eq_ignore_ascii_case(color))
+        {
+            self.full_line_color = None;
+        }
+        self.regions
+            .retain(|r| !r.color.eq_ignore_ascii_case(color));
+        (self.full_line_color.is_some(), self.regions.len()) != before
+    }
+}
+
+/// Marks of a view by file line (0-based), whatever the view's filters.
+pub type Marks = HashMap<usize, LineMarkings>;
+
+/// Notes attached to file lines (0-based) with `annotate`.
+pub type Annotations = BTreeMap<usize, String>;
+
+/// Named file lines (0-based) set with `bookmark set`.
+pub type Bookmarks = BTreeMap<String, usize>;
+
+/// What a marks file holds.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MarksFile {
+    pub marks: Marks,
+    pub annotations: Annotations,
+    pub bookmarks: Bookmarks,
+}
+
+/// Write `contents` to a JSON file, so a later session can load them again.
+/// `file` names the viewed file, for whoever reads the JSON.
+pub fn save(path: &Path, contents: &MarksFile, file: &str) -> Result<(), String> {
+    std::fs::write(path, to_json(contents, file)).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub fn load(path: &Path) -> Result<MarksFile, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// The marks as JSON, one line per marked or annotated line in line order:
+///
+/// ```json
+/// {
+///   "file": "app.log",
+///   "marks": [
+///     {"line": 42, "color": "red"},
+///     {"line": 50, "regions": [{"start": 10, "end": 20, "color": "yellow"}]},
+///     {"line": 77, "note": "retries start here"}
+///   ],
+///   "bookmarks": {"deploy": 1200}
+/// }
+/// ```
+///
+/// Lines and columns are numbered as in the `mark` command: from 1, with
+/// `end` exclusive. `bookmarks` is left out when there are none.
+pub fn to_json(contents: &MarksFile, file: &str) -> String {
+    let MarksFile {
+        marks,
+        annotations,
+        bookmarks,
+    } = contents;
+    let mut lines: Vec<usize> = marks
+        .iter()
+        .filter(|(_, m)| !m.is_empty())
+        .map(|(line, _)| *line)
+        .chain(annotations.keys().copied())
+        .collect();
+    lines.sort_unstable();
+    lines.dedup();
+
+    let unmarked = LineMarkings::default();
+    let mut json = format!("{{\n  \"file\": {},\n  \"marks\": [", json::string(file));
+    for (i, line) in lines.iter().enumerate() {
+        let markings = marks.get(line).unwrap_or(&unmarked);
+        json.push_str(if i == 0 { "\n    " } else { ",\n    " });
+        json.push_str(&format!("{{\"line\": {}", line + 1));
+        if let Some(color) = &markings.full_line_color {
+            json.push_str(&format!(", \"color\": {}", json::string(color)));
+        }
+        if !markings.regions.is_empty() {
+            let regions: Vec<String> = markings
+                .regions
+                .iter()
+                .map(|r| {
+                    format!(
+                        "{{\"start\": {}, \"end\": {}, \"color\": {}}}",
+                        r.start_col + 1,
+                        r.end_col + 1,
+                        json::string(&r.color)
+                    )
+                })
+                .collect();
+            json.push_str(&format!(", \"regions\": [{}]", regions.join(", ")));
+        }
+        if let Some(note) = annotations.get(line) {
+            json.push_str(&format!(", \"note\": {}", json::string(note)));
+        }
+        json.push('}');
+    }
+    json.push_str(if lines.is_empty() { "]" } else { "\n  ]" });
+    if !bookmarks.is_empty() {
+        let bookmarks: Vec<String> = bookmarks
+            .iter()
+            .map(|(name, line)| format!("{}: {}", json::string(name), line + 1))
+            .collect();
+        json.push_str(&format!(",\n  \"bookmarks\": {{{}}}", bookmarks.join(", ")));
+    }
+    json.push_str("\n}\n");
+    json
+}
+
+/// Read a marks file written by `to_json`. Unknown keys are ignored.
+pub fn parse(text: &str) -> Result<MarksFile, String> {
+    let value = json::parse(text)?;
+
+    let entries = match value.get("marks") {
+        Some(Json::Array(entries)) => entries,
+        Some(_) => return Err("\"marks\" is not an array".to_string()),
+        None => return Err("missing \"marks\"".to_string()),
+    };
+    let mut marks = Marks::new();
+    let mut annotations = Annotations::new();
+    for entry in entries {
+        let line = entry.position("line")?;
+        if let Some(note) = entry.get("note") {
+            annotations.insert(line, note.string("note")?.to_string());
+        }
+        let markings: &mut LineMarkings = marks.entry(line).or_default();
+        if let Some(color) = entry.get("color") {
+            markings.full_line_color = Some(color.string("color")?.to_string());
+        }
+        match entry.get("regions") {
+            Some(Json::Array(regions)) => {
+                for region in regions {
+                    let color = match region.get("color") {
+                        Some(color) => color.string("color")?.to_string(),
+                        None => return Err("region without \"color\"".to_string()),
+                    };
+                    let (start, end) = (region.position("start")?, region.position("end")?);
+                    markings.add_region(start, end, color);
+                }
+            }
+            Some(_) => return Err("\"regions\" is not an array".to_string()),
+            None => {}
+        }
+    }
+    marks.retain(|_, markings| !markings.is_empty());
+
+    let mut bookmarks = Bookmarks::new();
+    match value.get("bookmarks") {
+        Some(object @ Json::Object(fields)) => {
+            for (name, _) in fields {
+                bookmarks.insert(name.clone(), object.position(name)?);
+            }
+        }
+        Some(_) => return Err("\"bookmarks\" is not an object".to_string()),
+        None => {}
+    }
+    Ok(MarksFile {
+        marks,
+        annotations,
+        bookmarks,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Marks {
+        let mut marks = Marks::new();
+        marks.insert(
+            41,
+            LineMarkings {
+                full_line_color: Some("red".to_string()),
+                regions: Vec::new(),
+            },
+        );
+        let mut regions = LineMarkings::default();
+        regions.add_region(9, 19, "#FFAA00".to_string());
+        regions.add_region(0, 4, "say \"hi\"\\".to_string());
+        marks.insert(49, regions);
+        marks
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let contents = MarksFile {
+            marks: sample(),
+            ..MarksFile::default()
+        };
+        let json = to_json(&contents, "app.log");
+        assert_eq!(
+            json,
+            "{\n  \"file\": \"app.log\",\n  \"marks\": [\n    \
+             {\"line\": 42, \"color\": \"red\"},\n    \
+             {\"line\": 50, \"regions\": [{\"start\": 1, \"end\": 5, \"color\": \"say \\\"hi\\\"\\\\\"}, \
+             {\"start\": 10, \"end\": 20, \"color\": \"#FFAA00\"}]}\n  ]\n}\n"
+        );
+        assert_eq!(parse(&json), Ok(contents));
+        let empty = MarksFile::default();
+        assert_eq!(parse(&to_json(&empty, "x")), Ok(empty));
+    }
+
+    #[test]
+    fn test_json_annotations() {
+        let annotations = Annotations::from([
+            (41, "first \"OOM\"".to_string()),
+            (76, "retries start here".to_string()),
+        ]);
+        let contents = MarksFile {
+            marks: sample(),
+            annotations,
+            ..MarksFile::default()
+        };
+        let json = to_json(&contents, "app.log");
+        assert!(json.contains("{\"line\": 42, \"color\": \"red\", \"note\": \"first \\\"OOM\\\"\"}"));
+        assert!(json.contains("{\"line\": 77, \"note\": \"retries start here\"}"));
+        assert_eq!(parse(&json), Ok(contents));
+        assert!(parse(r#"{"marks": [{"line": 1, "note": 5}]}"#).is_err());
+    }
+
+    #[test]
+    fn test_json_bookmarks() {
+        let contents = MarksFile {
+            bookmarks: Bookmarks::from([("deploy".to_string(), 1199), ("oom".to_string(), 0)]),
+            ..MarksFile::default()
+        };
+        let json = to_json(&contents, "app.log");
+        assert_eq!(
+            json,
+            "{\n  \"file\": \"app.log\",\n  \"marks\": [],\n  \"bookmarks\": {\"deploy\": 1200, \"oom\": 1}\n}\n"
+        );
+        assert_eq!(parse(&json), Ok(contents));
+        assert!(parse(r#"{"marks": [], "bookmarks": {"a": 0}}"#).is_err());
+        assert!(parse(r#"{"marks": [], "bookmarks": [1]}"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_ignores_unknown_keys() {
+        let json = r#"{"version": 1, "marks": [
+            {"line": 3, "color": "blue", "meta": {"by": ["me", null, true]}},
+            {"line": 4}
+        ]}"#;
+        let marks = parse(json).unwrap().marks;
+        assert_eq!(marks.len(), 1);
+        assert_eq!(marks[&2].full_line_color.as_deref(), Some("blue"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("").is_err());
+        assert!(parse("{}").unwrap_err().contains("missing \"marks\""));
+        assert!(parse(r#"{"marks": [{"color": "red"}]}"#)
+            .unwrap_err()
+            .contains("missing \"line\""));
+        assert!(parse(r#"{"marks": [{"line": 0, "color": "red"}]}"#)
+            .unwrap_err()
+            .contains("invalid \"line\""));
+        assert!(parse(r#"{"marks": []} x"#).is_err());
+    }
+
+    #[test]
+    fn test_remove_color() {
+        let mut markings = sample().remove(&49).unwrap();
+        assert!(markings.remove_color("#ffaa00"));
+        assert_eq!(markings.regions.len(), 1);
+        assert!(!markings.remove_color("red"));
+    }
+
+    #[test]
+    fn test_color() {
+        let marks = sample();
+        assert_eq!(marks[&41].color(), Some("red"));
+        assert_eq!(marks[&49].color(), Some("say \"hi\"\\"));
+        assert_eq!(LineMarkings::default().color(), None);
+    }
+}
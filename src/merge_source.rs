@@ -0,0 +1,212 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::sync::Arc;
+
+use crate::error::Result;
+use crate::file_source::FileSource;
+use crate::timestamp::{parse_timestamp, Timestamp};
+
+/// Lines read per request while building the merge index.
+const INDEX_CHUNK_LINES: usize = 10_000;
+
+/// Several logs interleaved into one view ordered by line timestamp.
+///
+/// Lines without a timestamp (stack traces, wrapped messages) keep the
+/// timestamp of the line before them so they stay attached to it. Ties are
+/// broken by source order, then by line order within the source.
+pub struct MergedSource {
+    parts: Vec<Arc<dyn FileSource>>,
+    /// (part index, line within part) for every global line
+    order: Vec<(usize, usize)>,
+    display_name: String,
+}
+
+impl MergedSource {
+    pub fn new(parts: Vec<Arc<dyn FileSource>>) -> Result<Self> {
+        let mut keys = Vec::with_capacity(parts.len());
+        for part in &parts {
+            keys.push(timestamp_keys(part.as_ref())?);
+        }
+
+        let names: Vec<&str> = parts.iter().map(|p| p.display_name()).collect();
+        let display_name = format!("merged: {}", names.join(", "));
+
+        Ok(Self {
+            order: merge_order(&keys),
+            parts,
+            display_name,
+        })
+    }
+
+    /// Index of the input `line` comes from, in the order they were given.
+    pub fn origin(&self, line: usize) -> usize {
+        self.order[line].0
+    }
+}
+
+/// Sort key of every line of `source`, carrying timestamps forward.
+fn timestamp_keys(source: &dyn FileSource) -> Result<Vec<Timestamp>> {
+    source.wait_indexed();
+    let total = source.line_count();
+    let mut keys = Vec::with_capacity(total);
+    let mut last = Timestamp::MIN;
+
+    let mut start = 0;
+    while start < total {
+        let count = INDEX_CHUNK_LINES.min(total - start);
+        for (_, line) in source.get_lines(start, count)? {
+            if let Some(ts) = parse_timestamp(&line) {
+                last = ts;
+            }
+            keys.push(last);
+        }
+        start += count;
+    }
+
+    Ok(keys)
+}
+
+/// K-way merge of per-part key lists into a global (part, line) order.
+fn merge_order(keys: &[Vec<Timestamp>]) -> Vec<(usize, usize)> {
+    let total: usize = keys.iter().map(|k| k.len()).sum();
+    let mut order = Vec::with_capacity(total);
+
+    let mut heap: BinaryHeap<Reverse<(Timestamp, usize, usize)>> = keys
+        .iter()
+        .enumerate()
+        .filter(|(_, k)| !k.is_empty())
+        .map(|(part, k)| Reverse((k[0], part, 0)))
+        .collect();
+
+    while let Some(Reverse((_, part, line))) = heap.pop() {
+        order.push((part, line));
+        if let Some(&next) = keys[part].get(line + 1) {
+            heap.push(Reverse((next, part, line + 1)));
+        }
+    }
+
+    order
+}
+
+impl FileSource for MergedSource {
+    fn line_count(&self) -> usize {
+        self.order.len()
+    }
+
+    fn file_size(&self) -> Result<u64> {
+        let mut total = 0;
+        for part in &self.parts {
+            total += part.file_size()?;
+        }
+        Ok(total)
+    }
+
+    fn get_line(&self, line_num: usize) -> Result<Option<String>> {
+        match self.order.get(line_num) {
+            Some(&(part, line)) => self.parts[part].get_line(line),
+            None => Ok(None),
+        }
+    }
+
+    fn get_lines(&self, start_line: usize, count: usize) -> Result<Vec<(usize, String)>> {
+        let end_line = (start_line + count).min(self.order.len());
+        let mut result = Vec::with_capacity(end_line.saturating_sub(start_line));
+
+        // Fetch runs of consecutive lines from the same part in one request
+        let mut i = start_line;
+        while i < end_line {
+            let (part, first) = self.order[i];
+            let mut run = 1;
+            while i + run < end_line && self.order[i + run] == (part, first + run) {
+                run += 1;
+            }
+
+            let lines = self.parts[part].get_lines(first, run)?;
+            result.extend(lines.into_iter().map(|(n, text)| (i + (n - first), text)));
+            i += run;
+        }
+
+        Ok(result)
+    }
+
+    fn display_name(&self) -> &str {
+        &self.display_name
+    }
+
+    fn line_origin(&self, line_num: usize) -> Option<(usize, &str)> {
+        (line_num < self.order.len()).then(|| {
+            let part = self.origin(line_num);
+            (part, self.parts[part].display_name())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_source::VecSource;
+
+    fn source(name: &str, lines: &[&str]) -> Arc<dyn FileSource> {
+        Arc::new(VecSource::named(name, lines.iter().copied()))
+    }
+
+    #[test]
+    fn test_merge_order() {
+        let keys = vec![vec![1, 3, 5], vec![2, 3, 4]];
+        assert_eq!(
+            merge_order(&keys),
+            vec![(0, 0), (1, 0), (0, 1), (1, 1), (1, 2), (0, 2)]
+        );
+    }
+
+    #[test]
+    fn test_merged_source_interleaves_by_time() {
+        let web = source(
+            "web.log",
+            &[
+                "2024-01-01T10:00:00Z web start",
+                "2024-01-01T10:00:02Z web request",
+                "    continuation of request",
+                "2024-01-01T10:00:04Z web done",
+            ],
+        );
+        let db = source(
+            "db.log",
+            &["2024-01-01T10:00:01Z db start", "2024-01-01T10:00:03Z db query"],
+        );
+
+        let merged = MergedSource::new(vec![web, db]).unwrap();
+        assert_eq!(merged.display_name(), "merged: web.log, db.log");
+        assert_eq!(merged.line_count(), 6);
+
+        let lines: Vec<String> = merged
+            .get_lines(0, 10)
+            .unwrap()
+            .into_iter()
+            .map(|(_, l)| l)
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "2024-01-01T10:00:00Z web start",
+                "2024-01-01T10:00:01Z db start",
+                "2024-01-01T10:00:02Z web request",
+                "    continuation of request",
+                "2024-01-01T10:00:03Z db query",
+                "2024-01-01T10:00:04Z web done",
+            ]
+        );
+        assert_eq!(merged.get_line(3).unwrap(), Some("    continuation of request".to_string()));
+
+        assert_eq!(
+            (0..6).map(|line| merged.origin(line)).collect::<Vec<_>>(),
+            vec![0, 1, 0, 0, 1, 0]
+        );
+        assert_eq!(merged.line_origin(1), Some((1, "db.log")));
+        assert_eq!(merged.line_origin(6), None);
+
+        let tail = merged.get_lines(4, 5).unwrap();
+        assert_eq!(tail[0].0, 4);
+        assert_eq!(tail[1], (5, "2024-01-01T10:00:04Z web done".to_string()));
+    }
+}
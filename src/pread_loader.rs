@@ -0,0 +1,342 @@
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::{FileExt, MetadataExt};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use crate::cache::{LineCache, LineCounter, CHUNK_SIZE};
+use crate::encoding::{TextEncoding, DETECT_SAMPLE_LEN};
+use crate::error::Result;
+use crate::file_loader::{build_line_index, line_at};
+use crate::file_source::{FileSource, SourceChange};
+
+const MAX_CACHED_CHUNKS: usize = 20;
+const READ_BUF_LEN: usize = 1 << 20;
+
+/// Filesystems where a mapping can fault on a server hiccup or miss remote writes.
+const NETWORK_FILESYSTEMS: &[&str] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "9p",
+    "afs",
+    "ceph",
+    "glusterfs",
+    "lustre",
+    "fuse.sshfs",
+    "fuse.rclone",
+    "fuse.s3fs",
+];
+
+/// Whether `path` should be read with `PreadFile` rather than mapped: it lives
+/// on a network filesystem or is too large for the address space.
+pub fn mmap_unsuitable(path: &Path) -> bool {
+    if let Ok(metadata) = std::fs::metadata(path) {
+        if metadata.len() > isize::MAX as u64 {
+            return true;
+        }
+    }
+
+    let Ok(path) = path.canonicalize() else {
+        return false;
+    };
+    let Ok(mounts) = std::fs::read_to_string("/proc/self/mounts") else {
+        return false;
+    };
+    mount_fs_type(&mounts, &path).is_some_and(|fs| NETWORK_FILESYSTEMS.contains(&fs))
+}
+
+/// Filesystem type of the mount point in `mounts` (`/proc/self/mounts`
+/// format) that contains the absolute `path`.
+fn mount_fs_type<'a>(mounts: &'a str, path: &Path) -> Option<&'a str> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let mount_point = fields.nth(1)?.replace("\\040", " ");
+            let fs_type = fields.next()?;
+            path.starts_with(&mount_point)
+                .then_some((mount_point.len(), fs_type))
+        })
+        // Later and longer mount points shadow earlier ones
+        .max_by_key(|(len, _)| *len)
+        .map(|(_, fs_type)| fs_type)
+}
+
+/// A local file read with positioned reads instead of a memory mapping.
+///
+/// Opening reads the file once to record where every `CHUNK_SIZE`th line
+/// starts; chunks of lines are then read on demand into a `LineCache`, like
+/// remote files. Slower than `MappedFile`, but a network filesystem stalling
+/// or a file shrinking only fails a read instead of raising SIGBUS.
+pub struct PreadFile {
+    path: PathBuf,
+    path_display: String,
+    state: RwLock<PreadState>,
+    cache: RwLock<LineCache>,
+}
+
+struct PreadState {
+    file: File,
+    index: LineCounter,
+}
+
+impl PreadState {
+    /// Open and index `path`, detecting its encoding from the first bytes if none is given.
+    fn open(path: &Path, encoding: Option<TextEncoding>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let encoding = match encoding {
+            Some(encoding) => encoding,
+            None => {
+                let mut sample = vec![0; DETECT_SAMPLE_LEN];
+                let len = file.read_at(&mut sample, 0)?;
+                TextEncoding::detect(&sample[..len])
+            }
+        };
+
+        let mut index = LineCounter::new(encoding);
+        index_file(&file, &mut index)?;
+        Ok(Self { file, index })
+    }
+}
+
+/// Feed everything past the bytes already counted by `index`.
+fn index_file(file: &File, index: &mut LineCounter) -> io::Result<()> {
+    let mut buf = vec![0; READ_BUF_LEN];
+    loop {
+        match file.read_at(&mut buf, index.byte_len()) {
+            Ok(0) => return Ok(()),
+            Ok(len) => index.feed(&buf[..len]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+impl PreadFile {
+    pub fn open<P: AsRef<Path>>(path: P, encoding: Option<TextEncoding>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let state = PreadState::open(&path, encoding)?;
+        let encoding = state.index.encoding();
+        let path_display = if encoding.is_utf8() {
+            path.display().to_string()
+        } else {
+            format!("{} ({})", path.display(), encoding.name())
+        };
+
+        Ok(Self {
+            path,
+            path_display,
+            state: RwLock::new(state),
+            cache: RwLock::new(LineCache::new(MAX_CACHED_CHUNKS)),
+        })
+    }
+
+    fn fetch_chunk(state: &PreadState, chunk_start: usize) -> io::Result<Vec<String>> {
+        let (start, end) = state.index.chunk_bytes(chunk_start);
+        if end <= start {
+            return Ok(vec![String::new()]);
+        }
+
+        let mut data = vec![0; (end - start) as usize];
+        state.file.read_exact_at(&mut data, start)?;
+
+        let encoding = state.index.encoding();
+        let offsets = build_line_index(&data, encoding);
+        Ok((0..offsets.len())
+            .filter_map(|i| line_at(&data, &offsets, i, encoding))
+            .collect())
+    }
+
+    fn ensure_chunk_loaded(&self, chunk_start: usize) -> Result<()> {
+        // Held until the chunk is cached so `refresh` cannot invalidate it in between
+        let state = self.state.read().unwrap();
+        {
+            let cache = self.cache.read().unwrap();
+            if cache.contains_line(chunk_start) {
+                return Ok(());
+            }
+        }
+
+        let lines = Self::fetch_chunk(&state, chunk_start)?;
+
+        {
+            let mut cache = self.cache.write().unwrap();
+            cache.insert_chunk(chunk_start, lines);
+        }
+
+        Ok(())
+    }
+}
+
+impl FileSource for PreadFile {
+    fn line_count(&self) -> usize {
+        self.state.read().unwrap().index.line_count()
+    }
+
+    fn file_size(&self) -> Result<u64> {
+        Ok(self.state.read().unwrap().index.byte_len())
+    }
+
+    fn get_line(&self, line_num: usize) -> Result<Option<String>> {
+        if line_num >= self.line_count() {
+            return Ok(None);
+        }
+
+        let chunk_start = LineCache::chunk_start_for_line(line_num);
+        self.ensure_chunk_loaded(chunk_start)?;
+
+        let mut cache = self.cache.write().unwrap();
+        Ok(cache.get_line(line_num).cloned())
+    }
+
+    fn get_lines(&self, start_line: usize, count: usize) -> Result<Vec<(usize, String)>> {
+        let end_line = (start_line + count).min(self.line_count());
+        let actual_count = end_line.saturating_sub(start_line);
+
+        if actual_count == 0 {
+            return Ok(Vec::new());
+        }
+
+        let first_chunk = LineCache::chunk_start_for_line(start_line);
+        let last_chunk = LineCache::chunk_start_for_line(end_line.saturating_sub(1));
+
+        let mut chunk_start = first_chunk;
+        while chunk_start <= last_chunk {
+            self.ensure_chunk_loaded(chunk_start)?;
+            chunk_start += CHUNK_SIZE;
+        }
+
+        let mut result = Vec::with_capacity(actual_count);
+        let mut cache = self.cache.write().unwrap();
+
+        for line_num in start_line..end_line {
+            if let Some(line) = cache.get_line(line_num) {
+                result.push((line_num, line.clone()));
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn display_name(&self) -> &str {
+        &self.path_display
+    }
+
+    fn refresh(&self) -> Result<SourceChange> {
+        let on_disk = match std::fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            // Rotated away and not recreated yet; keep showing what we have
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(SourceChange::Unchanged),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut state = self.state.write().unwrap();
+        let opened = state.file.metadata()?;
+        let encoding = state.index.encoding();
+        if (on_disk.dev(), on_disk.ino()) != (opened.dev(), opened.ino()) {
+            *state = PreadState::open(&self.path, Some(encoding))?;
+            self.cache.write().unwrap().clear();
+            return Ok(SourceChange::Replaced);
+        }
+
+        let indexed_len = state.index.byte_len();
+        if opened.len() < indexed_len {
+            state.index = LineCounter::new(encoding);
+            let PreadState { file, index } = &mut *state;
+            index_file(file, index)?;
+            self.cache.write().unwrap().clear();
+            return Ok(SourceChange::Truncated);
+        }
+        if opened.len() == indexed_len {
+            return Ok(SourceChange::Unchanged);
+        }
+
+        // The cached chunk holding the old last line may gain lines
+        let last_line = state.index.line_count() - 1;
+        let PreadState { file, index } = &mut *state;
+        index_file(file, index)?;
+        self.cache.write().unwrap().remove_chunk_for_line(last_line);
+        Ok(SourceChange::Grew)
+    }
+
+    fn reload(&self) -> Result<SourceChange> {
+        let mut state = self.state.write().unwrap();
+        let encoding = state.index.encoding();
+        *state = PreadState::open(&self.path, Some(encoding))?;
+        self.cache.write().unwrap().clear();
+        Ok(SourceChange::Reloaded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_mount_fs_type() {
+        let mounts = "/dev/sda1 / ext4 rw,relatime 0 0\n\
+                      server:/export /mnt/logs nfs4 rw 0 0\n\
+                      //nas/share /mnt/logs/my\\040share cifs rw 0 0\n";
+        assert_eq!(
+            mount_fs_type(mounts, Path::new("/var/log/syslog")),
+            Some("ext4")
+        );
+        assert_eq!(
+            mount_fs_type(mounts, Path::new("/mnt/logs/app.log")),
+            Some("nfs4")
+        );
+        assert_eq!(
+            mount_fs_type(mounts, Path::new("/mnt/logs/my share/a.log")),
+            Some("cifs")
+        );
+        // Not a path prefix, just a string prefix
+        assert_eq!(
+            mount_fs_type(mounts, Path::new("/mnt/logsx/a.log")),
+            Some("ext4")
+        );
+    }
+
+    #[test]
+    fn test_pread_file_reads_and_refreshes() {
+        let path = std::env::temp_dir().join(format!("pog-pread-{}", std::process::id()));
+        let data: String = (0..CHUNK_SIZE + 10)
+            .map(|i| format!("line {}\n", i))
+            .collect();
+        std::fs::write(&path, &data).unwrap();
+
+        let file = PreadFile::open(&path, None).unwrap();
+        assert_eq!(file.line_count(), CHUNK_SIZE + 10);
+        let lines = file.get_lines(CHUNK_SIZE - 1, 2).unwrap();
+        assert_eq!(lines[0].1, format!("line {}", CHUNK_SIZE - 1));
+        assert_eq!(lines[1].1, format!("line {}", CHUNK_SIZE));
+
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"more\nlast")
+            .unwrap();
+        assert_eq!(file.refresh().unwrap(), SourceChange::Grew);
+        assert_eq!(file.line_count(), CHUNK_SIZE + 12);
+        assert_eq!(
+            file.get_line(CHUNK_SIZE + 11).unwrap(),
+            Some("last".to_string())
+        );
+
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(9)
+            .unwrap();
+        assert_eq!(file.refresh().unwrap(), SourceChange::Truncated);
+        assert_eq!(file.line_count(), 2);
+        assert_eq!(file.get_line(1).unwrap(), Some("li".to_string()));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
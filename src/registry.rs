@@ -0,0 +1,175 @@
+use std::path::{Path, PathBuf};
+
+use crate::json;
+
+/// A running pog window with a command server, as recorded in its runtime file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instance {
+    pub pid: u32,
+    pub port: u16,
+    pub file: String,
+}
+
+impl Instance {
+    /// The runtime file's contents:
+    ///
+    /// ```json
+    /// {"pid": 4242, "port": 9877, "address": "127.0.0.1:9877", "file": "myserver:/var/log/syslog"}
+    /// ```
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"pid\": {}, \"port\": {}, \"address\": {}, \"file\": {}}}\n",
+            self.pid,
+            self.port,
+            json::string(&format!("127.0.0.1:{}", self.port)),
+            json::string(&self.file)
+        )
+    }
+
+    pub fn parse(text: &str) -> Result<Instance, String> {
+        let value = json::parse(text)?;
+        let pid = value.unsigned("pid")?;
+        let port = value.unsigned("port")?;
+        let file = match value.get("file") {
+            Some(file) => file.string("file")?.to_string(),
+            None => return Err("missing \"file\"".to_string()),
+        };
+        Ok(Instance {
+            pid: u32::try_from(pid).map_err(|_| format!("invalid \"pid\": {}", pid))?,
+            port: u16::try_from(port).map_err(|_| format!("invalid \"port\": {}", port))?,
+            file,
+        })
+    }
+}
+
+/// `$XDG_RUNTIME_DIR/pog`, or `pog` in the temp directory without one.
+fn registry_dir() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("pog")
+}
+
+/// The runtime file of a registered window; removed when dropped.
+pub struct Registration {
+    path: PathBuf,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Record that this process serves `file` on `port`. A process may have
+/// several windows, so the file is named `<pid>-<port>.json`.
+pub fn register(port: u16, file: &str) -> Result<Registration, String> {
+    register_in(&registry_dir(), port, file)
+}
+
+fn register_in(dir: &Path, port: u16, file: &str) -> Result<Registration, String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let instance = Instance {
+        pid: std::process::id(),
+        port,
+        file: file.to_string(),
+    };
+    let path = dir.join(format!("{}-{}.json", instance.pid, port));
+    std::fs::write(&path, instance.to_json()).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(Registration { path })
+}
+
+/// The registered windows of running processes, by pid and port. Files
+/// left behind by processes that are gone (killed, crashed) are removed.
+pub fn instances() -> Vec<Instance> {
+    instances_in(&registry_dir())
+}
+
+fn instances_in(dir: &Path) -> Vec<Instance> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut instances = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let Ok(text) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        match Instance::parse(&text) {
+            Ok(instance) if is_running(instance.pid) => instances.push(instance),
+            Ok(_) => {
+                let _ = std::fs::remove_file(&path);
+            }
+            Err(e) => eprintln!("Ignoring {}: {}", path.display(), e),
+        }
+    }
+    instances.sort_by_key(|instance| (instance.pid, instance.port));
+    instances
+}
+
+fn is_running(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+/// Print the running instances, one per line. Returns false if there are none.
+pub fn run_list() -> bool {
+    let instances = instances();
+    if instances.is_empty() {
+        eprintln!("No running pog instances found in {}", registry_dir().display());
+        return false;
+    }
+    println!("{:>8}  {:>5}  FILE", "PID", "PORT");
+    for instance in &instances {
+        println!("{:>8}  {:>5}  {}", instance.pid, instance.port, instance.file);
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_round_trip() {
+        let instance = Instance {
+            pid: 4242,
+            port: 9877,
+            file: "myserver:/var/log/\"quoted\".log".to_string(),
+        };
+        assert_eq!(Instance::parse(&instance.to_json()), Ok(instance));
+        assert!(Instance::parse(r#"{"pid": 1, "port": 70000, "file": "a"}"#).is_err());
+        assert!(Instance::parse(r#"{"pid": 1, "port": 9876}"#).is_err());
+    }
+
+    #[test]
+    fn test_register_and_list() {
+        let dir = std::env::temp_dir().join(format!("pog-registry-test-{}", std::process::id()));
+        let registration = register_in(&dir, 9899, "app.log").unwrap();
+        // A process that no longer exists
+        let stale = dir.join("4000000000-9876.json");
+        let gone = Instance {
+            pid: 4_000_000_000,
+            port: 9876,
+            file: "old.log".to_string(),
+        };
+        std::fs::write(&stale, gone.to_json()).unwrap();
+
+        assert_eq!(
+            instances_in(&dir),
+            vec![Instance {
+                pid: std::process::id(),
+                port: 9899,
+                file: "app.log".to_string(),
+            }]
+        );
+        assert!(!stale.exists());
+
+        drop(registration);
+        assert!(instances_in(&dir).is_empty());
+        let _ = std::fs::remove_dir(&dir);
+    }
+}
@@ -0,0 +1,129 @@
+use std::borrow::Cow;
+use std::fmt;
+
+/// A command line for the remote shell.
+///
+/// Built word by word: every argument is quoted as needed, so paths and
+/// patterns with spaces, quotes or `$` reach the program unchanged. Pipes,
+/// sequences and redirections are the only shell syntax it produces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteCommand {
+    line: String,
+}
+
+impl RemoteCommand {
+    pub fn new(program: &str) -> Self {
+        Self {
+            line: quote(program).into_owned(),
+        }
+    }
+
+    pub fn arg(mut self, arg: impl fmt::Display) -> Self {
+        self.line.push(' ');
+        self.line.push_str(&quote(&arg.to_string()));
+        self
+    }
+
+    pub fn args<I>(self, args: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: fmt::Display,
+    {
+        args.into_iter().fold(self, Self::arg)
+    }
+
+    /// Read standard input from `path`.
+    pub fn stdin_from(mut self, path: &str) -> Self {
+        self.line.push_str(" < ");
+        self.line.push_str(&quote(path));
+        self
+    }
+
+    /// Feed this command's output to `next`.
+    pub fn pipe(mut self, next: RemoteCommand) -> Self {
+        self.line.push_str(" | ");
+        self.line.push_str(&next.line);
+        self
+    }
+
+    /// Run `next` after this command, whatever its exit status.
+    pub fn then(mut self, next: RemoteCommand) -> Self {
+        self.line.push_str("; ");
+        self.line.push_str(&next.line);
+        self
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.line
+    }
+}
+
+impl fmt::Display for RemoteCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.line)
+    }
+}
+
+/// Quote `word` for a POSIX shell. Words made only of characters no shell
+/// treats specially are left alone; anything else is single-quoted, with
+/// embedded single quotes written as `'\''`.
+pub fn quote(word: &str) -> Cow<'_, str> {
+    let is_plain = |c: char| c.is_ascii_alphanumeric() || "@%+=:,./_-".contains(c);
+    if !word.is_empty() && word.chars().all(is_plain) {
+        Cow::Borrowed(word)
+    } else {
+        Cow::Owned(format!("'{}'", word.replace('\'', r"'\''")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("/var/log/syslog"), "/var/log/syslog");
+        assert_eq!(quote("-c%s"), "-c%s");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("/logs/my app.log"), "'/logs/my app.log'");
+        assert_eq!(quote("/logs/$HOME"), "'/logs/$HOME'");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+        assert_eq!(quote("a;rm -rf ~"), "'a;rm -rf ~'");
+        assert_eq!(quote("*.log"), "'*.log'");
+    }
+
+    #[test]
+    fn test_build_command() {
+        let path = "/logs/it's $1.log";
+        let cmd = RemoteCommand::new("tail")
+            .arg("-n")
+            .arg(format!("+{}", 501))
+            .arg(path)
+            .pipe(RemoteCommand::new("head").args(["-n", "500"]));
+        assert_eq!(
+            cmd.as_str(),
+            r"tail -n +501 '/logs/it'\''s $1.log' | head -n 500"
+        );
+
+        let count = RemoteCommand::new("wc")
+            .arg("-lc")
+            .stdin_from(path)
+            .then(RemoteCommand::new("stat").arg("-c%s").arg("/a b"));
+        assert_eq!(
+            count.to_string(),
+            r"wc -lc < '/logs/it'\''s $1.log'; stat -c%s '/a b'"
+        );
+    }
+
+    #[test]
+    fn test_quoting_round_trips_through_sh() {
+        let awkward = r#"/tmp/a b'c"d$e`f\g;h|i&j*k?l~m{n}o(p)q<r>s"#;
+        let cmd = RemoteCommand::new("printf").arg("%s").arg(awkward);
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(cmd.as_str())
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), awkward);
+    }
+}
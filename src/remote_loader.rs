@@ -1,94 +1,269 @@
-use std::process::Command;
-use std::sync::RwLock;
+use std::io::Read;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use regex::Regex;
 
 use crate::cache::{LineCache, CHUNK_SIZE};
+use crate::encoding::{TextEncoding, DETECT_SAMPLE_LEN};
 use crate::error::{PogError, Result};
-use crate::file_source::FileSource;
+use crate::file_loader::line_count_for;
+use crate::file_source::{ConnectionState, FileSource};
+use crate::remote_command::RemoteCommand;
+use crate::search::{self, CancelToken, SearchDirection, SearchMatch};
+use crate::ssh::{SshSession, SshTarget};
+
+/// How remote files are read, chosen with `--remote-transport`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RemoteTransport {
+    /// `tail`, `head` and `wc` run through the remote shell (`RemoteFile`)
+    #[default]
+    Shell,
+    /// Byte ranges read over the SFTP subsystem (`SftpFile`)
+    Sftp,
+}
+
+impl RemoteTransport {
+    pub fn parse(name: &str) -> std::result::Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "shell" => Ok(Self::Shell),
+            "sftp" => Ok(Self::Sftp),
+            _ => Err(format!("unknown remote transport: {} (expected shell or sftp)", name)),
+        }
+    }
+}
+
+/// Settings for opening remote files, from the command line.
+#[derive(Debug, Clone, Default)]
+pub struct RemoteOptions {
+    pub transport: RemoteTransport,
+    /// Lines fetched per command (`--chunk-size`); adapted to the link if unset
+    pub chunk_size: Option<usize>,
+    /// Fetch chunks uncompressed even when the host has gzip
+    pub no_compression: bool,
+}
 
 const MAX_RETRIES: usize = 3;
 const RETRY_DELAY_MS: u64 = 500;
 const MAX_CACHED_CHUNKS: usize = 20;
+/// Most cache chunks fetched by one command, leaving room in the cache
+const MAX_FETCH_CHUNKS: usize = MAX_CACHED_CHUNKS / 2;
+/// Fetches faster than this grow the next one; slower ones shrink it
+const TARGET_FETCH_TIME: Duration = Duration::from_millis(300);
+
+/// How many `CHUNK_SIZE` cache chunks one remote command fetches.
+///
+/// Each command pays the round trip and remote process start-up, so on a
+/// high-latency link larger fetches are cheaper per line; when fetches are
+/// slow because lines are long or the link is thin, smaller ones keep
+/// scrolling responsive. Starting at one chunk, the size is scaled after
+/// every fetch toward `TARGET_FETCH_TIME`, unless fixed with `--chunk-size`.
+struct FetchSizer {
+    chunks: AtomicUsize,
+    fixed: bool,
+}
+
+impl FetchSizer {
+    fn new(chunk_size: Option<usize>) -> Self {
+        match chunk_size {
+            Some(lines) => Self {
+                chunks: AtomicUsize::new(lines.div_ceil(CHUNK_SIZE).clamp(1, MAX_FETCH_CHUNKS)),
+                fixed: true,
+            },
+            None => Self {
+                chunks: AtomicUsize::new(1),
+                fixed: false,
+            },
+        }
+    }
+
+    fn chunks(&self) -> usize {
+        self.chunks.load(Ordering::Relaxed)
+    }
+
+    /// Adapt to a fetch of `chunks` chunks that took `elapsed`.
+    fn record(&self, chunks: usize, elapsed: Duration) {
+        if !self.fixed {
+            self.chunks
+                .store(next_fetch_chunks(chunks, elapsed), Ordering::Relaxed);
+        }
+    }
+}
+
+/// Scale `chunks` by how far `elapsed` was from `TARGET_FETCH_TIME`, by at
+/// most a factor of two either way.
+fn next_fetch_chunks(chunks: usize, elapsed: Duration) -> usize {
+    let ratio = TARGET_FETCH_TIME.as_secs_f64() / elapsed.as_secs_f64().max(1e-3);
+    let scaled = (chunks as f64 * ratio.clamp(0.5, 2.0)).round() as usize;
+    scaled.clamp(1, MAX_FETCH_CHUNKS)
+}
 
 pub struct RemoteFile {
     host: String,
     path: String,
+    session: Arc<SshSession>,
     display_name: String,
+    encoding: TextEncoding,
     line_count: usize,
     cache: RwLock<LineCache>,
+    fetch_sizer: FetchSizer,
+    /// Chunks are sent through `gzip` on the host and inflated here
+    compressed: bool,
 }
 
 impl RemoteFile {
-    pub fn open(host: &str, path: &str) -> Result<Self> {
-        let display_name = format!("{}:{}", host, path);
-
-        let line_count = Self::fetch_line_count_static(host, path)?;
+    /// Open a remote file, detecting its encoding from the first bytes if none is given.
+    pub fn open(
+        target: &SshTarget,
+        path: &str,
+        encoding: Option<TextEncoding>,
+        options: &RemoteOptions,
+    ) -> Result<Self> {
+        let host = &target.to_string();
+        let session = SshSession::new(target);
+        let encoding = match encoding {
+            Some(encoding) => encoding,
+            None => TextEncoding::detect(&Self::fetch_sample(&session, host, path)?),
+        };
+        let details: Vec<String> = target
+            .config_route()
+            .into_iter()
+            .chain((!encoding.is_utf8()).then(|| encoding.name().to_string()))
+            .collect();
+        let display_name = if details.is_empty() {
+            format!("{}:{}", host, path)
+        } else {
+            format!("{}:{} ({})", host, path, details.join(", "))
+        };
+
+        let line_count = Self::fetch_line_count_static(&session, host, path, encoding)?;
+        let compressed = !options.no_compression && Self::has_gzip(&session);
 
         Ok(Self {
             host: host.to_string(),
             path: path.to_string(),
+            session: Arc::new(session),
             display_name,
+            encoding,
             line_count,
             cache: RwLock::new(LineCache::new(MAX_CACHED_CHUNKS)),
+            fetch_sizer: FetchSizer::new(options.chunk_size),
+            compressed,
         })
     }
 
-    fn fetch_line_count_static(host: &str, path: &str) -> Result<usize> {
+    /// Whether the host can compress chunks; any failure means it cannot.
+    fn has_gzip(session: &SshSession) -> bool {
+        session
+            .command(&RemoteCommand::new("gzip").arg("-V"))
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    }
+
+    fn fetch_sample(session: &SshSession, host: &str, path: &str) -> Result<Vec<u8>> {
         Self::with_retry(|| {
-            let output = Command::new("ssh")
-                .arg(host)
-                .arg(format!("wc -l < '{}'", path))
+            let output = session
+                .command(&RemoteCommand::new("head").arg("-c").arg(DETECT_SAMPLE_LEN).arg(path))
                 .output()?;
 
             if !output.status.success() {
-                let stderr = String::from_utf8_lossy(&output.stderr);
-                if stderr.contains("No such file") {
-                    return Err(PogError::FileNotFound {
-                        path: format!("{}:{}", host, path),
-                    });
-                }
-                if stderr.contains("Permission denied") {
-                    return Err(PogError::PermissionDenied {
-                        path: format!("{}:{}", host, path),
-                    });
-                }
-                return Err(PogError::Ssh {
-                    host: host.to_string(),
-                    message: stderr.to_string(),
-                });
+                return Err(Self::ssh_error(host, path, &output.stderr));
             }
+            Ok(output.stdout)
+        })
+    }
 
-            let stdout = String::from_utf8(output.stdout)?;
-            let count: usize = stdout
-                .trim()
-                .parse()
-                .map_err(|_| PogError::Ssh {
-                    host: host.to_string(),
-                    message: format!("Invalid line count: {}", stdout.trim()),
-                })?;
+    /// `tail`, `head` and `wc` split on single `\n` bytes, so UTF-16 files
+    /// are transcoded to UTF-8 on the remote side before line handling.
+    fn transcode_command(path: &str, encoding: TextEncoding) -> Option<RemoteCommand> {
+        encoding.is_utf16().then(|| {
+            RemoteCommand::new("iconv")
+                .args(["-f", encoding.name(), "-t", "UTF-8", path])
+        })
+    }
+
+    /// `count` lines from 1-based line `start_line` on:
+    /// `tail -n +N` outputs from line N onwards and `head -n M` keeps the first M.
+    fn line_range_command(&self, start_line: usize, count: usize) -> RemoteCommand {
+        let tail = RemoteCommand::new("tail").arg("-n").arg(format!("+{}", start_line));
+        let head = RemoteCommand::new("head").arg("-n").arg(count);
+        match Self::transcode_command(&self.path, self.encoding) {
+            Some(transcode) => transcode.pipe(tail).pipe(head),
+            None => tail.arg(&self.path).pipe(head),
+        }
+    }
+
+    /// Count lines like the local loaders do: `wc -lc` gives newlines and
+    /// bytes, and `tail -c 1` tells whether the last line is terminated.
+    fn fetch_line_count_static(
+        session: &SshSession,
+        host: &str,
+        path: &str,
+        encoding: TextEncoding,
+    ) -> Result<usize> {
+        let wc_lc = RemoteCommand::new("wc").arg("-lc");
+        let last_byte = RemoteCommand::new("tail").args(["-c", "1"]);
+        let wc_l = RemoteCommand::new("wc").arg("-l");
+        let cmd = match Self::transcode_command(path, encoding) {
+            Some(transcode) => transcode
+                .clone()
+                .pipe(wc_lc)
+                .then(transcode.pipe(last_byte).pipe(wc_l)),
+            None => wc_lc
+                .stdin_from(path)
+                .then(last_byte.arg(path).pipe(wc_l)),
+        };
 
-            Ok(count)
+        Self::with_retry(|| {
+            let output = session.command(&cmd).output()?;
+
+            if !output.status.success() {
+                return Err(Self::ssh_error(host, path, &output.stderr));
+            }
+
+            let stdout = String::from_utf8(output.stdout)?;
+            parse_line_count(&stdout).ok_or_else(|| PogError::Ssh {
+                host: host.to_string(),
+                message: format!("Invalid line count: {}", stdout.trim()),
+            })
         })
     }
 
-    fn fetch_chunk(&self, chunk_start: usize) -> Result<Vec<String>> {
+    /// Classify a failed ssh command by its stderr.
+    fn ssh_error(host: &str, path: &str, stderr: &[u8]) -> PogError {
+        let stderr = String::from_utf8_lossy(stderr);
+        if stderr.contains("No such file") {
+            return PogError::FileNotFound {
+                path: format!("{}:{}", host, path),
+            };
+        }
+        if stderr.contains("Permission denied") {
+            return PogError::PermissionDenied {
+                path: format!("{}:{}", host, path),
+            };
+        }
+        PogError::Ssh {
+            host: host.to_string(),
+            message: stderr.to_string(),
+        }
+    }
+
+    /// Fetch `chunks` cache chunks' worth of lines from `chunk_start` on.
+    fn fetch_chunks(&self, chunk_start: usize, chunks: usize) -> Result<Vec<String>> {
         let start_line = chunk_start + 1; // 1-based indexing
-        let count = CHUNK_SIZE.min(self.line_count.saturating_sub(chunk_start));
+        let count = (chunks * CHUNK_SIZE).min(self.line_count.saturating_sub(chunk_start));
+
+        let mut cmd = self.line_range_command(start_line, count);
+        if self.compressed {
+            // Fastest level: log text still shrinks 5-10x
+            cmd = cmd.pipe(RemoteCommand::new("gzip").args(["-c", "-1"]));
+        }
 
         Self::with_retry(|| {
-            // Use tail -n +N | head -n M for faster access
-            // tail -n +N outputs from line N onwards (1-based)
-            // head -n M takes first M lines from that
-            let cmd = format!(
-                "tail -n +{} '{}' | head -n {}",
-                start_line,
-                self.path,
-                count
-            );
-
-            let output = Command::new("ssh")
-                .arg(&self.host)
-                .arg(&cmd)
-                .output()?;
+            let output = self.session.run(&cmd)?;
 
             if !output.status.success() {
                 let stderr = String::from_utf8_lossy(&output.stderr);
@@ -98,12 +273,82 @@ impl RemoteFile {
                 });
             }
 
-            let stdout = String::from_utf8(output.stdout)?;
-            let lines: Vec<String> = stdout.lines().map(|l| l.to_string()).collect();
+            // Invalid sequences are replaced rather than failing the whole chunk.
+            // UTF-16 files arrive transcoded to UTF-8 by `transcode_command`
+            let decoding = if self.encoding.is_utf16() {
+                TextEncoding::default()
+            } else {
+                self.encoding
+            };
+            let data = if self.compressed {
+                let mut data = Vec::new();
+                flate2::read::GzDecoder::new(output.stdout.as_slice()).read_to_end(&mut data)?;
+                data
+            } else {
+                output.stdout
+            };
+            let mut lines = decoding.decode_lines(&data);
+            // A file of just "\n" (or nothing) still has one empty line
+            if lines.is_empty() {
+                lines.push(String::new());
+            }
             Ok(lines)
         })
     }
 
+    /// Whether `pattern` can be handed to the remote `grep -E`: the file is
+    /// not transcoded and the pattern uses no syntax that differs between
+    /// Rust regexes and POSIX EREs.
+    fn greppable(&self, pattern: &Regex) -> bool {
+        !self.encoding.is_utf16()
+            && (self.encoding.is_utf8() || pattern.as_str().is_ascii())
+            && is_portable_ere(grep_pattern(pattern.as_str()).0)
+    }
+
+    /// Run `input | grep -nE [options] pattern [| filter]` remotely and
+    /// return the matching lines, numbered from 1 within `input`.
+    fn remote_grep(
+        &self,
+        input: RemoteCommand,
+        pattern: &Regex,
+        options: &[&str],
+        filter: Option<RemoteCommand>,
+    ) -> Result<Vec<(usize, String)>> {
+        let (pattern_str, ignore_case) = grep_pattern(pattern.as_str());
+        let grep = RemoteCommand::new("grep")
+            .arg(if ignore_case { "-inE" } else { "-nE" })
+            .args(options)
+            .arg("-e")
+            .arg(pattern_str);
+        let cmd = match filter {
+            Some(filter) => input.pipe(grep).pipe(filter),
+            None => input.pipe(grep),
+        };
+        Self::with_retry(|| {
+            let output = self.session.run(&cmd)?;
+            // grep exits with 1 when nothing matched
+            let no_match = output.status.code() == Some(1) && output.stderr.is_empty();
+            if !output.status.success() && !no_match {
+                return Err(Self::ssh_error(&self.host, &self.path, &output.stderr));
+            }
+            Ok(parse_grep_output(&output.stdout, self.encoding))
+        })
+    }
+
+    /// Matches on lines found by the remote grep, with columns computed
+    /// locally so they agree with `search_lines`.
+    fn matches_in(
+        pattern: &Regex,
+        first_line: usize,
+        hits: &[(usize, String)],
+    ) -> Vec<SearchMatch> {
+        let lines: Vec<(usize, String)> = hits
+            .iter()
+            .map(|(n, text)| (first_line + n - 1, text.clone()))
+            .collect();
+        search::search_lines(pattern, &lines)
+    }
+
     fn with_retry<T, F>(mut operation: F) -> Result<T>
     where
         F: FnMut() -> Result<T>,
@@ -125,19 +370,37 @@ impl RemoteFile {
         Err(last_error.unwrap())
     }
 
+    /// Load the chunk at `chunk_start`, along with as many of the following
+    /// uncached chunks as the fetch size allows.
     fn ensure_chunk_loaded(&self, chunk_start: usize) -> Result<()> {
-        {
+        let chunks = {
             let cache = self.cache.read().unwrap();
             if cache.contains_line(chunk_start) {
                 return Ok(());
             }
-        }
+            (1..self.fetch_sizer.chunks())
+                .map(|i| chunk_start + i * CHUNK_SIZE)
+                .take_while(|&next| next < self.line_count && !cache.contains_line(next))
+                .count()
+                + 1
+        };
 
-        let lines = self.fetch_chunk(chunk_start)?;
+        let started = Instant::now();
+        let mut lines = self.fetch_chunks(chunk_start, chunks)?;
+        self.fetch_sizer.record(chunks, started.elapsed());
 
         {
             let mut cache = self.cache.write().unwrap();
-            cache.insert_chunk(chunk_start, lines);
+            let mut start = chunk_start;
+            loop {
+                let rest = lines.split_off(CHUNK_SIZE.min(lines.len()));
+                cache.insert_chunk(start, lines);
+                if rest.is_empty() {
+                    break;
+                }
+                lines = rest;
+                start += CHUNK_SIZE;
+            }
         }
 
         Ok(())
@@ -151,10 +414,9 @@ impl FileSource for RemoteFile {
 
     fn file_size(&self) -> Result<u64> {
         Self::with_retry(|| {
-            let output = Command::new("ssh")
-                .arg(&self.host)
-                .arg(format!("stat -c%s '{}'", self.path))
-                .output()?;
+            let output = self
+                .session
+                .run(&RemoteCommand::new("stat").arg("-c%s").arg(&self.path))?;
 
             if !output.status.success() {
                 let stderr = String::from_utf8_lossy(&output.stderr);
@@ -221,4 +483,197 @@ impl FileSource for RemoteFile {
     fn display_name(&self) -> &str {
         &self.display_name
     }
+
+    fn connection_state(&self) -> Option<ConnectionState> {
+        Some(self.session.state())
+    }
+
+    /// Runs `grep` on the host so only matching lines cross the network.
+    fn search_range(
+        &self,
+        pattern: &Regex,
+        start_line: usize,
+        end_line: usize,
+    ) -> Result<Vec<SearchMatch>> {
+        let end_line = end_line.min(self.line_count);
+        if !self.greppable(pattern) || start_line >= end_line {
+            let lines = self.get_lines(start_line, end_line.saturating_sub(start_line))?;
+            return Ok(search::search_lines(pattern, &lines));
+        }
+        let input = self.line_range_command(start_line + 1, end_line - start_line);
+        let hits = self.remote_grep(input, pattern, &[], None)?;
+        Ok(Self::matches_in(pattern, start_line, &hits))
+    }
+
+    /// Runs `grep` on the host, stopping at the first match going forward
+    /// and keeping only the last one going backward. A single `grep` cannot
+    /// be cancelled half way; the local fallback stops between chunks.
+    fn find_next(
+        &self,
+        pattern: &Regex,
+        from_line: usize,
+        direction: SearchDirection,
+        chunk_size: usize,
+        cancel: &CancelToken,
+    ) -> Result<Option<SearchMatch>> {
+        if !self.greppable(pattern) {
+            return search::scan_for_match(self, pattern, from_line, direction, chunk_size, cancel);
+        }
+        let hits = match direction {
+            SearchDirection::Forward => {
+                let input = RemoteCommand::new("tail")
+                    .arg("-n")
+                    .arg(format!("+{}", from_line + 2))
+                    .arg(&self.path);
+                self.remote_grep(input, pattern, &["-m", "1"], None)?
+            }
+            SearchDirection::Backward if from_line == 0 => return Ok(None),
+            SearchDirection::Backward => {
+                let input = RemoteCommand::new("head").arg("-n").arg(from_line).arg(&self.path);
+                let last = RemoteCommand::new("tail").args(["-n", "1"]);
+                self.remote_grep(input, pattern, &[], Some(last))?
+            }
+        };
+        let first_line = match direction {
+            SearchDirection::Forward => from_line + 1,
+            SearchDirection::Backward => 0,
+        };
+        // Lines grep matched but the Rust regex does not are skipped
+        let mut matches = Self::matches_in(pattern, first_line, &hits);
+        Ok(match direction {
+            SearchDirection::Forward => matches.into_iter().next(),
+            SearchDirection::Backward => matches.pop(),
+        })
+    }
+}
+
+/// The pattern to give `grep -E` and whether it needs `-i`: case-insensitive
+/// searches arrive with a leading `(?i)`.
+fn grep_pattern(pattern: &str) -> (&str, bool) {
+    match pattern.strip_prefix("(?i)") {
+        Some(rest) => (rest, true),
+        None => (pattern, false),
+    }
+}
+
+/// Whether `pattern` means the same to `grep -E` as to the `regex` crate.
+/// Conservative: escapes other than metacharacters and the `\w \s \b`
+/// family GNU grep understands (others draw "stray \" warnings), inline
+/// flags, lazy quantifiers and `\p{..}` classes all fall back to searching
+/// locally.
+fn is_portable_ere(pattern: &str) -> bool {
+    let mut chars = pattern.chars().peekable();
+    let mut prev = None;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(e) if search::REGEX_METACHARACTERS.contains(e) || "wWsSbB".contains(e) => {}
+                _ => return false,
+            },
+            '?' if matches!(prev, Some('(' | '*' | '+' | '?' | '}')) => return false,
+            _ => {}
+        }
+        prev = Some(c);
+    }
+    true
+}
+
+/// `N:text` lines printed by `grep -n`.
+fn parse_grep_output(output: &[u8], encoding: TextEncoding) -> Vec<(usize, String)> {
+    output
+        .split(|&b| b == b'\n')
+        .filter_map(|line| {
+            let colon = line.iter().position(|&b| b == b':')?;
+            let line_num = std::str::from_utf8(&line[..colon]).ok()?.parse().ok()?;
+            Some((line_num, encoding.decode_line(&line[colon + 1..])))
+        })
+        .collect()
+}
+
+/// Line count from the output of `wc -lc; tail -c 1 | wc -l`.
+fn parse_line_count(output: &str) -> Option<usize> {
+    let mut numbers = output.split_whitespace().map(|n| n.parse::<u64>().ok());
+    let newlines = numbers.next()??;
+    let len = numbers.next()??;
+    let ends_with_newline = numbers.next()?? == 1;
+    Some(line_count_for(newlines as usize, len, ends_with_newline))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_remote_transport() {
+        assert_eq!(RemoteTransport::parse("sftp"), Ok(RemoteTransport::Sftp));
+        assert_eq!(RemoteTransport::parse("Shell"), Ok(RemoteTransport::Shell));
+        assert!(RemoteTransport::parse("scp").is_err());
+    }
+
+    #[test]
+    fn test_is_portable_ere() {
+        assert!(is_portable_ere("error|warn(ing)?"));
+        assert!(is_portable_ere(r"\bGET /api\.v[0-9]+"));
+        assert!(is_portable_ere("a{2,3}"));
+        assert!(!is_portable_ere(r"\d+"));
+        assert!(!is_portable_ere("(?i)error"));
+        assert!(!is_portable_ere("a.*?b"));
+        assert!(!is_portable_ere(r"\p{Greek}"));
+        assert!(!is_portable_ere(r"a\-b"));
+        assert!(is_portable_ere(&search::escape_literal("GET /a?b=[1] (x)")));
+
+        assert_eq!(grep_pattern("(?i)disk full"), ("disk full", true));
+        assert_eq!(grep_pattern("disk (?i)full"), ("disk (?i)full", false));
+    }
+
+    #[test]
+    fn test_parse_grep_output() {
+        let hits = parse_grep_output(b"3:error: disk\n17:error: a:b\n", TextEncoding::default());
+        assert_eq!(
+            hits,
+            vec![(3, "error: disk".to_string()), (17, "error: a:b".to_string())]
+        );
+        let pattern = Regex::new("disk").unwrap();
+        let matches = RemoteFile::matches_in(&pattern, 100, &hits);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].line_num, 102);
+        assert_eq!(matches[0].start_col, 7);
+    }
+
+    #[test]
+    fn test_next_fetch_chunks() {
+        // Fast fetches double, up to the limit
+        assert_eq!(next_fetch_chunks(1, Duration::from_millis(20)), 2);
+        assert_eq!(next_fetch_chunks(8, Duration::from_millis(20)), MAX_FETCH_CHUNKS);
+        // Near the target stays put
+        assert_eq!(next_fetch_chunks(4, TARGET_FETCH_TIME), 4);
+        // Slow fetches halve, down to one chunk
+        assert_eq!(next_fetch_chunks(4, Duration::from_secs(3)), 2);
+        assert_eq!(next_fetch_chunks(1, Duration::from_secs(3)), 1);
+    }
+
+    #[test]
+    fn test_fixed_fetch_size() {
+        let sizer = FetchSizer::new(Some(1200));
+        assert_eq!(sizer.chunks(), 3);
+        sizer.record(3, Duration::from_millis(1));
+        assert_eq!(sizer.chunks(), 3);
+        assert_eq!(FetchSizer::new(Some(1_000_000)).chunks(), MAX_FETCH_CHUNKS);
+
+        let adaptive = FetchSizer::new(None);
+        adaptive.record(1, Duration::from_millis(1));
+        assert_eq!(adaptive.chunks(), 2);
+    }
+
+    #[test]
+    fn test_parse_line_count() {
+        // "a\nb": wc -l alone would say 1
+        assert_eq!(parse_line_count("      1       3\n0\n"), Some(2));
+        // "a\nb\n"
+        assert_eq!(parse_line_count("2 4\n1\n"), Some(2));
+        // Empty file
+        assert_eq!(parse_line_count("0 0\n0\n"), Some(1));
+        assert_eq!(parse_line_count("0 0\n"), None);
+        assert_eq!(parse_line_count("wc: oops"), None);
+    }
 }
@@ -0,0 +1,271 @@
+use std::path::PathBuf;
+
+use regex::Regex;
+
+use crate::config::Config;
+use crate::search::SearchMatch;
+
+/// Text attributes a coloring rule gives its matches.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TextStyle {
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+impl TextStyle {
+    /// The attributes of both styles.
+    pub fn union(self, other: TextStyle) -> TextStyle {
+        TextStyle {
+            bold: self.bold || other.bold,
+            italic: self.italic || other.italic,
+            underline: self.underline || other.underline,
+        }
+    }
+
+    pub fn is_plain(&self) -> bool {
+        *self == TextStyle::default()
+    }
+
+    /// Pango markup attributes of the style, each with a leading space.
+    pub fn markup_attributes(&self) -> String {
+        let mut attributes = String::new();
+        if self.bold {
+            attributes.push_str(" weight=\"bold\"");
+        }
+        if self.italic {
+            attributes.push_str(" style=\"italic\"");
+        }
+        if self.underline {
+            attributes.push_str(" underline=\"single\"");
+        }
+        attributes
+    }
+}
+
+/// A rule of the rules file: matches of its pattern get a background color,
+/// a text style, or both; with `line`, the whole line gets them.
+#[derive(Debug, Clone)]
+pub struct ColorRule {
+    pub color: Option<String>,
+    pub style: TextStyle,
+    pub line: bool,
+    regex: Regex,
+}
+
+impl ColorRule {
+    /// Character column ranges (start, exclusive end) the rule colors in `text`.
+    pub fn ranges(&self, text: &str) -> Vec<(usize, usize)> {
+        if self.line {
+            return if self.regex.is_match(text) {
+                vec![(0, text.chars().count())]
+            } else {
+                Vec::new()
+            };
+        }
+        self.regex
+            .find_iter(text)
+            .filter(|mat| !mat.is_empty())
+            .map(|mat| {
+                let m = SearchMatch::from_regex_match(0, text, mat);
+                (m.start_col, m.end_col)
+            })
+            .collect()
+    }
+}
+
+/// Coloring rules kept in `rules` next to the config file, so colorings of
+/// nginx, Java or syslog logs can be written once and reused.
+///
+/// Each rule is a line `<style> <regex>`, where style is a comma-separated
+/// list of a background color and any of `bold`, `italic`, `underline` and
+/// `line` (color the whole line). A `[<glob>, ...]` line starts a section
+/// whose rules apply only to files whose name matches one of the globs
+/// (`*` and `?` wildcards); rules above the first section apply to all files.
+/// Blank lines and comments, `#` followed by a space, are ignored: a `#`
+/// right before a word is a color like `#ffcccc`.
+#[derive(Debug, Clone, Default)]
+pub struct RuleSet {
+    /// File name globs, empty for every file, and the section's rules
+    sections: Vec<(Vec<String>, Vec<ColorRule>)>,
+}
+
+impl RuleSet {
+    pub fn default_path() -> Option<PathBuf> {
+        Some(Config::default_path()?.with_file_name("rules"))
+    }
+
+    /// Load the rules file, treating a missing file as no rules.
+    pub fn load() -> Result<Self, String> {
+        let Some(path) = Self::default_path() else {
+            return Ok(Self::default());
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => {
+                Self::parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut sections = vec![(Vec::new(), Vec::new())];
+        for (index, line) in contents.lines().enumerate() {
+            let line_no = index + 1;
+            let line = line.trim_start();
+            if line.trim().is_empty() || line.trim_end() == "#" || line.starts_with("# ") {
+                continue;
+            }
+            if let Some(globs) = line.trim_end().strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                let globs: Vec<String> = globs
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|glob| !glob.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                if globs.is_empty() {
+                    return Err(format!("line {}: section without a file pattern", line_no));
+                }
+                sections.push((globs, Vec::new()));
+                continue;
+            }
+            let rule = parse_rule(line).map_err(|e| format!("line {}: {}", line_no, e))?;
+            if let Some((_, rules)) = sections.last_mut() {
+                rules.push(rule);
+            }
+        }
+        Ok(Self { sections })
+    }
+
+    /// Background colors of the rules, to check before any is shown.
+    pub fn colors(&self) -> impl Iterator<Item = &str> {
+        self.sections
+            .iter()
+            .flat_map(|(_, rules)| rules.iter().filter_map(|rule| rule.color.as_deref()))
+    }
+
+    /// The rules applying to a file named `file_name`, in file order. Only
+    /// the last path component is matched against section globs.
+    pub fn for_file(&self, file_name: &str) -> Vec<ColorRule> {
+        let base_name = file_name.rsplit('/').next().unwrap_or(file_name);
+        self.sections
+            .iter()
+            .filter(|(globs, _)| {
+                globs.is_empty() || globs.iter().any(|glob| glob_matches(glob, base_name))
+            })
+            .flat_map(|(_, rules)| rules.iter().cloned())
+            .collect()
+    }
+}
+
+fn parse_rule(line: &str) -> Result<ColorRule, String> {
+    let Some((style_spec, pattern)) = line.split_once(' ') else {
+        return Err("expected `<style> <regex>`".to_string());
+    };
+    let mut rule_style = TextStyle::default();
+    let mut color = None;
+    let mut whole_line = false;
+    for word in style_spec.split(',').filter(|w| !w.is_empty()) {
+        match word {
+            "bold" => rule_style.bold = true,
+            "italic" => rule_style.italic = true,
+            "underline" => rule_style.underline = true,
+            "line" => whole_line = true,
+            _ if color.is_none() => color = Some(word.to_string()),
+            _ => return Err(format!("unknown style: {}", word)),
+        }
+    }
+    if color.is_none() && rule_style.is_plain() {
+        return Err(format!("rule without a color or style: {}", style_spec));
+    }
+    let pattern = pattern.trim();
+    let regex = Regex::new(pattern).map_err(|e| format!("invalid regex: {}", e))?;
+    Ok(ColorRule {
+        color,
+        style: rule_style,
+        line: whole_line,
+        regex,
+    })
+}
+
+/// Whether `name` matches `glob`, where `*` stands for any run of
+/// characters and `?` for one.
+fn glob_matches(glob: &str, name: &str) -> bool {
+    let glob: Vec<char> = glob.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut g, mut n) = (0, 0);
+    // Where the last `*` was, and the name position it has matched up to
+    let mut backtrack = None;
+    while n < name.len() {
+        match glob.get(g) {
+            Some('*') => {
+                backtrack = Some((g, n));
+                g += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                g += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    g = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    glob[g..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("*.log", "app.log"));
+        assert!(glob_matches("access*.log", "access-2024.log"));
+        assert!(glob_matches("syslog.?", "syslog.1"));
+        assert!(glob_matches("*", "anything"));
+        assert!(!glob_matches("*.log", "app.log.gz"));
+        assert!(!glob_matches("syslog.?", "syslog.10"));
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        let rules = RuleSet::parse(
+            "# everywhere\n\
+             bold \\b[0-9a-f]{8}\\b\n\
+             [access*.log, *.nginx]\n\
+             #ffcccc,line \" 5[0-9]{2} \n\
+             [*.java.log]\n\
+             lightblue,italic,underline ^\\S+\n",
+        )
+        .unwrap();
+        let nginx = rules.for_file("/var/log/nginx/access.log");
+        assert_eq!(nginx.len(), 2);
+        assert_eq!(nginx[1].color.as_deref(), Some("#ffcccc"));
+        assert_eq!(nginx[1].ranges("GET / \" 502 12"), vec![(0, 14)]);
+        assert!(nginx[1].ranges("GET / \" 200 12").is_empty());
+        assert_eq!(nginx[0].ranges("id deadbeef"), vec![(3, 11)]);
+        assert!(nginx[0].style.bold && nginx[0].color.is_none());
+
+        let java = rules.for_file("host:/srv/app.java.log");
+        assert_eq!(java.len(), 2);
+        assert_eq!(java[1].style.markup_attributes(), " style=\"italic\" underline=\"single\"");
+        assert_eq!(rules.for_file("other.txt").len(), 1);
+        assert_eq!(rules.colors().collect::<Vec<_>>(), vec!["#ffcccc", "lightblue"]);
+
+        assert!(RuleSet::parse("red").is_err());
+        assert!(RuleSet::parse("red,blue x").is_err());
+        assert!(RuleSet::parse("line x").is_err());
+        assert!(RuleSet::parse("red (").is_err());
+        assert_eq!(
+            RuleSet::parse("[ ]").unwrap_err(),
+            "line 1: section without a file pattern"
+        );
+    }
+}
@@ -0,0 +1,247 @@
+use std::sync::RwLock;
+
+use aws_sdk_s3::error::DisplayErrorContext;
+use aws_sdk_s3::Client;
+
+use crate::cache::{LineCache, LineCounter, CHUNK_SIZE};
+use crate::encoding::{TextEncoding, DETECT_SAMPLE_LEN};
+use crate::error::{PogError, Result};
+use crate::file_loader::{build_line_index, line_at};
+use crate::file_source::FileSource;
+
+const MAX_CACHED_CHUNKS: usize = 20;
+
+/// Split an `s3://bucket/key` URL into bucket and key.
+pub fn parse_s3_url(url: &str) -> Option<(String, String)> {
+    let rest = url.strip_prefix("s3://")?;
+    let (bucket, key) = rest.split_once('/')?;
+    if bucket.is_empty() || key.is_empty() {
+        return None;
+    }
+    Some((bucket.to_string(), key.to_string()))
+}
+
+/// A log stored in S3 (or an S3-compatible store), read with ranged GETs.
+///
+/// Opening streams the object once to record where every `CHUNK_SIZE`th line
+/// starts; nothing else is kept. Chunks of lines are then fetched on demand
+/// with ranged GETs and kept in the same LRU `LineCache` as remote files.
+/// Credentials, region and endpoint come from the standard AWS chain
+/// (environment, `~/.aws` profiles, SSO, instance metadata).
+pub struct S3File {
+    bucket: String,
+    key: String,
+    display_name: String,
+    encoding: TextEncoding,
+    runtime: tokio::runtime::Runtime,
+    client: Client,
+    index: LineCounter,
+    cache: RwLock<LineCache>,
+}
+
+impl S3File {
+    /// Open `s3://bucket/key`, detecting its encoding from the first bytes if none is given.
+    pub fn open(bucket: &str, key: &str, encoding: Option<TextEncoding>) -> Result<Self> {
+        let url = format!("s3://{}/{}", bucket, key);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let config = runtime.block_on(aws_config::load_defaults(
+            aws_config::BehaviorVersion::latest(),
+        ));
+        let client = Client::new(&config);
+
+        let index = runtime.block_on(index_object(&client, bucket, key, encoding))?;
+        let encoding = index.encoding();
+        let display_name = if encoding.is_utf8() {
+            url
+        } else {
+            format!("{} ({})", url, encoding.name())
+        };
+
+        Ok(Self {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            display_name,
+            encoding,
+            runtime,
+            client,
+            index,
+            cache: RwLock::new(LineCache::new(MAX_CACHED_CHUNKS)),
+        })
+    }
+
+    fn s3_error(&self, message: String) -> PogError {
+        PogError::S3 {
+            url: format!("s3://{}/{}", self.bucket, self.key),
+            message,
+        }
+    }
+
+    fn fetch_chunk(&self, chunk_start: usize) -> Result<Vec<String>> {
+        let (start, end) = self.index.chunk_bytes(chunk_start);
+        if end <= start {
+            return Ok(vec![String::new()]);
+        }
+
+        let data = self.runtime.block_on(async {
+            let object = self
+                .client
+                .get_object()
+                .bucket(&self.bucket)
+                .key(&self.key)
+                .range(format!("bytes={}-{}", start, end - 1))
+                .send()
+                .await
+                .map_err(|e| self.s3_error(DisplayErrorContext(&e).to_string()))?;
+            object
+                .body
+                .collect()
+                .await
+                .map(|data| data.into_bytes())
+                .map_err(|e| self.s3_error(e.to_string()))
+        })?;
+
+        let offsets = build_line_index(&data, self.encoding);
+        Ok((0..offsets.len())
+            .filter_map(|i| line_at(&data, &offsets, i, self.encoding))
+            .collect())
+    }
+
+    fn ensure_chunk_loaded(&self, chunk_start: usize) -> Result<()> {
+        {
+            let cache = self.cache.read().unwrap();
+            if cache.contains_line(chunk_start) {
+                return Ok(());
+            }
+        }
+
+        let lines = self.fetch_chunk(chunk_start)?;
+
+        {
+            let mut cache = self.cache.write().unwrap();
+            cache.insert_chunk(chunk_start, lines);
+        }
+
+        Ok(())
+    }
+}
+
+/// Stream the whole object once, counting lines and recording chunk offsets.
+async fn index_object(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    encoding: Option<TextEncoding>,
+) -> Result<LineCounter> {
+    let s3_error = |message: String| PogError::S3 {
+        url: format!("s3://{}/{}", bucket, key),
+        message,
+    };
+
+    let mut object = client
+        .get_object()
+        .bucket(bucket)
+        .key(key)
+        .send()
+        .await
+        .map_err(|e| s3_error(DisplayErrorContext(&e).to_string()))?;
+
+    let mut counter: Option<LineCounter> = encoding.map(LineCounter::new);
+    let mut sample = Vec::new();
+    while let Some(bytes) = object
+        .body
+        .try_next()
+        .await
+        .map_err(|e| s3_error(e.to_string()))?
+    {
+        match counter.as_mut() {
+            Some(counter) => counter.feed(&bytes),
+            None => {
+                // Hold back data until there is enough to detect the encoding
+                sample.extend_from_slice(&bytes);
+                if sample.len() >= DETECT_SAMPLE_LEN {
+                    let mut detected = LineCounter::new(TextEncoding::detect(&sample));
+                    detected.feed(&sample);
+                    counter = Some(detected);
+                }
+            }
+        }
+    }
+    Ok(counter.unwrap_or_else(|| {
+        let mut detected = LineCounter::new(TextEncoding::detect(&sample));
+        detected.feed(&sample);
+        detected
+    }))
+}
+
+impl FileSource for S3File {
+    fn line_count(&self) -> usize {
+        self.index.line_count()
+    }
+
+    fn file_size(&self) -> Result<u64> {
+        Ok(self.index.byte_len())
+    }
+
+    fn get_line(&self, line_num: usize) -> Result<Option<String>> {
+        if line_num >= self.line_count() {
+            return Ok(None);
+        }
+
+        let chunk_start = LineCache::chunk_start_for_line(line_num);
+        self.ensure_chunk_loaded(chunk_start)?;
+
+        let mut cache = self.cache.write().unwrap();
+        Ok(cache.get_line(line_num).cloned())
+    }
+
+    fn get_lines(&self, start_line: usize, count: usize) -> Result<Vec<(usize, String)>> {
+        let end_line = (start_line + count).min(self.line_count());
+        let actual_count = end_line.saturating_sub(start_line);
+
+        if actual_count == 0 {
+            return Ok(Vec::new());
+        }
+
+        let first_chunk = LineCache::chunk_start_for_line(start_line);
+        let last_chunk = LineCache::chunk_start_for_line(end_line.saturating_sub(1));
+
+        let mut chunk_start = first_chunk;
+        while chunk_start <= last_chunk {
+            self.ensure_chunk_loaded(chunk_start)?;
+            chunk_start += CHUNK_SIZE;
+        }
+
+        let mut result = Vec::with_capacity(actual_count);
+        let mut cache = self.cache.write().unwrap();
+
+        for line_num in start_line..end_line {
+            if let Some(line) = cache.get_line(line_num) {
+                result.push((line_num, line.clone()));
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn display_name(&self) -> &str {
+        &self.display_name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_s3_url() {
+        assert_eq!(
+            parse_s3_url("s3://logs/app/2024/app.log"),
+            Some(("logs".to_string(), "app/2024/app.log".to_string()))
+        );
+        assert_eq!(parse_s3_url("s3://logs/"), None);
+        assert_eq!(parse_s3_url("s3://logs"), None);
+        assert_eq!(parse_s3_url("host:/var/log/syslog"), None);
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::config::Config;
+
+/// A search pattern with the search bar options it was run with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedSearch {
+    /// The pattern as typed, before plain-text escaping
+    pub pattern: String,
+    pub ignore_case: bool,
+    pub literal: bool,
+    pub multiline: bool,
+}
+
+/// Searches saved under a name, kept in `searches` next to the config file so
+/// every window and later sessions can load them.
+///
+/// Each line is `<name> <flags> <pattern>`, where flags are `-` or any of `i`
+/// (ignore case), `l` (plain text) and `m` (multiline). Blank lines and lines
+/// starting with `#` are ignored.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SavedSearches {
+    searches: BTreeMap<String, SavedSearch>,
+}
+
+impl SavedSearches {
+    pub fn default_path() -> Option<PathBuf> {
+        Some(Config::default_path()?.with_file_name("searches"))
+    }
+
+    /// Load the saved searches, treating a missing file as none saved.
+    pub fn load() -> Result<Self, String> {
+        let Some(path) = Self::default_path() else {
+            return Ok(Self::default());
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => {
+                Self::parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    /// Save `search` as `name`, replacing an earlier one of that name, and
+    /// write the file.
+    pub fn save(name: &str, search: SavedSearch) -> Result<(), String> {
+        validate_name(name)?;
+        let path = Self::default_path().ok_or("cannot locate config directory")?;
+        let mut saved = Self::load()?;
+        saved.searches.insert(name.to_string(), search);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+        std::fs::write(&path, saved.to_contents()).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SavedSearch> {
+        self.searches.get(name)
+    }
+
+    /// Names of the saved searches, in alphabetical order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.searches.keys().map(String::as_str)
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut searches = BTreeMap::new();
+        for (index, line) in contents.lines().enumerate() {
+            let line_no = index + 1;
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+            let mut parts = line.trim_start().splitn(3, ' ');
+            let (Some(name), Some(flags), Some(pattern)) =
+                (parts.next(), parts.next(), parts.next())
+            else {
+                return Err(format!(
+                    "line {}: expected `<name> <flags> <pattern>`",
+                    line_no
+                ));
+            };
+            let mut search = SavedSearch {
+                pattern: pattern.to_string(),
+                ignore_case: false,
+                literal: false,
+                multiline: false,
+            };
+            for flag in flags.chars().filter(|&c| c != '-') {
+                match flag {
+                    'i' => search.ignore_case = true,
+                    'l' => search.literal = true,
+                    'm' => search.multiline = true,
+                    _ => return Err(format!("line {}: unknown flag: {}", line_no, flag)),
+                }
+            }
+            searches.insert(name.to_string(), search);
+        }
+        Ok(Self { searches })
+    }
+
+    fn to_contents(&self) -> String {
+        let mut contents = String::new();
+        for (name, search) in &self.searches {
+            let mut flags = String::new();
+            for (set, flag) in [
+                (search.ignore_case, 'i'),
+                (search.literal, 'l'),
+                (search.multiline, 'm'),
+            ] {
+                if set {
+                    flags.push(flag);
+                }
+            }
+            if flags.is_empty() {
+                flags.push('-');
+            }
+            contents.push_str(&format!("{} {} {}\n", name, flags, search.pattern));
+        }
+        contents
+    }
+}
+
+/// Names are single words, so commands can take them as one argument.
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.starts_with('#') || name.contains(char::is_whitespace) {
+        return Err(format!("invalid search name: {:?}", name));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(pattern: &str, ignore_case: bool, literal: bool, multiline: bool) -> SavedSearch {
+        SavedSearch {
+            pattern: pattern.to_string(),
+            ignore_case,
+            literal,
+            multiline,
+        }
+    }
+
+    #[test]
+    fn test_parse_and_write_back() {
+        let contents = "# saved\n\noom i oom-killer|Out of memory\ntrace lm at com.example\n\
+                        5xx -  5\\d\\d $\n";
+        let saved = SavedSearches::parse(contents).unwrap();
+        assert_eq!(
+            saved.names().collect::<Vec<_>>(),
+            vec!["5xx", "oom", "trace"]
+        );
+        assert_eq!(
+            saved.get("oom"),
+            Some(&search("oom-killer|Out of memory", true, false, false))
+        );
+        assert_eq!(
+            saved.get("trace"),
+            Some(&search("at com.example", false, true, true))
+        );
+        // Spaces in the pattern are kept
+        assert_eq!(
+            saved.get("5xx"),
+            Some(&search(" 5\\d\\d $", false, false, false))
+        );
+        assert_eq!(saved.get("missing"), None);
+
+        assert_eq!(SavedSearches::parse(&saved.to_contents()), Ok(saved));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(SavedSearches::parse("oom i")
+            .unwrap_err()
+            .starts_with("line 1:"));
+        assert!(SavedSearches::parse("\noom x pattern")
+            .unwrap_err()
+            .contains("unknown flag: x"));
+    }
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("oom-killer").is_ok());
+        assert!(validate_name("").is_err());
+        assert!(validate_name("two words").is_err());
+        assert!(validate_name("#comment").is_err());
+    }
+}
@@ -1,19 +1,226 @@
-use regex::Regex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::config::Config;
+use crate::error::{self, PogError};
+use crate::file_source::FileSource;
 
 #[derive(Debug, Clone)]
 pub struct SearchMatch {
     pub line_num: usize,   // 0-based
     pub start_col: usize,  // 0-based
-    pub end_col: usize,    // exclusive
+    pub end_line: usize,   // same as line_num unless a multiline match spans lines
+    pub end_col: usize,    // exclusive, on end_line
+    /// Spans of the pattern's capture groups: `groups[0]` is group 1. `None`
+    /// for groups that did not take part in the match.
+    pub groups: Vec<Option<SearchMatch>>,
+}
+
+/// Sizes used by viewport searches and full-file match navigation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchConfig {
+    /// Extra lines searched above and below the viewport
+    pub buffer_lines: usize,
+    /// Lines fetched per step when scanning for the next/previous match
+    pub chunk_size: usize,
+    /// Re-search once the viewport comes this close to the searched range's edge
+    pub research_margin: usize,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            buffer_lines: 100,
+            chunk_size: 1000,
+            research_margin: 50,
+        }
+    }
+}
+
+impl SearchConfig {
+    /// Pick sizes for a source based on how long a viewport fetch takes.
+    /// Slow (remote) sources get larger windows so fewer round trips are made.
+    pub fn for_latency(latency: Duration) -> Self {
+        let (buffer_lines, chunk_size) = if latency < Duration::from_millis(5) {
+            (100, 1000)
+        } else if latency < Duration::from_millis(50) {
+            (500, 5000)
+        } else {
+            (2000, 20000)
+        };
+        Self {
+            buffer_lines,
+            chunk_size,
+            research_margin: buffer_lines / 2,
+        }
+    }
+
+    /// Apply config file values on top of these settings.
+    pub fn with_overrides(mut self, config: &Config) -> Self {
+        if let Some(buffer_lines) = config.search_buffer_lines {
+            self.buffer_lines = buffer_lines;
+            self.research_margin = self.research_margin.min(buffer_lines);
+        }
+        if let Some(chunk_size) = config.search_chunk_size {
+            self.chunk_size = chunk_size;
+        }
+        if let Some(margin) = config.search_research_margin {
+            self.research_margin = margin.min(self.buffer_lines);
+        }
+        self
+    }
+
+    /// Line range to search around a viewport.
+    pub fn window(&self, viewport_start: usize, viewport_size: usize, total_lines: usize) -> (usize, usize) {
+        let start = viewport_start.saturating_sub(self.buffer_lines);
+        let end = (viewport_start + viewport_size + self.buffer_lines).min(total_lines);
+        (start, end)
+    }
+}
+
+/// Lines a multiline search match may span at most
+pub const MULTILINE_WINDOW: usize = 20;
+
+/// Characters with a special meaning in both Rust regexes and POSIX EREs.
+pub const REGEX_METACHARACTERS: &str = r"\.+*?()|[]{}^$";
+
+/// Search bar text for a number of matches.
+pub fn describe_match_count(matches: usize) -> String {
+    match matches {
+        0 => "No matches".to_string(),
+        1 => "1 match".to_string(),
+        n => format!("{} matches", n),
+    }
+}
+
+/// Escape `text` so it matches itself, both as a Rust regex and as a POSIX
+/// ERE (which `regex::escape` does not guarantee, escaping `-`, `#` or `~`).
+pub fn escape_literal(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if REGEX_METACHARACTERS.contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Whether searches match letters regardless of case.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CaseMode {
+    #[default]
+    Sensitive,
+    Insensitive,
+    /// Insensitive unless the pattern contains an uppercase letter
+    Smart,
+}
+
+impl CaseMode {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "sensitive" => Ok(Self::Sensitive),
+            "insensitive" => Ok(Self::Insensitive),
+            "smart" => Ok(Self::Smart),
+            _ => Err(format!(
+                "unknown case mode: {} (expected sensitive, insensitive or smart)",
+                name
+            )),
+        }
+    }
+
+    /// Whether `pattern` is matched ignoring case in this mode. Smart case
+    /// looks at the letters outside escapes, so `\S` or `\W` do not count.
+    pub fn ignores_case(self, pattern: &str) -> bool {
+        match self {
+            Self::Sensitive => false,
+            Self::Insensitive => true,
+            Self::Smart => {
+                let mut chars = pattern.chars();
+                while let Some(c) = chars.next() {
+                    if c == '\\' {
+                        chars.next();
+                    } else if c.is_uppercase() {
+                        return false;
+                    }
+                }
+                true
+            }
+        }
+    }
+}
+
+/// Stretches of the file the density of matches is kept for at most. They
+/// double in length whenever the file outgrows this many.
+const MAX_DENSITY_BUCKETS: usize = 2048;
+
+/// How many matches fall in each stretch of the file, for an overview of where
+/// they cluster. Unlike the match positions it covers every match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchDensity {
+    lines_per_bucket: usize,
+    buckets: Vec<usize>,
+}
+
+impl Default for MatchDensity {
+    fn default() -> Self {
+        Self {
+            lines_per_bucket: 1,
+            buckets: Vec::new(),
+        }
+    }
+}
+
+impl MatchDensity {
+    /// Count a match on `line`; matches must be added in line order.
+    pub fn add(&mut self, line: usize) {
+        while line / self.lines_per_bucket >= MAX_DENSITY_BUCKETS {
+            self.buckets = self.buckets.chunks(2).map(|pair| pair.iter().sum()).collect();
+            self.lines_per_bucket *= 2;
+        }
+        let bucket = line / self.lines_per_bucket;
+        if bucket >= self.buckets.len() {
+            self.buckets.resize(bucket + 1, 0);
+        }
+        self.buckets[bucket] += 1;
+    }
+
+    /// (first line, line count, matches) of every stretch holding matches.
+    pub fn spans(&self) -> impl Iterator<Item = (usize, usize, usize)> + '_ {
+        self.buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, &matches)| matches > 0)
+            .map(|(i, &matches)| (i * self.lines_per_bucket, self.lines_per_bucket, matches))
+    }
+
+    /// Matches in the busiest stretch.
+    pub fn max(&self) -> usize {
+        self.buckets.iter().copied().max().unwrap_or(0)
+    }
 }
 
 pub struct SearchState {
     pub pattern: Option<Regex>,
+    /// Regex source sent to the worker, with a leading `(?i)` when ignoring case
     pub pattern_str: String,
     pub viewport_matches: Vec<SearchMatch>,
     pub current_match_index: Option<usize>,
     pub last_searched_range: Option<(usize, usize)>,
     pub is_active: bool,
+    /// Matches may run across line boundaries (see `search_lines_multiline`)
+    pub multiline: bool,
+    /// Request id of the whole-file match count; responses to older counts are ignored
+    pub count_request_id: Option<u64>,
+    /// Matches in the whole file, once the background count has finished
+    pub total_matches: Option<usize>,
+    /// (line, start column) of every match in the file, from the background count
+    pub match_positions: Vec<(usize, usize)>,
+    /// Where the matches of the whole file lie, from the background count
+    pub match_density: MatchDensity,
 }
 
 impl Default for SearchState {
@@ -31,6 +238,11 @@ impl SearchState {
             current_match_index: None,
             last_searched_range: None,
             is_active: false,
+            multiline: false,
+            count_request_id: None,
+            total_matches: None,
+            match_positions: Vec::new(),
+            match_density: MatchDensity::default(),
         }
     }
 
@@ -41,23 +253,65 @@ impl SearchState {
         self.current_match_index = None;
         self.last_searched_range = None;
         self.is_active = false;
+        self.multiline = false;
+        self.count_request_id = None;
+        self.total_matches = None;
+        self.match_positions.clear();
+        self.match_density = MatchDensity::default();
     }
 
-    pub fn set_pattern(&mut self, pattern_str: &str) -> Result<(), String> {
-        match Regex::new(pattern_str) {
+    /// Compile the search; a `literal` pattern is plain text, not a regex.
+    /// A `multiline` search matches across line boundaries.
+    pub fn set_pattern(
+        &mut self,
+        pattern_str: &str,
+        case: CaseMode,
+        literal: bool,
+        multiline: bool,
+    ) -> Result<(), String> {
+        let escaped;
+        let pattern_str = if literal {
+            escaped = escape_literal(pattern_str);
+            &escaped
+        } else {
+            pattern_str
+        };
+        let ignore_case = case.ignores_case(pattern_str);
+        match RegexBuilder::new(pattern_str).case_insensitive(ignore_case).build() {
             Ok(regex) => {
                 self.pattern = Some(regex);
-                self.pattern_str = pattern_str.to_string();
+                self.pattern_str = if ignore_case {
+                    format!("(?i){}", pattern_str)
+                } else {
+                    pattern_str.to_string()
+                };
                 self.viewport_matches.clear();
                 self.current_match_index = None;
                 self.last_searched_range = None;
                 self.is_active = true;
+                self.multiline = multiline;
+                self.count_request_id = None;
+                self.total_matches = None;
+                self.match_positions.clear();
+        self.match_density = MatchDensity::default();
                 Ok(())
             }
             Err(e) => Err(format!("invalid regex: {}", e)),
         }
     }
 
+    /// Forget matches after the file content changed under them, keeping the
+    /// pattern so the next viewport update searches again. The worker restarts
+    /// the whole-file count by itself.
+    pub fn invalidate_matches(&mut self) {
+        self.viewport_matches.clear();
+        self.current_match_index = None;
+        self.last_searched_range = None;
+        self.total_matches = None;
+        self.match_positions.clear();
+        self.match_density = MatchDensity::default();
+    }
+
     pub fn update_matches(&mut self, matches: Vec<SearchMatch>, searched_range: (usize, usize)) {
         self.viewport_matches = matches;
         self.last_searched_range = Some(searched_range);
@@ -66,6 +320,30 @@ impl SearchState {
         }
     }
 
+    /// Record the result of the whole-file count, unless a newer search replaced it.
+    pub fn update_match_count(
+        &mut self,
+        request_id: u64,
+        matches: usize,
+        match_positions: Vec<(usize, usize)>,
+        match_density: MatchDensity,
+    ) {
+        if self.count_request_id == Some(request_id) {
+            self.total_matches = Some(matches);
+            self.match_positions = match_positions;
+            self.match_density = match_density;
+        }
+    }
+
+    /// 1-based position of `m` among all matches of the file, once the
+    /// background count has finished and recorded it.
+    pub fn match_ordinal(&self, m: &SearchMatch) -> Option<usize> {
+        self.total_matches?;
+        let position = (m.line_num, m.start_col);
+        let index = self.match_positions.partition_point(|&p| p < position);
+        (self.match_positions.get(index) == Some(&position)).then_some(index + 1)
+    }
+
     pub fn current_match(&self) -> Option<&SearchMatch> {
         self.current_match_index
             .and_then(|i| self.viewport_matches.get(i))
@@ -103,40 +381,602 @@ impl SearchState {
         self.viewport_matches.get(new_index)
     }
 
-    pub fn needs_research(&self, viewport_start: usize, viewport_size: usize, buffer: usize) -> bool {
+    pub fn needs_research(&self, viewport_start: usize, viewport_size: usize, margin: usize) -> bool {
         if !self.is_active || self.pattern.is_none() {
             return false;
         }
         match self.last_searched_range {
             Some((searched_start, searched_end)) => {
-                let half_buffer = buffer / 2;
-                viewport_start < searched_start.saturating_add(half_buffer)
-                    || viewport_start + viewport_size > searched_end.saturating_sub(half_buffer)
+                viewport_start < searched_start.saturating_add(margin)
+                    || viewport_start + viewport_size > searched_end.saturating_sub(margin)
             }
             None => true,
         }
     }
 }
 
+impl SearchMatch {
+    /// Build a match from a regex hit, converting byte offsets to character columns
+    /// so highlights line up with the rendered text for non-ASCII lines.
+    pub fn from_regex_match(line_num: usize, text: &str, mat: regex::Match) -> Self {
+        let start_col = text[..mat.start()].chars().count();
+        let end_col = start_col + mat.as_str().chars().count();
+        Self {
+            line_num,
+            start_col,
+            end_line: line_num,
+            end_col,
+            groups: Vec::new(),
+        }
+    }
+
+    /// A match with the spans of its capture groups.
+    fn from_captures(line_num: usize, text: &str, caps: &regex::Captures) -> Self {
+        let mut m = Self::from_regex_match(line_num, text, caps.get(0).unwrap());
+        m.groups = caps
+            .iter()
+            .skip(1)
+            .map(|group| group.map(|g| Self::from_regex_match(line_num, text, g)))
+            .collect();
+        m
+    }
+
+    /// Move the match and its groups `columns` to the right, for a match found
+    /// in a part of the line starting there.
+    pub fn shift_columns(&mut self, columns: usize) {
+        self.start_col += columns;
+        self.end_col += columns;
+        for group in self.groups.iter_mut().flatten() {
+            group.shift_columns(columns);
+        }
+    }
+
+    /// Columns (start, exclusive end) this match covers on `line`, if any.
+    /// Lines a multiline match runs on past return `usize::MAX` as the end.
+    pub fn columns_on(&self, line: usize) -> Option<(usize, usize)> {
+        if line < self.line_num || line > self.end_line {
+            return None;
+        }
+        let start = if line == self.line_num { self.start_col } else { 0 };
+        let end = if line == self.end_line { self.end_col } else { usize::MAX };
+        Some((start, end))
+    }
+}
+
 pub fn search_lines(
     pattern: &Regex,
     lines: &[(usize, String)],
 ) -> Vec<SearchMatch> {
     let mut matches = Vec::new();
+    // Capturing is slower than finding; only do it when there are groups to show
+    let has_groups = pattern.captures_len() > 1;
     for (line_num, text) in lines {
-        for mat in pattern.find_iter(text) {
-            matches.push(SearchMatch {
-                line_num: *line_num,
-                start_col: mat.start(),
-                end_col: mat.end(),
-            });
+        if has_groups {
+            for caps in pattern.captures_iter(text) {
+                matches.push(SearchMatch::from_captures(*line_num, text, &caps));
+            }
+        } else {
+            for mat in pattern.find_iter(text) {
+                matches.push(SearchMatch::from_regex_match(*line_num, text, mat));
+            }
+        }
+    }
+    matches
+}
+
+/// Matches of `pattern` in consecutive `lines` joined with newlines, so a
+/// match may run across line boundaries. Each match is confined to the
+/// `MULTILINE_WINDOW` lines starting at its first line; empty matches are
+/// skipped.
+pub fn search_lines_multiline(pattern: &Regex, lines: &[(usize, String)]) -> Vec<SearchMatch> {
+    let Some(&(first_line, _)) = lines.first() else {
+        return Vec::new();
+    };
+    let text = lines
+        .iter()
+        .map(|(_, line)| line.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut line_starts = Vec::with_capacity(lines.len());
+    let mut offset = 0;
+    for (_, line) in lines {
+        line_starts.push(offset);
+        offset += line.len() + 1;
+    }
+    // Line index and character column of a byte offset. An end offset right
+    // after a newline belongs to the end of the line before it.
+    let locate = |byte: usize, is_end: bool| {
+        let index = if is_end && byte > 0 {
+            line_starts.partition_point(|&start| start < byte) - 1
+        } else {
+            line_starts.partition_point(|&start| start <= byte) - 1
+        };
+        let line = &lines[index].1;
+        let col = text[line_starts[index]..byte].chars().count();
+        (index, col.min(line.chars().count()))
+    };
+
+    let span = |mat: regex::Match| {
+        let (start_index, start_col) = locate(mat.start(), false);
+        let (end_index, end_col) = locate(mat.end(), true);
+        SearchMatch {
+            line_num: first_line + start_index,
+            start_col,
+            end_line: first_line + end_index,
+            end_col,
+            groups: Vec::new(),
+        }
+    };
+
+    let has_groups = pattern.captures_len() > 1;
+    let mut matches = Vec::new();
+    let mut pos = 0;
+    while pos <= text.len() {
+        let Some(mut mat) = pattern.find_at(&text, pos) else {
+            break;
+        };
+        let (first, _) = locate(mat.start(), false);
+        let limit = line_starts
+            .get(first + MULTILINE_WINDOW)
+            .map_or(text.len(), |&start| start - 1);
+        let mut haystack = text.as_str();
+        if mat.end() > limit {
+            haystack = &text[..limit];
+            match pattern.find_at(haystack, mat.start()) {
+                Some(confined) => mat = confined,
+                None => {
+                    pos = next_char_boundary(&text, mat.start());
+                    continue;
+                }
+            }
+        }
+        if mat.is_empty() {
+            pos = next_char_boundary(&text, mat.start());
+            continue;
         }
+        let mut m = span(mat);
+        if has_groups {
+            if let Some(caps) = pattern.captures_at(haystack, mat.start()) {
+                m.groups = caps.iter().skip(1).map(|group| group.map(span)).collect();
+            }
+        }
+        matches.push(m);
+        pos = mat.end();
     }
     matches
 }
 
+/// Byte offset of the character after the one at `pos` (past the end when
+/// `pos` is the last one).
+fn next_char_boundary(text: &str, pos: usize) -> usize {
+    text[pos..].chars().next().map_or(pos + 1, |c| pos + c.len_utf8())
+}
+
+/// Multiline matches starting in lines `start_line..end_line`. Lines past
+/// `end_line` are fetched too, so matches may run on into them.
+pub fn search_range_multiline<S: FileSource + ?Sized>(
+    source: &S,
+    pattern: &Regex,
+    start_line: usize,
+    end_line: usize,
+) -> error::Result<Vec<SearchMatch>> {
+    let fetch_end = (end_line + MULTILINE_WINDOW - 1).min(source.line_count());
+    let lines = source.get_lines(start_line, fetch_end.saturating_sub(start_line))?;
+    let mut matches = search_lines_multiline(pattern, &lines);
+    matches.retain(|m| m.line_num < end_line);
+    Ok(matches)
+}
+
+/// `scan_for_match` for multiline searches: the first match starting after
+/// `from_line` (or the last one starting before it, going backward).
+pub fn scan_for_multiline_match<S: FileSource + ?Sized>(
+    source: &S,
+    pattern: &Regex,
+    from_line: usize,
+    direction: SearchDirection,
+    chunk_size: usize,
+    cancel: &CancelToken,
+) -> error::Result<Option<SearchMatch>> {
+    let total_lines = source.line_count();
+    let chunk_size = chunk_size.max(1);
+    match direction {
+        SearchDirection::Forward => {
+            let mut current = from_line + 1;
+            while current < total_lines {
+                cancel.check()?;
+                let end = (current + chunk_size).min(total_lines);
+                let matches = search_range_multiline(source, pattern, current, end)?;
+                if let Some(found) = matches.into_iter().next() {
+                    return Ok(Some(found));
+                }
+                current = end;
+            }
+        }
+        SearchDirection::Backward => {
+            let mut current_end = from_line.min(total_lines);
+            while current_end > 0 {
+                cancel.check()?;
+                let start = current_end.saturating_sub(chunk_size);
+                let matches = search_range_multiline(source, pattern, start, current_end)?;
+                if let Some(found) = matches.into_iter().next_back() {
+                    return Ok(Some(found));
+                }
+                current_end = start;
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Find the first match after `from_line` (or the first match on the last
+/// matching line before it, going backward) by searching `chunk_size` lines
+/// at a time with `FileSource::search_range`.
+pub fn scan_for_match<S: FileSource + ?Sized>(
+    source: &S,
+    pattern: &Regex,
+    from_line: usize,
+    direction: SearchDirection,
+    chunk_size: usize,
+    cancel: &CancelToken,
+) -> error::Result<Option<SearchMatch>> {
+    let total_lines = source.line_count();
+    match direction {
+        SearchDirection::Forward => {
+            let mut current = from_line + 1;
+            while current < total_lines {
+                cancel.check()?;
+                let end = (current + chunk_size).min(total_lines);
+                let matches = source.search_range(pattern, current, end)?;
+                if let Some(found) = matches.into_iter().next() {
+                    return Ok(Some(found));
+                }
+                current = end;
+            }
+        }
+        SearchDirection::Backward => {
+            let mut current_end = from_line.min(total_lines);
+            while current_end > 0 {
+                cancel.check()?;
+                let start = current_end.saturating_sub(chunk_size);
+                let matches = source.search_range(pattern, start, current_end)?;
+                if let Some(last) = matches.last() {
+                    let line_num = last.line_num;
+                    return Ok(matches.into_iter().find(|m| m.line_num == line_num));
+                }
+                current_end = start;
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Tells a long search that a newer one replaced it. Clones share the flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// `Err(PogError::Cancelled)` once cancelled, for use between chunks.
+    pub fn check(&self) -> error::Result<()> {
+        if self.is_cancelled() {
+            Err(PogError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum SearchDirection {
     Forward,
     Backward,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_config_for_latency() {
+        let local = SearchConfig::for_latency(Duration::from_micros(200));
+        assert_eq!(local, SearchConfig::default());
+
+        let remote = SearchConfig::for_latency(Duration::from_millis(120));
+        assert!(remote.buffer_lines > local.buffer_lines);
+        assert!(remote.chunk_size > local.chunk_size);
+        assert_eq!(remote.research_margin, remote.buffer_lines / 2);
+    }
+
+    #[test]
+    fn test_search_config_overrides() {
+        let config = Config {
+            search_buffer_lines: Some(40),
+            search_chunk_size: Some(250),
+            ..Config::default()
+        };
+        let tuned = SearchConfig::default().with_overrides(&config);
+        assert_eq!(tuned.buffer_lines, 40);
+        assert_eq!(tuned.chunk_size, 250);
+        // Margin can never exceed the buffer it is measured against
+        assert_eq!(tuned.research_margin, 40);
+    }
+
+    #[test]
+    fn test_invalidate_matches_keeps_pattern() {
+        let mut state = SearchState::new();
+        state.set_pattern("err", CaseMode::Sensitive, false, false).unwrap();
+        state.update_matches(
+            vec![SearchMatch {
+                line_num: 5,
+                start_col: 0,
+                end_line: 5,
+                end_col: 3,
+                groups: Vec::new(),
+            }],
+            (0, 100),
+        );
+        assert!(!state.needs_research(10, 10, 5));
+
+        state.invalidate_matches();
+        assert!(state.is_active);
+        assert_eq!(state.pattern_str, "err");
+        assert!(state.current_match().is_none());
+        assert!(state.needs_research(10, 10, 5));
+    }
+
+    #[test]
+    fn test_stale_match_count_ignored() {
+        let mut state = SearchState::new();
+        state.set_pattern("err", CaseMode::Sensitive, false, false).unwrap();
+        state.count_request_id = Some(2);
+        state.update_match_count(1, 7, vec![(3, 0)], MatchDensity::default());
+        assert_eq!(state.total_matches, None);
+        state.update_match_count(2, 2, vec![(3, 0), (9, 4)], MatchDensity::default());
+        assert_eq!(state.total_matches, Some(2));
+        assert_eq!(state.match_positions, vec![(3, 0), (9, 4)]);
+
+        state.set_pattern("warn", CaseMode::Sensitive, false, false).unwrap();
+        assert_eq!(state.total_matches, None);
+        assert!(state.match_positions.is_empty());
+    }
+
+    #[test]
+    fn test_match_density_stays_bounded() {
+        let mut density = MatchDensity::default();
+        for line in [0, 0, 5, 2047] {
+            density.add(line);
+        }
+        assert_eq!(
+            density.spans().collect::<Vec<_>>(),
+            vec![(0, 1, 2), (5, 1, 1), (2047, 1, 1)]
+        );
+
+        // A match past the last stretch merges neighbours instead of growing
+        density.add(5000);
+        assert_eq!(density.buckets.len(), 1251);
+        assert_eq!(
+            density.spans().collect::<Vec<_>>(),
+            vec![(0, 4, 2), (4, 4, 1), (2044, 4, 1), (5000, 4, 1)]
+        );
+        assert_eq!(density.max(), 2);
+        assert_eq!(MatchDensity::default().max(), 0);
+    }
+
+    #[test]
+    fn test_match_ordinal() {
+        let at = |line_num, start_col| SearchMatch {
+            line_num,
+            start_col,
+            end_line: line_num,
+            end_col: start_col + 3,
+            groups: Vec::new(),
+        };
+        let mut state = SearchState::new();
+        state.set_pattern("err", CaseMode::Sensitive, false, false).unwrap();
+        state.count_request_id = Some(1);
+        assert_eq!(state.match_ordinal(&at(3, 0)), None);
+
+        state.update_match_count(1, 4, vec![(3, 0), (3, 10), (9, 4)], MatchDensity::default());
+        assert_eq!(state.match_ordinal(&at(3, 0)), Some(1));
+        assert_eq!(state.match_ordinal(&at(3, 10)), Some(2));
+        assert_eq!(state.match_ordinal(&at(9, 4)), Some(3));
+        // Past the recorded positions the ordinal is unknown
+        assert_eq!(state.match_ordinal(&at(12, 0)), None);
+    }
+
+    #[test]
+    fn test_case_modes() {
+        assert!(!CaseMode::Sensitive.ignores_case("error"));
+        assert!(CaseMode::Insensitive.ignores_case("Error"));
+        assert!(CaseMode::Smart.ignores_case("error"));
+        assert!(!CaseMode::Smart.ignores_case("Error"));
+        // Escapes are not letters of the pattern
+        assert!(CaseMode::Smart.ignores_case(r"user\S+\W"));
+        assert_eq!(CaseMode::parse("Smart"), Ok(CaseMode::Smart));
+        assert!(CaseMode::parse("upper").is_err());
+
+        let mut state = SearchState::new();
+        state.set_pattern("error", CaseMode::Smart, false, false).unwrap();
+        assert_eq!(state.pattern_str, "(?i)error");
+        assert!(state.pattern.as_ref().unwrap().is_match("ERROR: disk full"));
+        // The worker compiles `pattern_str` and must agree
+        assert!(Regex::new(&state.pattern_str).unwrap().is_match("Error"));
+
+        state.set_pattern("Error", CaseMode::Smart, false, false).unwrap();
+        assert_eq!(state.pattern_str, "Error");
+        assert!(!state.pattern.as_ref().unwrap().is_match("error"));
+    }
+
+    #[test]
+    fn test_literal_search() {
+        let text = r"a[1]+(b)|c.d*e?{2}^$\f -#~&";
+        let escaped = escape_literal(text);
+        let regex = Regex::new(&format!("^{}$", escaped)).unwrap();
+        assert!(regex.is_match(text));
+        assert!(!Regex::new(&escaped).unwrap().is_match("a1+b"));
+
+        let mut state = SearchState::new();
+        assert!(state.set_pattern("(unclosed", CaseMode::Sensitive, false, false).is_err());
+        state.set_pattern("(unclosed", CaseMode::Smart, true, false).unwrap();
+        assert_eq!(state.pattern_str, r"(?i)\(unclosed");
+        assert!(Regex::new(&state.pattern_str).unwrap().is_match("call (UNCLOSED"));
+    }
+
+    #[test]
+    fn test_search_config_window() {
+        let config = SearchConfig::default();
+        assert_eq!(config.window(0, 50, 10_000), (0, 150));
+        assert_eq!(config.window(500, 50, 10_000), (400, 650));
+        assert_eq!(config.window(9_980, 50, 10_000), (9_880, 10_000));
+    }
+
+    #[test]
+    fn test_search_lines_ascii_columns() {
+        let regex = Regex::new("error").unwrap();
+        let lines = vec![(3, "an error here".to_string())];
+        let matches = search_lines(&regex, &lines);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].line_num, 3);
+        assert_eq!((matches[0].start_col, matches[0].end_col), (3, 8));
+    }
+
+    #[test]
+    fn test_search_lines_uses_char_columns() {
+        // Hebrew and Arabic letters are two bytes each in UTF-8
+        let regex = Regex::new("error").unwrap();
+        let lines = vec![(0, "שלום error مرحبا".to_string())];
+        let matches = search_lines(&regex, &lines);
+        assert_eq!((matches[0].start_col, matches[0].end_col), (5, 10));
+
+        let regex = Regex::new("مرحبا").unwrap();
+        let matches = search_lines(&regex, &lines);
+        assert_eq!((matches[0].start_col, matches[0].end_col), (11, 16));
+    }
+
+    #[test]
+    fn test_search_lines_capture_groups() {
+        let regex = Regex::new(r"user=(\w+)(?: id=(\d+))?").unwrap();
+        let lines = vec![(0, "ünï user=bob id=42, user=eve".to_string())];
+        let matches = search_lines(&regex, &lines);
+        let spans: Vec<Vec<Option<(usize, usize)>>> = matches
+            .iter()
+            .map(|m| {
+                m.groups
+                    .iter()
+                    .map(|g| g.as_ref().map(|g| (g.start_col, g.end_col)))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            spans,
+            vec![vec![Some((9, 12)), Some((16, 18))], vec![Some((25, 28)), None]]
+        );
+
+        // Without groups there is nothing to capture
+        let regex = Regex::new("user").unwrap();
+        assert!(search_lines(&regex, &lines)[0].groups.is_empty());
+    }
+
+    fn numbered(lines: &[&str]) -> Vec<(usize, String)> {
+        lines.iter().enumerate().map(|(i, l)| (i + 10, l.to_string())).collect()
+    }
+
+    #[test]
+    fn test_search_lines_multiline_spans_lines() {
+        let lines = numbered(&[
+            "ERROR worker crashed",
+            "  at parse (input.rs:12)",
+            "  at main (main.rs:3)",
+            "INFO restarted",
+        ]);
+        let regex = Regex::new(r"crashed\n(?:  at .*\n)*  at main").unwrap();
+        let matches = search_lines_multiline(&regex, &lines);
+        assert_eq!(matches.len(), 1);
+        let m = &matches[0];
+        assert_eq!((m.line_num, m.start_col, m.end_line, m.end_col), (10, 13, 12, 9));
+        assert_eq!(m.columns_on(10), Some((13, usize::MAX)));
+        assert_eq!(m.columns_on(11), Some((0, usize::MAX)));
+        assert_eq!(m.columns_on(12), Some((0, 9)));
+        assert_eq!(m.columns_on(13), None);
+
+        // Groups may span lines too
+        let regex = Regex::new(r"(crashed)\n(  at \w+)").unwrap();
+        let groups = &search_lines_multiline(&regex, &lines)[0].groups;
+        let group_lines: Vec<_> = groups
+            .iter()
+            .flatten()
+            .map(|g| (g.line_num, g.start_col, g.end_col))
+            .collect();
+        assert_eq!(group_lines, vec![(10, 13, 20), (11, 0, 10)]);
+
+        // Single-line matches look like those of `search_lines`
+        let regex = Regex::new("at").unwrap();
+        let matches = search_lines_multiline(&regex, &lines);
+        assert_eq!(matches.len(), 2);
+        assert_eq!((matches[0].line_num, matches[0].end_line), (11, 11));
+
+        // A match ending with a newline ends on the line it breaks
+        let regex = Regex::new(r"restarted|main.rs:3\)\n").unwrap();
+        let m = &search_lines_multiline(&regex, &lines)[0];
+        assert_eq!((m.line_num, m.end_line, m.end_col), (12, 12, 21));
+    }
+
+    #[test]
+    fn test_search_lines_multiline_confined_to_window() {
+        let mut text = vec!["BEGIN"];
+        text.extend(std::iter::repeat_n("body", MULTILINE_WINDOW + 5));
+        let lines = numbered(&text);
+        let regex = Regex::new(r"(?s)BEGIN.*body").unwrap();
+        let matches = search_lines_multiline(&regex, &lines);
+        assert_eq!(matches[0].line_num, 10);
+        assert_eq!(matches[0].end_line, 10 + MULTILINE_WINDOW - 1);
+    }
+
+    #[test]
+    fn test_scan_for_multiline_match() {
+        struct Lines(Vec<(usize, String)>);
+        impl FileSource for Lines {
+            fn line_count(&self) -> usize {
+                self.0.len()
+            }
+            fn file_size(&self) -> error::Result<u64> {
+                Ok(0)
+            }
+            fn get_line(&self, line_num: usize) -> error::Result<Option<String>> {
+                Ok(self.0.get(line_num).map(|(_, l)| l.clone()))
+            }
+            fn get_lines(&self, start: usize, count: usize) -> error::Result<Vec<(usize, String)>> {
+                Ok(self.0.iter().skip(start).take(count).cloned().collect())
+            }
+            fn display_name(&self) -> &str {
+                "lines"
+            }
+        }
+        let source = Lines(
+            ["a", "panic", "  frame", "b", "c", "panic", "  frame", "d"]
+                .iter()
+                .enumerate()
+                .map(|(i, l)| (i, l.to_string()))
+                .collect(),
+        );
+        let regex = Regex::new(r"panic\n  frame").unwrap();
+        let cancel = CancelToken::default();
+        // A chunk boundary between the two lines does not hide the match
+        let scan = |from_line, direction, chunk_size| {
+            scan_for_multiline_match(&source, &regex, from_line, direction, chunk_size, &cancel)
+                .unwrap()
+                .unwrap()
+        };
+        let found = scan(2, SearchDirection::Forward, 3);
+        assert_eq!((found.line_num, found.end_line), (5, 6));
+        let found = scan(5, SearchDirection::Backward, 1);
+        assert_eq!((found.line_num, found.end_line), (1, 2));
+    }
+}
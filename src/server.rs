@@ -1,9 +1,10 @@
 use std::io::{BufRead, BufReader, Write};
-use std::net::{TcpListener, TcpStream};
-use std::sync::mpsc;
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread::{self, JoinHandle};
 
-use crate::commands::{parse_command, CommandResponse, PogCommand};
+use crate::commands::{parse_command, CommandResponse, EventTopic, PogCommand, ViewerEvent};
 
 pub struct CommandRequest {
     pub command: PogCommand,
@@ -12,6 +13,43 @@ pub struct CommandRequest {
 
 const MAX_PORT_ATTEMPTS: u16 = 100;
 
+/// A subscribed connection's topics and the sender its events go through.
+type Subscriber = (Vec<EventTopic>, mpsc::Sender<String>);
+
+/// Connections that sent `subscribe`, each with the topics it asked for
+/// (all of them when empty). Cloned handles share the list.
+#[derive(Clone, Default)]
+pub struct EventHub {
+    subscribers: Arc<Mutex<Vec<Subscriber>>>,
+}
+
+impl EventHub {
+    /// Push `event` to every connection subscribed to its topic, and forget
+    /// connections that have gone.
+    pub fn publish(&self, event: &ViewerEvent) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        if subscribers.is_empty() {
+            return;
+        }
+        let topic = event.topic();
+        let line = event.to_string();
+        subscribers.retain(|(topics, tx)| {
+            !(topics.is_empty() || topics.contains(&topic)) || tx.send(line.clone()).is_ok()
+        });
+    }
+
+    pub(crate) fn subscribe(&self, topics: Vec<EventTopic>) -> mpsc::Receiver<String> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().unwrap().push((topics, tx));
+        rx
+    }
+
+    /// End every subscription.
+    fn close(&self) {
+        self.subscribers.lock().unwrap().clear();
+    }
+}
+
 fn try_bind_port(starting_port: u16) -> std::io::Result<(TcpListener, u16)> {
     for offset in 0..MAX_PORT_ATTEMPTS {
         let port = starting_port.saturating_add(offset);
@@ -33,21 +71,99 @@ fn try_bind_port(starting_port: u16) -> std::io::Result<(TcpListener, u16)> {
     ))
 }
 
+/// Each client connection's stream, to shut it down, and its thread.
+type Clients = Arc<Mutex<Vec<(TcpStream, JoinHandle<()>)>>>;
+
+/// A running command server: the accept thread and one thread per client.
+pub struct CommandServer {
+    port: u16,
+    events: EventHub,
+    stopping: Arc<AtomicBool>,
+    accept_thread: JoinHandle<()>,
+    clients: Clients,
+}
+
+impl CommandServer {
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Stop accepting connections, end every client connection once its
+    /// pending response is written, and wait for all server threads.
+    /// Requests already sent to the UI must be answered first, or this waits
+    /// for them forever.
+    pub fn shutdown(self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        // Wake the accept loop so it sees the flag
+        let _ = TcpStream::connect(("127.0.0.1", self.port));
+        let _ = self.accept_thread.join();
+
+        self.events.close();
+        let clients = std::mem::take(&mut *self.clients.lock().unwrap());
+        for (stream, _) in &clients {
+            // Only the read side: a client still gets the response it waits for
+            let _ = stream.shutdown(Shutdown::Read);
+        }
+        for (_, thread) in clients {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Handles one connection until it closes.
+pub(crate) type ClientHandler =
+    Arc<dyn Fn(TcpStream, async_channel::Sender<CommandRequest>, EventHub) + Send + Sync>;
+
+/// Start the command server on `port` or the next free port after it.
+/// Subscribed connections receive the events published on `events`.
 pub fn start_server(
     port: u16,
     command_tx: async_channel::Sender<CommandRequest>,
-) -> std::io::Result<JoinHandle<()>> {
+    events: EventHub,
+) -> std::io::Result<CommandServer> {
+    listen("pog server", port, command_tx, events, Arc::new(handle_client))
+}
+
+/// Accept connections on `port` or the next free port after it, each
+/// served by `handler` on its own thread.
+pub(crate) fn listen(
+    name: &str,
+    port: u16,
+    command_tx: async_channel::Sender<CommandRequest>,
+    events: EventHub,
+    handler: ClientHandler,
+) -> std::io::Result<CommandServer> {
     let (listener, actual_port) = try_bind_port(port)?;
-    eprintln!("pog server listening on 127.0.0.1:{}", actual_port);
+    eprintln!("{} listening on 127.0.0.1:{}", name, actual_port);
 
-    let handle = thread::spawn(move || {
+    let stopping = Arc::new(AtomicBool::new(false));
+    let clients: Clients = Arc::new(Mutex::new(Vec::new()));
+    let stopping_accept = stopping.clone();
+    let clients_accept = clients.clone();
+    let events_accept = events.clone();
+    let accept_thread = thread::spawn(move || {
         for stream in listener.incoming() {
+            if stopping_accept.load(Ordering::SeqCst) {
+                break;
+            }
             match stream {
                 Ok(stream) => {
+                    let shutdown_handle = match stream.try_clone() {
+                        Ok(s) => s,
+                        Err(e) => {
+                            eprintln!("Connection error: {}", e);
+                            continue;
+                        }
+                    };
                     let command_tx = command_tx.clone();
-                    thread::spawn(move || {
-                        handle_client(stream, command_tx);
+                    let events = events_accept.clone();
+                    let handler = handler.clone();
+                    let thread = thread::spawn(move || {
+                        handler(stream, command_tx, events);
                     });
+                    let mut clients = clients_accept.lock().unwrap();
+                    clients.retain(|(_, thread)| !thread.is_finished());
+                    clients.push((shutdown_handle, thread));
                 }
                 Err(e) => {
                     eprintln!("Connection error: {}", e);
@@ -56,10 +172,130 @@ pub fn start_server(
         }
     });
 
-    Ok(handle)
+    Ok(CommandServer {
+        port: actual_port,
+        events,
+        stopping,
+        accept_thread,
+        clients,
+    })
+}
+
+/// Commands of a connection between `begin` and `commit`, and how many lines
+/// were rejected, which discards the batch.
+#[derive(Default)]
+struct Batch {
+    commands: Vec<PogCommand>,
+    rejected: usize,
+}
+
+/// Send `command` to the window and wait for its response.
+fn run_on_ui(
+    command_tx: &async_channel::Sender<CommandRequest>,
+    command: PogCommand,
+) -> CommandResponse {
+    let (response_tx, response_rx) = mpsc::channel();
+    let request = CommandRequest {
+        command,
+        response_tx,
+    };
+
+    if command_tx.send_blocking(request).is_err() {
+        CommandResponse::Error("UI not available".to_string())
+    } else {
+        match response_rx.recv() {
+            Ok(resp) => resp,
+            Err(_) => CommandResponse::Error("no response from UI".to_string()),
+        }
+    }
+}
+
+/// What a connection sends back for one line.
+pub(crate) enum Reply {
+    Response(CommandResponse),
+    /// `OK`, then every event received until either side goes
+    Events(mpsc::Receiver<String>),
+}
+
+/// A connection's commands: queued between `begin` and `commit`, otherwise
+/// sent to the window one at a time.
+pub(crate) struct Session {
+    command_tx: async_channel::Sender<CommandRequest>,
+    events: EventHub,
+    batch: Option<Batch>,
 }
 
-fn handle_client(mut stream: TcpStream, command_tx: async_channel::Sender<CommandRequest>) {
+impl Session {
+    pub(crate) fn new(command_tx: async_channel::Sender<CommandRequest>, events: EventHub) -> Self {
+        Self {
+            command_tx,
+            events,
+            batch: None,
+        }
+    }
+
+    pub(crate) fn handle_line(&mut self, line: &str) -> Reply {
+        let response = match parse_command(line) {
+            Ok(PogCommand::Begin) if self.batch.is_some() => {
+                CommandResponse::Error("already in a batch".to_string())
+            }
+            Ok(PogCommand::Begin) => {
+                self.batch = Some(Batch::default());
+                CommandResponse::Ok(None)
+            }
+            Ok(PogCommand::Rollback) => match self.batch.take() {
+                Some(_) => CommandResponse::Ok(None),
+                None => CommandResponse::Error("no batch to roll back".to_string()),
+            },
+            Ok(PogCommand::Commit) => match self.batch.take() {
+                None => CommandResponse::Error("no batch to commit".to_string()),
+                Some(batch) if batch.rejected > 0 => CommandResponse::Error(format!(
+                    "batch discarded: {} command{} rejected",
+                    batch.rejected,
+                    if batch.rejected == 1 { "" } else { "s" }
+                )),
+                Some(batch) => run_on_ui(
+                    &self.command_tx,
+                    PogCommand::Batch {
+                        commands: batch.commands,
+                    },
+                ),
+            },
+            parsed => match self.batch.as_mut() {
+                // Queued until commit; a line that is rejected spoils the batch
+                Some(batch) => match parsed {
+                    Ok(PogCommand::Subscribe { .. }) => {
+                        batch.rejected += 1;
+                        CommandResponse::Error("subscribe cannot be batched".to_string())
+                    }
+                    Ok(cmd) => {
+                        batch.commands.push(cmd);
+                        CommandResponse::Ok(Some("queued".to_string()))
+                    }
+                    Err(e) => {
+                        batch.rejected += 1;
+                        CommandResponse::Error(e)
+                    }
+                },
+                None => match parsed {
+                    // Push mode: only events from now on
+                    Ok(PogCommand::Subscribe { topics }) => {
+                        return Reply::Events(self.events.subscribe(topics))
+                    }
+                    Ok(cmd) => run_on_ui(&self.command_tx, cmd),
+                    Err(e) => CommandResponse::Error(e),
+                },
+            },
+        };
+        Reply::Response(response)
+    }
+}
+
+fn handle_client(
+    mut stream: TcpStream,
+    command_tx: async_channel::Sender<CommandRequest>,
+    events: EventHub,
+) {
     let peer = stream
         .peer_addr()
         .map(|a| a.to_string())
@@ -73,6 +309,8 @@ fn handle_client(mut stream: TcpStream, command_tx: async_channel::Sender<Comman
         }
     };
 
+    let mut session = Session::new(command_tx, events);
+
     for line in reader.lines() {
         let line = match line {
             Ok(l) => l,
@@ -86,24 +324,20 @@ fn handle_client(mut stream: TcpStream, command_tx: async_channel::Sender<Comman
             continue;
         }
 
-        let response = match parse_command(&line) {
-            Ok(cmd) => {
-                let (response_tx, response_rx) = mpsc::channel();
-                let request = CommandRequest {
-                    command: cmd,
-                    response_tx,
-                };
-
-                if command_tx.send_blocking(request).is_err() {
-                    CommandResponse::Error("UI not available".to_string())
-                } else {
-                    match response_rx.recv() {
-                        Ok(resp) => resp,
-                        Err(_) => CommandResponse::Error("no response from UI".to_string()),
+        let response = match session.handle_line(&line) {
+            Reply::Response(response) => response,
+            Reply::Events(events) => {
+                // The connection carries only events from now on, until either side goes
+                if stream.write_all(b"OK\n").is_err() {
+                    break;
+                }
+                for event in events {
+                    if stream.write_all(format!("{}\n", event).as_bytes()).is_err() {
+                        break;
                     }
                 }
+                break;
             }
-            Err(e) => CommandResponse::Error(e),
         };
 
         let response_str = format!("{}\n", response);
@@ -117,3 +351,144 @@ fn handle_client(mut stream: TcpStream, command_tx: async_channel::Sender<Comman
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_shutdown_ends_connections() {
+        let (command_tx, command_rx) = async_channel::unbounded::<CommandRequest>();
+        let ui = thread::spawn(move || {
+            while let Ok(request) = command_rx.recv_blocking() {
+                let _ = request.response_tx.send(CommandResponse::Ok(Some("42".to_string())));
+            }
+        });
+        let server = start_server(19876, command_tx, EventHub::default()).unwrap();
+
+        let mut client = TcpStream::connect(("127.0.0.1", server.port())).unwrap();
+        client.write_all(b"lines\n").unwrap();
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+        let mut response = String::new();
+        reader.read_line(&mut response).unwrap();
+        assert_eq!(response, "OK 42\n");
+
+        server.shutdown();
+        // The server closed the connection and dropped its command sender
+        let mut rest = Vec::new();
+        assert_eq!(client.read_to_end(&mut rest).unwrap(), 0);
+        ui.join().unwrap();
+    }
+
+    #[test]
+    fn test_batch_runs_on_commit() {
+        let (command_tx, command_rx) = async_channel::unbounded::<CommandRequest>();
+        let ui = thread::spawn(move || {
+            let request = command_rx.recv_blocking().unwrap();
+            let response = match request.command {
+                PogCommand::Batch { commands } => format!("{} commands", commands.len()),
+                _ => "not a batch".to_string(),
+            };
+            let _ = request.response_tx.send(CommandResponse::Ok(Some(response)));
+        });
+        let server = start_server(19896, command_tx, EventHub::default()).unwrap();
+
+        let mut client = TcpStream::connect(("127.0.0.1", server.port())).unwrap();
+        client
+            .write_all(b"commit\nbegin\nmark 1 red\nbogus\ncommit\nbegin\nmark 1 red\nmark 2 red\ncommit\n")
+            .unwrap();
+        let reader = BufReader::new(client.try_clone().unwrap());
+        let responses: Vec<String> = reader.lines().take(9).map(|l| l.unwrap()).collect();
+        assert_eq!(
+            responses,
+            [
+                "ERROR no batch to commit",
+                "OK",
+                "OK queued",
+                "ERROR unknown command: bogus",
+                "ERROR batch discarded: 1 command rejected",
+                "OK",
+                "OK queued",
+                "OK queued",
+                "OK 2 commands",
+            ]
+        );
+        ui.join().unwrap();
+        server.shutdown();
+    }
+
+    #[test]
+    fn test_interleaved_batches_run_whole() {
+        let (command_tx, command_rx) = async_channel::unbounded::<CommandRequest>();
+        let ui = thread::spawn(move || {
+            let mut batches = Vec::new();
+            for _ in 0..2 {
+                let request = command_rx.recv_blocking().unwrap();
+                let PogCommand::Batch { commands } = request.command else {
+                    panic!("not a batch");
+                };
+                batches.push(commands);
+                let _ = request.response_tx.send(CommandResponse::Ok(None));
+            }
+            batches
+        });
+        let server = start_server(19906, command_tx, EventHub::default()).unwrap();
+
+        let connect = || {
+            let client = TcpStream::connect(("127.0.0.1", server.port())).unwrap();
+            let reader = BufReader::new(client.try_clone().unwrap());
+            (client, reader)
+        };
+        let send = |(client, reader): &mut (TcpStream, BufReader<TcpStream>), line: &str| {
+            client.write_all(format!("{}\n", line).as_bytes()).unwrap();
+            let mut response = String::new();
+            reader.read_line(&mut response).unwrap();
+            response
+        };
+        let mut first = connect();
+        let mut second = connect();
+        send(&mut first, "begin");
+        send(&mut second, "begin");
+        send(&mut first, "mark 1 red");
+        send(&mut second, "mark 2 blue");
+        send(&mut first, "mark 3 red");
+        assert_eq!(send(&mut second, "commit"), "OK\n");
+        assert_eq!(send(&mut first, "commit"), "OK\n");
+
+        let mark = |line, color: &str| PogCommand::Mark {
+            line,
+            region: None,
+            color: color.to_string(),
+        };
+        assert_eq!(
+            ui.join().unwrap(),
+            [vec![mark(2, "blue")], vec![mark(1, "red"), mark(3, "red")]]
+        );
+        server.shutdown();
+    }
+
+    #[test]
+    fn test_subscribe_receives_events() {
+        let (command_tx, _command_rx) = async_channel::unbounded::<CommandRequest>();
+        let events = EventHub::default();
+        let server = start_server(19886, command_tx, events.clone()).unwrap();
+
+        let mut client = TcpStream::connect(("127.0.0.1", server.port())).unwrap();
+        client.write_all(b"subscribe marks\n").unwrap();
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "OK\n");
+
+        events.publish(&ViewerEvent::Viewport { top: 1, bottom: 50 });
+        events.publish(&ViewerEvent::Marks { count: 3 });
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "EVENT marks 3\n");
+
+        server.shutdown();
+        line.clear();
+        assert_eq!(reader.read_line(&mut line).unwrap(), 0);
+    }
+}
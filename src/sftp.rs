@@ -0,0 +1,425 @@
+use std::collections::HashMap;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::process::{Child, Stdio};
+
+use crate::ssh::SshSession;
+
+const SFTP_VERSION: u32 = 3;
+
+const FXP_INIT: u8 = 1;
+const FXP_VERSION: u8 = 2;
+const FXP_OPEN: u8 = 3;
+const FXP_CLOSE: u8 = 4;
+const FXP_READ: u8 = 5;
+const FXP_FSTAT: u8 = 8;
+const FXP_STATUS: u8 = 101;
+const FXP_HANDLE: u8 = 102;
+const FXP_DATA: u8 = 103;
+const FXP_ATTRS: u8 = 105;
+
+const FXF_READ: u32 = 0x1;
+const FX_EOF: u32 = 1;
+const FX_NO_SUCH_FILE: u32 = 2;
+const FX_PERMISSION_DENIED: u32 = 3;
+const ATTR_SIZE: u32 = 0x1;
+
+/// Bytes asked for per READ request; every server supports at least this.
+const READ_BLOCK: u64 = 32 << 10;
+/// READ requests sent before waiting for the first reply.
+const MAX_IN_FLIGHT: usize = 64;
+
+/// An open remote file.
+pub struct Handle(Vec<u8>);
+
+enum Reply {
+    Status { code: u32, message: String },
+    Handle(Vec<u8>),
+    Data(Vec<u8>),
+    Attrs { size: Option<u64> },
+}
+
+/// Minimal SFTP (version 3) client: just enough to open a file, get its
+/// size and read byte ranges.
+///
+/// Runs over the `sftp` subsystem of an `ssh` child process, so it shares
+/// authentication and connection multiplexing with `SshSession`. Reads are
+/// pipelined to hide the round trip per block.
+pub struct SftpClient {
+    reader: Box<dyn Read + Send>,
+    writer: Box<dyn Write + Send>,
+    child: Option<Child>,
+    next_id: u32,
+}
+
+impl SftpClient {
+    pub fn connect(session: &SshSession) -> io::Result<Self> {
+        let mut child = session
+            .subsystem("sftp")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let reader = BufReader::new(child.stdout.take().expect("piped stdout"));
+        let writer = BufWriter::new(child.stdin.take().expect("piped stdin"));
+        Self::handshake(Box::new(reader), Box::new(writer), Some(child))
+    }
+
+    fn handshake(
+        reader: Box<dyn Read + Send>,
+        writer: Box<dyn Write + Send>,
+        child: Option<Child>,
+    ) -> io::Result<Self> {
+        let mut client = Self {
+            reader,
+            writer,
+            child,
+            next_id: 0,
+        };
+
+        let mut init = vec![FXP_INIT];
+        put_u32(&mut init, SFTP_VERSION);
+        client.send(&init)?;
+        let packet = client.recv_packet()?;
+        if packet.first() != Some(&FXP_VERSION) {
+            return Err(protocol_error("expected SSH_FXP_VERSION"));
+        }
+        Ok(client)
+    }
+
+    pub fn open(&mut self, path: &str) -> io::Result<Handle> {
+        let id = self.request(FXP_OPEN, |body| {
+            put_string(body, path.as_bytes());
+            put_u32(body, FXF_READ);
+            put_u32(body, 0); // no attributes
+        })?;
+        match self.recv(id)? {
+            Reply::Handle(handle) => Ok(Handle(handle)),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    pub fn size(&mut self, handle: &Handle) -> io::Result<u64> {
+        let id = self.request(FXP_FSTAT, |body| put_string(body, &handle.0))?;
+        match self.recv(id)? {
+            Reply::Attrs { size: Some(size) } => Ok(size),
+            Reply::Attrs { size: None } => Err(protocol_error("server did not report a size")),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    /// Read up to `len` bytes at `offset`; fewer only at end of file.
+    pub fn read(&mut self, handle: &Handle, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(len as usize);
+        while (data.len() as u64) < len {
+            let start = offset + data.len() as u64;
+            let end = offset + len;
+
+            let mut requests = Vec::new();
+            let mut block_start = start;
+            while block_start < end && requests.len() < MAX_IN_FLIGHT {
+                let block_len = READ_BLOCK.min(end - block_start);
+                let id = self.request(FXP_READ, |body| {
+                    put_string(body, &handle.0);
+                    put_u64(body, block_start);
+                    put_u32(body, block_len as u32);
+                })?;
+                requests.push((id, block_len));
+                block_start += block_len;
+            }
+
+            let mut replies = HashMap::new();
+            for _ in 0..requests.len() {
+                let (id, reply) = self.recv_any()?;
+                replies.insert(id, reply);
+            }
+
+            // Keep the contiguous prefix; after a short block the rest is re-requested
+            let before = data.len();
+            let mut eof = false;
+            for (id, block_len) in requests {
+                let reply = replies
+                    .remove(&id)
+                    .ok_or_else(|| protocol_error("missing reply to read"))?;
+                if eof {
+                    continue;
+                }
+                match reply {
+                    Reply::Data(bytes) => {
+                        let short = (bytes.len() as u64) < block_len;
+                        data.extend_from_slice(&bytes);
+                        if short {
+                            break;
+                        }
+                    }
+                    Reply::Status { code: FX_EOF, .. } => eof = true,
+                    reply => return Err(unexpected(reply)),
+                }
+            }
+            if eof || data.len() == before {
+                break;
+            }
+        }
+        Ok(data)
+    }
+
+    pub fn close(&mut self, handle: &Handle) -> io::Result<()> {
+        let id = self.request(FXP_CLOSE, |body| put_string(body, &handle.0))?;
+        match self.recv(id)? {
+            Reply::Status { code: 0, .. } => Ok(()),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    /// Send a request of type `kind` with a fresh id, its fields written by `fields`.
+    fn request(&mut self, kind: u8, fields: impl FnOnce(&mut Vec<u8>)) -> io::Result<u32> {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        let mut body = vec![kind];
+        put_u32(&mut body, id);
+        fields(&mut body);
+        self.send(&body)?;
+        Ok(id)
+    }
+
+    fn send(&mut self, body: &[u8]) -> io::Result<()> {
+        self.writer.write_all(&(body.len() as u32).to_be_bytes())?;
+        self.writer.write_all(body)?;
+        self.writer.flush()
+    }
+
+    fn recv_packet(&mut self) -> io::Result<Vec<u8>> {
+        let mut len = [0; 4];
+        self.reader.read_exact(&mut len)?;
+        let mut body = vec![0; u32::from_be_bytes(len) as usize];
+        self.reader.read_exact(&mut body)?;
+        Ok(body)
+    }
+
+    /// The reply to request `id`, which must be the only one outstanding.
+    fn recv(&mut self, id: u32) -> io::Result<Reply> {
+        let (reply_id, reply) = self.recv_any()?;
+        if reply_id != id {
+            return Err(protocol_error("reply to an unknown request"));
+        }
+        match reply {
+            Reply::Status { code, message } if code != 0 && code != FX_EOF => {
+                Err(status_error(code, message))
+            }
+            reply => Ok(reply),
+        }
+    }
+
+    fn recv_any(&mut self) -> io::Result<(u32, Reply)> {
+        let packet = self.recv_packet()?;
+        let mut fields = Fields(&packet);
+        let kind = fields.u8()?;
+        let id = fields.u32()?;
+        let reply = match kind {
+            FXP_STATUS => {
+                let code = fields.u32()?;
+                // Version 3 servers may omit the message
+                let message = fields
+                    .string()
+                    .map(|m| String::from_utf8_lossy(m).into_owned())
+                    .unwrap_or_default();
+                Reply::Status { code, message }
+            }
+            FXP_HANDLE => Reply::Handle(fields.string()?.to_vec()),
+            FXP_DATA => Reply::Data(fields.string()?.to_vec()),
+            FXP_ATTRS => {
+                let flags = fields.u32()?;
+                let size = if flags & ATTR_SIZE != 0 {
+                    Some(fields.u64()?)
+                } else {
+                    None
+                };
+                Reply::Attrs { size }
+            }
+            _ => return Err(protocol_error("unknown reply type")),
+        };
+        Ok((id, reply))
+    }
+}
+
+impl Drop for SftpClient {
+    fn drop(&mut self) {
+        if let Some(child) = &mut self.child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Cursor over the fields of a received packet.
+struct Fields<'a>(&'a [u8]);
+
+impl<'a> Fields<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(protocol_error("truncated packet"));
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> io::Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_u64(buf: &mut Vec<u8>, value: u64) {
+    buf.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_string(buf: &mut Vec<u8>, value: &[u8]) {
+    put_u32(buf, value.len() as u32);
+    buf.extend_from_slice(value);
+}
+
+fn protocol_error(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("sftp: {}", message))
+}
+
+fn status_error(code: u32, message: String) -> io::Error {
+    let kind = match code {
+        FX_NO_SUCH_FILE => io::ErrorKind::NotFound,
+        FX_PERMISSION_DENIED => io::ErrorKind::PermissionDenied,
+        _ => io::ErrorKind::Other,
+    };
+    io::Error::new(kind, format!("sftp: {} (status {})", message, code))
+}
+
+fn unexpected(reply: Reply) -> io::Error {
+    match reply {
+        Reply::Status { code, message } => status_error(code, message),
+        _ => protocol_error("unexpected reply"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answer requests on `reader`/`writer` as a server holding one file, `/log`,
+    /// that returns at most `max_read` bytes per READ.
+    fn serve(
+        mut reader: io::PipeReader,
+        mut writer: io::PipeWriter,
+        file: Vec<u8>,
+        max_read: usize,
+    ) {
+        let mut reply = |body: Vec<u8>| {
+            writer
+                .write_all(&(body.len() as u32).to_be_bytes())
+                .unwrap();
+            writer.write_all(&body).unwrap();
+        };
+        loop {
+            let mut len = [0; 4];
+            if reader.read_exact(&mut len).is_err() {
+                return;
+            }
+            let mut packet = vec![0; u32::from_be_bytes(len) as usize];
+            reader.read_exact(&mut packet).unwrap();
+            let mut fields = Fields(&packet);
+            let kind = fields.u8().unwrap();
+            if kind == FXP_INIT {
+                let mut body = vec![FXP_VERSION];
+                put_u32(&mut body, SFTP_VERSION);
+                reply(body);
+                continue;
+            }
+            let id = fields.u32().unwrap();
+            let status = |code: u32| {
+                let mut body = vec![FXP_STATUS];
+                put_u32(&mut body, id);
+                put_u32(&mut body, code);
+                put_string(&mut body, b"status");
+                put_string(&mut body, b"");
+                body
+            };
+            let mut body = Vec::new();
+            match kind {
+                FXP_OPEN if fields.string().unwrap() == b"/log" => {
+                    body.push(FXP_HANDLE);
+                    put_u32(&mut body, id);
+                    put_string(&mut body, b"h1");
+                }
+                FXP_OPEN => body = status(FX_NO_SUCH_FILE),
+                FXP_FSTAT => {
+                    body.push(FXP_ATTRS);
+                    put_u32(&mut body, id);
+                    put_u32(&mut body, ATTR_SIZE);
+                    put_u64(&mut body, file.len() as u64);
+                }
+                FXP_READ => {
+                    fields.string().unwrap();
+                    let offset = fields.u64().unwrap() as usize;
+                    let len = (fields.u32().unwrap() as usize).min(max_read);
+                    if offset >= file.len() {
+                        body = status(FX_EOF);
+                    } else {
+                        body.push(FXP_DATA);
+                        put_u32(&mut body, id);
+                        put_string(&mut body, &file[offset..(offset + len).min(file.len())]);
+                    }
+                }
+                FXP_CLOSE => body = status(0),
+                _ => panic!("unexpected request {}", kind),
+            }
+            reply(body);
+        }
+    }
+
+    fn client_for(file: Vec<u8>, max_read: usize) -> SftpClient {
+        let (server_reader, client_writer) = io::pipe().unwrap();
+        let (client_reader, server_writer) = io::pipe().unwrap();
+        std::thread::spawn(move || serve(server_reader, server_writer, file, max_read));
+        SftpClient::handshake(Box::new(client_reader), Box::new(client_writer), None).unwrap()
+    }
+
+    #[test]
+    fn test_read_ranges() {
+        let file: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+        let mut client = client_for(file.clone(), usize::MAX);
+        let handle = client.open("/log").unwrap();
+        assert_eq!(client.size(&handle).unwrap(), file.len() as u64);
+        assert_eq!(client.read(&handle, 10, 100).unwrap(), &file[10..110]);
+        // Spans more blocks than are sent at once
+        assert_eq!(client.read(&handle, 0, file.len() as u64).unwrap(), file);
+        // Stops at end of file
+        assert_eq!(
+            client.read(&handle, 299_990, 100).unwrap(),
+            &file[299_990..]
+        );
+        client.close(&handle).unwrap();
+
+        let missing = client.open("/nope").err().unwrap();
+        assert_eq!(missing.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_short_reads() {
+        let file: Vec<u8> = (0..100_000u32).map(|i| (i % 7) as u8).collect();
+        let mut client = client_for(file.clone(), 1000);
+        let handle = client.open("/log").unwrap();
+        assert_eq!(client.read(&handle, 5, 50_000).unwrap(), &file[5..50_005]);
+    }
+}
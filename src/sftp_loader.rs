@@ -0,0 +1,200 @@
+use std::io;
+use std::sync::{Mutex, RwLock};
+
+use crate::cache::{LineCache, LineCounter, CHUNK_SIZE};
+use crate::encoding::{TextEncoding, DETECT_SAMPLE_LEN};
+use crate::error::{PogError, Result};
+use crate::file_loader::{build_line_index, line_at};
+use crate::file_source::FileSource;
+use crate::sftp::{Handle, SftpClient};
+use crate::ssh::{SshSession, SshTarget};
+
+const MAX_CACHED_CHUNKS: usize = 20;
+/// Bytes read per request while indexing.
+const INDEX_READ_LEN: u64 = 4 << 20;
+
+/// A remote file read over SFTP (`--remote-transport sftp`).
+///
+/// Like `S3File`, opening reads the file once to record where every
+/// `CHUNK_SIZE`th line starts, and chunks are then fetched as exact byte
+/// ranges into a `LineCache`. Unlike the shell transport nothing runs in the
+/// remote shell, so paths need no quoting and no remote tools are required.
+/// It does not reconnect: once the connection drops, fetches fail with the
+/// error, and it reports no `connection_state`.
+pub struct SftpFile {
+    host: String,
+    path: String,
+    display_name: String,
+    /// Declared before `session` so the sftp process ends before the master
+    client: Mutex<SftpClient>,
+    handle: Handle,
+    _session: SshSession,
+    index: LineCounter,
+    cache: RwLock<LineCache>,
+}
+
+impl SftpFile {
+    /// Open `host:path`, detecting its encoding from the first bytes if none is given.
+    pub fn open(target: &SshTarget, path: &str, encoding: Option<TextEncoding>) -> Result<Self> {
+        let host = &target.to_string();
+        let sftp_error = |e: io::Error| match e.kind() {
+            io::ErrorKind::NotFound => PogError::FileNotFound {
+                path: format!("{}:{}", host, path),
+            },
+            io::ErrorKind::PermissionDenied => PogError::PermissionDenied {
+                path: format!("{}:{}", host, path),
+            },
+            _ => PogError::Ssh {
+                host: host.to_string(),
+                message: e.to_string(),
+            },
+        };
+
+        let session = SshSession::new(target);
+        let mut client = SftpClient::connect(&session).map_err(sftp_error)?;
+        let handle = client.open(path).map_err(sftp_error)?;
+        let size = client.size(&handle).map_err(sftp_error)?;
+
+        let encoding = match encoding {
+            Some(encoding) => encoding,
+            None => TextEncoding::detect(
+                &client
+                    .read(&handle, 0, DETECT_SAMPLE_LEN as u64)
+                    .map_err(sftp_error)?,
+            ),
+        };
+        let mut index = LineCounter::new(encoding);
+        while index.byte_len() < size {
+            let data = client
+                .read(&handle, index.byte_len(), INDEX_READ_LEN)
+                .map_err(sftp_error)?;
+            if data.is_empty() {
+                break;
+            }
+            index.feed(&data);
+        }
+
+        let details: Vec<String> = target
+            .config_route()
+            .into_iter()
+            .chain(Some("sftp".to_string()))
+            .chain((!encoding.is_utf8()).then(|| encoding.name().to_string()))
+            .collect();
+        let display_name = format!("{}:{} ({})", host, path, details.join(", "));
+
+        Ok(Self {
+            host: host.to_string(),
+            path: path.to_string(),
+            display_name,
+            client: Mutex::new(client),
+            handle,
+            _session: session,
+            index,
+            cache: RwLock::new(LineCache::new(MAX_CACHED_CHUNKS)),
+        })
+    }
+
+    fn fetch_chunk(&self, chunk_start: usize) -> Result<Vec<String>> {
+        let (start, end) = self.index.chunk_bytes(chunk_start);
+        if end <= start {
+            return Ok(vec![String::new()]);
+        }
+
+        let data = self
+            .client
+            .lock()
+            .unwrap()
+            .read(&self.handle, start, end - start)
+            .map_err(|e| PogError::Ssh {
+                host: self.host.clone(),
+                message: format!("reading {}: {}", self.path, e),
+            })?;
+
+        let encoding = self.index.encoding();
+        let offsets = build_line_index(&data, encoding);
+        Ok((0..offsets.len())
+            .filter_map(|i| line_at(&data, &offsets, i, encoding))
+            .collect())
+    }
+
+    fn ensure_chunk_loaded(&self, chunk_start: usize) -> Result<()> {
+        {
+            let cache = self.cache.read().unwrap();
+            if cache.contains_line(chunk_start) {
+                return Ok(());
+            }
+        }
+
+        let lines = self.fetch_chunk(chunk_start)?;
+
+        {
+            let mut cache = self.cache.write().unwrap();
+            cache.insert_chunk(chunk_start, lines);
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for SftpFile {
+    fn drop(&mut self) {
+        if let Ok(client) = self.client.get_mut() {
+            let _ = client.close(&self.handle);
+        }
+    }
+}
+
+impl FileSource for SftpFile {
+    fn line_count(&self) -> usize {
+        self.index.line_count()
+    }
+
+    fn file_size(&self) -> Result<u64> {
+        Ok(self.index.byte_len())
+    }
+
+    fn get_line(&self, line_num: usize) -> Result<Option<String>> {
+        if line_num >= self.line_count() {
+            return Ok(None);
+        }
+
+        let chunk_start = LineCache::chunk_start_for_line(line_num);
+        self.ensure_chunk_loaded(chunk_start)?;
+
+        let mut cache = self.cache.write().unwrap();
+        Ok(cache.get_line(line_num).cloned())
+    }
+
+    fn get_lines(&self, start_line: usize, count: usize) -> Result<Vec<(usize, String)>> {
+        let end_line = (start_line + count).min(self.line_count());
+        let actual_count = end_line.saturating_sub(start_line);
+
+        if actual_count == 0 {
+            return Ok(Vec::new());
+        }
+
+        let first_chunk = LineCache::chunk_start_for_line(start_line);
+        let last_chunk = LineCache::chunk_start_for_line(end_line.saturating_sub(1));
+
+        let mut chunk_start = first_chunk;
+        while chunk_start <= last_chunk {
+            self.ensure_chunk_loaded(chunk_start)?;
+            chunk_start += CHUNK_SIZE;
+        }
+
+        let mut result = Vec::with_capacity(actual_count);
+        let mut cache = self.cache.write().unwrap();
+
+        for line_num in start_line..end_line {
+            if let Some(line) = cache.get_line(line_num) {
+                result.push((line_num, line.clone()));
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn display_name(&self) -> &str {
+        &self.display_name
+    }
+}
@@ -0,0 +1,550 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::time::Duration;
+
+use crate::file_source::ConnectionState;
+use crate::remote_command::RemoteCommand;
+
+/// How long an idle master connection outlives its last use, in case pog
+/// exits without closing it.
+const CONTROL_PERSIST: &str = "10m";
+/// Keepalives let the master notice a dead link (after 3 missed replies)
+/// and exit, so commands fail instead of hanging on it.
+const SERVER_ALIVE_INTERVAL: &str = "15";
+/// Delay before the first reconnection attempt; doubled after each failure
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+/// Failed probes before giving up on the host, about eight minutes in
+const MAX_RECONNECT_ATTEMPTS: u32 = 20;
+/// What `ssh` prints when trying again cannot help: authentication was
+/// rejected, the host key changed or the host name does not resolve
+const PERMANENT_FAILURES: &[&str] = &[
+    "Permission denied",
+    "Too many authentication failures",
+    "Host key verification failed",
+    "REMOTE HOST IDENTIFICATION HAS CHANGED",
+    "Could not resolve hostname",
+];
+/// Exit status of `ssh` itself failing, as opposed to the remote command
+const SSH_FAILURE_STATUS: i32 = 255;
+
+static SESSION_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Split `[user@]host[:port]:/path` into host, port and path.
+pub fn parse_remote_path(input: &str) -> Option<(String, Option<u16>, String)> {
+    let (host, rest) = input.split_once(':')?;
+    if host.is_empty() || host.contains('/') {
+        return None;
+    }
+    let (port, path) = match rest.split_once(':') {
+        Some((port, path)) if path.starts_with('/') => match port.parse() {
+            Ok(port) => (Some(port), path),
+            Err(_) => (None, rest),
+        },
+        _ => (None, rest),
+    };
+    path.starts_with('/')
+        .then(|| (host.to_string(), port, path.to_string()))
+}
+
+/// A host to connect to and the options to give `ssh` for it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SshTarget {
+    /// `host` or `user@host`
+    pub host: String,
+    pub port: Option<u16>,
+    /// Passed as `-J`
+    pub jump_host: Option<String>,
+    /// Passed as `-o`, e.g. `IdentityFile=~/.ssh/prod`
+    pub options: Vec<String>,
+}
+
+impl SshTarget {
+    pub fn new(host: &str) -> Self {
+        Self {
+            host: host.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Arguments selecting the port, jump host and options.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(port) = self.port {
+            args.extend(["-p".to_string(), port.to_string()]);
+        }
+        if let Some(jump_host) = &self.jump_host {
+            args.extend(["-J".to_string(), jump_host.clone()]);
+        }
+        for option in &self.options {
+            args.extend(["-o".to_string(), option.clone()]);
+        }
+        args
+    }
+
+    /// Apply `~/.ssh/config` (and the target's own options) with `ssh -G`,
+    /// which only evaluates the config and does not connect.
+    pub fn resolve(&self) -> Option<ResolvedTarget> {
+        let output = Command::new("ssh")
+            .arg("-G")
+            .args(self.args())
+            .arg(&self.host)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        parse_resolved(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    /// Where the ssh config sends the target, when that is somewhere other
+    /// than it says (a `Host` alias or a configured jump host).
+    pub fn config_route(&self) -> Option<String> {
+        let given_host = self.host.rsplit('@').next().unwrap_or(&self.host);
+        let resolved = self.resolve()?;
+        let rerouted = resolved.hostname != given_host
+            || (resolved.proxy_jump.is_some() && self.jump_host.is_none());
+        rerouted.then(|| resolved.to_string())
+    }
+}
+
+/// `host` or `host:port`, as shown in titles and errors.
+impl fmt::Display for SshTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.port {
+            Some(port) => write!(f, "{}:{}", self.host, port),
+            None => write!(f, "{}", self.host),
+        }
+    }
+}
+
+/// Where `~/.ssh/config` sends a target, as reported by `ssh -G`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedTarget {
+    pub user: String,
+    pub hostname: String,
+    pub port: u16,
+    pub proxy_jump: Option<String>,
+}
+
+/// Parse the `key value` lines printed by `ssh -G`.
+pub fn parse_resolved(output: &str) -> Option<ResolvedTarget> {
+    let mut user = None;
+    let mut hostname = None;
+    let mut port = None;
+    let mut proxy_jump = None;
+    for line in output.lines() {
+        let Some((key, value)) = line.split_once(' ') else {
+            continue;
+        };
+        match key {
+            "user" => user = Some(value.to_string()),
+            "hostname" => hostname = Some(value.to_string()),
+            "port" => port = value.parse().ok(),
+            "proxyjump" if value != "none" => proxy_jump = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    Some(ResolvedTarget {
+        user: user?,
+        hostname: hostname?,
+        port: port?,
+        proxy_jump,
+    })
+}
+
+/// `user@hostname[:port][ via jump]`, the port only when not 22.
+impl fmt::Display for ResolvedTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}", self.user, self.hostname)?;
+        if self.port != 22 {
+            write!(f, ":{}", self.port)?;
+        }
+        if let Some(jump) = &self.proxy_jump {
+            write!(f, " via {}", jump)?;
+        }
+        Ok(())
+    }
+}
+
+/// A persistent, multiplexed SSH connection to one host.
+///
+/// An OpenSSH control master is started on first use; every command then
+/// runs as a new channel over it, skipping the TCP and key exchange
+/// handshakes and authentication. An idle master that exited is restarted;
+/// if one cannot be started, commands fall back to connecting on their own.
+///
+/// When a command fails because the host cannot be reached, `run` marks the
+/// session `Reconnecting`, a background thread probes the host with growing
+/// delays, and commands wait until it answers again. The thread gives up
+/// after `MAX_RECONNECT_ATTEMPTS` probes, or at once when ssh reports a
+/// failure that retrying cannot fix; waiting commands then fail, and the
+/// next one tries to connect again.
+pub struct SshSession {
+    target: SshTarget,
+    control_path: PathBuf,
+    /// Set once starting the master failed, so it is not retried per command
+    master_failed: Mutex<bool>,
+    state: Mutex<ConnectionState>,
+    reconnected: Condvar,
+}
+
+impl SshSession {
+    pub fn new(target: &SshTarget) -> Self {
+        // Socket paths are limited to ~100 bytes, so keep the name short
+        let dir = std::env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(std::env::temp_dir);
+        let control_path = dir.join(format!(
+            "pog-ssh-{}-{}",
+            std::process::id(),
+            SESSION_COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        Self {
+            target: target.clone(),
+            control_path,
+            master_failed: Mutex::new(false),
+            state: Mutex::new(ConnectionState::Connected),
+            reconnected: Condvar::new(),
+        }
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        self.state.lock().unwrap().clone()
+    }
+
+    /// Run `remote_cmd` and collect its output. If ssh cannot reach the
+    /// host, reconnect in the background and run it again once connected.
+    /// Fails when ssh gives a permanent error or reconnecting gives up.
+    pub fn run(self: &Arc<Self>, remote_cmd: &RemoteCommand) -> std::io::Result<Output> {
+        self.retry_failed();
+        loop {
+            self.wait_connected()?;
+            let output = self.command(remote_cmd).output()?;
+            if output.status.code() != Some(SSH_FAILURE_STATUS) {
+                return Ok(output);
+            }
+            let error = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            if is_permanent_failure(&error) {
+                return Err(std::io::Error::other(error));
+            }
+            self.connection_lost(&error);
+        }
+    }
+
+    /// After reconnecting gave up, let a new command try the host again.
+    fn retry_failed(&self) {
+        let mut state = self.state.lock().unwrap();
+        if matches!(*state, ConnectionState::Failed { .. }) {
+            *state = ConnectionState::Connected;
+        }
+    }
+
+    /// Wait while the session is reconnecting; fails if it gives up.
+    fn wait_connected(&self) -> std::io::Result<()> {
+        let state = self.state.lock().unwrap();
+        let state = self
+            .reconnected
+            .wait_while(state, |state| matches!(state, ConnectionState::Reconnecting { .. }))
+            .unwrap();
+        match &*state {
+            ConnectionState::Failed { error } => Err(std::io::Error::other(error.clone())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Mark the session `Reconnecting` and start probing the host, unless
+    /// that already happened.
+    fn connection_lost(self: &Arc<Self>, error: &str) {
+        let mut state = self.state.lock().unwrap();
+        if *state != ConnectionState::Connected {
+            return;
+        }
+        *state = ConnectionState::Reconnecting {
+            attempts: 0,
+            error: error.to_string(),
+        };
+        let session = Arc::downgrade(self);
+        let spawned = std::thread::Builder::new()
+            .name("pog-reconnect".to_string())
+            .spawn(move || reconnect(session));
+        if let Err(e) = spawned {
+            *state = ConnectionState::Failed {
+                error: format!("failed to start reconnecting: {}", e),
+            };
+            self.reconnected.notify_all();
+        }
+    }
+
+    /// Drop a master left over from before the link went down, and allow
+    /// starting a new one.
+    fn reset_master(&self) {
+        let mut failed = self.master_failed.lock().unwrap();
+        self.exit_master();
+        let _ = std::fs::remove_file(&self.control_path);
+        *failed = false;
+    }
+
+    fn exit_master(&self) {
+        if self.control_path.exists() {
+            let _ = Command::new("ssh")
+                .arg("-S")
+                .arg(&self.control_path)
+                .args(["-O", "exit"])
+                .arg(&self.target.host)
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status();
+        }
+    }
+
+    /// Start the master connection unless it is already running. Returns
+    /// whether commands can use it.
+    fn ensure_master(&self) -> bool {
+        // Held while starting so concurrent commands wait for one master
+        let mut failed = self.master_failed.lock().unwrap();
+        if *failed {
+            return false;
+        }
+        if self.control_path.exists() {
+            return true;
+        }
+        // `-f` returns once authenticated, leaving the master in the background
+        let started = Command::new("ssh")
+            .arg("-o")
+            .arg("ControlMaster=yes")
+            .arg("-o")
+            .arg(format!("ControlPersist={}", CONTROL_PERSIST))
+            .arg("-o")
+            .arg(format!("ServerAliveInterval={}", SERVER_ALIVE_INTERVAL))
+            .arg("-S")
+            .arg(&self.control_path)
+            .args(["-f", "-N"])
+            .args(self.target.args())
+            .arg(&self.target.host)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+            && self.control_path.exists();
+        *failed = !started;
+        started
+    }
+
+    /// `ssh` with the options to use the master connection when there is one.
+    fn ssh(&self) -> Command {
+        let mut command = Command::new("ssh");
+        if self.ensure_master() {
+            command
+                .arg("-o")
+                .arg("ControlMaster=no")
+                .arg("-S")
+                .arg(&self.control_path);
+        }
+        command.args(self.target.args());
+        command
+    }
+
+    /// An `ssh` command running `remote_cmd` on the host.
+    pub fn command(&self, remote_cmd: &RemoteCommand) -> Command {
+        let mut command = self.ssh();
+        command.arg(&self.target.host).arg(remote_cmd.as_str());
+        command
+    }
+
+    /// An `ssh` command connected to the host's `name` subsystem (e.g. `sftp`).
+    pub fn subsystem(&self, name: &str) -> Command {
+        let mut command = self.ssh();
+        command.arg("-s").arg(&self.target.host).arg(name);
+        command
+    }
+}
+
+impl Drop for SshSession {
+    fn drop(&mut self) {
+        self.exit_master();
+    }
+}
+
+/// Whether ssh's `stderr` reports a failure that reconnecting cannot fix.
+fn is_permanent_failure(stderr: &str) -> bool {
+    PERMANENT_FAILURES.iter().any(|failure| stderr.contains(failure))
+}
+
+/// Probe the host until it answers, then wake the waiting commands. Gives
+/// up once the session is dropped, after `MAX_RECONNECT_ATTEMPTS` probes or
+/// on a permanent failure, marking the session `Failed`.
+fn reconnect(session: Weak<SshSession>) {
+    let mut delay = RECONNECT_DELAY;
+    loop {
+        std::thread::sleep(delay);
+        let Some(session) = session.upgrade() else {
+            return;
+        };
+        session.reset_master();
+        let probe = session
+            .command(&RemoteCommand::new("true"))
+            .stdin(Stdio::null())
+            .output();
+        let error = match probe {
+            Ok(output) if output.status.success() => {
+                *session.state.lock().unwrap() = ConnectionState::Connected;
+                session.reconnected.notify_all();
+                return;
+            }
+            Ok(output) => String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            Err(e) => e.to_string(),
+        };
+        let mut state = session.state.lock().unwrap();
+        if let ConnectionState::Reconnecting {
+            attempts,
+            error: last_error,
+        } = &mut *state
+        {
+            *attempts += 1;
+            if *attempts >= MAX_RECONNECT_ATTEMPTS || is_permanent_failure(&error) {
+                *state = ConnectionState::Failed { error };
+                session.reconnected.notify_all();
+                return;
+            }
+            *last_error = error;
+        }
+        drop(state);
+        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_remote_path() {
+        assert_eq!(
+            parse_remote_path("myserver:/var/log/syslog"),
+            Some(("myserver".to_string(), None, "/var/log/syslog".to_string()))
+        );
+        assert_eq!(
+            parse_remote_path("admin@10.0.0.5:2222:/var/log/app.log"),
+            Some((
+                "admin@10.0.0.5".to_string(),
+                Some(2222),
+                "/var/log/app.log".to_string()
+            ))
+        );
+        // Colons later in the path are part of it
+        assert_eq!(
+            parse_remote_path("host:/logs/a:b:/c"),
+            Some(("host".to_string(), None, "/logs/a:b:/c".to_string()))
+        );
+        assert_eq!(parse_remote_path("host:logs/app.log"), None);
+        assert_eq!(parse_remote_path("./dir:/x"), None);
+        assert_eq!(parse_remote_path("/var/log/syslog"), None);
+    }
+
+    #[test]
+    fn test_parse_resolved() {
+        let output = "user admin\nhostname 10.0.0.5\nport 2222\nproxyjump bastion\n\
+                      identityfile ~/.ssh/prod\n";
+        let resolved = parse_resolved(output).unwrap();
+        assert_eq!(
+            resolved,
+            ResolvedTarget {
+                user: "admin".to_string(),
+                hostname: "10.0.0.5".to_string(),
+                port: 2222,
+                proxy_jump: Some("bastion".to_string()),
+            }
+        );
+        assert_eq!(resolved.to_string(), "admin@10.0.0.5:2222 via bastion");
+
+        let plain = parse_resolved("user root\nhostname db\nport 22\nproxyjump none\n").unwrap();
+        assert_eq!(plain.proxy_jump, None);
+        assert_eq!(plain.to_string(), "root@db");
+        assert_eq!(parse_resolved("hostname db\n"), None);
+    }
+
+    #[test]
+    fn test_commands_wait_for_reconnection() {
+        let session = Arc::new(SshSession::new(&SshTarget::new("db")));
+        *session.state.lock().unwrap() = ConnectionState::Reconnecting {
+            attempts: 2,
+            error: "Connection refused".to_string(),
+        };
+        let waiter = {
+            let session = session.clone();
+            std::thread::spawn(move || session.wait_connected())
+        };
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!waiter.is_finished());
+
+        *session.state.lock().unwrap() = ConnectionState::Connected;
+        session.reconnected.notify_all();
+        waiter.join().unwrap().unwrap();
+        assert_eq!(session.state(), ConnectionState::Connected);
+    }
+
+    #[test]
+    fn test_commands_fail_when_reconnecting_gives_up() {
+        let session = Arc::new(SshSession::new(&SshTarget::new("db")));
+        *session.state.lock().unwrap() = ConnectionState::Reconnecting {
+            attempts: 2,
+            error: "Connection refused".to_string(),
+        };
+        let waiter = {
+            let session = session.clone();
+            std::thread::spawn(move || session.wait_connected())
+        };
+        std::thread::sleep(Duration::from_millis(20));
+        *session.state.lock().unwrap() = ConnectionState::Failed {
+            error: "Permission denied (publickey).".to_string(),
+        };
+        session.reconnected.notify_all();
+        let error = waiter.join().unwrap().unwrap_err();
+        assert_eq!(error.to_string(), "Permission denied (publickey).");
+
+        // The next command tries the host again
+        session.retry_failed();
+        assert_eq!(session.state(), ConnectionState::Connected);
+    }
+
+    #[test]
+    fn test_permanent_failures() {
+        assert!(is_permanent_failure("admin@db: Permission denied (publickey)."));
+        assert!(is_permanent_failure(
+            "ssh: Could not resolve hostname nowhere: Name or service not known"
+        ));
+        assert!(is_permanent_failure("Host key verification failed."));
+        assert!(!is_permanent_failure("ssh: connect to host db port 22: Connection refused"));
+        assert!(!is_permanent_failure("Connection to db closed by remote host."));
+    }
+
+    #[test]
+    fn test_target_args() {
+        let target = SshTarget {
+            host: "admin@db".to_string(),
+            port: Some(2222),
+            jump_host: Some("bastion".to_string()),
+            options: vec!["IdentityFile=~/.ssh/prod".to_string()],
+        };
+        assert_eq!(
+            target.args(),
+            [
+                "-p",
+                "2222",
+                "-J",
+                "bastion",
+                "-o",
+                "IdentityFile=~/.ssh/prod"
+            ]
+        );
+        assert_eq!(target.to_string(), "admin@db:2222");
+        assert!(SshTarget::new("db").args().is_empty());
+    }
+}
@@ -0,0 +1,256 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+use crate::encoding::TextEncoding;
+use crate::error::{PogError, Result};
+use crate::file_source::{FileSource, SourceChange};
+use crate::line_index::SparseLineIndex;
+
+const READ_BUF_LEN: usize = 64 << 10;
+
+/// Whether `path` is a named pipe, which can be read only once, front to back.
+pub fn is_fifo(path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    std::fs::metadata(path).is_ok_and(|m| m.file_type().is_fifo())
+}
+
+/// A log read from a pipe as it is written, kept in memory.
+///
+/// A reader thread appends everything it reads and indexes it like
+/// `CompressedFile`; views poll `refresh` while `is_streaming` and see the
+/// new lines as `Grew`. A last line without its newline yet is held back
+/// until the newline arrives or the writer closes the pipe.
+pub struct StreamSource {
+    display_name: String,
+    state: Arc<RwLock<StreamState>>,
+    /// Line count last reported by `refresh`
+    reported_lines: AtomicUsize,
+}
+
+struct StreamState {
+    data: Vec<u8>,
+    /// Detected from the first bytes read unless given
+    encoding: Option<TextEncoding>,
+    index: SparseLineIndex,
+    finished: bool,
+    error: Option<String>,
+}
+
+impl StreamState {
+    fn encoding(&self) -> TextEncoding {
+        self.encoding.unwrap_or_default()
+    }
+
+    fn append(&mut self, bytes: &[u8]) {
+        let encoding = *self
+            .encoding
+            .get_or_insert_with(|| TextEncoding::detect(bytes));
+        let indexed_len = self.data.len();
+        self.data.extend_from_slice(bytes);
+        self.index.extend(&self.data, indexed_len, encoding);
+    }
+
+    /// Lines that are complete, or all of them once the stream has ended.
+    fn available_lines(&self) -> usize {
+        if self.finished || self.data.ends_with(self.encoding().newline()) {
+            self.index.line_count()
+        } else {
+            self.index.line_count() - 1
+        }
+    }
+}
+
+impl StreamSource {
+    /// Read the named pipe at `path` in the background. Opening blocks until
+    /// a writer connects, so it happens on the reader thread too.
+    pub fn open_fifo<P: AsRef<Path>>(path: P, encoding: Option<TextEncoding>) -> io::Result<Self> {
+        let path: PathBuf = path.as_ref().to_path_buf();
+        let display_name = format!("{} (pipe)", path.display());
+        Self::spawn(display_name, encoding, move || File::open(&path))
+    }
+
+    fn spawn<R, F>(display_name: String, encoding: Option<TextEncoding>, open: F) -> io::Result<Self>
+    where
+        R: Read,
+        F: FnOnce() -> io::Result<R> + Send + 'static,
+    {
+        let state = Arc::new(RwLock::new(StreamState {
+            data: Vec::new(),
+            encoding,
+            index: SparseLineIndex::default(),
+            finished: false,
+            error: None,
+        }));
+
+        let state_reader = state.clone();
+        std::thread::Builder::new()
+            .name("pog-stream".to_string())
+            .spawn(move || {
+                let result = open().and_then(|reader| read_stream(reader, &state_reader));
+                let mut state = state_reader.write().unwrap();
+                state.finished = true;
+                state.error = result.err().map(|e| e.to_string());
+            })?;
+
+        Ok(Self {
+            display_name,
+            state,
+            reported_lines: AtomicUsize::new(0),
+        })
+    }
+}
+
+fn read_stream(mut reader: impl Read, state: &RwLock<StreamState>) -> io::Result<()> {
+    let mut buf = vec![0; READ_BUF_LEN];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(len) => state.write().unwrap().append(&buf[..len]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+impl FileSource for StreamSource {
+    fn line_count(&self) -> usize {
+        self.reported_lines.load(Ordering::SeqCst)
+    }
+
+    /// Bytes read so far
+    fn file_size(&self) -> Result<u64> {
+        Ok(self.state.read().unwrap().data.len() as u64)
+    }
+
+    fn get_line(&self, line_num: usize) -> Result<Option<String>> {
+        Ok(self.get_lines(line_num, 1)?.pop().map(|(_, text)| text))
+    }
+
+    fn get_lines(&self, start_line: usize, count: usize) -> Result<Vec<(usize, String)>> {
+        let state = self.state.read().unwrap();
+        let encoding = state.encoding();
+        let count = count.min(self.line_count().saturating_sub(start_line));
+        Ok(state
+            .index
+            .lines(&state.data, start_line, encoding)
+            .take(count)
+            .map(|(i, bytes)| (i, encoding.decode_line(bytes)))
+            .collect())
+    }
+
+    fn get_raw_lines(&self, start_line: usize, count: usize) -> Result<Vec<(usize, Vec<u8>)>> {
+        let state = self.state.read().unwrap();
+        let encoding = state.encoding();
+        let count = count.min(self.line_count().saturating_sub(start_line));
+        Ok(state
+            .index
+            .lines(&state.data, start_line, encoding)
+            .take(count)
+            .map(|(i, bytes)| (i, encoding.strip_terminator(bytes).to_vec()))
+            .collect())
+    }
+
+    fn display_name(&self) -> &str {
+        &self.display_name
+    }
+
+    fn is_streaming(&self) -> bool {
+        !self.state.read().unwrap().finished
+    }
+
+    /// Block until the writer closes the pipe.
+    fn wait_indexed(&self) {
+        while self.is_streaming() {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+        self.reported_lines
+            .store(self.state.read().unwrap().available_lines(), Ordering::SeqCst);
+    }
+
+    /// Report lines read since the last call; line counts only ever grow.
+    fn refresh(&self) -> Result<SourceChange> {
+        let state = self.state.read().unwrap();
+        if let Some(error) = &state.error {
+            return Err(PogError::Io(io::Error::other(error.clone())));
+        }
+        let available = state.available_lines();
+        if self.reported_lines.swap(available, Ordering::SeqCst) == available {
+            Ok(SourceChange::Unchanged)
+        } else {
+            Ok(SourceChange::Grew)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wait_for(source: &StreamSource, lines: usize) {
+        for _ in 0..200 {
+            source.refresh().unwrap();
+            if source.line_count() >= lines {
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        panic!("stream never reached {} lines", lines);
+    }
+
+    #[test]
+    fn test_stream_holds_back_partial_line() {
+        let (tx, rx) = std::sync::mpsc::channel::<Vec<u8>>();
+        struct ChannelReader(std::sync::mpsc::Receiver<Vec<u8>>);
+        impl Read for ChannelReader {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                match self.0.recv() {
+                    Ok(bytes) => {
+                        buf[..bytes.len()].copy_from_slice(&bytes);
+                        Ok(bytes.len())
+                    }
+                    Err(_) => Ok(0),
+                }
+            }
+        }
+
+        let source =
+            StreamSource::spawn("test".to_string(), None, move || Ok(ChannelReader(rx))).unwrap();
+        assert_eq!(source.line_count(), 0);
+
+        tx.send(b"one\ntw".to_vec()).unwrap();
+        wait_for(&source, 1);
+        assert_eq!(source.line_count(), 1);
+        assert_eq!(source.get_lines(0, 10).unwrap(), vec![(0, "one".to_string())]);
+
+        tx.send(b"o\nthree".to_vec()).unwrap();
+        wait_for(&source, 2);
+        assert_eq!(source.get_line(1).unwrap(), Some("two".to_string()));
+        assert!(source.is_streaming());
+
+        drop(tx);
+        source.wait_indexed();
+        assert!(!source.is_streaming());
+        assert_eq!(source.line_count(), 3);
+        assert_eq!(source.get_line(2).unwrap(), Some("three".to_string()));
+        assert_eq!(source.refresh().unwrap(), SourceChange::Unchanged);
+    }
+
+    #[test]
+    fn test_fifo() {
+        let path = std::env::temp_dir().join(format!("pog-fifo-{}", std::process::id()));
+        let status = std::process::Command::new("mkfifo").arg(&path).status().unwrap();
+        assert!(status.success());
+        assert!(is_fifo(&path));
+
+        let source = StreamSource::open_fifo(&path, None).unwrap();
+        std::fs::write(&path, "a\nb\n").unwrap();
+        source.wait_indexed();
+        assert_eq!(source.line_count(), 2);
+        assert_eq!(source.get_line(1).unwrap(), Some("b".to_string()));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
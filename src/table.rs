@@ -0,0 +1,447 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use regex::Regex;
+
+use crate::error;
+use crate::file_source::FileSource;
+use crate::search::{self, SearchMatch};
+
+/// Drawn between the columns of the table view.
+const COLUMN_SEPARATOR: &str = " │ ";
+/// Widest a column grows to fit its values unless resized.
+pub const MAX_AUTO_WIDTH: usize = 40;
+
+/// What separates the fields of a line in the table view.
+#[derive(Debug, Clone)]
+pub enum Delimiter {
+    /// Runs of spaces and tabs
+    Space,
+    Tab,
+    /// Commas outside double quotes, as in CSV
+    Comma,
+    /// Non-empty matches of a regex
+    Pattern(Regex),
+}
+
+impl PartialEq for Delimiter {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Delimiter::Pattern(a), Delimiter::Pattern(b)) => a.as_str() == b.as_str(),
+            (Delimiter::Space, Delimiter::Space)
+            | (Delimiter::Tab, Delimiter::Tab)
+            | (Delimiter::Comma, Delimiter::Comma) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Delimiter {
+    /// `space`, `tab` or `comma`, otherwise a regex.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "space" => Ok(Delimiter::Space),
+            "tab" => Ok(Delimiter::Tab),
+            "comma" => Ok(Delimiter::Comma),
+            "" => Err("missing delimiter".to_string()),
+            pattern => Regex::new(pattern)
+                .map(Delimiter::Pattern)
+                .map_err(|e| format!("invalid delimiter regex: {}", e)),
+        }
+    }
+
+    /// The delimiter of most of `lines`: tabs, then commas, then spaces.
+    pub fn detect<'a>(lines: impl IntoIterator<Item = &'a str>) -> Self {
+        let (mut total, mut tabs, mut commas) = (0, 0, 0);
+        for line in lines.into_iter().filter(|line| !line.is_empty()) {
+            total += 1;
+            tabs += line.contains('\t') as usize;
+            commas += line.contains(',') as usize;
+        }
+        if total > 0 && tabs * 2 >= total {
+            Delimiter::Tab
+        } else if total > 0 && commas * 2 >= total {
+            Delimiter::Comma
+        } else {
+            Delimiter::Space
+        }
+    }
+
+    /// Character column ranges (start, exclusive end) of the fields of
+    /// `text`. Quotes around a CSV field are left out of its range.
+    pub fn fields(&self, text: &str) -> Vec<(usize, usize)> {
+        let chars: Vec<char> = text.chars().collect();
+        match self {
+            Delimiter::Space => {
+                let mut fields = Vec::new();
+                let mut start = None;
+                for (col, c) in chars.iter().enumerate() {
+                    match (c.is_whitespace(), start) {
+                        (true, Some(s)) => {
+                            fields.push((s, col));
+                            start = None;
+                        }
+                        (false, None) => start = Some(col),
+                        _ => {}
+                    }
+                }
+                if let Some(s) = start {
+                    fields.push((s, chars.len()));
+                }
+                fields
+            }
+            Delimiter::Tab => split_at(&chars, |c| c == '\t'),
+            Delimiter::Comma => csv_fields(&chars),
+            Delimiter::Pattern(regex) => {
+                let mut fields = Vec::new();
+                let mut start = 0;
+                let mut col = 0;
+                let mut byte = 0;
+                for mat in regex.find_iter(text).filter(|mat| !mat.is_empty()) {
+                    col += text[byte..mat.start()].chars().count();
+                    fields.push((start, col));
+                    col += mat.as_str().chars().count();
+                    start = col;
+                    byte = mat.end();
+                }
+                fields.push((start, chars.len()));
+                fields
+            }
+        }
+    }
+}
+
+impl fmt::Display for Delimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Delimiter::Space => write!(f, "space"),
+            Delimiter::Tab => write!(f, "tab"),
+            Delimiter::Comma => write!(f, "comma"),
+            Delimiter::Pattern(regex) => write!(f, "{}", regex.as_str()),
+        }
+    }
+}
+
+fn split_at(chars: &[char], is_delimiter: impl Fn(char) -> bool) -> Vec<(usize, usize)> {
+    let mut fields = Vec::new();
+    let mut start = 0;
+    for (col, &c) in chars.iter().enumerate() {
+        if is_delimiter(c) {
+            fields.push((start, col));
+            start = col + 1;
+        }
+    }
+    fields.push((start, chars.len()));
+    fields
+}
+
+fn csv_fields(chars: &[char]) -> Vec<(usize, usize)> {
+    let mut fields = Vec::new();
+    let mut col = 0;
+    loop {
+        if chars.get(col) == Some(&'"') {
+            // Up to the closing quote; a doubled quote is a quote in the value
+            let start = col + 1;
+            let mut end = start;
+            while end < chars.len() {
+                if chars[end] == '"' {
+                    if chars.get(end + 1) == Some(&'"') {
+                        end += 2;
+                        continue;
+                    }
+                    break;
+                }
+                end += 1;
+            }
+            fields.push((start, end));
+            col = end + 1;
+            while col < chars.len() && chars[col] != ',' {
+                col += 1;
+            }
+        } else {
+            let start = col;
+            while col < chars.len() && chars[col] != ',' {
+                col += 1;
+            }
+            fields.push((start, col));
+        }
+        if col >= chars.len() {
+            return fields;
+        }
+        col += 1;
+    }
+}
+
+/// A character of a line laid out as a table row, and the column of the
+/// line it shows; padding and separators come from no column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableCell {
+    pub ch: char,
+    pub source_col: Option<usize>,
+}
+
+/// The table view of a window: how lines are split into columns, and the
+/// widths and visibility of the columns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableLayout {
+    pub delimiter: Delimiter,
+    /// Widths fitting the values seen so far, up to MAX_AUTO_WIDTH
+    fitted: Vec<usize>,
+    /// Widths set by resizing a column
+    resized: Vec<Option<usize>>,
+    hidden: BTreeSet<usize>,
+}
+
+impl TableLayout {
+    pub fn new(delimiter: Delimiter) -> Self {
+        TableLayout {
+            delimiter,
+            fitted: Vec::new(),
+            resized: Vec::new(),
+            hidden: BTreeSet::new(),
+        }
+    }
+
+    /// Widen columns to fit the values of `lines`. Columns only grow, so
+    /// they stay put while scrolling.
+    pub fn fit<'a>(&mut self, lines: impl IntoIterator<Item = &'a str>) {
+        for line in lines {
+            for (column, (start, end)) in self.delimiter.fields(line).into_iter().enumerate() {
+                if self.fitted.len() <= column {
+                    self.fitted.resize(column + 1, 1);
+                }
+                let width = (end - start).min(MAX_AUTO_WIDTH);
+                self.fitted[column] = self.fitted[column].max(width);
+            }
+        }
+    }
+
+    /// Columns seen so far.
+    pub fn column_count(&self) -> usize {
+        self.fitted.len().max(self.resized.len())
+    }
+
+    /// Width of the 0-based `column` in characters.
+    pub fn width(&self, column: usize) -> usize {
+        match self.resized.get(column).copied().flatten() {
+            Some(width) => width,
+            None => self.fitted.get(column).copied().unwrap_or(1),
+        }
+    }
+
+    pub fn set_width(&mut self, column: usize, width: usize) {
+        if self.resized.len() <= column {
+            self.resized.resize(column + 1, None);
+        }
+        self.resized[column] = Some(width.max(1));
+    }
+
+    pub fn hide(&mut self, column: usize) {
+        self.hidden.insert(column);
+    }
+
+    /// Show `column` again, or every hidden column.
+    pub fn show(&mut self, column: Option<usize>) {
+        match column {
+            Some(column) => {
+                self.hidden.remove(&column);
+            }
+            None => self.hidden.clear(),
+        }
+    }
+
+    /// Hidden columns, 0-based and in order.
+    pub fn hidden(&self) -> impl Iterator<Item = usize> + '_ {
+        self.hidden.iter().copied()
+    }
+
+    /// `text` laid out as a row: each shown field padded or cut to its
+    /// column's width, with `…` ending a cut value.
+    pub fn render(&self, text: &str) -> Vec<TableCell> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut cells = Vec::new();
+        let fields = self.delimiter.fields(text);
+        let last_shown = (0..fields.len()).rev().find(|column| !self.hidden.contains(column));
+        for (column, &(start, end)) in fields.iter().enumerate() {
+            if self.hidden.contains(&column) {
+                continue;
+            }
+            let width = self.width(column);
+            let fits = end - start <= width;
+            let shown_end = if fits { end } else { start + width - 1 };
+            cells.extend((start..shown_end).map(|col| TableCell {
+                ch: chars[col],
+                source_col: Some(col),
+            }));
+            if !fits {
+                cells.push(TableCell { ch: '…', source_col: Some(shown_end) });
+            }
+            if Some(column) == last_shown {
+                break;
+            }
+            let padding = width - (shown_end - start) - usize::from(!fits);
+            cells.extend(std::iter::repeat_n(TableCell { ch: ' ', source_col: None }, padding));
+            cells.extend(COLUMN_SEPARATOR.chars().map(|ch| TableCell { ch, source_col: None }));
+        }
+        cells
+    }
+
+    /// The header row: each shown column's 1-based number, laid out as
+    /// `render` lays out its values.
+    pub fn header(&self) -> String {
+        let mut header = String::new();
+        let shown: Vec<usize> =
+            (0..self.column_count()).filter(|column| !self.hidden.contains(column)).collect();
+        for (index, &column) in shown.iter().enumerate() {
+            let width = self.width(column);
+            let name: String = (column + 1).to_string().chars().take(width).collect();
+            header.push_str(&name);
+            if index + 1 < shown.len() {
+                header.push_str(&" ".repeat(width - name.chars().count()));
+                header.push_str(COLUMN_SEPARATOR);
+            }
+        }
+        header
+    }
+
+    /// The column whose right edge is at character `col` of a row, give or
+    /// take one, for resizing it by dragging the edge in the header.
+    pub fn edge_at(&self, col: usize) -> Option<usize> {
+        let separator = COLUMN_SEPARATOR.chars().count();
+        let mut edge = 0;
+        for column in (0..self.column_count()).filter(|column| !self.hidden.contains(column)) {
+            edge += self.width(column);
+            if col + 1 >= edge && col <= edge + 1 {
+                return Some(column);
+            }
+            edge += separator;
+        }
+        None
+    }
+}
+
+/// A source searched only in one column of the table view, split by its
+/// delimiter: lines with fewer columns have no matches, and match columns
+/// count from the line's start.
+pub struct ColumnView<'a> {
+    source: &'a dyn FileSource,
+    delimiter: &'a Delimiter,
+    column: usize,
+}
+
+impl<'a> ColumnView<'a> {
+    pub fn new(source: &'a dyn FileSource, delimiter: &'a Delimiter, column: usize) -> Self {
+        Self {
+            source,
+            delimiter,
+            column,
+        }
+    }
+}
+
+impl FileSource for ColumnView<'_> {
+    fn line_count(&self) -> usize {
+        self.source.line_count()
+    }
+
+    fn file_size(&self) -> error::Result<u64> {
+        self.source.file_size()
+    }
+
+    fn get_line(&self, line_num: usize) -> error::Result<Option<String>> {
+        self.source.get_line(line_num)
+    }
+
+    fn get_lines(&self, start_line: usize, count: usize) -> error::Result<Vec<(usize, String)>> {
+        self.source.get_lines(start_line, count)
+    }
+
+    fn display_name(&self) -> &str {
+        self.source.display_name()
+    }
+
+    fn search_range(
+        &self,
+        pattern: &Regex,
+        start_line: usize,
+        end_line: usize,
+    ) -> error::Result<Vec<SearchMatch>> {
+        let lines = self.source.get_lines(start_line, end_line.saturating_sub(start_line))?;
+        let mut matches = Vec::new();
+        for (line_num, text) in lines {
+            let Some(&(start, end)) = self.delimiter.fields(&text).get(self.column) else {
+                continue;
+            };
+            let value: String = text.chars().skip(start).take(end - start).collect();
+            for mut m in search::search_lines(pattern, &[(line_num, value)]) {
+                m.shift_columns(start);
+                matches.push(m);
+            }
+        }
+        Ok(matches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_source::VecSource;
+
+    fn field_texts(delimiter: &Delimiter, text: &str) -> Vec<String> {
+        let chars: Vec<char> = text.chars().collect();
+        delimiter
+            .fields(text)
+            .into_iter()
+            .map(|(start, end)| chars[start..end].iter().collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_split_fields() {
+        assert_eq!(field_texts(&Delimiter::Space, "  a  bb\tc "), vec!["a", "bb", "c"]);
+        assert_eq!(field_texts(&Delimiter::Tab, "a\t\tc"), vec!["a", "", "c"]);
+        assert_eq!(
+            field_texts(&Delimiter::Comma, "1,\"x, \"\"y\"\"\",é"),
+            vec!["1", "x, \"\"y\"\"", "é"]
+        );
+        assert_eq!(field_texts(&Delimiter::Comma, "a,"), vec!["a", ""]);
+        let pipes = Delimiter::parse(r"\s*\|\s*").unwrap();
+        assert_eq!(field_texts(&pipes, "ü | b|c"), vec!["ü", "b", "c"]);
+        assert_eq!(Delimiter::parse("tab"), Ok(Delimiter::Tab));
+        assert!(Delimiter::parse("(").is_err());
+        assert_eq!(Delimiter::detect(["a,b", "c,d", "e f"]), Delimiter::Comma);
+        assert_eq!(Delimiter::detect(["a\tb,c", ""]), Delimiter::Tab);
+        assert_eq!(Delimiter::detect(["a b"]), Delimiter::Space);
+    }
+
+    #[test]
+    fn test_render_table() {
+        let mut table = TableLayout::new(Delimiter::Comma);
+        table.fit(["id,name,status", "7,alice,ok"]);
+        let text = |cells: Vec<TableCell>| cells.iter().map(|cell| cell.ch).collect::<String>();
+        assert_eq!(text(table.render("7,alice,ok")), "7  │ alice │ ok");
+        assert_eq!(table.header(), "1  │ 2     │ 3");
+        assert_eq!(table.render("7,bob")[5], TableCell { ch: 'b', source_col: Some(2) });
+
+        table.set_width(1, 3);
+        assert_eq!(text(table.render("7,alice,ok")), "7  │ al… │ ok");
+        assert_eq!(table.render("7,alice")[7], TableCell { ch: '…', source_col: Some(4) });
+        table.hide(0);
+        assert_eq!(text(table.render("7,alice,ok")), "al… │ ok");
+        assert_eq!(table.edge_at(3), Some(1));
+        assert_eq!(table.edge_at(10), None);
+        table.show(None);
+        assert_eq!(table.hidden().count(), 0);
+    }
+
+    #[test]
+    fn test_search_column() {
+        let source = VecSource::new(["7,alice,ok", "8,bob", "9,carol,alice"]);
+        let column = ColumnView::new(&source, &Delimiter::Comma, 2);
+        let regex = Regex::new("^(ok|alice)$").unwrap();
+        let matches = column.search_range(&regex, 0, 3).unwrap();
+        let found: Vec<(usize, usize, usize)> =
+            matches.iter().map(|m| (m.line_num, m.start_col, m.end_col)).collect();
+        assert_eq!(found, vec![(0, 8, 10), (2, 8, 13)]);
+    }
+}
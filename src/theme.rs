@@ -0,0 +1,210 @@
+use std::fmt;
+
+/// The theme asked for on the command line or in the config file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ThemeChoice {
+    /// Light or dark as the desktop prefers, changing along with it
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+impl ThemeChoice {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "system" => Ok(Self::System),
+            "light" => Ok(Self::Light),
+            "dark" => Ok(Self::Dark),
+            _ => Err(format!("unknown theme: {} (expected system, light or dark)", name)),
+        }
+    }
+
+    /// The theme to show, given whether the desktop prefers dark ones.
+    pub fn resolve(self, prefers_dark: bool) -> Theme {
+        match self {
+            ThemeChoice::System if prefers_dark => Theme::Dark,
+            ThemeChoice::System | ThemeChoice::Light => Theme::Light,
+            ThemeChoice::Dark => Theme::Dark,
+        }
+    }
+}
+
+/// Colors of the view: its stylesheet and the default colors of search
+/// matches and new highlight rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Theme {
+    Light,
+    Dark,
+}
+
+/// Colors given to highlights added without one on a dark background; the
+/// light theme uses `highlight::HIGHLIGHT_PALETTE`.
+const DARK_HIGHLIGHT_PALETTE: &[&str] = &[
+    "#2E6A93", "#3C7A3C", "#94435A", "#74508A", "#A0522D", "#2F7F7F", "#857A2A", "#5A5A5A",
+];
+
+/// The colors of a theme's stylesheet.
+struct Colors {
+    view_bg: &'static str,
+    view_fg: &'static str,
+    sidebar_bg: &'static str,
+    /// Line numbers of annotated lines
+    annotated: &'static str,
+    /// The search bar, notices and filter chips floating over the lines
+    overlay_bg: &'static str,
+    overlay_fg: &'static str,
+    /// The search bar's match count
+    info_fg: &'static str,
+    /// The cursor line, under any marks and matches on it
+    cursor_bg: &'static str,
+    /// Lines selected with Shift and the cursor keys
+    selection_bg: &'static str,
+}
+
+const LIGHT_COLORS: Colors = Colors {
+    view_bg: "#ffffff",
+    view_fg: "#1e1e1e",
+    sidebar_bg: "#f0f0f0",
+    annotated: "#b8860b",
+    overlay_bg: "rgba(235, 235, 235, 0.95)",
+    overlay_fg: "#333",
+    info_fg: "#666",
+    cursor_bg: "#e4ecf7",
+    selection_bg: "#cfdcf0",
+};
+
+const DARK_COLORS: Colors = Colors {
+    view_bg: "#1e1e1e",
+    view_fg: "#ddd",
+    sidebar_bg: "#2a2a2a",
+    annotated: "#e8c468",
+    overlay_bg: "rgba(50, 50, 50, 0.95)",
+    overlay_fg: "#ddd",
+    info_fg: "#aaa",
+    cursor_bg: "#2c3440",
+    selection_bg: "#34445c",
+};
+
+impl Theme {
+    /// Background of search matches.
+    pub fn search_color(self) -> &'static str {
+        match self {
+            Theme::Light => "#FFD700",
+            Theme::Dark => "#B8860B",
+        }
+    }
+
+    /// Colors of capture groups 1, 2, ... within a search match, cycling after the last.
+    pub fn search_group_colors(self) -> &'static [&'static str] {
+        match self {
+            Theme::Light => &["#FFA54F", "#98F5FF", "#FF82AB", "#C0FF3E"],
+            Theme::Dark => &["#C46A1C", "#2A8C96", "#B0406A", "#6B8E23"],
+        }
+    }
+
+    /// Text color of the keys of logfmt lines.
+    pub fn key_color(self) -> &'static str {
+        match self {
+            Theme::Light => "#1C5FB4",
+            Theme::Dark => "#78AEED",
+        }
+    }
+
+    /// Colors suggested, in order, for new highlight rules.
+    pub fn highlight_palette(self) -> &'static [&'static str] {
+        match self {
+            Theme::Light => crate::highlight::HIGHLIGHT_PALETTE,
+            Theme::Dark => DARK_HIGHLIGHT_PALETTE,
+        }
+    }
+
+    /// The stylesheet of a window's widgets.
+    pub fn css(self) -> String {
+        let Colors {
+            view_bg,
+            view_fg,
+            sidebar_bg,
+            annotated,
+            overlay_bg,
+            overlay_fg,
+            info_fg,
+            cursor_bg,
+            selection_bg,
+        } = match self {
+            Theme::Light => LIGHT_COLORS,
+            Theme::Dark => DARK_COLORS,
+        };
+        format!(
+            ".log-view {{ background-color: {view_bg}; color: {view_fg}; }}
+             .line-numbers-sidebar {{ background-color: {sidebar_bg}; padding-right: 8px; }}
+             .line-number {{ color: #888; }}
+             .line-number.annotated {{ color: {annotated}; }}
+             .selected-line {{ background-color: {selection_bg}; }}
+             .cursor-line {{ background-color: {cursor_bg}; }}
+             .marks-panel {{ background-color: {sidebar_bg}; border-left: 1px solid alpha(#888, 0.3); }}
+             .marks-panel-title {{ color: #888; margin: 6px 8px 2px 8px; }}
+             .table-header {{ background-color: {sidebar_bg}; color: #888; }}
+             .pinned-lines {{ background-color: {sidebar_bg}; border-bottom: 1px solid alpha(#888, 0.3); }}
+             .search-bar {{ background-color: {overlay_bg}; padding: 8px 16px; border-radius: 0 0 8px 8px; }}
+             .search-entry {{ min-width: 300px; }}
+             .search-info {{ color: {info_fg}; margin-left: 8px; margin-right: 8px; }}
+             .search-close {{ padding: 4px 8px; }}
+             .file-notice {{ background-color: {overlay_bg}; padding: 6px 12px; border-radius: 8px; }}
+             .file-notice label {{ color: {overlay_fg}; }}
+             .new-lines-badge {{ padding: 2px 10px; border-radius: 10px; background: rgba(30, 70, 130, 0.95); color: #fff; }}
+             .connection-banner {{ background-color: rgba(140, 40, 40, 0.95); padding: 6px 12px; border-radius: 8px; }}
+             .connection-banner label {{ color: #fff; }}
+             .error-toast {{ background-color: rgba(140, 40, 40, 0.95); padding: 6px 6px 6px 12px; border-radius: 8px; }}
+             .error-toast label {{ color: #fff; }}
+             .status-text {{ background-color: rgba(30, 70, 130, 0.95); color: #fff; padding: 6px 12px; border-radius: 8px; }}
+             .filter-chip {{ background-color: {overlay_bg}; padding: 2px 4px 2px 10px; border-radius: 12px; }}
+             .filter-chip label {{ color: {overlay_fg}; }}
+             .line-content link {{ color: #888; }}"
+        )
+    }
+}
+
+impl fmt::Display for Theme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Theme::Light => write!(f, "light"),
+            Theme::Dark => write!(f, "dark"),
+        }
+    }
+}
+
+/// Text color readable on a background of `red`, `green`, `blue` (0.0 to
+/// 1.0), whatever the theme: black on light colors, white on dark ones.
+pub fn contrast_foreground(red: f64, green: f64, blue: f64) -> &'static str {
+    let luminance = 0.2126 * red + 0.7152 * green + 0.0722 * blue;
+    if luminance > 0.5 {
+        "#000000"
+    } else {
+        "#ffffff"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_resolve_theme() {
+        assert_eq!(ThemeChoice::parse("Dark"), Ok(ThemeChoice::Dark));
+        assert_eq!(ThemeChoice::parse("system"), Ok(ThemeChoice::System));
+        assert!(ThemeChoice::parse("solarized").is_err());
+        assert_eq!(ThemeChoice::System.resolve(true), Theme::Dark);
+        assert_eq!(ThemeChoice::System.resolve(false), Theme::Light);
+        assert_eq!(ThemeChoice::Light.resolve(true), Theme::Light);
+        assert_eq!(ThemeChoice::Dark.resolve(false), Theme::Dark);
+    }
+
+    #[test]
+    fn test_contrast_foreground() {
+        assert_eq!(contrast_foreground(1.0, 0.843, 0.0), "#000000");
+        assert_eq!(contrast_foreground(1.0, 1.0, 1.0), "#000000");
+        assert_eq!(contrast_foreground(0.0, 0.0, 0.5), "#ffffff");
+        assert_eq!(contrast_foreground(0.18, 0.42, 0.58), "#ffffff");
+    }
+}
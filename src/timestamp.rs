@@ -0,0 +1,642 @@
+use std::sync::{OnceLock, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use regex::Regex;
+
+use crate::error::Result;
+use crate::file_source::FileSource;
+
+/// Microseconds since the Unix epoch (UTC). Lines without an explicit
+/// offset are treated as UTC.
+pub type Timestamp = i64;
+
+/// Timestamps are only looked for near the start of a line.
+const TIMESTAMP_SEARCH_PREFIX: usize = 64;
+
+/// Lines read from a probed line onward to find one with a timestamp.
+const TIME_PROBE_LINES: usize = 32;
+
+/// Lines per entry of a `TimeIndex`.
+const TIME_INDEX_BLOCK: usize = 256;
+
+/// Formats from the config file's `time_format` entries, tried before the
+/// built-in ones. Set once at startup.
+static CUSTOM_FORMATS: OnceLock<Vec<TimeFormat>> = OnceLock::new();
+
+/// Times a search is limited to: lines stamped at or after `after` and
+/// before `before`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TimeRange {
+    pub after: Option<Timestamp>,
+    pub before: Option<Timestamp>,
+}
+
+impl TimeRange {
+    pub fn is_unbounded(&self) -> bool {
+        self.after.is_none() && self.before.is_none()
+    }
+
+    /// Lines `start..end` of `source` within the range, assuming its lines
+    /// are in time order. `end` is `usize::MAX` without a `before` bound, so
+    /// lines appended later stay inside. `index`, when given, is the time
+    /// index of `source`'s own lines.
+    pub fn lines(&self, source: &dyn FileSource, index: Option<&TimeIndex>) -> Result<(usize, usize)> {
+        let start = match self.after {
+            Some(time) => first_line_at_indexed(source, index, time)?,
+            None => 0,
+        };
+        let end = match self.before {
+            Some(time) => first_line_at_indexed(source, index, time)?,
+            None => usize::MAX,
+        };
+        Ok((start, end.max(start)))
+    }
+}
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+fn iso_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"(\d{4})-(\d{2})-(\d{2})[T ](\d{2}):(\d{2}):(\d{2})(?:[.,](\d{1,9}))?\s?(Z|[+-]\d{2}:?\d{2})?",
+        )
+        .unwrap()
+    })
+}
+
+fn syslog_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^(Jan|Feb|Mar|Apr|May|Jun|Jul|Aug|Sep|Oct|Nov|Dec) +(\d{1,2}) (\d{2}):(\d{2}):(\d{2})")
+            .unwrap()
+    })
+}
+
+/// Apache and nginx access logs: `[10/Oct/2024:13:55:36 -0700]`.
+fn common_log_format() -> &'static TimeFormat {
+    static FORMAT: OnceLock<TimeFormat> = OnceLock::new();
+    FORMAT.get_or_init(|| TimeFormat::parse("%d/%b/%Y:%H:%M:%S %z").unwrap())
+}
+
+/// One field of a `TimeFormat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TimeField {
+    Year,
+    Month,
+    MonthName,
+    Day,
+    Hour,
+    Minute,
+    Second,
+    Fraction,
+    Offset,
+    Epoch,
+}
+
+/// A timestamp layout given strftime-style: `%Y` year, `%m` month, `%b`
+/// month name, `%d` day, `%H`, `%M`, `%S` hours, minutes and seconds, `%f`
+/// fraction of a second, `%z` UTC offset (`+0100`, `+01:00` or `Z`), `%s`
+/// seconds since the epoch and `%%` a percent sign. A space matches any run
+/// of spaces. Stamps without a year are in the current year, and stamps of a
+/// time alone on 1970-01-01, which still orders them within a day.
+#[derive(Debug, Clone)]
+pub struct TimeFormat {
+    pub spec: String,
+    regex: Regex,
+    fields: Vec<TimeField>,
+}
+
+impl PartialEq for TimeFormat {
+    fn eq(&self, other: &Self) -> bool {
+        self.spec == other.spec
+    }
+}
+
+impl TimeFormat {
+    pub fn parse(spec: &str) -> std::result::Result<Self, String> {
+        let mut pattern = String::new();
+        let mut fields = Vec::new();
+        let mut chars = spec.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '%' => {
+                    let directive = chars.next();
+                    let (field, group) = match directive {
+                        Some('Y') => (TimeField::Year, r"(\d{4})"),
+                        Some('m') => (TimeField::Month, r"(\d{1,2})"),
+                        Some('b') => (TimeField::MonthName, r"(Jan|Feb|Mar|Apr|May|Jun|Jul|Aug|Sep|Oct|Nov|Dec)"),
+                        Some('d') => (TimeField::Day, r"(\d{1,2})"),
+                        Some('H') => (TimeField::Hour, r"(\d{1,2})"),
+                        Some('M') => (TimeField::Minute, r"(\d{2})"),
+                        Some('S') => (TimeField::Second, r"(\d{2})"),
+                        Some('f') => (TimeField::Fraction, r"(\d{1,9})"),
+                        Some('z') => (TimeField::Offset, r"(Z|[+-]\d{2}:?\d{2})"),
+                        Some('s') => (TimeField::Epoch, r"(\d{1,12})"),
+                        Some('%') => {
+                            pattern.push('%');
+                            continue;
+                        }
+                        Some(other) => return Err(format!("unknown time format directive: %{}", other)),
+                        None => return Err("time format ends with %".to_string()),
+                    };
+                    if fields.contains(&field) {
+                        return Err(format!("time format repeats %{}", directive.unwrap_or('%')));
+                    }
+                    fields.push(field);
+                    pattern.push_str(group);
+                }
+                ' ' => pattern.push_str(" +"),
+                c => pattern.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+            }
+        }
+        let has = |field| fields.contains(&field);
+        if !(has(TimeField::Epoch) || has(TimeField::Hour) && has(TimeField::Minute)) {
+            return Err(format!("time format needs %s or %H and %M: {}", spec));
+        }
+        let regex = Regex::new(&pattern).map_err(|e| format!("invalid time format: {}", e))?;
+        Ok(Self {
+            spec: spec.to_string(),
+            regex,
+            fields,
+        })
+    }
+
+    /// The first stamp of this format in `text`.
+    fn find(&self, text: &str) -> Option<Timestamp> {
+        let caps = self.regex.captures(text)?;
+        let mut year = None;
+        let (mut month, mut day) = (None, None);
+        let (mut hour, mut min, mut sec) = (0, 0, 0);
+        let mut micros = 0;
+        let mut offset_secs = 0;
+        let mut epoch = None;
+        for (i, field) in self.fields.iter().enumerate() {
+            let value = caps.get(i + 1)?.as_str();
+            match field {
+                TimeField::Year => year = Some(value.parse().ok()?),
+                TimeField::Month => month = Some(value.parse().ok()?),
+                TimeField::MonthName => {
+                    month = Some(MONTHS.iter().position(|m| *m == value)? as u32 + 1)
+                }
+                TimeField::Day => day = Some(value.parse().ok()?),
+                TimeField::Hour => hour = value.parse().ok()?,
+                TimeField::Minute => min = value.parse().ok()?,
+                TimeField::Second => sec = value.parse().ok()?,
+                TimeField::Fraction => micros = parse_fraction(value),
+                TimeField::Offset => offset_secs = parse_offset(value)?,
+                TimeField::Epoch => epoch = Some(value.parse::<i64>().ok()?),
+            }
+        }
+        if let Some(secs) = epoch {
+            return Some(secs * 1_000_000 + micros);
+        }
+        if hour > 23 || min > 59 || sec > 60 {
+            return None;
+        }
+        let days = match (month, day) {
+            (Some(month), Some(day)) if (1..=12).contains(&month) && (1..=31).contains(&day) => {
+                days_from_civil(year.unwrap_or_else(current_year), month, day)
+            }
+            (None, None) if year.is_none() => 0,
+            _ => return None,
+        };
+        Some(to_timestamp(days, hour, min, sec, micros) - offset_secs * 1_000_000)
+    }
+}
+
+/// Use `formats` ahead of the built-in ones from now on. Only the first call
+/// has an effect.
+pub fn set_custom_formats(formats: Vec<TimeFormat>) {
+    let _ = CUSTOM_FORMATS.set(formats);
+}
+
+/// Microseconds in the digits after a decimal point.
+fn parse_fraction(digits: &str) -> i64 {
+    format!("{:0<6}", &digits[..digits.len().min(6)])
+        .parse()
+        .unwrap_or(0)
+}
+
+/// Seconds east of UTC of `Z`, `+0100` or `-01:30`.
+fn parse_offset(offset: &str) -> Option<i64> {
+    if offset == "Z" {
+        return Some(0);
+    }
+    let sign = if offset.starts_with('-') { -1 } else { 1 };
+    let digits: String = offset[1..].chars().filter(|c| *c != ':').collect();
+    let hours: i64 = digits.get(..2)?.parse().ok()?;
+    let mins: i64 = digits.get(2..)?.parse().ok()?;
+    Some(sign * (hours * 3600 + mins * 60))
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date.
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let m = month as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Year of a day count produced by `days_from_civil`.
+fn year_from_days(days: i64) -> i64 {
+    let z = days + 719_468;
+    let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    yoe + era * 400 + if month <= 2 { 1 } else { 0 }
+}
+
+fn current_year() -> i64 {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    year_from_days(secs / 86_400)
+}
+
+fn to_timestamp(days: i64, hour: u32, min: u32, sec: u32, micros: i64) -> Timestamp {
+    let secs = days * 86_400 + (hour * 3600 + min * 60 + sec) as i64;
+    secs * 1_000_000 + micros
+}
+
+/// Extract a timestamp from the start of a log line.
+///
+/// Tries the config file's formats first, then understands ISO-8601 / RFC
+/// 3339 style stamps (`2024-03-01T12:00:00.123Z`, `2024-03-01 12:00:00,123
+/// +0100`), access log stamps (`01/Mar/2024:12:00:00 +0000`) and classic
+/// syslog stamps (`Mar  1 12:00:00`, assumed to be in the current year).
+pub fn parse_timestamp(line: &str) -> Option<Timestamp> {
+    let mut end = line.len().min(TIMESTAMP_SEARCH_PREFIX);
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    let prefix = &line[..end];
+
+    if let Some(ts) = CUSTOM_FORMATS
+        .get()
+        .and_then(|formats| formats.iter().find_map(|format| format.find(prefix)))
+    {
+        return Some(ts);
+    }
+
+    if let Some(caps) = iso_regex().captures(prefix) {
+        let num = |i: usize| caps[i].parse::<u32>().ok();
+        let (month, day) = (num(2)?, num(3)?);
+        let (hour, min, sec) = (num(4)?, num(5)?, num(6)?);
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || min > 59 || sec > 60 {
+            return None;
+        }
+        let micros = caps.get(7).map_or(0, |m| parse_fraction(m.as_str()));
+        let days = days_from_civil(caps[1].parse().ok()?, month, day);
+        let mut ts = to_timestamp(days, hour, min, sec, micros);
+        if let Some(offset) = caps.get(8) {
+            ts -= parse_offset(offset.as_str())? * 1_000_000;
+        }
+        return Some(ts);
+    }
+
+    if let Some(ts) = common_log_format().find(prefix) {
+        return Some(ts);
+    }
+
+    if let Some(caps) = syslog_regex().captures(prefix) {
+        let month = MONTHS.iter().position(|m| *m == &caps[1])? as u32 + 1;
+        let day: u32 = caps[2].parse().ok()?;
+        let days = days_from_civil(current_year(), month, day);
+        return Some(to_timestamp(
+            days,
+            caps[3].parse().ok()?,
+            caps[4].parse().ok()?,
+            caps[5].parse().ok()?,
+            0,
+        ));
+    }
+
+    None
+}
+
+/// Parse a time given on the command line: a stamp as `parse_timestamp`
+/// reads it, or one cut short to minutes or to the date (`2024-05-01 12:00`,
+/// `2024-05-01`).
+pub fn parse_time_bound(text: &str) -> std::result::Result<Timestamp, String> {
+    let text = text.trim();
+    [
+        text.to_string(),
+        format!("{}:00", text),
+        format!("{} 00:00:00", text),
+    ]
+    .iter()
+    .find_map(|candidate| parse_timestamp(candidate))
+    .ok_or_else(|| format!("invalid time: {}", text))
+}
+
+/// The first line of `source` stamped at or after `time`, by binary search
+/// over its lines; the line count when there is none. Lines without a
+/// timestamp continue the stamped line before them. A probe that finds no
+/// stamp within `TIME_PROBE_LINES` counts as late.
+pub fn first_line_at(source: &dyn FileSource, time: Timestamp) -> Result<usize> {
+    let (mut low, mut high) = (0, source.line_count());
+    let mut first = high;
+    while low < high {
+        let mid = low + (high - low) / 2;
+        let stamped = source
+            .get_lines(mid, TIME_PROBE_LINES)?
+            .into_iter()
+            .find_map(|(line_num, text)| parse_timestamp(&text).map(|ts| (line_num, ts)));
+        match stamped {
+            Some((line_num, ts)) if ts < time => low = line_num + 1,
+            // No line from `mid` up to this one starts an entry, so an earlier
+            // answer lies before `mid`
+            Some((line_num, _)) => {
+                first = line_num;
+                high = mid;
+            }
+            None => high = mid,
+        }
+    }
+    Ok(first)
+}
+
+/// Where the stamps of a source's lines lie: for every block of
+/// `TIME_INDEX_BLOCK` lines, the stamp of the last stamped line up to its
+/// end. Built a chunk at a time by the source's worker while a view shows
+/// the time column, it lets the column find the stamp before the first line
+/// shown however far back it is, and jumps to a time skip the probing.
+#[derive(Default)]
+pub struct TimeIndex {
+    state: RwLock<TimeIndexState>,
+}
+
+#[derive(Default)]
+struct TimeIndexState {
+    /// Last stamp up to the end of each whole block, carried over blocks without one
+    block_ends: Vec<Option<Timestamp>>,
+    /// Lines examined so far
+    scanned: usize,
+    /// Last stamp among the lines examined
+    last: Option<Timestamp>,
+    /// Some stamp is earlier than one before it
+    unordered: bool,
+}
+
+impl TimeIndex {
+    pub fn scanned(&self) -> usize {
+        self.state.read().unwrap().scanned
+    }
+
+    /// Examine the next `chunk_size` lines of `source`, returning how many
+    /// were read (none once the index has caught up with the source).
+    pub fn extend(&self, source: &dyn FileSource, chunk_size: usize) -> Result<usize> {
+        let start = self.scanned();
+        let count = chunk_size.min(source.line_count().saturating_sub(start));
+        if count == 0 {
+            return Ok(0);
+        }
+        let lines = source.get_lines(start, count)?;
+
+        let mut state = self.state.write().unwrap();
+        // Reset while the lines were read: they belong to the old content
+        if state.scanned != start {
+            return Ok(0);
+        }
+        for (line_num, text) in &lines {
+            if let Some(ts) = parse_timestamp(text) {
+                state.unordered |= state.last.is_some_and(|last| ts < last);
+                state.last = Some(ts);
+            }
+            if (line_num + 1) % TIME_INDEX_BLOCK == 0 {
+                let last = state.last;
+                state.block_ends.push(last);
+            }
+        }
+        state.scanned = start + lines.len();
+        Ok(lines.len())
+    }
+
+    /// Start over, after the content of the source was replaced.
+    pub fn reset(&self) {
+        *self.state.write().unwrap() = TimeIndexState::default();
+    }
+
+    /// The last stamp before block `block`, once the index has got that far.
+    fn stamp_before_block(&self, block: usize) -> Option<Option<Timestamp>> {
+        match block {
+            0 => Some(None),
+            _ => self.state.read().unwrap().block_ends.get(block - 1).copied(),
+        }
+    }
+
+    /// The block holding the first line stamped at or after `time`, when
+    /// the index covers all `line_count` lines and they are in time order.
+    fn block_reaching(&self, time: Timestamp, line_count: usize) -> Option<usize> {
+        let state = self.state.read().unwrap();
+        if state.scanned < line_count || state.unordered {
+            return None;
+        }
+        Some(
+            state
+                .block_ends
+                .partition_point(|end| !end.is_some_and(|end| end >= time)),
+        )
+    }
+}
+
+/// `first_line_at`, read off `index`, the time index of `source`'s lines,
+/// when it covers them all, or by binary search otherwise.
+pub fn first_line_at_indexed(
+    source: &dyn FileSource,
+    index: Option<&TimeIndex>,
+    time: Timestamp,
+) -> Result<usize> {
+    let Some(block) = index.and_then(|index| index.block_reaching(time, source.line_count())) else {
+        return first_line_at(source, time);
+    };
+    // The previous blocks end before `time` and this one reaches it, so the
+    // line is in this one; past the last whole block, there may be none
+    let lines = source.get_lines(block * TIME_INDEX_BLOCK, TIME_INDEX_BLOCK)?;
+    Ok(lines
+        .iter()
+        .find(|(_, text)| parse_timestamp(text).is_some_and(|ts| ts >= time))
+        .map_or(source.line_count(), |(line_num, _)| *line_num))
+}
+
+/// The stamp of the last stamped line before `line` of `source`. With
+/// `index`, the time index of `source`'s lines, once it has got that far,
+/// only the lines of `line`'s block are read; otherwise up to
+/// `TIME_PROBE_LINES` lines before it.
+pub fn previous_time(
+    source: &dyn FileSource,
+    index: Option<&TimeIndex>,
+    line: usize,
+) -> Result<Option<Timestamp>> {
+    let block = line / TIME_INDEX_BLOCK;
+    let (from, earlier) = match index.and_then(|index| index.stamp_before_block(block)) {
+        Some(earlier) => (block * TIME_INDEX_BLOCK, earlier),
+        None => (line.saturating_sub(TIME_PROBE_LINES), None),
+    };
+    let lines = source.get_lines(from, line - from)?;
+    Ok(lines
+        .iter()
+        .rev()
+        .find_map(|(_, text)| parse_timestamp(text))
+        .or(earlier))
+}
+
+/// The time between two stamps, `micros` apart, as the time column shows it:
+/// `+0.250s`, `+42.5s`, `+3m05s`, `+2h14m`, `+3d04h`, and `-` in front when
+/// the log goes back in time.
+pub fn format_delta(micros: i64) -> String {
+    let sign = if micros < 0 { '-' } else { '+' };
+    let micros = micros.unsigned_abs();
+    let secs = micros / 1_000_000;
+    match secs {
+        0..=9 => format!("{}{}.{:03}s", sign, secs, micros % 1_000_000 / 1000),
+        10..=59 => format!("{}{}.{}s", sign, secs, micros % 1_000_000 / 100_000),
+        60..=3599 => format!("{}{}m{:02}s", sign, secs / 60, secs % 60),
+        3600..=86_399 => format!("{}{}h{:02}m", sign, secs / 3600, secs % 3600 / 60),
+        _ => format!("{}{}d{:02}h", sign, secs / 86_400, secs % 86_400 / 3600),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_source::VecSource;
+
+    #[test]
+    fn test_days_from_civil() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+        assert_eq!(days_from_civil(2024, 2, 29), 19_782);
+        assert_eq!(year_from_days(19_782), 2024);
+        assert_eq!(year_from_days(0), 1970);
+    }
+
+    #[test]
+    fn test_parse_iso() {
+        let base = parse_timestamp("2024-02-29T10:20:30Z started").unwrap();
+        assert_eq!(base, (19_782 * 86_400 + 10 * 3600 + 20 * 60 + 30) * 1_000_000);
+        assert_eq!(parse_timestamp("2024-02-29 10:20:30.5 INFO x"), Some(base + 500_000));
+        assert_eq!(parse_timestamp("[2024-02-29 10:20:30,123456] x"), Some(base + 123_456));
+        // 12:20:30+02:00 is 10:20:30Z
+        assert_eq!(parse_timestamp("2024-02-29T12:20:30+02:00 x"), Some(base));
+        assert_eq!(parse_timestamp("2024-02-29T09:20:30 -0100 x"), Some(base));
+    }
+
+    #[test]
+    fn test_parse_syslog() {
+        let a = parse_timestamp("Mar  1 12:00:00 host sshd[1]: hello").unwrap();
+        let b = parse_timestamp("Mar 10 12:00:00 host sshd[1]: hello").unwrap();
+        assert_eq!(b - a, 9 * 86_400 * 1_000_000);
+    }
+
+    #[test]
+    fn test_parse_time_bound() {
+        let noon = parse_timestamp("2024-05-01 12:00:00").unwrap();
+        assert_eq!(parse_time_bound("2024-05-01 12:00"), Ok(noon));
+        assert_eq!(parse_time_bound(" 2024-05-01T12:00:00Z "), Ok(noon));
+        assert_eq!(parse_time_bound("2024-05-01"), Ok(noon - 12 * 3600 * 1_000_000));
+        assert!(parse_time_bound("noon").unwrap_err().starts_with("invalid time"));
+    }
+
+    #[test]
+    fn test_time_range_lines() {
+        // A stamped line every minute from 12:00, each followed by a stack line
+        let source = VecSource::new((0..60).flat_map(|min| {
+            [
+                format!("2024-05-01 12:{:02}:00 ERROR", min),
+                "    at com.example.Foo".to_string(),
+            ]
+        }));
+        let time = |text| Some(parse_time_bound(text).unwrap());
+        let range = TimeRange {
+            after: time("2024-05-01 12:10"),
+            before: time("2024-05-01 12:30"),
+        };
+        assert_eq!(range.lines(&source, None).unwrap(), (20, 60));
+        assert_eq!(
+            TimeRange { after: time("2024-05-01 12:10:30"), before: None }
+                .lines(&source, None)
+                .unwrap(),
+            (22, usize::MAX)
+        );
+        assert_eq!(first_line_at(&source, time("2024-05-01").unwrap()).unwrap(), 0);
+        assert_eq!(first_line_at(&source, time("2024-05-02").unwrap()).unwrap(), 120);
+        assert!(TimeRange::default().is_unbounded());
+    }
+
+    #[test]
+    fn test_time_formats() {
+        let base = parse_timestamp("2024-02-29T10:20:30Z").unwrap();
+        assert_eq!(parse_timestamp("1.2.3.4 - - [29/Feb/2024:11:20:30 +0100] \"GET /\""), Some(base));
+
+        let format = TimeFormat::parse("%d.%m.%Y %H:%M:%S.%f").unwrap();
+        assert_eq!(format.find("I 29.02.2024  10:20:30.25 x"), Some(base + 250_000));
+        let epoch = TimeFormat::parse("%s.%f").unwrap();
+        assert_eq!(epoch.find("1709202030.5 x"), Some(base + 500_000));
+        let clock = TimeFormat::parse("[%H:%M:%S]").unwrap();
+        assert_eq!(clock.find("[00:01:02] x"), Some(62_000_000));
+        assert_eq!(clock.find("00:01:02 x"), None);
+
+        assert!(TimeFormat::parse("%Y-%m-%d").is_err());
+        assert!(TimeFormat::parse("%H:%M %q").is_err());
+        assert!(TimeFormat::parse("%H:%M %H").is_err());
+        assert!(TimeFormat::parse("%H:%M%").is_err());
+    }
+
+    #[test]
+    fn test_time_index() {
+        // A stamped line every 5 seconds, each followed by 99 unstamped ones
+        let source = VecSource::new((0..1000).map(|i| match i % 100 {
+            0 => format!("2024-05-01 12:00:{:02} start", i / 100 * 5),
+            _ => "    detail".to_string(),
+        }));
+        let index = TimeIndex::default();
+        while index.extend(&source, 300).unwrap() > 0 {}
+        assert_eq!(index.scanned(), 1000);
+
+        let time = |text| parse_time_bound(text).unwrap();
+        // Line 800 is a block past the stamp on line 700
+        assert_eq!(previous_time(&source, Some(&index), 800).unwrap(), Some(time("2024-05-01 12:00:35")));
+        assert_eq!(previous_time(&source, Some(&index), 0).unwrap(), None);
+        // Without the index, only the lines just above are read
+        assert_eq!(previous_time(&source, None, 850).unwrap(), None);
+        assert_eq!(previous_time(&source, None, 820).unwrap(), Some(time("2024-05-01 12:00:40")));
+
+        for (text, line) in [("2024-05-01 12:00:12", 300), ("2024-05-01", 0), ("2024-05-02", 1000)] {
+            assert_eq!(first_line_at_indexed(&source, Some(&index), time(text)).unwrap(), line);
+        }
+        // Probes land between stamps more than TIME_PROBE_LINES apart, which
+        // the index does not need to
+        assert_ne!(first_line_at(&source, time("2024-05-01 12:00:12")).unwrap(), 300);
+
+        index.reset();
+        assert_eq!(index.scanned(), 0);
+    }
+
+    #[test]
+    fn test_format_delta() {
+        assert_eq!(format_delta(250_000), "+0.250s");
+        assert_eq!(format_delta(-1_500_000), "-1.500s");
+        assert_eq!(format_delta(42_560_000), "+42.5s");
+        assert_eq!(format_delta(185_000_000), "+3m05s");
+        assert_eq!(format_delta(8_040_000_000), "+2h14m");
+        assert_eq!(format_delta(273_600_000_000), "+3d04h");
+    }
+
+    #[test]
+    fn test_parse_none() {
+        assert_eq!(parse_timestamp(""), None);
+        assert_eq!(parse_timestamp("    at com.example.Foo(Foo.java:42)"), None);
+        assert_eq!(parse_timestamp("2024-13-01 10:00:00 bad month"), None);
+    }
+}
@@ -0,0 +1,131 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask, Watches};
+
+/// Watch `path` for writes, truncation and replacement.
+///
+/// The parent directory is watched rather than the file itself so that a
+/// file renamed away and recreated under the same name is still noticed.
+/// Bursts of events are coalesced: the receiver holds at most one pending
+/// notification, and the caller is expected to re-check the file on each.
+pub fn watch(path: &Path) -> io::Result<FileWatch> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?
+        .to_os_string();
+    let dir = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    let mut inotify = Inotify::init()?;
+    let mut watches = inotify.watches();
+    let descriptor = watches.add(
+        &dir,
+        WatchMask::MODIFY | WatchMask::CREATE | WatchMask::MOVED_TO | WatchMask::CLOSE_WRITE,
+    )?;
+
+    let (tx, rx) = async_channel::bounded(1);
+    std::thread::spawn(move || {
+        let mut buffer = [0u8; 4096];
+        loop {
+            let events = match inotify.read_events_blocking(&mut buffer) {
+                Ok(events) => events,
+                Err(e) => {
+                    eprintln!("File watcher stopped: {}", e);
+                    return;
+                }
+            };
+            let mut changed = false;
+            for event in events {
+                // The watch was removed, by `FileWatch::drop` or with the directory
+                if event.mask.contains(EventMask::IGNORED) {
+                    return;
+                }
+                changed |= event.name == Some(file_name.as_os_str());
+            }
+            if changed {
+                // A full channel already has a notification pending
+                if let Err(async_channel::TrySendError::Closed(_)) = tx.try_send(()) {
+                    return;
+                }
+            }
+        }
+    });
+
+    Ok(FileWatch {
+        changes: rx,
+        watches,
+        descriptor,
+    })
+}
+
+/// A running `watch`. Dropping it stops the watcher thread right away rather
+/// than on the next change in the directory.
+pub struct FileWatch {
+    pub changes: async_channel::Receiver<()>,
+    watches: Watches,
+    descriptor: WatchDescriptor,
+}
+
+impl Drop for FileWatch {
+    fn drop(&mut self) {
+        // Wakes the thread's blocking read with an IN_IGNORED event
+        let _ = self.watches.remove(self.descriptor.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::time::{Duration, Instant};
+
+    fn notified(rx: &async_channel::Receiver<()>, wait: Duration) -> bool {
+        let deadline = Instant::now() + wait;
+        while Instant::now() < deadline {
+            if rx.try_recv().is_ok() {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    #[test]
+    fn test_watch_append_and_replace() {
+        let dir = std::env::temp_dir().join(format!("pog-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.log");
+        std::fs::write(&path, b"one\n").unwrap();
+
+        let watch = watch(&path).unwrap();
+        let rx = watch.changes.clone();
+        std::fs::write(dir.join("other.log"), b"ignored\n").unwrap();
+        assert!(!notified(&rx, Duration::from_millis(200)));
+
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"two\n")
+            .unwrap();
+        assert!(notified(&rx, Duration::from_secs(2)));
+
+        std::fs::write(dir.join("app.log.new"), b"fresh\n").unwrap();
+        while rx.try_recv().is_ok() {}
+        std::fs::rename(dir.join("app.log.new"), &path).unwrap();
+        assert!(notified(&rx, Duration::from_secs(2)));
+
+        // The thread ends, dropping its sender, without another event
+        drop(watch);
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while !rx.is_closed() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(rx.is_closed());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
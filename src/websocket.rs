@@ -0,0 +1,519 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::commands::CommandResponse;
+use crate::json::{self, Json};
+use crate::server::{self, CommandRequest, CommandServer, EventHub, Reply, Session};
+
+/// Appended to the client's key before hashing, per RFC 6455
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// Larger messages close the connection; commands are a line each
+const MAX_MESSAGE_BYTES: usize = 1 << 20;
+/// Larger upgrade requests are refused before their headers are read
+const MAX_HANDSHAKE_BYTES: u64 = 16 << 10;
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+/// Start a WebSocket server for browser clients on `port` or the next free
+/// port after it. Each text message is a command and is answered with a JSON
+/// message; see doc/pog-lang.md. Handshakes whose `Origin` is not a local
+/// page or one of `origins` are refused, so other web sites cannot drive pog.
+pub fn start_websocket_server(
+    port: u16,
+    command_tx: async_channel::Sender<CommandRequest>,
+    events: EventHub,
+    origins: Vec<String>,
+) -> io::Result<CommandServer> {
+    let origins = Arc::new(origins);
+    server::listen(
+        "pog websocket server",
+        port,
+        command_tx,
+        events,
+        Arc::new(move |stream, command_tx, events| {
+            handle_client(stream, command_tx, events, &origins)
+        }),
+    )
+}
+
+fn handle_client(
+    stream: TcpStream,
+    command_tx: async_channel::Sender<CommandRequest>,
+    events: EventHub,
+    origins: &[String],
+) {
+    let peer = stream
+        .peer_addr()
+        .map(|a| a.to_string())
+        .unwrap_or_else(|_| "unknown".to_string());
+    let mut reader = match stream.try_clone() {
+        Ok(s) => BufReader::new(s),
+        Err(e) => {
+            eprintln!("Failed to clone stream for {}: {}", peer, e);
+            return;
+        }
+    };
+    // Shared with the threads forwarding subscribed events
+    let writer = Arc::new(Mutex::new(stream));
+
+    if let Err(e) = handshake(&mut reader, &mut *writer.lock().unwrap(), origins) {
+        eprintln!("WebSocket handshake with {} failed: {}", peer, e);
+        return;
+    }
+
+    let mut session = Session::new(command_tx, events);
+    loop {
+        let message = match read_message(&mut reader, &writer) {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(e) => {
+                eprintln!("Read error from {}: {}", peer, e);
+                break;
+            }
+        };
+        let (id, line) = match parse_request(&message) {
+            Ok(request) => request,
+            Err(e) => {
+                let reply = reply_json("null", &CommandResponse::Error(e));
+                if send(&writer, OPCODE_TEXT, reply.as_bytes()).is_err() {
+                    break;
+                }
+                continue;
+            }
+        };
+        let reply = match session.handle_line(&line) {
+            Reply::Response(response) => reply_json(&id, &response),
+            Reply::Events(events) => {
+                // Events arrive as their own messages while commands go on
+                let writer = writer.clone();
+                thread::spawn(move || {
+                    for event in events {
+                        let event = event.strip_prefix("EVENT ").unwrap_or(&event);
+                        let message = format!("{{\"event\": {}}}", json::string(event));
+                        if send(&writer, OPCODE_TEXT, message.as_bytes()).is_err() {
+                            break;
+                        }
+                    }
+                });
+                reply_json(&id, &CommandResponse::Ok(None))
+            }
+        };
+        if let Err(e) = send(&writer, OPCODE_TEXT, reply.as_bytes()) {
+            eprintln!("Write error to {}: {}", peer, e);
+            break;
+        }
+    }
+}
+
+/// Read the HTTP upgrade request and accept it.
+fn handshake(reader: &mut impl BufRead, writer: &mut impl Write, origins: &[String]) -> Result<(), String> {
+    let mut key = None;
+    let mut origin = None;
+    let mut remaining = MAX_HANDSHAKE_BYTES;
+    loop {
+        let mut line = String::new();
+        let read = reader
+            .by_ref()
+            .take(remaining)
+            .read_line(&mut line)
+            .map_err(|e| e.to_string())?;
+        if read == 0 && remaining == 0 {
+            let status = "431 Request Header Fields Too Large";
+            let _ = writer.write_all(format!("HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status).as_bytes());
+            return Err("handshake headers too large".to_string());
+        }
+        if read == 0 {
+            return Err("connection closed during handshake".to_string());
+        }
+        remaining -= read as u64;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            match name.trim().to_ascii_lowercase().as_str() {
+                "sec-websocket-key" => key = Some(value.trim().to_string()),
+                "origin" => origin = Some(value.trim().to_string()),
+                _ => {}
+            }
+        }
+    }
+
+    let refusal = match (&key, &origin) {
+        (None, _) => Some("400 Bad Request"),
+        (_, Some(origin)) if !origin_allowed(origin, origins) => Some("403 Forbidden"),
+        _ => None,
+    };
+    if let Some(status) = refusal {
+        let _ = writer.write_all(format!("HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status).as_bytes());
+        return Err(match origin {
+            Some(origin) if key.is_some() => format!("origin not allowed: {}", origin),
+            _ => "not a WebSocket upgrade request".to_string(),
+        });
+    }
+
+    let response = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key.as_deref().unwrap_or_default())
+    );
+    writer.write_all(response.as_bytes()).map_err(|e| e.to_string())
+}
+
+/// Pages served from this machine, and those named with `--websocket-origin`.
+/// Clients that are not browsers send no `Origin` and are always accepted.
+fn origin_allowed(origin: &str, origins: &[String]) -> bool {
+    if origins.iter().any(|allowed| allowed.trim_end_matches('/') == origin) {
+        return true;
+    }
+    let host = origin
+        .split_once("://")
+        .map_or(origin, |(_, rest)| rest)
+        .trim_end_matches('/');
+    let host = match host.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    matches!(host, "localhost" | "127.0.0.1" | "::1")
+}
+
+/// `Sec-WebSocket-Accept` for the client's `Sec-WebSocket-Key`.
+fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, ACCEPT_GUID).as_bytes()))
+}
+
+/// The next text message, answering pings on the way. `None` when the
+/// client closes the connection.
+fn read_message(reader: &mut impl Read, writer: &Mutex<TcpStream>) -> io::Result<Option<String>> {
+    let mut message = Vec::new();
+    loop {
+        let mut header = [0u8; 2];
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let fin = header[0] & 0x80 != 0;
+        let opcode = header[0] & 0x0F;
+        if header[1] & 0x80 == 0 {
+            // RFC 6455 requires clients to mask every frame
+            let _ = send(writer, OPCODE_CLOSE, &1002u16.to_be_bytes());
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "unmasked client frame"));
+        }
+        let length = match header[1] & 0x7F {
+            126 => {
+                let mut bytes = [0u8; 2];
+                reader.read_exact(&mut bytes)?;
+                u16::from_be_bytes(bytes) as u64
+            }
+            127 => {
+                let mut bytes = [0u8; 8];
+                reader.read_exact(&mut bytes)?;
+                u64::from_be_bytes(bytes)
+            }
+            n => n as u64,
+        };
+        if length > (MAX_MESSAGE_BYTES - message.len()) as u64 {
+            let _ = send(writer, OPCODE_CLOSE, &1009u16.to_be_bytes());
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "message too large"));
+        }
+        let mut mask = [0u8; 4];
+        reader.read_exact(&mut mask)?;
+        let mut payload = vec![0u8; length as usize];
+        reader.read_exact(&mut payload)?;
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+
+        match opcode {
+            OPCODE_TEXT | OPCODE_CONTINUATION => {
+                message.extend_from_slice(&payload);
+                if fin {
+                    return String::from_utf8(message)
+                        .map(Some)
+                        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "message is not UTF-8"));
+                }
+            }
+            OPCODE_PING => send(writer, OPCODE_PONG, &payload)?,
+            OPCODE_PONG => {}
+            OPCODE_CLOSE => {
+                let _ = send(writer, OPCODE_CLOSE, &payload);
+                return Ok(None);
+            }
+            _ => {
+                // Binary messages are not part of the protocol
+                let _ = send(writer, OPCODE_CLOSE, &1003u16.to_be_bytes());
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "unsupported message type"));
+            }
+        }
+    }
+}
+
+/// Write one unmasked, unfragmented frame.
+fn send(writer: &Mutex<TcpStream>, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut stream = writer.lock().unwrap();
+    stream.write_all(&frame(opcode, payload))?;
+    stream.flush()
+}
+
+fn frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + 10);
+    frame.push(0x80 | opcode);
+    match payload.len() {
+        n if n < 126 => frame.push(n as u8),
+        n if n <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(n as u16).to_be_bytes());
+        }
+        n => {
+            frame.push(127);
+            frame.extend_from_slice(&(n as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// The request id, as JSON to echo back, and the command line of a message:
+/// either a bare command or `{"id": 7, "command": "goto 100"}`.
+fn parse_request(message: &str) -> Result<(String, String), String> {
+    let message = message.trim();
+    if !message.starts_with('{') {
+        return Ok(("null".to_string(), message.to_string()));
+    }
+    let value = json::parse(message)?;
+    let id = match value.get("id") {
+        Some(Json::Number(number)) => number.clone(),
+        Some(Json::String(text)) => json::string(text),
+        Some(Json::Null) | None => "null".to_string(),
+        Some(_) => return Err("\"id\" must be a number or a string".to_string()),
+    };
+    let command = match value.get("command") {
+        Some(command) => command.string("command")?.to_string(),
+        None => return Err("missing \"command\"".to_string()),
+    };
+    if command.contains('\n') {
+        return Err("\"command\" must be a single line".to_string());
+    }
+    Ok((id, command))
+}
+
+/// `{"id": 7, "ok": true, "response": "52301"}` or
+/// `{"id": 7, "ok": false, "error": "..."}`. `response` is the text after
+/// `OK`, with the same lines a TCP client would read, or null.
+fn reply_json(id: &str, response: &CommandResponse) -> String {
+    match response {
+        CommandResponse::Ok(None) => format!("{{\"id\": {}, \"ok\": true, \"response\": null}}", id),
+        CommandResponse::Ok(Some(text)) => format!(
+            "{{\"id\": {}, \"ok\": true, \"response\": {}}}",
+            id,
+            json::string(text)
+        ),
+        CommandResponse::Error(e) => {
+            format!("{{\"id\": {}, \"ok\": false, \"error\": {}}}", id, json::string(e))
+        }
+    }
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut padded = data.to_vec();
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    padded.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in padded.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+    for (chunk, state) in digest.chunks_mut(4).zip(h) {
+        chunk.copy_from_slice(&state.to_be_bytes());
+    }
+    digest
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_accept_key() {
+        // The example handshake of RFC 6455
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+        assert_eq!(base64(b"ab"), "YWI=");
+        assert_eq!(base64(b""), "");
+    }
+
+    #[test]
+    fn test_handshake() {
+        let request = "GET / HTTP/1.1\r\nHost: localhost:9877\r\nUpgrade: websocket\r\n\
+                       Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nOrigin: http://localhost:3000\r\n\r\n";
+        let mut response = Vec::new();
+        handshake(&mut Cursor::new(request), &mut response, &[]).unwrap();
+        let response = String::from_utf8(response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 101"));
+        assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+
+        let foreign = request.replace("http://localhost:3000", "https://example.com");
+        let mut response = Vec::new();
+        assert!(handshake(&mut Cursor::new(foreign.as_str()), &mut response, &[]).is_err());
+        assert!(response.starts_with(b"HTTP/1.1 403"));
+        let allowed = ["https://example.com".to_string()];
+        assert!(handshake(&mut Cursor::new(foreign.as_str()), &mut Vec::new(), &allowed).is_ok());
+
+        let mut response = Vec::new();
+        assert!(handshake(&mut Cursor::new("GET / HTTP/1.1\r\n\r\n"), &mut response, &[]).is_err());
+        assert!(response.starts_with(b"HTTP/1.1 400"));
+
+        let huge = format!("GET / HTTP/1.1\r\nX-Filler: {}\r\n\r\n", "a".repeat(20 << 10));
+        let mut response = Vec::new();
+        assert!(handshake(&mut Cursor::new(huge.as_str()), &mut response, &[]).is_err());
+        assert!(response.starts_with(b"HTTP/1.1 431"));
+    }
+
+    #[test]
+    fn test_origin_allowed() {
+        assert!(origin_allowed("http://127.0.0.1:8080", &[]));
+        assert!(origin_allowed("http://[::1]:8080", &[]));
+        assert!(!origin_allowed("http://localhost.example.com", &[]));
+        assert!(!origin_allowed("null", &[]));
+    }
+
+    #[test]
+    fn test_parse_request() {
+        assert_eq!(parse_request("goto 100\r\n"), Ok(("null".to_string(), "goto 100".to_string())));
+        assert_eq!(
+            parse_request(r#"{"id": 7, "command": "goto 100"}"#),
+            Ok(("7".to_string(), "goto 100".to_string()))
+        );
+        assert_eq!(
+            parse_request(r#"{"id": "a", "command": "lines"}"#),
+            Ok(("\"a\"".to_string(), "lines".to_string()))
+        );
+        assert!(parse_request(r#"{"id": 7}"#).is_err());
+        assert!(parse_request(r#"{"command": "goto 1\ngoto 2"}"#).is_err());
+    }
+
+    #[test]
+    fn test_reply_json() {
+        assert_eq!(
+            reply_json("7", &CommandResponse::Ok(Some("2\n41 1 a".to_string()))),
+            r#"{"id": 7, "ok": true, "response": "2\n41 1 a"}"#
+        );
+        assert_eq!(
+            reply_json("null", &CommandResponse::Error("unknown command".to_string())),
+            r#"{"id": null, "ok": false, "error": "unknown command"}"#
+        );
+    }
+
+    #[test]
+    fn test_commands_over_socket() {
+        let (command_tx, command_rx) = async_channel::unbounded::<CommandRequest>();
+        let ui = thread::spawn(move || {
+            while let Ok(request) = command_rx.recv_blocking() {
+                let _ = request.response_tx.send(CommandResponse::Ok(Some("42".to_string())));
+            }
+        });
+        let server = start_websocket_server(19906, command_tx, EventHub::default(), Vec::new()).unwrap();
+
+        let mut client = TcpStream::connect(("127.0.0.1", server.port())).unwrap();
+        client
+            .write_all(b"GET / HTTP/1.1\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n")
+            .unwrap();
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+        let mut line = String::new();
+        while line != "\r\n" {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+        }
+
+        // Clients mask their frames
+        let request = br#"{"id": 1, "command": "lines"}"#;
+        let mask = [1u8, 2, 3, 4];
+        let mut frame = vec![0x81, 0x80 | request.len() as u8];
+        frame.extend_from_slice(&mask);
+        frame.extend(request.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        client.write_all(&frame).unwrap();
+
+        let mut header = [0u8; 2];
+        reader.read_exact(&mut header).unwrap();
+        let mut reply = vec![0u8; header[1] as usize];
+        reader.read_exact(&mut reply).unwrap();
+        assert_eq!(header[0], 0x81);
+        assert_eq!(reply, br#"{"id": 1, "ok": true, "response": "42"}"#);
+
+        // An unmasked frame is a protocol error
+        let mut frame = vec![0x81, request.len() as u8];
+        frame.extend_from_slice(request);
+        client.write_all(&frame).unwrap();
+        let mut close = [0u8; 4];
+        reader.read_exact(&mut close).unwrap();
+        assert_eq!(close, [0x88, 2, 0x03, 0xEA]);
+
+        server.shutdown();
+        ui.join().unwrap();
+    }
+
+    #[test]
+    fn test_frame_lengths() {
+        assert_eq!(frame(OPCODE_TEXT, b"OK"), vec![0x81, 2, b'O', b'K']);
+        let long = frame(OPCODE_TEXT, &[0; 300]);
+        assert_eq!(&long[..4], &[0x81, 126, 1, 44]);
+        assert_eq!(long.len(), 304);
+    }
+}
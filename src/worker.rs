@@ -0,0 +1,2390 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::future::{poll_fn, Future};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::pin::pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::Poll;
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+
+use crate::file_source::{FileSource, SourceChange};
+use crate::filter::{FilteredLines, FilteredView, LineFilter, LineFold};
+use crate::hexview::{self, ViewMode};
+use crate::error::PogError;
+use crate::search::{self, CancelToken, MatchDensity, SearchDirection, SearchMatch};
+use crate::columns::FieldView;
+use crate::logfmt::KeyView;
+use crate::table::{ColumnView, Delimiter};
+use crate::timestamp::{self, TimeIndex, TimeRange, Timestamp};
+
+/// Identifies one view (window or tab) so worker responses reach only the view that asked.
+pub type ViewId = u64;
+
+static VIEW_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// How often background work (match counts, filter indexes, exports) reports progress
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Match positions a match count remembers; the total stays exact beyond it
+const MAX_MATCH_POSITIONS: usize = 100_000;
+
+/// A match found for a socket command: (line, column, length, wrapped around)
+pub type MatchLocation = (usize, usize, usize, bool);
+
+pub enum FileRequest {
+    GetLines {
+        start: usize,
+        count: usize,
+        request_id: u64,
+    },
+    SearchRange {
+        pattern: String,
+        start_line: usize,
+        end_line: usize,
+        request_id: u64,
+        navigate_to_first: bool,  // Only navigate to first match on initial search
+        multiline: bool,  // Matches may span lines
+    },
+    FindNextMatch {
+        pattern: String,
+        multiline: bool,
+        from_line: usize,
+        direction: SearchDirection,
+        /// Start over from the other end of the file when there are no more matches
+        wrap: bool,
+        request_id: u64,
+        // Channel to send back match info for a socket command's response
+        result_tx: Option<async_channel::Sender<Option<MatchLocation>>>,
+    },
+    /// Every match in the view, e.g. to mark them all. Fails rather than
+    /// collect more than `limit`.
+    FindAllMatches {
+        pattern: String,
+        multiline: bool,
+        limit: usize,
+        result_tx: async_channel::Sender<Result<Vec<SearchMatch>, String>>,
+    },
+    /// Every line with a match, for socket commands acting on whole lines
+    FindMatchingLines {
+        pattern: String,
+        limit: usize,
+        result_tx: async_channel::Sender<Result<Vec<usize>, String>>,
+    },
+    /// The text of up to `count` lines from `start`, for socket commands
+    /// such as `get` and `copy`. Read a chunk at a time, so a cancelled read
+    /// of many lines stops early.
+    ReadLines {
+        start: usize,
+        count: usize,
+        result_tx: async_channel::Sender<Result<Vec<(usize, String)>, String>>,
+    },
+    /// The first `limit` lines with a match from `from_line` on, with their
+    /// text, for `grep`
+    GrepLines {
+        pattern: String,
+        from_line: usize,
+        limit: usize,
+        result_tx: async_channel::Sender<Result<Vec<(usize, String)>, String>>,
+    },
+}
+
+impl FileRequest {
+    /// A new search or a jump to the next match makes earlier ones of the
+    /// same view pointless. Re-searches after scrolling only cover the
+    /// viewport and replace nothing.
+    fn supersedes_searches(&self) -> bool {
+        matches!(
+            self,
+            FileRequest::FindNextMatch { .. }
+                | FileRequest::SearchRange {
+                    navigate_to_first: true,
+                    ..
+                }
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum FileResponse {
+    Lines {
+        lines: Vec<(usize, String)>,
+        request_id: u64,
+        start: usize,
+        /// Some of the lines contain binary data
+        has_binary: bool,
+        /// Lines replaced by a hex dump, whose columns are not the text's
+        hex_dumps: Vec<usize>,
+        /// With the time column on, the stamp of the last stamped line before
+        /// `start`, which the first stamped line shown is timed from
+        previous_time: Option<Timestamp>,
+    },
+    Error {
+        message: String,
+        /// A page of lines failed to load, e.g. a remote read that timed
+        /// out: fetching it again may work
+        retry: bool,
+    },
+    SearchResults {
+        matches: Vec<SearchMatch>,
+        #[allow(dead_code)]
+        request_id: u64,
+        searched_range: (usize, usize),
+        navigate_to_first: bool,
+    },
+    FoundMatch {
+        match_info: Option<SearchMatch>,
+        line_num: Option<usize>,
+        direction: SearchDirection,
+        /// The search went past the end of the file (past the start, going
+        /// backward) and found the match after starting over
+        wrapped: bool,
+        #[allow(dead_code)]
+        request_id: u64,
+    },
+    SourceChanged {
+        change: SourceChange,
+        line_count: usize,
+    },
+    /// A match count is still running (or waiting for the file to be indexed)
+    MatchCountProgress {
+        matches: usize,
+        searched_lines: usize,
+        line_count: usize,
+        request_id: u64,
+    },
+    /// The view's filters changed or their index grew; `line_count` is the
+    /// number of lines the view now shows
+    Filtered {
+        line_count: usize,
+        /// The index has caught up with the source
+        done: bool,
+    },
+    /// A match count reached the end of the file. It resumes and reports again
+    /// when the file grows.
+    MatchCountDone {
+        matches: usize,
+        /// (line, start column) of each match, in order, up to `MAX_MATCH_POSITIONS`
+        match_positions: Vec<(usize, usize)>,
+        /// Where all the matches lie, for an overview of the file
+        match_density: MatchDensity,
+        request_id: u64,
+    },
+    /// An export has written `written` of its `total` lines
+    ExportProgress {
+        written: usize,
+        total: usize,
+    },
+    ExportDone {
+        lines: usize,
+        path: PathBuf,
+    },
+    /// An export stopped early; the file holds the lines written so far
+    ExportFailed {
+        message: String,
+    },
+}
+
+pub enum WorkerMessage {
+    Attach {
+        view_id: ViewId,
+        response_tx: async_channel::Sender<FileResponse>,
+    },
+    Detach {
+        view_id: ViewId,
+    },
+    Request {
+        view_id: ViewId,
+        request: FileRequest,
+        /// Set when a newer search of the same view replaces this one
+        cancel: CancelToken,
+    },
+    /// Check the file on disk for changes; the result goes to every view
+    Refresh,
+    /// Open the file again; the change goes to every view, the line count
+    /// known so far or the error to `result_tx`
+    Reload {
+        result_tx: async_channel::Sender<Result<usize, String>>,
+    },
+    SetViewMode {
+        view_id: ViewId,
+        mode: ViewMode,
+    },
+    SetTimeColumn {
+        view_id: ViewId,
+        enabled: bool,
+    },
+    CountMatches {
+        view_id: ViewId,
+        pattern: String,
+        multiline: bool,
+        request_id: u64,
+    },
+    CancelMatchCount {
+        view_id: ViewId,
+    },
+    SetFilters {
+        view_id: ViewId,
+        filter: Option<Arc<FilteredLines>>,
+    },
+    SetSearchScope {
+        view_id: ViewId,
+        scope: SearchScope,
+    },
+    Export {
+        view_id: ViewId,
+        start: usize,
+        end: usize,
+        file: File,
+        path: PathBuf,
+    },
+    CancelExport {
+        view_id: ViewId,
+    },
+}
+
+/// A view's connection to the worker of its source. Requests sent through it
+/// are tagged with the view id, and the worker routes the responses back to
+/// that view's own response channel.
+#[derive(Clone)]
+pub struct ViewChannel {
+    view_id: ViewId,
+    tx: async_channel::Sender<WorkerMessage>,
+    /// Token of the view's latest search, cancelled when the next one is sent
+    search_cancel: Arc<Mutex<CancelToken>>,
+    /// Time index of the source, shared by its views and built by the worker
+    time_index: Arc<TimeIndex>,
+}
+
+impl ViewChannel {
+    /// Send a request to the worker. A new search cancels the view's previous
+    /// one, whether it is still queued or already scanning.
+    pub fn send_blocking(
+        &self,
+        request: FileRequest,
+    ) -> Result<(), async_channel::SendError<WorkerMessage>> {
+        self.send(request).map(|_| ())
+    }
+
+    /// Send a request answered through its `result_tx` and await the answer,
+    /// giving up once `timeout` completes (a main loop timer for the UI).
+    ///
+    /// A request still queued or running by then is cancelled: scans stop at
+    /// their next chunk. A read already blocked in the source, such as a
+    /// fetch from a remote host that stopped answering, cannot be
+    /// interrupted, and the view's later requests wait until it returns.
+    pub async fn call<T>(
+        &self,
+        request: impl FnOnce(async_channel::Sender<T>) -> FileRequest,
+        timeout: impl Future<Output = ()>,
+    ) -> Result<T, String> {
+        let (result_tx, result_rx) = async_channel::bounded(1);
+        let cancel = self
+            .send(request(result_tx))
+            .map_err(|_| "file worker stopped".to_string())?;
+        match answer_within(result_rx, timeout).await {
+            Some(Ok(result)) => Ok(result),
+            // Dropped unanswered, e.g. a search replaced by a newer one
+            Some(Err(_)) => Err("request dropped by the file worker".to_string()),
+            None => {
+                cancel.cancel();
+                Err("timeout".to_string())
+            }
+        }
+    }
+
+    /// Send a request, returning the token that cancels it.
+    fn send(
+        &self,
+        request: FileRequest,
+    ) -> Result<CancelToken, async_channel::SendError<WorkerMessage>> {
+        let cancel = CancelToken::default();
+        if request.supersedes_searches() {
+            let mut current = self.search_cancel.lock().unwrap();
+            current.cancel();
+            *current = cancel.clone();
+        }
+        self.tx.send_blocking(WorkerMessage::Request {
+            view_id: self.view_id,
+            request,
+            cancel: cancel.clone(),
+        })?;
+        Ok(cancel)
+    }
+
+    /// Render this view's binary lines as text or hex dumps from now on.
+    pub fn set_view_mode(&self, mode: ViewMode) {
+        let _ = self.tx.send_blocking(WorkerMessage::SetViewMode {
+            view_id: self.view_id,
+            mode,
+        });
+    }
+
+    /// Send the stamp before the first line with each page of lines from
+    /// now on, for the time column, and keep the source's time index growing
+    /// while on.
+    pub fn set_time_column(&self, enabled: bool) {
+        let _ = self.tx.send_blocking(WorkerMessage::SetTimeColumn {
+            view_id: self.view_id,
+            enabled,
+        });
+    }
+
+    /// The time index of the source, as far as the worker has built it.
+    pub fn time_index(&self) -> &TimeIndex {
+        &self.time_index
+    }
+
+    /// Count the matches of `pattern` in the whole file in the background,
+    /// replacing any count this view has running. Progress and the final
+    /// total arrive as `MatchCountProgress` and `MatchCountDone` responses.
+    pub fn count_matches(&self, pattern: String, multiline: bool, request_id: u64) {
+        let _ = self.tx.send_blocking(WorkerMessage::CountMatches {
+            view_id: self.view_id,
+            pattern,
+            multiline,
+            request_id,
+        });
+    }
+
+    pub fn cancel_match_count(&self) {
+        let _ = self.tx.send_blocking(WorkerMessage::CancelMatchCount {
+            view_id: self.view_id,
+        });
+    }
+
+    /// Show only the lines `filters` keep, with runs of repeats collapsed by
+    /// `fold` and the source line ranges in `hidden` left out, or every line
+    /// when there are none of these. From now on this view's line numbers
+    /// count the kept lines; the returned index maps them back to lines of
+    /// the file while the worker builds it.
+    pub fn set_filters(
+        &self,
+        filters: Vec<LineFilter>,
+        fold: Option<LineFold>,
+        hidden: Vec<(usize, usize)>,
+    ) -> Option<Arc<FilteredLines>> {
+        let filter = (!filters.is_empty() || fold.is_some() || !hidden.is_empty())
+            .then(|| Arc::new(FilteredLines::new(filters, fold, hidden)));
+        let _ = self.tx.send_blocking(WorkerMessage::SetFilters {
+            view_id: self.view_id,
+            filter: filter.clone(),
+        });
+        filter
+    }
+
+    /// Limit this view's searches, match counts and match navigation to
+    /// `scope` until the next call. The default scope searches everything.
+    pub fn set_search_scope(&self, scope: SearchScope) {
+        let _ = self.tx.send_blocking(WorkerMessage::SetSearchScope {
+            view_id: self.view_id,
+            scope,
+        });
+    }
+
+    /// Write the view's lines `start..end` to `file` in the background,
+    /// replacing any export this view has running. Progress and the result
+    /// arrive as `ExportProgress`, `ExportDone` or `ExportFailed` responses.
+    pub fn export(&self, start: usize, end: usize, file: File, path: PathBuf) {
+        let _ = self.tx.send_blocking(WorkerMessage::Export {
+            view_id: self.view_id,
+            start,
+            end,
+            file,
+            path,
+        });
+    }
+
+    pub fn cancel_export(&self) {
+        let _ = self.tx.send_blocking(WorkerMessage::CancelExport {
+            view_id: self.view_id,
+        });
+    }
+
+    /// Ask the worker to check the file on disk for changes.
+    pub fn refresh(&self) {
+        let _ = self.tx.send_blocking(WorkerMessage::Refresh);
+    }
+
+    /// Re-open the file, returning its line count so far: the worker indexes
+    /// it again in the background, like a newly opened file. Gives up once
+    /// `timeout` completes, like `call`, though the worker still reloads.
+    pub async fn reload(&self, timeout: impl Future<Output = ()>) -> Result<usize, String> {
+        let (result_tx, result_rx) = async_channel::bounded(1);
+        self.tx
+            .send_blocking(WorkerMessage::Reload { result_tx })
+            .map_err(|_| "file worker stopped".to_string())?;
+        match answer_within(result_rx, timeout).await {
+            Some(Ok(result)) => result,
+            Some(Err(_)) => Err("file worker stopped".to_string()),
+            None => Err("timeout".to_string()),
+        }
+    }
+
+    /// Stop routing responses to this view. Pending requests for it are dropped.
+    pub fn detach(&self) {
+        let _ = self.tx.send_blocking(WorkerMessage::Detach {
+            view_id: self.view_id,
+        });
+    }
+}
+
+/// The answer on `result_rx`, or `None` once `timeout` completes first.
+async fn answer_within<T>(
+    result_rx: async_channel::Receiver<T>,
+    timeout: impl Future<Output = ()>,
+) -> Option<Result<T, async_channel::RecvError>> {
+    let mut answer = pin!(result_rx.recv());
+    let mut timeout = pin!(timeout);
+    poll_fn(|cx| match answer.as_mut().poll(cx) {
+        Poll::Ready(answer) => Poll::Ready(Some(answer)),
+        Poll::Pending => timeout.as_mut().poll(cx).map(|()| None),
+    })
+    .await
+}
+
+/// What a view's searches are limited to, until its next search sets it again.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchScope {
+    /// Only the lines stamped within these times
+    pub time_range: TimeRange,
+    /// Only this part of each line
+    pub field: Option<SearchField>,
+}
+
+/// The part of each line a search looks at.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchField {
+    /// The columns `start..end`, e.g. one fixed-width field
+    Columns(usize, usize),
+    /// The value of a logfmt key; lines without the key are skipped
+    Key(String),
+    /// Column `n` (0-based) of the table view, split by its delimiter;
+    /// lines with fewer columns are skipped
+    Delimited(Delimiter, usize),
+}
+
+/// A view's search scope applied to its lines: the lines within its search
+/// times, found by binary search over the line timestamps.
+#[derive(Debug, Clone)]
+struct SearchLimits {
+    lines: (usize, usize),
+    field: Option<SearchField>,
+}
+
+impl Default for SearchLimits {
+    fn default() -> Self {
+        Self {
+            lines: (0, usize::MAX),
+            field: None,
+        }
+    }
+}
+
+/// Worker-side state of one attached view.
+struct AttachedView {
+    response_tx: async_channel::Sender<FileResponse>,
+    mode: ViewMode,
+    match_count: Option<MatchCount>,
+    filter: Option<ViewFilter>,
+    export: Option<Export>,
+    search_scope: SearchScope,
+    search_limits: SearchLimits,
+    /// The view shows the time since the previous line
+    time_column: bool,
+}
+
+impl AttachedView {
+    /// Apply the view's search scope to its lines again, after they changed.
+    /// The time index of the source helps while the view has no filters.
+    fn resolve_search_limits(&mut self, source: &dyn FileSource, time_index: &TimeIndex) {
+        let scope = self.search_scope.clone();
+        let index = self.filter.is_none().then_some(time_index);
+        let lines = if scope.time_range.is_unbounded() {
+            Ok((0, usize::MAX))
+        } else {
+            with_filter(source, self.filter.as_ref(), |source| {
+                scope.time_range.lines(source, index)
+            })
+        };
+        self.search_limits = SearchLimits {
+            field: scope.field,
+            ..SearchLimits::default()
+        };
+        match lines {
+            Ok(lines) => self.search_limits.lines = lines,
+            Err(e) => {
+                let _ = self.response_tx.send_blocking(FileResponse::Error {
+                    message: e.to_string(),
+                    retry: false,
+                });
+            }
+        }
+    }
+
+    /// Whether the view's filter index, match count or export still has work to do.
+    fn is_busy(&self) -> bool {
+        self.filter.as_ref().is_some_and(|filter| filter.running)
+            || self.match_count.as_ref().is_some_and(|count| count.running)
+            || self.export.is_some()
+    }
+
+    /// Follow a change to the file in the view's filter index, match count
+    /// and export.
+    fn source_changed(&mut self, change: SourceChange) {
+        if let Some(filter) = self.filter.as_mut() {
+            if change.is_rewrite() {
+                filter.lines.reset();
+            }
+            filter.running |= change != SourceChange::Unchanged;
+        }
+        if let Some(count) = self.match_count.as_mut() {
+            count.source_changed(change);
+        }
+        if change.is_rewrite() {
+            self.fail_export("the file changed during the export");
+        }
+    }
+
+    /// Stop the view's export, if any, and tell the view why.
+    fn fail_export(&mut self, message: &str) {
+        if self.export.take().is_some() {
+            let _ = self.response_tx.send_blocking(FileResponse::ExportFailed {
+                message: message.to_string(),
+            });
+        }
+    }
+}
+
+/// A view's filters and the progress of their index, built a chunk at a time
+/// like match counts.
+struct ViewFilter {
+    lines: Arc<FilteredLines>,
+    running: bool,
+    last_progress: Instant,
+}
+
+impl ViewFilter {
+    fn new(lines: Arc<FilteredLines>) -> Self {
+        Self {
+            lines,
+            running: true,
+            last_progress: Instant::now(),
+        }
+    }
+}
+
+/// Run `f` on the source as a view sees it: through its filters, if any.
+fn with_filter<R>(
+    source: &dyn FileSource,
+    filter: Option<&ViewFilter>,
+    f: impl FnOnce(&dyn FileSource) -> R,
+) -> R {
+    match filter {
+        Some(filter) => f(&FilteredView::new(source, &filter.lines)),
+        None => f(source),
+    }
+}
+
+/// Matches starting in lines `start_line..end_line`, running across line
+/// boundaries for a multiline search. With a `field`, only the text in its
+/// columns, its table column or the value of its key is searched.
+fn search_range(
+    source: &dyn FileSource,
+    regex: &Regex,
+    multiline: bool,
+    field: Option<&SearchField>,
+    start_line: usize,
+    end_line: usize,
+) -> crate::error::Result<Vec<SearchMatch>> {
+    match field {
+        Some(&SearchField::Columns(start_col, end_col)) => {
+            let field = FieldView::new(source, start_col, end_col);
+            let mut matches = search_range(&field, regex, multiline, None, start_line, end_line)?;
+            for m in &mut matches {
+                m.shift_columns(start_col);
+            }
+            return Ok(matches);
+        }
+        Some(SearchField::Key(key)) => {
+            return KeyView::new(source, key).search_range(regex, start_line, end_line);
+        }
+        Some(SearchField::Delimited(delimiter, column)) => {
+            let view = ColumnView::new(source, delimiter, *column);
+            return view.search_range(regex, start_line, end_line);
+        }
+        None => {}
+    }
+    if multiline {
+        search::search_range_multiline(source, regex, start_line, end_line)
+    } else {
+        source.search_range(regex, start_line, end_line)
+    }
+}
+
+/// The next match after `from_line` (before it, going backward), across line
+/// boundaries for a multiline search and within the part of each line a
+/// `field` picks.
+#[allow(clippy::too_many_arguments)]
+fn find_match(
+    source: &dyn FileSource,
+    regex: &Regex,
+    multiline: bool,
+    field: Option<&SearchField>,
+    from_line: usize,
+    direction: SearchDirection,
+    chunk_size: usize,
+    cancel: &CancelToken,
+) -> crate::error::Result<Option<SearchMatch>> {
+    match field {
+        Some(&SearchField::Columns(start_col, end_col)) => {
+            let field = FieldView::new(source, start_col, end_col);
+            let found = find_match(
+                &field, regex, multiline, None, from_line, direction, chunk_size, cancel,
+            )?;
+            return Ok(found.map(|mut m| {
+                m.shift_columns(start_col);
+                m
+            }));
+        }
+        Some(SearchField::Key(key)) => {
+            let view = KeyView::new(source, key);
+            return view.find_next(regex, from_line, direction, chunk_size, cancel);
+        }
+        Some(SearchField::Delimited(delimiter, column)) => {
+            let view = ColumnView::new(source, delimiter, *column);
+            return view.find_next(regex, from_line, direction, chunk_size, cancel);
+        }
+        None => {}
+    }
+    if multiline {
+        search::scan_for_multiline_match(source, regex, from_line, direction, chunk_size, cancel)
+    } else {
+        source.find_next(regex, from_line, direction, chunk_size, cancel)
+    }
+}
+
+/// Characters a match covers, counting each line break it spans as one.
+fn match_length(source: &dyn FileSource, m: &SearchMatch) -> usize {
+    if m.end_line == m.line_num {
+        return m.end_col - m.start_col;
+    }
+    let lines = source
+        .get_lines(m.line_num, m.end_line - m.line_num)
+        .unwrap_or_default();
+    let before_end_line: usize = lines.iter().map(|(_, line)| line.chars().count() + 1).sum();
+    before_end_line.saturating_sub(m.start_col) + m.end_col
+}
+
+/// A whole-file match count. The worker searches one chunk at a time between
+/// requests, so counting a huge file never holds up scrolling.
+struct MatchCount {
+    regex: Regex,
+    multiline: bool,
+    request_id: u64,
+    next_line: usize,
+    matches: usize,
+    match_positions: Vec<(usize, usize)>,
+    match_density: MatchDensity,
+    running: bool,
+    last_progress: Instant,
+}
+
+impl MatchCount {
+    fn new(regex: Regex, multiline: bool, request_id: u64) -> Self {
+        Self {
+            regex,
+            multiline,
+            request_id,
+            next_line: 0,
+            matches: 0,
+            match_positions: Vec::new(),
+            match_density: MatchDensity::default(),
+            running: true,
+            last_progress: Instant::now(),
+        }
+    }
+
+    /// Follow a change to the file: count new lines, or start over when the
+    /// content was rewritten.
+    fn source_changed(&mut self, change: SourceChange) {
+        match change {
+            SourceChange::Unchanged => {}
+            SourceChange::Grew | SourceChange::Indexed => self.running = true,
+            SourceChange::Truncated | SourceChange::Replaced | SourceChange::Reloaded => {
+                *self = Self::new(self.regex.clone(), self.multiline, self.request_id);
+            }
+        }
+    }
+
+    /// Search the next `chunk_size` lines within `limits`, returning a
+    /// response when there is progress to report.
+    fn step(
+        &mut self,
+        source: &dyn FileSource,
+        chunk_size: usize,
+        limits: &SearchLimits,
+    ) -> crate::error::Result<Option<FileResponse>> {
+        let line_count = source.line_count().min(limits.lines.1);
+        self.next_line = self.next_line.max(limits.lines.0);
+        let end = (self.next_line + chunk_size.max(1)).min(line_count);
+        if self.next_line < end {
+            let matches = search_range(
+                source,
+                &self.regex,
+                self.multiline,
+                limits.field.as_ref(),
+                self.next_line,
+                end,
+            )?;
+            self.matches += matches.len();
+            let room = MAX_MATCH_POSITIONS - self.match_positions.len();
+            self.match_positions
+                .extend(matches.iter().take(room).map(|m| (m.line_num, m.start_col)));
+            for m in &matches {
+                self.match_density.add(m.line_num);
+            }
+            self.next_line = end;
+        }
+
+        if self.next_line >= line_count {
+            self.running = false;
+            // Lines still being indexed are counted once the worker hears of them
+            if source.indexing_progress().is_none() {
+                return Ok(Some(FileResponse::MatchCountDone {
+                    matches: self.matches,
+                    match_positions: self.match_positions.clone(),
+                    match_density: self.match_density.clone(),
+                    request_id: self.request_id,
+                }));
+            }
+        } else if self.last_progress.elapsed() < PROGRESS_INTERVAL {
+            return Ok(None);
+        }
+        self.last_progress = Instant::now();
+        Ok(Some(FileResponse::MatchCountProgress {
+            matches: self.matches,
+            searched_lines: self.next_line,
+            line_count,
+            request_id: self.request_id,
+        }))
+    }
+}
+
+/// Lines of a view written to a local file, a chunk at a time like match
+/// counts, so exporting a slice of a huge remote file never holds up scrolling.
+struct Export {
+    writer: BufWriter<File>,
+    path: PathBuf,
+    start: usize,
+    next_line: usize,
+    end: usize,
+    last_progress: Instant,
+}
+
+impl Export {
+    fn new(file: File, path: PathBuf, start: usize, end: usize) -> Self {
+        Self {
+            writer: BufWriter::new(file),
+            path,
+            start,
+            next_line: start,
+            end,
+            last_progress: Instant::now(),
+        }
+    }
+
+    /// Write the next `chunk_size` lines, returning a response when there is
+    /// progress to report. The export is finished once it returns `ExportDone`.
+    fn step(
+        &mut self,
+        source: &dyn FileSource,
+        chunk_size: usize,
+    ) -> crate::error::Result<Option<FileResponse>> {
+        let count = chunk_size.max(1).min(self.end - self.next_line);
+        let lines = source.get_lines(self.next_line, count)?;
+        if lines.is_empty() {
+            return Err(PogError::Unsupported("the file ended before the export did"));
+        }
+        for (_, text) in &lines {
+            self.writer.write_all(text.as_bytes())?;
+            self.writer.write_all(b"\n")?;
+        }
+        self.next_line += lines.len();
+
+        let total = self.end - self.start;
+        if self.next_line >= self.end {
+            self.writer.flush()?;
+            return Ok(Some(FileResponse::ExportDone {
+                lines: total,
+                path: self.path.clone(),
+            }));
+        }
+        if self.last_progress.elapsed() < PROGRESS_INTERVAL {
+            return Ok(None);
+        }
+        self.last_progress = Instant::now();
+        Ok(Some(FileResponse::ExportProgress {
+            written: self.next_line - self.start,
+            total,
+        }))
+    }
+}
+
+/// One worker thread per file source, shared by every view of that source.
+/// A slow source only blocks its own worker, never the views of other sources.
+#[derive(Default)]
+pub struct WorkerRegistry {
+    workers: HashMap<usize, (async_channel::WeakSender<WorkerMessage>, Arc<TimeIndex>)>,
+}
+
+impl WorkerRegistry {
+    pub fn open_view(
+        &mut self,
+        source: &Arc<dyn FileSource>,
+        search_chunk_size: usize,
+    ) -> (ViewChannel, async_channel::Receiver<FileResponse>) {
+        let key = Arc::as_ptr(source) as *const () as usize;
+        self.workers.retain(|_, (weak, _)| weak.upgrade().is_some());
+
+        let worker = self
+            .workers
+            .get(&key)
+            .and_then(|(weak, time_index)| Some((weak.upgrade()?, time_index.clone())));
+        let (tx, time_index) = match worker {
+            Some(worker) => worker,
+            None => {
+                let (tx, rx) = async_channel::unbounded();
+                let time_index = Arc::new(TimeIndex::default());
+                spawn_source_worker(source.clone(), search_chunk_size, time_index.clone(), rx);
+                self.workers.insert(key, (tx.downgrade(), time_index.clone()));
+                (tx, time_index)
+            }
+        };
+
+        let view_id = VIEW_COUNTER.fetch_add(1, Ordering::SeqCst);
+        let (response_tx, response_rx) = async_channel::unbounded();
+        let _ = tx.send_blocking(WorkerMessage::Attach {
+            view_id,
+            response_tx,
+        });
+
+        let channel = ViewChannel {
+            view_id,
+            tx,
+            search_cancel: Arc::default(),
+            time_index,
+        };
+        (channel, response_rx)
+    }
+}
+
+fn spawn_source_worker(
+    source: Arc<dyn FileSource>,
+    search_chunk_size: usize,
+    time_index: Arc<TimeIndex>,
+    rx: async_channel::Receiver<WorkerMessage>,
+) {
+    std::thread::spawn(move || {
+        let mut views: HashMap<ViewId, AttachedView> = HashMap::new();
+        // Set when reading lines for the time index failed, until the file changes
+        let mut time_index_failed = false;
+
+        // Exits once every ViewChannel for this source has been dropped
+        loop {
+            let views_busy = views.values().any(AttachedView::is_busy);
+            // The time index grows while a view shows the time column
+            let indexing_times = views.values().any(|view| view.time_column)
+                && !time_index_failed
+                && time_index.scanned() < source.line_count();
+            let message = if views_busy || indexing_times {
+                // Requests go first; background work advances while the queue is empty
+                match rx.try_recv() {
+                    Ok(message) => message,
+                    Err(async_channel::TryRecvError::Empty) => {
+                        if views_busy {
+                            step_background_work(source.as_ref(), search_chunk_size, &mut views);
+                        } else if let Err(e) = time_index.extend(source.as_ref(), search_chunk_size) {
+                            time_index_failed = true;
+                            broadcast(&views, || FileResponse::Error {
+                                message: e.to_string(),
+                                retry: false,
+                            });
+                        }
+                        continue;
+                    }
+                    Err(async_channel::TryRecvError::Closed) => break,
+                }
+            } else {
+                match rx.recv_blocking() {
+                    Ok(message) => message,
+                    Err(_) => break,
+                }
+            };
+
+            match message {
+                WorkerMessage::Attach {
+                    view_id,
+                    response_tx,
+                } => {
+                    views.insert(
+                        view_id,
+                        AttachedView {
+                            response_tx,
+                            mode: ViewMode::default(),
+                            match_count: None,
+                            filter: None,
+                            export: None,
+                            search_scope: SearchScope::default(),
+                            search_limits: SearchLimits::default(),
+                            time_column: false,
+                        },
+                    );
+                }
+                WorkerMessage::Detach { view_id } => {
+                    views.remove(&view_id);
+                }
+                WorkerMessage::Refresh => match source.refresh() {
+                    Ok(change) => {
+                        if change != SourceChange::Unchanged {
+                            time_index_failed = false;
+                        }
+                        notify_source_changed(source.as_ref(), &time_index, &mut views, change)
+                    }
+                    Err(e) => broadcast(&views, || FileResponse::Error {
+                        message: e.to_string(),
+                        retry: false,
+                    }),
+                },
+                WorkerMessage::Reload { result_tx } => {
+                    // Indexing goes on in the background; `Indexed` changes
+                    // tell the views once the count is exact
+                    let result = source.reload().map(|change| {
+                        time_index_failed = false;
+                        notify_source_changed(source.as_ref(), &time_index, &mut views, change);
+                        source.line_count()
+                    });
+                    let _ = result_tx.send_blocking(result.map_err(|e| e.to_string()));
+                }
+                WorkerMessage::SetViewMode { view_id, mode } => {
+                    if let Some(view) = views.get_mut(&view_id) {
+                        view.mode = mode;
+                    }
+                }
+                WorkerMessage::SetTimeColumn { view_id, enabled } => {
+                    if let Some(view) = views.get_mut(&view_id) {
+                        view.time_column = enabled;
+                    }
+                }
+                WorkerMessage::CountMatches {
+                    view_id,
+                    pattern,
+                    multiline,
+                    request_id,
+                } => {
+                    if let Some(view) = views.get_mut(&view_id) {
+                        view.match_count = match Regex::new(&pattern) {
+                            Ok(regex) => Some(MatchCount::new(regex, multiline, request_id)),
+                            Err(e) => {
+                                let _ = view.response_tx.send_blocking(FileResponse::Error {
+                                    message: format!("invalid regex: {}", e),
+                                    retry: false,
+                                });
+                                None
+                            }
+                        };
+                    }
+                }
+                WorkerMessage::CancelMatchCount { view_id } => {
+                    if let Some(view) = views.get_mut(&view_id) {
+                        view.match_count = None;
+                    }
+                }
+                WorkerMessage::SetFilters { view_id, filter } => {
+                    if let Some(view) = views.get_mut(&view_id) {
+                        view.fail_export("the view's filters changed during the export");
+                        view.filter = filter.map(ViewFilter::new);
+                        // Matches are counted again among the lines now shown
+                        view.resolve_search_limits(source.as_ref(), &time_index);
+                        if let Some(count) = view.match_count.as_mut() {
+                            count.source_changed(SourceChange::Replaced);
+                        }
+                        let line_count =
+                            with_filter(source.as_ref(), view.filter.as_ref(), |source| {
+                                source.line_count()
+                            });
+                        let _ = view.response_tx.send_blocking(FileResponse::Filtered {
+                            line_count,
+                            done: view.filter.is_none(),
+                        });
+                    }
+                }
+                WorkerMessage::SetSearchScope { view_id, scope } => {
+                    if let Some(view) = views.get_mut(&view_id) {
+                        view.search_scope = scope;
+                        view.resolve_search_limits(source.as_ref(), &time_index);
+                    }
+                }
+                WorkerMessage::Export {
+                    view_id,
+                    start,
+                    end,
+                    file,
+                    path,
+                } => {
+                    if let Some(view) = views.get_mut(&view_id) {
+                        view.export = Some(Export::new(file, path, start, end));
+                    }
+                }
+                WorkerMessage::CancelExport { view_id } => {
+                    if let Some(view) = views.get_mut(&view_id) {
+                        view.export = None;
+                    }
+                }
+                WorkerMessage::Request {
+                    view_id,
+                    request,
+                    cancel,
+                } => {
+                    // Superseded while queued: the view no longer wants the result
+                    if cancel.is_cancelled() {
+                        continue;
+                    }
+                    if let Some(view) = views.get(&view_id) {
+                        // The index counts the lines of the file, not of a filtered view
+                        let index = view.filter.is_none().then_some(time_index.as_ref());
+                        with_filter(source.as_ref(), view.filter.as_ref(), |source| {
+                            handle_request(
+                                source,
+                                search_chunk_size,
+                                view.mode,
+                                view.time_column,
+                                index,
+                                &view.search_limits,
+                                request,
+                                &cancel,
+                                &view.response_tx,
+                            )
+                        });
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Advance the background work of the first busy view by one chunk: its
+/// filter index first, since the match count and export run over the
+/// filtered lines.
+fn step_background_work(
+    source: &dyn FileSource,
+    search_chunk_size: usize,
+    views: &mut HashMap<ViewId, AttachedView>,
+) {
+    let Some(view) = views.values_mut().find(|view| view.is_busy()) else {
+        return;
+    };
+    if view.filter.as_ref().is_some_and(|filter| filter.running) {
+        step_filter(source, search_chunk_size, view);
+    } else if view.match_count.as_ref().is_some_and(|count| count.running) {
+        step_match_count(source, search_chunk_size, view);
+    } else {
+        step_export(source, search_chunk_size, view);
+    }
+}
+
+/// Index the next chunk of the view's filters, reporting the growing line
+/// count now and then and once the index has caught up.
+fn step_filter(source: &dyn FileSource, search_chunk_size: usize, view: &mut AttachedView) {
+    let Some(filter) = view.filter.as_mut() else {
+        return;
+    };
+    let shown = filter.lines.line_count();
+    match filter.lines.extend(source, search_chunk_size) {
+        Ok(read) => {
+            let done = read == 0 || filter.lines.scanned() >= source.line_count();
+            filter.running = !done;
+            if filter.lines.line_count() > shown {
+                if let Some(count) = view.match_count.as_mut() {
+                    count.running = true;
+                }
+            }
+            if done || filter.last_progress.elapsed() >= PROGRESS_INTERVAL {
+                filter.last_progress = Instant::now();
+                let _ = view.response_tx.send_blocking(FileResponse::Filtered {
+                    line_count: filter.lines.line_count(),
+                    done,
+                });
+            }
+        }
+        Err(e) => {
+            filter.running = false;
+            let _ = view.response_tx.send_blocking(FileResponse::Error {
+                message: e.to_string(),
+                retry: false,
+            });
+        }
+    }
+}
+
+/// Advance the view's match count by one chunk. A count that fails is
+/// dropped after reporting the error.
+fn step_match_count(source: &dyn FileSource, search_chunk_size: usize, view: &mut AttachedView) {
+    let limits = view.search_limits.clone();
+    let Some(count) = view.match_count.as_mut() else {
+        return;
+    };
+    let step = with_filter(source, view.filter.as_ref(), |source| {
+        count.step(source, search_chunk_size, &limits)
+    });
+    match step {
+        Ok(Some(response)) => {
+            let _ = view.response_tx.send_blocking(response);
+        }
+        Ok(None) => {}
+        Err(e) => {
+            view.match_count = None;
+            let _ = view.response_tx.send_blocking(FileResponse::Error {
+                message: e.to_string(),
+                retry: false,
+            });
+        }
+    }
+}
+
+/// Write the next chunk of the view's export, dropping it once it is done
+/// or has failed.
+fn step_export(source: &dyn FileSource, search_chunk_size: usize, view: &mut AttachedView) {
+    let Some(export) = view.export.as_mut() else {
+        return;
+    };
+    let step = with_filter(source, view.filter.as_ref(), |source| {
+        export.step(source, search_chunk_size)
+    });
+    match step {
+        Ok(Some(response)) => {
+            if matches!(response, FileResponse::ExportDone { .. }) {
+                view.export = None;
+            }
+            let _ = view.response_tx.send_blocking(response);
+        }
+        Ok(None) => {}
+        Err(e) => view.fail_export(&e.to_string()),
+    }
+}
+
+/// Send a response to every view of the worker's source.
+fn broadcast(views: &HashMap<ViewId, AttachedView>, response: impl Fn() -> FileResponse) {
+    for view in views.values() {
+        let _ = view.response_tx.send_blocking(response());
+    }
+}
+
+/// Tell every view how the file changed, with its line count after the
+/// change. The time index starts over when the content was replaced.
+fn notify_source_changed(
+    source: &dyn FileSource,
+    time_index: &TimeIndex,
+    views: &mut HashMap<ViewId, AttachedView>,
+    change: SourceChange,
+) {
+    if change.is_rewrite() {
+        time_index.reset();
+    }
+    for view in views.values_mut() {
+        view.source_changed(change);
+        if change != SourceChange::Unchanged {
+            view.resolve_search_limits(source, time_index);
+        }
+        let line_count = with_filter(source, view.filter.as_ref(), |source| source.line_count());
+        let _ = view
+            .response_tx
+            .send_blocking(FileResponse::SourceChanged { change, line_count });
+    }
+}
+
+/// A page of lines as displayed, with the fields of `FileResponse::Lines`.
+struct RenderedLines {
+    lines: Vec<(usize, String)>,
+    has_binary: bool,
+    hex_dumps: Vec<usize>,
+}
+
+/// Fetch lines for display. Control characters are made visible, or in hex
+/// mode whole binary lines are replaced by a dump of their raw bytes.
+fn render_lines(
+    source: &dyn FileSource,
+    start: usize,
+    count: usize,
+    mode: ViewMode,
+) -> crate::error::Result<RenderedLines> {
+    let mut lines = source.get_lines(start, count)?;
+    let binary: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, text))| hexview::is_binary(text))
+        .map(|(i, _)| i)
+        .collect();
+    if binary.is_empty() {
+        return Ok(RenderedLines {
+            lines,
+            has_binary: false,
+            hex_dumps: Vec::new(),
+        });
+    }
+
+    let mut hex_dumps = Vec::new();
+    match mode {
+        ViewMode::Text => {
+            for &i in &binary {
+                lines[i].1 = hexview::show_control_chars(&lines[i].1);
+            }
+        }
+        ViewMode::Hex => {
+            let raw: HashMap<usize, Vec<u8>> =
+                source.get_raw_lines(start, count)?.into_iter().collect();
+            for &i in &binary {
+                if let Some(bytes) = raw.get(&lines[i].0) {
+                    lines[i].1 = hexview::hex_dump(bytes);
+                    hex_dumps.push(lines[i].0);
+                }
+            }
+        }
+    }
+    Ok(RenderedLines {
+        lines,
+        has_binary: true,
+        hex_dumps,
+    })
+}
+
+/// Serve one request of a view. Searches and match navigation stay within
+/// the `limits` of the view's search scope. With the time column on, pages
+/// of lines come with the stamp before them, found through `time_index` when
+/// it indexes `source`'s lines.
+#[allow(clippy::too_many_arguments)]
+fn handle_request(
+    source: &dyn FileSource,
+    search_chunk_size: usize,
+    mode: ViewMode,
+    time_column: bool,
+    time_index: Option<&TimeIndex>,
+    limits: &SearchLimits,
+    request: FileRequest,
+    cancel: &CancelToken,
+    response_tx: &async_channel::Sender<FileResponse>,
+) {
+    match request {
+        FileRequest::GetLines {
+            start,
+            count,
+            request_id,
+        } => match render_lines(source, start, count, mode) {
+            Ok(RenderedLines {
+                lines,
+                has_binary,
+                hex_dumps,
+            }) => {
+                // A stamp that cannot be read leaves the first delta blank
+                let previous_time = if time_column {
+                    timestamp::previous_time(source, time_index, start).ok().flatten()
+                } else {
+                    None
+                };
+                let _ = response_tx.send_blocking(FileResponse::Lines {
+                    lines,
+                    request_id,
+                    start,
+                    has_binary,
+                    hex_dumps,
+                    previous_time,
+                });
+            }
+            Err(e) => {
+                let _ = response_tx.send_blocking(FileResponse::Error {
+                    message: e.to_string(),
+                    retry: true,
+                });
+            }
+        },
+        FileRequest::SearchRange {
+            pattern,
+            start_line,
+            end_line,
+            request_id,
+            navigate_to_first,
+            multiline,
+        } => {
+            // The requested range is reported as searched, even where it
+            // lies outside the search times
+            let (first_line, last_line) = (
+                start_line.max(limits.lines.0),
+                end_line.min(limits.lines.1),
+            );
+            match regex::Regex::new(&pattern) {
+                Ok(regex) => {
+                    let matches = if first_line < last_line {
+                        search_range(
+                            source,
+                            &regex,
+                            multiline,
+                            limits.field.as_ref(),
+                            first_line,
+                            last_line,
+                        )
+                    } else {
+                        Ok(Vec::new())
+                    };
+                    match matches {
+                        Ok(matches) => {
+                            let _ = response_tx.send_blocking(FileResponse::SearchResults {
+                                matches,
+                                request_id,
+                                searched_range: (start_line, end_line),
+                                navigate_to_first,
+                            });
+                        }
+                        Err(e) => {
+                            let _ = response_tx.send_blocking(FileResponse::Error {
+                                message: e.to_string(),
+                                retry: false,
+                            });
+                        }
+                    }
+                }
+                Err(e) => {
+                    let _ = response_tx.send_blocking(FileResponse::Error {
+                        message: format!("invalid regex: {}", e),
+                        retry: false,
+                    });
+                }
+            }
+        }
+        FileRequest::FindNextMatch {
+            pattern,
+            multiline,
+            from_line,
+            direction,
+            wrap,
+            request_id,
+            result_tx,
+        } => {
+            let mut wrapped = false;
+            let found = regex::Regex::new(&pattern)
+                .map_err(PogError::from)
+                .and_then(|regex| {
+                    let find = |from_line| {
+                        find_match(
+                            source,
+                            &regex,
+                            multiline,
+                            limits.field.as_ref(),
+                            from_line,
+                            direction,
+                            search_chunk_size,
+                            cancel,
+                        )
+                    };
+                    // `find` starts after its line, so the first line is checked apart
+                    let find_from = |line| {
+                        let field = limits.field.as_ref();
+                        let first = search_range(source, &regex, multiline, field, line, line + 1)?;
+                        match first.into_iter().next() {
+                            Some(first) => Ok(Some(first)),
+                            None => find(line),
+                        }
+                    };
+                    let (start_line, end_line) = limits.lines;
+                    let last_line = end_line.min(source.line_count());
+                    let in_bounds = |m: &SearchMatch| (start_line..end_line).contains(&m.line_num);
+                    let found = match direction {
+                        SearchDirection::Forward if from_line < start_line => {
+                            find_from(start_line)?
+                        }
+                        SearchDirection::Forward => find(from_line)?,
+                        SearchDirection::Backward => find(from_line.min(last_line))?,
+                    };
+                    match found.filter(in_bounds) {
+                        None if wrap => {
+                            let found = match direction {
+                                SearchDirection::Forward => find_from(start_line)?,
+                                SearchDirection::Backward => find(last_line)?,
+                            }
+                            .filter(in_bounds);
+                            wrapped = found.is_some();
+                            Ok(found)
+                        }
+                        found => Ok(found),
+                    }
+                });
+
+            // Send result through sync channel if provided (for socket commands)
+            if let Some(tx) = result_tx {
+                let result = found.as_ref().ok().and_then(|found| {
+                    found
+                        .as_ref()
+                        .map(|m| (m.line_num, m.start_col, match_length(source, m), wrapped))
+                });
+                let _ = tx.send_blocking(result);
+            }
+
+            match found {
+                Ok(found) => {
+                    let _ = response_tx.send_blocking(FileResponse::FoundMatch {
+                        line_num: found.as_ref().map(|m| m.line_num),
+                        match_info: found,
+                        direction,
+                        wrapped,
+                        request_id,
+                    });
+                }
+                // The search that replaced this one reports instead
+                Err(PogError::Cancelled) => {}
+                Err(e) => {
+                    let _ = response_tx.send_blocking(FileResponse::Error {
+                        message: e.to_string(),
+                        retry: false,
+                    });
+                }
+            }
+        }
+        FileRequest::FindAllMatches {
+            pattern,
+            multiline,
+            limit,
+            result_tx,
+        } => {
+            let result = regex::Regex::new(&pattern)
+                .map_err(|e| format!("invalid regex: {}", e))
+                .and_then(|regex| {
+                    let line_count = source.line_count();
+                    let mut matches = Vec::new();
+                    let mut start = 0;
+                    while start < line_count {
+                        if cancel.is_cancelled() {
+                            return Err(PogError::Cancelled.to_string());
+                        }
+                        let end = (start + search_chunk_size.max(1)).min(line_count);
+                        matches.extend(
+                            search_range(source, &regex, multiline, None, start, end)
+                                .map_err(|e| e.to_string())?,
+                        );
+                        if matches.len() > limit {
+                            return Err(format!("too many matches (more than {})", limit));
+                        }
+                        start = end;
+                    }
+                    Ok(matches)
+                });
+            let _ = result_tx.send_blocking(result);
+        }
+        FileRequest::FindMatchingLines {
+            pattern,
+            limit,
+            result_tx,
+        } => {
+            let result = regex::Regex::new(&pattern)
+                .map_err(|e| format!("invalid regex: {}", e))
+                .and_then(|regex| {
+                    let line_count = source.line_count();
+                    let mut lines = Vec::new();
+                    let mut start = 0;
+                    while start < line_count {
+                        if cancel.is_cancelled() {
+                            return Err(PogError::Cancelled.to_string());
+                        }
+                        let end = (start + search_chunk_size.max(1)).min(line_count);
+                        let matches = search_range(source, &regex, false, None, start, end)
+                            .map_err(|e| e.to_string())?;
+                        for m in matches {
+                            if lines.last() != Some(&m.line_num) {
+                                lines.push(m.line_num);
+                            }
+                        }
+                        if lines.len() > limit {
+                            return Err(format!("too many lines (more than {})", limit));
+                        }
+                        start = end;
+                    }
+                    Ok(lines)
+                });
+            let _ = result_tx.send_blocking(result);
+        }
+        FileRequest::ReadLines {
+            start,
+            count,
+            result_tx,
+        } => {
+            let end = start.saturating_add(count).min(source.line_count());
+            let mut lines = Vec::new();
+            let mut chunk_start = start;
+            let result = loop {
+                if chunk_start >= end {
+                    break Ok(lines);
+                }
+                if cancel.is_cancelled() {
+                    break Err(PogError::Cancelled.to_string());
+                }
+                let chunk = search_chunk_size.max(1).min(end - chunk_start);
+                match source.get_lines(chunk_start, chunk) {
+                    Ok(chunk_lines) => lines.extend(chunk_lines),
+                    Err(e) => break Err(e.to_string()),
+                }
+                chunk_start += chunk;
+            };
+            let _ = result_tx.send_blocking(result);
+        }
+        FileRequest::GrepLines {
+            pattern,
+            from_line,
+            limit,
+            result_tx,
+        } => {
+            let result = regex::Regex::new(&pattern)
+                .map_err(|e| format!("invalid regex: {}", e))
+                .and_then(|regex| {
+                    let line_count = source.line_count();
+                    let mut lines = Vec::new();
+                    let mut start = from_line;
+                    while start < line_count && lines.len() < limit {
+                        if cancel.is_cancelled() {
+                            return Err(PogError::Cancelled.to_string());
+                        }
+                        let end = (start + search_chunk_size.max(1)).min(line_count);
+                        // Matches are found where the source lives, and only
+                        // the matching lines are read
+                        let matches = search_range(source, &regex, false, None, start, end)
+                            .map_err(|e| e.to_string())?;
+                        for m in matches {
+                            if lines.len() == limit {
+                                break;
+                            }
+                            if lines.last().map(|(line, _)| *line) == Some(m.line_num) {
+                                continue;
+                            }
+                            if let Some(text) =
+                                source.get_line(m.line_num).map_err(|e| e.to_string())?
+                            {
+                                lines.push((m.line_num, text));
+                            }
+                        }
+                        start = end;
+                    }
+                    Ok(lines)
+                });
+            let _ = result_tx.send_blocking(result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_source::VecSource;
+    use crate::error::Result;
+
+    fn test_source() -> Arc<dyn FileSource> {
+        Arc::new(VecSource::new((0..10).map(|i| format!("line {}", i))))
+    }
+
+    #[test]
+    fn test_responses_routed_to_requesting_view() {
+        let source = test_source();
+        let mut registry = WorkerRegistry::default();
+        let (view_a, rx_a) = registry.open_view(&source, 1000);
+        let (view_b, rx_b) = registry.open_view(&source, 1000);
+        assert_ne!(view_a.view_id, view_b.view_id);
+
+        view_b
+            .send_blocking(FileRequest::GetLines {
+                start: 5,
+                count: 2,
+                request_id: 7,
+            })
+            .unwrap();
+
+        match rx_b.recv_blocking().unwrap() {
+            FileResponse::Lines { start, request_id, lines, .. } => {
+                assert_eq!((start, request_id, lines.len()), (5, 7, 2));
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+        assert!(rx_a.try_recv().is_err());
+    }
+
+    /// A remote source whose host went away: every read fails
+    struct UnreachableSource;
+
+    impl FileSource for UnreachableSource {
+        fn line_count(&self) -> usize {
+            10
+        }
+
+        fn file_size(&self) -> Result<u64> {
+            Ok(0)
+        }
+
+        fn get_line(&self, _line_num: usize) -> Result<Option<String>> {
+            Err(PogError::ConnectionFailed { host: "logs".to_string() })
+        }
+
+        fn get_lines(&self, _start_line: usize, _count: usize) -> Result<Vec<(usize, String)>> {
+            Err(PogError::ConnectionFailed { host: "logs".to_string() })
+        }
+
+        fn display_name(&self) -> &str {
+            "logs:/var/log/app.log"
+        }
+    }
+
+    #[test]
+    fn test_failed_page_can_be_retried() {
+        let source: Arc<dyn FileSource> = Arc::new(UnreachableSource);
+        let mut registry = WorkerRegistry::default();
+        let (view, rx) = registry.open_view(&source, 1000);
+
+        view.send_blocking(FileRequest::GetLines {
+            start: 0,
+            count: 5,
+            request_id: 1,
+        })
+        .unwrap();
+        match rx.recv_blocking().unwrap() {
+            FileResponse::Error { retry, .. } => assert!(retry),
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_views_of_same_source_share_worker() {
+        let source = test_source();
+        let other = test_source();
+        let mut registry = WorkerRegistry::default();
+        let (view_a, _rx_a) = registry.open_view(&source, 1000);
+        let (view_b, _rx_b) = registry.open_view(&source, 1000);
+        let (view_c, _rx_c) = registry.open_view(&other, 1000);
+        assert!(view_a.tx.same_channel(&view_b.tx));
+        assert!(!view_a.tx.same_channel(&view_c.tx));
+    }
+
+    #[test]
+    fn test_hex_mode_dumps_binary_lines() {
+        let source: Arc<dyn FileSource> = Arc::new(VecSource::new(["text", "bin\0ary"]));
+        let mut registry = WorkerRegistry::default();
+        let (view, rx) = registry.open_view(&source, 1000);
+        let get_lines = || FileRequest::GetLines {
+            start: 0,
+            count: 2,
+            request_id: 1,
+        };
+
+        view.send_blocking(get_lines()).unwrap();
+        match rx.recv_blocking().unwrap() {
+            FileResponse::Lines { lines, has_binary, .. } => {
+                assert!(has_binary);
+                assert_eq!(lines[0].1, "text");
+                assert_eq!(lines[1].1, "bin\u{2400}ary");
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        view.set_view_mode(ViewMode::Hex);
+        view.send_blocking(get_lines()).unwrap();
+        match rx.recv_blocking().unwrap() {
+            FileResponse::Lines { lines, hex_dumps, .. } => {
+                assert_eq!(lines[0].1, "text");
+                assert_eq!(lines[1].1, "62 69 6e 00 61 72 79  |bin.ary|");
+                assert_eq!(hex_dumps, vec![1]);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_time_column_sends_previous_stamp() {
+        let source: Arc<dyn FileSource> = Arc::new(VecSource::new([
+            "2024-05-01 12:00:00 start",
+            "2024-05-01 12:00:02 step",
+            "    detail",
+            "2024-05-01 12:00:05 end",
+        ]));
+        let mut registry = WorkerRegistry::default();
+        let (view, rx) = registry.open_view(&source, 1000);
+        let get_lines = || FileRequest::GetLines {
+            start: 3,
+            count: 1,
+            request_id: 1,
+        };
+
+        view.send_blocking(get_lines()).unwrap();
+        assert!(matches!(
+            rx.recv_blocking().unwrap(),
+            FileResponse::Lines { previous_time: None, .. }
+        ));
+
+        view.set_time_column(true);
+        view.send_blocking(get_lines()).unwrap();
+        match rx.recv_blocking().unwrap() {
+            FileResponse::Lines { previous_time, .. } => {
+                assert_eq!(previous_time, timestamp::parse_timestamp("2024-05-01 12:00:02"));
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+        // The index is built while the column is on
+        while view.time_index().scanned() < 4 {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_refresh_reaches_every_view() {
+        let source = test_source();
+        let mut registry = WorkerRegistry::default();
+        let (view_a, rx_a) = registry.open_view(&source, 1000);
+        let (_view_b, rx_b) = registry.open_view(&source, 1000);
+
+        view_a.refresh();
+        for rx in [&rx_a, &rx_b] {
+            match rx.recv_blocking().unwrap() {
+                FileResponse::SourceChanged { change, line_count } => {
+                    assert_eq!((change, line_count), (SourceChange::Unchanged, 10));
+                }
+                other => panic!("unexpected response: {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_reload_unsupported_leaves_views_alone() {
+        let source = test_source();
+        let mut registry = WorkerRegistry::default();
+        let (view, rx) = registry.open_view(&source, 1000);
+
+        assert_eq!(
+            block_on(view.reload(sleep(Duration::from_secs(5)))),
+            Err("this file cannot be reloaded".to_string())
+        );
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_match_count_covers_whole_file() {
+        let source = test_source();
+        let mut registry = WorkerRegistry::default();
+        let (view, rx) = registry.open_view(&source, 3);
+
+        view.count_matches("[13579]$".to_string(), false, 4);
+        loop {
+            match rx.recv_blocking().unwrap() {
+                FileResponse::MatchCountProgress { request_id, .. } => assert_eq!(request_id, 4),
+                FileResponse::MatchCountDone {
+                    matches,
+                    match_positions,
+                    match_density,
+                    request_id,
+                } => {
+                    assert_eq!((matches, request_id), (5, 4));
+                    assert_eq!(match_positions, vec![(1, 5), (3, 5), (5, 5), (7, 5), (9, 5)]);
+                    assert_eq!(match_density.spans().count(), 5);
+                    break;
+                }
+                other => panic!("unexpected response: {:?}", other),
+            }
+        }
+
+        // A finished count stays quiet until the file changes
+        view.refresh();
+        assert!(matches!(
+            rx.recv_blocking().unwrap(),
+            FileResponse::SourceChanged { .. }
+        ));
+        view.cancel_match_count();
+        view.send_blocking(FileRequest::GetLines {
+            start: 0,
+            count: 1,
+            request_id: 5,
+        })
+        .unwrap();
+        assert!(matches!(
+            rx.recv_blocking().unwrap(),
+            FileResponse::Lines { request_id: 5, .. }
+        ));
+    }
+
+    #[test]
+    fn test_find_next_wraps_around() {
+        let source = test_source();
+        let mut registry = WorkerRegistry::default();
+        let (view, _rx) = registry.open_view(&source, 3);
+        let find = |pattern: &str, from_line, direction, wrap| {
+            let (result_tx, result_rx) = async_channel::unbounded();
+            view.send_blocking(FileRequest::FindNextMatch {
+                pattern: pattern.to_string(),
+                multiline: false,
+                from_line,
+                direction,
+                wrap,
+                request_id: 1,
+                result_tx: Some(result_tx),
+            })
+            .unwrap();
+            result_rx.recv_blocking().unwrap()
+        };
+
+        assert_eq!(find("line [03]", 8, SearchDirection::Forward, false), None);
+        assert_eq!(
+            find("line [03]", 8, SearchDirection::Forward, true),
+            Some((0, 0, 6, true))
+        );
+        assert_eq!(
+            find("line [79]", 1, SearchDirection::Backward, true),
+            Some((9, 0, 6, true))
+        );
+        // Matches ahead are found without wrapping
+        assert_eq!(
+            find("line [79]", 1, SearchDirection::Forward, true),
+            Some((7, 0, 6, false))
+        );
+        assert_eq!(find("missing", 5, SearchDirection::Forward, true), None);
+    }
+
+    #[test]
+    fn test_search_times_bound_searches() {
+        let source: Arc<dyn FileSource> = Arc::new(VecSource::new(
+            (0..10).map(|i| format!("2024-05-01 12:0{}:00 line {}", i, i)),
+        ));
+        let mut registry = WorkerRegistry::default();
+        let (view, rx) = registry.open_view(&source, 3);
+        let time = |text| Some(crate::timestamp::parse_time_bound(text).unwrap());
+        view.set_search_scope(SearchScope {
+            time_range: TimeRange {
+                after: time("2024-05-01 12:03"),
+                before: time("2024-05-01 12:07"),
+            },
+            field: None,
+        });
+
+        let find = |from_line, direction| {
+            let (result_tx, result_rx) = async_channel::unbounded();
+            view.send_blocking(FileRequest::FindNextMatch {
+                pattern: "line".to_string(),
+                multiline: false,
+                from_line,
+                direction,
+                wrap: true,
+                request_id: 1,
+                result_tx: Some(result_tx),
+            })
+            .unwrap();
+            result_rx.recv_blocking().unwrap().map(|(line, _, _, wrapped)| (line, wrapped))
+        };
+        assert_eq!(find(0, SearchDirection::Forward), Some((3, false)));
+        assert_eq!(find(6, SearchDirection::Forward), Some((3, true)));
+        assert_eq!(find(9, SearchDirection::Backward), Some((6, false)));
+        assert_eq!(find(3, SearchDirection::Backward), Some((6, true)));
+        for _ in 0..4 {
+            assert!(matches!(rx.recv_blocking().unwrap(), FileResponse::FoundMatch { .. }));
+        }
+
+        view.send_blocking(FileRequest::SearchRange {
+            pattern: "line".to_string(),
+            start_line: 0,
+            end_line: 5,
+            request_id: 2,
+            navigate_to_first: true,
+            multiline: false,
+        })
+        .unwrap();
+        match rx.recv_blocking().unwrap() {
+            FileResponse::SearchResults {
+                matches,
+                searched_range,
+                ..
+            } => {
+                let lines: Vec<usize> = matches.iter().map(|m| m.line_num).collect();
+                assert_eq!((lines, searched_range), (vec![3, 4], (0, 5)));
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        view.count_matches("line".to_string(), false, 3);
+        loop {
+            if let FileResponse::MatchCountDone { matches, .. } = rx.recv_blocking().unwrap() {
+                assert_eq!(matches, 4);
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_field_matches_within_columns() {
+        let source: Arc<dyn FileSource> = Arc::new(VecSource::new([
+            "ERROR db   timeout",
+            "INFO  web  ERROR page",
+            "WARN  ERROR x",
+        ]));
+        let mut registry = WorkerRegistry::default();
+        let (view, rx) = registry.open_view(&source, 3);
+        view.set_search_scope(SearchScope {
+            time_range: TimeRange::default(),
+            field: Some(SearchField::Columns(6, 11)),
+        });
+
+        view.send_blocking(FileRequest::SearchRange {
+            pattern: "ERROR".to_string(),
+            start_line: 0,
+            end_line: 3,
+            request_id: 1,
+            navigate_to_first: false,
+            multiline: false,
+        })
+        .unwrap();
+        match rx.recv_blocking().unwrap() {
+            FileResponse::SearchResults { matches, .. } => {
+                let found: Vec<_> = matches
+                    .iter()
+                    .map(|m| (m.line_num, m.start_col, m.end_col))
+                    .collect();
+                assert_eq!(found, vec![(2, 6, 11)]);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        let (result_tx, result_rx) = async_channel::unbounded();
+        view.send_blocking(FileRequest::FindNextMatch {
+            pattern: "ERROR".to_string(),
+            multiline: false,
+            from_line: 0,
+            direction: SearchDirection::Forward,
+            wrap: false,
+            request_id: 2,
+            result_tx: Some(result_tx),
+        })
+        .unwrap();
+        let found = result_rx.recv_blocking().unwrap();
+        assert_eq!(
+            found.map(|(line, column, length, _)| (line, column, length)),
+            Some((2, 6, 5))
+        );
+    }
+
+    #[test]
+    fn test_search_key_matches_within_values() {
+        let source: Arc<dyn FileSource> = Arc::new(VecSource::new([
+            "msg=timeout user=bob",
+            "user=timeout msg=ok",
+            "msg=\"db timeout\" x=1",
+        ]));
+        let mut registry = WorkerRegistry::default();
+        let (view, rx) = registry.open_view(&source, 3);
+        view.set_search_scope(SearchScope {
+            time_range: TimeRange::default(),
+            field: Some(SearchField::Key("msg".to_string())),
+        });
+
+        view.send_blocking(FileRequest::SearchRange {
+            pattern: "^.*timeout$".to_string(),
+            start_line: 0,
+            end_line: 3,
+            request_id: 1,
+            navigate_to_first: false,
+            multiline: false,
+        })
+        .unwrap();
+        match rx.recv_blocking().unwrap() {
+            FileResponse::SearchResults { matches, .. } => {
+                let found: Vec<_> = matches
+                    .iter()
+                    .map(|m| (m.line_num, m.start_col, m.end_col))
+                    .collect();
+                assert_eq!(found, vec![(0, 4, 11), (2, 5, 15)]);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_find_all_matches() {
+        let source = test_source();
+        let mut registry = WorkerRegistry::default();
+        let (view, _rx) = registry.open_view(&source, 3);
+        let find_all = |pattern: &str, limit| {
+            let (result_tx, result_rx) = async_channel::unbounded();
+            view.send_blocking(FileRequest::FindAllMatches {
+                pattern: pattern.to_string(),
+                multiline: false,
+                limit,
+                result_tx,
+            })
+            .unwrap();
+            result_rx.recv_blocking().unwrap()
+        };
+
+        let lines: Vec<usize> = find_all("[2468]$", 10)
+            .unwrap()
+            .iter()
+            .map(|m| m.line_num)
+            .collect();
+        assert_eq!(lines, vec![2, 4, 6, 8]);
+        assert!(find_all("line", 5).unwrap_err().starts_with("too many matches"));
+        assert!(find_all("(", 5).unwrap_err().starts_with("invalid regex"));
+    }
+
+    #[test]
+    fn test_find_matching_lines() {
+        let source: Arc<dyn FileSource> =
+            Arc::new(VecSource::new(["error error", "ok", "error", "ok error"]));
+        let mut registry = WorkerRegistry::default();
+        let (view, _rx) = registry.open_view(&source, 3);
+        let find_lines = |pattern: &str, limit| {
+            let (result_tx, result_rx) = async_channel::unbounded();
+            view.send_blocking(FileRequest::FindMatchingLines {
+                pattern: pattern.to_string(),
+                limit,
+                result_tx,
+            })
+            .unwrap();
+            result_rx.recv_blocking().unwrap()
+        };
+
+        // A line matching twice is listed once
+        assert_eq!(find_lines("error", 3), Ok(vec![0, 2, 3]));
+        assert!(find_lines("error", 2).unwrap_err().starts_with("too many lines"));
+        assert!(find_lines("(", 2).unwrap_err().starts_with("invalid regex"));
+    }
+
+    #[test]
+    fn test_grep_lines() {
+        let source = test_source();
+        let mut registry = WorkerRegistry::default();
+        let (view, _rx) = registry.open_view(&source, 3);
+        let grep = |pattern: &str, from_line, limit| {
+            let (result_tx, result_rx) = async_channel::unbounded();
+            view.send_blocking(FileRequest::GrepLines {
+                pattern: pattern.to_string(),
+                from_line,
+                limit,
+                result_tx,
+            })
+            .unwrap();
+            result_rx.recv_blocking().unwrap()
+        };
+
+        let line = |n: usize| (n, format!("line {}", n));
+        assert_eq!(grep("[2468]", 0, 10), Ok(vec![line(2), line(4), line(6), line(8)]));
+        // Stops at the limit; the next page starts after the last line
+        assert_eq!(grep("[2468]", 0, 2), Ok(vec![line(2), line(4)]));
+        assert_eq!(grep("[2468]", 5, 2), Ok(vec![line(6), line(8)]));
+        assert_eq!(grep("[2468]", 9, 2), Ok(vec![]));
+        assert!(grep("(", 0, 2).unwrap_err().starts_with("invalid regex"));
+    }
+
+    #[test]
+    fn test_read_lines_across_chunks() {
+        let source = test_source();
+        let mut registry = WorkerRegistry::default();
+        let (view, _rx) = registry.open_view(&source, 3);
+        let read = |start, count| {
+            let (result_tx, result_rx) = async_channel::unbounded();
+            view.send_blocking(FileRequest::ReadLines {
+                start,
+                count,
+                result_tx,
+            })
+            .unwrap();
+            result_rx.recv_blocking().unwrap()
+        };
+
+        let lines = |range: std::ops::Range<usize>| {
+            range.map(|n| (n, format!("line {}", n))).collect::<Vec<_>>()
+        };
+        assert_eq!(read(2, 5), Ok(lines(2..7)));
+        // Stops at the last line
+        assert_eq!(read(8, 5), Ok(lines(8..10)));
+        assert_eq!(read(10, 5), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_export_writes_range() {
+        let source = test_source();
+        let mut registry = WorkerRegistry::default();
+        let (view, rx) = registry.open_view(&source, 3);
+        let path = std::env::temp_dir().join(format!("pog-export-test-{}.log", std::process::id()));
+
+        view.export(2, 9, File::create(&path).unwrap(), path.clone());
+        loop {
+            match rx.recv_blocking().unwrap() {
+                FileResponse::ExportProgress { written, total } => {
+                    assert!(written < total && total == 7)
+                }
+                FileResponse::ExportDone { lines, path: done } => {
+                    assert_eq!((lines, done), (7, path.clone()));
+                    break;
+                }
+                other => panic!("unexpected response: {:?}", other),
+            }
+        }
+        let expected: String = (2..9).map(|i| format!("line {}\n", i)).collect();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), expected);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    /// A source taking `delay` for every read, counting the reads.
+    struct SlowSource {
+        inner: VecSource,
+        delay: Duration,
+        reads: AtomicU64,
+    }
+
+    impl FileSource for SlowSource {
+        fn line_count(&self) -> usize {
+            self.inner.line_count()
+        }
+
+        fn file_size(&self) -> Result<u64> {
+            Ok(0)
+        }
+
+        fn get_line(&self, line_num: usize) -> Result<Option<String>> {
+            self.inner.get_line(line_num)
+        }
+
+        fn get_lines(&self, start_line: usize, count: usize) -> Result<Vec<(usize, String)>> {
+            self.reads.fetch_add(1, Ordering::SeqCst);
+            std::thread::sleep(self.delay);
+            self.inner.get_lines(start_line, count)
+        }
+
+        fn display_name(&self) -> &str {
+            "slow"
+        }
+    }
+
+    /// Run `future` on this thread, parking it until the future is woken.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        struct ThreadWaker(std::thread::Thread);
+        impl std::task::Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+        let waker = Arc::new(ThreadWaker(std::thread::current())).into();
+        let mut cx = std::task::Context::from_waker(&waker);
+        let mut future = pin!(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            std::thread::park();
+        }
+    }
+
+    /// Completes after `duration`, standing in for the UI's main loop timer.
+    async fn sleep(duration: Duration) {
+        let (tx, rx) = async_channel::bounded::<()>(1);
+        std::thread::spawn(move || {
+            std::thread::sleep(duration);
+            drop(tx);
+        });
+        let _ = rx.recv().await;
+    }
+
+    #[test]
+    fn test_call_times_out_and_cancels() {
+        let slow = Arc::new(SlowSource {
+            inner: VecSource::new((0..40).map(|i| format!("line {}", i))),
+            delay: Duration::from_millis(50),
+            reads: AtomicU64::new(0),
+        });
+        let source: Arc<dyn FileSource> = slow.clone();
+        let mut registry = WorkerRegistry::default();
+        let (view, rx) = registry.open_view(&source, 1);
+
+        let find = |pattern: &str, timeout| {
+            block_on(view.call(
+                |result_tx| FileRequest::FindMatchingLines {
+                    pattern: pattern.to_string(),
+                    limit: 100,
+                    result_tx,
+                },
+                sleep(timeout),
+            ))
+        };
+        // A regex that fails to compile is answered before any read
+        assert!(matches!(find("(", Duration::from_secs(5)), Ok(Err(_))));
+        assert_eq!(find("line", Duration::from_millis(120)), Err("timeout".to_string()));
+
+        // The worker drops the cancelled scan and answers the next request
+        view.send_blocking(FileRequest::GetLines {
+            start: 0,
+            count: 1,
+            request_id: 2,
+        })
+        .unwrap();
+        assert!(matches!(
+            rx.recv_blocking().unwrap(),
+            FileResponse::Lines { request_id: 2, .. }
+        ));
+        assert!(slow.reads.load(Ordering::SeqCst) < 40);
+    }
+
+    /// A source whose index is never complete, like a pipe still being written.
+    struct UnfinishedSource(VecSource);
+
+    impl FileSource for UnfinishedSource {
+        fn line_count(&self) -> usize {
+            self.0.line_count()
+        }
+
+        fn file_size(&self) -> Result<u64> {
+            Ok(0)
+        }
+
+        fn get_line(&self, line_num: usize) -> Result<Option<String>> {
+            self.0.get_line(line_num)
+        }
+
+        fn get_lines(&self, start_line: usize, count: usize) -> Result<Vec<(usize, String)>> {
+            self.0.get_lines(start_line, count)
+        }
+
+        fn display_name(&self) -> &str {
+            "unfinished"
+        }
+
+        fn wait_indexed(&self) {
+            loop {
+                std::thread::park();
+            }
+        }
+
+        fn indexing_progress(&self) -> Option<f64> {
+            Some(0.5)
+        }
+
+        fn reload(&self) -> Result<SourceChange> {
+            Ok(SourceChange::Reloaded)
+        }
+    }
+
+    #[test]
+    fn test_reload_answers_before_indexing_finishes() {
+        let source: Arc<dyn FileSource> =
+            Arc::new(UnfinishedSource(VecSource::new((0..7).map(|i| format!("line {}", i)))));
+        let mut registry = WorkerRegistry::default();
+        let (view, rx) = registry.open_view(&source, 1000);
+
+        assert_eq!(block_on(view.reload(sleep(Duration::from_secs(5)))), Ok(7));
+        assert!(matches!(
+            rx.recv_blocking().unwrap(),
+            FileResponse::SourceChanged {
+                change: SourceChange::Reloaded,
+                line_count: 7
+            }
+        ));
+    }
+
+    #[test]
+    fn test_new_search_cancels_previous() {
+        let source = test_source();
+        let mut registry = WorkerRegistry::default();
+        let (view, rx) = registry.open_view(&source, 1000);
+        let find_next = || FileRequest::FindNextMatch {
+            pattern: "line".to_string(),
+            multiline: false,
+            from_line: 0,
+            direction: SearchDirection::Forward,
+            wrap: false,
+            request_id: 1,
+            result_tx: None,
+        };
+
+        view.send_blocking(find_next()).unwrap();
+        let first = view.search_cancel.lock().unwrap().clone();
+        view.send_blocking(FileRequest::GetLines {
+            start: 0,
+            count: 1,
+            request_id: 2,
+        })
+        .unwrap();
+        assert!(!first.is_cancelled());
+        view.send_blocking(find_next()).unwrap();
+        assert!(first.is_cancelled());
+        assert!(!view.search_cancel.lock().unwrap().is_cancelled());
+        drop(rx);
+
+        let cancelled = CancelToken::default();
+        cancelled.cancel();
+        let regex = regex::Regex::new("line 9").unwrap();
+        let result = source.find_next(&regex, 0, SearchDirection::Forward, 3, &cancelled);
+        assert!(matches!(result, Err(PogError::Cancelled)));
+    }
+
+    #[test]
+    fn test_filters_apply_to_one_view() {
+        use crate::filter::FilterKind;
+
+        let source = test_source();
+        let mut registry = WorkerRegistry::default();
+        let (view, rx) = registry.open_view(&source, 3);
+        let (other, other_rx) = registry.open_view(&source, 3);
+
+        let exclude = LineFilter::new(FilterKind::Exclude, "[02468]$").unwrap();
+        let filter = view.set_filters(vec![exclude], None, Vec::new()).unwrap();
+        loop {
+            match rx.recv_blocking().unwrap() {
+                FileResponse::Filtered { done: false, .. } => {}
+                FileResponse::Filtered { line_count, done: true } => {
+                    assert_eq!(line_count, 5);
+                    break;
+                }
+                other => panic!("unexpected response: {:?}", other),
+            }
+        }
+        assert_eq!(filter.hits(), vec![5]);
+        assert_eq!(filter.source_line(1), Some(3));
+
+        let get_lines = || FileRequest::GetLines {
+            start: 1,
+            count: 2,
+            request_id: 1,
+        };
+        view.send_blocking(get_lines()).unwrap();
+        match rx.recv_blocking().unwrap() {
+            FileResponse::Lines { lines, .. } => {
+                assert_eq!(lines, vec![(1, "line 3".to_string()), (2, "line 5".to_string())]);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+        other.send_blocking(get_lines()).unwrap();
+        match other_rx.recv_blocking().unwrap() {
+            FileResponse::Lines { lines, .. } => assert_eq!(lines[0], (1, "line 1".to_string())),
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        assert!(view.set_filters(Vec::new(), None, Vec::new()).is_none());
+        assert!(matches!(
+            rx.recv_blocking().unwrap(),
+            FileResponse::Filtered { line_count: 10, done: true }
+        ));
+    }
+
+    #[test]
+    fn test_detached_view_gets_no_responses() {
+        let source = test_source();
+        let mut registry = WorkerRegistry::default();
+        let (view, rx) = registry.open_view(&source, 1000);
+        view.detach();
+        let _ = view.send_blocking(FileRequest::GetLines {
+            start: 0,
+            count: 1,
+            request_id: 1,
+        });
+        // The worker drops its sender for the view, closing the channel
+        assert!(rx.recv_blocking().is_err());
+    }
+}